@@ -293,6 +293,9 @@ def external_crates_repository(name, annotations):
             "gflags-derive": crate.spec(
                 version = "^0.1",
             ),
+            "gimli": crate.spec(
+                version = "^0.26.1",
+            ),
             "glob": crate.spec(
                 version = "^0.3.0",
             ),
@@ -359,7 +362,7 @@ def external_crates_repository(name, annotations):
                 ],
             ),
             "ic-cdk": crate.spec(
-                version = "^0.6.0",
+                version = "^0.6.8",
                 default_features = False,
             ),
             "ic-cdk-macros": crate.spec(
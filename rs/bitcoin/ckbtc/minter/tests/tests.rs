@@ -39,6 +39,7 @@ fn install_ledger(env: &StateMachine) -> CanisterId {
         },
         initial_balances: vec![],
         transfer_fee: 0,
+        fee_collector_account: None,
         token_name: "Test Token".to_string(),
         token_symbol: "TST".to_string(),
         metadata: vec![],
@@ -43,6 +43,9 @@ pub struct FeatureFlags {
     /// Use the `wasmparser` and `wasm-encoder` crates for instrumentation and
     /// validation instead of `parity-wasm`.
     pub new_wasm_transform_lib: FlagStatus,
+    /// Instrument canister modules to record executed basic blocks. Only for
+    /// use in tests, see `ic_embedders::wasm_utils::coverage`.
+    pub coverage_instrumentation: FlagStatus,
 }
 
 impl Default for FeatureFlags {
@@ -50,6 +53,7 @@ impl Default for FeatureFlags {
         Self {
             rate_limiting_of_debug_prints: FlagStatus::Enabled,
             new_wasm_transform_lib: FlagStatus::Disabled,
+            coverage_instrumentation: FlagStatus::Disabled,
         }
     }
 }
@@ -142,6 +142,11 @@ pub struct Config {
 
     /// Bitcoin configuration.
     pub bitcoin: BitcoinConfig,
+
+    /// If this flag is enabled, then canister modules are instrumented to
+    /// record which basic blocks were executed. This is only meant for
+    /// collecting code coverage in tests.
+    pub coverage_instrumentation: FlagStatus,
 }
 
 impl Default for Config {
@@ -186,6 +191,7 @@ impl Default for Config {
                 testnet_canister_id: Some(bitcoin_testnet_canister_id),
                 mainnet_canister_id: None,
             },
+            coverage_instrumentation: FlagStatus::Disabled,
         }
     }
 }
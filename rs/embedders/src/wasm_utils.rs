@@ -17,6 +17,7 @@ use wasmtime::Module;
 
 use crate::{serialized_module::SerializedModule, CompilationResult, WasmtimeEmbedder};
 
+pub mod coverage;
 pub mod decoding;
 pub mod errors;
 pub mod instrumentation;
@@ -176,16 +177,29 @@ fn validate_and_instrument(
     wasm: &BinaryEncodedWasm,
     config: &EmbeddersConfig,
 ) -> HypervisorResult<(WasmValidationDetails, InstrumentationOutput)> {
+    // The coverage instrumentation is applied after validation, so that the
+    // injected globals don't count towards the limits of the canister module.
+    let coverage_instrumented = match config.feature_flags.coverage_instrumentation {
+        FlagStatus::Enabled => Some(coverage::instrument(wasm)?),
+        FlagStatus::Disabled => None,
+    };
+    let instrumentation_input = coverage_instrumented.as_ref().unwrap_or(wasm);
     let (wasm_validation_details, instrumentation_output) =
         if config.feature_flags.new_wasm_transform_lib == FlagStatus::Enabled {
             (
                 new_validation::validate_wasm_binary(wasm, config)?,
-                new_instrumentation::instrument(wasm, config.cost_to_compile_wasm_instruction)?,
+                new_instrumentation::instrument(
+                    instrumentation_input,
+                    config.cost_to_compile_wasm_instruction,
+                )?,
             )
         } else {
             (
                 validation::validate_wasm_binary(wasm, config)?,
-                instrumentation::instrument(wasm, config.cost_to_compile_wasm_instruction)?,
+                instrumentation::instrument(
+                    instrumentation_input,
+                    config.cost_to_compile_wasm_instruction,
+                )?,
             )
         };
     Ok((wasm_validation_details, instrumentation_output))
@@ -0,0 +1,553 @@
+//! This module implements an optional instrumentation pass that records which
+//! basic blocks of a canister module were executed. It is only meant to be
+//! used in tests (see the `coverage_instrumentation` flag of the hypervisor
+//! configuration) and must never be enabled on a production subnet.
+//!
+//! The pass runs on the original module, before the instructions metering
+//! instrumentation. It adds `ceil(num_blocks / 64)` mutable `i64` globals
+//! which are used as a bitmap of executed basic blocks and inserts their
+//! exports (named `canister coverage_<i>`) at the beginning of the export
+//! section:
+//!
+//! ```wasm
+//! (global (;n;) (mut i64) (i64.const 0))
+//! (export "canister coverage_0" (global n))
+//! ```
+//!
+//! The hypervisor persists all exported mutable globals, in the order of their
+//! exports in the fully instrumented module, so the bitmap accumulates over all
+//! replicated executions until the code of the canister is replaced. Use
+//! [`bitmap_global_positions`] to find the bitmap globals among the persisted
+//! ones by their export names.
+//!
+//! At the beginning of every basic block it injects:
+//!
+//! ```wasm
+//! global.get n
+//! i64.const <bit of the block>
+//! i64.or
+//! global.set n
+//! ```
+//!
+//! Note that the injected instructions are metered like all other
+//! instructions, so instrumented canisters consume more cycles.
+//!
+//! The [`CoverageMap`] of a module describes its basic blocks and is used to
+//! turn a bitmap into an LCOV report. If the module contains DWARF debug
+//! information, blocks are mapped to source lines, otherwise they are reported
+//! per Wasm function index.
+
+use super::errors::into_parity_wasm_error;
+use ic_wasm_types::{BinaryEncodedWasm, WasmInstrumentationError};
+use parity_wasm::elements::{
+    ExportEntry, GlobalEntry, GlobalType, ImportCountType, InitExpr, Instruction, Internal, Module,
+    ValueType,
+};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Prefix of the names of the exported bitmap globals.
+pub const COVERAGE_GLOBAL_PREFIX: &str = "canister coverage_";
+
+const BITS_PER_GLOBAL: usize = 64;
+
+/// A basic block of a function of the original module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Index of the first instruction of the block in the function body.
+    pub instruction_index: usize,
+    /// Offset of the first instruction of the block relative to the start of
+    /// the code section, as used by DWARF. `None` if it could not be
+    /// determined.
+    pub code_offset: Option<u64>,
+}
+
+/// The basic blocks of a function defined in the original module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionBlocks {
+    /// Index of the function in the function index space of the module.
+    pub func_index: u32,
+    /// The name of the function according to the `name` section, if present.
+    pub name: Option<String>,
+    /// Index of the first block of the function in the module-wide block
+    /// numbering used by the bitmap.
+    pub first_block: usize,
+    pub blocks: Vec<BasicBlock>,
+}
+
+/// A source location from the DWARF line table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LineEntry {
+    address: u64,
+    file: String,
+    line: u64,
+}
+
+/// Describes the basic blocks of a module and how they are mapped to the bits
+/// of the coverage bitmap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoverageMap {
+    functions: Vec<FunctionBlocks>,
+    num_blocks: usize,
+    /// Sorted by address. Empty if the module has no DWARF line information.
+    line_table: Vec<LineEntry>,
+}
+
+/// Returns true if a new basic block starts right after the instruction.
+fn ends_basic_block(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Block(_)
+            | Instruction::Loop(_)
+            | Instruction::If(_)
+            | Instruction::Else
+            | Instruction::End
+            | Instruction::Br(_)
+            | Instruction::BrIf(_)
+            | Instruction::BrTable(_)
+            | Instruction::Return
+            | Instruction::Unreachable
+    )
+}
+
+/// Returns the indices of the instructions that start a basic block.
+fn block_starts(instructions: &[Instruction]) -> Vec<usize> {
+    let mut starts = vec![0];
+    // The last instruction is the `end` of the function body, so there is no
+    // block after it.
+    for (index, instruction) in instructions
+        .iter()
+        .enumerate()
+        .take(instructions.len().saturating_sub(1))
+    {
+        if ends_basic_block(instruction) {
+            starts.push(index + 1);
+        }
+    }
+    starts
+}
+
+/// Returns the offsets of all instructions of all function bodies relative to
+/// the start of the code section, or `None` if the module could not be parsed.
+fn instruction_offsets(wasm: &[u8]) -> Option<Vec<Vec<u64>>> {
+    let mut code_section_start = 0;
+    let mut offsets = vec![];
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload.ok()? {
+            wasmparser::Payload::CodeSectionStart { range, .. } => {
+                code_section_start = range.start;
+            }
+            wasmparser::Payload::CodeSectionEntry(body) => {
+                let mut body_offsets = vec![];
+                for operator in body.get_operators_reader().ok()?.into_iter_with_offsets() {
+                    let (_, offset) = operator.ok()?;
+                    body_offsets.push((offset - code_section_start) as u64);
+                }
+                offsets.push(body_offsets);
+            }
+            _ => {}
+        }
+    }
+    Some(offsets)
+}
+
+/// Loads the DWARF line table from the custom sections of the module.
+fn load_line_table(module: &Module) -> Vec<LineEntry> {
+    let sections: HashMap<&str, &[u8]> = module
+        .custom_sections()
+        .map(|section| (section.name(), section.payload()))
+        .collect();
+    if !sections.contains_key(".debug_line") {
+        return vec![];
+    }
+
+    let load_section = |id: gimli::SectionId| -> Result<Cow<[u8]>, gimli::Error> {
+        Ok(Cow::Borrowed(
+            sections.get(id.name()).copied().unwrap_or(&[][..]),
+        ))
+    };
+    let dwarf_cow = match gimli::Dwarf::load(&load_section) {
+        Ok(dwarf) => dwarf,
+        Err(_) => return vec![],
+    };
+    let dwarf = dwarf_cow.borrow(|section| gimli::EndianSlice::new(section, gimli::LittleEndian));
+
+    let mut entries = vec![];
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        let unit = match dwarf.unit(header) {
+            Ok(unit) => unit,
+            Err(_) => continue,
+        };
+        let program = match unit.line_program.clone() {
+            Some(program) => program,
+            None => continue,
+        };
+        let mut rows = program.rows();
+        while let Ok(Some((header, row))) = rows.next_row() {
+            if row.end_sequence() {
+                continue;
+            }
+            let (line, file) = match (row.line(), row.file(header)) {
+                (Some(line), Some(file)) => (line.get(), file),
+                _ => continue,
+            };
+            let mut path = String::new();
+            if let Some(dir) = file.directory(header) {
+                if let Ok(dir) = dwarf.attr_string(&unit, dir) {
+                    path.push_str(&dir.to_string_lossy());
+                    path.push('/');
+                }
+            }
+            if let Ok(name) = dwarf.attr_string(&unit, file.path_name()) {
+                path.push_str(&name.to_string_lossy());
+            }
+            entries.push(LineEntry {
+                address: row.address(),
+                file: path,
+                line,
+            });
+        }
+    }
+    entries.sort_by_key(|entry| entry.address);
+    entries
+}
+
+impl CoverageMap {
+    /// Computes the coverage map of the given (uninstrumented) module.
+    pub fn new(wasm: &BinaryEncodedWasm) -> Result<Self, WasmInstrumentationError> {
+        let module = parity_wasm::deserialize_buffer::<Module>(wasm.as_slice()).map_err(|err| {
+            WasmInstrumentationError::ParityDeserializeError(into_parity_wasm_error(err))
+        })?;
+        let module = module.parse_names().unwrap_or_else(|(_, module)| module);
+        Ok(Self::from_module(&module, wasm.as_slice()))
+    }
+
+    fn from_module(module: &Module, wasm: &[u8]) -> Self {
+        let num_imported_functions = module.import_count(ImportCountType::Function) as u32;
+        let offsets = instruction_offsets(wasm);
+        let names = module
+            .names_section()
+            .and_then(|names| names.functions())
+            .map(|functions| functions.names());
+
+        let mut functions = vec![];
+        let mut num_blocks = 0;
+        if let Some(code_section) = module.code_section() {
+            for (body_index, body) in code_section.bodies().iter().enumerate() {
+                let instructions = body.code().elements();
+                // The offsets can only be trusted if both parsers agree on the
+                // instructions of the body.
+                let body_offsets = offsets
+                    .as_ref()
+                    .and_then(|offsets| offsets.get(body_index))
+                    .filter(|body_offsets| body_offsets.len() == instructions.len());
+                let blocks: Vec<_> = block_starts(instructions)
+                    .into_iter()
+                    .map(|instruction_index| BasicBlock {
+                        instruction_index,
+                        code_offset: body_offsets.map(|offsets| offsets[instruction_index]),
+                    })
+                    .collect();
+                let func_index = num_imported_functions + body_index as u32;
+                functions.push(FunctionBlocks {
+                    func_index,
+                    name: names.and_then(|names| names.get(func_index).cloned()),
+                    first_block: num_blocks,
+                    blocks: blocks.clone(),
+                });
+                num_blocks += blocks.len();
+            }
+        }
+
+        Self {
+            functions,
+            num_blocks,
+            line_table: load_line_table(module),
+        }
+    }
+
+    /// Returns the basic blocks of all functions defined in the module.
+    pub fn functions(&self) -> &[FunctionBlocks] {
+        &self.functions
+    }
+
+    /// Returns the total number of basic blocks in the module.
+    pub fn num_blocks(&self) -> usize {
+        self.num_blocks
+    }
+
+    /// Returns the number of `i64` globals used for the bitmap.
+    pub fn num_globals(&self) -> usize {
+        (self.num_blocks + BITS_PER_GLOBAL - 1) / BITS_PER_GLOBAL
+    }
+
+    /// Returns true if the module contains DWARF line information.
+    pub fn has_debug_info(&self) -> bool {
+        !self.line_table.is_empty()
+    }
+
+    fn source_location(&self, block: &BasicBlock) -> Option<&LineEntry> {
+        let offset = block.code_offset?;
+        let index = self
+            .line_table
+            .partition_point(|entry| entry.address <= offset);
+        index.checked_sub(1).map(|index| &self.line_table[index])
+    }
+
+    /// Produces an LCOV report for the given bitmap, which must have
+    /// [`Self::num_globals`] words.
+    ///
+    /// If the module has DWARF line information, there is one record per
+    /// source file with line coverage. Otherwise, there is a single record for
+    /// `module_name` in which the "line" of a function is its Wasm function
+    /// index, and every basic block is reported as a branch of that line.
+    pub fn lcov_report(&self, module_name: &str, bitmap: &[u64]) -> String {
+        if self.has_debug_info() {
+            self.lcov_report_with_sources(bitmap)
+        } else {
+            self.lcov_report_with_func_indices(module_name, bitmap)
+        }
+    }
+
+    fn lcov_report_with_func_indices(&self, module_name: &str, bitmap: &[u64]) -> String {
+        let mut out = String::new();
+        writeln!(out, "TN:").unwrap();
+        writeln!(out, "SF:{}", module_name).unwrap();
+        for function in self.functions.iter() {
+            writeln!(
+                out,
+                "FN:{},{}",
+                function.func_index,
+                function_name(function)
+            )
+            .unwrap();
+        }
+        let mut functions_hit = 0;
+        let mut branches_hit = 0;
+        for function in self.functions.iter() {
+            let hit = block_hit(bitmap, function.first_block);
+            functions_hit += hit as usize;
+            writeln!(out, "FNDA:{},{}", hit as u8, function_name(function)).unwrap();
+        }
+        writeln!(out, "FNF:{}", self.functions.len()).unwrap();
+        writeln!(out, "FNH:{}", functions_hit).unwrap();
+        for function in self.functions.iter() {
+            for (block_index, _) in function.blocks.iter().enumerate() {
+                let hit = block_hit(bitmap, function.first_block + block_index);
+                branches_hit += hit as usize;
+                writeln!(
+                    out,
+                    "BRDA:{},0,{},{}",
+                    function.func_index, block_index, hit as u8
+                )
+                .unwrap();
+            }
+        }
+        writeln!(out, "BRF:{}", self.num_blocks).unwrap();
+        writeln!(out, "BRH:{}", branches_hit).unwrap();
+        let mut lines_hit = 0;
+        for function in self.functions.iter() {
+            let hit = block_hit(bitmap, function.first_block);
+            lines_hit += hit as usize;
+            writeln!(out, "DA:{},{}", function.func_index, hit as u8).unwrap();
+        }
+        writeln!(out, "LF:{}", self.functions.len()).unwrap();
+        writeln!(out, "LH:{}", lines_hit).unwrap();
+        writeln!(out, "end_of_record").unwrap();
+        out
+    }
+
+    fn lcov_report_with_sources(&self, bitmap: &[u64]) -> String {
+        // file -> line -> hit
+        let mut lines: BTreeMap<&str, BTreeMap<u64, bool>> = BTreeMap::new();
+        // file -> (line, name, hit)
+        let mut functions: BTreeMap<&str, Vec<(u64, String, bool)>> = BTreeMap::new();
+        for function in self.functions.iter() {
+            for (block_index, block) in function.blocks.iter().enumerate() {
+                let location = match self.source_location(block) {
+                    Some(location) => location,
+                    None => continue,
+                };
+                let hit = block_hit(bitmap, function.first_block + block_index);
+                *lines
+                    .entry(location.file.as_str())
+                    .or_default()
+                    .entry(location.line)
+                    .or_default() |= hit;
+                if block_index == 0 {
+                    functions.entry(location.file.as_str()).or_default().push((
+                        location.line,
+                        function_name(function),
+                        hit,
+                    ));
+                }
+            }
+        }
+
+        let mut out = String::new();
+        for (file, file_lines) in lines.iter() {
+            writeln!(out, "TN:").unwrap();
+            writeln!(out, "SF:{}", file).unwrap();
+            let file_functions = functions.get(file).map(Vec::as_slice).unwrap_or(&[]);
+            for (line, name, _) in file_functions.iter() {
+                writeln!(out, "FN:{},{}", line, name).unwrap();
+            }
+            for (_, name, hit) in file_functions.iter() {
+                writeln!(out, "FNDA:{},{}", *hit as u8, name).unwrap();
+            }
+            writeln!(out, "FNF:{}", file_functions.len()).unwrap();
+            writeln!(
+                out,
+                "FNH:{}",
+                file_functions.iter().filter(|(_, _, hit)| *hit).count()
+            )
+            .unwrap();
+            for (line, hit) in file_lines.iter() {
+                writeln!(out, "DA:{},{}", line, *hit as u8).unwrap();
+            }
+            writeln!(out, "LF:{}", file_lines.len()).unwrap();
+            writeln!(
+                out,
+                "LH:{}",
+                file_lines.values().filter(|hit| **hit).count()
+            )
+            .unwrap();
+            writeln!(out, "end_of_record").unwrap();
+        }
+        out
+    }
+}
+
+fn function_name(function: &FunctionBlocks) -> String {
+    function
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("func_{}", function.func_index))
+}
+
+/// Returns true if the bit of the given block is set in the bitmap.
+pub fn block_hit(bitmap: &[u64], block: usize) -> bool {
+    bitmap
+        .get(block / BITS_PER_GLOBAL)
+        .map(|word| word & (1 << (block % BITS_PER_GLOBAL)) != 0)
+        .unwrap_or(false)
+}
+
+/// Returns, for every word of the coverage bitmap of a module with
+/// `num_globals` bitmap globals, the position of the global among the exported
+/// globals of the given fully instrumented module, i.e. its index in the
+/// exported globals persisted by the hypervisor. The globals are looked up by
+/// their [`COVERAGE_GLOBAL_PREFIX`] export names. The position is `None` if
+/// the module does not export the global.
+pub fn bitmap_global_positions(
+    instrumented_wasm: &BinaryEncodedWasm,
+    num_globals: usize,
+) -> Result<Vec<Option<usize>>, WasmInstrumentationError> {
+    let module =
+        parity_wasm::deserialize_buffer::<Module>(instrumented_wasm.as_slice()).map_err(|err| {
+            WasmInstrumentationError::ParityDeserializeError(into_parity_wasm_error(err))
+        })?;
+
+    let mut positions = vec![None; num_globals];
+    let exported_globals = module
+        .export_section()
+        .map(|section| section.entries())
+        .unwrap_or(&[])
+        .iter()
+        .filter(|export| matches!(export.internal(), Internal::Global(_)));
+    for (position, export) in exported_globals.enumerate() {
+        let word = export
+            .field()
+            .strip_prefix(COVERAGE_GLOBAL_PREFIX)
+            .and_then(|word| word.parse::<usize>().ok());
+        if let Some(word) = word.filter(|word| *word < num_globals) {
+            positions[word] = Some(position);
+        }
+    }
+    Ok(positions)
+}
+
+/// Takes a Wasm binary and injects the basic block coverage instrumentation
+/// described in the module documentation.
+///
+/// Returns the instrumented binary or an error if the input binary could not
+/// be instrumented.
+pub(super) fn instrument(
+    wasm: &BinaryEncodedWasm,
+) -> Result<BinaryEncodedWasm, WasmInstrumentationError> {
+    let mut module = parity_wasm::deserialize_buffer::<Module>(wasm.as_slice()).map_err(|err| {
+        WasmInstrumentationError::ParityDeserializeError(into_parity_wasm_error(err))
+    })?;
+
+    let coverage_map = CoverageMap::from_module(&module, wasm.as_slice());
+    if coverage_map.num_blocks() == 0 {
+        return Ok(wasm.clone());
+    }
+
+    let first_global = module.globals_space() as u32;
+    let num_globals = coverage_map.num_globals() as u32;
+
+    if let Some(code_section) = module.code_section_mut() {
+        for (function, body) in coverage_map
+            .functions()
+            .iter()
+            .zip(code_section.bodies_mut().iter_mut())
+        {
+            let code = body.code_mut().elements_mut();
+            let mut elems = Vec::with_capacity(code.len() + 4 * function.blocks.len());
+            let mut last_injection_position = 0;
+            for (block_index, block) in function.blocks.iter().enumerate() {
+                let bit = function.first_block + block_index;
+                let global = first_global + (bit / BITS_PER_GLOBAL) as u32;
+                let mask = 1u64 << (bit % BITS_PER_GLOBAL);
+                elems.extend_from_slice(&code[last_injection_position..block.instruction_index]);
+                elems.extend_from_slice(&[
+                    Instruction::GetGlobal(global),
+                    Instruction::I64Const(mask as i64),
+                    Instruction::I64Or,
+                    Instruction::SetGlobal(global),
+                ]);
+                last_injection_position = block.instruction_index;
+            }
+            elems.extend_from_slice(&code[last_injection_position..]);
+            *code = elems;
+        }
+    }
+
+    let mut builder = parity_wasm::builder::from_module(module);
+    for _ in 0..num_globals {
+        builder.push_global(GlobalEntry::new(
+            GlobalType::new(ValueType::I64, true),
+            InitExpr::new(vec![Instruction::I64Const(0), Instruction::End]),
+        ));
+    }
+    let mut module = builder.build();
+
+    let exports: Vec<_> = (0..num_globals)
+        .map(|i| {
+            ExportEntry::new(
+                format!("{}{}", COVERAGE_GLOBAL_PREFIX, i),
+                Internal::Global(first_global + i),
+            )
+        })
+        .collect();
+    match module.export_section_mut() {
+        Some(export_section) => {
+            export_section.entries_mut().splice(0..0, exports);
+        }
+        None => {
+            let mut builder = parity_wasm::builder::from_module(module);
+            for export in exports {
+                builder.push_export(export);
+            }
+            module = builder.build();
+        }
+    }
+
+    let result = parity_wasm::serialize(module).map_err(|err| {
+        WasmInstrumentationError::ParitySerializeError(into_parity_wasm_error(err))
+    })?;
+    Ok(BinaryEncodedWasm::new(result))
+}
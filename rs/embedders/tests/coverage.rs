@@ -0,0 +1,129 @@
+use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
+use ic_embedders::{
+    wasm_utils::{
+        coverage::{bitmap_global_positions, block_hit, CoverageMap},
+        validate_and_instrument_for_testing,
+    },
+    WasmtimeEmbedder,
+};
+use ic_logger::replica_logger::no_op_logger;
+use ic_replicated_state::Global;
+use ic_test_utilities::wasmtime_instance::WasmtimeInstanceBuilder;
+use ic_types::methods::{FuncRef, WasmMethod};
+use ic_wasm_types::BinaryEncodedWasm;
+
+fn coverage_config() -> EmbeddersConfig {
+    let mut config = EmbeddersConfig::default();
+    config.feature_flags.coverage_instrumentation = FlagStatus::Enabled;
+    config
+}
+
+/// Runs the update method `run` of the module and returns the coverage bitmap
+/// read from the exported globals of the instance, along with the coverage map
+/// of the module.
+fn run_and_collect_bitmap(wat: &str) -> (CoverageMap, Vec<u64>) {
+    let wasm = BinaryEncodedWasm::new(wabt::wat2wasm(wat).unwrap());
+    let map = CoverageMap::new(&wasm).unwrap();
+    let (_, output) = validate_and_instrument_for_testing(
+        &WasmtimeEmbedder::new(coverage_config(), no_op_logger()),
+        &wasm,
+    )
+    .unwrap();
+    let positions = bitmap_global_positions(&output.binary, map.num_globals()).unwrap();
+
+    let mut instance = WasmtimeInstanceBuilder::new()
+        .with_wat(wat)
+        .with_config(coverage_config())
+        .build();
+    let result = instance
+        .run(FuncRef::Method(WasmMethod::Update("run".to_string())))
+        .unwrap();
+
+    let bitmap = positions
+        .iter()
+        .map(
+            |position| match result.exported_globals[position.unwrap()] {
+                Global::I64(word) => word as u64,
+                ref global => panic!("Unexpected coverage global {:?}", global),
+            },
+        )
+        .collect();
+    (map, bitmap)
+}
+
+/// Returns whether each block of each function of the module was hit.
+fn hits(map: &CoverageMap, bitmap: &[u64]) -> Vec<Vec<bool>> {
+    map.functions()
+        .iter()
+        .map(|function| {
+            (0..function.blocks.len())
+                .map(|block_index| block_hit(bitmap, function.first_block + block_index))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn records_executed_blocks() {
+    let wat = r#"
+        (module
+          (global (export "g") (mut i64) (i64.const 0))
+          (func $choose (param i32) (result i32)
+            (if (result i32) (local.get 0)
+              (then (i32.const 1))
+              (else (i32.const 2))))
+          (func $never_called)
+          (func (export "canister_update run")
+            (drop (call $choose (i32.const 0)))))
+    "#;
+    let (map, bitmap) = run_and_collect_bitmap(wat);
+
+    assert_eq!(map.num_globals(), 1);
+    assert_eq!(
+        hits(&map, &bitmap),
+        vec![
+            // Function entry, then branch, else branch, after the if.
+            vec![true, false, true, true],
+            vec![false],
+            vec![true],
+        ]
+    );
+}
+
+#[test]
+fn records_executed_blocks_with_multiple_globals() {
+    // 71 blocks in `$many_blocks` and one in `run`, so the bitmap needs two
+    // globals.
+    let blocks = "(block)".repeat(35);
+    let wat = format!(
+        r#"
+        (module
+          (func $many_blocks {})
+          (func (export "canister_update run")
+            (call $many_blocks)))
+        "#,
+        blocks
+    );
+    let (map, bitmap) = run_and_collect_bitmap(&wat);
+
+    assert_eq!(map.num_blocks(), 72);
+    assert_eq!(map.num_globals(), 2);
+    assert!(hits(&map, &bitmap).iter().flatten().all(|hit| *hit));
+}
+
+#[test]
+fn finds_bitmap_globals_by_export_name() {
+    let wat = r#"
+        (module
+          (global (export "g") (mut i64) (i64.const 0))
+          (func (export "f"))
+          (global (export "canister coverage_1") (mut i64) (i64.const 0))
+          (global (export "canister coverage_0") (mut i64) (i64.const 0)))
+    "#;
+    let wasm = BinaryEncodedWasm::new(wabt::wat2wasm(wat).unwrap());
+
+    assert_eq!(
+        bitmap_global_positions(&wasm, 3).unwrap(),
+        vec![Some(2), Some(1), None]
+    );
+}
@@ -230,6 +230,7 @@ impl Hypervisor {
         embedder_config.feature_flags.rate_limiting_of_debug_prints =
             config.rate_limiting_of_debug_prints;
         embedder_config.cost_to_compile_wasm_instruction = config.cost_to_compile_wasm_instruction;
+        embedder_config.feature_flags.coverage_instrumentation = config.coverage_instrumentation;
 
         let wasm_executor: Arc<dyn WasmExecutor> = match config.canister_sandboxing_flag {
             FlagStatus::Enabled => {
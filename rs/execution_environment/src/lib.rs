@@ -101,6 +101,61 @@ impl ExecutionServices {
         config: Config,
         cycles_account_manager: Arc<CyclesAccountManager>,
         state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+    ) -> ExecutionServices {
+        Self::setup(
+            logger,
+            metrics_registry,
+            own_subnet_id,
+            own_subnet_type,
+            scheduler_config,
+            config,
+            cycles_account_manager,
+            state_reader,
+            |_scheduler| {},
+        )
+    }
+
+    /// Same as [ExecutionServices::setup_execution], except that messages
+    /// between canisters on the same subnet are not inducted directly by the
+    /// scheduler but take the loopback stream. Only meant for the
+    /// `StateMachine` tests that intercept such messages in between rounds.
+    #[cfg(feature = "test")]
+    #[doc(hidden)]
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
+    pub fn setup_execution_without_same_subnet_induction(
+        logger: ReplicaLogger,
+        metrics_registry: &MetricsRegistry,
+        own_subnet_id: SubnetId,
+        own_subnet_type: SubnetType,
+        scheduler_config: SchedulerConfig,
+        config: Config,
+        cycles_account_manager: Arc<CyclesAccountManager>,
+        state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+    ) -> ExecutionServices {
+        Self::setup(
+            logger,
+            metrics_registry,
+            own_subnet_id,
+            own_subnet_type,
+            scheduler_config,
+            config,
+            cycles_account_manager,
+            state_reader,
+            |scheduler| scheduler.set_induct_messages_on_same_subnet(false),
+        )
+    }
+
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
+    fn setup(
+        logger: ReplicaLogger,
+        metrics_registry: &MetricsRegistry,
+        own_subnet_id: SubnetId,
+        own_subnet_type: SubnetType,
+        scheduler_config: SchedulerConfig,
+        config: Config,
+        cycles_account_manager: Arc<CyclesAccountManager>,
+        state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+        configure_scheduler: impl FnOnce(&mut SchedulerImpl),
     ) -> ExecutionServices {
         let hypervisor = Arc::new(Hypervisor::new(
             config.clone(),
@@ -175,7 +230,7 @@ impl ExecutionServices {
 
         let bitcoin_canister = Arc::new(BitcoinCanister::new(metrics_registry, logger.clone()));
 
-        let scheduler = Box::new(SchedulerImpl::new(
+        let mut scheduler = Box::new(SchedulerImpl::new(
             scheduler_config,
             own_subnet_id,
             Arc::clone(&ingress_history_writer) as Arc<_>,
@@ -188,6 +243,7 @@ impl ExecutionServices {
             config.rate_limiting_of_instructions,
             config.deterministic_time_slicing,
         ));
+        configure_scheduler(&mut *scheduler);
 
         Self {
             ingress_filter,
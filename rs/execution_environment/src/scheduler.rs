@@ -78,6 +78,11 @@ pub(crate) struct SchedulerImpl {
     rate_limiting_of_heap_delta: FlagStatus,
     rate_limiting_of_instructions: FlagStatus,
     deterministic_time_slicing: FlagStatus,
+    /// Whether messages between canisters on this subnet are inducted directly
+    /// in between the iterations of a round. Can only be disabled with the
+    /// `test` feature, by the `StateMachine` tests that need to intercept such
+    /// messages.
+    induct_messages_on_same_subnet: bool,
 }
 
 impl SchedulerImpl {
@@ -267,9 +272,15 @@ impl SchedulerImpl {
             rate_limiting_of_heap_delta,
             rate_limiting_of_instructions,
             deterministic_time_slicing,
+            induct_messages_on_same_subnet: true,
         }
     }
 
+    #[cfg(feature = "test")]
+    pub(crate) fn set_induct_messages_on_same_subnet(&mut self, enabled: bool) {
+        self.induct_messages_on_same_subnet = enabled;
+    }
+
     /// Makes progress in executing long-running `install_code` messages.
     fn advance_long_running_install_code(
         &self,
@@ -558,7 +569,7 @@ impl SchedulerImpl {
             if total_heap_delta >= self.config.max_heap_delta_per_iteration {
                 break state;
             }
-            {
+            if self.induct_messages_on_same_subnet {
                 let _induction_timer = self.metrics.round_inner_iteration_fin_induct.start_timer();
                 self.induct_messages_on_same_subnet(&mut state);
             }
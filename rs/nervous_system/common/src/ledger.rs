@@ -5,8 +5,8 @@ use dfn_core::{call, CanisterId};
 use dfn_protobuf::protobuf;
 use ic_crypto_sha::Sha256;
 use icp_ledger::{
-    tokens_from_proto, AccountBalanceArgs, AccountIdentifier, Memo, SendArgs, Subaccount, Tokens,
-    TotalSupplyArgs,
+    tokens_from_proto, AccountBalanceArgs, AccountIdentifier, Memo, SendArgs, Subaccount,
+    TimeStamp, Tokens, TotalSupplyArgs,
 };
 
 pub struct LedgerCanister {
@@ -19,6 +19,45 @@ impl LedgerCanister {
     pub fn new(id: CanisterId) -> Self {
         LedgerCanister { id }
     }
+
+    async fn send(&self, send_args: SendArgs) -> Result<u64, NervousSystemError> {
+        // Send 'amount' to the target account.
+        //
+        // We expect the 'fee' AND 'amount' to be deducted from the
+        // from_subaccount. When calling this method, make sure that the
+        // staked amount can cover BOTH of these amounts, otherwise there will
+        // be an error.
+        let result: Result<u64, (Option<i32>, String)> =
+            call(self.id, "send_pb", protobuf, send_args).await;
+
+        result.map_err(|(code, msg)| {
+            NervousSystemError::new_with_message(format!(
+                "Error calling method 'send' of the ledger canister. Code: {:?}. Message: {}",
+                code, msg
+            ))
+        })
+    }
+}
+
+/// If `error_message` says that a transfer was rejected as a duplicate (see
+/// `icp_ledger::TransferError::TxDuplicate`), returns the block height of the
+/// original transfer.
+fn duplicate_of(error_message: &str) -> Option<u64> {
+    const DUPLICATE: &str = "transaction is a duplicate of another transaction in block ";
+    let (_, block_height) = error_message.split_once(DUPLICATE)?;
+    block_height
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Returns whether `error` says that the ledger rejected a transfer because its
+/// `created_at_time` is older than the ledger's deduplication window (see
+/// `icp_ledger::TransferError::TxTooOld`). Such a transfer can no longer be
+/// retried with the same `created_at_time`.
+pub fn is_transaction_too_old(error: &NervousSystemError) -> bool {
+    error.error_message.contains("transaction is older than ")
 }
 
 /// A trait defining common patterns for accessing the Ledger canister.
@@ -37,6 +76,26 @@ pub trait Ledger: Send + Sync {
         memo: u64,
     ) -> Result<u64, NervousSystemError>;
 
+    /// Like `transfer_funds`, but also sets the `created_at_time` of the
+    /// transfer. Within its deduplication window, the ledger then recognizes
+    /// a retry of the same transfer (same amount, fee, accounts, memo and
+    /// `created_at_time`) as a duplicate, in which case this returns the block
+    /// height of the original transfer instead of transferring again.
+    ///
+    /// By default, `created_at_time` is ignored and no deduplication happens.
+    async fn transfer_funds_deduplicated(
+        &self,
+        amount_e8s: u64,
+        fee_e8s: u64,
+        from_subaccount: Option<Subaccount>,
+        to: AccountIdentifier,
+        memo: u64,
+        _created_at_time: TimeStamp,
+    ) -> Result<u64, NervousSystemError> {
+        self.transfer_funds(amount_e8s, fee_e8s, from_subaccount, to, memo)
+            .await
+    }
+
     /// Gets the total supply of tokens from the sum of all accounts except for the
     /// minting canister's.
     async fn total_supply(&self) -> Result<Tokens, NervousSystemError>;
@@ -61,34 +120,36 @@ impl Ledger for LedgerCanister {
         to: AccountIdentifier,
         memo: u64,
     ) -> Result<u64, NervousSystemError> {
-        // Send 'amount_e8s' to the target account.
-        //
-        // We expect the 'fee_e8s' AND 'amount_e8s' to be
-        // deducted from the from_subaccount. When calling
-        // this method, make sure that the staked amount
-        // can cover BOTH of these amounts, otherwise there
-        // will be an error.
-        let result: Result<u64, (Option<i32>, String)> = call(
-            self.id,
-            "send_pb",
-            protobuf,
-            SendArgs {
-                memo: Memo(memo),
-                amount: Tokens::from_e8s(amount_e8s),
-                fee: Tokens::from_e8s(fee_e8s),
-                from_subaccount,
-                to,
-                created_at_time: None,
-            },
-        )
-        .await;
+        self.send(SendArgs {
+            memo: Memo(memo),
+            amount: Tokens::from_e8s(amount_e8s),
+            fee: Tokens::from_e8s(fee_e8s),
+            from_subaccount,
+            to,
+            created_at_time: None,
+        })
+        .await
+    }
 
-        result.map_err(|(code, msg)| {
-            NervousSystemError::new_with_message(format!(
-                "Error calling method 'send' of the ledger canister. Code: {:?}. Message: {}",
-                code, msg
-            ))
+    async fn transfer_funds_deduplicated(
+        &self,
+        amount_e8s: u64,
+        fee_e8s: u64,
+        from_subaccount: Option<Subaccount>,
+        to: AccountIdentifier,
+        memo: u64,
+        created_at_time: TimeStamp,
+    ) -> Result<u64, NervousSystemError> {
+        self.send(SendArgs {
+            memo: Memo(memo),
+            amount: Tokens::from_e8s(amount_e8s),
+            fee: Tokens::from_e8s(fee_e8s),
+            from_subaccount,
+            to,
+            created_at_time: Some(created_at_time),
         })
+        .await
+        .or_else(|err| duplicate_of(&err.error_message).ok_or(err))
     }
 
     async fn total_supply(&self) -> Result<Tokens, NervousSystemError> {
@@ -176,3 +237,48 @@ pub fn compute_distribution_subaccount_bytes(principal_id: PrincipalId, nonce: u
 pub fn compute_distribution_subaccount(principal_id: PrincipalId, nonce: u64) -> Subaccount {
     Subaccount(compute_distribution_subaccount_bytes(principal_id, nonce))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_of() {
+        assert_eq!(
+            duplicate_of(
+                "Error calling method 'send' of the ledger canister. Code: Some(5). Message: \
+                 Canister ryjl3-tyaaa-aaaaa-aaaba-cai trapped explicitly: transaction is a \
+                 duplicate of another transaction in block 1234"
+            ),
+            Some(1234)
+        );
+        assert_eq!(
+            duplicate_of("transaction is a duplicate of another transaction in block 42."),
+            Some(42)
+        );
+        assert_eq!(
+            duplicate_of("transaction is older than 86400 seconds"),
+            None
+        );
+        assert_eq!(
+            duplicate_of("transaction is a duplicate of another transaction in block "),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_transaction_too_old() {
+        assert!(is_transaction_too_old(
+            &NervousSystemError::new_with_message(
+                "Error calling method 'send' of the ledger canister. Code: Some(5). Message: \
+             Canister ryjl3-tyaaa-aaaaa-aaaba-cai trapped explicitly: transaction is older \
+             than 86400 seconds"
+            )
+        ));
+        assert!(!is_transaction_too_old(
+            &NervousSystemError::new_with_message(
+                "transaction is a duplicate of another transaction in block 42"
+            )
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! The subset of the exchange rate canister interface that the cycles minting
+//! canister uses to fetch the ICP/XDR conversion rate.
+
+use crate::IcpXdrConversionRate;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// The symbol of ICP in the exchange rate canister.
+pub const ICP_SYMBOL: &str = "ICP";
+
+/// The symbol of the XDR rate computed by the exchange rate canister from the
+/// rates of the currencies in the SDR basket.
+pub const CXDR_SYMBOL: &str = "CXDR";
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub enum AssetClass {
+    Cryptocurrency,
+    FiatCurrency,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct Asset {
+    pub symbol: String,
+    pub class: AssetClass,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct GetExchangeRateRequest {
+    pub base_asset: Asset,
+    pub quote_asset: Asset,
+    /// The UNIX epoch time in seconds of the requested rate. The exchange rate
+    /// canister uses the start of the minute containing the timestamp.
+    pub timestamp: Option<u64>,
+}
+
+impl GetExchangeRateRequest {
+    /// Returns the request for the ICP/XDR rate at the given time.
+    pub fn icp_xdr(timestamp_seconds: u64) -> Self {
+        Self {
+            base_asset: Asset {
+                symbol: ICP_SYMBOL.to_string(),
+                class: AssetClass::Cryptocurrency,
+            },
+            quote_asset: Asset {
+                symbol: CXDR_SYMBOL.to_string(),
+                class: AssetClass::FiatCurrency,
+            },
+            timestamp: Some(timestamp_seconds),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct ExchangeRateMetadata {
+    /// The number of decimal places of the `rate` field.
+    pub decimals: u32,
+    pub base_asset_num_queried_sources: u64,
+    pub base_asset_num_received_rates: u64,
+    pub quote_asset_num_queried_sources: u64,
+    pub quote_asset_num_received_rates: u64,
+    pub standard_deviation: u64,
+    pub forex_timestamp: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct ExchangeRate {
+    pub base_asset: Asset,
+    pub quote_asset: Asset,
+    pub timestamp: u64,
+    pub rate: u64,
+    pub metadata: ExchangeRateMetadata,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct OtherError {
+    pub code: u32,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub enum ExchangeRateError {
+    AnonymousPrincipalNotAllowed,
+    Pending,
+    CryptoBaseAssetNotFound,
+    CryptoQuoteAssetNotFound,
+    StablecoinRateNotFound,
+    StablecoinRateTooFewRates,
+    StablecoinRateZeroRate,
+    ForexInvalidTimestamp,
+    ForexBaseAssetNotFound,
+    ForexQuoteAssetNotFound,
+    ForexAssetsNotFound,
+    RateLimited,
+    NotEnoughCycles,
+    FailedToAcceptCycles,
+    InconsistentRatesReceived,
+    Other(OtherError),
+}
+
+pub type GetExchangeRateResult = Result<ExchangeRate, ExchangeRateError>;
+
+/// The minimum number of sources that must have reported a rate for each
+/// asset of an exchange rate the cycles minting canister accepts.
+pub const MIN_NUM_RECEIVED_RATES: u64 = 4;
+
+impl TryFrom<ExchangeRate> for IcpXdrConversionRate {
+    type Error = String;
+
+    fn try_from(rate: ExchangeRate) -> Result<Self, Self::Error> {
+        if rate.base_asset.symbol != ICP_SYMBOL || rate.quote_asset.symbol != CXDR_SYMBOL {
+            return Err(format!(
+                "Expected an {}/{} rate, got {}/{}",
+                ICP_SYMBOL, CXDR_SYMBOL, rate.base_asset.symbol, rate.quote_asset.symbol
+            ));
+        }
+        let metadata = &rate.metadata;
+        if metadata.base_asset_num_received_rates < MIN_NUM_RECEIVED_RATES
+            || metadata.quote_asset_num_received_rates < MIN_NUM_RECEIVED_RATES
+        {
+            return Err(format!(
+                "The rate is based on too few sources ({} for {}, {} for {}, at least {} required)",
+                metadata.base_asset_num_received_rates,
+                ICP_SYMBOL,
+                metadata.quote_asset_num_received_rates,
+                CXDR_SYMBOL,
+                MIN_NUM_RECEIVED_RATES
+            ));
+        }
+        let divisor = 10u128
+            .checked_pow(metadata.decimals)
+            .ok_or_else(|| format!("Unsupported number of decimals: {}", metadata.decimals))?;
+        let xdr_permyriad_per_icp = (rate.rate as u128 * 10_000 / divisor)
+            .try_into()
+            .map_err(|_| format!("The rate {} is out of range", rate.rate))?;
+        Ok(Self {
+            timestamp_seconds: rate.timestamp,
+            xdr_permyriad_per_icp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icp_xdr_rate(rate: u64, decimals: u32, num_received_rates: u64) -> ExchangeRate {
+        let request = GetExchangeRateRequest::icp_xdr(1_670_000_040);
+        ExchangeRate {
+            base_asset: request.base_asset,
+            quote_asset: request.quote_asset,
+            timestamp: 1_670_000_040,
+            rate,
+            metadata: ExchangeRateMetadata {
+                decimals,
+                base_asset_num_queried_sources: num_received_rates,
+                base_asset_num_received_rates: num_received_rates,
+                quote_asset_num_queried_sources: num_received_rates,
+                quote_asset_num_received_rates: num_received_rates,
+                standard_deviation: 0,
+                forex_timestamp: None,
+            },
+        }
+    }
+
+    #[test]
+    fn converts_exchange_rate_to_permyriad() {
+        assert_eq!(
+            IcpXdrConversionRate::try_from(icp_xdr_rate(3_456_789_012, 9, 5)),
+            Ok(IcpXdrConversionRate {
+                timestamp_seconds: 1_670_000_040,
+                xdr_permyriad_per_icp: 34_567,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_rates_from_too_few_sources() {
+        assert!(IcpXdrConversionRate::try_from(icp_xdr_rate(3_456_789_012, 9, 3)).is_err());
+    }
+
+    #[test]
+    fn rejects_other_assets() {
+        let mut rate = icp_xdr_rate(3_456_789_012, 9, 5);
+        rate.quote_asset.symbol = "USD".to_string();
+        assert!(IcpXdrConversionRate::try_from(rate).is_err());
+    }
+}
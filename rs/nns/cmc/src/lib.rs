@@ -6,10 +6,13 @@ use icp_ledger::{
 };
 use serde::{Deserialize, Serialize};
 
+pub mod exchange_rate_canister;
+
 pub const DEFAULT_CYCLES_PER_XDR: u128 = 1_000_000_000_000u128; // 1T cycles = 1 XDR
 
 pub const CREATE_CANISTER_REFUND_FEE: Tokens = Tokens::from_e8s(DEFAULT_TRANSFER_FEE.get_e8s() * 4);
 pub const TOP_UP_CANISTER_REFUND_FEE: Tokens = Tokens::from_e8s(DEFAULT_TRANSFER_FEE.get_e8s() * 2);
+pub const MINT_CYCLES_REFUND_FEE: Tokens = Tokens::from_e8s(DEFAULT_TRANSFER_FEE.get_e8s() * 2);
 
 #[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
 pub struct CyclesCanisterInitPayload {
@@ -17,6 +20,34 @@ pub struct CyclesCanisterInitPayload {
     pub governance_canister_id: CanisterId,
     pub minting_account_id: Option<AccountIdentifier>,
     pub last_purged_notification: Option<BlockIndex>,
+    /// The cycles ledger that receives the cycles minted by `notify_mint_cycles`.
+    #[serde(default)]
+    pub cycles_ledger_canister_id: Option<CanisterId>,
+    /// The exchange rate canister from which the ICP/XDR conversion rate is
+    /// fetched periodically. If not set, the rate is only updated by
+    /// proposals.
+    #[serde(default)]
+    pub exchange_rate_canister_id: Option<CanisterId>,
+}
+
+/// The argument of a cycles minting canister upgrade.
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct CyclesCanisterUpgradeArgs {
+    /// If set, replaces the cycles ledger that receives the cycles minted by
+    /// `notify_mint_cycles`.
+    pub cycles_ledger_canister_id: Option<CanisterId>,
+    /// If set, changes the source of the automatic ICP/XDR conversion rate
+    /// updates.
+    #[serde(default)]
+    pub exchange_rate_canister: Option<ExchangeRateCanister>,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub enum ExchangeRateCanister {
+    /// Stops the automatic updates, the rate is only updated by proposals.
+    Unset,
+    /// Fetches the rate periodically from the given canister.
+    Set(CanisterId),
 }
 
 /// Argument taken by top up notification endpoint
@@ -32,6 +63,73 @@ pub struct NotifyCreateCanister {
     pub block_index: BlockIndex,
     pub controller: PrincipalId,
     pub subnet_type: Option<String>,
+    /// Where to create the canister. Cannot be combined with `subnet_type`.
+    #[serde(default)]
+    pub subnet_selection: Option<SubnetSelection>,
+    /// The settings of the new canister. If the settings do not specify the
+    /// controllers, `controller` becomes the only controller.
+    #[serde(default)]
+    pub settings: Option<CanisterSettingsArgs>,
+}
+
+/// Argument taken by the mint cycles notification endpoint. The cycles go to
+/// the cycles ledger account of the caller with the specified subaccount.
+#[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq)]
+pub struct NotifyMintCyclesArg {
+    pub block_index: BlockIndex,
+    pub to_subaccount: Option<[u8; 32]>,
+    /// The memo of the deposit transaction on the cycles ledger.
+    pub deposit_memo: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq)]
+pub struct NotifyMintCyclesSuccess {
+    /// The index of the deposit block on the cycles ledger.
+    pub block_index: candid::Nat,
+    /// The amount of cycles minted.
+    pub minted: candid::Nat,
+    /// The balance of the cycles ledger account after the deposit.
+    pub balance: candid::Nat,
+}
+
+/// An account on the cycles ledger.
+#[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq)]
+pub struct CyclesLedgerAccount {
+    pub owner: PrincipalId,
+    pub subaccount: Option<[u8; 32]>,
+}
+
+/// The argument of the cycles ledger `deposit` endpoint.
+#[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq)]
+pub struct CyclesLedgerDepositArgs {
+    pub to: CyclesLedgerAccount,
+    pub memo: Option<Vec<u8>>,
+}
+
+/// The result of the cycles ledger `deposit` endpoint.
+#[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq)]
+pub struct CyclesLedgerDepositResult {
+    pub block_index: candid::Nat,
+    pub balance: candid::Nat,
+}
+
+/// The subnet on which the cycles minting canister creates a canister.
+#[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq)]
+pub enum SubnetSelection {
+    /// A specific subnet. The subnet must be one of the subnets the controller
+    /// is authorized to use or be assigned to a subnet type.
+    Subnet { subnet: SubnetId },
+    /// Any subnet of the specified type.
+    SubnetType { subnet_type: String },
+}
+
+/// The settings of a canister that the cycles minting canister creates.
+#[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq, Default)]
+pub struct CanisterSettingsArgs {
+    pub controllers: Option<Vec<PrincipalId>>,
+    pub compute_allocation: Option<candid::Nat>,
+    pub memory_allocation: Option<candid::Nat>,
+    pub freezing_threshold: Option<candid::Nat>,
 }
 
 /// Error for notify endpoints
@@ -101,6 +199,7 @@ impl std::fmt::Display for NotifyError {
 
 pub const MEMO_CREATE_CANISTER: Memo = Memo(0x41455243); // == 'CREA'
 pub const MEMO_TOP_UP_CANISTER: Memo = Memo(0x50555054); // == 'TPUP'
+pub const MEMO_MINT_CYCLES: Memo = Memo(0x544e494d); // == 'MINT'
 
 pub fn create_canister_txn(
     amount: Tokens,
@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::cmp::{max, min};
 use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
 use std::convert::TryInto;
@@ -16,7 +16,7 @@ use ic_crypto_tree_hash::{
     flatmap, HashTreeBuilder, HashTreeBuilderImpl, Label, LabeledTree, WitnessGenerator,
     WitnessGeneratorImpl,
 };
-use ic_ic00_types::{CanisterIdRecord, CanisterSettingsArgs, CreateCanisterArgs, Method, IC_00};
+use ic_ic00_types::{CanisterIdRecord, CreateCanisterArgs, Method, IC_00};
 use ic_ledger_core::block::BlockType;
 use ic_nns_constants::{GOVERNANCE_CANISTER_ID, REGISTRY_CANISTER_ID};
 use ic_types::{CanisterId, Cycles, PrincipalId, SubnetId};
@@ -26,6 +26,9 @@ use icp_ledger::{
 };
 use on_wire::{FromWire, IntoWire, NewType};
 
+use cycles_minting_canister::exchange_rate_canister::{
+    GetExchangeRateRequest, GetExchangeRateResult,
+};
 use ic_nns_common::types::UpdateIcpXdrConversionRatePayload;
 use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use serde::{Deserialize, Serialize};
@@ -48,8 +51,49 @@ const MAX_NOTIFY_PURGE: usize = 100_000;
 const MIN_MATURITY_MODULATION_PERMYRIAD: i32 = -500;
 const MAX_MATURITY_MODULATION_PERMYRIAD: i32 = 500;
 
+/// How often the ICP/XDR rate is fetched from the exchange rate canister.
+const EXCHANGE_RATE_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// The cycles attached to an exchange rate canister request. The exchange
+/// rate canister refunds the cycles it does not use.
+const EXCHANGE_RATE_CANISTER_REQUEST_CYCLES: u64 = 10_000_000_000;
+/// The maximum relative deviation, in percent, of a fetched rate from the
+/// average of the recent rates.
+const MAX_EXCHANGE_RATE_DEVIATION_PERCENT: u64 = 50;
+/// Proposals can update the ICP/XDR rate only if the exchange rate canister
+/// has not provided a rate for this long.
+const EXCHANGE_RATE_CANISTER_UNAVAILABLE_AFTER: Duration = Duration::from_secs(30 * 60);
+
 thread_local! {
     static STATE: RefCell<Option<State>> = RefCell::new(None);
+
+    /// True while a request to the exchange rate canister is in flight, see
+    /// FetchingExchangeRateGuard.
+    static FETCHING_EXCHANGE_RATE: Cell<bool> = Cell::new(false);
+}
+
+/// Marks a request to the exchange rate canister as in flight for as long as
+/// it is alive.
+///
+/// The guard is dropped whenever update_exchange_rate stops, including when
+/// the callback of the request traps, as the cleanup callback of the request
+/// then drops the whole future.
+struct FetchingExchangeRateGuard;
+
+impl FetchingExchangeRateGuard {
+    /// Returns None if a request is already in flight.
+    fn new() -> Option<Self> {
+        if FETCHING_EXCHANGE_RATE.with(|fetching| fetching.replace(true)) {
+            None
+        } else {
+            Some(Self)
+        }
+    }
+}
+
+impl Drop for FetchingExchangeRateGuard {
+    fn drop(&mut self) {
+        FETCHING_EXCHANGE_RATE.with(|fetching| fetching.set(false));
+    }
 }
 
 fn with_state<R>(f: impl FnOnce(&State) -> R) -> R {
@@ -73,6 +117,8 @@ enum NotificationStatus {
     NotifiedTopUp(Result<Cycles, NotifyError>),
     /// The cached result of a completed canister creation.
     NotifiedCreateCanister(Result<CanisterId, NotifyError>),
+    /// The cached result of a completed deposit to the cycles ledger.
+    NotifiedMintCycles(Result<NotifyMintCyclesSuccess, NotifyError>),
 }
 
 #[derive(Serialize, Deserialize, Clone, CandidType, Eq, PartialEq, Debug)]
@@ -134,6 +180,18 @@ struct State {
     /// Each subnet can be assigned to at most one type and cannot be a default
     /// or an authorized subnet.
     subnet_types_to_subnets: Option<BTreeMap<String, BTreeSet<SubnetId>>>,
+
+    /// The cycles ledger that receives the cycles minted by
+    /// `notify_mint_cycles`.
+    cycles_ledger_canister_id: Option<CanisterId>,
+
+    /// The exchange rate canister from which the ICP/XDR rate is fetched
+    /// every `EXCHANGE_RATE_REFRESH_INTERVAL`.
+    exchange_rate_canister_id: Option<CanisterId>,
+
+    /// The UNIX epoch time in seconds of the last rate successfully fetched
+    /// from the exchange rate canister.
+    last_exchange_rate_canister_update_seconds: Option<u64>,
 }
 
 impl State {
@@ -201,6 +259,9 @@ impl Default for State {
             last_purged_notification: Some(0),
             maturity_modulation_permyriad: Some(0),
             subnet_types_to_subnets: Some(BTreeMap::new()),
+            cycles_ledger_canister_id: None,
+            exchange_rate_canister_id: None,
+            last_exchange_rate_canister_update_seconds: None,
         }
     }
 }
@@ -234,7 +295,10 @@ fn init(args: CyclesCanisterInitPayload) {
         state.governance_canister_id = args.governance_canister_id;
         state.minting_account_id = args.minting_account_id;
         state.last_purged_notification = args.last_purged_notification;
+        state.cycles_ledger_canister_id = args.cycles_ledger_canister_id;
+        state.exchange_rate_canister_id = args.exchange_rate_canister_id;
     });
+    schedule_exchange_rate_update();
 }
 
 ic_nervous_system_common_build_metadata::define_get_build_metadata_candid_method! {}
@@ -647,6 +711,12 @@ fn set_icp_xdr_conversion_rate_() {
     over(
         candid_one,
         |proposed_conversion_rate: UpdateIcpXdrConversionRatePayload| -> Result<(), String> {
+            if with_state(|state| exchange_rate_canister_is_available(state, now_seconds())) {
+                return Err(
+                    "The ICP/XDR conversion rate is updated by the exchange rate canister"
+                        .to_string(),
+                );
+            }
             let rate: IcpXdrConversionRate = proposed_conversion_rate.into();
             update_recent_icp_xdr_rates(&rate);
             set_icp_xdr_conversion_rate(rate)
@@ -654,6 +724,146 @@ fn set_icp_xdr_conversion_rate_() {
     );
 }
 
+fn now_seconds() -> u64 {
+    dfn_core::api::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the current time is before the UNIX epoch")
+        .as_secs()
+}
+
+/// Returns true if the exchange rate canister is configured and provided a
+/// rate recently. Proposals only update the rate when this is not the case.
+fn exchange_rate_canister_is_available(state: &State, now_seconds: u64) -> bool {
+    if state.exchange_rate_canister_id.is_none() {
+        return false;
+    }
+    match state.last_exchange_rate_canister_update_seconds {
+        Some(last_update) => {
+            now_seconds.saturating_sub(last_update)
+                < EXCHANGE_RATE_CANISTER_UNAVAILABLE_AFTER.as_secs()
+        }
+        None => false,
+    }
+}
+
+/// Sets the global timer to fetch the next ICP/XDR rate if an exchange rate
+/// canister is configured.
+fn schedule_exchange_rate_update() {
+    if with_state(|state| state.exchange_rate_canister_id.is_some()) {
+        ic_cdk::api::set_global_timer(
+            dfn_core::api::time_nanos() + EXCHANGE_RATE_REFRESH_INTERVAL.as_nanos() as u64,
+        );
+    }
+}
+
+#[export_name = "canister_global_timer"]
+fn global_timer() {
+    schedule_exchange_rate_update();
+    // The timer handler must be synchronous, so we cannot .await the future.
+    dfn_core::api::futures::spawn(update_exchange_rate());
+}
+
+/// Fetches the ICP/XDR rate from the exchange rate canister and, if it passes
+/// the sanity checks, makes it the current rate.
+async fn update_exchange_rate() {
+    let exchange_rate_canister_id = match with_state(|state| state.exchange_rate_canister_id) {
+        Some(canister_id) => canister_id,
+        None => return,
+    };
+    let guard = match FetchingExchangeRateGuard::new() {
+        Some(guard) => guard,
+        None => return,
+    };
+    let result = fetch_icp_xdr_conversion_rate(exchange_rate_canister_id).await;
+    std::mem::drop(guard);
+
+    let rate = match result {
+        Ok(rate) => rate,
+        Err(err) => {
+            print(format!(
+                "[cycles] failed to fetch the ICP/XDR rate from {}: {}",
+                exchange_rate_canister_id, err
+            ));
+            return;
+        }
+    };
+
+    let now_seconds = now_seconds();
+    if let Err(err) = with_state(|state| check_exchange_rate(state, &rate, now_seconds)) {
+        print(format!(
+            "[cycles] rejected the ICP/XDR rate {:?} from {}: {}",
+            rate, exchange_rate_canister_id, err
+        ));
+        return;
+    }
+
+    update_recent_icp_xdr_rates(&rate);
+    match set_icp_xdr_conversion_rate(rate) {
+        Ok(()) => with_state_mut(|state| {
+            state.last_exchange_rate_canister_update_seconds = Some(now_seconds)
+        }),
+        Err(err) => print(format!("[cycles] failed to set the ICP/XDR rate: {}", err)),
+    }
+}
+
+async fn fetch_icp_xdr_conversion_rate(
+    exchange_rate_canister_id: CanisterId,
+) -> Result<IcpXdrConversionRate, String> {
+    // The exchange rate canister returns the rate for the start of the minute.
+    let timestamp_seconds = now_seconds() / 60 * 60;
+
+    let res: Result<GetExchangeRateResult, (Option<i32>, String)> =
+        dfn_core::api::call_with_funds_and_cleanup(
+            exchange_rate_canister_id,
+            "get_exchange_rate",
+            candid_one,
+            GetExchangeRateRequest::icp_xdr(timestamp_seconds),
+            dfn_core::api::Funds::new(EXCHANGE_RATE_CANISTER_REQUEST_CYCLES),
+        )
+        .await;
+
+    res.map_err(|(code, msg)| {
+        format!(
+            "Calling the exchange rate canister failed with code {}: {:?}",
+            code.unwrap_or_default(),
+            msg
+        )
+    })?
+    .map_err(|err| format!("The exchange rate canister returned an error: {:?}", err))?
+    .try_into()
+}
+
+/// Checks that a rate fetched from the exchange rate canister is newer than
+/// the current rate and close to the average of the recent rates.
+fn check_exchange_rate(
+    state: &State,
+    rate: &IcpXdrConversionRate,
+    now_seconds: u64,
+) -> Result<(), String> {
+    if let Some(current_rate) = state.icp_xdr_conversion_rate.as_ref() {
+        if rate.timestamp_seconds <= current_rate.timestamp_seconds {
+            return Err("The rate is not newer than the current rate".to_string());
+        }
+    }
+    let average_rate = state
+        .recent_icp_xdr_rates
+        .as_ref()
+        .and_then(|recent_rates| compute_average_icp_xdr_rate_at_time(recent_rates, now_seconds));
+    if let Some(average_rate) = average_rate {
+        let average = average_rate.xdr_permyriad_per_icp;
+        let deviation = rate.xdr_permyriad_per_icp.abs_diff(average);
+        if deviation.saturating_mul(100)
+            > average.saturating_mul(MAX_EXCHANGE_RATE_DEVIATION_PERCENT)
+        {
+            return Err(format!(
+                "The rate deviates by more than {}% from the average of the recent rates ({})",
+                MAX_EXCHANGE_RATE_DEVIATION_PERCENT, average
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[export_name = "canister_query get_average_icp_xdr_conversion_rate"]
 fn get_average_icp_xdr_conversion_rate_() {
     with_state(|state| {
@@ -912,6 +1122,11 @@ fn notify_create_canister_() {
     over_async(candid_one, notify_create_canister)
 }
 
+#[export_name = "canister_update notify_mint_cycles"]
+fn notify_mint_cycles_() {
+    over_async(candid_one, notify_mint_cycles)
+}
+
 fn is_transient_error<T>(result: &Result<T, NotifyError>) -> bool {
     if let Err(e) = result {
         return e.is_retriable();
@@ -957,6 +1172,11 @@ async fn notify_top_up(
                         "The same payment is already processed as create canister request".into(),
                     )))
                 }
+                NotificationStatus::NotifiedMintCycles(_) => {
+                    Some(Err(NotifyError::InvalidTransaction(
+                        "The same payment is already processed as mint cycles request".into(),
+                    )))
+                }
             },
             Entry::Vacant(entry) => {
                 entry.insert(NotificationStatus::Processing);
@@ -998,6 +1218,8 @@ async fn notify_create_canister(
         block_index,
         controller,
         subnet_type,
+        subnet_selection,
+        settings,
     }: NotifyCreateCanister,
 ) -> Result<CanisterId, NotifyError> {
     let cmc_id = dfn_core::api::id();
@@ -1022,6 +1244,11 @@ async fn notify_create_canister(
                 NotificationStatus::NotifiedTopUp(_) => Some(Err(NotifyError::InvalidTransaction(
                     "The same payment is already processed as a top up request.".into(),
                 ))),
+                NotificationStatus::NotifiedMintCycles(_) => {
+                    Some(Err(NotifyError::InvalidTransaction(
+                        "The same payment is already processed as a mint cycles request.".into(),
+                    )))
+                }
             },
             Entry::Vacant(entry) => {
                 entry.insert(NotificationStatus::Processing);
@@ -1033,7 +1260,15 @@ async fn notify_create_canister(
     match maybe_early_result {
         Some(result) => result,
         None => {
-            let result = process_create_canister(controller, from, amount, subnet_type).await;
+            let result = process_create_canister(
+                controller,
+                from,
+                amount,
+                subnet_type,
+                subnet_selection,
+                settings.unwrap_or_default(),
+            )
+            .await;
 
             with_state_mut(|state| {
                 state.blocks_notified.as_mut().unwrap().insert(
@@ -1050,6 +1285,84 @@ async fn notify_create_canister(
     }
 }
 
+/// Notify about mint cycles transaction
+///
+/// # Arguments
+///
+/// * `block_index` -  The height of the block you would like to send a
+///   notification about.
+/// * `to_subaccount` - The subaccount of the caller's cycles ledger account
+///   that receives the cycles.
+/// * `deposit_memo` - The memo of the deposit on the cycles ledger.
+#[candid_method(update, rename = "notify_mint_cycles")]
+async fn notify_mint_cycles(
+    NotifyMintCyclesArg {
+        block_index,
+        to_subaccount,
+        deposit_memo,
+    }: NotifyMintCyclesArg,
+) -> Result<NotifyMintCyclesSuccess, NotifyError> {
+    let cmc_id = dfn_core::api::id();
+    let caller = caller();
+    let sub = Subaccount::from(&caller);
+    let expected_to = AccountIdentifier::new(cmc_id.get(), Some(sub));
+
+    let (amount, from) = fetch_transaction(block_index, expected_to, MEMO_MINT_CYCLES).await?;
+
+    let maybe_early_result = with_state_mut(|state| {
+        state.purge_old_notifications(MAX_NOTIFY_HISTORY);
+
+        if block_index <= state.last_purged_notification.unwrap() {
+            return Some(Err(NotifyError::TransactionTooOld(
+                state.last_purged_notification.unwrap() + 1,
+            )));
+        }
+
+        match state.blocks_notified.as_mut().unwrap().entry(block_index) {
+            Entry::Occupied(entry) => match entry.get() {
+                NotificationStatus::Processing => Some(Err(NotifyError::Processing)),
+                NotificationStatus::NotifiedMintCycles(resp) => Some(resp.clone()),
+                NotificationStatus::NotifiedTopUp(_) => Some(Err(NotifyError::InvalidTransaction(
+                    "The same payment is already processed as a top up request.".into(),
+                ))),
+                NotificationStatus::NotifiedCreateCanister(_) => {
+                    Some(Err(NotifyError::InvalidTransaction(
+                        "The same payment is already processed as a create canister request."
+                            .into(),
+                    )))
+                }
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(NotificationStatus::Processing);
+                None
+            }
+        }
+    });
+
+    match maybe_early_result {
+        Some(result) => result,
+        None => {
+            let to = CyclesLedgerAccount {
+                owner: caller,
+                subaccount: to_subaccount,
+            };
+            let result = process_mint_cycles(to, deposit_memo, from, amount).await;
+
+            with_state_mut(|state| {
+                state.blocks_notified.as_mut().unwrap().insert(
+                    block_index,
+                    NotificationStatus::NotifiedMintCycles(result.clone()),
+                );
+                if is_transient_error(&result) {
+                    state.blocks_notified.as_mut().unwrap().remove(&block_index);
+                }
+            });
+
+            result
+        }
+    }
+}
+
 async fn query_block(block_index: BlockIndex, ledger_id: CanisterId) -> Result<Block, NotifyError> {
     fn failed_to_fetch_block(error_message: String) -> NotifyError {
         NotifyError::Other {
@@ -1100,6 +1413,7 @@ fn memo_to_intent_str(memo: Memo) -> String {
     match memo {
         MEMO_CREATE_CANISTER => "CreateCanister".into(),
         MEMO_TOP_UP_CANISTER => "TopUp".into(),
+        MEMO_MINT_CYCLES => "MintCycles".into(),
         _ => "unrecognized".into(),
     }
 }
@@ -1180,6 +1494,9 @@ async fn transaction_notification(tn: TransactionNotification) -> Result<CyclesR
                 NotificationStatus::NotifiedCreateCanister(resp) => {
                     Err(format!("Already notified: {:?}", resp))
                 }
+                NotificationStatus::NotifiedMintCycles(resp) => {
+                    Err(format!("Already notified: {:?}", resp))
+                }
             },
             Entry::Vacant(entry) => {
                 entry.insert(NotificationStatus::Processing);
@@ -1196,7 +1513,16 @@ async fn transaction_notification(tn: TransactionNotification) -> Result<CyclesR
             .ok_or_else(|| "Reserving requires a principal.".to_string())?)
             .try_into()
             .map_err(|err| format!("Cannot parse subaccount: {}", err))?;
-        match process_create_canister(controller, from, tn.amount, None).await {
+        match process_create_canister(
+            controller,
+            from,
+            tn.amount,
+            None,
+            None,
+            CanisterSettingsArgs::default(),
+        )
+        .await
+        {
             Ok(canister_id) => (
                 Ok(CyclesResponse::CanisterCreated(canister_id)),
                 Some(NotificationStatus::NotifiedCreateCanister(Ok(canister_id))),
@@ -1299,6 +1625,8 @@ async fn process_create_canister(
     from: AccountIdentifier,
     amount: Tokens,
     subnet_type: Option<String>,
+    subnet_selection: Option<SubnetSelection>,
+    settings: CanisterSettingsArgs,
 ) -> Result<CanisterId, NotifyError> {
     let cycles = tokens_to_cycles(amount)?;
 
@@ -1312,7 +1640,7 @@ async fn process_create_canister(
     // Create the canister. If this fails, refund. Either way,
     // return a result so that the notification cannot be retried.
     // If refund fails, we allow to retry.
-    match create_canister(controller, cycles, subnet_type).await {
+    match create_canister(controller, cycles, subnet_type, subnet_selection, settings).await {
         Ok(canister_id) => {
             burn_and_log(sub, amount).await;
             Ok(canister_id)
@@ -1356,6 +1684,43 @@ async fn process_top_up(
     }
 }
 
+async fn process_mint_cycles(
+    to: CyclesLedgerAccount,
+    deposit_memo: Option<Vec<u8>>,
+    from: AccountIdentifier,
+    amount: Tokens,
+) -> Result<NotifyMintCyclesSuccess, NotifyError> {
+    let cycles = tokens_to_cycles(amount)?;
+
+    let sub = Subaccount::from(&to.owner);
+
+    print(format!(
+        "Minting {} cycles to the cycles ledger account {:?}.",
+        cycles, to
+    ));
+
+    match deposit_to_cycles_ledger(to, deposit_memo, cycles).await {
+        Ok(CyclesLedgerDepositResult {
+            block_index,
+            balance,
+        }) => {
+            burn_and_log(sub, amount).await;
+            Ok(NotifyMintCyclesSuccess {
+                block_index,
+                minted: candid::Nat::from(cycles.get()),
+                balance,
+            })
+        }
+        Err(err) => {
+            let refund_block = refund(sub, from, amount, MINT_CYCLES_REFUND_FEE).await?;
+            Err(NotifyError::Refunded {
+                reason: err,
+                block_index: refund_block,
+            })
+        }
+    }
+}
+
 /// Attempt to burn the funds.
 /// Burning doesn't return errors - we don't want to reject the transaction
 /// notification because then it could be retried.
@@ -1473,38 +1838,51 @@ async fn deposit_cycles(canister_id: CanisterId, cycles: Cycles) -> Result<(), S
     Ok(())
 }
 
+async fn deposit_to_cycles_ledger(
+    to: CyclesLedgerAccount,
+    memo: Option<Vec<u8>>,
+    cycles: Cycles,
+) -> Result<CyclesLedgerDepositResult, String> {
+    let cycles_ledger_canister_id = with_state(|state| state.cycles_ledger_canister_id)
+        .ok_or_else(|| "No cycles ledger is configured in the CMC".to_string())?;
+
+    ensure_balance(cycles)?;
+
+    let res: Result<CyclesLedgerDepositResult, (Option<i32>, String)> =
+        dfn_core::api::call_with_funds_and_cleanup(
+            cycles_ledger_canister_id,
+            "deposit",
+            candid_one,
+            CyclesLedgerDepositArgs { to, memo },
+            dfn_core::api::Funds::new(u128::from(cycles) as u64),
+        )
+        .await;
+
+    res.map_err(|(code, msg)| {
+        format!(
+            "Depositing cycles to the cycles ledger failed with code {}: {:?}",
+            code.unwrap_or_default(),
+            msg
+        )
+    })
+}
+
 async fn create_canister(
     controller_id: PrincipalId,
     cycles: Cycles,
     subnet_type: Option<String>,
+    subnet_selection: Option<SubnetSelection>,
+    settings: CanisterSettingsArgs,
 ) -> Result<CanisterId, String> {
+    let settings = ic00_settings(controller_id, settings)?;
+
     // Retrieve randomness from the system to use later to get a random
     // permutation of subnets. Performing the asynchronous call before
     // we retrieve the list of subnets to avoid having the list of
     // subnets change in the meantime.
     let mut rng = get_rng().await?;
 
-    // If subnet_type is `Some`, then use it to determine the eligible list
-    // of subnets. Otherwise, fall back to the list of subnets for the
-    // provided controller id.
-    let mut subnets: Vec<SubnetId> = match subnet_type {
-        Some(subnet_type) => with_state(|state| {
-            let subnet_types_to_subnets = state
-                .subnet_types_to_subnets
-                .as_ref()
-                .expect("subnet types to subnets mapping is not `None`");
-            match subnet_types_to_subnets.get(&subnet_type) {
-                Some(s) => Ok(s.iter().copied().collect()),
-                None => {
-                    return Err(format!(
-                        "Provided subnet type {} does not exist",
-                        subnet_type
-                    ))
-                }
-            }
-        }),
-        None => Ok(get_subnets_for(&controller_id)),
-    }?;
+    let mut subnets = get_eligible_subnets(&controller_id, subnet_type, subnet_selection)?;
 
     // Perform a random permutation of the eligible list of subnets to ensure
     // that we load balance canister creations among them.
@@ -1524,10 +1902,7 @@ async fn create_canister(
             &Method::CreateCanister.to_string(),
             dfn_candid::candid_one,
             CreateCanisterArgs {
-                settings: Some(CanisterSettingsArgs {
-                    controller: Some(controller_id),
-                    ..CanisterSettingsArgs::default()
-                }),
+                settings: Some(settings.clone()),
             },
             dfn_core::api::Funds::new(cycles.get().try_into().unwrap()),
         )
@@ -1559,6 +1934,86 @@ async fn create_canister(
     Err(last_err.unwrap_or_else(|| "No subnets in which to create a canister.".to_owned()))
 }
 
+/// Returns the subnets on which the canister can be created.
+///
+/// If neither `subnet_type` nor `subnet_selection` is set, falls back to the
+/// list of subnets for the provided controller id.
+fn get_eligible_subnets(
+    controller_id: &PrincipalId,
+    subnet_type: Option<String>,
+    subnet_selection: Option<SubnetSelection>,
+) -> Result<Vec<SubnetId>, String> {
+    let subnet_selection = match (subnet_type, subnet_selection) {
+        (Some(_), Some(_)) => {
+            return Err("Cannot specify both a subnet type and a subnet selection".to_string())
+        }
+        (Some(subnet_type), None) => Some(SubnetSelection::SubnetType { subnet_type }),
+        (None, subnet_selection) => subnet_selection,
+    };
+    match subnet_selection {
+        Some(SubnetSelection::SubnetType { subnet_type }) => with_state(|state| {
+            let subnet_types_to_subnets = state
+                .subnet_types_to_subnets
+                .as_ref()
+                .expect("subnet types to subnets mapping is not `None`");
+            match subnet_types_to_subnets.get(&subnet_type) {
+                Some(s) => Ok(s.iter().copied().collect()),
+                None => Err(format!(
+                    "Provided subnet type {} does not exist",
+                    subnet_type
+                )),
+            }
+        }),
+        Some(SubnetSelection::Subnet { subnet }) => {
+            let is_typed_subnet = with_state(|state| {
+                state
+                    .subnet_types_to_subnets
+                    .as_ref()
+                    .expect("subnet types to subnets mapping is not `None`")
+                    .values()
+                    .any(|subnets| subnets.contains(&subnet))
+            });
+            if is_typed_subnet || get_subnets_for(controller_id).contains(&subnet) {
+                Ok(vec![subnet])
+            } else {
+                Err(format!(
+                    "Subnet {} is not authorized for controller {}",
+                    subnet, controller_id
+                ))
+            }
+        }
+        None => Ok(get_subnets_for(controller_id)),
+    }
+}
+
+/// Converts the requested settings to the management canister settings,
+/// making `controller_id` the only controller unless the settings specify
+/// the controllers.
+///
+/// The subnets on which the canister can be created are authorized for
+/// `controller_id`, so the settings must keep `controller_id` among the
+/// controllers. Otherwise, a controller could create canisters for other
+/// principals on subnets that are not authorized for them.
+fn ic00_settings(
+    controller_id: PrincipalId,
+    settings: CanisterSettingsArgs,
+) -> Result<ic_ic00_types::CanisterSettingsArgs, String> {
+    let controllers = settings.controllers.unwrap_or_else(|| vec![controller_id]);
+    if !controllers.contains(&controller_id) {
+        return Err(format!(
+            "The controllers of the new canister must include {}",
+            controller_id
+        ));
+    }
+    Ok(ic_ic00_types::CanisterSettingsArgs {
+        controller: None,
+        controllers: Some(controllers),
+        compute_allocation: settings.compute_allocation,
+        memory_allocation: settings.memory_allocation,
+        freezing_threshold: settings.freezing_threshold,
+    })
+}
+
 fn ensure_balance(cycles: Cycles) -> Result<(), String> {
     let now = dfn_core::api::now();
 
@@ -1640,7 +2095,7 @@ fn pre_upgrade() {
 
 #[export_name = "canister_post_upgrade"]
 fn post_upgrade() {
-    over_init(|_: BytesS| {
+    over_init(|BytesS(args)| {
         let bytes = stable::get();
         print(format!(
             "[cycles] deserializing state after upgrade ({} bytes)",
@@ -1652,7 +2107,26 @@ fn post_upgrade() {
             new_state.subnet_types_to_subnets = Some(BTreeMap::new());
         }
 
+        // Upgrades without arguments keep the configuration unchanged.
+        if !args.is_empty() {
+            let upgrade_args: Option<CyclesCanisterUpgradeArgs> =
+                candid::decode_one(&args).expect("Failed to decode upgrade arguments");
+            if let Some(upgrade_args) = upgrade_args {
+                if let Some(cycles_ledger_canister_id) = upgrade_args.cycles_ledger_canister_id {
+                    new_state.cycles_ledger_canister_id = Some(cycles_ledger_canister_id);
+                }
+                match upgrade_args.exchange_rate_canister {
+                    Some(ExchangeRateCanister::Set(exchange_rate_canister_id)) => {
+                        new_state.exchange_rate_canister_id = Some(exchange_rate_canister_id)
+                    }
+                    Some(ExchangeRateCanister::Unset) => new_state.exchange_rate_canister_id = None,
+                    None => (),
+                }
+            }
+        }
+
         STATE.with(|state| state.replace(Some(new_state)));
+        schedule_exchange_rate_update();
     })
 }
 
@@ -1709,6 +2183,8 @@ mod tests {
             governance_canister_id: CanisterId::ic_00(),
             minting_account_id: None,
             last_purged_notification: Some(0),
+            cycles_ledger_canister_id: None,
+            exchange_rate_canister_id: None,
         })
     }
 
@@ -2056,6 +2532,64 @@ mod tests {
         assert_eq!(maturity_modulation, computed_maturity_modulation);
     }
 
+    #[test]
+    fn test_check_exchange_rate() {
+        let now_seconds = 1_632_700_800;
+        let mut state = State::default();
+        state.recent_icp_xdr_rates = Some(get_sample_conversion_rates(now_seconds));
+        let average = compute_average_icp_xdr_rate_at_time(
+            state.recent_icp_xdr_rates.as_ref().unwrap(),
+            now_seconds,
+        )
+        .unwrap()
+        .xdr_permyriad_per_icp;
+        let rate_at = |xdr_permyriad_per_icp| IcpXdrConversionRate {
+            timestamp_seconds: now_seconds,
+            xdr_permyriad_per_icp,
+        };
+
+        assert_eq!(
+            check_exchange_rate(&state, &rate_at(average), now_seconds),
+            Ok(())
+        );
+        assert_eq!(
+            check_exchange_rate(&state, &rate_at(average * 3 / 2), now_seconds),
+            Ok(())
+        );
+        assert!(check_exchange_rate(&state, &rate_at(average * 2), now_seconds).is_err());
+        assert!(check_exchange_rate(&state, &rate_at(average / 3), now_seconds).is_err());
+
+        // Rates that are not newer than the current rate are rejected.
+        state.icp_xdr_conversion_rate = Some(rate_at(average));
+        assert!(check_exchange_rate(&state, &rate_at(average), now_seconds).is_err());
+    }
+
+    #[test]
+    fn test_exchange_rate_canister_is_available() {
+        let now_seconds = 1_632_700_800;
+        let mut state = State::default();
+        assert!(!exchange_rate_canister_is_available(&state, now_seconds));
+
+        state.exchange_rate_canister_id = Some(CanisterId::from_u64(42));
+        assert!(!exchange_rate_canister_is_available(&state, now_seconds));
+
+        state.last_exchange_rate_canister_update_seconds = Some(now_seconds - 60);
+        assert!(exchange_rate_canister_is_available(&state, now_seconds));
+
+        let unavailable_after = EXCHANGE_RATE_CANISTER_UNAVAILABLE_AFTER.as_secs();
+        state.last_exchange_rate_canister_update_seconds = Some(now_seconds - unavailable_after);
+        assert!(!exchange_rate_canister_is_available(&state, now_seconds));
+    }
+
+    #[test]
+    fn test_fetching_exchange_rate_guard() {
+        let guard = FetchingExchangeRateGuard::new().unwrap();
+        assert!(FetchingExchangeRateGuard::new().is_none());
+
+        std::mem::drop(guard);
+        assert!(FetchingExchangeRateGuard::new().is_some());
+    }
+
     #[test]
     fn test_add_subnet_type() {
         init_test_state();
@@ -2218,6 +2752,126 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_eligible_subnets() {
+        let subnet1 = subnet_test_id(0);
+        let subnet2 = subnet_test_id(1);
+        let subnet3 = subnet_test_id(2);
+        let subnet4 = subnet_test_id(3);
+        let controller = user_test_id(0).get();
+        let other_controller = user_test_id(1).get();
+
+        let mut authorized_subnets = BTreeMap::new();
+        authorized_subnets.insert(controller, vec![subnet1]);
+        STATE.with(|state| {
+            state.replace(Some(State {
+                authorized_subnets,
+                default_subnets: vec![subnet2],
+                ..Default::default()
+            }))
+        });
+        add_subnet_type("Fiduciary".to_string()).unwrap();
+        add_subnets_to_type(vec![subnet3], "Fiduciary".to_string()).unwrap();
+
+        assert_eq!(
+            get_eligible_subnets(&controller, None, None),
+            Ok(vec![subnet1])
+        );
+        assert_eq!(
+            get_eligible_subnets(&other_controller, None, None),
+            Ok(vec![subnet2])
+        );
+        assert_eq!(
+            get_eligible_subnets(
+                &other_controller,
+                None,
+                Some(SubnetSelection::SubnetType {
+                    subnet_type: "Fiduciary".to_string()
+                })
+            ),
+            Ok(vec![subnet3])
+        );
+        assert!(get_eligible_subnets(
+            &controller,
+            None,
+            Some(SubnetSelection::SubnetType {
+                subnet_type: "Storage".to_string()
+            })
+        )
+        .is_err());
+
+        // A specific subnet must be authorized for the controller or have a type.
+        assert_eq!(
+            get_eligible_subnets(
+                &controller,
+                None,
+                Some(SubnetSelection::Subnet { subnet: subnet1 })
+            ),
+            Ok(vec![subnet1])
+        );
+        assert_eq!(
+            get_eligible_subnets(
+                &controller,
+                None,
+                Some(SubnetSelection::Subnet { subnet: subnet3 })
+            ),
+            Ok(vec![subnet3])
+        );
+        assert!(get_eligible_subnets(
+            &controller,
+            None,
+            Some(SubnetSelection::Subnet { subnet: subnet2 })
+        )
+        .is_err());
+        assert!(get_eligible_subnets(
+            &other_controller,
+            None,
+            Some(SubnetSelection::Subnet { subnet: subnet4 })
+        )
+        .is_err());
+        assert!(get_eligible_subnets(
+            &controller,
+            Some("Fiduciary".to_string()),
+            Some(SubnetSelection::Subnet { subnet: subnet1 })
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_ic00_settings() {
+        let controller = user_test_id(0).get();
+        let other_controller = user_test_id(1).get();
+
+        let settings = ic00_settings(controller, CanisterSettingsArgs::default()).unwrap();
+        assert_eq!(settings.controllers, Some(vec![controller]));
+
+        let settings = ic00_settings(
+            controller,
+            CanisterSettingsArgs {
+                controllers: Some(vec![other_controller, controller]),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            settings.controllers,
+            Some(vec![other_controller, controller])
+        );
+
+        // The caller cannot use the subnets authorized for it to create
+        // canisters that only other principals control.
+        for controllers in [vec![other_controller], vec![]] {
+            assert!(ic00_settings(
+                controller,
+                CanisterSettingsArgs {
+                    controllers: Some(controllers),
+                    ..Default::default()
+                },
+            )
+            .is_err());
+        }
+    }
+
     #[test]
     fn test_candid_interface_compatibility() {
         use candid::utils::{service_compatible, CandidSource};
@@ -10,13 +10,13 @@
 // did definition of the method.
 
 use std::boxed::Box;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use async_trait::async_trait;
 use candid::candid_method;
 use dfn_candid::{candid, candid_one};
 use dfn_core::{
-    api::{arg_data, call_with_callbacks, caller, now, reject_message},
+    api::{arg_data, call_with_callbacks, caller, now, reject_message, time_nanos},
     over, over_async, println,
 };
 use dfn_protobuf::protobuf;
@@ -26,9 +26,7 @@ use rand_chacha::ChaCha20Rng;
 
 use ic_base_types::{CanisterId, PrincipalId};
 use ic_nervous_system_common::{
-    ledger::LedgerCanister,
-    stable_mem_utils::{BufferedStableMemReader, BufferedStableMemWriter},
-    MethodAuthzChange,
+    ledger::LedgerCanister, stable_mem_utils::BufferedStableMemReader, MethodAuthzChange,
 };
 use ic_nns_common::{
     access_control::{check_caller_is_gtc, check_caller_is_ledger, check_caller_is_root},
@@ -38,6 +36,7 @@ use ic_nns_common::{
 use ic_nns_constants::{CYCLES_MINTING_CANISTER_ID, LEDGER_CANISTER_ID};
 use ic_nns_governance::{
     governance::{Environment, Governance, HeapGrowthPotential, TimeWarp, CMC},
+    neuron_store,
     pb::v1::{
         claim_or_refresh_neuron_from_account_response::Result as ClaimOrRefreshNeuronFromAccountResponseResult,
         governance_error::ErrorType,
@@ -48,10 +47,11 @@ use ic_nns_governance::{
         manage_neuron_response, ClaimOrRefreshNeuronFromAccount,
         ClaimOrRefreshNeuronFromAccountResponse, ExecuteNnsFunction, Governance as GovernanceProto,
         GovernanceError, ListKnownNeuronsResponse, ListNeurons, ListNeuronsResponse,
-        ListNodeProvidersResponse, ListProposalInfo, ListProposalInfoResponse, ManageNeuron,
-        ManageNeuronResponse, MostRecentMonthlyNodeProviderRewards, NetworkEconomics, Neuron,
-        NeuronInfo, NnsFunction, NodeProvider, Proposal, ProposalInfo, RewardEvent,
-        RewardNodeProviders, SettleCommunityFundParticipation, UpdateNodeProvider, Vote,
+        ListNodeProvidersResponse, ListProposalInfo, ListProposalInfoResponse, ListPublicNeurons,
+        ListPublicNeuronsResponse, ManageNeuron, ManageNeuronResponse,
+        MostRecentMonthlyNodeProviderRewards, NetworkEconomics, Neuron, NeuronInfo, NnsFunction,
+        NodeProvider, Proposal, ProposalInfo, RewardEvent, RewardNodeProviders,
+        SettleCommunityFundParticipation, UpdateNodeProvider, Vote,
     },
 };
 
@@ -63,6 +63,10 @@ use ic_nns_governance::{
 /// some cases.
 const STABLE_MEM_BUFFER_SIZE: u32 = 100 * 1024 * 1024; // 100MiB
 
+/// How long to wait before looking for neurons to move to stable memory
+/// again, once the heap has no such neurons left or moving a batch failed.
+const NEURONS_MIGRATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 pub(crate) const LOG_PREFIX: &str = "[Governance] ";
 
 // https://dfinity.atlassian.net/browse/NNS1-1050: We are not following
@@ -300,20 +304,15 @@ fn canister_init_(init_payload: GovernanceProto) {
     governance()
         .validate()
         .expect("Error initializing the governance canister.");
+    schedule_neurons_migration(Duration::ZERO);
 }
 
 #[export_name = "canister_pre_upgrade"]
 fn canister_pre_upgrade() {
     println!("{}Executing pre upgrade", LOG_PREFIX);
 
-    let mut writer = BufferedStableMemWriter::new(STABLE_MEM_BUFFER_SIZE);
-
-    governance()
-        .proto
-        .encode(&mut writer)
+    neuron_store::write_upgrades_memory(&governance().proto)
         .expect("Error. Couldn't serialize canister pre-upgrade.");
-
-    writer.flush(); // or `drop(writer)`
 }
 
 #[export_name = "canister_post_upgrade"]
@@ -321,9 +320,15 @@ fn canister_post_upgrade() {
     dfn_core::printer::hook();
     println!("{}Executing post upgrade", LOG_PREFIX);
 
-    let reader = BufferedStableMemReader::new(STABLE_MEM_BUFFER_SIZE);
+    // Governance versions that kept all neurons in the heap serialized the
+    // whole state to the raw stable memory.
+    let proto = if neuron_store::is_legacy_layout() {
+        GovernanceProto::decode(BufferedStableMemReader::new(STABLE_MEM_BUFFER_SIZE))
+    } else {
+        neuron_store::read_upgrades_memory()
+    };
 
-    match GovernanceProto::decode(reader) {
+    match proto {
         Err(err) => {
             println!(
                 "Error deserializing canister state post-upgrade. \
@@ -637,6 +642,17 @@ fn list_neurons_(req: ListNeurons) -> ListNeuronsResponse {
     governance().list_neurons_by_principal(&req, &caller())
 }
 
+#[export_name = "canister_query list_public_neurons"]
+fn list_public_neurons() {
+    println!("{}list_public_neurons", LOG_PREFIX);
+    over(candid_one, list_public_neurons_)
+}
+
+#[candid_method(query, rename = "list_public_neurons")]
+fn list_public_neurons_(req: ListPublicNeurons) -> ListPublicNeuronsResponse {
+    governance().list_public_neurons(&req)
+}
+
 #[export_name = "canister_update get_monthly_node_provider_rewards"]
 fn get_monthly_node_provider_rewards() {
     println!("{}get_monthly_node_provider_rewards", LOG_PREFIX);
@@ -740,6 +756,28 @@ fn get_network_economics_parameters_() -> NetworkEconomics {
         .clone()
 }
 
+fn schedule_neurons_migration(delay: Duration) {
+    ic_cdk::api::set_global_timer(time_nanos() + delay.as_nanos() as u64);
+}
+
+/// Moves the next batch of neurons to stable memory.
+///
+/// This must not trap: the timer is only scheduled again by this function.
+#[export_name = "canister_global_timer"]
+fn canister_global_timer() {
+    match governance_mut().move_neurons_to_stable_memory() {
+        Ok(true) => schedule_neurons_migration(Duration::ZERO),
+        Ok(false) => schedule_neurons_migration(NEURONS_MIGRATION_INTERVAL),
+        Err(err) => {
+            println!(
+                "{}Failed to move neurons to stable memory, retrying in {:?}: {}",
+                LOG_PREFIX, NEURONS_MIGRATION_INTERVAL, err
+            );
+            schedule_neurons_migration(NEURONS_MIGRATION_INTERVAL);
+        }
+    }
+}
+
 #[export_name = "canister_heartbeat"]
 fn canister_heartbeat() {
     let future = governance_mut().run_periodic_tasks();
@@ -865,7 +903,7 @@ fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::i
     )?;
     w.encode_gauge(
         "governance_neurons_total",
-        governance.proto.neurons.len() as f64,
+        governance.num_neurons() as f64,
         "Total number of neurons.",
     )?;
     w.encode_gauge(
@@ -71,7 +71,10 @@ pub struct NeuronInfo {
     /// See \[Neuron::recent_ballots\] for a description.
     #[prost(message, repeated, tag = "5")]
     pub recent_ballots: ::prost::alloc::vec::Vec<BallotInfo>,
-    /// Current voting power of the neuron.
+    /// Current voting power of the neuron, i.e. the voting power the neuron
+    /// would be granted on a proposal made now. This is the potential voting
+    /// power reduced according to how long ago the voting power was last
+    /// refreshed (see \[VotingPowerEconomics\]).
     #[prost(uint64, tag = "6")]
     pub voting_power: u64,
     /// When the Neuron was created. A neuron can only vote on proposals
@@ -87,6 +90,16 @@ pub struct NeuronInfo {
     /// If this neuron is a known neuron, this is data associated with it, including the neuron's name and (optionally) a description.
     #[prost(message, optional, tag = "10")]
     pub known_neuron_data: ::core::option::Option<KnownNeuronData>,
+    /// The voting power of the neuron if it had refreshed its voting power
+    /// recently, as determined by its stake, dissolve delay and age.
+    #[prost(uint64, optional, tag = "11")]
+    pub potential_voting_power: ::core::option::Option<u64>,
+    /// See \[Neuron::voting_power_refreshed_timestamp_seconds\].
+    #[prost(uint64, optional, tag = "12")]
+    pub voting_power_refreshed_timestamp_seconds: ::core::option::Option<u64>,
+    /// See \[Neuron::visibility\].
+    #[prost(enumeration = "Visibility", optional, tag = "13")]
+    pub visibility: ::core::option::Option<i32>,
 }
 /// A transfer performed from some account to stake a new neuron.
 #[derive(
@@ -227,6 +240,21 @@ pub struct Neuron {
     /// automatically staked and will contribute to the neuron's voting power.
     #[prost(bool, optional, tag = "21")]
     pub auto_stake_maturity: ::core::option::Option<bool>,
+    /// The last time, in seconds from the Unix epoch, at which the voting power
+    /// of this neuron was refreshed, i.e. the last time its controller or one of
+    /// its hot keys voted directly, set its following, or sent a
+    /// RefreshVotingPower command. A neuron that has not refreshed its voting
+    /// power for long enough sees it decrease, see \[VotingPowerEconomics\].
+    #[prost(uint64, optional, tag = "22")]
+    pub voting_power_refreshed_timestamp_seconds: ::core::option::Option<u64>,
+    /// Whether anyone can see the full details of this neuron. Unset means
+    /// private, unless the neuron is a known neuron, see \[Neuron::visibility\].
+    #[prost(enumeration = "Visibility", optional, tag = "23")]
+    pub visibility: ::core::option::Option<i32>,
+    /// The maturity that is being disbursed by this neuron, see
+    /// \[ManageNeuron.DisburseMaturity\], in the order it was disbursed.
+    #[prost(message, repeated, tag = "24")]
+    pub maturity_disbursements_in_progress: ::prost::alloc::vec::Vec<MaturityDisbursement>,
     /// Whether this neuron is "Not for profit", making it dissolvable
     /// by voting.
     #[prost(bool, tag = "16")]
@@ -318,6 +346,44 @@ pub mod neuron {
         DissolveDelaySeconds(u64),
     }
 }
+/// Maturity of a neuron that is being disbursed to an account. The maturity
+/// is minted, modulated by the maturity modulation of the day, once the
+/// disbursement is finalized.
+#[derive(
+    candid::CandidType,
+    candid::Deserialize,
+    comparable::Comparable,
+    Clone,
+    PartialEq,
+    ::prost::Message,
+)]
+pub struct MaturityDisbursement {
+    /// The amount of maturity being disbursed, before maturity modulation.
+    #[prost(uint64, tag = "1")]
+    pub amount_e8s: u64,
+    /// The time at which the maturity was disbursed, in seconds since the Unix
+    /// epoch.
+    #[prost(uint64, tag = "2")]
+    pub timestamp_of_disbursement_seconds: u64,
+    /// The time at which the disbursement is finalized, i.e. the maturity is
+    /// minted to `account_to_disburse_to`, in seconds since the Unix epoch.
+    #[prost(uint64, tag = "3")]
+    pub finalize_disbursement_timestamp_seconds: u64,
+    /// The account the maturity is disbursed to.
+    #[prost(message, optional, tag = "4")]
+    pub account_to_disburse_to: ::core::option::Option<::icp_ledger::protobuf::AccountIdentifier>,
+    /// The amount minted, after maturity modulation. Set on the first attempt to
+    /// mint the maturity, so that retries mint exactly the same transfer.
+    #[prost(uint64, optional, tag = "5")]
+    pub amount_to_mint_e8s: ::core::option::Option<u64>,
+    /// The time of the first attempt to mint the maturity, in seconds since the
+    /// Unix epoch. Used as the created_at_time of the transfer, so that the ledger
+    /// recognizes retries as duplicates instead of minting again.
+    /// If the maturity is not minted within the deduplication window of the
+    /// ledger, the disbursement fails and the maturity goes back to the neuron.
+    #[prost(uint64, optional, tag = "6")]
+    pub first_mint_attempt_timestamp_seconds: ::core::option::Option<u64>,
+}
 /// Payload of a proposal that calls a function on another NNS
 /// canister. The canister and function to call is derived from the
 /// `nns_function`.
@@ -559,7 +625,7 @@ pub struct Proposal {
     /// take.
     #[prost(
         oneof = "proposal::Action",
-        tags = "10, 12, 13, 14, 15, 16, 17, 18, 19, 21, 22, 23"
+        tags = "10, 12, 13, 14, 15, 16, 17, 18, 19, 21, 22, 23, 24"
     )]
     pub action: ::core::option::Option<proposal::Action>,
 }
@@ -643,6 +709,9 @@ pub mod proposal {
         /// Call the open method on an SNS swap canister.
         #[prost(message, tag = "23")]
         OpenSnsTokenSwap(super::OpenSnsTokenSwap),
+        /// Deploy a new SNS, hand the dapp canisters over to it, and open its swap.
+        #[prost(message, tag = "24")]
+        CreateServiceNervousSystem(super::CreateServiceNervousSystem),
     }
 }
 /// Empty message to use in oneof fields that represent empty
@@ -680,7 +749,7 @@ pub struct ManageNeuron {
     pub neuron_id_or_subaccount: ::core::option::Option<manage_neuron::NeuronIdOrSubaccount>,
     #[prost(
         oneof = "manage_neuron::Command",
-        tags = "2, 3, 4, 5, 6, 7, 8, 9, 10, 13, 14, 15"
+        tags = "2, 3, 4, 5, 6, 7, 8, 9, 10, 13, 14, 15, 16, 17, 18"
     )]
     pub command: ::core::option::Option<manage_neuron::Command>,
 }
@@ -799,6 +868,20 @@ pub mod manage_neuron {
         #[prost(bool, tag = "1")]
         pub requested_setting_for_auto_stake_maturity: bool,
     }
+    /// Sets who can see the full details of this Neuron. Known neurons can
+    /// only be public.
+    #[derive(
+        candid::CandidType,
+        candid::Deserialize,
+        comparable::Comparable,
+        Clone,
+        PartialEq,
+        ::prost::Message,
+    )]
+    pub struct SetVisibility {
+        #[prost(enumeration = "super::Visibility", optional, tag = "1")]
+        pub visibility: ::core::option::Option<i32>,
+    }
     /// Commands that only configure a given neuron, but do not interact
     /// with the outside world. They all require the caller to be the
     /// controller of the neuron.
@@ -811,7 +894,7 @@ pub mod manage_neuron {
         ::prost::Message,
     )]
     pub struct Configure {
-        #[prost(oneof = "configure::Operation", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9")]
+        #[prost(oneof = "configure::Operation", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10")]
         pub operation: ::core::option::Option<configure::Operation>,
     }
     /// Nested message and enum types in `Configure`.
@@ -843,6 +926,8 @@ pub mod manage_neuron {
             LeaveCommunityFund(super::LeaveCommunityFund),
             #[prost(message, tag = "9")]
             ChangeAutoStakeMaturity(super::ChangeAutoStakeMaturity),
+            #[prost(message, tag = "10")]
+            SetVisibility(super::SetVisibility),
         }
     }
     /// Disburse this neuron's stake: transfer the staked ICP to the
@@ -968,6 +1053,75 @@ pub mod manage_neuron {
         #[prost(uint32, optional, tag = "1")]
         pub percentage_to_stake: ::core::option::Option<u32>,
     }
+    /// Refresh the voting power of a neuron, i.e. confirm that the neuron is
+    /// still actively managed, without voting or changing its following.
+    #[derive(
+        candid::CandidType,
+        candid::Deserialize,
+        comparable::Comparable,
+        Clone,
+        PartialEq,
+        ::prost::Message,
+    )]
+    pub struct RefreshVotingPower {}
+    /// Disburse the maturity of a neuron to an account. Rather than being
+    /// minted right away, the maturity is set aside and minted, modulated by
+    /// the maturity modulation of the day, after a delay of
+    /// DISBURSE_MATURITY_DELAY_SECONDS.
+    #[derive(
+        candid::CandidType,
+        candid::Deserialize,
+        comparable::Comparable,
+        Clone,
+        PartialEq,
+        ::prost::Message,
+    )]
+    pub struct DisburseMaturity {
+        /// The percentage of maturity to disburse, from 1 to 100 (inclusive).
+        #[prost(uint32, tag = "1")]
+        pub percentage_to_disburse: u32,
+        /// The account to disburse the maturity to. If not set, the caller's
+        /// default account is used.
+        #[prost(message, optional, tag = "2")]
+        pub to_account: ::core::option::Option<::icp_ledger::protobuf::AccountIdentifier>,
+    }
+    /// Set the followees of a neuron on several topics at once. Either all
+    /// topics are updated or, if any of them is invalid, none is.
+    ///
+    /// Topics that are not mentioned keep their current followees, and a topic
+    /// with an empty list of followees has its followees removed. Every
+    /// followee must be an existing neuron, and the neuron may follow at most
+    /// MAX_FOLLOWEES_PER_NEURON neurons in total, over all topics.
+    #[derive(
+        candid::CandidType,
+        candid::Deserialize,
+        comparable::Comparable,
+        Clone,
+        PartialEq,
+        ::prost::Message,
+    )]
+    pub struct SetFollowing {
+        #[prost(message, repeated, tag = "1")]
+        pub topic_following: ::prost::alloc::vec::Vec<set_following::FolloweesForTopic>,
+    }
+    /// Nested message and enum types in `SetFollowing`.
+    pub mod set_following {
+        #[derive(
+            candid::CandidType,
+            candid::Deserialize,
+            comparable::Comparable,
+            Clone,
+            PartialEq,
+            ::prost::Message,
+        )]
+        pub struct FolloweesForTopic {
+            /// Topic UNSPECIFIED means set following for the 'catch all'.
+            #[prost(enumeration = "super::super::Topic", tag = "1")]
+            pub topic: i32,
+            #[prost(message, repeated, tag = "2")]
+            pub followees: ::prost::alloc::vec::Vec<::ic_nns_common::pb::v1::NeuronId>,
+        }
+    }
     /// Disburse a portion of this neuron's stake into another neuron.
     /// This allows to split a neuron but with a new dissolve delay
     /// and owned by someone else.
@@ -1151,6 +1305,12 @@ pub mod manage_neuron {
         Merge(Merge),
         #[prost(message, tag = "15")]
         StakeMaturity(StakeMaturity),
+        #[prost(message, tag = "16")]
+        RefreshVotingPower(RefreshVotingPower),
+        #[prost(message, tag = "17")]
+        DisburseMaturity(DisburseMaturity),
+        #[prost(message, tag = "18")]
+        SetFollowing(SetFollowing),
     }
 }
 /// The response of the ManageNeuron command
@@ -1167,7 +1327,7 @@ pub mod manage_neuron {
 pub struct ManageNeuronResponse {
     #[prost(
         oneof = "manage_neuron_response::Command",
-        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13"
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16"
     )]
     pub command: ::core::option::Option<manage_neuron_response::Command>,
 }
@@ -1244,6 +1404,38 @@ pub mod manage_neuron_response {
         PartialEq,
         ::prost::Message,
     )]
+    pub struct RefreshVotingPowerResponse {}
+    #[derive(
+        candid::CandidType,
+        candid::Deserialize,
+        comparable::Comparable,
+        Clone,
+        PartialEq,
+        ::prost::Message,
+    )]
+    pub struct DisburseMaturityResponse {
+        /// The amount of maturity set aside for disbursement, before maturity
+        /// modulation.
+        #[prost(uint64, tag = "1")]
+        pub amount_disbursed_e8s: u64,
+    }
+    #[derive(
+        candid::CandidType,
+        candid::Deserialize,
+        comparable::Comparable,
+        Clone,
+        PartialEq,
+        ::prost::Message,
+    )]
+    pub struct SetFollowingResponse {}
+    #[derive(
+        candid::CandidType,
+        candid::Deserialize,
+        comparable::Comparable,
+        Clone,
+        PartialEq,
+        ::prost::Message,
+    )]
     pub struct FollowResponse {}
     #[derive(
         candid::CandidType,
@@ -1349,6 +1541,12 @@ pub mod manage_neuron_response {
         Merge(MergeResponse),
         #[prost(message, tag = "13")]
         StakeMaturity(StakeMaturityResponse),
+        #[prost(message, tag = "14")]
+        RefreshVotingPower(RefreshVotingPowerResponse),
+        #[prost(message, tag = "15")]
+        DisburseMaturity(DisburseMaturityResponse),
+        #[prost(message, tag = "16")]
+        SetFollowing(SetFollowingResponse),
     }
 }
 #[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]
@@ -1565,6 +1763,17 @@ pub struct ProposalData {
     /// operation in the execution of an OpenSnsTokenSwap proposal.
     #[prost(enumeration = "::ic_sns_swap::pb::v1::Lifecycle", optional, tag = "19")]
     pub sns_token_swap_lifecycle: ::core::option::Option<i32>,
+    /// This is populated when a CreateServiceNervousSystem proposal is executed,
+    /// once SNS-W has deployed the new SNS. It is used to authorize the swap
+    /// canister of that SNS when it calls settle_community_fund_participation.
+    #[prost(message, optional, tag = "20")]
+    pub deployed_sns_swap_canister_id: ::core::option::Option<::ic_base_types::PrincipalId>,
+    /// A human-readable (Markdown) rendering of the payload of the proposal's
+    /// action. This is populated when the proposal is made, for the kinds of
+    /// actions whose payload is otherwise opaque to voters (currently, only
+    /// ExecuteNnsFunction).
+    #[prost(string, optional, tag = "21")]
+    pub payload_text_rendering: ::core::option::Option<::prost::alloc::string::String>,
 }
 /// Stores data relevant to the "wait for quiet" implementation.
 #[derive(
@@ -1639,6 +1848,9 @@ pub struct ProposalInfo {
     pub reward_status: i32,
     #[prost(uint64, optional, tag = "19")]
     pub deadline_timestamp_seconds: ::core::option::Option<u64>,
+    /// See \[ProposalData::payload_text_rendering\].
+    #[prost(string, optional, tag = "20")]
+    pub payload_text_rendering: ::core::option::Option<::prost::alloc::string::String>,
 }
 /// Network economics contains the parameters for several operations related
 /// to the economy of the network. When submitting a NetworkEconomics proposal
@@ -1698,6 +1910,31 @@ pub struct NetworkEconomics {
     /// If unspecified or zero, all proposals are kept.
     #[prost(uint32, tag = "10")]
     pub max_proposals_to_keep_per_topic: u32,
+    /// Parameters of the reduction of the voting power of neurons that have not
+    /// refreshed it recently.
+    #[prost(message, optional, tag = "11")]
+    pub voting_power_economics: ::core::option::Option<VotingPowerEconomics>,
+}
+/// Parameters that determine how the voting power of a neuron decreases when
+/// the neuron does not refresh it, see
+/// \[Neuron::voting_power_refreshed_timestamp_seconds\].
+///
+/// A neuron keeps its full (potential) voting power for
+/// `start_reducing_voting_power_after_seconds` after the last refresh. Over the
+/// following `clear_following_after_seconds`, the voting power decreases
+/// linearly to zero. Once it has reached zero, the neuron's followees are
+/// cleared, except for the NeuronManagement topic.
+///
+/// As for the other fields of NetworkEconomics, unset values are considered
+/// unchanged in a ManageNetworkEconomics proposal.
+#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]
+#[self_describing]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VotingPowerEconomics {
+    #[prost(uint64, optional, tag = "1")]
+    pub start_reducing_voting_power_after_seconds: ::core::option::Option<u64>,
+    #[prost(uint64, optional, tag = "2")]
+    pub clear_following_after_seconds: ::core::option::Option<u64>,
 }
 /// A reward event is an event at which neuron maturity is increased
 #[derive(
@@ -1786,6 +2023,37 @@ pub struct OpenSnsTokenSwap {
     #[prost(uint64, optional, tag = "3")]
     pub community_fund_investment_e8s: ::core::option::Option<u64>,
 }
+/// Proposal action to create a new SNS. When adopted, NNS governance asks SNS-W
+/// to deploy the SNS described by sns_init_payload, hands control of the dapp
+/// canisters over to the new SNS root, and then opens the swap of the new SNS
+/// in the same way as OpenSnsTokenSwap does.
+///
+/// NNS governance must be a controller of each of the dapp canisters by the
+/// time the proposal is executed.
+#[derive(
+    candid::CandidType,
+    candid::Deserialize,
+    comparable::Comparable,
+    Clone,
+    PartialEq,
+    ::prost::Message,
+)]
+pub struct CreateServiceNervousSystem {
+    /// The configuration of the new SNS (token, governance parameters, and
+    /// initial token distribution).
+    #[prost(message, optional, tag = "1")]
+    pub sns_init_payload: ::core::option::Option<::ic_sns_init::pb::v1::SnsInitPayload>,
+    /// Various limits on the swap of the new SNS.
+    #[prost(message, optional, tag = "2")]
+    pub swap_params: ::core::option::Option<::ic_sns_swap::pb::v1::Params>,
+    /// The amount that the community fund will collectively spend in maturity on
+    /// the swap.
+    #[prost(uint64, optional, tag = "3")]
+    pub community_fund_investment_e8s: ::core::option::Option<u64>,
+    /// The canisters that will be controlled by the new SNS.
+    #[prost(message, repeated, tag = "4")]
+    pub dapp_canisters: ::prost::alloc::vec::Vec<::ic_base_types::PrincipalId>,
+}
 /// This represents the whole NNS governance system. It contains all
 /// information about the NNS governance system that must be kept
 /// across upgrades of the NNS governance system.
@@ -1899,7 +2167,7 @@ pub mod governance {
         pub timestamp: u64,
         #[prost(
             oneof = "neuron_in_flight_command::Command",
-            tags = "2, 3, 5, 7, 8, 9, 10, 20, 21"
+            tags = "2, 3, 5, 7, 8, 9, 10, 20, 21, 22"
         )]
         pub command: ::core::option::Option<neuron_in_flight_command::Command>,
     }
@@ -1948,6 +2216,8 @@ pub mod governance {
             Spawn(::ic_nns_common::pb::v1::NeuronId),
             #[prost(message, tag = "21")]
             SyncCommand(SyncCommand),
+            #[prost(message, tag = "22")]
+            FinalizeMaturityDisbursement(::ic_nns_common::pb::v1::NeuronId),
         }
     }
     /// Stores metrics that are too costly to compute each time metrics are
@@ -2060,10 +2330,34 @@ pub struct ListNeuronsResponse {
     /// For each neuron ID in the "requested list", if the neuron exists,
     /// and the caller is authorized to read the full neuron (controller,
     /// hot key, or controller or hot key of some followee on the
-    /// `ManageNeuron` topic).
+    /// `ManageNeuron` topic), or the neuron is public.
     #[prost(message, repeated, tag = "2")]
     pub full_neurons: ::prost::alloc::vec::Vec<Neuron>,
 }
+/// A request to list the public neurons, one page at a time, in increasing
+/// order of neuron ID.
+#[derive(candid::CandidType, candid::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct ListPublicNeurons {
+    /// The page to return, starting at 0. Defaults to 0.
+    #[prost(uint64, optional, tag = "1")]
+    pub page_number: ::core::option::Option<u64>,
+    /// The number of neurons per page. Defaults to, and is capped at,
+    /// MAX_LIST_PUBLIC_NEURONS_PAGE_SIZE.
+    #[prost(uint64, optional, tag = "2")]
+    pub page_size: ::core::option::Option<u64>,
+}
+/// A response to a `ListPublicNeurons` request.
+#[derive(candid::CandidType, candid::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct ListPublicNeuronsResponse {
+    /// The public neurons in the requested page, including their recent
+    /// ballots.
+    #[prost(message, repeated, tag = "1")]
+    pub full_neurons: ::prost::alloc::vec::Vec<Neuron>,
+    /// The total number of pages of public neurons, with the requested page
+    /// size.
+    #[prost(uint64, tag = "2")]
+    pub total_pages_available: u64,
+}
 /// A response to "ListKnownNeurons"
 #[derive(candid::CandidType, candid::Deserialize, Clone, PartialEq, ::prost::Message)]
 pub struct ListKnownNeuronsResponse {
@@ -2387,6 +2681,44 @@ impl NeuronState {
         }
     }
 }
+/// Who can see the full details of a neuron (e.g. its followees and how it
+/// voted), in addition to its controller, hot keys and managers.
+#[derive(
+    candid::CandidType,
+    candid::Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum Visibility {
+    Unspecified = 0,
+    /// Only the controller, hot keys and managers of the neuron can see its
+    /// full details. This is the default.
+    Private = 1,
+    /// Anyone can see the full details of the neuron. Known neurons are always
+    /// public.
+    Public = 2,
+}
+impl Visibility {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Visibility::Unspecified => "VISIBILITY_UNSPECIFIED",
+            Visibility::Private => "VISIBILITY_PRIVATE",
+            Visibility::Public => "VISIBILITY_PUBLIC",
+        }
+    }
+}
 /// The types of votes the Neuron can issue.
 #[derive(
     candid::CandidType,
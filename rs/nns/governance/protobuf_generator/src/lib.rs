@@ -7,6 +7,7 @@ pub struct ProtoPaths<'a> {
     pub nns_common: &'a Path,
     pub ledger: &'a Path,
     pub sns_swap: &'a Path,
+    pub sns_init: &'a Path,
 
     // Indirectly requiredby sns_swap
     pub sns_root: &'a Path,
@@ -32,6 +33,7 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
     config.extern_path(".ic_ledger.pb.v1", "::icp-ledger::protobuf");
     config.extern_path(".ic_sns_root.pb.v1", "::ic-sns-root::pb::v1");
     config.extern_path(".ic_sns_swap.pb.v1", "::ic-sns-swap::pb::v1");
+    config.extern_path(".ic_sns_init.pb.v1", "::ic-sns-init::pb::v1");
 
     config.type_attribute(
         "ic_nns_governance.pb.v1.SettleCommunityFundParticipation",
@@ -54,6 +56,10 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
         "ic_nns_governance.pb.v1.OpenSnsTokenSwap",
         "#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]",
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.CreateServiceNervousSystem",
+        "#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]",
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.SetSnsTokenSwapOpenTimeWindow",
         "#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]",
@@ -78,6 +84,10 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
         "ic_nns_governance.pb.v1.NeuronState",
         "#[derive(candid::CandidType, candid::Deserialize)]",
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.Visibility",
+        "#[derive(candid::CandidType, candid::Deserialize)]",
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.BallotInfo",
         [
@@ -106,6 +116,10 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
         "ic_nns_governance.pb.v1.Neuron.dissolve_state",
         ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.MaturityDisbursement",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.Neuron.Followees",
         ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
@@ -130,6 +144,10 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
         "ic_nns_governance.pb.v1.ManageNeuron.ChangeAutoStakeMaturity",
         ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ManageNeuron.SetVisibility",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.ManageNeuron.SetDissolveTimestamp",
         ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
@@ -182,6 +200,22 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
         "ic_nns_governance.pb.v1.ManageNeuron.StakeMaturity",
         ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ManageNeuron.RefreshVotingPower",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ManageNeuron.DisburseMaturity",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ManageNeuron.SetFollowing",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ManageNeuron.SetFollowing.FolloweesForTopic",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.ManageNeuron.Split",
         ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
@@ -250,6 +284,18 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
         "ic_nns_governance.pb.v1.ManageNeuronResponse.StakeMaturityResponse",
         ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ManageNeuronResponse.RefreshVotingPowerResponse",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ManageNeuronResponse.DisburseMaturityResponse",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ManageNeuronResponse.SetFollowingResponse",
+        ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.ManageNeuronResponse.FollowResponse",
         ["#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]"].join(" "),
@@ -298,6 +344,14 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
         ]
         .join(" "),
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.VotingPowerEconomics",
+        [
+            "#[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]",
+            "#[self_describing]",
+        ]
+        .join(" "),
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.Motion",
         [
@@ -416,6 +470,14 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
         "ic_nns_governance.pb.v1.ListNeuronsResponse",
         "#[derive(candid::CandidType, candid::Deserialize)]",
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ListPublicNeurons",
+        "#[derive(candid::CandidType, candid::Deserialize)]",
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ListPublicNeuronsResponse",
+        "#[derive(candid::CandidType, candid::Deserialize)]",
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.ListKnownNeurons",
         "#[derive(candid::CandidType, candid::Deserialize)]",
@@ -515,6 +577,7 @@ pub fn generate_prost_files(proto: ProtoPaths<'_>, out: &Path) {
                 proto.ledger,
                 proto.sns_root,
                 proto.sns_swap,
+                proto.sns_init,
             ],
         )
         .unwrap();
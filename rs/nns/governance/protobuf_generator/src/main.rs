@@ -13,6 +13,7 @@ fn main() {
     let nns_common_proto = manifest_dir.join("../../common/proto");
     let sns_root_proto = manifest_dir.join("../../../sns/root/proto");
     let sns_swap_proto = manifest_dir.join("../../../sns/swap/proto");
+    let sns_init_proto = manifest_dir.join("../../../sns/init/proto");
 
     match std::fs::remove_dir_all(&out) {
         Ok(_) => (),
@@ -31,6 +32,7 @@ fn main() {
             nns_common: &nns_common_proto,
             sns_root: &sns_root_proto,
             sns_swap: &sns_swap_proto,
+            sns_init: &sns_init_proto,
         },
         out.as_ref(),
     );
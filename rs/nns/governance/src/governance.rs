@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
@@ -5,6 +6,8 @@ use std::fmt;
 use std::ops::RangeInclusive;
 use std::string::ToString;
 
+use crate::neuron_store;
+use crate::nns_function_payload::validate_and_render_execute_nns_function;
 use crate::pb::v1::governance::neuron_in_flight_command::SyncCommand;
 use crate::pb::v1::{
     add_or_remove_node_provider::Change,
@@ -21,21 +24,23 @@ use crate::pb::v1::{
     neuron::Followees,
     proposal,
     reward_node_provider::RewardMode,
-    settle_community_fund_participation, Ballot, BallotInfo, ExecuteNnsFunction,
-    Governance as GovernanceProto, GovernanceError, KnownNeuron, KnownNeuronData,
-    ListKnownNeuronsResponse, ListNeurons, ListNeuronsResponse, ListProposalInfo,
-    ListProposalInfoResponse, ManageNeuron, ManageNeuronResponse,
-    MostRecentMonthlyNodeProviderRewards, Motion, NetworkEconomics, Neuron, NeuronInfo,
-    NeuronState, NnsFunction, NodeProvider, OpenSnsTokenSwap, Proposal, ProposalData, ProposalInfo,
-    ProposalRewardStatus, ProposalStatus, RewardEvent, RewardNodeProvider, RewardNodeProviders,
-    SetSnsTokenSwapOpenTimeWindow, SettleCommunityFundParticipation, Tally, Topic,
-    UpdateNodeProvider, Vote,
+    settle_community_fund_participation, Ballot, BallotInfo, CreateServiceNervousSystem,
+    ExecuteNnsFunction, Governance as GovernanceProto, GovernanceError, KnownNeuron,
+    KnownNeuronData, ListKnownNeuronsResponse, ListNeurons, ListNeuronsResponse, ListProposalInfo,
+    ListProposalInfoResponse, ListPublicNeurons, ListPublicNeuronsResponse, ManageNeuron,
+    ManageNeuronResponse, MaturityDisbursement, MostRecentMonthlyNodeProviderRewards, Motion,
+    NetworkEconomics, Neuron, NeuronInfo, NeuronState, NnsFunction, NodeProvider, OpenSnsTokenSwap,
+    Proposal, ProposalData, ProposalInfo, ProposalRewardStatus, ProposalStatus, RewardEvent,
+    RewardNodeProvider, RewardNodeProviders, SetSnsTokenSwapOpenTimeWindow,
+    SettleCommunityFundParticipation, Tally, Topic, UpdateNodeProvider, Visibility, Vote,
+    VotingPowerEconomics,
 };
 
 use async_trait::async_trait;
 use candid::{Decode, Encode};
 use dfn_protobuf::ToProto;
 use ic_base_types::{CanisterId, PrincipalId};
+use ic_ic00_types::{CanisterSettingsArgs, UpdateSettingsArgs};
 use ic_nns_common::pb::v1::{NeuronId, ProposalId};
 use ic_nns_common::types::UpdateIcpXdrConversionRatePayload;
 use ic_nns_constants::{
@@ -43,9 +48,12 @@ use ic_nns_constants::{
     LIFELINE_CANISTER_ID, REGISTRY_CANISTER_ID, ROOT_CANISTER_ID, SNS_WASM_CANISTER_ID,
 };
 use ic_protobuf::registry::dc::v1::AddOrRemoveDataCentersProposalPayload;
+use ic_sns_root::pb::v1::{RegisterDappCanisterRequest, RegisterDappCanisterResponse};
 use ic_sns_swap::pb::v1 as sns_swap_pb;
-use ic_sns_wasm::pb::v1::{ListDeployedSnsesRequest, ListDeployedSnsesResponse};
-use icp_ledger::{AccountIdentifier, Subaccount, DEFAULT_TRANSFER_FEE};
+use ic_sns_wasm::pb::v1::{
+    DeployNewSnsRequest, DeployNewSnsResponse, ListDeployedSnsesRequest, ListDeployedSnsesResponse,
+};
+use icp_ledger::{AccountIdentifier, Subaccount, TimeStamp, DEFAULT_TRANSFER_FEE};
 use registry_canister::mutations::do_add_node_operator::AddNodeOperatorPayload;
 
 #[cfg(target_arch = "wasm32")]
@@ -101,6 +109,23 @@ pub const PROPOSAL_MOTION_TEXT_BYTES_MAX: usize = 10000;
 // The maximum dissolve delay allowed for a neuron.
 pub const MAX_DISSOLVE_DELAY_SECONDS: u64 = 8 * ONE_YEAR_SECONDS;
 
+/// By default, the voting power of a neuron starts to decrease when the
+/// neuron has not refreshed it for this long. See [VotingPowerEconomics].
+pub const DEFAULT_START_REDUCING_VOTING_POWER_AFTER_SECONDS: u64 = 6 * ONE_MONTH_SECONDS;
+
+/// By default, the voting power of a neuron decreases to zero over this
+/// period, after which the following of the neuron is cleared. See
+/// [VotingPowerEconomics].
+pub const DEFAULT_CLEAR_FOLLOWING_AFTER_SECONDS: u64 = ONE_MONTH_SECONDS;
+
+/// ManageNetworkEconomics proposals may not make the voting power of neurons
+/// start to decrease sooner than this after they last refreshed it.
+pub const MIN_START_REDUCING_VOTING_POWER_AFTER_SECONDS: u64 = ONE_MONTH_SECONDS;
+
+/// ManageNetworkEconomics proposals may not make the voting power of neurons
+/// decrease to zero (and their following be cleared) faster than this.
+pub const MIN_CLEAR_FOLLOWING_AFTER_SECONDS: u64 = 7 * ONE_DAY_SECONDS;
+
 // The age of a neuron that saturates the age bonus for the voting power
 // computation.
 pub const MAX_NEURON_AGE_FOR_AGE_BONUS: u64 = 4 * ONE_YEAR_SECONDS;
@@ -111,6 +136,12 @@ pub const MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS: u64 = 6 * ONE_MONTH_S
 /// The maximum number of followees each neuron can establish for each topic.
 pub const MAX_FOLLOWEES_PER_TOPIC: usize = 15;
 
+/// The maximum number of followees each neuron can establish over all
+/// topics. A neuron that already follows more neurons than this can still
+/// change its following, as long as it does not follow more neurons
+/// afterwards. See `validate_num_followees`.
+pub const MAX_FOLLOWEES_PER_NEURON: usize = 100;
+
 /// The maximum number of recent ballots to keep, per neuron.
 pub const MAX_NEURON_RECENT_BALLOTS: usize = 100;
 
@@ -127,9 +158,21 @@ pub const REWARD_DISTRIBUTION_PERIOD_SECONDS: u64 = ONE_DAY_SECONDS;
 /// The maximum number of neurons supported.
 pub const MAX_NUMBER_OF_NEURONS: usize = 200_000;
 
+/// The minimum age of a neuron before it can be moved to stable memory, so
+/// that neurons that were just claimed are not moved before their stake is
+/// refreshed.
+pub const NEURON_MIN_AGE_FOR_STABLE_MEMORY_SECONDS: u64 = 14 * ONE_DAY_SECONDS;
+
+/// The maximum number of neurons moved to stable memory in one batch.
+pub const MAX_NEURONS_TO_MOVE_TO_STABLE_MEMORY_PER_BATCH: usize = 1000;
+
 /// The maximum number results returned by the method `list_proposals`.
 pub const MAX_LIST_PROPOSAL_RESULTS: u32 = 100;
 
+/// The maximum number of neurons returned by the method
+/// `list_public_neurons`, which is also the default page size.
+pub const MAX_LIST_PUBLIC_NEURONS_PAGE_SIZE: u64 = 100;
+
 /// The number of e8s per ICP;
 const E8S_PER_ICP: u64 = TOKEN_SUBDIVIDABLE_BY;
 
@@ -143,6 +186,13 @@ pub const MAX_NUMBER_OF_OPEN_MANAGE_NEURON_PROPOSALS: usize = 100000;
 /// Max number of hot key for each neuron.
 pub const MAX_NUM_HOT_KEYS_PER_NEURON: usize = 10;
 
+/// The delay between the time maturity is disbursed, with the
+/// `DisburseMaturity` command, and the time it is minted.
+pub const DISBURSE_MATURITY_DELAY_SECONDS: u64 = 7 * ONE_DAY_SECONDS;
+
+/// Max number of maturity disbursements in progress for each neuron.
+pub const MAX_NUM_DISBURSEMENTS_IN_PROGRESS_PER_NEURON: usize = 10;
+
 const MAX_HEAP_SIZE_IN_KIB: usize = 4 * 1024 * 1024;
 const WASM32_PAGE_SIZE_IN_KIB: usize = 64;
 
@@ -180,8 +230,64 @@ impl NetworkEconomics {
             minimum_icp_xdr_rate: 100,                                  // 1 XDR
             transaction_fee_e8s: DEFAULT_TRANSFER_FEE.get_e8s(),
             max_proposals_to_keep_per_topic: 100,
+            voting_power_economics: Some(VotingPowerEconomics::with_default_values()),
+        }
+    }
+}
+
+impl VotingPowerEconomics {
+    pub const fn with_default_values() -> Self {
+        Self {
+            start_reducing_voting_power_after_seconds: Some(
+                DEFAULT_START_REDUCING_VOTING_POWER_AFTER_SECONDS,
+            ),
+            clear_following_after_seconds: Some(DEFAULT_CLEAR_FOLLOWING_AFTER_SECONDS),
         }
     }
+
+    pub fn get_start_reducing_voting_power_after_seconds(&self) -> u64 {
+        self.start_reducing_voting_power_after_seconds
+            .unwrap_or(DEFAULT_START_REDUCING_VOTING_POWER_AFTER_SECONDS)
+    }
+
+    pub fn get_clear_following_after_seconds(&self) -> u64 {
+        self.clear_following_after_seconds
+            .unwrap_or(DEFAULT_CLEAR_FOLLOWING_AFTER_SECONDS)
+    }
+
+    /// Returns the part of `potential_voting_power` that a neuron keeps when
+    /// it last refreshed its voting power `seconds_since_refresh` ago.
+    ///
+    /// The voting power is kept in full for
+    /// `start_reducing_voting_power_after_seconds`, then decreases linearly
+    /// to zero over `clear_following_after_seconds`.
+    pub fn adjust_voting_power(
+        &self,
+        potential_voting_power: u64,
+        seconds_since_refresh: u64,
+    ) -> u64 {
+        let start_reducing = self.get_start_reducing_voting_power_after_seconds();
+        let reduction_period = self.get_clear_following_after_seconds();
+        if seconds_since_refresh <= start_reducing {
+            return potential_voting_power;
+        }
+        let seconds_into_reduction = seconds_since_refresh - start_reducing;
+        if seconds_into_reduction >= reduction_period {
+            return 0;
+        }
+        // Here, 0 < seconds_into_reduction < reduction_period.
+        let remaining = (reduction_period - seconds_into_reduction) as u128;
+        (potential_voting_power as u128 * remaining / reduction_period as u128) as u64
+    }
+
+    /// Returns true if a neuron that last refreshed its voting power
+    /// `seconds_since_refresh` ago has no voting power left.
+    pub fn is_voting_power_depleted(&self, seconds_since_refresh: u64) -> bool {
+        seconds_since_refresh
+            >= self
+                .get_start_reducing_voting_power_after_seconds()
+                .saturating_add(self.get_clear_following_after_seconds())
+    }
 }
 
 // Utility to transform a subaccount vector, as stored in the protobuf, into an
@@ -374,6 +480,24 @@ impl ManageNeuronResponse {
         }
     }
 
+    pub fn disburse_maturity_response(amount_disbursed_e8s: u64) -> Self {
+        ManageNeuronResponse {
+            command: Some(manage_neuron_response::Command::DisburseMaturity(
+                manage_neuron_response::DisburseMaturityResponse {
+                    amount_disbursed_e8s,
+                },
+            )),
+        }
+    }
+
+    pub fn refresh_voting_power_response() -> Self {
+        ManageNeuronResponse {
+            command: Some(manage_neuron_response::Command::RefreshVotingPower(
+                manage_neuron_response::RefreshVotingPowerResponse {},
+            )),
+        }
+    }
+
     pub fn follow_response() -> Self {
         ManageNeuronResponse {
             command: Some(manage_neuron_response::Command::Follow(
@@ -382,6 +506,14 @@ impl ManageNeuronResponse {
         }
     }
 
+    pub fn set_following_response() -> Self {
+        ManageNeuronResponse {
+            command: Some(manage_neuron_response::Command::SetFollowing(
+                manage_neuron_response::SetFollowingResponse {},
+            )),
+        }
+    }
+
     pub fn make_proposal_response(proposal_id: ProposalId) -> Self {
         let proposal_id = Some(proposal_id);
         ManageNeuronResponse {
@@ -621,6 +753,90 @@ impl Neuron {
         std::cmp::min(ad_stake, u64::MAX as u128) as u64
     }
 
+    /// Returns the voting power of this neuron that counts when voting, i.e.
+    /// its potential voting power (see [Neuron::voting_power]) reduced
+    /// according to how long ago the neuron last refreshed its voting power.
+    pub fn deciding_voting_power(
+        &self,
+        voting_power_economics: &VotingPowerEconomics,
+        now_seconds: u64,
+    ) -> u64 {
+        voting_power_economics.adjust_voting_power(
+            self.voting_power(now_seconds),
+            self.seconds_since_voting_power_refreshed(now_seconds),
+        )
+    }
+
+    /// Records that the neuron has been actively managed at `now_seconds`,
+    /// restoring its full voting power.
+    pub fn refresh_voting_power(&mut self, now_seconds: u64) {
+        self.voting_power_refreshed_timestamp_seconds = Some(now_seconds);
+    }
+
+    fn seconds_since_voting_power_refreshed(&self, now_seconds: u64) -> u64 {
+        // Neurons are given a refresh timestamp when they are created, or
+        // when governance is initialized for those that predate it.
+        let refreshed_timestamp_seconds = self
+            .voting_power_refreshed_timestamp_seconds
+            .unwrap_or(now_seconds);
+        now_seconds.saturating_sub(refreshed_timestamp_seconds)
+    }
+
+    /// Returns true if this neuron has no voting power left because it has
+    /// not refreshed it, and still follows other neurons on topics other
+    /// than NeuronManagement.
+    fn should_clear_following(
+        &self,
+        voting_power_economics: &VotingPowerEconomics,
+        now_seconds: u64,
+    ) -> bool {
+        voting_power_economics
+            .is_voting_power_depleted(self.seconds_since_voting_power_refreshed(now_seconds))
+            && self
+                .followees
+                .keys()
+                .any(|topic| *topic != Topic::NeuronManagement as i32)
+    }
+
+    /// Returns who can see the full details of this neuron. Known neurons
+    /// are always public, and other neurons are private unless their
+    /// controller made them public.
+    pub fn visibility(&self) -> Visibility {
+        if self.known_neuron_data.is_some() {
+            return Visibility::Public;
+        }
+        match self.visibility.and_then(Visibility::from_i32) {
+            Some(Visibility::Public) => Visibility::Public,
+            _ => Visibility::Private,
+        }
+    }
+
+    fn set_visibility(
+        &mut self,
+        set_visibility: &manage_neuron::SetVisibility,
+    ) -> Result<(), GovernanceError> {
+        let visibility = match set_visibility.visibility.and_then(Visibility::from_i32) {
+            Some(visibility @ (Visibility::Private | Visibility::Public)) => visibility,
+            _ => {
+                return Err(GovernanceError::new_with_message(
+                    ErrorType::InvalidCommand,
+                    format!(
+                        "Invalid visibility {:?}. It must be either private or public.",
+                        set_visibility.visibility
+                    ),
+                ))
+            }
+        };
+        if visibility == Visibility::Private && self.known_neuron_data.is_some() {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                "Known neurons are always public.",
+            ));
+        }
+        self.visibility = Some(visibility as i32);
+        Ok(())
+    }
+
     /// Given the specified `ballots`: determine how this neuron would
     /// vote on a proposal of `topic` based on which neurons this
     /// neuron follows on this topic (or on the default topic if this
@@ -1030,22 +1246,32 @@ impl Neuron {
                 }
                 Ok(())
             }
+            manage_neuron::configure::Operation::SetVisibility(set_visibility) => {
+                self.set_visibility(set_visibility)
+            }
         }
     }
 
     /// Get the 'public' information associated with this neuron.
-    pub fn get_neuron_info(&self, now_seconds: u64) -> NeuronInfo {
+    pub fn get_neuron_info(
+        &self,
+        voting_power_economics: &VotingPowerEconomics,
+        now_seconds: u64,
+    ) -> NeuronInfo {
         NeuronInfo {
             retrieved_at_timestamp_seconds: now_seconds,
             state: self.state(now_seconds) as i32,
             age_seconds: self.age_seconds(now_seconds),
             dissolve_delay_seconds: self.dissolve_delay_seconds(now_seconds),
             recent_ballots: self.recent_ballots.clone(),
-            voting_power: self.voting_power(now_seconds),
+            voting_power: self.deciding_voting_power(voting_power_economics, now_seconds),
             created_timestamp_seconds: self.created_timestamp_seconds,
             stake_e8s: self.stake_e8s(),
             joined_community_fund_timestamp_seconds: self.joined_community_fund_timestamp_seconds,
             known_neuron_data: self.known_neuron_data.as_ref().cloned(),
+            potential_voting_power: Some(self.voting_power(now_seconds)),
+            voting_power_refreshed_timestamp_seconds: self.voting_power_refreshed_timestamp_seconds,
+            visibility: Some(self.visibility() as i32),
         }
     }
 
@@ -1200,7 +1426,8 @@ impl Proposal {
                     );
                     Topic::SnsAndCommunityFund
                 }
-                proposal::Action::OpenSnsTokenSwap(_) => Topic::SnsAndCommunityFund,
+                proposal::Action::OpenSnsTokenSwap(_)
+                | proposal::Action::CreateServiceNervousSystem(_) => Topic::SnsAndCommunityFund,
             }
         } else {
             Topic::Unspecified
@@ -1521,7 +1748,7 @@ impl ProposalData {
             return false;
         }
 
-        if let Some(Action::OpenSnsTokenSwap(_)) =
+        if let Some(Action::OpenSnsTokenSwap(_) | Action::CreateServiceNervousSystem(_)) =
             self.proposal.as_ref().and_then(|p| p.action.as_ref())
         {
             return self
@@ -1789,7 +2016,8 @@ impl GovernanceProto {
     ) -> Result<(), GovernanceError> {
         for followees in proposed.values() {
             for followee in &followees.followees {
-                if !self.neurons.contains_key(&followee.id) {
+                if !self.neurons.contains_key(&followee.id) && !neuron_store::contains(followee.id)
+                {
                     return Err(GovernanceError::new_with_message(
                         ErrorType::NotFound,
                         "One or more of the neurons proposed to become\
@@ -1801,7 +2029,8 @@ impl GovernanceProto {
         Ok(())
     }
 
-    /// Iterate over all neurons and compute `GovernanceCachedMetrics`
+    /// Iterate over all neurons, in the heap and in stable memory, and compute
+    /// `GovernanceCachedMetrics`
     pub fn compute_cached_metrics(&self, now: u64, icp_supply: Tokens) -> GovernanceCachedMetrics {
         let mut metrics = GovernanceCachedMetrics {
             timestamp_seconds: now,
@@ -1815,7 +2044,7 @@ impl GovernanceProto {
             0
         };
 
-        for (_, neuron) in self.neurons.iter() {
+        let mut add_neuron_to_metrics = |neuron: &Neuron| {
             metrics.total_staked_e8s += neuron.stake_e8s();
 
             if neuron.joined_community_fund_timestamp_seconds.unwrap_or(0) > 0 {
@@ -1879,7 +2108,11 @@ impl GovernanceProto {
                     *count_entry += 1;
                 }
             }
+        };
+        for neuron in self.neurons.values() {
+            add_neuron_to_metrics(neuron);
         }
+        neuron_store::for_each_neuron_without_details(|neuron| add_neuron_to_metrics(&neuron));
 
         metrics
     }
@@ -2031,6 +2264,13 @@ pub struct Governance {
     /// This set is cached and will be removed and recreated when the state is saved and restored.
     pub known_neuron_name_set: HashSet<String>,
 
+    /// The IDs of the public neurons in the heap, see [Neuron::visibility].
+    /// The public neurons in stable memory are indexed by the neuron store.
+    ///
+    /// This is a cached index and will be removed and recreated when the state
+    /// is saved and restored.
+    pub public_neuron_ids_index: BTreeSet<u64>,
+
     /// Timestamp, in seconds since the unix epoch, until which no proposal
     /// needs to be processed.
     closest_proposal_deadline_timestamp_seconds: u64,
@@ -2072,6 +2312,14 @@ impl Governance {
                 distributed_e8s_equivalent: 0,
             })
         }
+        // Neurons that predate the tracking of voting power refreshes are
+        // considered to have been refreshed now.
+        let now = env.now();
+        for neuron in proto.neurons.values_mut() {
+            if neuron.voting_power_refreshed_timestamp_seconds.is_none() {
+                neuron.refresh_voting_power(now);
+            }
+        }
 
         let mut gov = Self {
             proto,
@@ -2081,6 +2329,7 @@ impl Governance {
             topic_followee_index: BTreeMap::new(),
             principal_to_neuron_ids_index: BTreeMap::new(),
             known_neuron_name_set: HashSet::new(),
+            public_neuron_ids_index: BTreeSet::new(),
             closest_proposal_deadline_timestamp_seconds: 0,
             latest_gc_timestamp_seconds: 0,
             latest_gc_num_proposals: 0,
@@ -2133,8 +2382,48 @@ impl Governance {
     /// setting a new proto).
     fn initialize_indices(&mut self) {
         self.topic_followee_index = self.proto.build_topic_followee_index();
+        // The followee index also covers the neurons in stable memory, so
+        // that they vote by following.
+        neuron_store::for_each_followees(|id, topic, followees| {
+            GovernanceProto::add_neuron_to_topic_followee_index(
+                &mut self.topic_followee_index,
+                &Neuron {
+                    id: Some(NeuronId { id }),
+                    followees: [(topic, followees)].into_iter().collect(),
+                    ..Default::default()
+                },
+            );
+        });
         self.principal_to_neuron_ids_index = self.proto.build_principal_to_neuron_ids_index();
         self.known_neuron_name_set = self.proto.build_known_neuron_name_index();
+        self.known_neuron_name_set.extend(
+            neuron_store::known_neurons()
+                .into_iter()
+                .map(|(_id, known_neuron_data)| known_neuron_data.name),
+        );
+        self.public_neuron_ids_index = self
+            .proto
+            .neurons
+            .iter()
+            .filter(|(_id, neuron)| neuron.visibility() == Visibility::Public)
+            .map(|(id, _neuron)| *id)
+            .collect();
+    }
+
+    /// Updates the entry of the neuron with the given ID in
+    /// `public_neuron_ids_index`, after the neuron was added to or removed
+    /// from the heap, or its visibility may have changed.
+    fn update_public_neuron_ids_index(&mut self, id: u64) {
+        let is_public = self
+            .proto
+            .neurons
+            .get(&id)
+            .map_or(false, |neuron| neuron.visibility() == Visibility::Public);
+        if is_public {
+            self.public_neuron_ids_index.insert(id);
+        } else {
+            self.public_neuron_ids_index.remove(&id);
+        }
     }
 
     fn transaction_fee(&self) -> u64 {
@@ -2146,7 +2435,7 @@ impl Governance {
         let mut id = self.env.random_u64();
         // Don't allow IDs that are already in use. In addition, zero
         // is an invalid ID as it can be confused with an unset ID.
-        while self.proto.neurons.contains_key(&id) || id == 0 {
+        while self.neuron_exists(id) || id == 0 {
             id = self.env.random_u64();
         }
         NeuronId { id }
@@ -2186,13 +2475,205 @@ impl Governance {
             .ok_or_else(|| Self::neuron_not_found_error(nid))
     }
 
-    fn find_neuron(&self, find_by: &NeuronIdOrSubaccount) -> Result<&Neuron, GovernanceError> {
+    /// Returns the neuron identified by `find_by`, either from the heap or
+    /// from stable memory.
+    fn find_neuron(
+        &self,
+        find_by: &NeuronIdOrSubaccount,
+    ) -> Result<Cow<'_, Neuron>, GovernanceError> {
         match find_by {
-            NeuronIdOrSubaccount::NeuronId(nid) => self.get_neuron(nid),
-            NeuronIdOrSubaccount::Subaccount(sid) => self
-                .get_neuron_by_subaccount(&Self::bytes_to_subaccount(sid)?)
-                .ok_or_else(|| Self::no_neuron_for_subaccount_error(sid)),
+            NeuronIdOrSubaccount::NeuronId(nid) => match self.proto.neurons.get(&nid.id) {
+                Some(neuron) => Ok(Cow::Borrowed(neuron)),
+                None => neuron_store::get(nid.id)
+                    .map(Cow::Owned)
+                    .ok_or_else(|| Self::neuron_not_found_error(nid)),
+            },
+            NeuronIdOrSubaccount::Subaccount(sid) => {
+                let subaccount = Self::bytes_to_subaccount(sid)?;
+                match self.get_neuron_by_subaccount(&subaccount) {
+                    Some(neuron) => Ok(Cow::Borrowed(neuron)),
+                    None => neuron_store::neuron_id_by_subaccount(&subaccount)
+                        .and_then(neuron_store::get)
+                        .map(Cow::Owned)
+                        .ok_or_else(|| Self::no_neuron_for_subaccount_error(sid)),
+                }
+            }
+        }
+    }
+
+    /// Returns true if a neuron with the given ID exists, either in the heap
+    /// or in stable memory.
+    fn neuron_exists(&self, id: u64) -> bool {
+        self.proto.neurons.contains_key(&id) || neuron_store::contains(id)
+    }
+
+    /// Returns the number of neurons, in the heap and in stable memory.
+    pub fn num_neurons(&self) -> usize {
+        self.proto.neurons.len() + neuron_store::len() as usize
+    }
+
+    /// Moves the neuron with the given ID from stable memory back to the
+    /// heap, if it is stored in stable memory.
+    fn restore_neuron_from_stable_memory(&mut self, id: u64) {
+        if self.proto.neurons.contains_key(&id) {
+            return;
+        }
+        if let Some(mut neuron) = neuron_store::remove(id) {
+            // See Governance::new.
+            if neuron.voting_power_refreshed_timestamp_seconds.is_none() {
+                neuron.refresh_voting_power(self.env.now());
+            }
+            // The topic followee index covers the neurons in stable memory
+            // already.
+            GovernanceProto::add_neuron_to_principal_to_neuron_ids_index(
+                &mut self.principal_to_neuron_ids_index,
+                id,
+                &neuron,
+            );
+            self.proto.neurons.insert(id, neuron);
+            self.update_public_neuron_ids_index(id);
+        }
+    }
+
+    /// Moves the neurons that the given command operates on from stable
+    /// memory back to the heap.
+    ///
+    /// Only the neurons that `caller` controls or is a hot key of are moved,
+    /// so that callers cannot pull arbitrary neurons into the heap. Refreshing
+    /// a neuron is the exception: anyone may refresh any neuron.
+    fn restore_managed_neurons_from_stable_memory(
+        &mut self,
+        caller: &PrincipalId,
+        mgmt: &ManageNeuron,
+    ) {
+        let mut managed_ids = vec![];
+        match mgmt.get_neuron_id_or_subaccount() {
+            Ok(Some(NeuronIdOrSubaccount::NeuronId(nid))) => managed_ids.push(nid.id),
+            Ok(Some(NeuronIdOrSubaccount::Subaccount(sid))) => managed_ids.extend(
+                Self::bytes_to_subaccount(&sid)
+                    .ok()
+                    .and_then(|subaccount| neuron_store::neuron_id_by_subaccount(&subaccount)),
+            ),
+            _ => (),
+        }
+        let refreshed_subaccount = match &mgmt.command {
+            Some(Command::ClaimOrRefresh(ClaimOrRefresh {
+                by: Some(By::Memo(memo)),
+            })) => Some(ledger::compute_neuron_staking_subaccount(*caller, *memo)),
+            Some(Command::ClaimOrRefresh(ClaimOrRefresh {
+                by: Some(By::MemoAndController(memo_and_controller)),
+            })) => Some(ledger::compute_neuron_staking_subaccount(
+                memo_and_controller.controller.unwrap_or(*caller),
+                memo_and_controller.memo,
+            )),
+            Some(Command::Merge(merge)) => {
+                managed_ids.extend(merge.source_neuron_id.as_ref().map(|nid| nid.id));
+                None
+            }
+            _ => None,
+        };
+
+        if let Some(Command::ClaimOrRefresh(_)) = &mgmt.command {
+            let refreshed_id = refreshed_subaccount
+                .as_ref()
+                .and_then(neuron_store::neuron_id_by_subaccount);
+            for id in managed_ids.into_iter().chain(refreshed_id) {
+                self.restore_neuron_from_stable_memory(id);
+            }
+            return;
+        }
+
+        for id in managed_ids {
+            let is_authorized = neuron_store::get(id)
+                .map(|neuron| neuron.is_authorized_to_vote(caller))
+                .unwrap_or(false);
+            if is_authorized {
+                self.restore_neuron_from_stable_memory(id);
+            }
+        }
+    }
+
+    /// Returns true if the neuron can be moved to stable memory: it is not
+    /// being spawned, has no maturity being disbursed, no staked maturity that
+    /// is about to be moved to its maturity, is not locked by a ledger
+    /// operation, has not joined the community fund, is at least
+    /// `NEURON_MIN_AGE_FOR_STABLE_MEMORY_SECONDS` old and has no ballots in
+    /// proposals that are not settled yet.
+    ///
+    /// These are the neurons that the periodic tasks, which only go through
+    /// the neurons in the heap, do not need to see.
+    fn can_move_to_stable_memory(
+        &self,
+        neuron: &Neuron,
+        unsettled_proposals: &[&ProposalData],
+    ) -> bool {
+        let id = match neuron.id.as_ref() {
+            Some(id) => id.id,
+            None => return false,
+        };
+        let now = self.env.now();
+        let has_pending_staked_maturity = neuron.staked_maturity_e8s_equivalent.unwrap_or(0) > 0
+            && neuron.state(now) != NeuronState::NotDissolving;
+        neuron.spawn_at_timestamp_seconds.is_none()
+            && neuron.maturity_disbursements_in_progress.is_empty()
+            && !has_pending_staked_maturity
+            && neuron.joined_community_fund_timestamp_seconds.is_none()
+            && neuron
+                .created_timestamp_seconds
+                .saturating_add(NEURON_MIN_AGE_FOR_STABLE_MEMORY_SECONDS)
+                <= now
+            && !self.proto.in_flight_commands.contains_key(&id)
+            && !unsettled_proposals
+                .iter()
+                .any(|proposal| proposal.ballots.contains_key(&id))
+    }
+
+    /// Moves up to `MAX_NEURONS_TO_MOVE_TO_STABLE_MEMORY_PER_BATCH` neurons
+    /// from the heap to stable memory. Returns true if the batch was full,
+    /// i.e. if there may be more neurons to move left in the heap.
+    ///
+    /// Neurons that do not fit into the stable maps stay in the heap. If a
+    /// neuron cannot be stored for another reason, the batch is stopped and
+    /// the error is returned; the neuron stays in the heap.
+    pub fn move_neurons_to_stable_memory(&mut self) -> Result<bool, String> {
+        let unsettled_proposals: Vec<&ProposalData> = self
+            .proto
+            .proposals
+            .values()
+            .filter(|proposal| proposal.reward_event_round == 0)
+            .collect();
+        let neuron_ids: Vec<u64> = self
+            .proto
+            .neurons
+            .iter()
+            .filter(|(_id, neuron)| self.can_move_to_stable_memory(neuron, &unsettled_proposals))
+            .filter(|(id, neuron)| match neuron_store::check_fits(neuron) {
+                Ok(()) => true,
+                Err(err) => {
+                    println!("{}Neuron {} stays in the heap: {}", LOG_PREFIX, id, err);
+                    false
+                }
+            })
+            .map(|(id, _neuron)| *id)
+            .take(MAX_NEURONS_TO_MOVE_TO_STABLE_MEMORY_PER_BATCH)
+            .collect();
+
+        for id in neuron_ids.iter() {
+            if let Some(neuron) = self.proto.neurons.get(id) {
+                neuron_store::insert(neuron)?;
+            }
+            // The topic followee index also covers the neurons in stable
+            // memory.
+            if let Some(neuron) = self.proto.neurons.remove(id) {
+                GovernanceProto::remove_neuron_from_principal_to_neuron_ids_index(
+                    &mut self.principal_to_neuron_ids_index,
+                    &neuron,
+                );
+            }
+            self.update_public_neuron_ids_index(*id);
         }
+
+        Ok(neuron_ids.len() == MAX_NEURONS_TO_MOVE_TO_STABLE_MEMORY_PER_BATCH)
     }
 
     /// Locks a given neuron for a specific, signaling there is an ongoing
@@ -2297,13 +2778,13 @@ impl Governance {
         // New neurons are not allowed when the heap is too large.
         self.check_heap_can_grow()?;
 
-        if self.proto.neurons.len() + 1 > MAX_NUMBER_OF_NEURONS {
+        if self.num_neurons() + 1 > MAX_NUMBER_OF_NEURONS {
             return Err(GovernanceError::new_with_message(
                 ErrorType::PreconditionFailed,
                 "Cannot add neuron. Max number of neurons reached.",
             ));
         }
-        if self.proto.neurons.contains_key(&neuron_id) {
+        if self.neuron_exists(neuron_id) {
             return Err(GovernanceError::new_with_message(
                 ErrorType::PreconditionFailed,
                 format!(
@@ -2332,6 +2813,7 @@ impl Governance {
         );
 
         self.proto.neurons.insert(neuron_id, neuron);
+        self.update_public_neuron_ids_index(neuron_id);
 
         Ok(())
     }
@@ -2362,6 +2844,7 @@ impl Governance {
         );
 
         self.proto.neurons.remove(&neuron_id);
+        self.update_public_neuron_ids_index(neuron_id);
 
         Ok(())
     }
@@ -2371,8 +2854,11 @@ impl Governance {
     pub fn get_neuron_ids_by_principal(&self, principal: &PrincipalId) -> Vec<u64> {
         self.principal_to_neuron_ids_index
             .get(principal)
-            .map(|ids| ids.iter().copied().collect())
-            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .copied()
+            .chain(neuron_store::neuron_ids_by_principal(principal))
+            .collect()
     }
 
     /// Return the union of `followees` with the set of Neuron IDs of all
@@ -2401,6 +2887,7 @@ impl Governance {
         caller: &PrincipalId,
     ) -> ListNeuronsResponse {
         let now = self.env.now();
+        let voting_power_economics = self.voting_power_economics();
         let implicitly_requested_neurons = if req.include_neurons_readable_by_caller {
             self.get_neuron_ids_by_principal(caller)
         } else {
@@ -2414,10 +2901,9 @@ impl Governance {
         ListNeuronsResponse {
             neuron_infos: requested_list()
                 .filter_map(|x| {
-                    self.proto
-                        .neurons
-                        .get(x)
-                        .map(|y| (*x, y.get_neuron_info(now)))
+                    self.find_neuron(&NeuronIdOrSubaccount::NeuronId(NeuronId { id: *x }))
+                        .ok()
+                        .map(|y| (*x, y.get_neuron_info(&voting_power_economics, now)))
                 })
                 .collect(),
             full_neurons: requested_list()
@@ -2426,6 +2912,45 @@ impl Governance {
         }
     }
 
+    /// Returns one page of the public neurons, in increasing order of neuron
+    /// ID, along with the total number of pages. Anyone can list the public
+    /// neurons, e.g. to see how known neurons voted.
+    pub fn list_public_neurons(&self, req: &ListPublicNeurons) -> ListPublicNeuronsResponse {
+        let page_size = req
+            .page_size
+            .unwrap_or(MAX_LIST_PUBLIC_NEURONS_PAGE_SIZE)
+            .clamp(1, MAX_LIST_PUBLIC_NEURONS_PAGE_SIZE) as usize;
+        let page_number = req.page_number.unwrap_or(0) as usize;
+
+        // The neurons in the heap and in stable memory are disjoint.
+        let mut public_neuron_ids = self
+            .public_neuron_ids_index
+            .iter()
+            .copied()
+            .chain(neuron_store::public_neuron_ids())
+            .collect::<Vec<_>>();
+        public_neuron_ids.sort_unstable();
+
+        let total_pages_available =
+            (public_neuron_ids.len() + page_size - 1) as u64 / page_size as u64;
+        let full_neurons = public_neuron_ids
+            .chunks(page_size)
+            .nth(page_number)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|id| {
+                self.find_neuron(&NeuronIdOrSubaccount::NeuronId(NeuronId { id: *id }))
+                    .ok()
+                    .map(Cow::into_owned)
+            })
+            .collect();
+
+        ListPublicNeuronsResponse {
+            full_neurons,
+            total_pages_available,
+        }
+    }
+
     /// Returns a neuron, given a subaccount.
     ///
     /// Currently we just do linear search on the neurons. We tried an index at
@@ -2464,6 +2989,14 @@ impl Governance {
                 id: Some(NeuronId { id: *id }),
                 known_neuron_data: neuron.known_neuron_data.clone(),
             })
+            .chain(
+                neuron_store::known_neurons()
+                    .into_iter()
+                    .map(|(id, known_neuron_data)| KnownNeuron {
+                        id: Some(NeuronId { id }),
+                        known_neuron_data: Some(known_neuron_data),
+                    }),
+            )
             .collect();
         ListKnownNeuronsResponse { known_neurons }
     }
@@ -2485,6 +3018,9 @@ impl Governance {
             return Err(GovernanceError::new(ErrorType::NotAuthorized));
         }
 
+        for id in neuron_ids.iter() {
+            self.restore_neuron_from_stable_memory(id.id);
+        }
         let ids_are_valid = neuron_ids.iter().all(|id| {
             if let Some(neuron) = self.proto.neurons.get(&id.id) {
                 neuron.controller.as_ref() == Some(GENESIS_TOKEN_CANISTER_ID.get_ref())
@@ -2538,6 +3074,8 @@ impl Governance {
             return Err(GovernanceError::new(ErrorType::NotAuthorized));
         }
 
+        self.restore_neuron_from_stable_memory(recipient_neuron_id.id);
+
         let donor_neuron = self.get_neuron(donor_neuron_id)?;
         let recipient_neuron = self.get_neuron(recipient_neuron_id)?;
 
@@ -2912,6 +3450,10 @@ impl Governance {
                 .joined_community_fund_timestamp_seconds,
             known_neuron_data: None,
             spawn_at_timestamp_seconds: None,
+            voting_power_refreshed_timestamp_seconds: parent_neuron
+                .voting_power_refreshed_timestamp_seconds,
+            visibility: parent_neuron.visibility,
+            maturity_disbursements_in_progress: vec![],
         };
 
         // Add the child neuron to the set of neurons undergoing ledger updates.
@@ -3433,6 +3975,9 @@ impl Governance {
             // considered part of the community fund.
             joined_community_fund_timestamp_seconds: None,
             known_neuron_data: None,
+            voting_power_refreshed_timestamp_seconds: Some(creation_timestamp_seconds),
+            visibility: None,
+            maturity_disbursements_in_progress: vec![],
         };
 
         // `add_neuron` will verify that `child_neuron.controller` `is_self_authenticating()`, so we don't need to check it here.
@@ -3563,6 +4108,29 @@ impl Governance {
         })
     }
 
+    /// Refreshes the voting power of a neuron, so that it does not decrease
+    /// for another `start_reducing_voting_power_after_seconds` (see
+    /// [VotingPowerEconomics]).
+    ///
+    /// Pre-conditions:
+    /// - The caller is the controller or a hot key of the neuron.
+    pub fn refresh_voting_power(
+        &mut self,
+        id: &NeuronId,
+        caller: &PrincipalId,
+    ) -> Result<(), GovernanceError> {
+        let now_seconds = self.env.now();
+        let neuron = self.get_neuron_mut(id)?;
+        if !neuron.is_authorized_to_vote(caller) {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::NotAuthorized,
+                "Caller is not authorized to refresh the voting power of the neuron.",
+            ));
+        }
+        neuron.refresh_voting_power(now_seconds);
+        Ok(())
+    }
+
     /// Stakes the maturity of a neuron.
     ///
     /// This method allows a neuron controller to stake the currently
@@ -3642,65 +4210,180 @@ impl Governance {
         })
     }
 
-    /// Disburse part of the stake of a neuron into a new neuron, possibly
-    /// owned by someone else and with a different dissolve delay.
-    ///
-    /// The parent neuron's stake is decreased by the amount specified in
-    /// DisburseToNeuron, while the child neuron is created with a stake
-    /// equal to that amount, minus the transfer fee.
-    ///
-    /// The child neuron doesn't inherit any of the properties of the parent
-    /// neuron, except its following.
+    /// Disburses the maturity of a neuron to an account.
     ///
-    /// On success returns the newly created neuron's id.
+    /// The maturity is removed from the neuron right away, but it is only
+    /// minted, modulated by the maturity modulation of the day, after
+    /// `DISBURSE_MATURITY_DELAY_SECONDS` (see `finalize_maturity_disbursements`).
+    /// Returns the amount of maturity that was disbursed.
     ///
-    /// Preconditions:
-    /// - The parent neuron exists
-    /// - The caller is the controller of the neuron
-    /// - The parent neuron is not already undergoing ledger updates.
-    /// - The parent neuron is not in spawning state.
-    /// - The parent neuron's state is `Dissolved` at the current timestamp.
-    /// - The staked amount minus amount to split is more than the minimum
-    ///   stake.
-    /// - The amount to split minus the transfer fee is more than the minimum
-    ///   stake.
-    pub async fn disburse_to_neuron(
+    /// Pre-conditions:
+    /// - The neuron is controlled by `caller`
+    /// - The neuron is not in spawning state.
+    /// - The percentage to disburse is between 1 and 100 (inclusive).
+    /// - The amount to disburse, after the worst case maturity modulation, is
+    ///   at least the transaction fee.
+    /// - The neuron has less than `MAX_NUM_DISBURSEMENTS_IN_PROGRESS_PER_NEURON`
+    ///   disbursements in progress.
+    pub fn disburse_maturity(
         &mut self,
         id: &NeuronId,
         caller: &PrincipalId,
-        disburse_to_neuron: &manage_neuron::DisburseToNeuron,
-    ) -> Result<NeuronId, GovernanceError> {
-        let economics = self
-            .proto
-            .economics
-            .as_ref()
-            .expect("Governance must have economics.")
-            .clone();
-
-        let creation_timestamp_seconds = self.env.now();
-        let transaction_fee_e8s = self.transaction_fee();
-
-        let parent_neuron = self.get_neuron(id)?.clone();
-        let parent_nid = parent_neuron.id.as_ref().expect("Neurons must have an id");
+        disburse_maturity: &manage_neuron::DisburseMaturity,
+    ) -> Result<u64, GovernanceError> {
+        let neuron = self.get_neuron(id)?.clone();
+        let now = self.env.now();
 
-        if parent_neuron.state(self.env.now()) == NeuronState::Spawning {
+        if neuron.state(now) == NeuronState::Spawning {
             return Err(GovernanceError::new_with_message(
                 ErrorType::PreconditionFailed,
                 "Can't perform operation on neuron: Neuron is spawning.",
             ));
         }
 
-        if !parent_neuron.is_controlled_by(caller) {
+        if !neuron.is_controlled_by(caller) {
             return Err(GovernanceError::new(ErrorType::NotAuthorized));
         }
 
-        let min_stake = economics.neuron_minimum_stake_e8s;
-        if disburse_to_neuron.amount_e8s < min_stake + transaction_fee_e8s {
-            return Err(GovernanceError::new_with_message(
-                ErrorType::InsufficientFunds,
-                format!(
-                    "Called `disburse_to_neuron` with `amount` argument {} e8s. This is too little: \
-                      at the minimum, one needs the minimum neuron stake, which is {} e8s, \
+        let to_account = match disburse_maturity.to_account.as_ref() {
+            None => AccountIdentifier::new(*caller, None),
+            Some(account) => AccountIdentifier::try_from(account).map_err(|e| {
+                GovernanceError::new_with_message(
+                    ErrorType::InvalidCommand,
+                    format!(
+                        "The account to disburse the maturity to is invalid due to: {}",
+                        e
+                    ),
+                )
+            })?,
+        };
+
+        let percentage = disburse_maturity.percentage_to_disburse;
+        if percentage > 100 || percentage == 0 {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                "The percentage of maturity to disburse must be a value between 1 and 100 (inclusive)."));
+        }
+
+        if neuron.maturity_disbursements_in_progress.len()
+            >= MAX_NUM_DISBURSEMENTS_IN_PROGRESS_PER_NEURON
+        {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                format!(
+                    "The neuron already has {} maturity disbursements in progress, \
+                     which is the maximum.",
+                    MAX_NUM_DISBURSEMENTS_IN_PROGRESS_PER_NEURON
+                ),
+            ));
+        }
+
+        let maturity_to_disburse = neuron
+            .maturity_e8s_equivalent
+            .saturating_mul(percentage as u64)
+            / 100;
+
+        // Check that the least possible amount minted is more than the
+        // transaction fee.
+        let least_possible_amount = (maturity_to_disburse as f64 * (1f64 - 0.05)) as u64;
+        let transaction_fee_e8s = self.transaction_fee();
+        if least_possible_amount < transaction_fee_e8s {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::InsufficientFunds,
+                format!(
+                    "There isn't enough maturity to disburse due to worst case maturity \
+                     modulation: {} e8s would be disbursed, but the transaction fee is {} e8s.",
+                    least_possible_amount, transaction_fee_e8s
+                ),
+            ));
+        }
+
+        let in_flight_command = NeuronInFlightCommand {
+            timestamp: now,
+            command: Some(InFlightCommand::SyncCommand(SyncCommand {})),
+        };
+
+        // Lock the neuron so that we're sure that we are not disbursing the
+        // maturity in the middle of another ongoing operation.
+        let _neuron_lock = self.lock_neuron_for_command(id.id, in_flight_command)?;
+
+        let neuron = self
+            .get_neuron_mut(id)
+            .expect("Expected the neuron to exist");
+        neuron.maturity_e8s_equivalent = neuron
+            .maturity_e8s_equivalent
+            .saturating_sub(maturity_to_disburse);
+        neuron
+            .maturity_disbursements_in_progress
+            .push(MaturityDisbursement {
+                amount_e8s: maturity_to_disburse,
+                timestamp_of_disbursement_seconds: now,
+                finalize_disbursement_timestamp_seconds: now + DISBURSE_MATURITY_DELAY_SECONDS,
+                account_to_disburse_to: Some(to_account.into()),
+            });
+
+        Ok(maturity_to_disburse)
+    }
+
+    /// Disburse part of the stake of a neuron into a new neuron, possibly
+    /// owned by someone else and with a different dissolve delay.
+    ///
+    /// The parent neuron's stake is decreased by the amount specified in
+    /// DisburseToNeuron, while the child neuron is created with a stake
+    /// equal to that amount, minus the transfer fee.
+    ///
+    /// The child neuron doesn't inherit any of the properties of the parent
+    /// neuron, except its following.
+    ///
+    /// On success returns the newly created neuron's id.
+    ///
+    /// Preconditions:
+    /// - The parent neuron exists
+    /// - The caller is the controller of the neuron
+    /// - The parent neuron is not already undergoing ledger updates.
+    /// - The parent neuron is not in spawning state.
+    /// - The parent neuron's state is `Dissolved` at the current timestamp.
+    /// - The staked amount minus amount to split is more than the minimum
+    ///   stake.
+    /// - The amount to split minus the transfer fee is more than the minimum
+    ///   stake.
+    pub async fn disburse_to_neuron(
+        &mut self,
+        id: &NeuronId,
+        caller: &PrincipalId,
+        disburse_to_neuron: &manage_neuron::DisburseToNeuron,
+    ) -> Result<NeuronId, GovernanceError> {
+        let economics = self
+            .proto
+            .economics
+            .as_ref()
+            .expect("Governance must have economics.")
+            .clone();
+
+        let creation_timestamp_seconds = self.env.now();
+        let transaction_fee_e8s = self.transaction_fee();
+
+        let parent_neuron = self.get_neuron(id)?.clone();
+        let parent_nid = parent_neuron.id.as_ref().expect("Neurons must have an id");
+
+        if parent_neuron.state(self.env.now()) == NeuronState::Spawning {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                "Can't perform operation on neuron: Neuron is spawning.",
+            ));
+        }
+
+        if !parent_neuron.is_controlled_by(caller) {
+            return Err(GovernanceError::new(ErrorType::NotAuthorized));
+        }
+
+        let min_stake = economics.neuron_minimum_stake_e8s;
+        if disburse_to_neuron.amount_e8s < min_stake + transaction_fee_e8s {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::InsufficientFunds,
+                format!(
+                    "Called `disburse_to_neuron` with `amount` argument {} e8s. This is too little: \
+                      at the minimum, one needs the minimum neuron stake, which is {} e8s, \
                       plus the transaction fee, which is {}. Hence the mininum disburse amount is {}.",
                     disburse_to_neuron.amount_e8s,
                     min_stake,
@@ -3843,6 +4526,9 @@ impl Governance {
             joined_community_fund_timestamp_seconds: None,
             known_neuron_data: None,
             spawn_at_timestamp_seconds: None,
+            voting_power_refreshed_timestamp_seconds: Some(creation_timestamp_seconds),
+            visibility: None,
+            maturity_disbursements_in_progress: vec![],
         };
 
         self.add_neuron(child_nid.id, child_neuron.clone())?;
@@ -3945,12 +4631,10 @@ impl Governance {
     /// neuron is accessible to any caller.
     pub fn get_neuron_info(&self, id: &NeuronId) -> Result<NeuronInfo, GovernanceError> {
         let neuron = self
-            .proto
-            .neurons
-            .get(&id.id)
-            .ok_or_else(|| GovernanceError::new(ErrorType::NotFound))?;
+            .find_neuron(&NeuronIdOrSubaccount::NeuronId(id.clone()))
+            .map_err(|_| GovernanceError::new(ErrorType::NotFound))?;
         let now = self.env.now();
-        Ok(neuron.get_neuron_info(now))
+        Ok(neuron.get_neuron_info(&self.voting_power_economics(), now))
     }
 
     /// Returns the neuron info for a neuron identified by id or subaccount.
@@ -3962,7 +4646,7 @@ impl Governance {
     ) -> Result<NeuronInfo, GovernanceError> {
         let neuron = self.find_neuron(by)?;
         let now = self.env.now();
-        Ok(neuron.get_neuron_info(now))
+        Ok(neuron.get_neuron_info(&self.voting_power_economics(), now))
     }
 
     /// Returns the complete neuron data for a given neuron `id` or
@@ -3976,6 +4660,10 @@ impl Governance {
         caller: &PrincipalId,
     ) -> Result<Neuron, GovernanceError> {
         let neuron = self.find_neuron(by)?;
+        // Public neurons can be read by anyone.
+        if neuron.visibility() == Visibility::Public {
+            return Ok(neuron.into_owned());
+        }
         // Check that the caller is authorized for the requested
         // neuron (controller or hot key).
         if !neuron.is_authorized_to_vote(caller) {
@@ -3984,7 +4672,9 @@ impl Governance {
             let authorized = &mut false;
             if let Some(followees) = neuron.neuron_managers() {
                 for f in followees.iter() {
-                    if let Some(f_neuron) = self.proto.neurons.get(&f.id) {
+                    if let Ok(f_neuron) =
+                        self.find_neuron(&NeuronIdOrSubaccount::NeuronId(f.clone()))
+                    {
                         if f_neuron.is_authorized_to_vote(caller) {
                             *authorized = true;
                             break;
@@ -3996,7 +4686,7 @@ impl Governance {
                 return Err(GovernanceError::new(ErrorType::NotAuthorized));
             }
         }
-        Ok(neuron.clone())
+        Ok(neuron.into_owned())
     }
 
     /// Returns the complete neuron data for a given neuron `id` after
@@ -4151,6 +4841,7 @@ impl Governance {
             deadline_timestamp_seconds: Some(
                 data.get_deadline_timestamp_seconds(voting_period_seconds),
             ),
+            payload_text_rendering: data.payload_text_rendering.clone(),
         }
     }
 
@@ -4167,7 +4858,7 @@ impl Governance {
             if let Some(mgr_ids) = self
                 .find_neuron(managed_id)
                 .ok()
-                .and_then(|x| x.neuron_managers())
+                .and_then(|x| x.neuron_managers().cloned())
             {
                 // Find one ID in the list of manager IDs that is also
                 // in 'caller_neurons'.
@@ -4547,6 +5238,9 @@ impl Governance {
                     joined_community_fund_timestamp_seconds: None,
                     known_neuron_data: None,
                     spawn_at_timestamp_seconds: None,
+                    voting_power_refreshed_timestamp_seconds: Some(now),
+                    visibility: None,
+                    maturity_disbursements_in_progress: vec![],
                 };
                 self.add_neuron(nid.id, neuron)
             }
@@ -4725,8 +5419,7 @@ impl Governance {
                         if let Some(controller) = self
                             .find_neuron(managed_neuron_id)
                             .ok()
-                            .and_then(|x| x.controller.as_ref())
-                            .copied()
+                            .and_then(|x| x.controller)
                         {
                             let result = self.manage_neuron(&controller, &mgmt).await;
                             match result.command {
@@ -4791,6 +5484,22 @@ impl Governance {
                         economics.max_proposals_to_keep_per_topic =
                             ne.max_proposals_to_keep_per_topic
                     }
+                    if let Some(new_voting_power_economics) = &ne.voting_power_economics {
+                        let voting_power_economics = economics
+                            .voting_power_economics
+                            .get_or_insert_with(VotingPowerEconomics::with_default_values);
+                        if let Some(seconds) =
+                            new_voting_power_economics.start_reducing_voting_power_after_seconds
+                        {
+                            voting_power_economics.start_reducing_voting_power_after_seconds =
+                                Some(seconds)
+                        }
+                        if let Some(seconds) =
+                            new_voting_power_economics.clear_following_after_seconds
+                        {
+                            voting_power_economics.clear_following_after_seconds = Some(seconds)
+                        }
+                    }
                 } else {
                     // If for some reason, we don't have an
                     // 'economics' proto, use the proposed one.
@@ -4934,6 +5643,15 @@ impl Governance {
                 )
                 .await;
             }
+            proposal::Action::CreateServiceNervousSystem(ref create_service_nervous_system) => {
+                self.create_service_nervous_system(
+                    pid,
+                    create_service_nervous_system,
+                    original_total_community_fund_maturity_e8s_equivalent
+                        .expect("Missing original_total_community_fund_maturity_e8s_equivalent."),
+                )
+                .await;
+            }
         }
     }
 
@@ -4961,6 +5679,22 @@ impl Governance {
         open_sns_token_swap: &OpenSnsTokenSwap,
         original_total_community_fund_maturity_e8s_equivalent: u64,
     ) {
+        let result = self
+            .try_open_sns_token_swap(
+                proposal_id,
+                open_sns_token_swap,
+                original_total_community_fund_maturity_e8s_equivalent,
+            )
+            .await;
+        self.set_proposal_execution_status(proposal_id, result);
+    }
+
+    async fn try_open_sns_token_swap(
+        &mut self,
+        proposal_id: u64,
+        open_sns_token_swap: &OpenSnsTokenSwap,
+        original_total_community_fund_maturity_e8s_equivalent: u64,
+    ) -> Result<(), GovernanceError> {
         let params = open_sns_token_swap
             .params
             .as_ref()
@@ -4984,18 +5718,14 @@ impl Governance {
             None => {
                 let failed_refunds =
                     refund_community_fund_maturity(&mut self.proto.neurons, &cf_participants);
-                self.set_proposal_execution_status(
-                    proposal_id,
-                    Err(GovernanceError::new_with_message(
-                        ErrorType::NotFound,
-                        format!(
-                            "OpenSnsTokenSwap proposal {} not found while trying to execute it. \
-                             open_sns_token_swap = {:#?}. failed_refunds = {:#?}",
-                            proposal_id, open_sns_token_swap, failed_refunds,
-                        ),
-                    )),
-                );
-                return;
+                return Err(GovernanceError::new_with_message(
+                    ErrorType::NotFound,
+                    format!(
+                        "OpenSnsTokenSwap proposal {} not found while trying to execute it. \
+                         open_sns_token_swap = {:#?}. failed_refunds = {:#?}",
+                        proposal_id, open_sns_token_swap, failed_refunds,
+                    ),
+                ));
             }
         }
 
@@ -5042,8 +5772,7 @@ impl Governance {
                     "proposal_id = {:?}. open_sns_token_swap = {:#?}. failed_refunds = {:#?}",
                     proposal_id, open_sns_token_swap, failed_refunds,
                 );
-                self.set_proposal_execution_status(proposal_id, Err(err));
-                return;
+                return Err(err);
             }
         }
 
@@ -5052,9 +5781,346 @@ impl Governance {
         // non-conservation of maturity, depending on what kind of failure we
         // are dealing with...
 
+        result
+    }
+
+    /// Deploys a new SNS via SNS-W, hands control of the dapp canisters over
+    /// to the root of the new SNS, and then opens its swap, exactly like an
+    /// OpenSnsTokenSwap proposal targeting the newly created swap canister
+    /// would.
+    ///
+    /// If handing over the dapp canisters or opening the swap fails, the new
+    /// swap is asked to abort and to give the dapp canisters back to the
+    /// fallback controllers named in the SNS init payload. Community fund
+    /// maturity drawn for the swap is refunded once the aborted swap is
+    /// finalized, like for any other aborted swap.
+    async fn create_service_nervous_system(
+        &mut self,
+        proposal_id: u64,
+        create_service_nervous_system: &CreateServiceNervousSystem,
+        original_total_community_fund_maturity_e8s_equivalent: u64,
+    ) {
+        let (root_canister_id, swap_canister_id) =
+            match self.deploy_new_sns(create_service_nervous_system).await {
+                Ok(canister_ids) => canister_ids,
+                Err(err) => {
+                    self.set_proposal_execution_status(proposal_id, Err(err));
+                    return;
+                }
+            };
+
+        // Remember the new swap canister right away, so that it is later
+        // allowed to call settle_community_fund_participation, and so that the
+        // proposal records which SNS was deployed even if a later step fails.
+        match self.proto.proposals.get_mut(&proposal_id) {
+            Some(proposal_data) => {
+                proposal_data.deployed_sns_swap_canister_id = Some(swap_canister_id.get());
+            }
+            None => {
+                self.set_proposal_execution_status(
+                    proposal_id,
+                    Err(GovernanceError::new_with_message(
+                        ErrorType::NotFound,
+                        format!(
+                            "CreateServiceNervousSystem proposal {} not found while trying to \
+                             execute it. root_canister_id = {}, swap_canister_id = {}",
+                            proposal_id, root_canister_id, swap_canister_id,
+                        ),
+                    )),
+                );
+                return;
+            }
+        }
+
+        if let Err((err, unregistered_dapp_canisters)) = self
+            .hand_over_dapp_canisters(
+                root_canister_id,
+                &create_service_nervous_system.dapp_canisters,
+            )
+            .await
+        {
+            let err = self
+                .restore_dapp_controllers_of_new_sns(
+                    root_canister_id,
+                    swap_canister_id,
+                    &unregistered_dapp_canisters,
+                    err,
+                )
+                .await;
+            self.set_proposal_execution_status(proposal_id, Err(err));
+            return;
+        }
+
+        let open_sns_token_swap = OpenSnsTokenSwap {
+            target_swap_canister_id: Some(swap_canister_id.get()),
+            params: create_service_nervous_system.swap_params.clone(),
+            community_fund_investment_e8s: create_service_nervous_system
+                .community_fund_investment_e8s,
+        };
+        let result = match self
+            .try_open_sns_token_swap(
+                proposal_id,
+                &open_sns_token_swap,
+                original_total_community_fund_maturity_e8s_equivalent,
+            )
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(err) => Err(self
+                .restore_dapp_controllers_of_new_sns(root_canister_id, swap_canister_id, &[], err)
+                .await),
+        };
         self.set_proposal_execution_status(proposal_id, result);
     }
 
+    /// Asks SNS-W to deploy the SNS described by create_service_nervous_system.
+    ///
+    /// Returns the IDs of the root and swap canisters of the new SNS.
+    async fn deploy_new_sns(
+        &mut self,
+        create_service_nervous_system: &CreateServiceNervousSystem,
+    ) -> Result<(CanisterId, CanisterId), GovernanceError> {
+        let request = DeployNewSnsRequest {
+            sns_init_payload: create_service_nervous_system.sns_init_payload.clone(),
+        };
+        let response = self
+            .env
+            .call_canister_method(
+                SNS_WASM_CANISTER_ID,
+                "deploy_new_sns",
+                Encode!(&request).expect("Unable to encode DeployNewSnsRequest."),
+            )
+            .await
+            .map_err(|err| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "Call to the deploy_new_sns method of SNS-W failed: {:?}",
+                        err
+                    ),
+                )
+            })?;
+        let response = Decode!(&response, DeployNewSnsResponse).map_err(|err| {
+            GovernanceError::new_with_message(
+                ErrorType::External,
+                format!("Unable to decode response as DeployNewSnsResponse: {}", err),
+            )
+        })?;
+        if let Some(error) = response.error {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::External,
+                format!("SNS-W failed to deploy the new SNS: {}", error.message),
+            ));
+        }
+        let sns_canister_ids = response.canisters.unwrap_or_default();
+        let (root, swap) = match (sns_canister_ids.root, sns_canister_ids.swap) {
+            (Some(root), Some(swap)) => (root, swap),
+            _ => {
+                return Err(GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "SNS-W did not report the IDs of the new SNS canisters: {:#?}",
+                        sns_canister_ids,
+                    ),
+                ))
+            }
+        };
+        let to_canister_id = |principal_id: PrincipalId| {
+            CanisterId::new(principal_id).map_err(|err| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "SNS-W returned an invalid canister ID {}: {}",
+                        principal_id, err
+                    ),
+                )
+            })
+        };
+
+        Ok((to_canister_id(root)?, to_canister_id(swap)?))
+    }
+
+    /// Makes the root of a new SNS the sole controller of the dapp canisters
+    /// and registers them with it, one dapp canister at a time.
+    ///
+    /// On failure, the error names the dapp canisters that were already
+    /// registered with root and the one that was being handed over. Dapp
+    /// canisters that come after it are left untouched. Along with the error,
+    /// returns the dapp canisters that root already controls, but that are not
+    /// registered with it, so that they can be registered before root is asked
+    /// to give the dapp canisters back.
+    async fn hand_over_dapp_canisters(
+        &mut self,
+        root_canister_id: CanisterId,
+        dapp_canisters: &[PrincipalId],
+    ) -> Result<(), (GovernanceError, Vec<PrincipalId>)> {
+        let mut registered_dapp_canisters = vec![];
+        for dapp_canister_id in dapp_canisters {
+            let new_error = |err: String| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "{} Dapp canisters already registered with SNS root {}: {:?}",
+                        err, root_canister_id, registered_dapp_canisters,
+                    ),
+                )
+            };
+            if let Err(err) = self
+                .set_dapp_canister_controller_to_sns_root(root_canister_id, *dapp_canister_id)
+                .await
+            {
+                return Err((new_error(err), vec![]));
+            }
+            // From here on, only root can give the dapp canister back.
+            if let Err(err) = self
+                .register_dapp_canister_with_sns_root(root_canister_id, *dapp_canister_id)
+                .await
+            {
+                return Err((new_error(err), vec![*dapp_canister_id]));
+            }
+            registered_dapp_canisters.push(*dapp_canister_id);
+        }
+
+        Ok(())
+    }
+
+    async fn set_dapp_canister_controller_to_sns_root(
+        &mut self,
+        root_canister_id: CanisterId,
+        dapp_canister_id: PrincipalId,
+    ) -> Result<(), String> {
+        let request = UpdateSettingsArgs {
+            canister_id: dapp_canister_id,
+            settings: CanisterSettingsArgs {
+                controllers: Some(vec![root_canister_id.get()]),
+                // Leave everything else alone.
+                controller: None,
+                compute_allocation: None,
+                memory_allocation: None,
+                freezing_threshold: None,
+            },
+        };
+        self.env
+            .call_canister_method(
+                CanisterId::ic_00(),
+                "update_settings",
+                Encode!(&request).expect("Unable to encode UpdateSettingsArgs."),
+            )
+            .await
+            .map_err(|err| {
+                format!(
+                    "Failed to hand control of dapp canister {} over to SNS root {}: {:?}.",
+                    dapp_canister_id, root_canister_id, err,
+                )
+            })?;
+
+        Ok(())
+    }
+
+    async fn register_dapp_canister_with_sns_root(
+        &mut self,
+        root_canister_id: CanisterId,
+        dapp_canister_id: PrincipalId,
+    ) -> Result<(), String> {
+        let request = RegisterDappCanisterRequest {
+            canister_id: Some(dapp_canister_id),
+        };
+        let response = self
+            .env
+            .call_canister_method(
+                root_canister_id,
+                "register_dapp_canister",
+                Encode!(&request).expect("Unable to encode RegisterDappCanisterRequest."),
+            )
+            .await
+            .map_err(|err| {
+                format!(
+                    "Dapp canister {} is controlled by SNS root {}, but registering it \
+                     failed: {:?}.",
+                    dapp_canister_id, root_canister_id, err,
+                )
+            })?;
+        Decode!(&response, RegisterDappCanisterResponse).map_err(|err| {
+            format!(
+                "Dapp canister {} is controlled by SNS root {}, but the response to \
+                 registering it could not be decoded as RegisterDappCanisterResponse: {}.",
+                dapp_canister_id, root_canister_id, err,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Aborts the swap of a new SNS whose creation failed part way, which
+    /// makes the swap ask SNS root to give the registered dapp canisters back
+    /// to the fallback controllers.
+    ///
+    /// Dapp canisters that root controls, but that are not registered with it
+    /// yet (unregistered_dapp_canisters), are registered first, as otherwise
+    /// root would not give them back.
+    ///
+    /// Returns err with the outcome appended to its message.
+    async fn restore_dapp_controllers_of_new_sns(
+        &mut self,
+        root_canister_id: CanisterId,
+        swap_canister_id: CanisterId,
+        unregistered_dapp_canisters: &[PrincipalId],
+        mut err: GovernanceError,
+    ) -> GovernanceError {
+        for dapp_canister_id in unregistered_dapp_canisters {
+            if let Err(register_err) = self
+                .register_dapp_canister_with_sns_root(root_canister_id, *dapp_canister_id)
+                .await
+            {
+                let outcome = format!(
+                    "Retrying failed as well, so dapp canister {} must be given back by \
+                     hand: {}",
+                    dapp_canister_id, register_err,
+                );
+                println!("{}{}", LOG_PREFIX, outcome);
+                err.error_message = format!("{} {}", err.error_message, outcome);
+            }
+        }
+
+        let result = self
+            .env
+            .call_canister_method(
+                swap_canister_id,
+                "restore_dapp_controllers",
+                Encode!(&RestoreDappControllersRequest {})
+                    .expect("Unable to encode RestoreDappControllersRequest."),
+            )
+            .await;
+
+        use sns_swap_pb::restore_dapp_controllers_response::Possibility;
+        let outcome = match result {
+            Err(call_err) => format!(
+                "Call to the restore_dapp_controllers method of swap canister {} failed: {:?}",
+                swap_canister_id, call_err,
+            ),
+            Ok(response) => match Decode!(&response, sns_swap_pb::RestoreDappControllersResponse) {
+                Err(decode_err) => format!(
+                    "Unable to decode response as RestoreDappControllersResponse: {}",
+                    decode_err,
+                ),
+                Ok(response) => match response.possibility {
+                    Some(Possibility::Ok(set_dapp_controllers_response)) => format!(
+                        "Dapp canister controllers restored by swap canister {}: {:?}",
+                        swap_canister_id, set_dapp_controllers_response,
+                    ),
+                    other => format!(
+                        "Swap canister {} failed to restore dapp canister controllers: {:?}",
+                        swap_canister_id, other,
+                    ),
+                },
+            },
+        };
+        println!("{}{}", LOG_PREFIX, outcome);
+
+        err.error_message = format!("{} {}", err.error_message, outcome);
+        err
+    }
+
     fn set_sns_token_swap_lifecycle_to_open(
         proposal_data: Option<&mut ProposalData>,
     ) -> Result<(), GovernanceError> {
@@ -5099,6 +6165,7 @@ impl Governance {
 
         for principal in principal_set {
             for neuron_id in self.get_neuron_ids_by_principal(principal) {
+                self.restore_neuron_from_stable_memory(neuron_id);
                 if let Some(neuron) = self.proto.neurons.get_mut(&neuron_id) {
                     if neuron.controller.as_ref() == Some(principal) {
                         neuron.kyc_verified = true;
@@ -5300,6 +6367,13 @@ impl Governance {
             .expect("NetworkEconomics not present")
     }
 
+    fn voting_power_economics(&self) -> VotingPowerEconomics {
+        self.economics()
+            .voting_power_economics
+            .clone()
+            .unwrap_or_else(VotingPowerEconomics::with_default_values)
+    }
+
     /// Inserts a proposals that has already been validated in the state.
     ///
     /// This is a low-level function that makes no verification whatsoever.
@@ -5325,7 +6399,13 @@ impl Governance {
             .map_or(1, |(k, _)| k + 1)
     }
 
-    async fn validate_proposal(&mut self, proposal: &Proposal) -> Result<(), GovernanceError> {
+    /// Validates the proposal, and returns a human-readable rendering of its
+    /// payload, if the action is of a kind that has one (currently, only
+    /// ExecuteNnsFunction).
+    async fn validate_proposal(
+        &mut self,
+        proposal: &Proposal,
+    ) -> Result<Option<String>, GovernanceError> {
         let invalid_proposal = |message| {
             Err(GovernanceError::new_with_message(
                 ErrorType::InvalidProposal,
@@ -5368,11 +6448,20 @@ impl Governance {
         // Finally, perform Action-specific validation.
         match action {
             Action::ExecuteNnsFunction(execute_nns_function) => {
-                self.validate_execute_nns_function(execute_nns_function)
+                self.validate_execute_nns_function(execute_nns_function)?;
+                return validate_and_render_execute_nns_function(execute_nns_function)
+                    .map(Some)
+                    .map_err(|message| {
+                        GovernanceError::new_with_message(ErrorType::InvalidProposal, message)
+                    });
             }
 
             Action::Motion(motion) => validate_motion(motion),
 
+            Action::ManageNetworkEconomics(network_economics) => {
+                validate_manage_network_economics(network_economics)
+            }
+
             Action::SetSnsTokenSwapOpenTimeWindow(set_sns_token_swap_open_time_window) => {
                 validate_set_sns_token_swap_open_time_window(set_sns_token_swap_open_time_window)
             }
@@ -5381,15 +6470,20 @@ impl Governance {
                 self.validate_open_sns_token_swap(open_sns_token_swap).await
             }
 
+            Action::CreateServiceNervousSystem(create_service_nervous_system) => {
+                self.validate_create_service_nervous_system(create_service_nervous_system)
+            }
+
             Action::ManageNeuron(_)
-            | Action::ManageNetworkEconomics(_)
             | Action::ApproveGenesisKyc(_)
             | Action::AddOrRemoveNodeProvider(_)
             | Action::RewardNodeProvider(_)
             | Action::SetDefaultFollowees(_)
             | Action::RewardNodeProviders(_)
             | Action::RegisterKnownNeuron(_) => Ok(()),
-        }
+        }?;
+
+        Ok(None)
     }
 
     fn validate_execute_nns_function(
@@ -5492,6 +6586,23 @@ impl Governance {
         validate_open_sns_token_swap(open_sns_token_swap, &mut *self.env).await?;
 
         // Enforce that it would be unique.
+        self.validate_no_open_swap_proposal()
+    }
+
+    /// Like OpenSnsTokenSwap, there can be at most one CreateServiceNervousSystem
+    /// proposal at a time (counting both kinds of proposals together).
+    fn validate_create_service_nervous_system(
+        &self,
+        create_service_nervous_system: &CreateServiceNervousSystem,
+    ) -> Result<(), GovernanceError> {
+        validate_create_service_nervous_system(create_service_nervous_system)?;
+
+        self.validate_no_open_swap_proposal()
+    }
+
+    /// Returns an error if there is an open proposal that would open an SNS
+    /// token swap when executed.
+    fn validate_no_open_swap_proposal(&self) -> Result<(), GovernanceError> {
         for proposal_data in self.proto.proposals.values() {
             if proposal_data.status() != ProposalStatus::Open {
                 continue;
@@ -5499,7 +6610,8 @@ impl Governance {
 
             match &proposal_data.proposal {
                 Some(Proposal {
-                    action: Some(Action::OpenSnsTokenSwap(_)),
+                    action:
+                        Some(Action::OpenSnsTokenSwap(_) | Action::CreateServiceNervousSystem(_)),
                     ..
                 }) => {}
                 _ => continue,
@@ -5509,7 +6621,8 @@ impl Governance {
                 ErrorType::InvalidProposal,
                 format!(
                     "{}ERROR: there can only be at most one open OpenSnsTokenSwap proposal \
-                     at a time, but there is already one: {:#?}",
+                     (or CreateServiceNervousSystem proposal) at a time, but there is \
+                     already one: {:#?}",
                     LOG_PREFIX, proposal_data,
                 ),
             ));
@@ -5528,7 +6641,7 @@ impl Governance {
         let now_seconds = self.env.now();
 
         // Validate proposal
-        self.validate_proposal(proposal).await?;
+        let payload_text_rendering = self.validate_proposal(proposal).await?;
 
         if let Some(proposal::Action::ManageNeuron(m)) = &proposal.action {
             assert_eq!(topic, Topic::NeuronManagement);
@@ -5609,6 +6722,13 @@ impl Governance {
                 Please try again later.",
             ));
         }
+        // The proposer votes yes on its own proposal, which, like any
+        // direct vote, refreshes its voting power.
+        self.proto
+            .neurons
+            .get_mut(&proposer_id.id)
+            .expect("Proposer not found.")
+            .refresh_voting_power(now_seconds);
         // === Preparation
         //
         // For normal proposals, every neuron with a
@@ -5626,9 +6746,10 @@ impl Governance {
             LOG_PREFIX,
             proposal
         );
+        let voting_power_economics = self.voting_power_economics();
         let mut electoral_roll = HashMap::<u64, Ballot>::new();
         let mut total_power: u128 = 0;
-        for (k, v) in self.proto.neurons.iter() {
+        let mut add_to_electoral_roll = |k: u64, v: &Neuron| {
             // If this neuron is eligible to vote, record its
             // voting power at the time of making the
             // proposal.
@@ -5636,18 +6757,30 @@ impl Governance {
                 < MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS
             {
                 // Not eligible due to dissolve delay.
-                continue;
+                return;
+            }
+            let power = v.deciding_voting_power(&voting_power_economics, now_seconds);
+            if power == 0 {
+                // Not eligible because the neuron has not refreshed its
+                // voting power for too long.
+                return;
             }
-            let power = v.voting_power(now_seconds);
             total_power += power as u128;
             electoral_roll.insert(
-                *k,
+                k,
                 Ballot {
                     vote: Vote::Unspecified as i32,
                     voting_power: power,
                 },
             );
+        };
+        for (k, v) in self.proto.neurons.iter() {
+            add_to_electoral_roll(*k, v);
         }
+        // The neurons in stable memory are eligible to vote as well.
+        neuron_store::for_each_neuron_without_details(|v| {
+            add_to_electoral_roll(v.id.as_ref().expect("Neuron must have an id").id, &v)
+        });
         if total_power >= (u64::MAX as u128) {
             // The way the neurons are configured, the total voting
             // power on this proposal would overflow a u64!
@@ -5669,7 +6802,9 @@ impl Governance {
         let proposal_num = self.next_proposal_id();
         let proposal_id = ProposalId { id: proposal_num };
         let original_total_community_fund_maturity_e8s_equivalent =
-            if let Some(Action::OpenSnsTokenSwap(_)) = proposal.action {
+            if let Some(Action::OpenSnsTokenSwap(_) | Action::CreateServiceNervousSystem(_)) =
+                proposal.action
+            {
                 Some(total_community_fund_maturity_e8s_equivalent(
                     &self.proto.neurons,
                 ))
@@ -5685,6 +6820,7 @@ impl Governance {
             proposal_timestamp_seconds: now_seconds,
             ballots: electoral_roll,
             original_total_community_fund_maturity_e8s_equivalent,
+            payload_text_rendering,
             ..Default::default()
         };
 
@@ -5725,7 +6861,8 @@ impl Governance {
     // cascade voting according to the following relationships
     // specified in 'followee_index' (mapping followees to followers for
     // the topic) and 'neurons' (which contains a mapping of followers
-    // to followees).
+    // to followees). Followers that are not in 'neurons' are looked up
+    // in stable memory.
     fn cast_vote_and_cascade_follow(
         proposal_id: &ProposalId,
         ballots: &mut HashMap<u64, Ballot>,
@@ -5754,15 +6891,33 @@ impl Governance {
                 if let Some(k_ballot) = ballots.get_mut(k) {
                     // Neuron with ID k is eligible to vote.
                     if k_ballot.vote == (Vote::Unspecified as i32) {
-                        if let Some(k_neuron) = neurons.get_mut(k) {
+                        // Register the neuron's ballot in the neuron itself,
+                        // which may be in stable memory.
+                        let k_neuron_found = match neurons.get_mut(k) {
+                            Some(k_neuron) => {
+                                k_neuron.register_recent_ballot(topic, proposal_id, *v);
+                                true
+                            }
+                            None => match neuron_store::update(*k, |k_neuron| {
+                                k_neuron.register_recent_ballot(topic, proposal_id, *v)
+                            }) {
+                                Ok(found) => found.is_some(),
+                                Err(err) => {
+                                    println!(
+                                        "{}Failed to register the ballot of neuron {} \
+                                         in stable memory: {}",
+                                        LOG_PREFIX, k, err
+                                    );
+                                    true
+                                }
+                            },
+                        };
+                        if k_neuron_found {
                             // Only update a vote if it was previously
                             // unspecified. Following can trigger votes
                             // for neurons that have already voted
                             // (manually) and we don't change these votes.
                             k_ballot.vote = *v as i32;
-                            // Register the neuron's ballot in the
-                            // neuron itself.
-                            k_neuron.register_recent_ballot(topic, proposal_id, *v);
                             // Here k is the followee, i.e., the neuron
                             // that has just cast a vote that may be
                             // followed by other neurons.
@@ -5805,7 +6960,11 @@ impl Governance {
             // new set now.
             induction_votes.clear();
             for f in all_followers.iter() {
-                if let Some(f_neuron) = neurons.get(f) {
+                let f_neuron = match neurons.get(f) {
+                    Some(f_neuron) => Some(Cow::Borrowed(f_neuron)),
+                    None => neuron_store::get(*f).map(Cow::Owned),
+                };
+                if let Some(f_neuron) = f_neuron {
                     let f_vote = f_neuron.would_follow_ballots(topic, ballots);
                     if f_vote != Vote::Unspecified {
                         // f_vote is yes or no, i.e., f_neuron's
@@ -5920,6 +7079,12 @@ impl Governance {
             );
         }
 
+        // Voting directly shows that the neuron is actively managed.
+        let now_seconds = self.env.now();
+        if let Some(neuron) = self.proto.neurons.get_mut(&neuron_id.id) {
+            neuron.refresh_voting_power(now_seconds);
+        }
+
         self.process_proposal(proposal_id.id);
 
         Ok(())
@@ -5941,6 +7106,8 @@ impl Governance {
         // fact that we have to maintain a reverse index of all follow
         // relationships, i.e., the `topic_followee_index`.
 
+        let now_seconds = self.env.now();
+
         // Find the neuron to modify.
         let neuron = self.proto.neurons.get_mut(&id.id).ok_or_else(||
             // The specified neuron is not present.
@@ -5974,6 +7141,17 @@ impl Governance {
                 "Too many followees.",
             ));
         }
+        let mut new_followees = neuron.followees.clone();
+        new_followees.insert(
+            f.topic,
+            Followees {
+                followees: f.followees.clone(),
+            },
+        );
+        validate_num_followees(&neuron.followees, &new_followees)?;
+        // Setting the following of a neuron shows that it is actively
+        // managed.
+        neuron.refresh_voting_power(now_seconds);
         // First, remove the current followees for this neuron and
         // this topic from the follower cache.
         if let Some(neuron_followees) = neuron.followees.get(&f.topic) {
@@ -6035,6 +7213,123 @@ impl Governance {
         }
     }
 
+    /// Set the followees of this neuron on several topics at once.
+    ///
+    /// All topics are validated before any of them is modified, so that
+    /// either all of the requested following is set or none of it is. The
+    /// problems found are reported together, per topic, in a single error.
+    fn set_following(
+        &mut self,
+        id: &NeuronId,
+        caller: &PrincipalId,
+        set_following: &manage_neuron::SetFollowing,
+    ) -> Result<(), GovernanceError> {
+        let now_seconds = self.env.now();
+
+        let neuron = self.proto.neurons.get(&id.id).ok_or_else(|| {
+            GovernanceError::new_with_message(
+                ErrorType::NotFound,
+                &format!("Neuron not found: {}", id.id),
+            )
+        })?;
+
+        // Same authorization rules as for the `Follow` command: only the
+        // controller can change the followees for the ManageNeuron topic.
+        if !neuron.is_authorized_to_vote(caller) {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::NotAuthorized,
+                "Caller is not authorized to manage following of neuron.",
+            ));
+        }
+        if set_following
+            .topic_following
+            .iter()
+            .any(|f| f.topic() == Topic::NeuronManagement)
+            && !neuron.is_controlled_by(caller)
+        {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::NotAuthorized,
+                "Caller is not authorized to manage following of neuron for the ManageNeuron topic.",
+            ));
+        }
+
+        let mut errors = Vec::new();
+        let mut seen_topics = BTreeSet::new();
+        for topic_following in &set_following.topic_following {
+            let topic = match Topic::from_i32(topic_following.topic) {
+                Some(topic) => topic,
+                None => {
+                    errors.push(format!("Invalid topic {}.", topic_following.topic));
+                    continue;
+                }
+            };
+            if !seen_topics.insert(topic) {
+                errors.push(format!("Topic {:?} is specified more than once.", topic));
+                continue;
+            }
+
+            let mut topic_errors = Vec::new();
+            if topic_following.followees.len() > MAX_FOLLOWEES_PER_TOPIC {
+                topic_errors.push(format!(
+                    "too many followees ({}, the maximum is {})",
+                    topic_following.followees.len(),
+                    MAX_FOLLOWEES_PER_TOPIC
+                ));
+            }
+            let mut seen_followees = BTreeSet::new();
+            for followee in &topic_following.followees {
+                if !seen_followees.insert(followee.id) {
+                    topic_errors.push(format!(
+                        "neuron {} is specified more than once",
+                        followee.id
+                    ));
+                } else if !self.neuron_exists(followee.id) {
+                    topic_errors.push(format!("neuron {} does not exist", followee.id));
+                }
+            }
+            if !topic_errors.is_empty() {
+                errors.push(format!("Topic {:?}: {}.", topic, topic_errors.join(", ")));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::InvalidCommand,
+                &format!("Invalid following: {}", errors.join(" ")),
+            ));
+        }
+
+        let mut new_followees = neuron.followees.clone();
+        for topic_following in &set_following.topic_following {
+            if topic_following.followees.is_empty() {
+                new_followees.remove(&topic_following.topic);
+            } else {
+                new_followees.insert(
+                    topic_following.topic,
+                    Followees {
+                        followees: topic_following.followees.clone(),
+                    },
+                );
+            }
+        }
+        validate_num_followees(&neuron.followees, &new_followees)?;
+
+        let neuron = self
+            .proto
+            .neurons
+            .get_mut(&id.id)
+            .expect("Neuron must exist");
+        GovernanceProto::remove_neuron_from_topic_followee_index(
+            &mut self.topic_followee_index,
+            neuron,
+        );
+        neuron.followees = new_followees;
+        GovernanceProto::add_neuron_to_topic_followee_index(&mut self.topic_followee_index, neuron);
+        // Setting the following of a neuron shows that it is actively
+        // managed.
+        neuron.refresh_voting_power(now_seconds);
+        Ok(())
+    }
+
     fn configure_neuron(
         &mut self,
         id: &NeuronId,
@@ -6078,6 +7373,7 @@ impl Governance {
                 }
                 _ => (),
             }
+            self.update_public_neuron_ids_index(id.id);
             Ok(())
         } else {
             Err(GovernanceError::new_with_message(
@@ -6257,6 +7553,9 @@ impl Governance {
             joined_community_fund_timestamp_seconds: None,
             known_neuron_data: None,
             spawn_at_timestamp_seconds: None,
+            voting_power_refreshed_timestamp_seconds: Some(now),
+            visibility: None,
+            maturity_disbursements_in_progress: vec![],
         };
 
         // This also verifies that there are not too many neurons already.
@@ -6371,6 +7670,7 @@ impl Governance {
             ));
         }
 
+        self.restore_neuron_from_stable_memory(neuron_id.id);
         let neuron = self.proto.neurons.get_mut(&neuron_id.id).ok_or_else(||
             // The specified neuron is not present.
             GovernanceError::new_with_message(ErrorType::NotFound, "Neuron not found"))?;
@@ -6380,6 +7680,8 @@ impl Governance {
         neuron.known_neuron_data = Some(known_neuron_data.clone());
         self.known_neuron_name_set
             .insert(known_neuron_data.name.clone());
+        // Known neurons are public.
+        self.update_public_neuron_ids_index(neuron_id.id);
 
         Ok(())
     }
@@ -6399,6 +7701,11 @@ impl Governance {
         caller: &PrincipalId,
         mgmt: &ManageNeuron,
     ) -> Result<ManageNeuronResponse, GovernanceError> {
+        // Neurons may have been moved to stable memory, the commands below
+        // operate on the neurons in the heap. Neurons are only restored for
+        // callers that are authorized to manage them.
+        self.restore_managed_neurons_from_stable_memory(caller, mgmt);
+
         // We run claim or refresh before we check whether a neuron exists because it
         // may not in the case of the neuron being claimed
         if let Some(manage_neuron::Command::ClaimOrRefresh(claim_or_refresh)) = &mgmt.command {
@@ -6482,6 +7789,12 @@ impl Governance {
             Some(manage_neuron::Command::StakeMaturity(s)) => self
                 .stake_maturity_of_neuron(&id, caller, s)
                 .map(ManageNeuronResponse::stake_maturity_response),
+            Some(manage_neuron::Command::RefreshVotingPower(_)) => self
+                .refresh_voting_power(&id, caller)
+                .map(|_| ManageNeuronResponse::refresh_voting_power_response()),
+            Some(manage_neuron::Command::DisburseMaturity(d)) => self
+                .disburse_maturity(&id, caller, d)
+                .map(ManageNeuronResponse::disburse_maturity_response),
             Some(manage_neuron::Command::Split(s)) => self
                 .split_neuron(&id, caller, s)
                 .await
@@ -6497,6 +7810,9 @@ impl Governance {
             Some(manage_neuron::Command::Follow(f)) => self
                 .follow(&id, caller, f)
                 .map(|_| ManageNeuronResponse::follow_response()),
+            Some(manage_neuron::Command::SetFollowing(s)) => self
+                .set_following(&id, caller, s)
+                .map(|_| ManageNeuronResponse::set_following_response()),
             Some(manage_neuron::Command::MakeProposal(p)) => self
                 .make_proposal(&id, caller, p)
                 .await
@@ -6628,6 +7944,7 @@ impl Governance {
         }
 
         self.maybe_move_staked_maturity();
+        self.clear_following_of_neurons_without_voting_power();
         self.maybe_gc();
     }
 
@@ -6681,12 +7998,76 @@ impl Governance {
         }
     }
 
+    /// Clears the followees of the neurons that have not refreshed their
+    /// voting power for so long that they have none left, except on the
+    /// NeuronManagement topic. Such neurons no longer vote by following, until
+    /// their controller sets their following again.
+    fn clear_following_of_neurons_without_voting_power(&mut self) {
+        let now_seconds = self.env.now();
+        let voting_power_economics = self.voting_power_economics();
+        for neuron in self
+            .proto
+            .neurons
+            .values_mut()
+            .filter(|n| n.should_clear_following(&voting_power_economics, now_seconds))
+        {
+            GovernanceProto::remove_neuron_from_topic_followee_index(
+                &mut self.topic_followee_index,
+                neuron,
+            );
+            neuron
+                .followees
+                .retain(|topic, _| *topic == Topic::NeuronManagement as i32);
+            GovernanceProto::add_neuron_to_topic_followee_index(
+                &mut self.topic_followee_index,
+                neuron,
+            );
+        }
+
+        let mut stable_neuron_ids = vec![];
+        neuron_store::for_each_neuron_without_details(|neuron| {
+            if voting_power_economics
+                .is_voting_power_depleted(neuron.seconds_since_voting_power_refreshed(now_seconds))
+            {
+                stable_neuron_ids.extend(neuron.id.map(|id| id.id));
+            }
+        });
+        for id in stable_neuron_ids {
+            let should_clear_following = neuron_store::get(id)
+                .map(|neuron| neuron.should_clear_following(&voting_power_economics, now_seconds))
+                .unwrap_or(false);
+            if !should_clear_following {
+                continue;
+            }
+            let result = neuron_store::update(id, |neuron| {
+                GovernanceProto::remove_neuron_from_topic_followee_index(
+                    &mut self.topic_followee_index,
+                    neuron,
+                );
+                neuron
+                    .followees
+                    .retain(|topic, _| *topic == Topic::NeuronManagement as i32);
+                GovernanceProto::add_neuron_to_topic_followee_index(
+                    &mut self.topic_followee_index,
+                    neuron,
+                );
+            });
+            if let Err(err) = result {
+                println!(
+                    "{}Failed to clear the following of neuron {} in stable memory: {}",
+                    LOG_PREFIX, id, err
+                );
+            }
+        }
+    }
+
     fn can_spawn_neurons(&self) -> bool {
         let spawning = self.proto.spawning_neurons;
         spawning.is_none() || !spawning.unwrap()
     }
 
-    /// Actually spawn neurons by minting their maturity, modulated by the maturity modulation rate of the day.
+    /// Actually spawn neurons by minting their maturity, modulated by the maturity modulation rate of the day,
+    /// and finalize the maturity disbursements that are due in the same way.
     /// There can only be one execution of this method running at a time to keep the reasoning about this simple.
     /// This means that programming in this method needs to be extra-defensive on the handling of results so that
     /// we're sure not to trap after we've acquired the global lock and made an async call, as otherwise the global
@@ -6819,10 +8200,154 @@ impl Governance {
             }
         }
 
+        self.finalize_maturity_disbursements(now_seconds, maturity_modulation)
+            .await;
+
         // Release the global spawning lock
         self.proto.spawning_neurons = Some(false);
     }
 
+    /// Mints the oldest maturity disbursement of each neuron for which it is
+    /// due (see [Governance::disburse_maturity]), modulated by
+    /// `maturity_modulation`.
+    ///
+    /// This must only be called by `spawn_neurons`, while holding the global
+    /// "spawning" lock, and so must not trap either.
+    async fn finalize_maturity_disbursements(
+        &mut self,
+        now_seconds: u64,
+        maturity_modulation: i32,
+    ) {
+        let is_due = |disbursement: &MaturityDisbursement| {
+            disbursement.finalize_disbursement_timestamp_seconds <= now_seconds
+        };
+        let neuron_ids = self
+            .proto
+            .neurons
+            .values()
+            .filter(|neuron| {
+                neuron
+                    .maturity_disbursements_in_progress
+                    .first()
+                    .map_or(false, is_due)
+            })
+            .filter_map(|neuron| neuron.id.clone())
+            .collect::<Vec<NeuronId>>();
+
+        for id in neuron_ids {
+            let in_flight_command = NeuronInFlightCommand {
+                timestamp: now_seconds,
+                command: Some(InFlightCommand::FinalizeMaturityDisbursement(id.clone())),
+            };
+            let _lock = match self.lock_neuron_for_command(id.id, in_flight_command) {
+                Ok(lock) => lock,
+                Err(error) => {
+                    println!(
+                        "{}Tried to finalize a maturity disbursement of neuron {:?} but it \
+                         was already locked. Error: {:?}",
+                        LOG_PREFIX, id, error,
+                    );
+                    continue;
+                }
+            };
+
+            // Remove the disbursement from the neuron before minting it. This is
+            // conservative to prevent the maturity from being both pending and
+            // minted at any point in time.
+            let mut disbursement = match self.get_neuron_mut(&id) {
+                Ok(neuron) => {
+                    if !neuron
+                        .maturity_disbursements_in_progress
+                        .first()
+                        .map_or(false, is_due)
+                    {
+                        continue;
+                    }
+                    neuron.maturity_disbursements_in_progress.remove(0)
+                }
+                Err(_) => continue,
+            };
+
+            // The amount and the created_at_time of the transfer are fixed on the
+            // first attempt, so that the ledger deduplicates retries. This makes
+            // it safe to retry after any error, even one where the transfer
+            // might have gone through.
+            let amount_e8s = *disbursement.amount_to_mint_e8s.get_or_insert_with(|| {
+                // Since we're multiplying a potentially pretty big number by up to 10500, do
+                // the calculations as u128 before converting back.
+                let amount_e8s = (disbursement.amount_e8s as u128)
+                    .saturating_mul((10000 + maturity_modulation) as u128)
+                    / 10000;
+                u64::try_from(amount_e8s).unwrap_or(u64::MAX)
+            });
+            let created_at_time_seconds = *disbursement
+                .first_mint_attempt_timestamp_seconds
+                .get_or_insert(now_seconds);
+            let to_account = disbursement
+                .account_to_disburse_to
+                .as_ref()
+                .and_then(|account| AccountIdentifier::try_from(account).ok());
+
+            let result = match to_account {
+                Some(to_account) => {
+                    self.ledger
+                        .transfer_funds_deduplicated(
+                            amount_e8s,
+                            0, // Minting transfer don't pay a fee.
+                            None,
+                            to_account,
+                            id.id,
+                            TimeStamp::new(created_at_time_seconds, 0),
+                        )
+                        .await
+                }
+                None => Err(NervousSystemError::new_with_message(
+                    "The account to disburse the maturity to is invalid.",
+                )),
+            };
+
+            match result {
+                Ok(_) => println!(
+                    "{}Finalized the disbursement of {} e8s of maturity of neuron {:?}.",
+                    LOG_PREFIX, amount_e8s, id,
+                ),
+                Err(error) if ledger::is_transaction_too_old(&error) => {
+                    // The deduplication window of the ledger has passed since the
+                    // first attempt, so the transfer can neither be retried nor
+                    // recognized as a duplicate anymore. Every attempt within the
+                    // window failed (a retry after a successful one would have been
+                    // recognized as a duplicate), so nothing was minted. The
+                    // disbursement fails and its maturity is given back to the
+                    // neuron, from where it can be disbursed again.
+                    println!(
+                        "{}The maturity disbursement of neuron {:?} expired before it \
+                         could be minted. Returning {} e8s of maturity to the neuron. \
+                         Ledger update failed with err: {:?}.",
+                        LOG_PREFIX, id, disbursement.amount_e8s, error,
+                    );
+                    if let Ok(neuron) = self.get_neuron_mut(&id) {
+                        neuron.maturity_e8s_equivalent = neuron
+                            .maturity_e8s_equivalent
+                            .saturating_add(disbursement.amount_e8s);
+                    }
+                }
+                Err(error) => {
+                    println!(
+                        "{}Error finalizing a maturity disbursement of neuron {:?}. \
+                         Ledger update failed with err: {:?}.",
+                        LOG_PREFIX, id, error,
+                    );
+                    // Put the disbursement back, so that it is retried later.
+                    if let Ok(neuron) = self.get_neuron_mut(&id) {
+                        neuron
+                            .maturity_disbursements_in_progress
+                            .insert(0, disbursement);
+                    }
+                }
+            }
+        }
+    }
+
     /// Return `true` if rewards should be distributed, `false` otherwise
     fn should_distribute_rewards(&self) -> bool {
         let reward_available_at = self.proto.genesis_timestamp_seconds
@@ -6902,32 +8427,44 @@ impl Governance {
         };
 
         for (neuron_id, used_voting_rights) in voters_to_used_voting_right {
-            match self.get_neuron_mut(&neuron_id) {
-                Ok(mut neuron) => {
-                    // Note that "as" rounds toward zero; this is the desired
-                    // behavior here. Also note that `total_voting_rights` has
-                    // to be positive because (1) voters_to_used_voting_right
-                    // is non-empty (otherwise we wouldn't be here in the
-                    // first place) and (2) the voting power of all ballots is
-                    // positive (non-zero).
-                    let reward = (used_voting_rights * distributed_e8s_equivalent_float
-                        / total_voting_rights) as u64;
-                    // If the neuron has auto-stake-maturity on, add the new maturity to the
-                    // staked maturity, otherwise add it to the un-staked maturity.
-                    if neuron.auto_stake_maturity.unwrap_or(false) {
-                        neuron.staked_maturity_e8s_equivalent =
-                            Some(neuron.staked_maturity_e8s_equivalent.unwrap_or(0) + reward);
-                    } else {
-                        neuron.maturity_e8s_equivalent += reward;
-                    }
-                    actually_distributed_e8s_equivalent += reward;
+            // Note that "as" rounds toward zero; this is the desired
+            // behavior here. Also note that `total_voting_rights` has
+            // to be positive because (1) voters_to_used_voting_right
+            // is non-empty (otherwise we wouldn't be here in the
+            // first place) and (2) the voting power of all ballots is
+            // positive (non-zero).
+            let reward = (used_voting_rights * distributed_e8s_equivalent_float
+                / total_voting_rights) as u64;
+            // If the neuron has auto-stake-maturity on, add the new maturity to the
+            // staked maturity, otherwise add it to the un-staked maturity.
+            let add_reward = |neuron: &mut Neuron| {
+                if neuron.auto_stake_maturity.unwrap_or(false) {
+                    neuron.staked_maturity_e8s_equivalent =
+                        Some(neuron.staked_maturity_e8s_equivalent.unwrap_or(0) + reward);
+                } else {
+                    neuron.maturity_e8s_equivalent += reward;
+                }
+            };
+            // Neurons in stable memory receive their rewards in place.
+            let result = match self.get_neuron_mut(&neuron_id) {
+                Ok(neuron) => {
+                    add_reward(neuron);
+                    Ok(())
                 }
+                Err(e) => match neuron_store::update(neuron_id.id, add_reward) {
+                    Ok(Some(())) => Ok(()),
+                    Ok(None) => Err(format!("{:?}", e)),
+                    Err(err) => Err(err),
+                },
+            };
+            match result {
+                Ok(()) => actually_distributed_e8s_equivalent += reward,
                 Err(e) => println!(
                     "{}Cannot find neuron {}, despite having voted with power {} \
                         in the considered reward period. The reward that should have been \
                         distributed to this neuron is simply skipped, so the total amount \
                         of distributed reward for this period will be lower than the maximum \
-                        allowed. Underlying error: {:?}.",
+                        allowed. Underlying error: {}.",
                     LOG_PREFIX, neuron_id.id, used_voting_rights, e
                 ),
             }
@@ -7071,17 +8608,23 @@ impl Governance {
         };
 
         // Unpack proposal.
-        let open_sns_token_swap = match proposal_data
+        let target_swap_canister_id = match proposal_data
             .proposal
             .as_ref()
             .and_then(|p| p.action.as_ref())
         {
-            Some(Action::OpenSnsTokenSwap(open_sns_token_swap)) => open_sns_token_swap,
+            Some(Action::OpenSnsTokenSwap(open_sns_token_swap)) => {
+                open_sns_token_swap.target_swap_canister_id
+            }
+            Some(Action::CreateServiceNervousSystem(_)) => {
+                proposal_data.deployed_sns_swap_canister_id
+            }
             _ => {
                 return Err(GovernanceError::new_with_message(
                     ErrorType::NotFound,
                     format!(
-                        "Proposal {} is not of type OpenSnsTokenSwap. request = {:#?}",
+                        "Proposal {} is not of type OpenSnsTokenSwap or \
+                         CreateServiceNervousSystem. request = {:#?}",
                         proposal_id, request,
                     ),
                 ))
@@ -7089,13 +8632,13 @@ impl Governance {
         };
 
         // Check authorization.
-        if Some(caller) != open_sns_token_swap.target_swap_canister_id {
+        if Some(caller) != target_swap_canister_id {
             return Err(GovernanceError::new_with_message(
                 ErrorType::NotAuthorized,
                 format!(
                     "Caller was {}, but needs to be {:?}, the \
-                     target_swap_canister_id in the original proposal.",
-                    caller, open_sns_token_swap.target_swap_canister_id,
+                     swap canister targeted by the original proposal.",
+                    caller, target_swap_canister_id,
                 ),
             ));
         }
@@ -7510,6 +9053,77 @@ fn validate_motion(motion: &Motion) -> Result<(), GovernanceError> {
     Ok(())
 }
 
+/// Checks that changing the followees of a neuron from `old_followees` to
+/// `new_followees` does not make it follow too many neurons in total, which
+/// would allow a memory exhaustion attack on the governance canister.
+///
+/// The check only applies when the total grows, so that a neuron that
+/// already follows more than MAX_FOLLOWEES_PER_NEURON neurons can still
+/// change (and in particular reduce) its following.
+fn validate_num_followees(
+    old_followees: &HashMap<i32, Followees>,
+    new_followees: &HashMap<i32, Followees>,
+) -> Result<(), GovernanceError> {
+    let count = |followees: &HashMap<i32, Followees>| -> usize {
+        followees.values().map(|f| f.followees.len()).sum()
+    };
+    let num_followees = count(new_followees);
+    if num_followees <= MAX_FOLLOWEES_PER_NEURON || num_followees <= count(old_followees) {
+        return Ok(());
+    }
+
+    Err(GovernanceError::new_with_message(
+        ErrorType::InvalidCommand,
+        &format!(
+            "Too many followees: the neuron would follow {} neurons in total, \
+             but the maximum is {}.",
+            num_followees, MAX_FOLLOWEES_PER_NEURON
+        ),
+    ))
+}
+
+/// Rejects voting power economics that would take the voting power of
+/// neurons away (and clear their following) too soon or too abruptly. Unset
+/// fields are fine, as they leave the current values alone.
+fn validate_manage_network_economics(
+    network_economics: &NetworkEconomics,
+) -> Result<(), GovernanceError> {
+    let voting_power_economics = match &network_economics.voting_power_economics {
+        Some(voting_power_economics) => voting_power_economics,
+        None => return Ok(()),
+    };
+
+    let mut defects = vec![];
+    if let Some(seconds) = voting_power_economics.start_reducing_voting_power_after_seconds {
+        if seconds < MIN_START_REDUCING_VOTING_POWER_AFTER_SECONDS {
+            defects.push(format!(
+                "start_reducing_voting_power_after_seconds ({}) must be at least {}.",
+                seconds, MIN_START_REDUCING_VOTING_POWER_AFTER_SECONDS,
+            ));
+        }
+    }
+    if let Some(seconds) = voting_power_economics.clear_following_after_seconds {
+        if seconds < MIN_CLEAR_FOLLOWING_AFTER_SECONDS {
+            defects.push(format!(
+                "clear_following_after_seconds ({}) must be at least {}.",
+                seconds, MIN_CLEAR_FOLLOWING_AFTER_SECONDS,
+            ));
+        }
+    }
+
+    if defects.is_empty() {
+        return Ok(());
+    }
+
+    Err(GovernanceError::new_with_message(
+        ErrorType::InvalidProposal,
+        format!(
+            "ManageNetworkEconomics proposal has invalid voting_power_economics:\n{}",
+            defects.join("\n"),
+        ),
+    ))
+}
+
 /// Always fails, because this type of proposal is obsolete.
 fn validate_set_sns_token_swap_open_time_window(
     action: &SetSnsTokenSwapOpenTimeWindow,
@@ -7613,6 +9227,81 @@ async fn validate_open_sns_token_swap(
     Ok(())
 }
 
+fn validate_create_service_nervous_system(
+    create_service_nervous_system: &CreateServiceNervousSystem,
+) -> Result<(), GovernanceError> {
+    let mut defects = vec![];
+
+    // Inspect sns_init_payload.
+    match &create_service_nervous_system.sns_init_payload {
+        None => defects.push(
+            "CreateServiceNervousSystem lacks a value in its sns_init_payload field.".to_string(),
+        ),
+
+        Some(sns_init_payload) => {
+            if let Err(err) = sns_init_payload.validate() {
+                defects.push(format!(
+                    "CreateServiceNervousSystem.sns_init_payload is invalid: {}.",
+                    err
+                ));
+            }
+        }
+    }
+
+    // Inspect swap_params.
+    match &create_service_nervous_system.swap_params {
+        None => defects
+            .push("CreateServiceNervousSystem lacks a value in its swap_params field.".to_string()),
+
+        Some(params) => {
+            if let Err(err) = params.validate() {
+                defects.push(format!(
+                    "CreateServiceNervousSystem.swap_params is invalid: {:?}.",
+                    err
+                ));
+            }
+
+            // community_fund_investment_e8s must be less than max_icp_e8s.
+            if let Some(community_fund_investment_e8s) =
+                create_service_nervous_system.community_fund_investment_e8s
+            {
+                if community_fund_investment_e8s > params.max_icp_e8s {
+                    defects.push(format!(
+                        "community_fund_investment_e8s ({}) > swap_params.max_icp_e8s ({}).",
+                        community_fund_investment_e8s, params.max_icp_e8s,
+                    ));
+                }
+            }
+        }
+    }
+
+    // Inspect dapp_canisters.
+    let mut seen_dapp_canisters = HashSet::new();
+    for dapp_canister_id in &create_service_nervous_system.dapp_canisters {
+        if let Err(err) = CanisterId::try_from(*dapp_canister_id) {
+            defects.push(format!(
+                "CreateServiceNervousSystem.dapp_canisters contains an invalid canister ID: {:?}",
+                err
+            ));
+        }
+        if !seen_dapp_canisters.insert(dapp_canister_id) {
+            defects.push(format!(
+                "CreateServiceNervousSystem.dapp_canisters contains {} more than once.",
+                dapp_canister_id
+            ));
+        }
+    }
+
+    // Construct final result.
+    if !defects.is_empty() {
+        return Err(GovernanceError::new_with_message(
+            ErrorType::InvalidProposal,
+            defects.join("\n"),
+        ));
+    }
+    Ok(())
+}
+
 /// A helper for the Registry's get_node_providers_monthly_xdr_rewards method
 async fn get_node_providers_monthly_xdr_rewards(
 ) -> Result<NodeProvidersMonthlyXdrRewards, GovernanceError> {
@@ -7785,6 +9474,76 @@ mod tests {
         assert_eq!(w.apply(100_u64), 58);
     }
 
+    #[test]
+    fn test_adjust_voting_power() {
+        let economics = VotingPowerEconomics {
+            start_reducing_voting_power_after_seconds: Some(100),
+            clear_following_after_seconds: Some(10),
+        };
+
+        assert_eq!(economics.adjust_voting_power(1000, 0), 1000);
+        assert_eq!(economics.adjust_voting_power(1000, 100), 1000);
+        assert_eq!(economics.adjust_voting_power(1000, 101), 900);
+        assert_eq!(economics.adjust_voting_power(1000, 105), 500);
+        assert_eq!(economics.adjust_voting_power(1000, 109), 100);
+        assert_eq!(economics.adjust_voting_power(1000, 110), 0);
+        assert_eq!(economics.adjust_voting_power(1000, u64::MAX), 0);
+
+        assert!(!economics.is_voting_power_depleted(109));
+        assert!(economics.is_voting_power_depleted(110));
+    }
+
+    #[test]
+    fn test_validate_manage_network_economics() {
+        let with_voting_power_economics = |start_reducing, clear_following| NetworkEconomics {
+            voting_power_economics: Some(VotingPowerEconomics {
+                start_reducing_voting_power_after_seconds: start_reducing,
+                clear_following_after_seconds: clear_following,
+            }),
+            ..Default::default()
+        };
+
+        // Leaving voting power economics alone is fine.
+        assert_eq!(
+            validate_manage_network_economics(&NetworkEconomics::default()),
+            Ok(())
+        );
+        assert_eq!(
+            validate_manage_network_economics(&with_voting_power_economics(None, None)),
+            Ok(())
+        );
+        assert_eq!(
+            validate_manage_network_economics(&with_voting_power_economics(
+                Some(MIN_START_REDUCING_VOTING_POWER_AFTER_SECONDS),
+                Some(MIN_CLEAR_FOLLOWING_AFTER_SECONDS),
+            )),
+            Ok(())
+        );
+
+        for (start_reducing, clear_following) in [
+            (Some(0), None),
+            (None, Some(0)),
+            (
+                Some(MIN_START_REDUCING_VOTING_POWER_AFTER_SECONDS - 1),
+                None,
+            ),
+            (None, Some(MIN_CLEAR_FOLLOWING_AFTER_SECONDS - 1)),
+            (Some(ONE_DAY_SECONDS), Some(ONE_MONTH_SECONDS)),
+        ] {
+            let err = validate_manage_network_economics(&with_voting_power_economics(
+                start_reducing,
+                clear_following,
+            ))
+            .unwrap_err();
+            assert_eq!(
+                err.error_type,
+                ErrorType::InvalidProposal as i32,
+                "{:?}",
+                err
+            );
+        }
+    }
+
     const PARAMS: sns_swap_pb::Params = sns_swap_pb::Params {
         max_icp_e8s: 1000,
         min_icp_e8s: 10,
@@ -0,0 +1,814 @@
+//! Neurons stored in stable memory.
+//!
+//! Governance keeps the neurons it is working with in the heap
+//! (`GovernanceProto::neurons`). All other neurons are moved in batches to the
+//! stable maps defined in this module (see
+//! `Governance::move_neurons_to_stable_memory`), so that they no longer need
+//! to be serialized on upgrades. Neurons in stable memory still get ballots,
+//! vote by following and receive voting rewards in place; a neuron is moved
+//! back to the heap as soon as it is managed again.
+//!
+//! A neuron in stable memory is split across several maps: the neuron without
+//! its followees, recent ballots and known neuron data, one entry per topic the
+//! neuron follows, one entry per recent ballot, and one entry in the known
+//! neuron index if the neuron is a known neuron. Secondary indices map
+//! controllers, hot keys and subaccounts to the IDs of the neurons in stable
+//! memory, and another one holds the IDs of the public neurons in stable
+//! memory.
+//!
+//! The rest of the governance state is serialized to a dedicated virtual
+//! memory on upgrades, see [write_upgrades_memory] and [read_upgrades_memory].
+use crate::pb::v1::{
+    neuron::Followees, BallotInfo, Governance as GovernanceProto, KnownNeuronData, Neuron,
+    Visibility,
+};
+use bytes::buf::UninitSlice;
+use bytes::BufMut;
+use ic_base_types::PrincipalId;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, Memory, StableBTreeMap};
+use icp_ledger::Subaccount;
+use prost::Message;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+const WASM_PAGE_SIZE: u64 = 65536;
+
+/// The magic bytes the memory manager writes at the beginning of the stable
+/// memory.
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+
+const UPGRADES_MEMORY_ID: MemoryId = MemoryId::new(0);
+const NEURONS_MEMORY_ID: MemoryId = MemoryId::new(1);
+const FOLLOWEES_MEMORY_ID: MemoryId = MemoryId::new(2);
+const RECENT_BALLOTS_MEMORY_ID: MemoryId = MemoryId::new(3);
+const KNOWN_NEURONS_MEMORY_ID: MemoryId = MemoryId::new(4);
+const CONTROLLER_INDEX_MEMORY_ID: MemoryId = MemoryId::new(5);
+const HOT_KEY_INDEX_MEMORY_ID: MemoryId = MemoryId::new(6);
+const SUBACCOUNT_INDEX_MEMORY_ID: MemoryId = MemoryId::new(7);
+const PUBLIC_NEURON_INDEX_MEMORY_ID: MemoryId = MemoryId::new(8);
+
+/// The size of the encoding of a neuron ID.
+const NEURON_ID_SIZE: u32 = 8;
+/// The maximum size of an encoded neuron without its followees, recent
+/// ballots and known neuron data.
+const MAX_NEURON_SIZE: u32 = 2048;
+/// The maximum size of the encoded followees of a neuron on one topic.
+const MAX_FOLLOWEES_SIZE: u32 = 512;
+/// The maximum size of an encoded ballot.
+const MAX_BALLOT_INFO_SIZE: u32 = 64;
+/// The maximum size of encoded known neuron data.
+const MAX_KNOWN_NEURON_DATA_SIZE: u32 = 4096;
+/// The maximum size of the length-prefixed bytes of a principal.
+const MAX_PRINCIPAL_SIZE: u32 = 1 + PrincipalId::MAX_LENGTH_IN_BYTES as u32;
+/// The size of a subaccount.
+const SUBACCOUNT_SIZE: u32 = 32;
+
+type VMem = VirtualMemory<DefaultMemoryImpl>;
+type StableMap = StableBTreeMap<VMem, Vec<u8>, Vec<u8>>;
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    /// Neurons without their followees, recent ballots and known neuron data
+    /// indexed by the big-endian neuron ID.
+    static NEURONS: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(NEURONS_MEMORY_ID),
+        NEURON_ID_SIZE,
+        MAX_NEURON_SIZE,
+    ));
+
+    /// Followees indexed by the neuron ID followed by the big-endian topic.
+    static FOLLOWEES: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(FOLLOWEES_MEMORY_ID),
+        NEURON_ID_SIZE + 4,
+        MAX_FOLLOWEES_SIZE,
+    ));
+
+    /// Recent ballots indexed by the neuron ID followed by the big-endian
+    /// position of the ballot in `Neuron::recent_ballots`.
+    static RECENT_BALLOTS: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(RECENT_BALLOTS_MEMORY_ID),
+        NEURON_ID_SIZE + 4,
+        MAX_BALLOT_INFO_SIZE,
+    ));
+
+    /// Known neuron data indexed by the neuron ID.
+    static KNOWN_NEURONS: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(KNOWN_NEURONS_MEMORY_ID),
+        NEURON_ID_SIZE,
+        MAX_KNOWN_NEURON_DATA_SIZE,
+    ));
+
+    /// The set of (controller, neuron ID) pairs.
+    static CONTROLLER_INDEX: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(CONTROLLER_INDEX_MEMORY_ID),
+        MAX_PRINCIPAL_SIZE + NEURON_ID_SIZE,
+        0,
+    ));
+
+    /// The set of (hot key, neuron ID) pairs.
+    static HOT_KEY_INDEX: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(HOT_KEY_INDEX_MEMORY_ID),
+        MAX_PRINCIPAL_SIZE + NEURON_ID_SIZE,
+        0,
+    ));
+
+    /// Neuron IDs indexed by the subaccount of the neuron.
+    static SUBACCOUNT_INDEX: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(SUBACCOUNT_INDEX_MEMORY_ID),
+        SUBACCOUNT_SIZE,
+        NEURON_ID_SIZE,
+    ));
+
+    /// The set of the IDs of the public neurons, see [Neuron::visibility].
+    static PUBLIC_NEURON_INDEX: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(PUBLIC_NEURON_INDEX_MEMORY_ID),
+        NEURON_ID_SIZE,
+        0,
+    ));
+}
+
+fn memory(id: MemoryId) -> VMem {
+    MEMORY_MANAGER.with(|m| m.borrow().get(id))
+}
+
+/// Returns true if the stable memory contains the state written by a
+/// governance version that serialized its whole state to the raw stable
+/// memory.
+///
+/// This function must be called before any stable structure is accessed: the
+/// memory manager claims the stable memory on initialization.
+pub fn is_legacy_layout() -> bool {
+    let memory = DefaultMemoryImpl::default();
+    if memory.size() == 0 {
+        return false;
+    }
+    let mut magic = [0u8; 3];
+    memory.read(0, &mut magic);
+    &magic != MEMORY_MANAGER_MAGIC
+}
+
+/// The size of the buffer used to write the governance state to the upgrades
+/// memory.
+const UPGRADES_BUFFER_SIZE: usize = 10 * 1024 * 1024; // 10MiB
+
+/// Writes the governance state to the upgrades memory, prefixed by its
+/// length.
+///
+/// The state is encoded in chunks of `UPGRADES_BUFFER_SIZE` bytes, so that
+/// the encoded state never needs to fit into the heap at once.
+pub fn write_upgrades_memory(proto: &GovernanceProto) -> Result<(), String> {
+    let mut writer = UpgradesMemoryWriter::new(memory(UPGRADES_MEMORY_ID));
+    proto
+        .encode(&mut writer)
+        .map_err(|err| format!("Failed to encode the governance state: {}", err))?;
+    writer.finish()
+}
+
+/// An implementation of `BufMut` that writes to the upgrades memory in
+/// chunks, after the 8 bytes reserved for the length of the content.
+struct UpgradesMemoryWriter {
+    /// In-memory buffer
+    buffer: Vec<u8>,
+
+    /// Current offset in `buffer`, in bytes
+    buffer_offset: usize,
+
+    /// The offset in the upgrades memory that the buffer is written to next.
+    memory_offset: u64,
+
+    memory: VMem,
+
+    /// The first error encountered while writing to the memory. Once set, the
+    /// remaining content is discarded.
+    error: Option<String>,
+}
+
+impl UpgradesMemoryWriter {
+    fn new(memory: VMem) -> Self {
+        Self {
+            buffer: vec![0; UPGRADES_BUFFER_SIZE],
+            buffer_offset: 0,
+            memory_offset: 8,
+            memory,
+            error: None,
+        }
+    }
+
+    /// Writes the buffer contents to the upgrades memory.
+    fn flush(&mut self) {
+        if self.error.is_none() {
+            let bytes = &self.buffer[0..self.buffer_offset];
+            match write_memory(&self.memory, self.memory_offset, bytes) {
+                Ok(()) => self.memory_offset += bytes.len() as u64,
+                Err(err) => self.error = Some(err),
+            }
+        }
+        self.buffer_offset = 0;
+    }
+
+    /// Flushes the buffer and writes the length of the content.
+    fn finish(mut self) -> Result<(), String> {
+        self.flush();
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        let len = self.memory_offset - 8;
+        write_memory(&self.memory, 0, &len.to_le_bytes())
+    }
+}
+
+unsafe impl BufMut for UpgradesMemoryWriter {
+    fn remaining_mut(&self) -> usize {
+        usize::MAX - self.buffer.len()
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        let new_len = self.buffer_offset + cnt;
+        assert!(
+            new_len <= self.buffer.len(),
+            "new_len = {}; capacity = {}",
+            new_len,
+            self.buffer.len(),
+        );
+        self.buffer_offset = new_len;
+    }
+
+    fn chunk_mut(&mut self) -> &mut UninitSlice {
+        if self.buffer_offset == self.buffer.len() {
+            self.flush();
+        }
+
+        let len = self.buffer_offset;
+        let cap = self.buffer.len();
+        let ptr = self.buffer.as_mut_ptr();
+
+        unsafe { &mut UninitSlice::from_raw_parts_mut(ptr, cap)[len..] }
+    }
+}
+
+/// Writes `bytes` at `offset`, growing the memory if needed.
+fn write_memory(memory: &VMem, offset: u64, bytes: &[u8]) -> Result<(), String> {
+    let required_size = offset + bytes.len() as u64;
+    let capacity = memory.size() * WASM_PAGE_SIZE;
+    if required_size > capacity {
+        let additional_pages = (required_size - capacity + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+        if memory.grow(additional_pages) == -1 {
+            return Err(format!(
+                "Failed to grow the upgrades memory by {} pages",
+                additional_pages
+            ));
+        }
+    }
+    memory.write(offset, bytes);
+    Ok(())
+}
+
+/// Reads the governance state written by [write_upgrades_memory].
+pub fn read_upgrades_memory() -> Result<GovernanceProto, prost::DecodeError> {
+    let memory = memory(UPGRADES_MEMORY_ID);
+    let mut len_bytes = [0u8; 8];
+    memory.read(0, &mut len_bytes);
+    let mut bytes = vec![0u8; u64::from_le_bytes(len_bytes) as usize];
+    memory.read(8, &mut bytes);
+    GovernanceProto::decode(&bytes[..])
+}
+
+fn neuron_key(id: u64) -> Vec<u8> {
+    id.to_be_bytes().to_vec()
+}
+
+fn neuron_entry_key(id: u64, position: u32) -> Vec<u8> {
+    let mut key = neuron_key(id);
+    key.extend_from_slice(&position.to_be_bytes());
+    key
+}
+
+fn principal_prefix(principal: &PrincipalId) -> Vec<u8> {
+    let bytes = principal.as_slice();
+    let mut prefix = Vec::with_capacity(1 + bytes.len());
+    prefix.push(bytes.len() as u8);
+    prefix.extend_from_slice(bytes);
+    prefix
+}
+
+fn principal_index_key(principal: &PrincipalId, id: u64) -> Vec<u8> {
+    let mut key = principal_prefix(principal);
+    key.extend_from_slice(&id.to_be_bytes());
+    key
+}
+
+fn decode_neuron_id(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes.try_into().expect("BUG: invalid neuron ID encoding"))
+}
+
+fn neuron_subaccount(neuron: &Neuron) -> Option<Vec<u8>> {
+    Subaccount::try_from(&neuron.account[..])
+        .ok()
+        .map(|subaccount| subaccount.to_vec())
+}
+
+/// Returns the IDs of the neurons in `map` whose index key starts with
+/// `prefix`.
+fn ids_with_prefix(map: &StableMap, prefix: Vec<u8>) -> Vec<u64> {
+    let prefix_len = prefix.len();
+    map.range(prefix, None)
+        .map(|(key, _)| decode_neuron_id(&key[prefix_len..]))
+        .collect()
+}
+
+/// Returns the values of the entries of `map` that belong to the neuron
+/// `id`.
+fn neuron_entries(map: &StableMap, id: u64) -> Vec<(Vec<u8>, Vec<u8>)> {
+    map.range(neuron_key(id), None).collect()
+}
+
+/// The parts a neuron is split into in stable memory.
+struct NeuronParts {
+    id: u64,
+    neuron: Vec<u8>,
+    followees: Vec<(Vec<u8>, Vec<u8>)>,
+    recent_ballots: Vec<(Vec<u8>, Vec<u8>)>,
+    known_neuron_data: Option<Vec<u8>>,
+}
+
+impl NeuronParts {
+    /// Splits and encodes the neuron, checking that each part fits into its
+    /// stable map.
+    fn new(neuron: &Neuron) -> Result<Self, String> {
+        let id = neuron
+            .id
+            .as_ref()
+            .ok_or_else(|| "Neuron must have an id".to_string())?
+            .id;
+        let check_size = |what: &str, bytes: Vec<u8>, max_size: u32| {
+            if bytes.len() > max_size as usize {
+                Err(format!(
+                    "The {} of neuron {} take {} bytes, more than the maximum of {}",
+                    what,
+                    id,
+                    bytes.len(),
+                    max_size
+                ))
+            } else {
+                Ok(bytes)
+            }
+        };
+
+        let followees: Vec<(Vec<u8>, Vec<u8>)> = neuron
+            .followees
+            .iter()
+            .map(|(topic, followees)| -> Result<_, String> {
+                Ok((
+                    neuron_entry_key(id, *topic as u32),
+                    check_size("followees", followees.encode_to_vec(), MAX_FOLLOWEES_SIZE)?,
+                ))
+            })
+            .collect::<Result<_, String>>()?;
+        let recent_ballots: Vec<(Vec<u8>, Vec<u8>)> = neuron
+            .recent_ballots
+            .iter()
+            .enumerate()
+            .map(|(position, ballot)| -> Result<_, String> {
+                Ok((
+                    neuron_entry_key(id, position as u32),
+                    check_size("ballots", ballot.encode_to_vec(), MAX_BALLOT_INFO_SIZE)?,
+                ))
+            })
+            .collect::<Result<_, String>>()?;
+        let known_neuron_data = neuron
+            .known_neuron_data
+            .as_ref()
+            .map(|data| {
+                check_size(
+                    "known neuron data",
+                    data.encode_to_vec(),
+                    MAX_KNOWN_NEURON_DATA_SIZE,
+                )
+            })
+            .transpose()?;
+        let main = Neuron {
+            followees: Default::default(),
+            recent_ballots: vec![],
+            known_neuron_data: None,
+            ..neuron.clone()
+        };
+
+        Ok(Self {
+            id,
+            neuron: check_size("fields", main.encode_to_vec(), MAX_NEURON_SIZE)?,
+            followees,
+            recent_ballots,
+            known_neuron_data,
+        })
+    }
+}
+
+/// Returns an error if the neuron cannot be stored in stable memory.
+pub(crate) fn check_fits(neuron: &Neuron) -> Result<(), String> {
+    NeuronParts::new(neuron).map(|_| ())
+}
+
+/// Stores a neuron in stable memory, replacing any neuron with the same ID.
+///
+/// Nothing is stored if the neuron does not have an ID or if one of its parts
+/// does not fit into the stable maps.
+pub(crate) fn insert(neuron: &Neuron) -> Result<(), String> {
+    let parts = NeuronParts::new(neuron)?;
+    let id = parts.id;
+    remove(id);
+
+    let result = insert_parts(parts, neuron);
+    if result.is_err() {
+        remove_entries(id, neuron);
+    }
+    result
+}
+
+fn insert_parts(parts: NeuronParts, neuron: &Neuron) -> Result<(), String> {
+    let id = parts.id;
+    let insert_entry = |map: &RefCell<StableMap>, key: Vec<u8>, value: Vec<u8>| {
+        map.borrow_mut()
+            .insert(key, value)
+            .map(|_| ())
+            .map_err(|err| format!("Failed to store neuron {}: {:?}", id, err))
+    };
+
+    for (key, value) in parts.followees {
+        FOLLOWEES.with(|map| insert_entry(map, key, value))?;
+    }
+    for (key, value) in parts.recent_ballots {
+        RECENT_BALLOTS.with(|map| insert_entry(map, key, value))?;
+    }
+    if let Some(value) = parts.known_neuron_data {
+        KNOWN_NEURONS.with(|map| insert_entry(map, neuron_key(id), value))?;
+    }
+    if let Some(controller) = neuron.controller.as_ref() {
+        CONTROLLER_INDEX
+            .with(|map| insert_entry(map, principal_index_key(controller, id), vec![]))?;
+    }
+    for hot_key in neuron.hot_keys.iter() {
+        HOT_KEY_INDEX.with(|map| insert_entry(map, principal_index_key(hot_key, id), vec![]))?;
+    }
+    if let Some(subaccount) = neuron_subaccount(neuron) {
+        SUBACCOUNT_INDEX.with(|map| insert_entry(map, subaccount, neuron_key(id)))?;
+    }
+    if neuron.visibility() == Visibility::Public {
+        PUBLIC_NEURON_INDEX.with(|map| insert_entry(map, neuron_key(id), vec![]))?;
+    }
+    NEURONS.with(|map| insert_entry(map, neuron_key(id), parts.neuron))
+}
+
+/// Returns the neuron with the given ID if it is stored in stable memory.
+pub(crate) fn get(id: u64) -> Option<Neuron> {
+    let mut neuron = NEURONS.with(|map| {
+        map.borrow()
+            .get(&neuron_key(id))
+            .map(|bytes| Neuron::decode(&bytes[..]).expect("failed to decode a stable neuron"))
+    })?;
+
+    neuron.followees = FOLLOWEES.with(|map| {
+        neuron_entries(&map.borrow(), id)
+            .into_iter()
+            .map(|(key, value)| {
+                let topic = u32::from_be_bytes(key[8..].try_into().unwrap()) as i32;
+                let followees =
+                    Followees::decode(&value[..]).expect("failed to decode stable followees");
+                (topic, followees)
+            })
+            .collect()
+    });
+    neuron.recent_ballots = RECENT_BALLOTS.with(|map| {
+        neuron_entries(&map.borrow(), id)
+            .into_iter()
+            .map(|(_, value)| {
+                BallotInfo::decode(&value[..]).expect("failed to decode a stable ballot")
+            })
+            .collect()
+    });
+    neuron.known_neuron_data = KNOWN_NEURONS.with(|map| {
+        map.borrow().get(&neuron_key(id)).map(|bytes| {
+            KnownNeuronData::decode(&bytes[..]).expect("failed to decode known neuron data")
+        })
+    });
+
+    Some(neuron)
+}
+
+/// Removes the neuron with the given ID from stable memory and returns it.
+pub(crate) fn remove(id: u64) -> Option<Neuron> {
+    let neuron = get(id)?;
+    remove_entries(id, &neuron);
+    Some(neuron)
+}
+
+/// Removes all the entries of the neuron from the stable maps, including the
+/// partial entries of an insertion that failed.
+fn remove_entries(id: u64, neuron: &Neuron) {
+    NEURONS.with(|map| map.borrow_mut().remove(&neuron_key(id)));
+    FOLLOWEES.with(|map| {
+        let mut map = map.borrow_mut();
+        for (key, _) in neuron_entries(&map, id) {
+            map.remove(&key);
+        }
+    });
+    RECENT_BALLOTS.with(|map| {
+        let mut map = map.borrow_mut();
+        for (key, _) in neuron_entries(&map, id) {
+            map.remove(&key);
+        }
+    });
+    KNOWN_NEURONS.with(|map| map.borrow_mut().remove(&neuron_key(id)));
+    if let Some(controller) = neuron.controller.as_ref() {
+        CONTROLLER_INDEX.with(|map| {
+            map.borrow_mut()
+                .remove(&principal_index_key(controller, id))
+        });
+    }
+    HOT_KEY_INDEX.with(|map| {
+        let mut map = map.borrow_mut();
+        for hot_key in neuron.hot_keys.iter() {
+            map.remove(&principal_index_key(hot_key, id));
+        }
+    });
+    if let Some(subaccount) = neuron_subaccount(neuron) {
+        SUBACCOUNT_INDEX.with(|map| {
+            let mut map = map.borrow_mut();
+            // Only remove the index entry if it points to this neuron.
+            if map.get(&subaccount) == Some(neuron_key(id)) {
+                map.remove(&subaccount);
+            }
+        });
+    }
+    PUBLIC_NEURON_INDEX.with(|map| map.borrow_mut().remove(&neuron_key(id)));
+}
+
+/// Applies `f` to the neuron with the given ID in stable memory and stores
+/// the result. Returns `Ok(None)` if there is no such neuron.
+///
+/// If the modified neuron cannot be stored, the neuron is left unchanged.
+pub(crate) fn update<R>(id: u64, f: impl FnOnce(&mut Neuron) -> R) -> Result<Option<R>, String> {
+    let original = match get(id) {
+        Some(neuron) => neuron,
+        None => return Ok(None),
+    };
+    let mut neuron = original.clone();
+    let result = f(&mut neuron);
+    if let Err(err) = insert(&neuron) {
+        insert(&original)?;
+        return Err(err);
+    }
+    Ok(Some(result))
+}
+
+/// Returns true if the neuron with the given ID is stored in stable memory.
+pub(crate) fn contains(id: u64) -> bool {
+    NEURONS.with(|map| map.borrow().contains_key(&neuron_key(id)))
+}
+
+/// Returns the number of neurons stored in stable memory.
+pub(crate) fn len() -> u64 {
+    NEURONS.with(|map| map.borrow().len())
+}
+
+/// Returns the IDs of the neurons in stable memory that have `principal` as
+/// their controller or as one of their hot keys.
+pub(crate) fn neuron_ids_by_principal(principal: &PrincipalId) -> BTreeSet<u64> {
+    let prefix = principal_prefix(principal);
+    let controlled = CONTROLLER_INDEX.with(|map| ids_with_prefix(&map.borrow(), prefix.clone()));
+    let hot_key_of = HOT_KEY_INDEX.with(|map| ids_with_prefix(&map.borrow(), prefix));
+    controlled.into_iter().chain(hot_key_of).collect()
+}
+
+/// Returns the ID of the neuron in stable memory with the given subaccount.
+pub(crate) fn neuron_id_by_subaccount(subaccount: &Subaccount) -> Option<u64> {
+    SUBACCOUNT_INDEX.with(|map| {
+        map.borrow()
+            .get(&subaccount.to_vec())
+            .map(|bytes| decode_neuron_id(&bytes))
+    })
+}
+
+/// Returns the IDs of the public neurons in stable memory, in ascending order.
+pub(crate) fn public_neuron_ids() -> Vec<u64> {
+    PUBLIC_NEURON_INDEX.with(|map| {
+        map.borrow()
+            .iter()
+            .map(|(key, _)| decode_neuron_id(&key))
+            .collect()
+    })
+}
+
+/// Calls `f` with each neuron stored in stable memory, without its
+/// followees, recent ballots and known neuron data.
+///
+/// `f` must not access the neurons in stable memory.
+pub(crate) fn for_each_neuron_without_details(mut f: impl FnMut(Neuron)) {
+    NEURONS.with(|map| {
+        for (_, bytes) in map.borrow().iter() {
+            f(Neuron::decode(&bytes[..]).expect("failed to decode a stable neuron"));
+        }
+    });
+}
+
+/// Calls `f` with the ID, the topic and the followees of each entry in the
+/// followees of the neurons stored in stable memory.
+pub(crate) fn for_each_followees(mut f: impl FnMut(u64, i32, Followees)) {
+    FOLLOWEES.with(|map| {
+        for (key, value) in map.borrow().iter() {
+            let topic = u32::from_be_bytes(key[8..].try_into().unwrap()) as i32;
+            f(
+                decode_neuron_id(&key[..8]),
+                topic,
+                Followees::decode(&value[..]).expect("failed to decode stable followees"),
+            );
+        }
+    });
+}
+
+/// Returns the known neurons stored in stable memory.
+pub(crate) fn known_neurons() -> Vec<(u64, KnownNeuronData)> {
+    KNOWN_NEURONS.with(|map| {
+        map.borrow()
+            .iter()
+            .map(|(key, value)| {
+                (
+                    decode_neuron_id(&key),
+                    KnownNeuronData::decode(&value[..])
+                        .expect("failed to decode known neuron data"),
+                )
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::v1::Vote;
+    use ic_nns_common::pb::v1::{NeuronId, ProposalId};
+
+    fn neuron(id: u64) -> Neuron {
+        let mut account = vec![0u8; 32];
+        account[..8].copy_from_slice(&id.to_be_bytes());
+        Neuron {
+            id: Some(NeuronId { id }),
+            account,
+            controller: Some(PrincipalId::new_user_test_id(id)),
+            hot_keys: vec![PrincipalId::new_user_test_id(1000 + id)],
+            created_timestamp_seconds: 1,
+            followees: [(
+                3,
+                Followees {
+                    followees: vec![NeuronId { id: 42 }],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            recent_ballots: vec![
+                BallotInfo {
+                    proposal_id: Some(ProposalId { id: 2 }),
+                    vote: Vote::Yes as i32,
+                },
+                BallotInfo {
+                    proposal_id: Some(ProposalId { id: 1 }),
+                    vote: Vote::No as i32,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut known = neuron(2);
+        known.known_neuron_data = Some(KnownNeuronData {
+            name: "known".to_string(),
+            description: None,
+        });
+        insert(&neuron(1)).unwrap();
+        insert(&known).unwrap();
+
+        assert_eq!(len(), 2);
+        assert!(contains(1));
+        assert_eq!(get(1), Some(neuron(1)));
+        assert_eq!(get(2), Some(known.clone()));
+        assert_eq!(get(3), None);
+        assert_eq!(
+            known_neurons(),
+            vec![(2, known.known_neuron_data.clone().unwrap())]
+        );
+
+        assert_eq!(remove(2), Some(known));
+        assert_eq!(len(), 1);
+        assert!(!contains(2));
+        assert!(known_neurons().is_empty());
+        assert_eq!(remove(2), None);
+    }
+
+    #[test]
+    fn test_secondary_indices() {
+        let mut both = neuron(3);
+        both.hot_keys.push(PrincipalId::new_user_test_id(1));
+        insert(&neuron(1)).unwrap();
+        insert(&both).unwrap();
+
+        assert_eq!(
+            neuron_ids_by_principal(&PrincipalId::new_user_test_id(1)),
+            vec![1, 3].into_iter().collect()
+        );
+        assert_eq!(
+            neuron_ids_by_principal(&PrincipalId::new_user_test_id(1003)),
+            vec![3].into_iter().collect()
+        );
+        let subaccount = Subaccount::try_from(&neuron(3).account[..]).unwrap();
+        assert_eq!(neuron_id_by_subaccount(&subaccount), Some(3));
+
+        remove(3);
+        assert_eq!(
+            neuron_ids_by_principal(&PrincipalId::new_user_test_id(1)),
+            vec![1].into_iter().collect()
+        );
+        assert!(neuron_ids_by_principal(&PrincipalId::new_user_test_id(1003)).is_empty());
+        assert_eq!(neuron_id_by_subaccount(&subaccount), None);
+    }
+
+    #[test]
+    fn test_public_neuron_index() {
+        let mut public = neuron(1);
+        public.visibility = Some(Visibility::Public as i32);
+        let mut known = neuron(2);
+        known.known_neuron_data = Some(KnownNeuronData {
+            name: "known".to_string(),
+            description: None,
+        });
+        insert(&public).unwrap();
+        insert(&known).unwrap();
+        insert(&neuron(3)).unwrap();
+        assert_eq!(public_neuron_ids(), vec![1, 2]);
+
+        update(1, |neuron| {
+            neuron.visibility = Some(Visibility::Private as i32)
+        })
+        .unwrap();
+        update(3, |neuron| {
+            neuron.visibility = Some(Visibility::Public as i32)
+        })
+        .unwrap();
+        assert_eq!(public_neuron_ids(), vec![2, 3]);
+
+        remove(2);
+        assert_eq!(public_neuron_ids(), vec![3]);
+    }
+
+    #[test]
+    fn test_update() {
+        insert(&neuron(1)).unwrap();
+
+        let result = update(1, |neuron| {
+            neuron.hot_keys = vec![PrincipalId::new_user_test_id(7)];
+            neuron.recent_ballots.truncate(1);
+            neuron.maturity_e8s_equivalent = 10;
+        });
+        assert_eq!(result, Ok(Some(())));
+        let updated = get(1).unwrap();
+        assert_eq!(updated.maturity_e8s_equivalent, 10);
+        assert_eq!(
+            updated.recent_ballots,
+            neuron(1).recent_ballots[..1].to_vec()
+        );
+        assert!(neuron_ids_by_principal(&PrincipalId::new_user_test_id(1001)).is_empty());
+        assert_eq!(
+            neuron_ids_by_principal(&PrincipalId::new_user_test_id(7)),
+            vec![1].into_iter().collect()
+        );
+        assert_eq!(update(2, |_| ()), Ok(None));
+    }
+
+    #[test]
+    fn test_neuron_that_does_not_fit_is_not_stored() {
+        let mut big = neuron(1);
+        big.hot_keys = (0..100).map(PrincipalId::new_user_test_id).collect();
+        assert!(check_fits(&big).is_err());
+        assert!(insert(&big).is_err());
+        assert!(!contains(1));
+        assert!(neuron_ids_by_principal(&PrincipalId::new_user_test_id(1)).is_empty());
+
+        // A failed update leaves the neuron unchanged.
+        insert(&neuron(1)).unwrap();
+        assert!(update(1, |neuron| neuron.hot_keys = big.hot_keys.clone()).is_err());
+        assert_eq!(get(1), Some(neuron(1)));
+    }
+
+    #[test]
+    fn test_upgrades_memory_round_trip() {
+        let proto = GovernanceProto {
+            neurons: (1..=100).map(|id| (id, neuron(id))).collect(),
+            ..Default::default()
+        };
+        write_upgrades_memory(&proto).unwrap();
+        assert_eq!(read_upgrades_memory(), Ok(proto));
+    }
+}
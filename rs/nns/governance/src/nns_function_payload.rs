@@ -0,0 +1,338 @@
+//! Decoding and rendering of the payloads of ExecuteNnsFunction proposals.
+//!
+//! The payload of an ExecuteNnsFunction proposal is an opaque blob that
+//! governance passes on, as-is, to the method that implements the NNS
+//! function. To let voters know what they are voting on, the payload is decoded
+//! into the type expected by that method when the proposal is made, and a
+//! human-readable rendering of the result is recorded along with the proposal.
+//! Proposals whose payload does not decode are rejected at submission time.
+
+use crate::pb::v1::{ExecuteNnsFunction, NnsFunction};
+use candid::{CandidType, Decode, Deserialize};
+use cycles_minting_canister::{
+    ChangeSubnetTypeAssignmentArgs, SetAuthorizedSubnetworkListArgs, UpdateSubnetTypeArgs,
+};
+use ic_crypto_sha::Sha256;
+use ic_ic00_types::CanisterIdRecord;
+use ic_nervous_system_root::{
+    AddCanisterProposal, ChangeCanisterProposal, StopOrStartCanisterProposal,
+};
+use ic_nns_common::types::UpdateIcpXdrConversionRatePayload;
+use ic_protobuf::registry::{
+    dc::v1::AddOrRemoveDataCentersProposalPayload, node_operator::v1::RemoveNodeOperatorsPayload,
+    node_rewards::v2::UpdateNodeRewardsTableProposalPayload,
+};
+use ic_sns_wasm::pb::v1::{
+    AddWasmRequest, SnsCanisterType, UpdateAllowedPrincipalsRequest, UpdateSnsSubnetListRequest,
+};
+use registry_canister::mutations::{
+    complete_canister_migration::CompleteCanisterMigrationPayload,
+    do_add_node_operator::AddNodeOperatorPayload,
+    do_add_nodes_to_subnet::AddNodesToSubnetPayload,
+    do_bless_replica_version::BlessReplicaVersionPayload,
+    do_change_subnet_membership::ChangeSubnetMembershipPayload,
+    do_create_subnet::CreateSubnetPayload,
+    do_recover_subnet::RecoverSubnetPayload,
+    do_remove_nodes_from_subnet::RemoveNodesFromSubnetPayload,
+    do_retire_replica_version::RetireReplicaVersionPayload,
+    do_set_firewall_config::SetFirewallConfigPayload,
+    do_update_node_operator_config::UpdateNodeOperatorConfigPayload,
+    do_update_subnet::UpdateSubnetPayload,
+    do_update_subnet_replica::UpdateSubnetReplicaVersionPayload,
+    do_update_unassigned_nodes_config::UpdateUnassignedNodesConfigPayload,
+    firewall::{AddFirewallRulesPayload, RemoveFirewallRulesPayload, UpdateFirewallRulesPayload},
+    node_management::do_remove_nodes::RemoveNodesPayload,
+    prepare_canister_migration::PrepareCanisterMigrationPayload,
+    reroute_canister_ranges::RerouteCanisterRangesPayload,
+};
+use std::fmt;
+
+/// The payload of the upgrade_root method of the lifeline canister.
+///
+/// The "authoritative" data structure is the one defined in `lifeline.mo`.
+#[derive(CandidType, Deserialize)]
+pub struct UpgradeRootProposalPayload {
+    #[serde(with = "serde_bytes")]
+    pub wasm_module: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub module_arg: Vec<u8>,
+    pub stop_upgrade_start: bool,
+}
+
+impl fmt::Debug for UpgradeRootProposalPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpgradeRootProposalPayload")
+            .field("wasm_module_sha256", &sha256_hex(&self.wasm_module))
+            .field("module_arg_sha256", &sha256_hex(&self.module_arg))
+            .field("stop_upgrade_start", &self.stop_upgrade_start)
+            .finish()
+    }
+}
+
+/// Decodes the payload of execute_nns_function into the type that the target
+/// method of the NNS function expects, and returns a Markdown rendering of the
+/// decoded payload.
+///
+/// Large binary fields (e.g. wasm modules) are rendered by their SHA-256 hash.
+///
+/// Returns an error if the NNS function is not known, or if the payload does
+/// not decode.
+pub fn validate_and_render_execute_nns_function(
+    execute_nns_function: &ExecuteNnsFunction,
+) -> Result<String, String> {
+    let nns_function = NnsFunction::from_i32(execute_nns_function.nns_function)
+        .filter(|nns_function| *nns_function != NnsFunction::Unspecified)
+        .ok_or_else(|| {
+            format!(
+                "Unknown or unspecified NNS function: {}",
+                execute_nns_function.nns_function
+            )
+        })?;
+    let (canister_id, method_name) = nns_function
+        .canister_and_function()
+        .map_err(|err| err.to_string())?;
+
+    let payload = &execute_nns_function.payload;
+    let rendered_payload = match nns_function {
+        NnsFunction::Unspecified => unreachable!("Filtered out above."),
+        NnsFunction::CreateSubnet => render::<CreateSubnetPayload>(payload),
+        NnsFunction::AddNodeToSubnet => render::<AddNodesToSubnetPayload>(payload),
+        NnsFunction::NnsCanisterInstall => render::<AddCanisterProposal>(payload),
+        NnsFunction::NnsCanisterUpgrade => render::<ChangeCanisterProposal>(payload),
+        NnsFunction::BlessReplicaVersion => render::<BlessReplicaVersionPayload>(payload),
+        NnsFunction::RecoverSubnet => render::<RecoverSubnetPayload>(payload),
+        NnsFunction::UpdateConfigOfSubnet => render::<UpdateSubnetPayload>(payload),
+        NnsFunction::AssignNoid => render::<AddNodeOperatorPayload>(payload),
+        NnsFunction::NnsRootUpgrade => render::<UpgradeRootProposalPayload>(payload),
+        NnsFunction::IcpXdrConversionRate => render::<UpdateIcpXdrConversionRatePayload>(payload),
+        NnsFunction::UpdateSubnetReplicaVersion => {
+            render::<UpdateSubnetReplicaVersionPayload>(payload)
+        }
+        NnsFunction::ClearProvisionalWhitelist => Decode!(payload)
+            .map(|()| "(no arguments)".to_string())
+            .map_err(|err| format!("The payload could not be decoded: {}", err)),
+        NnsFunction::RemoveNodesFromSubnet => render::<RemoveNodesFromSubnetPayload>(payload),
+        NnsFunction::SetAuthorizedSubnetworks => render::<SetAuthorizedSubnetworkListArgs>(payload),
+        NnsFunction::SetFirewallConfig => render::<SetFirewallConfigPayload>(payload),
+        NnsFunction::UpdateNodeOperatorConfig => render::<UpdateNodeOperatorConfigPayload>(payload),
+        NnsFunction::StopOrStartNnsCanister => render::<StopOrStartCanisterProposal>(payload),
+        NnsFunction::RemoveNodes => render::<RemoveNodesPayload>(payload),
+        NnsFunction::UninstallCode => render::<CanisterIdRecord>(payload),
+        NnsFunction::UpdateNodeRewardsTable => {
+            render::<UpdateNodeRewardsTableProposalPayload>(payload)
+        }
+        NnsFunction::AddOrRemoveDataCenters => {
+            render::<AddOrRemoveDataCentersProposalPayload>(payload)
+        }
+        NnsFunction::UpdateUnassignedNodesConfig => {
+            render::<UpdateUnassignedNodesConfigPayload>(payload)
+        }
+        NnsFunction::RemoveNodeOperators => render::<RemoveNodeOperatorsPayload>(payload),
+        NnsFunction::RerouteCanisterRanges => render::<RerouteCanisterRangesPayload>(payload),
+        NnsFunction::AddFirewallRules => render::<AddFirewallRulesPayload>(payload),
+        NnsFunction::RemoveFirewallRules => render::<RemoveFirewallRulesPayload>(payload),
+        NnsFunction::UpdateFirewallRules => render::<UpdateFirewallRulesPayload>(payload),
+        NnsFunction::PrepareCanisterMigration => render::<PrepareCanisterMigrationPayload>(payload),
+        NnsFunction::CompleteCanisterMigration => {
+            render::<CompleteCanisterMigrationPayload>(payload)
+        }
+        NnsFunction::AddSnsWasm => render_add_sns_wasm(payload),
+        NnsFunction::ChangeSubnetMembership => render::<ChangeSubnetMembershipPayload>(payload),
+        NnsFunction::UpdateSubnetType => render::<UpdateSubnetTypeArgs>(payload),
+        NnsFunction::ChangeSubnetTypeAssignment => {
+            render::<ChangeSubnetTypeAssignmentArgs>(payload)
+        }
+        NnsFunction::UpdateSnsWasmSnsSubnetIds => render::<UpdateSnsSubnetListRequest>(payload),
+        NnsFunction::UpdateAllowedPrincipals => render::<UpdateAllowedPrincipalsRequest>(payload),
+        NnsFunction::RetireReplicaVersion => render::<RetireReplicaVersionPayload>(payload),
+    }?;
+
+    Ok(format!(
+        r"# Proposal to execute NNS function {:?}
+
+## Target canister: {}
+
+## Target method: {}
+
+## Payload
+
+```
+{}
+```",
+        nns_function, canister_id, method_name, rendered_payload,
+    ))
+}
+
+/// Decodes payload as a single Candid value of type T, and renders it using
+/// its (pretty-printed) Debug representation.
+fn render<T>(payload: &[u8]) -> Result<String, String>
+where
+    T: CandidType + for<'de> Deserialize<'de> + fmt::Debug,
+{
+    let decoded = Decode!(payload, T).map_err(|err| {
+        format!(
+            "The payload could not be decoded into a {}: {}",
+            short_type_name::<T>(),
+            err,
+        )
+    })?;
+    Ok(format!("{:#?}", decoded))
+}
+
+/// Like render, but shows the hash of the wasm instead of the wasm itself.
+fn render_add_sns_wasm(payload: &[u8]) -> Result<String, String> {
+    let AddWasmRequest { wasm, hash } = Decode!(payload, AddWasmRequest).map_err(|err| {
+        format!(
+            "The payload could not be decoded into a AddWasmRequest: {}",
+            err
+        )
+    })?;
+    let wasm = wasm.ok_or_else(|| "AddWasmRequest lacks a value in its wasm field.".to_string())?;
+    let canister_type = SnsCanisterType::from_i32(wasm.canister_type)
+        .map(|canister_type| format!("{:?}", canister_type))
+        .unwrap_or_else(|| format!("Unknown ({})", wasm.canister_type));
+
+    Ok(format!(
+        "AddWasmRequest {{\n    \
+             canister_type: {},\n    \
+             wasm_sha256: {},\n    \
+             hash: {},\n\
+         }}",
+        canister_type,
+        sha256_hex(&wasm.wasm),
+        to_hex(&hash),
+    ))
+}
+
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    let mut state = Sha256::new();
+    state.write(bytes);
+    to_hex(&state.finish())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Encode;
+    use ic_base_types::CanisterId;
+    use ic_ic00_types::CanisterInstallMode;
+    use ic_nns_constants::REGISTRY_CANISTER_ID;
+
+    fn execute_nns_function(nns_function: NnsFunction, payload: Vec<u8>) -> ExecuteNnsFunction {
+        ExecuteNnsFunction {
+            nns_function: nns_function as i32,
+            payload,
+        }
+    }
+
+    #[test]
+    fn test_render_icp_xdr_conversion_rate() {
+        let payload = Encode!(&UpdateIcpXdrConversionRatePayload {
+            data_source: "test".to_string(),
+            timestamp_seconds: 1_000,
+            xdr_permyriad_per_icp: 123_456,
+        })
+        .unwrap();
+
+        let rendering = validate_and_render_execute_nns_function(&execute_nns_function(
+            NnsFunction::IcpXdrConversionRate,
+            payload,
+        ))
+        .unwrap();
+
+        assert!(
+            rendering.contains("# Proposal to execute NNS function IcpXdrConversionRate"),
+            "{}",
+            rendering
+        );
+        assert!(
+            rendering.contains("set_icp_xdr_conversion_rate"),
+            "{}",
+            rendering
+        );
+        assert!(
+            rendering.contains("xdr_permyriad_per_icp: 123456"),
+            "{}",
+            rendering
+        );
+    }
+
+    #[test]
+    fn test_render_nns_canister_upgrade_shows_wasm_hash() {
+        let wasm = vec![0, 0x61, 0x73, 0x6d, 1, 0, 0, 0];
+        let payload = Encode!(&ChangeCanisterProposal::new(
+            true,
+            CanisterInstallMode::Upgrade,
+            REGISTRY_CANISTER_ID,
+        )
+        .with_wasm(wasm.clone()))
+        .unwrap();
+
+        let rendering = validate_and_render_execute_nns_function(&execute_nns_function(
+            NnsFunction::NnsCanisterUpgrade,
+            payload,
+        ))
+        .unwrap();
+
+        assert!(
+            rendering.contains(&format!("{:x?}", {
+                let mut state = Sha256::new();
+                state.write(&wasm);
+                state.finish()
+            })),
+            "{}",
+            rendering
+        );
+        assert!(rendering.contains("change_nns_canister"), "{}", rendering);
+    }
+
+    #[test]
+    fn test_render_uninstall_code() {
+        let payload = Encode!(&CanisterIdRecord::from(CanisterId::from_u64(42))).unwrap();
+
+        let rendering = validate_and_render_execute_nns_function(&execute_nns_function(
+            NnsFunction::UninstallCode,
+            payload,
+        ))
+        .unwrap();
+
+        assert!(rendering.contains("uninstall_code"), "{}", rendering);
+        assert!(
+            rendering.contains(&CanisterId::from_u64(42).to_string()),
+            "{}",
+            rendering
+        );
+    }
+
+    #[test]
+    fn test_payload_that_does_not_decode_is_rejected() {
+        let payload = Encode!(&"not a BlessReplicaVersionPayload").unwrap();
+
+        let err = validate_and_render_execute_nns_function(&execute_nns_function(
+            NnsFunction::BlessReplicaVersion,
+            payload,
+        ))
+        .unwrap_err();
+
+        assert!(err.contains("BlessReplicaVersionPayload"), "{}", err);
+    }
+
+    #[test]
+    fn test_unspecified_nns_function_is_rejected() {
+        let result = validate_and_render_execute_nns_function(&execute_nns_function(
+            NnsFunction::Unspecified,
+            Encode!().unwrap(),
+        ));
+
+        assert!(result.is_err(), "{:?}", result);
+    }
+}
@@ -17,6 +17,7 @@ fn check_generated_files() {
     let nns_common_proto = manifest_dir.join("../common/proto");
     let sns_root_proto = manifest_dir.join("../../sns/root/proto");
     let sns_swap_proto = manifest_dir.join("../../sns/swap/proto");
+    let sns_init_proto = manifest_dir.join("../../sns/init/proto");
     generate_prost_files(
         ProtoPaths {
             governance: &governance_proto,
@@ -25,6 +26,7 @@ fn check_generated_files() {
             nns_common: &nns_common_proto,
             sns_root: &sns_root_proto,
             sns_swap: &sns_swap_proto,
+            sns_init: &sns_init_proto,
         },
         out.path(),
     );
@@ -7,7 +7,7 @@ use ic_nns_common::pb::v1::{NeuronId, ProposalId};
 use ic_nns_common::types::UpdateIcpXdrConversionRatePayload;
 use ic_nns_constants::{GOVERNANCE_CANISTER_ID, LEDGER_CANISTER_ID, SNS_WASM_CANISTER_ID};
 use ic_nns_governance::{
-    governance::{Environment, Governance, HeapGrowthPotential, CMC},
+    governance::{Environment, Governance, HeapGrowthPotential, CMC, ONE_DAY_SECONDS},
     pb::v1::{
         manage_neuron, manage_neuron::NeuronIdOrSubaccount, manage_neuron_response, proposal,
         ExecuteNnsFunction, GovernanceError, ManageNeuron, ManageNeuronResponse, Motion,
@@ -15,7 +15,7 @@ use ic_nns_governance::{
     },
 };
 use ic_sns_wasm::pb::v1::{DeployedSns, ListDeployedSnsesRequest, ListDeployedSnsesResponse};
-use icp_ledger::{AccountIdentifier, Subaccount, Tokens};
+use icp_ledger::{AccountIdentifier, Subaccount, TimeStamp, Tokens};
 use lazy_static::lazy_static;
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
@@ -261,6 +261,28 @@ impl Ledger for FakeDriver {
         Ok(0)
     }
 
+    async fn transfer_funds_deduplicated(
+        &self,
+        amount_e8s: u64,
+        fee_e8s: u64,
+        from_subaccount: Option<Subaccount>,
+        to_account: AccountIdentifier,
+        memo: u64,
+        created_at_time: TimeStamp,
+    ) -> Result<u64, NervousSystemError> {
+        // Like the real ledger, reject transfers created before its
+        // deduplication window.
+        let created_at_time_seconds = created_at_time.as_nanos_since_unix_epoch() / 1_000_000_000;
+        if created_at_time_seconds + ONE_DAY_SECONDS < self.now() {
+            return Err(NervousSystemError::new_with_message(format!(
+                "transaction is older than {} seconds",
+                ONE_DAY_SECONDS
+            )));
+        }
+        self.transfer_funds(amount_e8s, fee_e8s, from_subaccount, to_account, memo)
+            .await
+    }
+
     async fn total_supply(&self) -> Result<Tokens, NervousSystemError> {
         Ok(self.get_supply())
     }
@@ -56,10 +56,10 @@ use ic_nns_governance::{
         proposal,
         reward_node_provider::{RewardMode, RewardToAccount, RewardToNeuron},
         AddOrRemoveNodeProvider, Ballot, BallotInfo, Empty, ExecuteNnsFunction,
-        Governance as GovernanceProto, GovernanceError, ListNeurons, ListNeuronsResponse,
-        ListProposalInfo, ManageNeuron, Motion, NetworkEconomics, Neuron, NeuronStakeTransfer,
-        NeuronState, NnsFunction, NodeProvider, Proposal, ProposalData, ProposalStatus,
-        RewardEvent, RewardNodeProvider, SetDefaultFollowees, Tally, Topic, Vote,
+        Governance as GovernanceProto, GovernanceError, KnownNeuronData, ListNeurons,
+        ListNeuronsResponse, ListProposalInfo, ManageNeuron, Motion, NetworkEconomics, Neuron,
+        NeuronStakeTransfer, NeuronState, NnsFunction, NodeProvider, Proposal, ProposalData,
+        ProposalStatus, RewardEvent, RewardNodeProvider, SetDefaultFollowees, Tally, Topic, Vote,
     },
 };
 use icp_ledger::{AccountIdentifier, Memo, Tokens};
@@ -186,6 +186,7 @@ pub struct NeuronBuilder {
     kyc_verified: bool,
     not_for_profit: bool,
     joined_community_fund: Option<u64>,
+    known_neuron_data: Option<KnownNeuronData>,
     do_not_create_subaccount: bool,
 }
 
@@ -209,6 +210,7 @@ impl From<Neuron> for NeuronBuilder {
             kyc_verified: neuron.kyc_verified,
             not_for_profit: neuron.not_for_profit,
             joined_community_fund: neuron.joined_community_fund_timestamp_seconds,
+            known_neuron_data: neuron.known_neuron_data,
             do_not_create_subaccount: false,
         }
     }
@@ -230,6 +232,7 @@ impl NeuronBuilder {
             kyc_verified: true,
             not_for_profit: false,
             joined_community_fund: None,
+            known_neuron_data: None,
             do_not_create_subaccount: false,
         }
     }
@@ -249,6 +252,7 @@ impl NeuronBuilder {
             kyc_verified: true,
             not_for_profit: false,
             joined_community_fund: None,
+            known_neuron_data: None,
             do_not_create_subaccount: false,
         }
     }
@@ -322,6 +326,14 @@ impl NeuronBuilder {
         self
     }
 
+    pub fn set_known_neuron_data(mut self, name: &str) -> Self {
+        self.known_neuron_data = Some(KnownNeuronData {
+            name: name.to_string(),
+            description: None,
+        });
+        self
+    }
+
     pub fn do_not_create_subaccount(mut self) -> Self {
         self.do_not_create_subaccount = true;
         self
@@ -353,6 +365,7 @@ impl NeuronBuilder {
             not_for_profit: self.not_for_profit,
             followees: self.followees,
             joined_community_fund_timestamp_seconds: self.joined_community_fund,
+            known_neuron_data: self.known_neuron_data,
             ..Neuron::default()
         }
     }
@@ -12,14 +12,16 @@ use dfn_protobuf::ToProto;
 use futures::future::FutureExt;
 use ic_base_types::{CanisterId, PrincipalId};
 use ic_crypto_sha::Sha256;
+use ic_ic00_types::{CanisterInstallMode, CanisterSettingsArgs, UpdateSettingsArgs};
 use ic_nervous_system_common_test_keys::{
     TEST_NEURON_1_OWNER_PRINCIPAL, TEST_NEURON_2_OWNER_PRINCIPAL,
 };
+use ic_nervous_system_root::ChangeCanisterProposal;
 use ic_nns_common::{
     pb::v1::{NeuronId, ProposalId},
     types::UpdateIcpXdrConversionRatePayload,
 };
-use ic_nns_constants::{GOVERNANCE_CANISTER_ID, SNS_WASM_CANISTER_ID};
+use ic_nns_constants::{GOVERNANCE_CANISTER_ID, REGISTRY_CANISTER_ID, SNS_WASM_CANISTER_ID};
 #[cfg(feature = "test")]
 use ic_nns_governance::{
     governance::governance_minting_account,
@@ -32,8 +34,9 @@ use ic_nns_governance::{
 use ic_nns_governance::{
     governance::{
         subaccount_from_slice, validate_proposal_title, Environment, Governance,
-        HeapGrowthPotential, EXECUTE_NNS_FUNCTION_PAYLOAD_LISTING_BYTES_MAX,
-        MAX_DISSOLVE_DELAY_SECONDS, MAX_NEURON_AGE_FOR_AGE_BONUS,
+        HeapGrowthPotential, DISBURSE_MATURITY_DELAY_SECONDS,
+        EXECUTE_NNS_FUNCTION_PAYLOAD_LISTING_BYTES_MAX, MAX_DISSOLVE_DELAY_SECONDS,
+        MAX_FOLLOWEES_PER_NEURON, MAX_NEURON_AGE_FOR_AGE_BONUS,
         MAX_NUMBER_OF_PROPOSALS_WITH_BALLOTS, MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
         ONE_DAY_SECONDS, ONE_MONTH_SECONDS, ONE_YEAR_SECONDS, PROPOSAL_MOTION_TEXT_BYTES_MAX,
         REWARD_DISTRIBUTION_PERIOD_SECONDS, WAIT_FOR_QUIET_DEADLINE_INCREASE_SECONDS,
@@ -51,31 +54,39 @@ use ic_nns_governance::{
             claim_or_refresh::{By, MemoAndController},
             configure::Operation,
             disburse::Amount,
+            set_following::FolloweesForTopic,
             ChangeAutoStakeMaturity, ClaimOrRefresh, Command, Configure, Disburse,
-            DisburseToNeuron, Follow, IncreaseDissolveDelay, JoinCommunityFund, LeaveCommunityFund,
-            Merge, MergeMaturity, NeuronIdOrSubaccount, SetDissolveTimestamp, Spawn, Split,
-            StartDissolving,
+            DisburseMaturity, DisburseToNeuron, Follow, IncreaseDissolveDelay, JoinCommunityFund,
+            LeaveCommunityFund, Merge, MergeMaturity, NeuronIdOrSubaccount, SetDissolveTimestamp,
+            SetFollowing, SetVisibility, Spawn, Split, StartDissolving,
         },
         manage_neuron_response::{self, Command as CommandResponse, MergeMaturityResponse},
         neuron::{self, DissolveState, Followees},
         proposal::{self, Action},
         reward_node_provider::{RewardMode, RewardToAccount, RewardToNeuron},
-        AddOrRemoveNodeProvider, ApproveGenesisKyc, Ballot, BallotInfo, Empty, ExecuteNnsFunction,
+        settle_community_fund_participation, AddOrRemoveNodeProvider, ApproveGenesisKyc, Ballot,
+        BallotInfo, CreateServiceNervousSystem, Empty, ExecuteNnsFunction,
         Governance as GovernanceProto, GovernanceError, KnownNeuron, KnownNeuronData, ListNeurons,
-        ListNeuronsResponse, ListProposalInfo, ManageNeuron, Motion, NetworkEconomics, Neuron,
-        NeuronState, NnsFunction, NodeProvider, OpenSnsTokenSwap, Proposal, ProposalData,
-        ProposalRewardStatus,
+        ListNeuronsResponse, ListProposalInfo, ListPublicNeurons, ListPublicNeuronsResponse,
+        ManageNeuron, MaturityDisbursement, Motion, NetworkEconomics, Neuron, NeuronState,
+        NnsFunction, NodeProvider, OpenSnsTokenSwap, Proposal, ProposalData, ProposalRewardStatus,
         ProposalRewardStatus::{AcceptVotes, ReadyToSettle},
         ProposalStatus,
         ProposalStatus::Rejected,
-        RewardEvent, RewardNodeProvider, RewardNodeProviders, SetDefaultFollowees, Tally, Topic,
-        UpdateNodeProvider, Vote,
+        RewardEvent, RewardNodeProvider, RewardNodeProviders, SetDefaultFollowees,
+        SettleCommunityFundParticipation, Tally, Topic, UpdateNodeProvider, Visibility, Vote,
+        VotingPowerEconomics,
     },
 };
+use ic_sns_init::pb::v1::SnsInitPayload;
+use ic_sns_root::pb::v1::{RegisterDappCanisterRequest, RegisterDappCanisterResponse};
 use ic_sns_swap::pb::v1::{
     self as sns_swap_pb, params::NeuronBasketConstructionParameters, Params,
 };
-use ic_sns_wasm::pb::v1::{DeployedSns, ListDeployedSnsesRequest, ListDeployedSnsesResponse};
+use ic_sns_wasm::pb::v1::{
+    DeployNewSnsRequest, DeployNewSnsResponse, DeployedSns, ListDeployedSnsesRequest,
+    ListDeployedSnsesResponse, SnsCanisterIds,
+};
 use icp_ledger::{AccountIdentifier, Memo, Subaccount, Tokens};
 use maplit::hashmap;
 use proptest::prelude::{prop_assert, prop_assert_eq, proptest, TestCaseError};
@@ -1407,6 +1418,99 @@ async fn test_node_provider_must_be_registered() {
     .unwrap();
 }
 
+/// Tests that ExecuteNnsFunction proposals whose payload does not decode into
+/// what the target method expects are rejected, and that the payload of
+/// accepted ones is rendered in the ProposalInfo.
+#[tokio::test]
+async fn test_execute_nns_function_payload_is_validated_and_rendered() {
+    let driver = fake::FakeDriver::default();
+    let mut gov = Governance::new(
+        fixture_for_following(),
+        driver.get_fake_env(),
+        driver.get_fake_ledger(),
+        driver.get_fake_cmc(),
+    );
+    // Must match neuron 1's serialized_id.
+    let proposer = PrincipalId::try_from(b"SID1".to_vec()).unwrap();
+
+    // This should fail, because the payload is not a ChangeCanisterProposal.
+    let err = gov
+        .make_proposal(
+            &NeuronId { id: 1 },
+            &proposer,
+            &Proposal {
+                title: Some("A Reasonable Title".to_string()),
+                summary: "test".to_string(),
+                action: Some(proposal::Action::ExecuteNnsFunction(ExecuteNnsFunction {
+                    nns_function: NnsFunction::NnsCanisterUpgrade as i32,
+                    payload: Encode!(&"garbage").unwrap(),
+                })),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.error_type,
+        ErrorType::InvalidProposal as i32,
+        "{:?}",
+        err
+    );
+    assert!(
+        err.error_message.contains("ChangeCanisterProposal"),
+        "{:?}",
+        err
+    );
+
+    // This should succeed.
+    let wasm_module = vec![0, 0x61, 0x73, 0x6d, 1, 0, 0, 0];
+    let proposal_id = gov
+        .make_proposal(
+            &NeuronId { id: 1 },
+            &proposer,
+            &Proposal {
+                title: Some("A Reasonable Title".to_string()),
+                summary: "test".to_string(),
+                action: Some(proposal::Action::ExecuteNnsFunction(ExecuteNnsFunction {
+                    nns_function: NnsFunction::NnsCanisterUpgrade as i32,
+                    payload: Encode!(&ChangeCanisterProposal::new(
+                        false,
+                        CanisterInstallMode::Upgrade,
+                        REGISTRY_CANISTER_ID,
+                    )
+                    .with_wasm(wasm_module.clone()))
+                    .unwrap(),
+                })),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let rendering = gov
+        .get_proposal_info(&proposer, proposal_id)
+        .unwrap()
+        .payload_text_rendering
+        .unwrap();
+    assert!(
+        rendering.contains("# Proposal to execute NNS function NnsCanisterUpgrade"),
+        "{}",
+        rendering
+    );
+    assert!(
+        rendering.contains(&REGISTRY_CANISTER_ID.to_string()),
+        "{}",
+        rendering
+    );
+    let mut wasm_sha = Sha256::new();
+    wasm_sha.write(&wasm_module);
+    assert!(
+        rendering.contains(&format!("{:x?}", wasm_sha.finish())),
+        "{}",
+        rendering
+    );
+}
+
 /// In this scenario, we simply test that you cannot make a proposal
 /// if you have insufficient stake (less than the reject fee).
 #[tokio::test]
@@ -3317,6 +3421,7 @@ fn create_mature_neuron(dissolved: bool) -> (fake::FakeDriver, Governance, Neuro
             aging_since_timestamp_seconds: driver.now(),
             dissolve_state: Some(DissolveState::DissolveDelaySeconds(dissolve_delay_seconds)),
             kyc_verified: true,
+            voting_power_refreshed_timestamp_seconds: Some(driver.now()),
             ..Default::default()
         }
     );
@@ -3341,7 +3446,9 @@ fn create_mature_neuron(dissolved: bool) -> (fake::FakeDriver, Governance, Neuro
         // The neuron state should now be "Dissolved", meaning we can
         // now disburse the neuron.
         assert_eq!(
-            neuron.get_neuron_info(driver.now()).state(),
+            neuron
+                .get_neuron_info(&VotingPowerEconomics::with_default_values(), driver.now())
+                .state(),
             NeuronState::Dissolved
         );
     } else {
@@ -4160,7 +4267,9 @@ fn test_neuron_split_fails() {
         .neuron_minimum_stake_e8s;
 
     assert_eq!(
-        neuron.get_neuron_info(driver.now()).state(),
+        neuron
+            .get_neuron_info(&VotingPowerEconomics::with_default_values(), driver.now())
+            .state(),
         NeuronState::NotDissolving
     );
 
@@ -4265,7 +4374,9 @@ fn test_neuron_split() {
     let transaction_fee = gov.proto.economics.as_ref().unwrap().transaction_fee_e8s;
 
     assert_eq!(
-        neuron.get_neuron_info(driver.now()).state(),
+        neuron
+            .get_neuron_info(&VotingPowerEconomics::with_default_values(), driver.now())
+            .state(),
         NeuronState::NotDissolving
     );
 
@@ -4310,6 +4421,8 @@ fn test_neuron_split() {
                 parent_neuron.dissolve_delay_seconds(driver.get_fake_env().now())
             )),
             kyc_verified: true,
+            voting_power_refreshed_timestamp_seconds: parent_neuron
+                .voting_power_refreshed_timestamp_seconds,
             ..Default::default()
         }
     );
@@ -5091,7 +5204,9 @@ fn test_neuron_spawn() {
     let neuron = gov.get_neuron_mut(&id).expect("Neuron did not exist");
 
     assert_eq!(
-        neuron.get_neuron_info(driver.now()).state(),
+        neuron
+            .get_neuron_info(&VotingPowerEconomics::with_default_values(), driver.now())
+            .state(),
         NeuronState::NotDissolving
     );
 
@@ -5187,6 +5302,7 @@ fn test_neuron_spawn() {
             )),
             kyc_verified: true,
             maturity_e8s_equivalent: parent_maturity_e8s_equivalent,
+            voting_power_refreshed_timestamp_seconds: Some(driver.now()),
             ..Default::default()
         }
     );
@@ -5228,6 +5344,7 @@ fn test_neuron_spawn() {
             )),
             kyc_verified: true,
             maturity_e8s_equivalent: 0,
+            voting_power_refreshed_timestamp_seconds: Some(creation_timestamp),
             ..Default::default()
         }
     );
@@ -5254,7 +5371,9 @@ fn test_neuron_spawn_with_subaccount() {
     let neuron = gov.get_neuron_mut(&id).expect("Neuron did not exist");
 
     assert_eq!(
-        neuron.get_neuron_info(driver.now()).state(),
+        neuron
+            .get_neuron_info(&VotingPowerEconomics::with_default_values(), driver.now())
+            .state(),
         NeuronState::NotDissolving
     );
 
@@ -5380,6 +5499,7 @@ fn test_neuron_spawn_with_subaccount() {
             )),
             kyc_verified: true,
             maturity_e8s_equivalent: 0,
+            voting_power_refreshed_timestamp_seconds: Some(creation_timestamp),
             ..Default::default()
         }
     );
@@ -5428,7 +5548,9 @@ fn assert_neuron_spawn_partial(
 
     let neuron = gov.get_neuron_mut(&id).expect("Neuron did not exist");
     assert_eq!(
-        neuron.get_neuron_info(driver.now()).state(),
+        neuron
+            .get_neuron_info(&VotingPowerEconomics::with_default_values(), driver.now())
+            .state(),
         NeuronState::NotDissolving
     );
 
@@ -5525,11 +5647,228 @@ fn assert_neuron_spawn_partial(
             )),
             kyc_verified: true,
             maturity_e8s_equivalent: 0,
+            voting_power_refreshed_timestamp_seconds: Some(creation_timestamp),
             ..Default::default()
         }
     );
 }
 
+/// Checks that:
+/// * disbursing maturity removes it from the neuron right away, but only
+///   mints it, with the maturity modulation applied, after
+///   DISBURSE_MATURITY_DELAY_SECONDS.
+/// * disbursing too little maturity fails and changes nothing.
+#[test]
+fn test_disburse_maturity() {
+    let from = *TEST_NEURON_1_OWNER_PRINCIPAL;
+    let (mut driver, mut gov, id, _) = governance_with_staked_neuron(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+        1_000_000_000,
+        543212234,
+        from,
+        1234,
+    );
+    let to_account = AccountIdentifier::new(*TEST_NEURON_2_OWNER_PRINCIPAL, None);
+    let disburse_maturity = |percentage_to_disburse: u32| DisburseMaturity {
+        percentage_to_disburse,
+        to_account: Some(to_account.into()),
+    };
+
+    // Starts with too little maturity.
+    gov.get_neuron_mut(&id).unwrap().maturity_e8s_equivalent = 187;
+    let neuron_before = gov.get_neuron(&id).unwrap().clone();
+    assert_matches!(
+        gov.disburse_maturity(&id, &from, &disburse_maturity(100)),
+        Err(GovernanceError { error_type: code, .. }) if code == InsufficientFunds as i32
+    );
+    assert_eq!(*gov.get_neuron(&id).unwrap(), neuron_before);
+
+    // Only the controller can disburse maturity, and only between 1 and 100%.
+    let maturity_e8s = 123_456_789;
+    gov.get_neuron_mut(&id).unwrap().maturity_e8s_equivalent = maturity_e8s;
+    assert_matches!(
+        gov.disburse_maturity(&id, &*TEST_NEURON_2_OWNER_PRINCIPAL, &disburse_maturity(100)),
+        Err(GovernanceError { error_type: code, .. }) if code == NotAuthorized as i32
+    );
+    assert_matches!(
+        gov.disburse_maturity(&id, &from, &disburse_maturity(101)),
+        Err(GovernanceError { error_type: code, .. }) if code == PreconditionFailed as i32
+    );
+
+    let disbursed_e8s = gov
+        .disburse_maturity(&id, &from, &disburse_maturity(50))
+        .unwrap();
+    assert_eq!(disbursed_e8s, maturity_e8s / 2);
+    let neuron = gov.get_neuron(&id).unwrap();
+    assert_eq!(neuron.maturity_e8s_equivalent, maturity_e8s - disbursed_e8s);
+    assert_eq!(
+        neuron.maturity_disbursements_in_progress,
+        vec![MaturityDisbursement {
+            amount_e8s: disbursed_e8s,
+            timestamp_of_disbursement_seconds: driver.now(),
+            finalize_disbursement_timestamp_seconds: driver.now() + DISBURSE_MATURITY_DELAY_SECONDS,
+            account_to_disburse_to: Some(to_account.into()),
+            amount_to_mint_e8s: None,
+            first_mint_attempt_timestamp_seconds: None,
+        }]
+    );
+
+    // Nothing is minted before the delay has passed.
+    driver.advance_time_by(DISBURSE_MATURITY_DELAY_SECONDS - 1);
+    run_periodic_tasks_on_governance_often_enough_to_spawn(&mut gov);
+    assert_eq!(
+        gov.get_neuron(&id)
+            .unwrap()
+            .maturity_disbursements_in_progress
+            .len(),
+        1
+    );
+
+    // After the delay, the maturity is minted with the maturity modulation
+    // of the day (1% in the fake CMC).
+    driver.advance_time_by(1);
+    run_periodic_tasks_on_governance_often_enough_to_spawn(&mut gov);
+    assert!(gov
+        .get_neuron(&id)
+        .unwrap()
+        .maturity_disbursements_in_progress
+        .is_empty());
+    driver.assert_account_contains(&to_account, disbursed_e8s * 101 / 100);
+}
+
+/// Checks that a maturity disbursement that fails to be minted is retried
+/// with the amount and the created_at_time of the first attempt, so that the
+/// ledger can recognize the retry as a duplicate.
+#[test]
+fn test_disburse_maturity_retries_the_same_transfer() {
+    let from = *TEST_NEURON_1_OWNER_PRINCIPAL;
+    let (mut driver, mut gov, id, _) = governance_with_staked_neuron(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+        1_000_000_000,
+        543212234,
+        from,
+        1234,
+    );
+    let to_account = AccountIdentifier::new(*TEST_NEURON_2_OWNER_PRINCIPAL, None);
+    gov.get_neuron_mut(&id).unwrap().maturity_e8s_equivalent = 123_456_789;
+    let disbursed_e8s = gov
+        .disburse_maturity(
+            &id,
+            &from,
+            &DisburseMaturity {
+                percentage_to_disburse: 100,
+                to_account: Some(to_account.into()),
+            },
+        )
+        .unwrap();
+
+    // Make the first attempt to mint fail.
+    let disbursement = |gov: &Governance| {
+        gov.get_neuron(&id)
+            .unwrap()
+            .maturity_disbursements_in_progress
+            .first()
+            .cloned()
+    };
+    gov.get_neuron_mut(&id)
+        .unwrap()
+        .maturity_disbursements_in_progress[0]
+        .account_to_disburse_to = Some(icp_ledger::protobuf::AccountIdentifier {
+        hash: vec![1, 2, 3],
+    });
+    driver.advance_time_by(DISBURSE_MATURITY_DELAY_SECONDS);
+    let first_attempt_timestamp_seconds = driver.now();
+    run_periodic_tasks_on_governance_often_enough_to_spawn(&mut gov);
+    let failed = disbursement(&gov).unwrap();
+    assert_eq!(failed.amount_to_mint_e8s, Some(disbursed_e8s * 101 / 100));
+    assert_eq!(
+        failed.first_mint_attempt_timestamp_seconds,
+        Some(first_attempt_timestamp_seconds)
+    );
+
+    // A later attempt mints the recorded amount instead of modulating the
+    // maturity again (here, the recorded amount is changed to tell them apart).
+    gov.get_neuron_mut(&id)
+        .unwrap()
+        .maturity_disbursements_in_progress[0]
+        .account_to_disburse_to = Some(to_account.into());
+    gov.get_neuron_mut(&id)
+        .unwrap()
+        .maturity_disbursements_in_progress[0]
+        .amount_to_mint_e8s = Some(disbursed_e8s);
+    driver.advance_time_by(ONE_DAY_SECONDS);
+    run_periodic_tasks_on_governance_often_enough_to_spawn(&mut gov);
+    assert_eq!(disbursement(&gov), None);
+    driver.assert_account_contains(&to_account, disbursed_e8s);
+}
+
+/// Checks that a maturity disbursement that could not be minted within the
+/// deduplication window of the ledger fails, and that its maturity is given
+/// back to the neuron, from where it can be disbursed again.
+#[test]
+fn test_disburse_maturity_fails_after_the_ledger_deduplication_window() {
+    let from = *TEST_NEURON_1_OWNER_PRINCIPAL;
+    let (mut driver, mut gov, id, _) = governance_with_staked_neuron(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+        1_000_000_000,
+        543212234,
+        from,
+        1234,
+    );
+    let to_account = AccountIdentifier::new(*TEST_NEURON_2_OWNER_PRINCIPAL, None);
+    gov.get_neuron_mut(&id).unwrap().maturity_e8s_equivalent = 123_456_789;
+    let disburse_maturity = DisburseMaturity {
+        percentage_to_disburse: 100,
+        to_account: Some(to_account.into()),
+    };
+    let disbursed_e8s = gov
+        .disburse_maturity(&id, &from, &disburse_maturity)
+        .unwrap();
+    assert_eq!(gov.get_neuron(&id).unwrap().maturity_e8s_equivalent, 0);
+
+    // Make the first attempt to mint fail.
+    gov.get_neuron_mut(&id)
+        .unwrap()
+        .maturity_disbursements_in_progress[0]
+        .account_to_disburse_to = Some(icp_ledger::protobuf::AccountIdentifier {
+        hash: vec![1, 2, 3],
+    });
+    driver.advance_time_by(DISBURSE_MATURITY_DELAY_SECONDS);
+    run_periodic_tasks_on_governance_often_enough_to_spawn(&mut gov);
+    assert_eq!(
+        gov.get_neuron(&id)
+            .unwrap()
+            .maturity_disbursements_in_progress
+            .len(),
+        1
+    );
+
+    // Once the deduplication window has passed, the ledger rejects the retry
+    // as too old. The disbursement fails and the neuron gets its maturity back.
+    gov.get_neuron_mut(&id)
+        .unwrap()
+        .maturity_disbursements_in_progress[0]
+        .account_to_disburse_to = Some(to_account.into());
+    driver.advance_time_by(ONE_DAY_SECONDS + 1);
+    run_periodic_tasks_on_governance_often_enough_to_spawn(&mut gov);
+    let neuron = gov.get_neuron(&id).unwrap();
+    assert!(neuron.maturity_disbursements_in_progress.is_empty());
+    assert_eq!(neuron.maturity_e8s_equivalent, disbursed_e8s);
+
+    // The maturity can then be disbursed again, with a new created_at_time. It
+    // is minted exactly once.
+    gov.disburse_maturity(&id, &from, &disburse_maturity)
+        .unwrap();
+    driver.advance_time_by(DISBURSE_MATURITY_DELAY_SECONDS);
+    run_periodic_tasks_on_governance_often_enough_to_spawn(&mut gov);
+    assert!(gov
+        .get_neuron(&id)
+        .unwrap()
+        .maturity_disbursements_in_progress
+        .is_empty());
+    driver.assert_account_contains(&to_account, disbursed_e8s * 101 / 100);
+}
+
 /// Assert that a neuron cannot be created with a non self-authenticating
 /// controller `PrincipalId` (via a call to `spawn_neuron`).
 #[test]
@@ -5760,7 +6099,9 @@ fn test_disburse_to_neuron() {
     // The neuron state should now be "Dissolved", meaning we can
     // now disburse the neuron.
     assert_eq!(
-        parent_neuron.get_neuron_info(driver.now()).state(),
+        parent_neuron
+            .get_neuron_info(&VotingPowerEconomics::with_default_values(), driver.now())
+            .state(),
         NeuronState::Dissolved
     );
 
@@ -6097,6 +6438,140 @@ fn test_add_and_remove_hot_key() {
         .contains(&neuron.id.as_ref().unwrap().id));
 }
 
+/// Neurons are moved to stable memory, can still be queried there, and are
+/// moved back to the heap when they are managed by an authorized caller.
+#[test]
+fn test_neurons_move_to_stable_memory_and_back() {
+    let driver = fake::FakeDriver::default();
+    let mut gov = Governance::new(
+        fixture_for_manage_neuron(),
+        driver.get_fake_env(),
+        driver.get_fake_ledger(),
+        driver.get_fake_cmc(),
+    );
+    let neuron_5 = gov.proto.neurons[&5].clone();
+
+    assert_eq!(gov.move_neurons_to_stable_memory(), Ok(false));
+    assert!(gov.proto.neurons.is_empty());
+    assert_eq!(gov.num_neurons(), 6);
+
+    assert_eq!(
+        gov.get_full_neuron(&NeuronId { id: 5 }, &principal(5))
+            .unwrap(),
+        neuron_5
+    );
+    assert_eq!(gov.get_neuron_ids_by_principal(&principal(5)), vec![5]);
+
+    let add_hot_key = ManageNeuron {
+        id: None,
+        neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId { id: 5 })),
+        command: Some(Command::Configure(Configure {
+            operation: Some(Operation::AddHotKey(manage_neuron::AddHotKey {
+                new_hot_key: Some(principal(7)),
+            })),
+        })),
+    };
+
+    // Callers that neither control the neuron nor are one of its hot keys
+    // cannot move it back to the heap.
+    let result = gov
+        .manage_neuron(&principal(6), &add_hot_key)
+        .now_or_never()
+        .unwrap();
+    assert_matches!(result.command, Some(CommandResponse::Error(_)));
+    assert!(!gov.proto.neurons.contains_key(&5));
+
+    let result = gov
+        .manage_neuron(&principal(5), &add_hot_key)
+        .now_or_never()
+        .unwrap();
+
+    assert_matches!(result.command, Some(CommandResponse::Configure(_)));
+    assert_eq!(gov.proto.neurons[&5].hot_keys, vec![principal(7)]);
+    assert_eq!(gov.get_neuron_ids_by_principal(&principal(7)), vec![5]);
+    assert_eq!(gov.num_neurons(), 6);
+}
+
+/// Neurons in stable memory get ballots, vote by following and receive
+/// voting rewards without being moved back to the heap.
+#[test]
+fn test_neurons_in_stable_memory_vote_by_following() {
+    let mut driver = fake::FakeDriver::default();
+    let mut proto = fixture_for_following();
+    proto.neurons.get_mut(&2).unwrap().controller = Some(principal(2));
+    let mut gov = Governance::new(
+        proto,
+        driver.get_fake_env(),
+        driver.get_fake_ledger(),
+        driver.get_fake_cmc(),
+    );
+    assert_eq!(gov.move_neurons_to_stable_memory(), Ok(false));
+    assert!(gov.proto.neurons.is_empty());
+
+    // Neuron 1 is moved back to the heap to make the proposal.
+    let result = gov
+        .manage_neuron(
+            &principal(1),
+            &ManageNeuron {
+                id: None,
+                neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId { id: 1 })),
+                command: Some(Command::MakeProposal(Box::new(Proposal {
+                    title: Some("A Reasonable Title".to_string()),
+                    summary: "test".to_string(),
+                    action: Some(proposal::Action::ManageNetworkEconomics(NetworkEconomics {
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                }))),
+            },
+        )
+        .now_or_never()
+        .unwrap();
+    assert_matches!(result.command, Some(CommandResponse::MakeProposal(_)));
+    let proposal = gov.get_proposal_data(ProposalId { id: 1 }).unwrap();
+    assert_eq!(proposal.ballots.len(), 9);
+
+    // Neuron 2 follows neuron 1 and neurons 5 and 6 through neuron 3.
+    fake::register_vote_assert_success(
+        &mut gov,
+        principal(5),
+        NeuronId { id: 5 },
+        ProposalId { id: 1 },
+        Vote::Yes,
+    );
+    fake::register_vote_assert_success(
+        &mut gov,
+        principal(6),
+        NeuronId { id: 6 },
+        ProposalId { id: 1 },
+        Vote::Yes,
+    );
+
+    let proposal = gov.get_proposal_data(ProposalId { id: 1 }).unwrap();
+    assert_eq!(proposal.ballots[&2].vote, Vote::Yes as i32);
+    assert_eq!(proposal.ballots[&3].vote, Vote::Yes as i32);
+    assert_eq!(proposal.status(), ProposalStatus::Executed);
+    assert!(!gov.proto.neurons.contains_key(&2));
+    assert_eq!(
+        gov.get_neuron_info(&NeuronId { id: 2 })
+            .unwrap()
+            .recent_ballots,
+        vec![BallotInfo {
+            proposal_id: Some(ProposalId { id: 1 }),
+            vote: Vote::Yes as i32,
+        }]
+    );
+
+    // Neuron 2 receives its voting rewards in stable memory.
+    driver.advance_time_by(5 * 24 * 3600);
+    gov.run_periodic_tasks().now_or_never();
+    assert!(!gov.proto.neurons.contains_key(&2));
+    let neuron_2 = gov
+        .get_full_neuron(&NeuronId { id: 2 }, &principal(2))
+        .unwrap();
+    assert!(neuron_2.maturity_e8s_equivalent > 0);
+}
+
 #[test]
 fn test_manage_and_reward_node_providers() {
     let p = match std::env::var("NEURON_CSV_PATH") {
@@ -8114,7 +8589,12 @@ async fn test_max_number_of_proposals_with_ballots() {
                 summary: "NnsCanisterUpgrade should go through despite the limit".to_string(),
                 action: Some(proposal::Action::ExecuteNnsFunction(ExecuteNnsFunction {
                     nns_function: NnsFunction::NnsCanisterUpgrade as i32,
-                    payload: Vec::new(),
+                    payload: Encode!(&ChangeCanisterProposal::new(
+                        false,
+                        CanisterInstallMode::Upgrade,
+                        REGISTRY_CANISTER_ID,
+                    ))
+                    .unwrap(),
                 })),
                 ..Default::default()
             },
@@ -10694,84 +11174,82 @@ async fn test_known_neurons() {
     assert_eq!(expected_known_neuron_name_set, gov.known_neuron_name_set);
 }
 
-#[tokio::test]
-async fn test_open_sns_token_swap_proposal() {
-    // Step 0: Define helper(s)
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct ExpectedCallCanisterMethodCallArguments<'a> {
+    target: CanisterId,
+    method_name: &'a str,
+    request: Vec<u8>,
+}
 
-    #[derive(Debug, PartialEq, Eq, Clone)]
-    struct ExpectedCallCanisterMethodCallArguments<'a> {
+#[allow(clippy::type_complexity)]
+struct MockEnvironment<'a> {
+    expected_call_canister_method_calls: Arc<
+        Mutex<
+            VecDeque<(
+                ExpectedCallCanisterMethodCallArguments<'a>,
+                Result<Vec<u8>, (Option<i32>, String)>,
+            )>,
+        >,
+    >,
+}
+
+#[async_trait]
+impl Environment for MockEnvironment<'_> {
+    async fn call_canister_method(
+        &mut self,
         target: CanisterId,
-        method_name: &'a str,
+        method_name: &str,
         request: Vec<u8>,
-    }
+    ) -> Result<Vec<u8>, (Option<i32>, String)> {
+        let (expected_arguments, result) = self
+            .expected_call_canister_method_calls
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap();
 
-    #[allow(clippy::type_complexity)]
-    struct MockEnvironment<'a> {
-        expected_call_canister_method_calls: Arc<
-            Mutex<
-                VecDeque<(
-                    ExpectedCallCanisterMethodCallArguments<'a>,
-                    Result<Vec<u8>, (Option<i32>, String)>,
-                )>,
-            >,
-        >,
+        assert_eq!(
+            ExpectedCallCanisterMethodCallArguments {
+                target,
+                method_name,
+                request
+            },
+            expected_arguments,
+        );
+
+        result
     }
 
-    #[async_trait]
-    impl Environment for MockEnvironment<'_> {
-        async fn call_canister_method(
-            &mut self,
-            target: CanisterId,
-            method_name: &str,
-            request: Vec<u8>,
-        ) -> Result<Vec<u8>, (Option<i32>, String)> {
-            let (expected_arguments, result) = self
-                .expected_call_canister_method_calls
-                .lock()
-                .unwrap()
-                .pop_front()
-                .unwrap();
+    // Other methods don't do anything interesting. We implement them mostly
+    // to fulfill the trait requirements.
 
-            assert_eq!(
-                ExpectedCallCanisterMethodCallArguments {
-                    target,
-                    method_name,
-                    request
-                },
-                expected_arguments,
-            );
+    fn now(&self) -> u64 {
+        DEFAULT_TEST_START_TIMESTAMP_SECONDS
+    }
 
-            result
-        }
+    fn random_u64(&mut self) -> u64 {
+        panic!("Unexpected call to Environment::random_u64");
+    }
 
-        // Other methods don't do anything interesting. We implement them mostly
-        // to fulfill the trait requirements.
+    fn random_byte_array(&mut self) -> [u8; 32] {
+        panic!("Unexpected call to Environment::random_byte_array");
+    }
 
-        fn now(&self) -> u64 {
-            DEFAULT_TEST_START_TIMESTAMP_SECONDS
-        }
+    fn execute_nns_function(
+        &self,
+        _proposal_id: u64,
+        _update: &ExecuteNnsFunction,
+    ) -> Result<(), GovernanceError> {
+        panic!("Unexpected call to Environment::execute_nns_function");
+    }
 
-        fn random_u64(&mut self) -> u64 {
-            panic!("Unexpected call to Environment::random_u64");
-        }
-
-        fn random_byte_array(&mut self) -> [u8; 32] {
-            panic!("Unexpected call to Environment::random_byte_array");
-        }
-
-        fn execute_nns_function(
-            &self,
-            _proposal_id: u64,
-            _update: &ExecuteNnsFunction,
-        ) -> Result<(), GovernanceError> {
-            panic!("Unexpected call to Environment::execute_nns_function");
-        }
-
-        fn heap_growth_potential(&self) -> HeapGrowthPotential {
-            HeapGrowthPotential::NoIssue
-        }
+    fn heap_growth_potential(&self) -> HeapGrowthPotential {
+        HeapGrowthPotential::NoIssue
     }
+}
 
+#[tokio::test]
+async fn test_open_sns_token_swap_proposal() {
     // Step 1: Prepare the world.
 
     // Parameters which will later be used to construct the swap proposal.
@@ -10965,6 +11443,438 @@ async fn test_open_sns_token_swap_proposal() {
     assert_eq!(proposal.failure_reason, None, "{:#?}", proposal);
 }
 
+#[tokio::test]
+async fn test_create_service_nervous_system_proposal() {
+    // Step 1: Prepare the world.
+
+    // The canisters that SNS-W will (pretend to) create, and the dapp canister
+    // that is to be handed over to the new SNS.
+    let sns_root_canister_id = CanisterId::from_u64(1000).get();
+    let sns_swap_canister_id = CanisterId::from_u64(1001).get();
+    let dapp_canister_id = CanisterId::from_u64(2000).get();
+
+    let sns_init_payload = SnsInitPayload::with_valid_values_for_testing();
+    let swap_params = sns_swap_pb::Params {
+        sns_token_e8s: 1_000_000,
+        min_icp_e8s: 1,
+        max_icp_e8s: 42_000,
+        min_participant_icp_e8s: 1,
+        max_participant_icp_e8s: 42_000,
+        min_participants: 1,
+        swap_due_timestamp_seconds: DEFAULT_TEST_START_TIMESTAMP_SECONDS + 2 * ONE_DAY_SECONDS,
+        neuron_basket_construction_parameters: Some(
+            sns_swap_pb::params::NeuronBasketConstructionParameters {
+                count: 3,
+                dissolve_delay_interval_seconds: 7890000, // 3 months
+            },
+        ),
+    };
+
+    // A single Community Fund neuron that has 100e-8 ICP in maturity.
+    let governance_proto = GovernanceProto {
+        economics: Some(NetworkEconomics::with_default_values()),
+        neurons: hashmap! {
+            1 => Neuron {
+                id: Some(NeuronId { id: 1 }),
+                controller: Some(principal(1)),
+                cached_neuron_stake_e8s: 100_000_000,
+                dissolve_state: Some(DissolveState::DissolveDelaySeconds(
+                    MAX_DISSOLVE_DELAY_SECONDS,
+                )),
+                maturity_e8s_equivalent: 100,
+                joined_community_fund_timestamp_seconds: Some(1),
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    };
+
+    let community_fund_investment_e8s = 50;
+    let cf_participants = vec![sns_swap_pb::CfParticipant {
+        hotkey_principal: principal(1).to_string(),
+        cf_neurons: vec![sns_swap_pb::CfNeuron {
+            nns_neuron_id: 1,
+            amount_icp_e8s: community_fund_investment_e8s,
+        }],
+    }];
+    let expected_call_canister_method_calls: Arc<Mutex<VecDeque<_>>> = Arc::new(Mutex::new(
+        [
+            (
+                ExpectedCallCanisterMethodCallArguments {
+                    target: SNS_WASM_CANISTER_ID,
+                    method_name: "deploy_new_sns",
+                    request: Encode!(&DeployNewSnsRequest {
+                        sns_init_payload: Some(sns_init_payload.clone()),
+                    })
+                    .unwrap(),
+                },
+                Ok(Encode!(&DeployNewSnsResponse {
+                    subnet_id: None,
+                    canisters: Some(SnsCanisterIds {
+                        root: Some(sns_root_canister_id),
+                        swap: Some(sns_swap_canister_id),
+                        ..Default::default() // Not used by governance.
+                    }),
+                    error: None,
+                })
+                .unwrap()),
+            ),
+            (
+                ExpectedCallCanisterMethodCallArguments {
+                    target: CanisterId::ic_00(),
+                    method_name: "update_settings",
+                    request: Encode!(&UpdateSettingsArgs {
+                        canister_id: dapp_canister_id,
+                        settings: CanisterSettingsArgs {
+                            controllers: Some(vec![sns_root_canister_id]),
+                            controller: None,
+                            compute_allocation: None,
+                            memory_allocation: None,
+                            freezing_threshold: None,
+                        },
+                    })
+                    .unwrap(),
+                },
+                Ok(Encode!(&()).unwrap()),
+            ),
+            (
+                ExpectedCallCanisterMethodCallArguments {
+                    target: CanisterId::try_from(sns_root_canister_id).unwrap(),
+                    method_name: "register_dapp_canister",
+                    request: Encode!(&RegisterDappCanisterRequest {
+                        canister_id: Some(dapp_canister_id),
+                    })
+                    .unwrap(),
+                },
+                Ok(Encode!(&RegisterDappCanisterResponse {}).unwrap()),
+            ),
+            (
+                ExpectedCallCanisterMethodCallArguments {
+                    target: CanisterId::try_from(sns_swap_canister_id).unwrap(),
+                    method_name: "open",
+                    request: Encode!(&sns_swap_pb::OpenRequest {
+                        params: Some(swap_params.clone()),
+                        cf_participants: cf_participants.clone(),
+                        open_sns_token_swap_proposal_id: Some(1),
+                    })
+                    .unwrap(),
+                },
+                Ok(Encode!(&sns_swap_pb::OpenResponse {}).unwrap()),
+            ),
+        ]
+        .into(),
+    ));
+
+    let driver = fake::FakeDriver::default();
+    let mut gov = Governance::new(
+        governance_proto,
+        Box::new(MockEnvironment {
+            expected_call_canister_method_calls: Arc::clone(&expected_call_canister_method_calls),
+        }),
+        driver.get_fake_ledger(),
+        driver.get_fake_cmc(),
+    );
+
+    // Step 2: Run code under test. This is done indirectly via proposal.
+    gov.make_proposal(
+        &NeuronId { id: 1 },
+        &principal(1),
+        &Proposal {
+            title: Some("Create Service Nervous System".to_string()),
+            summary: "".to_string(),
+            action: Some(proposal::Action::CreateServiceNervousSystem(
+                CreateServiceNervousSystem {
+                    sns_init_payload: Some(sns_init_payload),
+                    swap_params: Some(swap_params),
+                    community_fund_investment_e8s: Some(community_fund_investment_e8s),
+                    dapp_canisters: vec![dapp_canister_id],
+                },
+            )),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    // Step 3: Inspect results.
+
+    // Step 3.1: Make sure expected canister call(s) take place.
+    assert!(
+        expected_call_canister_method_calls
+            .lock()
+            .unwrap()
+            .is_empty(),
+        "Calls that should have been made, but were not: {:#?}",
+        expected_call_canister_method_calls,
+    );
+
+    // Step 3.2: Inspect the proposal.
+    assert_eq!(gov.proto.proposals.len(), 1, "{:#?}", gov.proto.proposals);
+    let proposal = gov.proto.proposals.values().next().unwrap();
+    assert_eq!(
+        proposal.executed_timestamp_seconds, DEFAULT_TEST_START_TIMESTAMP_SECONDS,
+        "{:#?}",
+        proposal
+    );
+    assert_eq!(proposal.failure_reason, None, "{:#?}", proposal);
+    assert_eq!(
+        proposal.deployed_sns_swap_canister_id,
+        Some(sns_swap_canister_id)
+    );
+    assert_eq!(proposal.cf_participants, cf_participants);
+    assert_eq!(
+        proposal.sns_token_swap_lifecycle,
+        Some(sns_swap_pb::Lifecycle::Open as i32)
+    );
+
+    // Step 3.3: Only the swap canister of the new SNS may settle the
+    // Community Fund participation.
+    let request = SettleCommunityFundParticipation {
+        open_sns_token_swap_proposal_id: Some(1),
+        result: Some(settle_community_fund_participation::Result::Aborted(
+            settle_community_fund_participation::Aborted {},
+        )),
+    };
+    let result = gov
+        .settle_community_fund_participation(dapp_canister_id, &request)
+        .await;
+    assert_matches!(
+        result,
+        Err(GovernanceError { error_type, .. }) if error_type == ErrorType::NotAuthorized as i32
+    );
+    gov.settle_community_fund_participation(sns_swap_canister_id, &request)
+        .await
+        .unwrap();
+    assert_eq!(
+        gov.proto.proposals[&1].sns_token_swap_lifecycle,
+        Some(sns_swap_pb::Lifecycle::Aborted as i32)
+    );
+    assert_eq!(
+        gov.proto.neurons[&1].maturity_e8s_equivalent, 100,
+        "{:#?}",
+        gov.proto.neurons[&1]
+    );
+}
+
+#[tokio::test]
+async fn test_create_service_nervous_system_proposal_restores_dapp_controllers_on_failure() {
+    // Step 1: Prepare the world.
+
+    // Handing over the first dapp canister succeeds, but registering the
+    // second one with SNS root fails after root has already taken control of
+    // it.
+    let sns_root_canister_id = CanisterId::from_u64(1000).get();
+    let sns_swap_canister_id = CanisterId::from_u64(1001).get();
+    let dapp_canister_ids = vec![
+        CanisterId::from_u64(2000).get(),
+        CanisterId::from_u64(2001).get(),
+        CanisterId::from_u64(2002).get(),
+    ];
+
+    let sns_init_payload = SnsInitPayload::with_valid_values_for_testing();
+    let swap_params = sns_swap_pb::Params {
+        sns_token_e8s: 1_000_000,
+        min_icp_e8s: 1,
+        max_icp_e8s: 42_000,
+        min_participant_icp_e8s: 1,
+        max_participant_icp_e8s: 42_000,
+        min_participants: 1,
+        swap_due_timestamp_seconds: DEFAULT_TEST_START_TIMESTAMP_SECONDS + 2 * ONE_DAY_SECONDS,
+        neuron_basket_construction_parameters: Some(
+            sns_swap_pb::params::NeuronBasketConstructionParameters {
+                count: 3,
+                dissolve_delay_interval_seconds: 7890000, // 3 months
+            },
+        ),
+    };
+
+    let governance_proto = GovernanceProto {
+        economics: Some(NetworkEconomics::with_default_values()),
+        neurons: hashmap! {
+            1 => Neuron {
+                id: Some(NeuronId { id: 1 }),
+                controller: Some(principal(1)),
+                cached_neuron_stake_e8s: 100_000_000,
+                dissolve_state: Some(DissolveState::DissolveDelaySeconds(
+                    MAX_DISSOLVE_DELAY_SECONDS,
+                )),
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    };
+
+    let register = |dapp_canister_id: PrincipalId,
+                    register_result: Result<Vec<u8>, (Option<i32>, String)>| {
+        (
+            ExpectedCallCanisterMethodCallArguments {
+                target: CanisterId::try_from(sns_root_canister_id).unwrap(),
+                method_name: "register_dapp_canister",
+                request: Encode!(&RegisterDappCanisterRequest {
+                    canister_id: Some(dapp_canister_id),
+                })
+                .unwrap(),
+            },
+            register_result,
+        )
+    };
+    let hand_over = |dapp_canister_id: PrincipalId,
+                     register_result: Result<Vec<u8>, (Option<i32>, String)>| {
+        vec![
+            (
+                ExpectedCallCanisterMethodCallArguments {
+                    target: CanisterId::ic_00(),
+                    method_name: "update_settings",
+                    request: Encode!(&UpdateSettingsArgs {
+                        canister_id: dapp_canister_id,
+                        settings: CanisterSettingsArgs {
+                            controllers: Some(vec![sns_root_canister_id]),
+                            controller: None,
+                            compute_allocation: None,
+                            memory_allocation: None,
+                            freezing_threshold: None,
+                        },
+                    })
+                    .unwrap(),
+                },
+                Ok(Encode!(&()).unwrap()),
+            ),
+            register(dapp_canister_id, register_result),
+        ]
+    };
+    let mut expected_calls = vec![(
+        ExpectedCallCanisterMethodCallArguments {
+            target: SNS_WASM_CANISTER_ID,
+            method_name: "deploy_new_sns",
+            request: Encode!(&DeployNewSnsRequest {
+                sns_init_payload: Some(sns_init_payload.clone()),
+            })
+            .unwrap(),
+        },
+        Ok(Encode!(&DeployNewSnsResponse {
+            subnet_id: None,
+            canisters: Some(SnsCanisterIds {
+                root: Some(sns_root_canister_id),
+                swap: Some(sns_swap_canister_id),
+                ..Default::default() // Not used by governance.
+            }),
+            error: None,
+        })
+        .unwrap()),
+    )];
+    expected_calls.extend(hand_over(
+        dapp_canister_ids[0],
+        Ok(Encode!(&RegisterDappCanisterResponse {}).unwrap()),
+    ));
+    expected_calls.extend(hand_over(
+        dapp_canister_ids[1],
+        Err((Some(5), "Root is out of cycles.".to_string())),
+    ));
+    // The third dapp canister is not touched. Instead, the second one is
+    // registered with root after all, so that root gives it back too when the
+    // swap asks root to give the dapp canisters back to the fallback
+    // controllers.
+    expected_calls.push(register(
+        dapp_canister_ids[1],
+        Ok(Encode!(&RegisterDappCanisterResponse {}).unwrap()),
+    ));
+    expected_calls.push((
+        ExpectedCallCanisterMethodCallArguments {
+            target: CanisterId::try_from(sns_swap_canister_id).unwrap(),
+            method_name: "restore_dapp_controllers",
+            request: Encode!(&sns_swap_pb::RestoreDappControllersRequest {}).unwrap(),
+        },
+        Ok(Encode!(&sns_swap_pb::RestoreDappControllersResponse {
+            possibility: Some(
+                sns_swap_pb::restore_dapp_controllers_response::Possibility::Ok(
+                    sns_swap_pb::SetDappControllersResponse {
+                        failed_updates: vec![],
+                    },
+                ),
+            ),
+        })
+        .unwrap()),
+    ));
+    let expected_call_canister_method_calls: Arc<Mutex<VecDeque<_>>> =
+        Arc::new(Mutex::new(expected_calls.into()));
+
+    let driver = fake::FakeDriver::default();
+    let mut gov = Governance::new(
+        governance_proto,
+        Box::new(MockEnvironment {
+            expected_call_canister_method_calls: Arc::clone(&expected_call_canister_method_calls),
+        }),
+        driver.get_fake_ledger(),
+        driver.get_fake_cmc(),
+    );
+
+    // Step 2: Run code under test. This is done indirectly via proposal.
+    gov.make_proposal(
+        &NeuronId { id: 1 },
+        &principal(1),
+        &Proposal {
+            title: Some("Create Service Nervous System".to_string()),
+            summary: "".to_string(),
+            action: Some(proposal::Action::CreateServiceNervousSystem(
+                CreateServiceNervousSystem {
+                    sns_init_payload: Some(sns_init_payload),
+                    swap_params: Some(swap_params),
+                    community_fund_investment_e8s: None,
+                    dapp_canisters: dapp_canister_ids.clone(),
+                },
+            )),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    // Step 3: Inspect results.
+
+    // Step 3.1: Make sure expected canister call(s) take place, and that the
+    // swap was never opened.
+    assert!(
+        expected_call_canister_method_calls
+            .lock()
+            .unwrap()
+            .is_empty(),
+        "Calls that should have been made, but were not: {:#?}",
+        expected_call_canister_method_calls,
+    );
+
+    // Step 3.2: The proposal failed, but records the SNS that was deployed and
+    // how far the hand over got.
+    let proposal = &gov.proto.proposals[&1];
+    assert_eq!(proposal.executed_timestamp_seconds, 0, "{:#?}", proposal);
+    assert_eq!(
+        proposal.failed_timestamp_seconds, DEFAULT_TEST_START_TIMESTAMP_SECONDS,
+        "{:#?}",
+        proposal
+    );
+    assert_eq!(
+        proposal.deployed_sns_swap_canister_id,
+        Some(sns_swap_canister_id)
+    );
+    assert_eq!(proposal.sns_token_swap_lifecycle, None);
+    let failure_reason = proposal.failure_reason.as_ref().unwrap();
+    let error_message = &failure_reason.error_message;
+    assert_eq!(
+        failure_reason.error_type,
+        ErrorType::External as i32,
+        "{:#?}",
+        failure_reason
+    );
+    for expected in [
+        format!("Dapp canister {}", dapp_canister_ids[1]),
+        "Root is out of cycles.".to_string(),
+        format!("{:?}", vec![dapp_canister_ids[0]]),
+        "Dapp canister controllers restored".to_string(),
+    ] {
+        assert!(error_message.contains(&expected), "{}", error_message);
+    }
+    assert!(!error_message.contains("by hand"), "{}", error_message);
+}
+
 #[tokio::test]
 async fn distribute_rewards_load_test() {
     // Step 1: Prepare the world.
@@ -11152,3 +12062,536 @@ fn test_accept_reasonable_proposal_title() {
     let result = validate_proposal_title(&Some("When In The Course of Human Events".to_string()));
     assert!(result.is_ok());
 }
+
+/// Checks that the voting power of a neuron starts decreasing once the neuron
+/// has not refreshed it for a while, that refreshing it restores it in full,
+/// and that the following of a neuron without voting power is cleared
+/// (except on the NeuronManagement topic).
+#[test]
+fn test_voting_power_decreases_without_refresh_and_following_is_cleared() {
+    let voting_power_economics = VotingPowerEconomics::with_default_values();
+    let start_reducing = voting_power_economics.get_start_reducing_voting_power_after_seconds();
+    let clear_following = voting_power_economics.get_clear_following_after_seconds();
+
+    let mut nns = NNSBuilder::new()
+        .add_neuron(
+            NeuronBuilder::new(1, 100_000_000, principal(1))
+                .set_dissolve_delay(ONE_YEAR_SECONDS)
+                .add_followees(
+                    Topic::Governance as i32,
+                    neuron::Followees {
+                        followees: vec![NeuronId { id: 2 }],
+                    },
+                )
+                .add_followees(
+                    Topic::NeuronManagement as i32,
+                    neuron::Followees {
+                        followees: vec![NeuronId { id: 2 }],
+                    },
+                ),
+        )
+        .add_neuron(
+            NeuronBuilder::new(2, 100_000_000, principal(2)).set_dissolve_delay(ONE_YEAR_SECONDS),
+        )
+        .create();
+    let id = NeuronId { id: 1 };
+
+    // Right up to the start of the reduction, the neuron keeps all its voting power.
+    nns.advance_time_by(start_reducing);
+    let info = nns.governance.get_neuron_info(&id).unwrap();
+    assert_eq!(info.potential_voting_power, Some(info.voting_power));
+    assert!(info.voting_power > 0);
+
+    // Half way through the reduction, about half of it is left.
+    nns.advance_time_by(clear_following / 2);
+    let info = nns.governance.get_neuron_info(&id).unwrap();
+    let potential_voting_power = info.potential_voting_power.unwrap();
+    assert!(info.voting_power < potential_voting_power);
+    assert!(info.voting_power >= potential_voting_power / 2 - 1);
+    assert!(info.voting_power <= potential_voting_power / 2 + 1);
+
+    // Only the controller or a hot key can refresh the voting power.
+    let refresh = ManageNeuron {
+        id: None,
+        neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(id.clone())),
+        command: Some(Command::RefreshVotingPower(
+            manage_neuron::RefreshVotingPower {},
+        )),
+    };
+    let response = nns
+        .governance
+        .manage_neuron(&principal(2), &refresh)
+        .now_or_never()
+        .unwrap();
+    assert_matches!(
+        response.command,
+        Some(CommandResponse::Error(err)) if err.error_type == NotAuthorized as i32
+    );
+    let response = nns
+        .governance
+        .manage_neuron(&principal(1), &refresh)
+        .now_or_never()
+        .unwrap();
+    assert_eq!(
+        response.command,
+        Some(CommandResponse::RefreshVotingPower(
+            manage_neuron_response::RefreshVotingPowerResponse {}
+        ))
+    );
+
+    let info = nns.governance.get_neuron_info(&id).unwrap();
+    assert_eq!(info.potential_voting_power, Some(info.voting_power));
+    assert_eq!(
+        info.voting_power_refreshed_timestamp_seconds,
+        Some(nns.now())
+    );
+
+    // Once the voting power is gone, the periodic tasks clear the following
+    // of the neuron, except on the NeuronManagement topic.
+    nns.advance_time_by(start_reducing + clear_following);
+    let info = nns.governance.get_neuron_info(&id).unwrap();
+    assert_eq!(info.voting_power, 0);
+    assert!(info.potential_voting_power.unwrap() > 0);
+
+    nns.run_periodic_tasks();
+    let followees = &nns.get_neuron(&id).followees;
+    assert_eq!(
+        followees.keys().copied().collect::<Vec<_>>(),
+        vec![Topic::NeuronManagement as i32]
+    );
+}
+
+/// Checks that neurons are private by default, that their controller can make
+/// them public, that anyone can read the full details of public (and known)
+/// neurons, and that public neurons can be listed page by page.
+#[test]
+fn test_neuron_visibility_and_list_public_neurons() {
+    let mut nns = NNSBuilder::new()
+        .add_neuron(NeuronBuilder::new(1, 100_000_000, principal(1)))
+        .add_neuron(NeuronBuilder::new(2, 100_000_000, principal(2)).set_known_neuron_data("Known"))
+        .add_neuron(NeuronBuilder::new(3, 100_000_000, principal(3)))
+        .add_neuron(NeuronBuilder::new(4, 100_000_000, principal(4)))
+        .create();
+    let stranger = principal(5);
+
+    // Neurons are private by default.
+    let info = nns.governance.get_neuron_info(&NeuronId { id: 1 }).unwrap();
+    assert_eq!(info.visibility, Some(Visibility::Private as i32));
+    assert_matches!(
+        nns.governance.get_full_neuron(&NeuronId { id: 1 }, &stranger),
+        Err(err) if err.error_type == NotAuthorized as i32
+    );
+    // Known neurons are public.
+    assert_eq!(
+        nns.governance
+            .list_public_neurons(&ListPublicNeurons::default()),
+        ListPublicNeuronsResponse {
+            full_neurons: vec![nns.get_neuron(&NeuronId { id: 2 }).clone()],
+            total_pages_available: 1,
+        }
+    );
+
+    // Only the controller can make a neuron public.
+    let set_visibility = |visibility: Visibility| ManageNeuron {
+        id: None,
+        neuron_id_or_subaccount: None,
+        command: Some(Command::Configure(Configure {
+            operation: Some(Operation::SetVisibility(SetVisibility {
+                visibility: Some(visibility as i32),
+            })),
+        })),
+    };
+    for id in [1, 3, 4] {
+        let response = nns
+            .governance
+            .manage_neuron(
+                &principal(id),
+                &ManageNeuron {
+                    neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId { id })),
+                    ..set_visibility(Visibility::Public)
+                },
+            )
+            .now_or_never()
+            .unwrap();
+        assert_eq!(
+            response.command,
+            Some(CommandResponse::Configure(
+                manage_neuron_response::ConfigureResponse {}
+            ))
+        );
+    }
+    let response = nns
+        .governance
+        .manage_neuron(
+            &stranger,
+            &ManageNeuron {
+                neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId { id: 2 })),
+                ..set_visibility(Visibility::Public)
+            },
+        )
+        .now_or_never()
+        .unwrap();
+    assert_matches!(
+        response.command,
+        Some(CommandResponse::Error(err)) if err.error_type == NotAuthorized as i32
+    );
+
+    // Anyone can read a public neuron.
+    let info = nns.governance.get_neuron_info(&NeuronId { id: 1 }).unwrap();
+    assert_eq!(info.visibility, Some(Visibility::Public as i32));
+    assert_eq!(
+        nns.governance
+            .get_full_neuron(&NeuronId { id: 1 }, &stranger)
+            .unwrap(),
+        *nns.get_neuron(&NeuronId { id: 1 })
+    );
+
+    // Known neurons are public, and cannot be made private.
+    assert!(nns
+        .governance
+        .get_full_neuron(&NeuronId { id: 2 }, &stranger)
+        .is_ok());
+    let response = nns
+        .governance
+        .manage_neuron(
+            &principal(2),
+            &ManageNeuron {
+                neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId { id: 2 })),
+                ..set_visibility(Visibility::Private)
+            },
+        )
+        .now_or_never()
+        .unwrap();
+    assert_matches!(
+        response.command,
+        Some(CommandResponse::Error(err)) if err.error_type == PreconditionFailed as i32
+    );
+
+    // Public neurons are listed in order of neuron ID, one page at a time.
+    let list_page = |governance: &Governance, page_number: u64| {
+        let response = governance.list_public_neurons(&ListPublicNeurons {
+            page_number: Some(page_number),
+            page_size: Some(3),
+        });
+        (
+            response
+                .full_neurons
+                .iter()
+                .map(|neuron| neuron.id.as_ref().unwrap().id)
+                .collect::<Vec<_>>(),
+            response.total_pages_available,
+        )
+    };
+    assert_eq!(list_page(&nns.governance, 0), (vec![1, 2, 3], 2));
+    assert_eq!(list_page(&nns.governance, 1), (vec![4], 2));
+    assert_eq!(list_page(&nns.governance, 2), (vec![], 2));
+
+    // A neuron that is made private again is no longer listed.
+    let response = nns
+        .governance
+        .manage_neuron(
+            &principal(3),
+            &ManageNeuron {
+                neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId { id: 3 })),
+                ..set_visibility(Visibility::Private)
+            },
+        )
+        .now_or_never()
+        .unwrap();
+    assert_matches!(response.command, Some(CommandResponse::Configure(_)));
+    assert_eq!(list_page(&nns.governance, 0), (vec![1, 2, 4], 1));
+}
+
+/// Public neurons are listed, and stop being listed once they are made
+/// private, while they move between the heap and stable memory.
+#[test]
+fn test_list_public_neurons_in_stable_memory() {
+    let driver = fake::FakeDriver::default();
+    let mut gov = Governance::new(
+        fixture_for_manage_neuron(),
+        driver.get_fake_env(),
+        driver.get_fake_ledger(),
+        driver.get_fake_cmc(),
+    );
+    let set_visibility = |gov: &mut Governance, visibility: Visibility| {
+        let response = gov
+            .manage_neuron(
+                &principal(5),
+                &ManageNeuron {
+                    id: None,
+                    neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId {
+                        id: 5,
+                    })),
+                    command: Some(Command::Configure(Configure {
+                        operation: Some(Operation::SetVisibility(SetVisibility {
+                            visibility: Some(visibility as i32),
+                        })),
+                    })),
+                },
+            )
+            .now_or_never()
+            .unwrap();
+        assert_matches!(response.command, Some(CommandResponse::Configure(_)));
+    };
+    let list_public_neuron_ids = |gov: &Governance| {
+        gov.list_public_neurons(&ListPublicNeurons::default())
+            .full_neurons
+            .iter()
+            .map(|neuron| neuron.id.as_ref().unwrap().id)
+            .collect::<Vec<_>>()
+    };
+
+    set_visibility(&mut gov, Visibility::Public);
+    assert_eq!(list_public_neuron_ids(&gov), vec![5]);
+
+    assert_eq!(gov.move_neurons_to_stable_memory(), Ok(false));
+    assert!(gov.proto.neurons.is_empty());
+    assert!(gov.public_neuron_ids_index.is_empty());
+    assert_eq!(list_public_neuron_ids(&gov), vec![5]);
+
+    // Managing the neuron moves it back to the heap.
+    set_visibility(&mut gov, Visibility::Private);
+    assert!(gov.proto.neurons.contains_key(&5));
+    assert!(list_public_neuron_ids(&gov).is_empty());
+}
+
+#[test]
+fn test_set_following() {
+    let mut builder = NNSBuilder::new();
+    for id in 1..=110 {
+        builder = builder.add_neuron(NeuronBuilder::new(id, 100_000_000, principal(id)));
+    }
+    let mut nns = builder.create();
+
+    let set_following =
+        |governance: &mut Governance, caller: u64, topic_following: Vec<FolloweesForTopic>| {
+            governance
+                .manage_neuron(
+                    &principal(caller),
+                    &ManageNeuron {
+                        id: None,
+                        neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId {
+                            id: 1,
+                        })),
+                        command: Some(Command::SetFollowing(SetFollowing { topic_following })),
+                    },
+                )
+                .now_or_never()
+                .unwrap()
+                .command
+                .unwrap()
+        };
+    let followees_for_topic = |topic: Topic, ids: Vec<u64>| FolloweesForTopic {
+        topic: topic as i32,
+        followees: ids.into_iter().map(|id| NeuronId { id }).collect(),
+    };
+    let followees_of_neuron_1 = |governance: &Governance| {
+        let mut followees = governance
+            .get_full_neuron(&NeuronId { id: 1 }, &principal(1))
+            .unwrap()
+            .followees
+            .iter()
+            .map(|(topic, followees)| {
+                (
+                    *topic,
+                    followees.followees.iter().map(|f| f.id).collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        followees.sort();
+        followees
+    };
+
+    // Following can be set on several topics at once.
+    assert_eq!(
+        set_following(
+            &mut nns.governance,
+            1,
+            vec![
+                followees_for_topic(Topic::NeuronManagement, vec![2]),
+                followees_for_topic(Topic::Governance, vec![2, 3]),
+                followees_for_topic(Topic::Unspecified, vec![4]),
+            ]
+        ),
+        CommandResponse::SetFollowing(manage_neuron_response::SetFollowingResponse {})
+    );
+    let expected_followees = vec![
+        (Topic::Unspecified as i32, vec![4]),
+        (Topic::NeuronManagement as i32, vec![2]),
+        (Topic::Governance as i32, vec![2, 3]),
+    ];
+    assert_eq!(followees_of_neuron_1(&nns.governance), expected_followees);
+    let mut managed = nns.governance.get_managed_neuron_ids_for(&[2]);
+    managed.sort_unstable();
+    assert_eq!(managed, vec![1, 2]);
+
+    // Only the controller can set following, and only if all the topics and
+    // followees are valid. Otherwise, nothing changes.
+    assert_matches!(
+        set_following(
+            &mut nns.governance,
+            2,
+            vec![followees_for_topic(Topic::Governance, vec![3])]
+        ),
+        CommandResponse::Error(err) if err.error_type == NotAuthorized as i32
+    );
+    match set_following(
+        &mut nns.governance,
+        1,
+        vec![
+            followees_for_topic(Topic::Governance, vec![]),
+            followees_for_topic(Topic::NetworkEconomics, vec![3, 999]),
+            followees_for_topic(Topic::ExchangeRate, vec![5, 5]),
+            followees_for_topic(Topic::Kyc, (2..=17).collect()),
+        ],
+    ) {
+        CommandResponse::Error(err) => {
+            assert_eq!(err.error_type, InvalidCommand as i32, "{:?}", err);
+            assert!(
+                err.error_message
+                    .contains("NetworkEconomics: neuron 999 does not exist"),
+                "{}",
+                err.error_message
+            );
+            assert!(
+                err.error_message
+                    .contains("ExchangeRate: neuron 5 is specified more than once"),
+                "{}",
+                err.error_message
+            );
+            assert!(
+                err.error_message.contains("Kyc: too many followees"),
+                "{}",
+                err.error_message
+            );
+            assert!(
+                !err.error_message.contains("Governance"),
+                "{}",
+                err.error_message
+            );
+        }
+        response => panic!("Unexpected response: {:?}", response),
+    }
+    assert_eq!(followees_of_neuron_1(&nns.governance), expected_followees);
+
+    // The total number of followees of a neuron is capped.
+    let topics = [
+        Topic::ExchangeRate,
+        Topic::NetworkEconomics,
+        Topic::NodeAdmin,
+        Topic::ParticipantManagement,
+        Topic::SubnetManagement,
+        Topic::NetworkCanisterManagement,
+        Topic::Kyc,
+    ];
+    let topic_following = topics
+        .iter()
+        .enumerate()
+        .map(|(i, topic)| {
+            let first = 5 + 15 * i as u64;
+            followees_for_topic(*topic, (first..first + 15).collect())
+        })
+        .collect::<Vec<_>>();
+    assert!(topics.len() * 15 + 4 > MAX_FOLLOWEES_PER_NEURON);
+    assert_matches!(
+        set_following(&mut nns.governance, 1, topic_following),
+        CommandResponse::Error(err) if err.error_type == InvalidCommand as i32
+            && err.error_message.contains("Too many followees")
+    );
+    assert_eq!(followees_of_neuron_1(&nns.governance), expected_followees);
+
+    // An empty list of followees removes the following on that topic.
+    assert_eq!(
+        set_following(
+            &mut nns.governance,
+            1,
+            vec![followees_for_topic(Topic::NeuronManagement, vec![])]
+        ),
+        CommandResponse::SetFollowing(manage_neuron_response::SetFollowingResponse {})
+    );
+    assert_eq!(
+        followees_of_neuron_1(&nns.governance),
+        vec![
+            (Topic::Unspecified as i32, vec![4]),
+            (Topic::Governance as i32, vec![2, 3]),
+        ]
+    );
+    assert_eq!(nns.governance.get_managed_neuron_ids_for(&[2]), vec![2]);
+
+    // The Follow command is subject to the same cap.
+    let follow = |governance: &mut Governance, topic: Topic, ids: Vec<u64>| {
+        governance
+            .manage_neuron(
+                &principal(1),
+                &ManageNeuron {
+                    id: None,
+                    neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId {
+                        id: 1,
+                    })),
+                    command: Some(Command::Follow(Follow {
+                        topic: topic as i32,
+                        followees: ids.into_iter().map(|id| NeuronId { id }).collect(),
+                    })),
+                },
+            )
+            .now_or_never()
+            .unwrap()
+            .command
+            .unwrap()
+    };
+    let (last_topic, other_topics) = topics.split_last().unwrap();
+    for (i, topic) in other_topics.iter().enumerate() {
+        let first = 5 + 15 * i as u64;
+        assert_eq!(
+            follow(&mut nns.governance, *topic, (first..first + 15).collect()),
+            CommandResponse::Follow(manage_neuron_response::FollowResponse {})
+        );
+    }
+    assert_matches!(
+        follow(&mut nns.governance, *last_topic, (95..110).collect()),
+        CommandResponse::Error(err) if err.error_type == InvalidCommand as i32
+            && err.error_message.contains("Too many followees")
+    );
+
+    // A neuron that already follows too many neurons (e.g. because it did so
+    // before the cap was introduced) can still change its following, as long
+    // as it does not follow more neurons afterwards.
+    nns.governance
+        .proto
+        .neurons
+        .get_mut(&1)
+        .unwrap()
+        .followees
+        .insert(
+            *last_topic as i32,
+            Followees {
+                followees: (95..110).map(|id| NeuronId { id }).collect(),
+            },
+        );
+    assert_eq!(
+        set_following(
+            &mut nns.governance,
+            1,
+            vec![followees_for_topic(Topic::Governance, vec![5])]
+        ),
+        CommandResponse::SetFollowing(manage_neuron_response::SetFollowingResponse {})
+    );
+    assert_eq!(
+        follow(&mut nns.governance, Topic::Unspecified, vec![6]),
+        CommandResponse::Follow(manage_neuron_response::FollowResponse {})
+    );
+    assert_matches!(
+        set_following(
+            &mut nns.governance,
+            1,
+            vec![followees_for_topic(Topic::NeuronManagement, vec![2])]
+        ),
+        CommandResponse::Error(err) if err.error_type == InvalidCommand as i32
+            && err.error_message.contains("Too many followees")
+    );
+    assert_matches!(
+        follow(&mut nns.governance, Topic::NeuronManagement, vec![2]),
+        CommandResponse::Error(err) if err.error_type == InvalidCommand as i32
+            && err.error_message.contains("Too many followees")
+    );
+}
@@ -2,6 +2,7 @@
 
 use clap::Parser;
 use ic_base_types::CanisterId;
+use ic_ledger_core::balances::InspectableBalancesStore;
 use ic_nns_constants::{
     CYCLES_MINTING_CANISTER_ID, GENESIS_TOKEN_CANISTER_ID, GOVERNANCE_CANISTER_ID,
     LEDGER_CANISTER_ID, REGISTRY_CANISTER_ID,
@@ -0,0 +1,163 @@
+use candid::{Decode, Encode, Nat};
+use canister_test::Project;
+use cycles_minting_canister::{
+    CyclesCanisterUpgradeArgs, CyclesLedgerAccount, NotifyError, NotifyMintCyclesArg,
+    NotifyMintCyclesSuccess, MEMO_MINT_CYCLES,
+};
+use ic_base_types::{CanisterId, PrincipalId};
+use ic_cycles_ledger::InitArgs as CyclesLedgerInitArgs;
+use ic_nns_constants::{CYCLES_MINTING_CANISTER_ID, LEDGER_CANISTER_ID};
+use ic_nns_test_utils::common::{build_cmc_wasm, NnsInitPayloadsBuilder};
+use ic_nns_test_utils::state_test_helpers::setup_nns_canisters;
+use ic_state_machine_tests::StateMachine;
+use ic_types::ingress::WasmResult;
+use icp_ledger::{
+    AccountIdentifier, ArchiveOptions, BlockIndex, Subaccount, Tokens, TransferArgs, TransferError,
+    DEFAULT_TRANSFER_FEE,
+};
+
+fn install_cycles_ledger(state_machine: &StateMachine) -> CanisterId {
+    state_machine
+        .install_canister(
+            Project::cargo_bin_maybe_from_env("ic-cycles-ledger", &[]).bytes(),
+            Encode!(&CyclesLedgerInitArgs {
+                transfer_fee: 100_000_000,
+                archive_options: ArchiveOptions {
+                    trigger_threshold: 1_000,
+                    num_blocks_to_archive: 500,
+                    node_max_memory_size_bytes: None,
+                    max_message_size_bytes: None,
+                    controller_id: PrincipalId::new_user_test_id(100),
+                    cycles_for_archive_creation: None,
+                    max_transactions_per_response: None,
+                },
+            })
+            .unwrap(),
+            None,
+        )
+        .unwrap()
+}
+
+fn reply_bytes(result: WasmResult, method: &str) -> Vec<u8> {
+    match result {
+        WasmResult::Reply(bytes) => bytes,
+        WasmResult::Reject(reason) => panic!("{} was rejected: {}", method, reason),
+    }
+}
+
+fn notify_mint_cycles(
+    state_machine: &StateMachine,
+    sender: PrincipalId,
+    block_index: BlockIndex,
+) -> Result<NotifyMintCyclesSuccess, NotifyError> {
+    let result = state_machine
+        .execute_ingress_as(
+            sender,
+            CYCLES_MINTING_CANISTER_ID,
+            "notify_mint_cycles",
+            Encode!(&NotifyMintCyclesArg {
+                block_index,
+                to_subaccount: None,
+                deposit_memo: None,
+            })
+            .unwrap(),
+        )
+        .unwrap();
+    Decode!(
+        &reply_bytes(result, "notify_mint_cycles"),
+        Result<NotifyMintCyclesSuccess, NotifyError>
+    )
+    .unwrap()
+}
+
+/// Converts ICP into cycles with `notify_mint_cycles` and checks that the CMC
+/// deposits the cycles to the caller's cycles ledger account.
+#[test]
+fn test_notify_mint_cycles_deposits_to_cycles_ledger() {
+    let user = PrincipalId::new_user_test_id(1);
+
+    let state_machine = StateMachine::new();
+    let nns_init_payloads = NnsInitPayloadsBuilder::new()
+        .with_ledger_account(
+            AccountIdentifier::new(user, None),
+            Tokens::from_tokens(100).unwrap(),
+        )
+        .build();
+    setup_nns_canisters(&state_machine, nns_init_payloads);
+
+    let cycles_ledger = install_cycles_ledger(&state_machine);
+    state_machine
+        .upgrade_canister(
+            CYCLES_MINTING_CANISTER_ID,
+            build_cmc_wasm().bytes(),
+            Encode!(&Some(CyclesCanisterUpgradeArgs {
+                cycles_ledger_canister_id: Some(cycles_ledger),
+                exchange_rate_canister: None,
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+    // Pay the CMC.
+    let result = state_machine
+        .execute_ingress_as(
+            user,
+            LEDGER_CANISTER_ID,
+            "transfer",
+            Encode!(&TransferArgs {
+                memo: MEMO_MINT_CYCLES,
+                amount: Tokens::from_tokens(10).unwrap(),
+                fee: DEFAULT_TRANSFER_FEE,
+                from_subaccount: None,
+                to: AccountIdentifier::new(
+                    CYCLES_MINTING_CANISTER_ID.get(),
+                    Some(Subaccount::from(&user))
+                )
+                .to_address(),
+                created_at_time: None,
+            })
+            .unwrap(),
+        )
+        .unwrap();
+    let block_index = Decode!(
+        &reply_bytes(result, "transfer"),
+        Result<BlockIndex, TransferError>
+    )
+    .unwrap()
+    .expect("failed to pay the CMC");
+
+    let ledger_cycles = state_machine.cycle_balance(cycles_ledger);
+    let success =
+        notify_mint_cycles(&state_machine, user, block_index).expect("failed to mint cycles");
+    assert!(success.minted > Nat::from(0u64));
+    assert_eq!(success.balance, success.minted);
+
+    // The cycles ledger holds the minted cycles on behalf of the user.
+    let result = state_machine
+        .query(
+            cycles_ledger,
+            "icrc1_balance_of",
+            Encode!(&CyclesLedgerAccount {
+                owner: user,
+                subaccount: None,
+            })
+            .unwrap(),
+        )
+        .unwrap();
+    let balance = Decode!(&reply_bytes(result, "icrc1_balance_of"), Nat).unwrap();
+    assert_eq!(balance, success.minted);
+    assert_eq!(
+        Nat::from(state_machine.cycle_balance(cycles_ledger) - ledger_cycles),
+        success.minted
+    );
+
+    // Notifying again returns the same result without minting more cycles.
+    assert_eq!(
+        notify_mint_cycles(&state_machine, user, block_index),
+        Ok(success)
+    );
+    assert_eq!(
+        Nat::from(state_machine.cycle_balance(cycles_ledger) - ledger_cycles),
+        balance
+    );
+}
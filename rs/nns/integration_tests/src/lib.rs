@@ -18,6 +18,9 @@ mod autonomy;
 #[cfg(test)]
 mod bad_input;
 
+#[cfg(test)]
+mod cycles_ledger;
+
 #[cfg(test)]
 mod cycles_minting_canister;
 
@@ -167,6 +167,17 @@ impl CanisterApi for CanisterApiImpl {
         Ok(available)
     }
 
+    fn this_canister_has_enough_cycles(&self, required_cycles: u64) -> Result<u64, String> {
+        let balance = dfn_core::api::canister_cycle_balance();
+        if balance < required_cycles {
+            return Err(format!(
+                "This canister requires at least {} cycles, but only has {} cycles.",
+                required_cycles, balance,
+            ));
+        }
+        Ok(balance)
+    }
+
     fn accept_message_cycles(&self, cycles: Option<u64>) -> Result<u64, String> {
         let cycles = cycles.unwrap_or_else(dfn_core::api::msg_cycles_available);
         self.message_has_enough_cycles(cycles)?;
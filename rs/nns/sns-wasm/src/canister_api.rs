@@ -36,6 +36,9 @@ pub trait CanisterApi {
     /// Return the cycles available, or fail if insufficient cycles are available.
     fn message_has_enough_cycles(&self, required_cycles: u64) -> Result<u64, String>;
 
+    /// Return the cycles balance of this canister, or fail if it holds fewer than required_cycles.
+    fn this_canister_has_enough_cycles(&self, required_cycles: u64) -> Result<u64, String>;
+
     /// Accept Some(number) of cycles, or if no cycles are given (i.e. None), accept all available cycles in the message
     fn accept_message_cycles(&self, cycles: Option<u64>) -> Result<u64, String>;
 
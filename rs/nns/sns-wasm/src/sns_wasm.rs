@@ -70,6 +70,9 @@ const ONE_BILLION: u64 = 1_000_000_000;
 
 const SNS_CREATION_FEE: u64 = 50 * ONE_TRILLION;
 const INITIAL_CANISTER_CREATION_CYCLES: u64 = 500 * ONE_BILLION;
+/// The cycles that must remain in this canister after it pays for an SNS
+/// deployed by NNS Governance, so that such deployments cannot drain it.
+const SNS_WASM_CYCLES_RESERVE: u64 = 50 * ONE_TRILLION;
 
 /// Internal implementation to give the wasms we explicitly handle a name (instead of Vec<u8>) for
 /// safer handling in our internal logic.  This is not intended to be persisted outside of method logic
@@ -331,7 +334,14 @@ where
             )
             .into();
         }
-        match Self::do_deploy_new_sns(thread_safe_sns, canister_api, deploy_new_sns_payload).await {
+        match Self::do_deploy_new_sns(
+            thread_safe_sns,
+            canister_api,
+            deploy_new_sns_payload,
+            caller,
+        )
+        .await
+        {
             Ok((subnet_id, canisters)) => DeployNewSnsResponse {
                 subnet_id: Some(subnet_id.get()),
                 canisters: Some(canisters),
@@ -346,15 +356,24 @@ where
         }
     }
 
+    /// NNS Governance may always deploy an SNS, as it does so when a
+    /// CreateServiceNervousSystem proposal is adopted. Such deployments are
+    /// only carried out while this canister can pay for them and still keep
+    /// SNS_WASM_CYCLES_RESERVE cycles (see do_deploy_new_sns).
     pub fn allowed_to_deploy_sns(&self, caller: PrincipalId) -> bool {
-        self.allowed_principals.contains(&caller)
+        caller == GOVERNANCE_CANISTER_ID.get() || self.allowed_principals.contains(&caller)
     }
 
     async fn do_deploy_new_sns(
         thread_safe_sns: &'static LocalKey<RefCell<SnsWasmCanister<M>>>,
         canister_api: &impl CanisterApi,
         deploy_new_sns_request: DeployNewSnsRequest,
+        caller: PrincipalId,
     ) -> Result<(SubnetId, SnsCanisterIds), DeployError> {
+        // SNSs deployed by NNS Governance are paid for with the cycles of
+        // this canister instead of the cycles attached to the request.
+        let paid_by_caller = caller != GOVERNANCE_CANISTER_ID.get();
+
         let sns_init_payload = deploy_new_sns_request
             .sns_init_payload
             // Validate presence
@@ -372,15 +391,26 @@ where
             .with(|sns_wasms| sns_wasms.borrow().get_latest_version_wasms())
             .map_err(validation_deploy_error)?;
 
-        // If the fee is not present, we fail.
-        canister_api
-            .message_has_enough_cycles(SNS_CREATION_FEE)
-            .map_err(validation_deploy_error)?;
+        // If the fee is not present, we fail. Likewise, if paying for the SNS
+        // ourselves would eat into our reserve.
+        if paid_by_caller {
+            canister_api
+                .message_has_enough_cycles(SNS_CREATION_FEE)
+                .map_err(validation_deploy_error)?;
+        } else {
+            canister_api
+                .this_canister_has_enough_cycles(SNS_CREATION_FEE + SNS_WASM_CYCLES_RESERVE)
+                .map_err(validation_deploy_error)?;
+        }
 
         // After this step, we need to delete the canisters if things fail
-        let canisters =
-            Self::create_sns_canisters(canister_api, subnet_id, INITIAL_CANISTER_CREATION_CYCLES)
-                .await?;
+        let canisters = Self::create_sns_canisters(
+            canister_api,
+            subnet_id,
+            INITIAL_CANISTER_CREATION_CYCLES,
+            paid_by_caller,
+        )
+        .await?;
         // This step should never fail unless the step before it fails which would return
         // an error.
         let sns_init_canister_ids = canisters.try_into().expect(
@@ -435,7 +465,7 @@ where
         // even if one fails, since we can no longer back out
         join_errors_or_ok(vec![
             // Accept all remaining cycles and fund the canisters
-            Self::fund_canisters(canister_api, &canisters).await,
+            Self::fund_canisters(canister_api, &canisters, paid_by_caller).await,
             // Remove self as the controller
             Self::remove_self_as_controller(canister_api, &canisters).await,
         ])
@@ -445,14 +475,20 @@ where
     }
 
     /// Accept remaining cycles in the request, subtract the cycles we've already used, and distribute
-    /// the remainder among the canisters
+    /// the remainder among the canisters. If the SNS is not paid by the caller, distribute the part
+    /// of the creation fee that was not used to create the canisters.
     async fn fund_canisters(
         canister_api: &impl CanisterApi,
         canisters: &SnsCanisterIds,
+        paid_by_caller: bool,
     ) -> Result<(), String> {
-        // Accept the remaining cycles in the request we need to fund the canisters
-        let remaining_unaccepted_cycles = canister_api.accept_message_cycles(None).unwrap();
-        let cycles_per_canister = remaining_unaccepted_cycles / 5;
+        let cycles_to_distribute = if paid_by_caller {
+            // Accept the remaining cycles in the request we need to fund the canisters
+            canister_api.accept_message_cycles(None).unwrap()
+        } else {
+            SNS_CREATION_FEE - INITIAL_CANISTER_CREATION_CYCLES * 5
+        };
+        let cycles_per_canister = cycles_to_distribute / 5;
 
         let results = futures::future::join_all(canisters.into_named_tuples().into_iter().map(
             |(label, canister_id)| async move {
@@ -645,20 +681,23 @@ where
         canister_api: &impl CanisterApi,
         subnet_id: SubnetId,
         initial_cycles_per_canister: u64,
+        paid_by_caller: bool,
     ) -> Result<SnsCanisterIds, DeployError> {
         // Accept enough cycles to simply create the canisters.
-        canister_api
-            .accept_message_cycles(Some(initial_cycles_per_canister.saturating_mul(5)))
-            .map_err(|e| {
-                DeployError::Reversible(RerversibleDeployError {
-                    message: format!(
-                        "Could not accept cycles from request needed to create canisters: {}",
-                        e
-                    ),
-                    canisters_to_delete: None,
-                    subnet: None,
-                })
-            })?;
+        if paid_by_caller {
+            canister_api
+                .accept_message_cycles(Some(initial_cycles_per_canister.saturating_mul(5)))
+                .map_err(|e| {
+                    DeployError::Reversible(RerversibleDeployError {
+                        message: format!(
+                            "Could not accept cycles from request needed to create canisters: {}",
+                            e
+                        ),
+                        canisters_to_delete: None,
+                        subnet: None,
+                    })
+                })?;
+        }
 
         let this_canister_id = canister_api.local_canister_id().get();
         let new_canister = || {
@@ -1103,6 +1142,8 @@ mod test {
         pub canisters_deleted: Arc<Mutex<Vec<CanisterId>>>,
         // How many cycles does the pretend request contain?
         pub cycles_found_in_request: Arc<Mutex<u64>>,
+        // How many cycles does the pretend canister hold?
+        pub cycles_in_this_canister: Arc<Mutex<u64>>,
         // Errors that can be thrown at some nth function call
         pub errors_on_create_canister: Arc<Mutex<Vec<Option<String>>>>,
         pub errors_on_set_controller: Arc<Mutex<Vec<Option<String>>>>,
@@ -1200,6 +1241,17 @@ mod test {
             Ok(amount)
         }
 
+        fn this_canister_has_enough_cycles(&self, required_cycles: u64) -> Result<u64, String> {
+            let amount = *self.cycles_in_this_canister.lock().unwrap();
+            if amount < required_cycles {
+                return Err(format!(
+                    "Not enough cycles in canister.  Required: {}. Found: {}",
+                    required_cycles, amount
+                ));
+            }
+            Ok(amount)
+        }
+
         async fn send_cycles_to_canister(
             &self,
             target_canister: CanisterId,
@@ -1232,6 +1284,9 @@ mod test {
             cycles_sent: Arc::new(Mutex::new(vec![])),
             canisters_deleted: Arc::new(Mutex::new(vec![])),
             cycles_found_in_request: Arc::new(Mutex::new(SNS_CREATION_FEE)),
+            cycles_in_this_canister: Arc::new(Mutex::new(
+                SNS_CREATION_FEE + SNS_WASM_CYCLES_RESERVE,
+            )),
             errors_on_create_canister: Arc::new(Mutex::new(vec![])),
             errors_on_set_controller: Arc::new(Mutex::new(vec![])),
             errors_on_delete_canister: Arc::new(Mutex::new(vec![])),
@@ -2386,4 +2441,82 @@ mod test {
             },
         )
     }
+
+    #[tokio::test]
+    async fn test_nns_governance_deploys_sns_with_cycles_of_sns_wasm() {
+        let mut canister_api = new_canister_api();
+        canister_api.cycles_found_in_request = Arc::new(Mutex::new(0));
+
+        thread_local! {
+            static CANISTER_WRAPPER: RefCell<SnsWasmCanister<TestCanisterStableMemory>> = RefCell::new(new_wasm_canister()) ;
+        }
+
+        CANISTER_WRAPPER.with(|c| {
+            c.borrow_mut().set_sns_subnets(vec![subnet_test_id(1)]);
+            add_mock_wasms(&mut c.borrow_mut());
+        });
+
+        let response = SnsWasmCanister::deploy_new_sns(
+            &CANISTER_WRAPPER,
+            &canister_api,
+            DeployNewSnsRequest {
+                sns_init_payload: Some(SnsInitPayload::with_valid_values_for_testing()),
+            },
+            GOVERNANCE_CANISTER_ID.get(),
+        )
+        .await;
+
+        assert_eq!(response.error, None);
+        assert!(response.canisters.is_some());
+        assert!(canister_api.cycles_accepted.lock().unwrap().is_empty());
+        let cycles_sent = canister_api.cycles_sent.lock().unwrap();
+        assert_eq!(cycles_sent.len(), 5);
+        assert!(cycles_sent
+            .iter()
+            .all(|(_, cycles)| *cycles == (SNS_CREATION_FEE - CANISTER_CREATION_CYCLES) / 5));
+    }
+
+    #[tokio::test]
+    async fn test_nns_governance_cannot_deploy_sns_into_cycles_reserve_of_sns_wasm() {
+        let mut canister_api = new_canister_api();
+        canister_api.cycles_found_in_request = Arc::new(Mutex::new(0));
+        let cycles_in_this_canister = SNS_CREATION_FEE + SNS_WASM_CYCLES_RESERVE - 1;
+        canister_api.cycles_in_this_canister = Arc::new(Mutex::new(cycles_in_this_canister));
+
+        thread_local! {
+            static CANISTER_WRAPPER: RefCell<SnsWasmCanister<TestCanisterStableMemory>> = RefCell::new(new_wasm_canister()) ;
+        }
+
+        CANISTER_WRAPPER.with(|c| {
+            c.borrow_mut().set_sns_subnets(vec![subnet_test_id(1)]);
+            add_mock_wasms(&mut c.borrow_mut());
+        });
+
+        let response = SnsWasmCanister::deploy_new_sns(
+            &CANISTER_WRAPPER,
+            &canister_api,
+            DeployNewSnsRequest {
+                sns_init_payload: Some(SnsInitPayload::with_valid_values_for_testing()),
+            },
+            GOVERNANCE_CANISTER_ID.get(),
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            DeployNewSnsResponse {
+                subnet_id: None,
+                canisters: None,
+                error: Some(SnsWasmError {
+                    message: format!(
+                        "Not enough cycles in canister.  Required: {}. Found: {}",
+                        SNS_CREATION_FEE + SNS_WASM_CYCLES_RESERVE,
+                        cycles_in_this_canister,
+                    ),
+                }),
+            }
+        );
+        assert_eq!(*canister_api.canisters_created.lock().unwrap(), 0);
+        assert!(canister_api.cycles_sent.lock().unwrap().is_empty());
+    }
 }
@@ -1,17 +1,20 @@
 use dfn_core::api::now;
 use ic_base_types::{CanisterId, PrincipalId};
+use ic_icrc1::Account;
+use ic_ledger_canister_core::approvals::{AllowanceTable, HeapAllowancesData};
 use ic_ledger_canister_core::archive::ArchiveCanisterWasm;
 use ic_ledger_canister_core::blockchain::Blockchain;
 use ic_ledger_canister_core::ledger::{self as core_ledger, LedgerData, TransactionInfo};
+use ic_ledger_canister_core::stable_memory::{MigrateToStable, StableBalances, StableBlockData};
 use ic_ledger_core::{
     balances::Balances,
-    block::{EncodedBlock, HashOf},
+    block::{EncodedBlock, FeeCollector, HashOf},
     timestamp::TimeStamp,
 };
 use ic_ledger_core::{block::BlockIndex, tokens::Tokens};
 use icp_ledger::{
-    AccountIdentifier, Block, LedgerBalances, Memo, Operation, PaymentError, Transaction,
-    TransferError, TransferFee, DEFAULT_TRANSFER_FEE,
+    AccountIdentifier, Block, Memo, Operation, PaymentError, Transaction, TransferError,
+    TransferFee, DEFAULT_TRANSFER_FEE,
 };
 use intmap::IntMap;
 use lazy_static::lazy_static;
@@ -58,10 +61,14 @@ fn unknown_token() -> String {
     "???".to_string()
 }
 
+/// The maximum number of balances and blocks moved to stable memory in a
+/// single migration step.
+const MIGRATION_BATCH_SIZE: usize = 10_000;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Ledger {
-    pub balances: LedgerBalances,
-    pub blockchain: Blockchain<dfn_runtime::DfnRuntime, IcpLedgerArchiveWasm>,
+    pub balances: Balances<AccountIdentifier, StableBalances<AccountIdentifier>>,
+    pub blockchain: Blockchain<dfn_runtime::DfnRuntime, IcpLedgerArchiveWasm, StableBlockData>,
     // A cap on the maximum number of accounts
     pub maximum_number_of_accounts: usize,
     // When maximum number of accounts is exceeded, a specified number of
@@ -97,6 +104,13 @@ pub struct Ledger {
     /// Token name
     #[serde(default = "unknown_token")]
     pub token_name: String,
+    /// The minting account in the ICRC-1 format, if it is known.
+    #[serde(default)]
+    pub icrc1_minting_account: Option<Account>,
+    /// The ICP ledger does not support approvals, so this table is always
+    /// empty.
+    #[serde(default)]
+    approvals: AllowanceTable<HeapAllowancesData<AccountIdentifier>>,
 }
 
 impl LedgerData for Ledger {
@@ -105,6 +119,9 @@ impl LedgerData for Ledger {
     type ArchiveWasm = IcpLedgerArchiveWasm;
     type Transaction = Transaction;
     type Block = Block;
+    type BalancesStore = StableBalances<AccountIdentifier>;
+    type AllowancesData = HeapAllowancesData<AccountIdentifier>;
+    type BlockData = StableBlockData;
 
     fn transaction_window(&self) -> Duration {
         self.transaction_window
@@ -134,19 +151,37 @@ impl LedgerData for Ledger {
         &self.token_symbol
     }
 
-    fn balances(&self) -> &Balances<Self::AccountId, HashMap<Self::AccountId, Tokens>> {
+    fn fee_collector(&self) -> Option<&FeeCollector<Self::AccountId>> {
+        None
+    }
+
+    fn fee_collector_mut(&mut self) -> Option<&mut FeeCollector<Self::AccountId>> {
+        None
+    }
+
+    fn balances(&self) -> &Balances<Self::AccountId, Self::BalancesStore> {
         &self.balances
     }
 
-    fn balances_mut(&mut self) -> &mut Balances<Self::AccountId, HashMap<Self::AccountId, Tokens>> {
+    fn balances_mut(&mut self) -> &mut Balances<Self::AccountId, Self::BalancesStore> {
         &mut self.balances
     }
 
-    fn blockchain(&self) -> &Blockchain<Self::Runtime, Self::ArchiveWasm> {
+    fn approvals(&self) -> &AllowanceTable<Self::AllowancesData> {
+        &self.approvals
+    }
+
+    fn approvals_mut(&mut self) -> &mut AllowanceTable<Self::AllowancesData> {
+        &mut self.approvals
+    }
+
+    fn blockchain(&self) -> &Blockchain<Self::Runtime, Self::ArchiveWasm, Self::BlockData> {
         &self.blockchain
     }
 
-    fn blockchain_mut(&mut self) -> &mut Blockchain<Self::Runtime, Self::ArchiveWasm> {
+    fn blockchain_mut(
+        &mut self,
+    ) -> &mut Blockchain<Self::Runtime, Self::ArchiveWasm, Self::BlockData> {
         &mut self.blockchain
     }
 
@@ -174,7 +209,7 @@ impl LedgerData for Ledger {
 impl Default for Ledger {
     fn default() -> Self {
         Self {
-            balances: LedgerBalances::default(),
+            balances: Balances::default(),
             blockchain: Blockchain::default(),
             maximum_number_of_accounts: 28_000_000,
             accounts_overflow_trim_quantity: 100_000,
@@ -188,6 +223,8 @@ impl Default for Ledger {
             transfer_fee: DEFAULT_TRANSFER_FEE,
             token_symbol: unknown_token(),
             token_name: unknown_token(),
+            icrc1_minting_account: None,
+            approvals: AllowanceTable::default(),
         }
     }
 }
@@ -202,6 +239,22 @@ impl Ledger {
     /// See Ledger::max_transactions_in_window
     const DEFAULT_MAX_TRANSACTIONS_IN_WINDOW: usize = 3_000_000;
 
+    /// Moves a batch of balances and blocks loaded from a legacy ledger state
+    /// to stable memory. Returns true if the migration is complete.
+    pub fn migrate_to_stable(&mut self) -> bool {
+        self.balances.store.migrate_to_stable(MIGRATION_BATCH_SIZE);
+        self.blockchain
+            .blocks
+            .migrate_to_stable(MIGRATION_BATCH_SIZE);
+        self.is_migrated()
+    }
+
+    /// Returns true if all the balances and blocks are stored in stable
+    /// memory.
+    pub fn is_migrated(&self) -> bool {
+        self.balances.store.is_migrated() && self.blockchain.blocks.is_migrated()
+    }
+
     /// This creates a block and adds it to the ledger
     pub fn add_payment(
         &mut self,
@@ -245,6 +298,11 @@ impl Ledger {
                 }),
                 CTE::TxCreatedInFuture { .. } => PTE(TE::TxCreatedInFuture),
                 CTE::TxDuplicate { duplicate_of } => PTE(TE::TxDuplicate { duplicate_of }),
+                CTE::InsufficientAllowance { .. }
+                | CTE::ExpiredApproval { .. }
+                | CTE::AllowanceChanged { .. } => {
+                    unreachable!("the ICP ledger does not support approvals")
+                }
                 CTE::TxThrottled => PaymentError::Reject(
                     concat!(
                         "Too many transactions in replay prevention window, ",
@@ -275,11 +333,13 @@ impl Ledger {
         transfer_fee: Option<Tokens>,
         token_symbol: Option<String>,
         token_name: Option<String>,
+        icrc1_minting_account: Option<Account>,
     ) {
         self.token_symbol = token_symbol.unwrap_or_else(|| "ICP".to_string());
         self.token_name = token_name.unwrap_or_else(|| "Internet Computer".to_string());
         self.balances.token_pool = Tokens::MAX;
         self.minting_account_id = Some(minting_account);
+        self.icrc1_minting_account = icrc1_minting_account;
         if let Some(t) = transaction_window {
             self.transaction_window = t;
         }
@@ -1,4 +1,4 @@
-use candid::candid_method;
+use candid::{candid_method, Nat};
 use dfn_candid::{candid, candid_one, CandidOne};
 use dfn_core::{
     api::{caller, data_certificate, print, set_certified_data, trap_with},
@@ -6,25 +6,38 @@ use dfn_core::{
 };
 use dfn_protobuf::protobuf;
 use ic_base_types::CanisterId;
+use ic_icrc1::{
+    endpoints::{StandardRecord, TransferArg, TransferError as Icrc1TransferError, Value},
+    Account, Memo as Icrc1Memo,
+};
 use ic_ledger_canister_core::{
     archive::{Archive, ArchiveOptions},
-    ledger::{archive_blocks, block_locations, find_block_in_archive, LedgerAccess},
+    blockchain::BlockData,
+    ledger::{
+        apply_transaction, archive_blocks, block_locations, find_block_in_archive,
+        upgrade_archives, LedgerAccess,
+    },
     range_utils,
+    stable_memory::{is_legacy_layout, UpgradesReader, UpgradesWriter},
 };
 use ic_ledger_core::{
+    balances::InspectableBalancesStore,
     block::{BlockIndex, BlockType, EncodedBlock},
     timestamp::TimeStamp,
     tokens::{Tokens, DECIMAL_PLACES},
 };
 use icp_ledger::{
     protobuf, tokens_into_proto, AccountBalanceArgs, AccountIdentifier, ArchiveInfo,
-    ArchivedBlocksRange, Archives, BinaryAccountBalanceArgs, Block, BlockArg, BlockRes,
-    CandidBlock, Decimals, GetBlocksArgs, IterBlocksArgs, LedgerCanisterInitPayload, Memo, Name,
-    Operation, PaymentError, QueryArchiveFn, QueryBlocksResponse, SendArgs, Subaccount, Symbol,
-    TipOfChainRes, TotalSupplyArgs, TransferArgs, TransferError, TransferFee, TransferFeeArgs,
+    ArchivedBlocksRange, Archives, ArchivesForAccountArgs, BinaryAccountBalanceArgs, Block,
+    BlockArg, BlockRes, CandidBlock, Decimals, GetBlocksArgs, IterBlocksArgs,
+    LedgerCanisterInitPayload, LedgerCanisterUpgradePayload, Memo, Name, Operation, PaymentError,
+    QueryArchiveFn, QueryBlocksResponse, SendArgs, Subaccount, Symbol, TipOfChainRes,
+    TotalSupplyArgs, Transaction, TransferArgs, TransferError, TransferFee, TransferFeeArgs,
     MAX_BLOCKS_PER_REQUEST,
 };
 use ledger_canister::{Ledger, LEDGER, MAX_MESSAGE_SIZE_BYTES};
+use num_traits::ToPrimitive;
+use serde_bytes::ByteBuf;
 use std::{
     collections::{HashMap, HashSet},
     sync::{Arc, RwLock},
@@ -49,6 +62,7 @@ use std::{
 /// * `transfer_fee` - The fee to pay to perform a transaction.
 /// * `token_symbol` - Token symbol.
 /// * `token_name` - Token name.
+/// * `icrc1_minting_account` - The minting account in the ICRC-1 format.
 #[allow(clippy::too_many_arguments)]
 fn init(
     minting_account: AccountIdentifier,
@@ -60,11 +74,19 @@ fn init(
     transfer_fee: Option<Tokens>,
     token_symbol: Option<String>,
     token_name: Option<String>,
+    icrc1_minting_account: Option<Account>,
 ) {
     print(format!(
         "[ledger] init(): minting account is {}",
         minting_account
     ));
+    if let Some(icrc1_minting_account) = &icrc1_minting_account {
+        assert_eq!(
+            AccountIdentifier::from(icrc1_minting_account.clone()),
+            minting_account,
+            "minting_account and icrc1_minting_account must refer to the same account"
+        );
+    }
     LEDGER.write().unwrap().from_init(
         initial_values,
         minting_account,
@@ -74,6 +96,7 @@ fn init(
         transfer_fee,
         token_symbol,
         token_name,
+        icrc1_minting_account,
     );
     match max_message_size_bytes {
         None => {
@@ -409,7 +432,7 @@ fn block(block_index: BlockIndex) -> Option<Result<EncodedBlock, CanisterId>> {
             "[ledger] Checking the ledger for block [{}]",
             block_index
         ));
-        state.blockchain.get(block_index).cloned().map(Ok)
+        state.blockchain.get(block_index).map(Ok)
     }
 }
 
@@ -419,6 +442,175 @@ fn account_balance(account: AccountIdentifier) -> Tokens {
     LEDGER.read().unwrap().balances.account_balance(&account)
 }
 
+/// The maximum length of the memos accepted by `icrc1_transfer`. The ICP
+/// ledger blocks store 64-bit memos, so longer memos cannot be recorded.
+const MAX_ICRC1_MEMO_BYTES: usize = std::mem::size_of::<u64>();
+
+/// Converts an ICRC-1 memo into a memo of the ICP ledger, interpreting the
+/// memo bytes as a big-endian number. Memos longer than
+/// `MAX_ICRC1_MEMO_BYTES` are rejected instead of being cut.
+fn icrc1_memo_to_memo(memo: Option<Icrc1Memo>) -> Result<Memo, Icrc1TransferError> {
+    let bytes = match memo {
+        Some(memo) => ByteBuf::from(memo).into_vec(),
+        None => return Ok(Memo::default()),
+    };
+    if bytes.len() > MAX_ICRC1_MEMO_BYTES {
+        return Err(Icrc1TransferError::GenericError {
+            error_code: Nat::from(0u64),
+            message: format!(
+                "the memo field is {} bytes long, the ledger only supports memos of at most {} bytes",
+                bytes.len(),
+                MAX_ICRC1_MEMO_BYTES
+            ),
+        });
+    }
+    let mut be_bytes = [0u8; MAX_ICRC1_MEMO_BYTES];
+    be_bytes[MAX_ICRC1_MEMO_BYTES - bytes.len()..].copy_from_slice(&bytes);
+    Ok(Memo(u64::from_be_bytes(be_bytes)))
+}
+
+#[candid_method(query, rename = "icrc1_name")]
+fn icrc1_name() -> String {
+    LEDGER.read().unwrap().token_name.clone()
+}
+
+#[candid_method(query, rename = "icrc1_symbol")]
+fn icrc1_symbol() -> String {
+    LEDGER.read().unwrap().token_symbol.clone()
+}
+
+#[candid_method(query, rename = "icrc1_decimals")]
+fn icrc1_decimals() -> u8 {
+    debug_assert!(DECIMAL_PLACES <= u8::MAX as u32);
+    DECIMAL_PLACES as u8
+}
+
+#[candid_method(query, rename = "icrc1_fee")]
+fn icrc1_fee() -> Nat {
+    Nat::from(LEDGER.read().unwrap().transfer_fee.get_e8s())
+}
+
+#[candid_method(query, rename = "icrc1_metadata")]
+fn icrc1_metadata() -> Vec<(String, Value)> {
+    let ledger = LEDGER.read().unwrap();
+    vec![
+        Value::entry("icrc1:decimals", DECIMAL_PLACES as u64),
+        Value::entry("icrc1:name", ledger.token_name.as_str()),
+        Value::entry("icrc1:symbol", ledger.token_symbol.as_str()),
+        Value::entry("icrc1:fee", ledger.transfer_fee.get_e8s()),
+    ]
+}
+
+#[candid_method(query, rename = "icrc1_minting_account")]
+fn icrc1_minting_account() -> Option<Account> {
+    LEDGER.read().unwrap().icrc1_minting_account.clone()
+}
+
+#[candid_method(query, rename = "icrc1_balance_of")]
+fn icrc1_balance_of(account: Account) -> Nat {
+    Nat::from(account_balance(AccountIdentifier::from(account)).get_e8s())
+}
+
+#[candid_method(query, rename = "icrc1_total_supply")]
+fn icrc1_total_supply() -> Nat {
+    Nat::from(total_supply().get_e8s())
+}
+
+#[candid_method(query, rename = "icrc1_supported_standards")]
+fn icrc1_supported_standards() -> Vec<StandardRecord> {
+    vec![StandardRecord {
+        name: "ICRC-1".to_string(),
+        url: "https://github.com/dfinity/ICRC-1".to_string(),
+    }]
+}
+
+/// Transfers tokens from the ICRC-1 account of the caller. The accounts are
+/// mapped to account identifiers and the transaction is recorded in the same
+/// block format as transactions made with `transfer`.
+#[candid_method(update, rename = "icrc1_transfer")]
+async fn icrc1_transfer(arg: TransferArg) -> Result<Nat, Icrc1TransferError> {
+    let caller_principal_id = caller();
+
+    if !LEDGER.read().unwrap().can_send(&caller_principal_id) {
+        panic!("Sending from {} is not allowed", caller_principal_id);
+    }
+
+    let from = AccountIdentifier::new(caller_principal_id, arg.from_subaccount.map(Subaccount));
+    let to = AccountIdentifier::from(arg.to);
+    let memo = icrc1_memo_to_memo(arg.memo)?;
+    let created_at_time = arg
+        .created_at_time
+        .map(TimeStamp::from_nanos_since_unix_epoch);
+
+    let (height, hash) = {
+        let mut ledger = LEDGER.write().unwrap();
+        let minting_acc = ledger
+            .minting_account_id
+            .expect("Minting canister id not initialized");
+        let now = TimeStamp::from(dfn_core::api::now());
+
+        let amount = match arg.amount.0.to_u64() {
+            Some(e8s) => Tokens::from_e8s(e8s),
+            None => {
+                // No one can have so many tokens.
+                let balance = Nat::from(ledger.balances.account_balance(&from).get_e8s());
+                assert!(balance < arg.amount);
+                return Err(Icrc1TransferError::InsufficientFunds { balance });
+            }
+        };
+
+        let operation = if to == minting_acc {
+            let expected_fee = Nat::from(0u64);
+            if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
+                return Err(Icrc1TransferError::BadFee { expected_fee });
+            }
+            let min_burn_amount = ledger.transfer_fee;
+            if amount < min_burn_amount {
+                return Err(Icrc1TransferError::BadBurn {
+                    min_burn_amount: Nat::from(min_burn_amount.get_e8s()),
+                });
+            }
+            Operation::Burn { from, amount }
+        } else if from == minting_acc {
+            let expected_fee = Nat::from(0u64);
+            if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
+                return Err(Icrc1TransferError::BadFee { expected_fee });
+            }
+            Operation::Mint { to, amount }
+        } else {
+            let fee = ledger.transfer_fee;
+            let expected_fee = Nat::from(fee.get_e8s());
+            if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
+                return Err(Icrc1TransferError::BadFee { expected_fee });
+            }
+            Operation::Transfer {
+                from,
+                to,
+                amount,
+                fee,
+            }
+        };
+
+        apply_transaction(
+            &mut *ledger,
+            Transaction {
+                operation,
+                memo,
+                created_at_time,
+            },
+            now,
+        )?
+    };
+    set_certified_data(&hash.into_bytes());
+
+    // Don't put anything that could ever trap after this call or people using this
+    // endpoint. If something did panic the payment would appear to fail, but would
+    // actually succeed on chain.
+    let max_msg_size = *MAX_MESSAGE_SIZE_BYTES.read().unwrap();
+    archive_blocks::<Access>(max_msg_size).await;
+    Ok(Nat::from(height))
+}
+
 #[candid_method(query, rename = "transfer_fee")]
 fn transfer_fee(_: TransferFeeArgs) -> TransferFee {
     LEDGER.read().unwrap().transfer_fee()
@@ -462,6 +654,7 @@ fn canister_init(arg: LedgerCanisterInitPayload) {
         arg.transfer_fee,
         arg.token_symbol,
         arg.token_name,
+        arg.icrc1_minting_account,
     )
 }
 
@@ -472,12 +665,38 @@ fn main() {
 
 #[export_name = "canister_post_upgrade"]
 fn post_upgrade() {
-    over_init(|_: BytesS| {
+    over_init(|BytesS(args): BytesS| {
         let mut ledger = LEDGER.write().unwrap();
-        *ledger = ciborium::de::from_reader(stable::StableReader::new())
-            .expect("Decoding stable memory failed");
+        // Ledgers installed before the balances and blocks moved to stable
+        // memory serialized their whole state to the raw stable memory.
+        *ledger = if is_legacy_layout() {
+            ciborium::de::from_reader(stable::StableReader::new())
+        } else {
+            ciborium::de::from_reader(UpgradesReader::default())
+        }
+        .expect("Decoding stable memory failed");
+
+        // Upgrades without an argument keep the current settings.
+        let upgrade_payload: Option<LedgerCanisterUpgradePayload> = if args.is_empty() {
+            None
+        } else {
+            candid::decode_one(&args).expect("failed to decode the upgrade argument")
+        };
+        if let Some(icrc1_minting_account) =
+            upgrade_payload.and_then(|payload| payload.icrc1_minting_account)
+        {
+            assert_eq!(
+                Some(AccountIdentifier::from(icrc1_minting_account.clone())),
+                ledger.minting_account_id,
+                "minting_account and icrc1_minting_account must refer to the same account"
+            );
+            ledger.icrc1_minting_account = Some(icrc1_minting_account);
+        }
 
         ledger.maximum_number_of_accounts = 28_000_000;
+        // Canisters cannot make calls in post_upgrade, so the archive nodes
+        // are upgraded from the timer.
+        schedule_global_timer();
 
         set_certified_data(
             &ledger
@@ -499,10 +718,32 @@ fn pre_upgrade() {
         .read()
         // This should never happen, but it's better to be safe than sorry
         .unwrap_or_else(|poisoned| poisoned.into_inner());
-    ciborium::ser::into_writer(&*ledger, stable::StableWriter::new())
+    ciborium::ser::into_writer(&*ledger, UpgradesWriter::default())
         .expect("failed to write ledger state to stable memory");
 }
 
+fn schedule_global_timer() {
+    ic_cdk::api::set_global_timer(dfn_core::api::time_nanos());
+}
+
+/// Upgrades the archive nodes after a ledger upgrade and moves the next batch
+/// of the legacy ledger state to stable memory.
+#[export_name = "canister_global_timer"]
+fn global_timer() {
+    setup::START.call_once(|| {
+        printer::hook();
+    });
+
+    dfn_core::api::futures::spawn(async {
+        if !upgrade_archives::<Access>().await {
+            schedule_global_timer();
+        }
+    });
+    if !LEDGER.write().unwrap().migrate_to_stable() {
+        schedule_global_timer();
+    }
+}
+
 struct Access;
 
 impl LedgerAccess for Access {
@@ -731,6 +972,56 @@ fn token_decimals_candid() {
     over(candid_one, |()| token_decimals())
 }
 
+#[export_name = "canister_query icrc1_name"]
+fn icrc1_name_candid() {
+    over(candid_one, |()| icrc1_name())
+}
+
+#[export_name = "canister_query icrc1_symbol"]
+fn icrc1_symbol_candid() {
+    over(candid_one, |()| icrc1_symbol())
+}
+
+#[export_name = "canister_query icrc1_decimals"]
+fn icrc1_decimals_candid() {
+    over(candid_one, |()| icrc1_decimals())
+}
+
+#[export_name = "canister_query icrc1_fee"]
+fn icrc1_fee_candid() {
+    over(candid_one, |()| icrc1_fee())
+}
+
+#[export_name = "canister_query icrc1_metadata"]
+fn icrc1_metadata_candid() {
+    over(candid_one, |()| icrc1_metadata())
+}
+
+#[export_name = "canister_query icrc1_minting_account"]
+fn icrc1_minting_account_candid() {
+    over(candid_one, |()| icrc1_minting_account())
+}
+
+#[export_name = "canister_query icrc1_balance_of"]
+fn icrc1_balance_of_candid() {
+    over(candid_one, icrc1_balance_of)
+}
+
+#[export_name = "canister_query icrc1_total_supply"]
+fn icrc1_total_supply_candid() {
+    over(candid_one, |()| icrc1_total_supply())
+}
+
+#[export_name = "canister_query icrc1_supported_standards"]
+fn icrc1_supported_standards_candid() {
+    over(candid_one, |()| icrc1_supported_standards())
+}
+
+#[export_name = "canister_update icrc1_transfer"]
+fn icrc1_transfer_candid() {
+    over_async(candid_one, icrc1_transfer)
+}
+
 #[export_name = "canister_query total_supply_pb"]
 fn total_supply_() {
     over(protobuf, |_: TotalSupplyArgs| {
@@ -747,7 +1038,9 @@ fn total_supply_() {
 fn iter_blocks_() {
     over(protobuf, |IterBlocksArgs { start, length }| {
         let blocks = &LEDGER.read().unwrap().blockchain.blocks;
-        icp_ledger::iter_blocks(blocks, start, length)
+        icp_ledger::iter_blocks_with(blocks.len() as usize, start, length, |positions| {
+            blocks.get_blocks(positions.start as u64..positions.end as u64)
+        })
     });
 }
 
@@ -758,7 +1051,17 @@ fn get_blocks_() {
     over(protobuf, |GetBlocksArgs { start, length }| {
         let blockchain = &LEDGER.read().unwrap().blockchain;
         let start_offset = blockchain.num_archived_blocks();
-        icp_ledger::get_blocks(&blockchain.blocks, start_offset, start, length)
+        icp_ledger::get_blocks_with(
+            blockchain.blocks.len() as usize,
+            start_offset,
+            start,
+            length,
+            |positions| {
+                blockchain
+                    .blocks
+                    .get_blocks(positions.start as u64..positions.end as u64)
+            },
+        )
     });
 }
 
@@ -842,6 +1145,32 @@ fn archives_candid() {
     over(candid_one, |()| archives());
 }
 
+#[candid_method(query, rename = "archives_for_account")]
+fn archives_for_account(arg: ArchivesForAccountArgs) -> Archives {
+    let account = AccountIdentifier::from_address(arg.account).unwrap_or_else(|e| {
+        trap_with(&format!("Invalid account identifier: {}", e));
+        unreachable!()
+    });
+    let ledger_guard = LEDGER.try_read().expect("Failed to get ledger read lock");
+    let archive_guard = ledger_guard.blockchain.archive.read().unwrap();
+    let archives = archive_guard
+        .as_ref()
+        .iter()
+        .flat_map(|archive| {
+            archive
+                .index_for_account(&account)
+                .into_iter()
+                .map(|(_, canister_id)| ArchiveInfo { canister_id })
+        })
+        .collect();
+    Archives { archives }
+}
+
+#[export_name = "canister_query archives_for_account"]
+fn archives_for_account_candid() {
+    over(candid_one, archives_for_account);
+}
+
 fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
     let ledger = LEDGER.try_read().map_err(|err| {
         std::io::Error::new(
@@ -877,7 +1206,7 @@ fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::i
     )?;
     w.encode_gauge(
         "ledger_blocks",
-        ledger.blockchain.blocks.len() as f64,
+        ledger.blockchain.num_unarchived_blocks() as f64,
         "Total number of blocks stored in the main memory.",
     )?;
     // This value can go down -- the number is increased before archiving, and if
@@ -1,7 +1,10 @@
 use crate::Ledger;
 use ic_base_types::{CanisterId, PrincipalId};
-use ic_ledger_canister_core::{archive::Archive, ledger as core_ledger, ledger::LedgerTransaction};
+use ic_ledger_canister_core::{
+    archive::Archive, blockchain::BlockData, ledger as core_ledger, ledger::LedgerTransaction,
+};
 use ic_ledger_core::{
+    balances::{Balances, InspectableBalancesStore},
     block::{BlockIndex, BlockType},
     timestamp::TimeStamp,
     tokens::Tokens,
@@ -10,13 +13,13 @@ use icp_ledger::{
     apply_operation, ArchiveOptions, Block, LedgerBalances, Memo, Operation, PaymentError,
     Transaction, TransferError, DEFAULT_TRANSFER_FEE,
 };
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime};
 
 #[test]
 fn balances_overflow() {
-    let balances = LedgerBalances::new();
+    let balances = Balances::new();
     let mut state = Ledger {
         balances,
         maximum_number_of_accounts: 8,
@@ -214,6 +217,7 @@ fn serialize() {
         None,
         Some("ICP".into()),
         Some("icp".into()),
+        None,
     );
 
     let txn = Transaction::new(
@@ -274,7 +278,14 @@ fn serialize() {
         state.blockchain.blocks.len(),
         state_decoded.blockchain.blocks.len()
     );
-    assert_eq!(state.balances.store, state_decoded.balances.store);
+    assert_eq!(
+        state.balances.store.iter().collect::<BTreeMap<_, _>>(),
+        state_decoded
+            .balances
+            .store
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+    );
 }
 
 /// Check that 'created_at_time' is not too far in the past or
@@ -547,6 +558,7 @@ fn get_blocks_returns_correct_blocks() {
         None,
         Some("ICP".into()),
         Some("icp".into()),
+        None,
     );
 
     for i in 0..10 {
@@ -568,15 +580,18 @@ fn get_blocks_returns_correct_blocks() {
         state.add_block(block).unwrap();
     }
 
-    let blocks = &state.blockchain.blocks;
+    let blocks = state
+        .blockchain
+        .blocks
+        .get_blocks(0..state.blockchain.blocks.len());
 
-    let first_blocks = icp_ledger::get_blocks(blocks, 0, 1, 5).0.unwrap();
+    let first_blocks = icp_ledger::get_blocks(&blocks, 0, 1, 5).0.unwrap();
     for i in 0..first_blocks.len() {
         let block = Block::decode(first_blocks.get(i).unwrap().clone()).unwrap();
         assert_eq!(block.transaction.memo.0, i as u64);
     }
 
-    let last_blocks = icp_ledger::get_blocks(blocks, 0, 6, 5).0.unwrap();
+    let last_blocks = icp_ledger::get_blocks(&blocks, 0, 6, 5).0.unwrap();
     for i in 0..last_blocks.len() {
         let block = Block::decode(last_blocks.get(i).unwrap().clone()).unwrap();
         assert_eq!(block.transaction.memo.0, 5 + i as u64);
@@ -607,6 +622,7 @@ fn test_purge() {
         None,
         Some("ICP".into()),
         Some("icp".into()),
+        None,
     );
     let little_later = genesis + Duration::from_millis(1);
 
@@ -2,6 +2,8 @@ use candid::CandidType;
 use dfn_core::CanisterId;
 use ic_base_types::{CanisterIdError, PrincipalId, PrincipalIdError};
 use ic_crypto_sha::Sha224;
+use ic_icrc1::Account;
+use ic_ledger_canister_core::stable_memory::StableAccountId;
 use serde::{de, de::Error, Deserialize, Serialize};
 use std::{
     convert::{TryFrom, TryInto},
@@ -29,6 +31,21 @@ impl AsRef<[u8]> for AccountIdentifier {
     }
 }
 
+/// The stable encoding of an account identifier is its 28-byte hash.
+impl StableAccountId for AccountIdentifier {
+    fn to_stable_bytes(&self) -> Vec<u8> {
+        self.hash.to_vec()
+    }
+
+    fn from_stable_bytes(bytes: &[u8]) -> Self {
+        Self {
+            hash: bytes
+                .try_into()
+                .expect("BUG: invalid stable encoding of an account identifier"),
+        }
+    }
+}
+
 impl TryFrom<&proto::AccountIdentifier> for AccountIdentifier {
     type Error = AccountIdParseError;
     fn try_from(id: &proto::AccountIdentifier) -> Result<Self, AccountIdParseError> {
@@ -175,6 +192,12 @@ impl From<CanisterId> for AccountIdentifier {
     }
 }
 
+impl From<Account> for AccountIdentifier {
+    fn from(account: Account) -> Self {
+        AccountIdentifier::new(account.owner, account.subaccount.map(Subaccount))
+    }
+}
+
 fn check_sum(hex: [u8; 32]) -> Result<AccountIdentifier, ChecksumError> {
     // Get the checksum provided
     let found_checksum = &hex[0..4];
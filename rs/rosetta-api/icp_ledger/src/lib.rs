@@ -2,11 +2,12 @@ use candid::CandidType;
 use dfn_protobuf::ProtoBuf;
 use ic_base_types::{CanisterId, PrincipalId};
 use ic_crypto_sha::Sha256;
+use ic_icrc1::Account;
 pub use ic_ledger_canister_core::archive::ArchiveOptions;
-use ic_ledger_canister_core::ledger::LedgerTransaction;
+use ic_ledger_canister_core::ledger::{LedgerData, LedgerTransaction, TxApplyError};
 use ic_ledger_core::{
     balances::{BalanceError, Balances, BalancesStore},
-    block::{BlockType, EncodedBlock, HashOf, HASH_LENGTH},
+    block::{BlockType, EncodedBlock, FeeCollector, HashOf, HASH_LENGTH},
 };
 use on_wire::{FromWire, IntoWire};
 use serde::{Deserialize, Serialize};
@@ -146,11 +147,11 @@ impl LedgerTransaction for Transaction {
         HashOf::new(state.finish())
     }
 
-    fn apply<S>(&self, balances: &mut Balances<Self::AccountId, S>) -> Result<(), BalanceError>
+    fn apply<L>(&self, ledger: &mut L, _now: TimeStamp) -> Result<(), TxApplyError>
     where
-        S: Default + BalancesStore<Self::AccountId>,
+        L: LedgerData<AccountId = Self::AccountId>,
     {
-        apply_operation(balances, &self.operation)
+        apply_operation(ledger.balances_mut(), &self.operation).map_err(TxApplyError::from)
     }
 }
 
@@ -199,7 +200,12 @@ impl Block {
             memo,
             created_at_time: Some(created_at_time),
         };
-        Ok(Self::from_transaction(parent_hash, transaction, timestamp))
+        Ok(Self::from_transaction(
+            parent_hash,
+            transaction,
+            timestamp,
+            None,
+        ))
     }
 
     #[inline]
@@ -208,7 +214,7 @@ impl Block {
         transaction: Transaction,
         timestamp: TimeStamp,
     ) -> Self {
-        Self::from_transaction(parent_hash, transaction, timestamp)
+        Self::from_transaction(parent_hash, transaction, timestamp, None)
     }
 
     pub fn transaction(&self) -> Cow<Transaction> {
@@ -218,6 +224,7 @@ impl Block {
 
 impl BlockType for Block {
     type Transaction = Transaction;
+    type AccountId = AccountIdentifier;
 
     fn encode(self) -> EncodedBlock {
         EncodedBlock::from_vec(
@@ -245,11 +252,21 @@ impl BlockType for Block {
         self.timestamp
     }
 
+    fn accounts(&self) -> Vec<Self::AccountId> {
+        match &self.transaction.operation {
+            Operation::Burn { from, .. } => vec![*from],
+            Operation::Mint { to, .. } => vec![*to],
+            Operation::Transfer { from, to, .. } => vec![*from, *to],
+        }
+    }
+
     fn from_transaction(
         parent_hash: Option<HashOf<EncodedBlock>>,
         transaction: Self::Transaction,
         timestamp: TimeStamp,
+        _fee_collector: Option<&FeeCollector<Self::AccountId>>,
     ) -> Self {
+        // The ICP ledger burns transaction fees.
         Self {
             parent_hash,
             transaction,
@@ -284,6 +301,10 @@ pub struct LedgerCanisterInitPayload {
     pub transfer_fee: Option<Tokens>,
     pub token_symbol: Option<String>,
     pub token_name: Option<String>,
+    /// The minting account in the ICRC-1 format. If set, it must correspond to
+    /// `minting_account`.
+    #[serde(default)]
+    pub icrc1_minting_account: Option<Account>,
 }
 
 impl LedgerCanisterInitPayload {
@@ -302,6 +323,7 @@ pub struct LedgerCanisterInitPayloadBuilder {
     transfer_fee: Option<Tokens>,
     token_symbol: Option<String>,
     token_name: Option<String>,
+    icrc1_minting_account: Option<Account>,
 }
 
 impl LedgerCanisterInitPayloadBuilder {
@@ -316,6 +338,7 @@ impl LedgerCanisterInitPayloadBuilder {
             transfer_fee: None,
             token_symbol: None,
             token_name: None,
+            icrc1_minting_account: None,
         }
     }
 
@@ -360,11 +383,28 @@ impl LedgerCanisterInitPayloadBuilder {
         self
     }
 
+    /// Sets the minting account in the ICRC-1 format. This also sets the
+    /// minting account identifier.
+    pub fn icrc1_minting_account(mut self, minting_account: Account) -> Self {
+        self.minting_account = Some(AccountIdentifier::from(minting_account.clone()));
+        self.icrc1_minting_account = Some(minting_account);
+        self
+    }
+
     pub fn build(self) -> Result<LedgerCanisterInitPayload, String> {
         let minting_account = self
             .minting_account
             .ok_or("minting_account must be set in the payload")?;
 
+        if let Some(icrc1_minting_account) = &self.icrc1_minting_account {
+            if AccountIdentifier::from(icrc1_minting_account.clone()) != minting_account {
+                return Err(
+                    "minting_account and icrc1_minting_account must refer to the same account"
+                        .to_string(),
+                );
+            }
+        }
+
         // verify ledger's invariant about the maximum amount
         let mut sum = Tokens::ZERO;
         for initial_value in self.initial_values.values() {
@@ -388,10 +428,22 @@ impl LedgerCanisterInitPayloadBuilder {
             transfer_fee: self.transfer_fee,
             token_symbol: self.token_symbol,
             token_name: self.token_name,
+            icrc1_minting_account: self.icrc1_minting_account,
         })
     }
 }
 
+/// Argument taken by the ledger canister on upgrade.
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LedgerCanisterUpgradePayload {
+    /// The minting account in the ICRC-1 format. Ledgers installed before the
+    /// ICRC-1 endpoints were added only know the minting account identifier,
+    /// so the account has to be supplied on upgrade. It must correspond to the
+    /// minting account identifier of the ledger.
+    #[serde(default)]
+    pub icrc1_minting_account: Option<Account>,
+}
+
 /// Argument taken by the send endpoint
 #[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq)]
 pub struct SendArgs {
@@ -674,6 +726,12 @@ pub struct Archives {
     pub archives: Vec<ArchiveInfo>,
 }
 
+/// Argument taken by the archives_for_account endpoint.
+#[derive(Serialize, Deserialize, CandidType, Clone, Hash, Debug, PartialEq, Eq)]
+pub struct ArchivesForAccountArgs {
+    pub account: AccountIdBlob,
+}
+
 /// Argument returned by the tip_of_chain endpoint
 pub struct TipOfChainRes {
     pub certification: Option<Vec<u8>>,
@@ -730,11 +788,30 @@ pub fn get_blocks(
     range_from_offset: BlockIndex,
     range_from: BlockIndex,
     length: usize,
+) -> GetBlocksRes {
+    get_blocks_with(
+        blocks.len(),
+        range_from_offset,
+        range_from,
+        length,
+        |positions| blocks[positions].to_vec(),
+    )
+}
+
+/// Same as [get_blocks], but reads the blocks at the requested positions
+/// using the `fetch` function, so that the blocks do not need to be stored in
+/// a contiguous slice.
+pub fn get_blocks_with(
+    num_blocks: usize,
+    range_from_offset: BlockIndex,
+    range_from: BlockIndex,
+    length: usize,
+    fetch: impl FnOnce(std::ops::Range<usize>) -> Vec<EncodedBlock>,
 ) -> GetBlocksRes {
     // Inclusive end of the range of *requested* blocks
     let requested_range_to = range_from as usize + length - 1;
     // Inclusive end of the range of *available* blocks
-    let range_to = range_from_offset as usize + blocks.len() - 1;
+    let range_to = range_from_offset as usize + num_blocks - 1;
     // Example: If the Node stores 10 blocks beginning at BlockIndex 100, i.e.
     // [100 .. 109] then requesting blocks at BlockIndex < 100 or BlockIndex
     // > 109 is an error
@@ -745,16 +822,28 @@ pub fn get_blocks(
     // Example: If the node stores blocks [100 .. 109] then BLOCK_HEIGHT_OFFSET
     // is 100 and the Block with BlockIndex 100 is at index 0
     let offset = (range_from - range_from_offset) as usize;
-    GetBlocksRes(Ok(blocks[offset..offset + length].to_vec()))
+    GetBlocksRes(Ok(fetch(offset..offset + length)))
 }
 
 // A helper function for ledger/iter_blocks and archive_node/iter_blocks
 // endpoints
 pub fn iter_blocks(blocks: &[EncodedBlock], offset: usize, length: usize) -> IterBlocksRes {
-    let start = std::cmp::min(offset, blocks.len());
-    let end = std::cmp::min(start + length, blocks.len());
-    let blocks = blocks[start..end].to_vec();
-    IterBlocksRes(blocks)
+    iter_blocks_with(blocks.len(), offset, length, |positions| {
+        blocks[positions].to_vec()
+    })
+}
+
+/// Same as [iter_blocks], but reads the blocks at the requested positions
+/// using the `fetch` function.
+pub fn iter_blocks_with(
+    num_blocks: usize,
+    offset: usize,
+    length: usize,
+    fetch: impl FnOnce(std::ops::Range<usize>) -> Vec<EncodedBlock>,
+) -> IterBlocksRes {
+    let start = std::cmp::min(offset, num_blocks);
+    let end = std::cmp::min(start + length, num_blocks);
+    IterBlocksRes(fetch(start..end))
 }
 
 #[derive(CandidType, Deserialize)]
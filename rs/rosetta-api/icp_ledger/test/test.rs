@@ -1,9 +1,13 @@
-use candid::CandidType;
+use candid::{CandidType, Nat};
 use canister_test::*;
 use dfn_candid::{candid, candid_one, CandidOne};
 use dfn_protobuf::protobuf;
 use ic_base_types::{CanisterId, PrincipalId};
 use ic_canister_client_sender::Sender;
+use ic_icrc1::{
+    endpoints::{TransferArg, TransferError as Icrc1TransferError},
+    Account,
+};
 use ic_ledger_canister_core::archive::ArchiveOptions;
 use ic_ledger_core::{
     block::{BlockIndex, BlockType, EncodedBlock},
@@ -12,10 +16,10 @@ use ic_ledger_core::{
 use icp_ledger::{
     tokens_from_proto, AccountBalanceArgs, AccountIdentifier, Archives, BinaryAccountBalanceArgs,
     Block, BlockArg, BlockRange, BlockRes, CandidBlock, GetBlocksArgs, GetBlocksError,
-    GetBlocksRes, GetBlocksResult, IterBlocksArgs, IterBlocksRes, LedgerCanisterInitPayload, Memo,
-    NotifyCanisterArgs, Operation, QueryBlocksResponse, SendArgs, Subaccount, Tokens,
-    TotalSupplyArgs, Transaction, TransferArgs, TransferError, TransferFee, TransferFeeArgs,
-    DEFAULT_TRANSFER_FEE,
+    GetBlocksRes, GetBlocksResult, IterBlocksArgs, IterBlocksRes, LedgerCanisterInitPayload,
+    LedgerCanisterUpgradePayload, Memo, NotifyCanisterArgs, Operation, QueryBlocksResponse,
+    SendArgs, Subaccount, Tokens, TotalSupplyArgs, Transaction, TransferArgs, TransferError,
+    TransferFee, TransferFeeArgs, DEFAULT_TRANSFER_FEE,
 };
 use on_wire::IntoWire;
 use serde::Deserialize;
@@ -50,7 +54,7 @@ fn example_block() -> Block {
         Memo(456),
         TimeStamp::new(2_000_000_000, 123_456_789),
     );
-    Block::from_transaction(None, transaction, TimeStamp::new(1, 1))
+    Block::from_transaction(None, transaction, TimeStamp::new(1, 1), None)
 }
 
 async fn simple_send(
@@ -2099,6 +2103,284 @@ fn test_transfer_u64_overflow() {
         Ok(())
     });
 }
+
+async fn icrc1_balance_of(ledger: &Canister<'_>, account: Account) -> u64 {
+    let balance: Nat = ledger
+        .query_("icrc1_balance_of", candid_one, account)
+        .await
+        .expect("failed to query balance");
+    balance.0.try_into().unwrap()
+}
+
+async fn icrc1_transfer(
+    ledger: &Canister<'_>,
+    from: &Sender,
+    arg: TransferArg,
+) -> Result<Nat, Icrc1TransferError> {
+    ledger
+        .update_from_sender("icrc1_transfer", candid_one, arg, from)
+        .await
+        .expect("icrc1_transfer call trapped")
+}
+
+#[test]
+fn test_icrc1_transfer() {
+    local_test_e(|r| async move {
+        let proj = Project::new();
+
+        let minting_account = create_sender(0);
+        let acc1 = create_sender(1);
+        let acc2 = create_sender(2);
+
+        let minting_icrc1_account = Account {
+            owner: minting_account.get_principal_id(),
+            subaccount: None,
+        };
+        let acc1_account = Account {
+            owner: acc1.get_principal_id(),
+            subaccount: None,
+        };
+        let acc2_account = Account {
+            owner: acc2.get_principal_id(),
+            subaccount: Some([1; 32]),
+        };
+
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            AccountIdentifier::from(acc1_account.clone()),
+            Tokens::from_e8s(1_000_000_000),
+        );
+
+        let ledger = proj
+            .cargo_bin("ledger-canister", &[])
+            .install_(
+                &r,
+                CandidOne(
+                    LedgerCanisterInitPayload::builder()
+                        .icrc1_minting_account(minting_icrc1_account.clone())
+                        .initial_values(accounts)
+                        .build()
+                        .unwrap(),
+                ),
+            )
+            .await?;
+
+        let minting_account_res: Option<Account> = ledger
+            .query_("icrc1_minting_account", candid_one, ())
+            .await?;
+        assert_eq!(minting_account_res, Some(minting_icrc1_account.clone()));
+
+        let fee: Nat = ledger.query_("icrc1_fee", candid_one, ()).await?;
+        assert_eq!(fee, Nat::from(DEFAULT_TRANSFER_FEE.get_e8s()));
+
+        let block_index = icrc1_transfer(
+            &ledger,
+            &acc1,
+            TransferArg {
+                from_subaccount: None,
+                to: acc2_account.clone(),
+                fee: None,
+                created_at_time: None,
+                memo: Some(ic_icrc1::Memo::from(42u64)),
+                amount: Nat::from(10_000_000u64),
+            },
+        )
+        .await
+        .expect("failed to transfer funds");
+
+        assert_eq!(
+            icrc1_balance_of(&ledger, acc1_account.clone()).await,
+            989_990_000
+        );
+        assert_eq!(
+            icrc1_balance_of(&ledger, acc2_account.clone()).await,
+            10_000_000
+        );
+        assert_eq!(
+            account_balance_candid(&ledger, &AccountIdentifier::from(acc2_account.clone())).await,
+            Tokens::from_e8s(10_000_000)
+        );
+
+        // The transaction is recorded in the ICP block format.
+        let block_index: u64 = block_index.0.try_into().unwrap();
+        let BlockRes(block) = ledger
+            .query_("block_pb", protobuf, BlockArg(block_index))
+            .await?;
+        let block = Block::decode(block.unwrap().unwrap()).unwrap();
+        assert_eq!(block.transaction.memo, Memo(42));
+        assert_eq!(
+            block.transaction.operation,
+            Operation::Transfer {
+                from: AccountIdentifier::from(acc1_account.clone()),
+                to: AccountIdentifier::from(acc2_account.clone()),
+                amount: Tokens::from_e8s(10_000_000),
+                fee: DEFAULT_TRANSFER_FEE,
+            }
+        );
+
+        // Test error cases
+        assert_eq!(
+            icrc1_transfer(
+                &ledger,
+                &acc1,
+                TransferArg {
+                    from_subaccount: None,
+                    to: acc2_account.clone(),
+                    fee: Some(Nat::from(1u64)),
+                    created_at_time: None,
+                    memo: None,
+                    amount: Nat::from(10_000_000u64),
+                },
+            )
+            .await,
+            Err(Icrc1TransferError::BadFee {
+                expected_fee: Nat::from(DEFAULT_TRANSFER_FEE.get_e8s()),
+            })
+        );
+
+        assert_eq!(
+            icrc1_transfer(
+                &ledger,
+                &acc1,
+                TransferArg {
+                    from_subaccount: None,
+                    to: minting_icrc1_account.clone(),
+                    fee: None,
+                    created_at_time: None,
+                    memo: None,
+                    amount: Nat::from(1u64),
+                },
+            )
+            .await,
+            Err(Icrc1TransferError::BadBurn {
+                min_burn_amount: Nat::from(DEFAULT_TRANSFER_FEE.get_e8s()),
+            })
+        );
+
+        // Memos that do not fit into the 64-bit memos of the ICP blocks are
+        // rejected rather than cut, and nothing is transferred.
+        let acc1_balance = icrc1_balance_of(&ledger, acc1_account.clone()).await;
+        assert_eq!(
+            icrc1_transfer(
+                &ledger,
+                &acc1,
+                TransferArg {
+                    from_subaccount: None,
+                    to: acc2_account.clone(),
+                    fee: None,
+                    created_at_time: None,
+                    memo: Some(ic_icrc1::Memo::try_from(vec![1u8; 9]).unwrap()),
+                    amount: Nat::from(10_000_000u64),
+                },
+            )
+            .await,
+            Err(Icrc1TransferError::GenericError {
+                error_code: Nat::from(0u64),
+                message: "the memo field is 9 bytes long, the ledger only supports memos of \
+                          at most 8 bytes"
+                    .to_string(),
+            })
+        );
+        assert_eq!(
+            icrc1_balance_of(&ledger, acc1_account.clone()).await,
+            acc1_balance
+        );
+
+        assert_eq!(
+            icrc1_transfer(
+                &ledger,
+                &acc2,
+                TransferArg {
+                    from_subaccount: None,
+                    to: acc1_account.clone(),
+                    fee: None,
+                    created_at_time: None,
+                    memo: None,
+                    amount: Nat::from(10_000_000u64),
+                },
+            )
+            .await,
+            Err(Icrc1TransferError::InsufficientFunds {
+                balance: Nat::from(0u64),
+            })
+        );
+
+        // Transfers without created_at_time are not deduplicated.
+        let repeated_transfer = TransferArg {
+            from_subaccount: None,
+            to: acc2_account.clone(),
+            fee: None,
+            created_at_time: None,
+            memo: Some(ic_icrc1::Memo::from(43u64)),
+            amount: Nat::from(1_000u64),
+        };
+        let first_index = icrc1_transfer(&ledger, &acc1, repeated_transfer.clone())
+            .await
+            .expect("failed to transfer funds");
+        let second_index = icrc1_transfer(&ledger, &acc1, repeated_transfer)
+            .await
+            .expect("failed to transfer funds");
+        assert_ne!(first_index, second_index);
+
+        Ok(())
+    });
+}
+
+#[test]
+fn test_icrc1_minting_account_upgrade() {
+    local_test_e(|r| async move {
+        let proj = Project::new();
+
+        let minting_account = create_sender(0);
+        let minting_icrc1_account = Account {
+            owner: minting_account.get_principal_id(),
+            subaccount: None,
+        };
+
+        let mut ledger = proj
+            .cargo_bin("ledger-canister", &[])
+            .install_(
+                &r,
+                CandidOne(
+                    LedgerCanisterInitPayload::builder()
+                        .minting_account(AccountIdentifier::from(minting_icrc1_account.clone()))
+                        .build()
+                        .unwrap(),
+                ),
+            )
+            .await?;
+
+        let minting_account_res: Option<Account> = ledger
+            .query_("icrc1_minting_account", candid_one, ())
+            .await?;
+        assert_eq!(minting_account_res, None);
+
+        ledger
+            .upgrade_to_self_binary(
+                CandidOne(Some(LedgerCanisterUpgradePayload {
+                    icrc1_minting_account: Some(minting_icrc1_account.clone()),
+                }))
+                .into_bytes()
+                .unwrap(),
+            )
+            .await?;
+
+        let minting_account_res: Option<Account> = ledger
+            .query_("icrc1_minting_account", candid_one, ())
+            .await?;
+        assert_eq!(minting_account_res, Some(minting_icrc1_account));
+
+        // Upgrades without an argument keep the minting account.
+        ledger.upgrade_to_self_binary(Vec::new()).await?;
+        let minting_account_res: Option<Account> = ledger
+            .query_("icrc1_minting_account", candid_one, ())
+            .await?;
+        assert!(minting_account_res.is_some());
+
+        Ok(())
+    });
+}
+
 async fn ledger_assert_num_blocks(ledger: &Canister<'_>, num_expected: usize) {
     let IterBlocksRes(blocks) = ledger
         .query_(
@@ -1,7 +1,11 @@
 use candid::{candid_method, Principal};
 use ic_cdk_macros::{init, post_upgrade, query, update};
 use ic_icrc1::{
-    endpoints::{GetTransactionsRequest, Transaction, TransactionRange},
+    blocks::encoded_block_to_generic_block,
+    endpoints::{
+        BlockRange, GenericBlock, GetBlocksRequest, GetTransactionsRequest, Transaction,
+        TransactionRange,
+    },
     Block,
 };
 use ic_ledger_core::block::{BlockIndex, BlockType, EncodedBlock};
@@ -120,6 +124,11 @@ fn decode_transaction(txid: u64, bytes: Vec<u8>) -> Transaction {
         .into()
 }
 
+fn decode_generic_block(index: u64, bytes: Vec<u8>) -> GenericBlock {
+    encoded_block_to_generic_block(&EncodedBlock::from(bytes))
+        .unwrap_or_else(|e| ic_cdk::api::trap(&format!("failed to decode block {}: {}", index, e)))
+}
+
 #[init]
 #[candid_method(init)]
 fn init(
@@ -214,9 +223,12 @@ fn get_transaction(index: BlockIndex) -> Option<Transaction> {
     Some(decode_transaction(index, block))
 }
 
-#[query]
-#[candid_method(query)]
-fn get_transactions(req: GetTransactionsRequest) -> TransactionRange {
+/// Returns the decoded blocks in the range that the request specifies.
+/// Traps if the request range starts before the first block of this archive.
+fn decode_block_range<R>(
+    req: GetTransactionsRequest,
+    decode: impl Fn(u64, Vec<u8>) -> R,
+) -> Vec<R> {
     let (start, length) = req
         .as_start_and_length()
         .unwrap_or_else(|msg| ic_cdk::api::trap(&msg));
@@ -232,15 +244,28 @@ fn get_transactions(req: GetTransactionsRequest) -> TransactionRange {
     });
 
     let length = length.min(with_archive_opts(|opts| opts.max_transactions_per_response));
-    let transactions = with_blocks(|blocks| {
+    with_blocks(|blocks| {
         let limit = blocks.len().min(offset.saturating_add(length));
         (offset..limit)
-            .map(|i| decode_transaction(start + i as u64, blocks.get(i).unwrap()))
+            .map(|i| decode(start + i as u64, blocks.get(i).unwrap()))
             .collect()
-    });
+    })
+}
+
+#[query]
+#[candid_method(query)]
+fn get_transactions(req: GetTransactionsRequest) -> TransactionRange {
+    let transactions = decode_block_range(req, decode_transaction);
     TransactionRange { transactions }
 }
 
+#[query]
+#[candid_method(query)]
+fn get_blocks(req: GetBlocksRequest) -> BlockRange {
+    let blocks = decode_block_range(req, decode_generic_block);
+    BlockRange { blocks }
+}
+
 fn main() {}
 
 #[test]
@@ -0,0 +1,100 @@
+use candid::{types::number::Nat, CandidType};
+use ic_base_types::PrincipalId;
+use ic_icrc1::{Account, Memo, Subaccount};
+use ic_ledger_canister_core::{archive::ArchiveOptions, ledger::TransferError};
+use serde::Deserialize;
+
+/// The number of decimal places of the cycles ledger token: one token is one
+/// trillion cycles.
+pub const DECIMALS: u8 = 12;
+
+pub const TOKEN_NAME: &str = "Cycles";
+pub const TOKEN_SYMBOL: &str = "CYCLES";
+
+#[derive(Deserialize, CandidType, Clone, Debug, PartialEq)]
+pub struct InitArgs {
+    /// The fee in cycles for transfers and withdrawals.
+    pub transfer_fee: u64,
+    pub archive_options: ArchiveOptions,
+}
+
+/// The argument of the `deposit` endpoint. The ledger mints all the cycles
+/// attached to the call to the `to` account.
+#[derive(Deserialize, CandidType, Clone, Debug, PartialEq)]
+pub struct DepositArgs {
+    pub to: Account,
+    #[serde(default)]
+    pub memo: Option<Memo>,
+}
+
+#[derive(Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct DepositResult {
+    /// The index of the block minting the deposited cycles.
+    pub block_index: Nat,
+    /// The balance of the `to` account after the deposit.
+    pub balance: Nat,
+}
+
+/// The argument of the `withdraw` endpoint. The ledger burns `amount` plus
+/// the transfer fee from the caller's account and deposits `amount` cycles to
+/// the `to` canister.
+#[derive(Deserialize, CandidType, Clone, Debug, PartialEq)]
+pub struct WithdrawArgs {
+    #[serde(default)]
+    pub from_subaccount: Option<Subaccount>,
+    pub to: PrincipalId,
+    #[serde(default)]
+    pub created_at_time: Option<u64>,
+    pub amount: Nat,
+}
+
+#[derive(Deserialize, CandidType, Clone, Debug, PartialEq)]
+pub enum WithdrawError {
+    InsufficientFunds {
+        balance: Nat,
+    },
+    TooOld,
+    CreatedInFuture {
+        ledger_time: u64,
+    },
+    TemporarilyUnavailable,
+    Duplicate {
+        duplicate_of: Nat,
+    },
+    /// The management canister rejected the deposit. The ledger minted the
+    /// withdrawn amount back to the caller's account, the fee is not
+    /// refunded.
+    FailedToWithdraw {
+        refund_block: Option<Nat>,
+        rejection_code: i32,
+        rejection_reason: String,
+    },
+    GenericError {
+        error_code: Nat,
+        message: String,
+    },
+}
+
+impl From<TransferError> for WithdrawError {
+    fn from(err: TransferError) -> Self {
+        match err {
+            TransferError::InsufficientFunds { balance } => Self::InsufficientFunds {
+                balance: Nat::from(balance.get_e8s()),
+            },
+            TransferError::TxTooOld { .. } => Self::TooOld,
+            TransferError::TxCreatedInFuture { ledger_time } => Self::CreatedInFuture {
+                ledger_time: ledger_time.as_nanos_since_unix_epoch(),
+            },
+            TransferError::TxThrottled => Self::TemporarilyUnavailable,
+            TransferError::TxDuplicate { duplicate_of } => Self::Duplicate {
+                duplicate_of: Nat::from(duplicate_of),
+            },
+            TransferError::BadFee { .. }
+            | TransferError::InsufficientAllowance { .. }
+            | TransferError::ExpiredApproval { .. }
+            | TransferError::AllowanceChanged { .. } => {
+                unreachable!("withdrawals do not charge explicit fees or use approvals")
+            }
+        }
+    }
+}
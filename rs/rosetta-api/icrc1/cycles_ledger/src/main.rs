@@ -0,0 +1,314 @@
+use candid::candid_method;
+use candid::types::number::Nat;
+use ic_base_types::{CanisterId, PrincipalId};
+use ic_cdk_macros::{init, post_upgrade, pre_upgrade, query, update};
+use ic_cycles_ledger::{
+    DepositArgs, DepositResult, InitArgs, WithdrawArgs, WithdrawError, DECIMALS, TOKEN_NAME,
+    TOKEN_SYMBOL,
+};
+use ic_icrc1::{
+    endpoints::{
+        GetBlocksRequest, GetBlocksResponse, StandardRecord, TransferArg, TransferError, Value,
+    },
+    Account, Operation, Transaction,
+};
+use ic_icrc1_ledger::{cdk_runtime::CdkRuntime, make_transfer, Ledger};
+use ic_ledger_canister_core::ledger::{
+    apply_transaction, archive_blocks, LedgerAccess, LedgerData,
+};
+use ic_ledger_canister_core::spawn::deposit_cycles;
+use ic_ledger_canister_core::stable_memory::{UpgradesReader, UpgradesWriter};
+use ic_ledger_core::{timestamp::TimeStamp, tokens::Tokens};
+use num_traits::ToPrimitive;
+use std::cell::RefCell;
+
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+thread_local! {
+    static LEDGER: RefCell<Option<Ledger>> = RefCell::new(None);
+}
+
+struct Access;
+impl LedgerAccess for Access {
+    type Ledger = Ledger;
+
+    fn with_ledger<R>(f: impl FnOnce(&Ledger) -> R) -> R {
+        LEDGER.with(|cell| {
+            f(cell
+                .borrow()
+                .as_ref()
+                .expect("ledger state not initialized"))
+        })
+    }
+
+    fn with_ledger_mut<R>(f: impl FnOnce(&mut Ledger) -> R) -> R {
+        LEDGER.with(|cell| {
+            f(cell
+                .borrow_mut()
+                .as_mut()
+                .expect("ledger state not initialized"))
+        })
+    }
+}
+
+/// The cycles ledger mints deposits from and burns withdrawals to its own
+/// default account.
+fn minting_account() -> Account {
+    Account {
+        owner: PrincipalId::from(ic_cdk::api::id()),
+        subaccount: None,
+    }
+}
+
+fn now() -> TimeStamp {
+    TimeStamp::from_nanos_since_unix_epoch(ic_cdk::api::time())
+}
+
+#[init]
+fn init(args: InitArgs) {
+    let ledger = Ledger::from_init_args(
+        ic_icrc1_ledger::InitArgs {
+            minting_account: minting_account(),
+            fee_collector_account: None,
+            initial_balances: vec![],
+            transfer_fee: args.transfer_fee,
+            token_name: TOKEN_NAME.to_string(),
+            token_symbol: TOKEN_SYMBOL.to_string(),
+            metadata: vec![],
+            archive_options: args.archive_options,
+        },
+        now(),
+    );
+    LEDGER.with(|cell| *cell.borrow_mut() = Some(ledger))
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    Access::with_ledger(|ledger| ciborium::ser::into_writer(ledger, UpgradesWriter::default()))
+        .expect("failed to encode ledger state");
+}
+
+#[post_upgrade]
+fn post_upgrade() {
+    let ledger: Ledger = ciborium::de::from_reader(UpgradesReader::default())
+        .expect("failed to decode ledger state");
+    LEDGER.with(|cell| *cell.borrow_mut() = Some(ledger));
+}
+
+#[query]
+#[candid_method(query)]
+fn icrc1_name() -> String {
+    Access::with_ledger(|ledger| ledger.token_name().to_string())
+}
+
+#[query]
+#[candid_method(query)]
+fn icrc1_symbol() -> String {
+    Access::with_ledger(|ledger| ledger.token_symbol().to_string())
+}
+
+#[query]
+#[candid_method(query)]
+fn icrc1_decimals() -> u8 {
+    DECIMALS
+}
+
+#[query]
+#[candid_method(query)]
+fn icrc1_fee() -> Nat {
+    Nat::from(Access::with_ledger(|ledger| ledger.transfer_fee()).get_e8s())
+}
+
+#[query]
+#[candid_method(query)]
+fn icrc1_metadata() -> Vec<(String, Value)> {
+    // The generic ledger reports the decimals of the ICP token, cycles have a
+    // different precision.
+    Access::with_ledger(|ledger| ledger.metadata())
+        .into_iter()
+        .map(|(key, value)| {
+            if key == "icrc1:decimals" {
+                Value::entry(key, DECIMALS as u64)
+            } else {
+                (key, value)
+            }
+        })
+        .collect()
+}
+
+#[query]
+#[candid_method(query)]
+fn icrc1_minting_account() -> Option<Account> {
+    Access::with_ledger(|ledger| Some(ledger.minting_account().clone()))
+}
+
+#[query(name = "icrc1_balance_of")]
+#[candid_method(query, rename = "icrc1_balance_of")]
+fn icrc1_balance_of(account: Account) -> Nat {
+    Access::with_ledger(|ledger| Nat::from(ledger.balances().account_balance(&account).get_e8s()))
+}
+
+#[query(name = "icrc1_total_supply")]
+#[candid_method(query, rename = "icrc1_total_supply")]
+fn icrc1_total_supply() -> Nat {
+    Access::with_ledger(|ledger| Nat::from(ledger.balances().total_supply().get_e8s()))
+}
+
+#[query(name = "icrc1_supported_standards")]
+#[candid_method(query, rename = "icrc1_supported_standards")]
+fn supported_standards() -> Vec<StandardRecord> {
+    vec![StandardRecord {
+        name: "ICRC-1".to_string(),
+        url: "https://github.com/dfinity/ICRC-1".to_string(),
+    }]
+}
+
+#[update]
+#[candid_method(update)]
+async fn icrc1_transfer(arg: TransferArg) -> Result<Nat, TransferError> {
+    let block_idx = Access::with_ledger_mut(|ledger| {
+        let from_account = Account {
+            owner: PrincipalId::from(ic_cdk::api::caller()),
+            subaccount: arg.from_subaccount,
+        };
+        let tx = make_transfer(ledger, from_account, arg)?;
+        let (block_idx, _) = apply_transaction(ledger, tx, now())?;
+        Ok(block_idx)
+    })?;
+
+    ic_cdk::api::set_certified_data(&Access::with_ledger(Ledger::root_hash));
+
+    archive_blocks::<Access>(MAX_MESSAGE_SIZE).await;
+    Ok(Nat::from(block_idx))
+}
+
+/// Mints the cycles attached to the call to the specified account.
+#[update]
+#[candid_method(update)]
+async fn deposit(arg: DepositArgs) -> DepositResult {
+    let amount = ic_cdk::api::call::msg_cycles_available();
+    if amount == 0 {
+        ic_cdk::api::trap("the deposit call must attach cycles");
+    }
+
+    let (block_index, balance) = Access::with_ledger_mut(|ledger| {
+        let tx = Transaction::mint(arg.to.clone(), Tokens::from_e8s(amount), None, arg.memo);
+        let (block_index, _) = apply_transaction(ledger, tx, now())
+            .unwrap_or_else(|err| ic_cdk::api::trap(&format!("failed to deposit: {:?}", err)));
+        (block_index, ledger.balances().account_balance(&arg.to))
+    });
+    // We accept the cycles only after the mint succeeded, so a trap above
+    // returns the cycles to the caller.
+    let accepted = ic_cdk::api::call::msg_cycles_accept(amount);
+    assert_eq!(accepted, amount);
+
+    ic_cdk::api::set_certified_data(&Access::with_ledger(Ledger::root_hash));
+
+    archive_blocks::<Access>(MAX_MESSAGE_SIZE).await;
+    DepositResult {
+        block_index: Nat::from(block_index),
+        balance: Nat::from(balance.get_e8s()),
+    }
+}
+
+/// Burns cycles from the caller's account and deposits them to a canister.
+#[update]
+#[candid_method(update)]
+async fn withdraw(arg: WithdrawArgs) -> Result<Nat, WithdrawError> {
+    let from = Account {
+        owner: PrincipalId::from(ic_cdk::api::caller()),
+        subaccount: arg.from_subaccount,
+    };
+    let to = CanisterId::new(arg.to).map_err(|err| WithdrawError::GenericError {
+        error_code: Nat::from(0u64),
+        message: format!("invalid destination canister {}: {}", arg.to, err),
+    })?;
+
+    let (amount, block_index) = Access::with_ledger_mut(|ledger| {
+        let balance = ledger.balances().account_balance(&from);
+        let amount = match arg.amount.0.to_u64() {
+            Some(amount) => amount,
+            None => {
+                return Err(WithdrawError::InsufficientFunds {
+                    balance: Nat::from(balance.get_e8s()),
+                })
+            }
+        };
+        let fee = ledger.transfer_fee().get_e8s();
+        let total = amount
+            .checked_add(fee)
+            .ok_or(WithdrawError::InsufficientFunds {
+                balance: Nat::from(balance.get_e8s()),
+            })?;
+        let tx = Transaction {
+            operation: Operation::Burn {
+                from: from.clone(),
+                amount: total,
+            },
+            created_at_time: arg.created_at_time,
+            memo: None,
+        };
+        let (block_index, _) = apply_transaction(ledger, tx, now())?;
+        Ok((amount, block_index))
+    })?;
+
+    // NB. we need to set the certified data before the first async call to make sure that the
+    // blockchain state agrees with the certificate while the deposit is in progress.
+    ic_cdk::api::set_certified_data(&Access::with_ledger(Ledger::root_hash));
+
+    if let Err((rejection_code, rejection_reason)) = deposit_cycles::<CdkRuntime>(to, amount).await
+    {
+        // The cycles did not leave the ledger, return them to the caller.
+        let refund_block = Access::with_ledger_mut(|ledger| {
+            let tx = Transaction::mint(from, Tokens::from_e8s(amount), None, None);
+            apply_transaction(ledger, tx, now())
+                .map(|(block_index, _)| Nat::from(block_index))
+                .map_err(|err| {
+                    ic_cdk::api::print(format!(
+                        "[cycles ledger] failed to refund {} cycles: {:?}",
+                        amount, err
+                    ))
+                })
+                .ok()
+        });
+        ic_cdk::api::set_certified_data(&Access::with_ledger(Ledger::root_hash));
+        return Err(WithdrawError::FailedToWithdraw {
+            refund_block,
+            rejection_code,
+            rejection_reason,
+        });
+    }
+
+    archive_blocks::<Access>(MAX_MESSAGE_SIZE).await;
+    Ok(Nat::from(block_index))
+}
+
+#[query]
+#[candid_method(query)]
+fn get_blocks(req: GetBlocksRequest) -> GetBlocksResponse {
+    let (start, length) = req
+        .as_start_and_length()
+        .unwrap_or_else(|msg| ic_cdk::api::trap(&msg));
+    Access::with_ledger(|ledger| ledger.get_blocks(start, length))
+}
+
+fn main() {}
+
+#[test]
+fn check_candid_interface() {
+    use candid::utils::{service_compatible, CandidSource};
+    use std::path::PathBuf;
+
+    candid::export_service!();
+
+    let new_interface = __export_service();
+
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let old_interface = manifest_dir.join("cycles_ledger.did");
+
+    service_compatible(
+        CandidSource::Text(&new_interface),
+        CandidSource::File(old_interface.as_path()),
+    )
+    .expect("the cycles ledger interface is not compatible with the cycles_ledger.did file");
+}
@@ -0,0 +1,225 @@
+use candid::types::number::Nat;
+use candid::{Decode, Encode};
+use ic_base_types::PrincipalId;
+use ic_cycles_ledger::{DepositArgs, DepositResult, InitArgs, WithdrawArgs, WithdrawError};
+use ic_icrc1::Account;
+use ic_ledger_canister_core::archive::ArchiveOptions;
+use ic_state_machine_tests::{CanisterId, Cycles, StateMachine, WasmResult};
+use ic_universal_canister::{call_args, wasm, UNIVERSAL_CANISTER_WASM};
+use num_traits::ToPrimitive;
+
+const FEE: u64 = 100_000_000;
+const INITIAL_CYCLES: u128 = 100_000_000_000_000;
+
+fn cycles_ledger_wasm() -> Vec<u8> {
+    ic_test_utilities_load_wasm::load_wasm(
+        std::env::var("CARGO_MANIFEST_DIR").unwrap(),
+        "ic-cycles-ledger",
+        &[],
+    )
+}
+
+fn install_cycles_ledger(env: &StateMachine) -> CanisterId {
+    env.install_canister(
+        cycles_ledger_wasm(),
+        Encode!(&InitArgs {
+            transfer_fee: FEE,
+            archive_options: ArchiveOptions {
+                trigger_threshold: 1_000,
+                num_blocks_to_archive: 500,
+                node_max_memory_size_bytes: None,
+                max_message_size_bytes: None,
+                controller_id: PrincipalId::new_user_test_id(100),
+                cycles_for_archive_creation: None,
+                max_transactions_per_response: None,
+            },
+        })
+        .unwrap(),
+        None,
+    )
+    .unwrap()
+}
+
+fn install_universal_canister(env: &StateMachine) -> CanisterId {
+    env.install_canister_with_cycles(
+        UNIVERSAL_CANISTER_WASM.into(),
+        vec![],
+        None,
+        Cycles::new(INITIAL_CYCLES),
+    )
+    .unwrap()
+}
+
+fn balance_of(env: &StateMachine, ledger: CanisterId, account: impl Into<Account>) -> u64 {
+    Decode!(
+        &env.query(
+            ledger,
+            "icrc1_balance_of",
+            Encode!(&account.into()).unwrap()
+        )
+        .expect("failed to query balance")
+        .bytes(),
+        Nat
+    )
+    .expect("failed to decode balance_of response")
+    .0
+    .to_u64()
+    .unwrap()
+}
+
+/// Makes the universal canister call the `deposit` endpoint with the
+/// specified amount of cycles attached.
+fn deposit(
+    env: &StateMachine,
+    ledger: CanisterId,
+    depositor: CanisterId,
+    to: impl Into<Account>,
+    cycles: u64,
+) -> DepositResult {
+    let arg = Encode!(&DepositArgs {
+        to: to.into(),
+        memo: None,
+    })
+    .unwrap();
+    let payload = wasm()
+        .call_with_cycles(
+            ledger,
+            "deposit",
+            call_args()
+                .other_side(arg)
+                .on_reply(wasm().message_payload().reply_data_append().reply())
+                .on_reject(wasm().reject_message().reject()),
+            (0, cycles),
+        )
+        .build();
+    match env
+        .execute_ingress(depositor, "update", payload)
+        .expect("failed to call the universal canister")
+    {
+        WasmResult::Reply(bytes) => {
+            Decode!(&bytes, DepositResult).expect("failed to decode deposit response")
+        }
+        WasmResult::Reject(reason) => panic!("deposit was rejected: {}", reason),
+    }
+}
+
+fn withdraw(
+    env: &StateMachine,
+    ledger: CanisterId,
+    from: PrincipalId,
+    to: CanisterId,
+    amount: u64,
+) -> Result<u64, WithdrawError> {
+    Decode!(
+        &env.execute_ingress_as(
+            from,
+            ledger,
+            "withdraw",
+            Encode!(&WithdrawArgs {
+                from_subaccount: None,
+                to: to.get(),
+                created_at_time: None,
+                amount: Nat::from(amount),
+            })
+            .unwrap()
+        )
+        .expect("failed to withdraw")
+        .bytes(),
+        Result<Nat, WithdrawError>
+    )
+    .expect("failed to decode withdraw response")
+    .map(|n| n.0.to_u64().unwrap())
+}
+
+#[test]
+fn test_deposit() {
+    let env = StateMachine::new();
+    let ledger = install_cycles_ledger(&env);
+    let depositor = install_universal_canister(&env);
+    let p1 = PrincipalId::new_user_test_id(1);
+
+    let result = deposit(&env, ledger, depositor, p1, 1_000_000_000);
+    assert_eq!(result.block_index, Nat::from(0u64));
+    assert_eq!(result.balance, Nat::from(1_000_000_000u64));
+
+    let result = deposit(&env, ledger, depositor, p1, 500_000_000);
+    assert_eq!(result.block_index, Nat::from(1u64));
+    assert_eq!(result.balance, Nat::from(1_500_000_000u64));
+
+    assert_eq!(balance_of(&env, ledger, p1), 1_500_000_000);
+    assert!(env.cycle_balance(depositor) <= INITIAL_CYCLES - 1_500_000_000);
+    assert!(env.cycle_balance(ledger) >= 1_500_000_000);
+}
+
+#[test]
+fn test_deposit_without_cycles_is_rejected() {
+    let env = StateMachine::new();
+    let ledger = install_cycles_ledger(&env);
+    let p1 = PrincipalId::new_user_test_id(1);
+
+    let result = env.execute_ingress(
+        ledger,
+        "deposit",
+        Encode!(&DepositArgs {
+            to: p1.into(),
+            memo: None,
+        })
+        .unwrap(),
+    );
+    assert!(result.is_err(), "{:?}", result);
+    assert_eq!(balance_of(&env, ledger, p1), 0);
+}
+
+#[test]
+fn test_withdraw() {
+    let env = StateMachine::new();
+    let ledger = install_cycles_ledger(&env);
+    let depositor = install_universal_canister(&env);
+    let target = install_universal_canister(&env);
+    let p1 = PrincipalId::new_user_test_id(1);
+
+    deposit(&env, ledger, depositor, p1, 10_000_000_000);
+
+    let target_balance = env.cycle_balance(target);
+    let block_index =
+        withdraw(&env, ledger, p1, target, 1_000_000_000).expect("failed to withdraw");
+    assert_eq!(block_index, 1);
+    assert_eq!(
+        balance_of(&env, ledger, p1),
+        10_000_000_000 - 1_000_000_000 - FEE
+    );
+    assert_eq!(env.cycle_balance(target), target_balance + 1_000_000_000);
+
+    assert_eq!(
+        withdraw(&env, ledger, p1, target, 10_000_000_000),
+        Err(WithdrawError::InsufficientFunds {
+            balance: Nat::from(10_000_000_000u64 - 1_000_000_000 - FEE)
+        })
+    );
+}
+
+#[test]
+fn test_failed_withdraw_refunds_the_amount() {
+    let env = StateMachine::new();
+    let ledger = install_cycles_ledger(&env);
+    let depositor = install_universal_canister(&env);
+    let p1 = PrincipalId::new_user_test_id(1);
+
+    deposit(&env, ledger, depositor, p1, 10_000_000_000);
+    let ledger_cycles = env.cycle_balance(ledger);
+
+    // The canister does not exist, so the management canister rejects the
+    // deposit.
+    let missing_canister = CanisterId::from_u64(1_000_000);
+    match withdraw(&env, ledger, p1, missing_canister, 1_000_000_000) {
+        Err(WithdrawError::FailedToWithdraw {
+            refund_block: Some(refund_block),
+            ..
+        }) => assert_eq!(refund_block, Nat::from(2u64)),
+        result => panic!("expected a refunded withdrawal, got {:?}", result),
+    }
+
+    // Only the fee is gone, the cycles stayed with the ledger.
+    assert_eq!(balance_of(&env, ledger, p1), 10_000_000_000 - FEE);
+    assert_eq!(env.cycle_balance(ledger), ledger_cycles);
+}
@@ -1,14 +1,21 @@
 use std::cell::RefCell;
 use std::collections::{btree_map, BTreeMap};
+use std::io;
+use std::time::Duration;
 
 use candid::{CandidType, Nat};
 use ic_base_types::{CanisterId, PrincipalId};
-use ic_cdk::api::stable::{StableReader, StableWriter};
-use ic_icrc1::endpoints::{ArchivedTransactionRange, TransactionRange};
-use ic_icrc1::{
-    endpoints::{GetTransactionsRequest, GetTransactionsResponse, Transaction, Transfer},
-    Account, Subaccount,
+use ic_cdk::api::stable::StableReader;
+use ic_icrc1::blocks::{encoded_block_to_generic_block, generic_block_to_encoded_block};
+use ic_icrc1::endpoints::{
+    ArchivedBlockRange, BlockRange, GenericBlock, GetBlocksRequest,
+    GetBlocksResponse as LedgerGetBlocksResponse,
 };
+use ic_icrc1::{endpoints::Transaction, Account, Block, LedgerBalances, Operation, Subaccount};
+use ic_ledger_core::block::{BlockType, EncodedBlock};
+use ic_ledger_core::tokens::Tokens;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{log::Log as StableLog, DefaultMemoryImpl, Memory};
 use num_traits::cast::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use std::ops::Bound::{Included, Unbounded};
@@ -19,9 +26,29 @@ const MAX_TRANSACTIONS_PER_RESPONSE: usize = 1000;
 // Maximum number of subaccounts that can be returned
 // by [list_subaccounts]
 const MAX_SUBACCOUNTS_PER_RESPONSE: usize = 1000;
+// Maximum number of blocks that can be returned by [get_blocks]
+// and that are fetched from the Ledger in a single call
+const MAX_BLOCKS_PER_RESPONSE: usize = 1000;
+
+// The default interval between two consecutive fetches of
+// blocks from the Ledger
+const DEFAULT_RETRIEVE_BLOCKS_FROM_LEDGER_INTERVAL: Duration = Duration::from_secs(1);
 
 const LOG_PREFIX: &str = "[ic-icrc1-index] ";
 
+const WASM_PAGE_SIZE: u64 = 65536;
+
+/// The magic bytes the memory manager writes at the beginning of the stable
+/// memory.
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+
+const UPGRADES_MEMORY_ID: MemoryId = MemoryId::new(0);
+const BLOCK_LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(1);
+const BLOCK_LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(2);
+
+type VMem = VirtualMemory<DefaultMemoryImpl>;
+type BlockLog = StableLog<VMem, VMem>;
+
 type TxId = Nat;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -32,14 +59,28 @@ struct Index {
     // The next txid to query from the Ledger
     pub next_txid: u64,
 
-    // Whether there is a [heartbeat] running right now
-    pub is_heartbeat_running: bool,
+    // Whether there is a [build_index] running right now
+    #[serde(alias = "is_heartbeat_running")]
+    pub is_build_index_running: bool,
 
     // The index of transactions per account
     pub account_index: BTreeMap<PrincipalId, BTreeMap<Subaccount, Vec<u64>>>,
 
     // The number of unique (principal, subaccount) pairs in the index.
     pub accounts_num: u64,
+
+    // The balances of the accounts computed from the indexed blocks
+    #[serde(default)]
+    pub balances: LedgerBalances,
+
+    // The interval between two consecutive fetches of blocks
+    // from the Ledger
+    #[serde(default = "default_retrieve_blocks_from_ledger_interval")]
+    pub retrieve_blocks_from_ledger_interval: Duration,
+}
+
+fn default_retrieve_blocks_from_ledger_interval() -> Duration {
+    DEFAULT_RETRIEVE_BLOCKS_FROM_LEDGER_INTERVAL
 }
 
 impl Index {
@@ -47,15 +88,80 @@ impl Index {
         Self {
             ledger_id: init_args.ledger_id,
             next_txid: 0,
-            is_heartbeat_running: false,
+            is_build_index_running: false,
             account_index: BTreeMap::new(),
             accounts_num: 0,
+            balances: LedgerBalances::default(),
+            retrieve_blocks_from_ledger_interval: init_args
+                .retrieve_blocks_from_ledger_interval_seconds
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_RETRIEVE_BLOCKS_FROM_LEDGER_INTERVAL),
         }
     }
 }
 
+/// The state written by Index versions that serialized the blocks along
+/// with the rest of the state to the raw stable memory.
+#[derive(Deserialize)]
+struct LegacyIndex {
+    #[serde(flatten)]
+    index: Index,
+    #[serde(default)]
+    blocks: Vec<EncodedBlock>,
+}
+
 thread_local! {
     static INDEX: RefCell<Option<Index>>  = RefCell::new(None);
+
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    // The blocks fetched from the Ledger, the position of a block
+    // is its txid
+    static BLOCKS: RefCell<BlockLog> = RefCell::new(
+        BlockLog::init(
+            memory(BLOCK_LOG_INDEX_MEMORY_ID),
+            memory(BLOCK_LOG_DATA_MEMORY_ID),
+        )
+        .expect("failed to initialize the block log"),
+    );
+}
+
+fn memory(id: MemoryId) -> VMem {
+    MEMORY_MANAGER.with(|m| m.borrow().get(id))
+}
+
+fn with_blocks<R>(f: impl FnOnce(&BlockLog) -> R) -> R {
+    BLOCKS.with(|blocks| f(&*blocks.borrow()))
+}
+
+fn num_blocks() -> u64 {
+    with_blocks(|blocks| blocks.len() as u64)
+}
+
+fn get_block(txid: u64) -> Option<EncodedBlock> {
+    let pos = usize::try_from(txid).ok()?;
+    with_blocks(|blocks| blocks.get(pos)).map(EncodedBlock::from)
+}
+
+fn push_block(txid: u64, block: &EncodedBlock) {
+    with_blocks(|blocks| blocks.append(block.as_slice()))
+        .unwrap_or_else(|e| ic_cdk::api::trap(&format!("Unable to store block {}: {:?}", txid, e)));
+}
+
+/// Returns true if the stable memory contains the state written by an Index
+/// version that serialized its whole state to the raw stable memory.
+///
+/// This function must be called before the blocks are accessed: the memory
+/// manager claims the stable memory on initialization.
+fn is_legacy_layout() -> bool {
+    let memory = DefaultMemoryImpl::default();
+    if memory.size() == 0 {
+        return false;
+    }
+    let mut magic = [0u8; 3];
+    memory.read(0, &mut magic);
+    &magic != MEMORY_MANAGER_MAGIC
 }
 
 fn with_index<R>(f: impl FnOnce(&Index) -> R) -> R {
@@ -74,10 +180,14 @@ pub fn ledger_id() -> CanisterId {
 pub struct InitArgs {
     // The Ledger canister id of the Ledger to index
     pub ledger_id: CanisterId,
+    // The interval in seconds between two consecutive fetches
+    // of blocks from the Ledger. Defaults to 1 second.
+    pub retrieve_blocks_from_ledger_interval_seconds: Option<u64>,
 }
 
 pub fn init(init_args: InitArgs) {
     INDEX.with(|idx| *idx.borrow_mut() = Some(Index::from(init_args)));
+    schedule_build_index(Duration::ZERO);
 }
 
 #[derive(CandidType, Debug, candid::Deserialize, PartialEq)]
@@ -140,43 +250,70 @@ pub fn list_subaccounts(list_subaccounts_args: ListSubaccountsArgs) -> Vec<Subac
     )
 }
 
-pub async fn heartbeat() {
-    if with_index(|idx| idx.is_heartbeat_running) {
-        return;
+/// Marks [build_index] as running for as long as the guard is alive.
+///
+/// The flag is reset when the guard is dropped, which also happens when
+/// the callback holding the guard traps.
+struct BuildIndexGuard;
+
+impl BuildIndexGuard {
+    fn new() -> Option<Self> {
+        with_index_mut(|idx| {
+            if idx.is_build_index_running {
+                return None;
+            }
+            idx.is_build_index_running = true;
+            Some(BuildIndexGuard)
+        })
+    }
+}
+
+impl Drop for BuildIndexGuard {
+    fn drop(&mut self) {
+        with_index_mut(|idx| idx.is_build_index_running = false);
     }
-    with_index_mut(|idx| idx.is_heartbeat_running = true);
+}
+
+/// Schedules the next fetch after the configured interval, then
+/// fetches the new blocks from the Ledger and indexes them.
+///
+/// The next fetch is scheduled before the first call to the Ledger so
+/// that the Index keeps syncing even if one of the callbacks traps.
+pub async fn build_index_and_reschedule() {
+    schedule_build_index(with_index(|idx| idx.retrieve_blocks_from_ledger_interval));
+    let _guard = match BuildIndexGuard::new() {
+        Some(guard) => guard,
+        None => return,
+    };
 
     if let Err(err) = build_index().await {
         ic_cdk::eprintln!("{}Failed to fetch blocks: {}", LOG_PREFIX, err);
     }
+}
 
-    with_index_mut(|idx| idx.is_heartbeat_running = false);
+fn schedule_build_index(after: Duration) {
+    let now = ic_cdk::api::time();
+    ic_cdk::api::set_global_timer(now.saturating_add(after.as_nanos() as u64));
 }
 
-async fn get_transactions_from_ledger(
-    start: u64,
-    length: usize,
-) -> Result<GetTransactionsResponse, String> {
+async fn get_blocks_from_ledger(start: u64) -> Result<LedgerGetBlocksResponse, String> {
     let ledger_id = ledger_id();
-    let req = GetTransactionsRequest {
+    let req = GetBlocksRequest {
         start: Nat::from(start),
-        length: Nat::from(length),
+        length: Nat::from(MAX_BLOCKS_PER_RESPONSE),
     };
-    let (res,): (GetTransactionsResponse,) =
-        ic_cdk::call(ledger_id.get().0, "get_transactions", (req,))
-            .await
-            .map_err(|(code, str)| format!("code: {:#?} message: {}", code, str))?;
+    let (res,): (LedgerGetBlocksResponse,) = ic_cdk::call(ledger_id.get().0, "get_blocks", (req,))
+        .await
+        .map_err(|(code, str)| format!("code: {:#?} message: {}", code, str))?;
     Ok(res)
 }
 
-async fn get_transactions_from_archive(
-    archived: &ArchivedTransactionRange,
-) -> Result<TransactionRange, String> {
-    let req = GetTransactionsRequest {
+async fn get_blocks_from_archive(archived: &ArchivedBlockRange) -> Result<BlockRange, String> {
+    let req = GetBlocksRequest {
         start: archived.start.clone(),
         length: archived.length.clone(),
     };
-    let (res,): (TransactionRange,) = ic_cdk::call(
+    let (res,): (BlockRange,) = ic_cdk::call(
         archived.callback.canister_id.get().0,
         &archived.callback.method,
         (req,),
@@ -188,26 +325,32 @@ async fn get_transactions_from_archive(
 
 async fn build_index() -> Result<(), String> {
     let next_txid = with_index(|idx| idx.next_txid);
-    let res = get_transactions_from_ledger(next_txid, MAX_TRANSACTIONS_PER_RESPONSE).await?;
-    for archived in res.archived_transactions {
-        // The archive node limits the number of transactions returned by a
-        // single get_transaction call.
+    let res = get_blocks_from_ledger(next_txid).await?;
+    for archived in res.archived_blocks {
+        // The archive node limits the number of blocks returned by a
+        // single get_blocks call.
         let last_txid = archived.start.clone() + archived.length.clone();
         let mut next_archived_txid = archived.start.clone();
         while next_archived_txid < last_txid {
-            let archived = ArchivedTransactionRange {
-                start: next_archived_txid,
-                length: archived.length.clone(),
+            let archived = ArchivedBlockRange {
+                start: next_archived_txid.clone(),
+                length: last_txid.clone() - next_archived_txid,
                 callback: archived.callback.clone(),
             };
-            let res = get_transactions_from_archive(&archived).await?;
+            let res = get_blocks_from_archive(&archived).await?;
+            if res.blocks.is_empty() {
+                return Err(format!(
+                    "The archive {} returned no blocks starting from {}",
+                    archived.callback.canister_id, archived.start
+                ));
+            }
             let mut idx = archived
                 .start
                 .0
                 .to_u64()
                 .expect("The Ledger returned an index that is not a valid u64");
-            for transaction in res.transactions {
-                index_transaction(idx, transaction)?;
+            for block in res.blocks {
+                append_block(idx, block)?;
                 idx += 1;
             }
             next_archived_txid = Nat::from(idx);
@@ -218,30 +361,126 @@ async fn build_index() -> Result<(), String> {
         .0
         .to_u64()
         .expect("The Ledger returned an index that is not a valid u64");
-    for transaction in res.transactions {
-        index_transaction(idx, transaction)?;
+    for block in res.blocks {
+        append_block(idx, block)?;
         idx += 1;
     }
     Ok(())
 }
 
-fn index_transaction(txid: u64, transaction: Transaction) -> Result<(), String> {
-    match transaction.kind.as_str() {
-        "mint" => {
-            add_tx(txid, transaction.mint.unwrap().to);
-            Ok(())
+/// Verifies that the block extends the chain of indexed blocks, then
+/// indexes its transaction and applies it to the balances.
+fn append_block(txid: u64, generic_block: GenericBlock) -> Result<(), String> {
+    let next_txid = with_index(|idx| idx.next_txid);
+    if txid != next_txid {
+        return Err(format!(
+            "Expected block {} but the Ledger returned block {}",
+            next_txid, txid
+        ));
+    }
+    let encoded_block = generic_block_to_encoded_block(generic_block)
+        .map_err(|e| format!("Unable to convert block {}: {}", txid, e))?;
+    let block = Block::decode(encoded_block.clone())
+        .map_err(|e| format!("Unable to decode block {}: {}", txid, e))?;
+    let parent_hash = num_blocks()
+        .checked_sub(1)
+        .and_then(get_block)
+        .map(|block| Block::block_hash(&block));
+    if block.parent_hash != parent_hash {
+        return Err(format!(
+            "The parent hash of block {} is {:?} but the previous block has hash {:?}",
+            txid, block.parent_hash, parent_hash
+        ));
+    }
+    process_balance_changes(txid, &block)?;
+    index_block(txid, &block);
+    push_block(txid, &encoded_block);
+    Ok(())
+}
+
+fn process_balance_changes(txid: u64, block: &Block) -> Result<(), String> {
+    let fee_collector = get_fee_collector(block)?;
+    with_index_mut(|idx| {
+        let balances = &mut idx.balances;
+        let fee = match &block.transaction.operation {
+            Operation::Mint { to, amount } => {
+                balances.mint(to, Tokens::from_e8s(*amount))?;
+                Tokens::ZERO
+            }
+            Operation::Burn { from, amount } => {
+                balances.burn(from, Tokens::from_e8s(*amount))?;
+                Tokens::ZERO
+            }
+            Operation::Transfer {
+                from,
+                to,
+                amount,
+                fee,
+                ..
+            } => {
+                let fee = Tokens::from_e8s(*fee);
+                balances.transfer(from, to, Tokens::from_e8s(*amount), fee)?;
+                fee
+            }
+            Operation::Approve { from, fee, .. } => {
+                let fee = Tokens::from_e8s(*fee);
+                balances.burn(from, fee)?;
+                fee
+            }
+        };
+        if let Some(fee_collector) = fee_collector {
+            balances.mint(&fee_collector, fee)?;
         }
-        "burn" => {
-            add_tx(txid, transaction.burn.unwrap().from);
-            Ok(())
+        Ok(())
+    })
+    .map_err(|e: ic_ledger_core::balances::BalanceError| {
+        format!("Unable to apply block {} to the balances: {:?}", txid, e)
+    })
+}
+
+/// Returns the account that received the fee of the transaction in `block`.
+fn get_fee_collector(block: &Block) -> Result<Option<Account>, String> {
+    if block.fee_collector.is_some() {
+        return Ok(block.fee_collector.clone());
+    }
+    let block_index = match block.fee_collector_block_index {
+        Some(block_index) => block_index,
+        None => return Ok(None),
+    };
+    let fee_collector_block = get_block(block_index)
+        .ok_or_else(|| format!("Fee collector block {} not found", block_index))?;
+    let fee_collector_block = Block::decode(fee_collector_block).map_err(|e| {
+        format!(
+            "Unable to decode fee collector block {}: {}",
+            block_index, e
+        )
+    })?;
+    match fee_collector_block.fee_collector {
+        Some(fee_collector) => Ok(Some(fee_collector)),
+        None => Err(format!(
+            "Block {} doesn't set the fee collector",
+            block_index
+        )),
+    }
+}
+
+fn index_block(txid: u64, block: &Block) {
+    match &block.transaction.operation {
+        Operation::Mint { to, .. } => add_tx(txid, to.clone()),
+        Operation::Burn { from, .. } => add_tx(txid, from.clone()),
+        Operation::Transfer {
+            from, to, spender, ..
+        } => {
+            add_tx(txid, from.clone());
+            add_tx(txid, to.clone());
+            if let Some(spender) = spender {
+                add_tx(txid, spender.clone());
+            }
         }
-        "transfer" => {
-            let Transfer { from, to, .. } = transaction.transfer.unwrap();
-            add_tx(txid, from);
-            add_tx(txid, to);
-            Ok(())
+        Operation::Approve { from, spender, .. } => {
+            add_tx(txid, from.clone());
+            add_tx(txid, spender.clone());
         }
-        kind => Err(format!("Found transaction of unknown kind {}", kind)),
     }
 }
 
@@ -315,41 +554,22 @@ fn get_account_transactions_ids(args: GetAccountTransactionsArgs) -> Vec<u64> {
     })
 }
 
-pub async fn get_account_transactions(args: GetAccountTransactionsArgs) -> GetTransactionsResult {
+pub fn get_account_transactions(args: GetAccountTransactionsArgs) -> GetTransactionsResult {
     let txids = get_account_transactions_ids(args);
     let mut txs = vec![];
     for txid in &txids {
-        match get_transactions_from_ledger(*txid, 1).await {
-            Ok(mut res) => {
-                if let Some(tx) = res.transactions.pop() {
-                    txs.push(TransactionWithId {
-                        id: Nat::from(*txid),
-                        transaction: tx,
-                    })
-                } else if let Some(archive) = res.archived_transactions.get(0) {
-                    match get_transactions_from_archive(archive).await {
-                        Ok(res) if !res.transactions.is_empty() => txs.push(TransactionWithId {
-                            id: Nat::from(*txid),
-                            transaction: res.transactions.get(0).unwrap().clone(),
-                        }),
-                        Ok(_) => {
-                            let message = format!("Error fetching transaction {} from archive {}: archive didn't   return the transaction!", txid, archive.callback.canister_id);
-                            ic_cdk::eprintln!("{}{}", LOG_PREFIX, message);
-                            return Err(GetTransactionsErr { message });
-                        }
-                        Err(e) => {
-                            let message = format!(
-                                "Error fetching transaction {}from archive {}: {}",
-                                txid, archive.callback.canister_id, e
-                            );
-                            ic_cdk::eprintln!("{}{}", LOG_PREFIX, message);
-                            return Err(GetTransactionsErr { message });
-                        }
-                    }
-                }
+        match get_block(*txid).map(Block::decode) {
+            Some(Ok(block)) => txs.push(TransactionWithId {
+                id: Nat::from(*txid),
+                transaction: Transaction::from(block),
+            }),
+            Some(Err(e)) => {
+                let message = format!("Error decoding transaction {}: {}", txid, e);
+                ic_cdk::eprintln!("{}{}", LOG_PREFIX, message);
+                return Err(GetTransactionsErr { message });
             }
-            Err(e) => {
-                let message = format!("Error fetching transaction {}: {}", txid, e);
+            None => {
+                let message = format!("Transaction {} not found", txid);
                 ic_cdk::eprintln!("{}{}", LOG_PREFIX, message);
                 return Err(GetTransactionsErr { message });
             }
@@ -361,6 +581,58 @@ pub async fn get_account_transactions(args: GetAccountTransactionsArgs) -> GetTr
     })
 }
 
+#[derive(CandidType, Debug, candid::Deserialize, PartialEq)]
+pub struct GetBlocksResponse {
+    // The number of blocks synced by the Index.
+    pub chain_length: u64,
+    pub blocks: Vec<GenericBlock>,
+}
+
+/// Returns at most [MAX_BLOCKS_PER_RESPONSE] blocks synced from
+/// the Ledger starting from req.start.
+pub fn get_blocks(req: GetBlocksRequest) -> GetBlocksResponse {
+    let start = req.start.0.to_usize().unwrap_or(usize::MAX);
+    let length = req
+        .length
+        .0
+        .to_usize()
+        .unwrap_or(usize::MAX)
+        .min(MAX_BLOCKS_PER_RESPONSE);
+    with_blocks(|blocks| {
+        let end = blocks.len().min(start.saturating_add(length));
+        let blocks_in_range = (start.min(end)..end)
+            .map(|pos| {
+                let block = EncodedBlock::from(blocks.get(pos).expect("bug: missing block"));
+                encoded_block_to_generic_block(&block)
+                    .expect("bug: failed to convert an indexed block")
+            })
+            .collect();
+        GetBlocksResponse {
+            chain_length: blocks.len() as u64,
+            blocks: blocks_in_range,
+        }
+    })
+}
+
+/// Returns the balance of the account computed from the blocks
+/// synced from the Ledger.
+pub fn icrc1_balance_of(account: Account) -> Nat {
+    with_index(|idx| Nat::from(idx.balances.account_balance(&account).get_e8s()))
+}
+
+#[derive(CandidType, Debug, candid::Deserialize, PartialEq)]
+pub struct Status {
+    // The number of blocks synced from the Ledger. The last
+    // synced block is num_blocks_synced - 1.
+    pub num_blocks_synced: Nat,
+}
+
+pub fn status() -> Status {
+    Status {
+        num_blocks_synced: Nat::from(num_blocks()),
+    }
+}
+
 pub fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
     w.encode_gauge(
         "index_stable_memory_pages",
@@ -385,18 +657,101 @@ pub fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> st
     Ok(())
 }
 
+/// Writes the upgrade state to the dedicated virtual memory, growing the
+/// memory as needed.
+struct UpgradesWriter {
+    memory: VMem,
+    offset: u64,
+}
+
+impl Default for UpgradesWriter {
+    fn default() -> Self {
+        Self {
+            memory: memory(UPGRADES_MEMORY_ID),
+            offset: 0,
+        }
+    }
+}
+
+impl io::Write for UpgradesWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let end = self.offset + buf.len() as u64;
+        let capacity = self.memory.size() * WASM_PAGE_SIZE;
+        if end > capacity {
+            let additional_pages = (end - capacity + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+            if self.memory.grow(additional_pages) == -1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "failed to grow the upgrades memory",
+                ));
+            }
+        }
+        self.memory.write(self.offset, buf);
+        self.offset = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads the upgrade state written by [UpgradesWriter].
+struct UpgradesReader {
+    memory: VMem,
+    offset: u64,
+}
+
+impl Default for UpgradesReader {
+    fn default() -> Self {
+        Self {
+            memory: memory(UPGRADES_MEMORY_ID),
+            offset: 0,
+        }
+    }
+}
+
+impl io::Read for UpgradesReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let capacity = self.memory.size() * WASM_PAGE_SIZE;
+        let n = (buf.len() as u64).min(capacity.saturating_sub(self.offset));
+        self.memory
+            .read(self.offset, &mut buf[..usize::try_from(n).unwrap()]);
+        self.offset += n;
+        Ok(usize::try_from(n).unwrap())
+    }
+}
+
 pub fn pre_upgrade() {
-    with_index(|idx| ciborium::ser::into_writer(idx, StableWriter::default()))
+    with_index(|idx| ciborium::ser::into_writer(idx, UpgradesWriter::default()))
         .expect("failed to encode index state");
 }
 
 pub fn post_upgrade() {
-    INDEX.with(|idx| {
-        *idx.borrow_mut() = Some(
-            ciborium::de::from_reader(StableReader::default())
-                .expect("failed to decode index state"),
-        )
-    });
+    let mut index = if is_legacy_layout() {
+        let LegacyIndex { mut index, blocks } = ciborium::de::from_reader(StableReader::default())
+            .expect("failed to decode index state");
+        if blocks.len() as u64 == index.next_txid {
+            // The blocks are moved to the block log, which claims the
+            // stable memory the legacy state was read from.
+            for (txid, block) in blocks.iter().enumerate() {
+                push_block(txid as u64, block);
+            }
+        } else {
+            // Previous versions of the Index didn't store the blocks, so
+            // they must be fetched from the Ledger again.
+            index.next_txid = 0;
+            index.account_index = BTreeMap::new();
+            index.accounts_num = 0;
+            index.balances = LedgerBalances::default();
+        }
+        index
+    } else {
+        ciborium::de::from_reader(UpgradesReader::default()).expect("failed to decode index state")
+    };
+    index.is_build_index_running = false;
+    INDEX.with(|idx| *idx.borrow_mut() = Some(index));
+    schedule_build_index(Duration::ZERO);
 }
 
 #[cfg(test)]
@@ -410,8 +765,10 @@ mod tests {
     use proptest::{option, proptest};
 
     use crate::{
-        add_tx, get_account_transactions_ids, with_index, GetAccountTransactionsArgs, Index, INDEX,
+        add_tx, get_account_transactions_ids, with_index, BuildIndexGuard,
+        GetAccountTransactionsArgs, Index, DEFAULT_RETRIEVE_BLOCKS_FROM_LEDGER_INTERVAL, INDEX,
     };
+    use ic_icrc1::LedgerBalances;
 
     fn account(n: u64) -> Account {
         Account {
@@ -437,9 +794,11 @@ mod tests {
             *idx.borrow_mut() = Some(Index {
                 ledger_id: CanisterId::from_u64(42),
                 next_txid: 0,
-                is_heartbeat_running: false,
+                is_build_index_running: false,
                 account_index,
                 accounts_num: 0,
+                balances: LedgerBalances::default(),
+                retrieve_blocks_from_ledger_interval: DEFAULT_RETRIEVE_BLOCKS_FROM_LEDGER_INTERVAL,
             });
         });
     }
@@ -607,4 +966,18 @@ mod tests {
         assert_eq!(7, add_tx_for(2, 3));
         assert_eq!(8, add_tx_for(2, 10));
     }
+
+    #[test]
+    fn build_index_guard_resets_flag_on_drop() {
+        init_state(vec![]);
+
+        let guard = BuildIndexGuard::new().expect("build_index is not running");
+        assert!(with_index(|idx| idx.is_build_index_running));
+        // Only one build_index can run at a time.
+        assert!(BuildIndexGuard::new().is_none());
+
+        drop(guard);
+        assert!(!with_index(|idx| idx.is_build_index_running));
+        assert!(BuildIndexGuard::new().is_some());
+    }
 }
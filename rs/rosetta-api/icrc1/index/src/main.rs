@@ -1,9 +1,12 @@
 use candid::candid_method;
+use candid::Nat;
 use dfn_core::CanisterId;
-use ic_cdk_macros::{heartbeat, init, post_upgrade, pre_upgrade, query, update};
-use ic_icrc1::Subaccount;
+use ic_cdk_macros::{init, post_upgrade, pre_upgrade, query};
+use ic_icrc1::endpoints::GetBlocksRequest;
+use ic_icrc1::{Account, Subaccount};
 use ic_icrc1_index::{
-    GetAccountTransactionsArgs, GetTransactionsResult, InitArgs, ListSubaccountsArgs,
+    GetAccountTransactionsArgs, GetBlocksResponse, GetTransactionsResult, InitArgs,
+    ListSubaccountsArgs, Status,
 };
 
 fn main() {}
@@ -13,15 +16,34 @@ fn init(args: InitArgs) {
     ic_icrc1_index::init(args);
 }
 
-#[heartbeat]
-async fn heartbeat() {
-    ic_icrc1_index::heartbeat().await;
+#[export_name = "canister_global_timer"]
+fn global_timer() {
+    ic_cdk::setup();
+    ic_cdk::spawn(ic_icrc1_index::build_index_and_reschedule());
 }
 
-#[update]
-#[candid_method(update)]
-async fn get_account_transactions(args: GetAccountTransactionsArgs) -> GetTransactionsResult {
-    ic_icrc1_index::get_account_transactions(args).await
+#[query]
+#[candid_method(query)]
+fn get_account_transactions(args: GetAccountTransactionsArgs) -> GetTransactionsResult {
+    ic_icrc1_index::get_account_transactions(args)
+}
+
+#[query]
+#[candid_method(query)]
+fn get_blocks(req: GetBlocksRequest) -> GetBlocksResponse {
+    ic_icrc1_index::get_blocks(req)
+}
+
+#[query]
+#[candid_method(query)]
+fn icrc1_balance_of(account: Account) -> Nat {
+    ic_icrc1_index::icrc1_balance_of(account)
+}
+
+#[query]
+#[candid_method(query)]
+fn status() -> Status {
+    ic_icrc1_index::status()
 }
 
 #[query]
@@ -1,14 +1,18 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
 use candid::{Decode, Encode, Nat};
 use ic_base_types::PrincipalId;
 use ic_icrc1::{
-    endpoints::{ArchiveInfo, TransferArg, TransferError, Value},
+    endpoints::{
+        ArchiveInfo, GenericValue, GetBlocksRequest, GetBlocksResponse as LedgerGetBlocksResponse,
+        TransferArg, TransferError, Value,
+    },
     Account, Block, Memo, Operation, Subaccount, Transaction,
 };
 use ic_icrc1_index::{
-    GetAccountTransactionsArgs, GetTransactionsResult, InitArgs as IndexInitArgs,
-    ListSubaccountsArgs, TransactionWithId,
+    GetAccountTransactionsArgs, GetBlocksResponse, GetTransactionsResult,
+    InitArgs as IndexInitArgs, ListSubaccountsArgs, Status, TransactionWithId,
 };
 use ic_icrc1_ledger::InitArgs as LedgerInitArgs;
 use ic_ledger_canister_core::archive::ArchiveOptions;
@@ -72,6 +76,7 @@ fn mint_block() -> EncodedBlock {
             memo: Some(Memo::from([1; 32])),
         },
         TimeStamp::new(3, 4),
+        None,
     )
     .encode()
 }
@@ -97,6 +102,7 @@ fn install_ledger(
         minting_account: MINTER.clone(),
         initial_balances,
         transfer_fee: FEE,
+        fee_collector_account: None,
         token_name: TOKEN_NAME.to_string(),
         token_symbol: TOKEN_SYMBOL.to_string(),
         metadata: vec![
@@ -112,7 +118,10 @@ fn install_ledger(
 }
 
 fn install_index(env: &StateMachine, ledger_id: CanisterId) -> CanisterId {
-    let args = IndexInitArgs { ledger_id };
+    let args = IndexInitArgs {
+        ledger_id,
+        retrieve_blocks_from_ledger_interval_seconds: None,
+    };
     env.install_canister(index_wasm(), Encode!(&args).unwrap(), None)
         .unwrap()
 }
@@ -227,8 +236,7 @@ fn get_account_transactions(
     max_results: u64,
 ) -> Vec<TransactionWithId> {
     Decode!(
-        &env.execute_ingress_as(
-            account.owner,
+        &env.query(
             index,
             "get_account_transactions",
             Encode!(&GetAccountTransactionsArgs {
@@ -281,6 +289,94 @@ fn index_ledger_id(env: &StateMachine, index: CanisterId) -> CanisterId {
     .expect("failed to decode ledger_id response")
 }
 
+fn status(env: &StateMachine, index: CanisterId) -> Status {
+    Decode!(
+        &env.query(index, "status", Encode!().unwrap())
+            .expect("failed to query status")
+            .bytes(),
+        Status
+    )
+    .expect("failed to decode status response")
+}
+
+fn icrc1_balance_of(env: &StateMachine, canister_id: CanisterId, account: Account) -> u64 {
+    Decode!(
+        &env.query(canister_id, "icrc1_balance_of", Encode!(&account).unwrap())
+            .expect("failed to query icrc1_balance_of")
+            .bytes(),
+        Nat
+    )
+    .expect("failed to decode icrc1_balance_of response")
+    .0
+    .to_u64()
+    .unwrap()
+}
+
+fn ledger_get_blocks(
+    env: &StateMachine,
+    ledger: CanisterId,
+    start: u64,
+    length: u64,
+) -> LedgerGetBlocksResponse {
+    Decode!(
+        &env.query(
+            ledger,
+            "get_blocks",
+            Encode!(&GetBlocksRequest {
+                start: Nat::from(start),
+                length: Nat::from(length),
+            })
+            .unwrap()
+        )
+        .expect("failed to get_blocks from the ledger")
+        .bytes(),
+        LedgerGetBlocksResponse
+    )
+    .expect("failed to decode the ledger get_blocks response")
+}
+
+fn index_get_blocks(
+    env: &StateMachine,
+    index: CanisterId,
+    start: u64,
+    length: u64,
+) -> GetBlocksResponse {
+    Decode!(
+        &env.query(
+            index,
+            "get_blocks",
+            Encode!(&GetBlocksRequest {
+                start: Nat::from(start),
+                length: Nat::from(length),
+            })
+            .unwrap()
+        )
+        .expect("failed to get_blocks from the index")
+        .bytes(),
+        GetBlocksResponse
+    )
+    .expect("failed to decode the index get_blocks response")
+}
+
+// Advances the time until the index has synced all the blocks of the ledger.
+fn wait_until_sync_is_completed(env: &StateMachine, index: CanisterId, ledger: CanisterId) {
+    const MAX_ATTEMPTS: usize = 100;
+    let chain_length = ledger_get_blocks(env, ledger, 0, 0).chain_length;
+    let mut num_blocks_synced = 0;
+    for _ in 0..MAX_ATTEMPTS {
+        env.advance_time(Duration::from_secs(60));
+        env.tick();
+        num_blocks_synced = status(env, index).num_blocks_synced.0.to_u64().unwrap();
+        if num_blocks_synced == chain_length {
+            return;
+        }
+    }
+    panic!(
+        "The index canister was unable to sync all the blocks with the ledger. Number of blocks synced {} but the ledger chain length is {}",
+        num_blocks_synced, chain_length
+    );
+}
+
 fn account(n: u64) -> Account {
     Account {
         owner: PrincipalId::new_user_test_id(n),
@@ -313,7 +409,7 @@ fn test() {
 
     assert_eq!(ledger_id, index_ledger_id(&env, index_id));
 
-    env.run_until_completion(10_000);
+    wait_until_sync_is_completed(&env, index_id, ledger_id);
 
     // add some transactions
     mint(&env, ledger_id, account(1), 100000); // block=0
@@ -323,7 +419,7 @@ fn test() {
     transfer(&env, ledger_id, account(2), account(1), 20); // block=4
     burn(&env, ledger_id, account(1), 10000); // block=5
 
-    env.tick(); // trigger index heartbeat
+    wait_until_sync_is_completed(&env, index_id, ledger_id);
 
     let txs = get_account_transactions(&env, index_id, account(1), None, u64::MAX);
     assert_eq!(5, txs.len());
@@ -344,7 +440,7 @@ fn test() {
     transfer(&env, ledger_id, account(1), account(3), 6); // block=6
     transfer(&env, ledger_id, account(1), account(2), 7); // block=7
 
-    env.tick(); // trigger index heartbeat
+    wait_until_sync_is_completed(&env, index_id, ledger_id);
 
     // fetch the more recent transfers
     let txs = get_account_transactions(&env, index_id, account(1), Some(offset + 8), 2);
@@ -378,10 +474,19 @@ fn test_upgrade() {
     mint(&env, ledger_id, account(1), 100000); // block=0
     transfer(&env, ledger_id, account(1), account(2), 1); // block=1
 
+    wait_until_sync_is_completed(&env, index_id, ledger_id);
+
     // upgrade the Index
     env.upgrade_canister(index_id, index_wasm(), vec![])
         .expect("Failed to upgrade the Index canister");
 
+    // the blocks are preserved across the upgrade
+    assert_eq!(Nat::from(2), status(&env, index_id).num_blocks_synced);
+    assert_eq!(
+        100000 - 1 - FEE,
+        icrc1_balance_of(&env, index_id, account(1))
+    );
+
     let txs = get_account_transactions(&env, index_id, account(1), None, u64::MAX);
     check_mint(0, account(1), 100000, txs.get(1).unwrap());
     check_transfer(1, account(1), account(2), 1, txs.get(0).unwrap());
@@ -411,7 +516,7 @@ fn test_index_archived_txs() {
 
     // install the index and let it index all the transaction
     let index_id = install_index(&env, ledger_id);
-    env.run_until_completion(10_000);
+    wait_until_sync_is_completed(&env, index_id, ledger_id);
     let txs = get_account_transactions(&env, index_id, account(1), None, u64::MAX);
     assert_eq!(num_txs, txs.len());
     for idx in 0..(num_txs as u64) {
@@ -452,7 +557,7 @@ fn test_index_archived_txs_paging() {
 
     // install the index and let it index all the transaction
     let index_id = install_index(&env, ledger_id);
-    env.run_until_completion(10_000);
+    wait_until_sync_is_completed(&env, index_id, ledger_id);
 
     // check that the index has exactly indexed num_txs transactions
     let txs = get_account_transactions(&env, index_id, account(1), None, u64::MAX);
@@ -460,3 +565,96 @@ fn test_index_archived_txs_paging() {
     let expected_txids: Vec<u64> = (0..ARCHIVE_TRIGGER_THRESHOLD).rev().collect();
     assert_eq!(expected_txids, actual_txids);
 }
+
+#[test]
+fn test_icrc1_balance_of() {
+    let env = StateMachine::new();
+    let ledger_id = install_ledger(
+        &env,
+        vec![(account(1), 1_000_000)],
+        default_archive_options(),
+    );
+    let index_id = install_index(&env, ledger_id);
+
+    mint(&env, ledger_id, account(2), 200_000);
+    transfer(&env, ledger_id, account(1), account(2), 1_000);
+    transfer(&env, ledger_id, account(2), account(3), 150_000);
+    burn(&env, ledger_id, account(1), 50_000);
+    // add enough blocks to archive some of them
+    for _ in 0..ARCHIVE_TRIGGER_THRESHOLD {
+        transfer(&env, ledger_id, account(3), account(1), 1);
+    }
+    assert!(!archives(&env, ledger_id).is_empty());
+
+    wait_until_sync_is_completed(&env, index_id, ledger_id);
+
+    for n in 1..4 {
+        assert_eq!(
+            icrc1_balance_of(&env, ledger_id, account(n)),
+            icrc1_balance_of(&env, index_id, account(n)),
+            "balance mismatch for account {}",
+            account(n)
+        );
+    }
+}
+
+#[test]
+fn test_get_blocks() {
+    let env = StateMachine::new();
+    let ledger_id = install_ledger(
+        &env,
+        vec![(account(1), 1_000_000)],
+        default_archive_options(),
+    );
+    let index_id = install_index(&env, ledger_id);
+
+    for i in 0..ARCHIVE_TRIGGER_THRESHOLD {
+        transfer(&env, ledger_id, account(1), account(2), i + 1);
+    }
+    assert!(!archives(&env, ledger_id).is_empty());
+
+    wait_until_sync_is_completed(&env, index_id, ledger_id);
+
+    let chain_length = ARCHIVE_TRIGGER_THRESHOLD + 1;
+    assert_eq!(
+        Status {
+            num_blocks_synced: Nat::from(chain_length)
+        },
+        status(&env, index_id)
+    );
+
+    // the index serves the same blocks as the ledger, including the
+    // archived ones
+    let ledger_blocks = ledger_get_blocks(&env, ledger_id, 0, chain_length);
+    let res = index_get_blocks(&env, index_id, 0, chain_length);
+    assert_eq!(chain_length, res.chain_length);
+    assert_eq!(chain_length as usize, res.blocks.len());
+    let first_local_block = ledger_blocks.first_index.0.to_u64().unwrap() as usize;
+    assert_eq!(
+        ledger_blocks.blocks,
+        res.blocks[first_local_block..].to_vec()
+    );
+    // the blocks form a valid hash chain
+    for i in 1..res.blocks.len() {
+        let parent_hash = match &res.blocks[i] {
+            GenericValue::Map(fields) => match fields.get("phash") {
+                Some(GenericValue::Blob(parent_hash)) => parent_hash.to_vec(),
+                parent_hash => panic!("expected a blob, got {:?}", parent_hash),
+            },
+            block => panic!("expected a map, got {:?}", block),
+        };
+        assert_eq!(
+            parent_hash,
+            res.blocks[i - 1].hash().to_vec(),
+            "block {} does not refer to the previous block",
+            i
+        );
+    }
+
+    // the start is inclusive and the length is capped by the chain length
+    let res = index_get_blocks(&env, index_id, 2, u64::MAX);
+    assert_eq!(res.blocks.len() as u64, chain_length - 2);
+    assert!(index_get_blocks(&env, index_id, chain_length, 1)
+        .blocks
+        .is_empty());
+}
@@ -5,26 +5,32 @@ use candid::{
     types::number::{Int, Nat},
     CandidType,
 };
+use ic_crypto_tree_hash::{Label, MixedHashTree};
+use ic_icrc1::blocks::encoded_block_to_generic_block;
 use ic_icrc1::endpoints::{
-    ArchivedTransactionRange, GetTransactionsResponse, QueryArchiveFn, Transaction as Tx, Value,
+    ArchivedBlockRange, ArchivedTransactionRange, GetBlocksResponse, GetTransactionsResponse,
+    QueryArchiveFn, QueryBlockArchiveFn, Transaction as Tx, TransferArg, TransferError, Value,
 };
-use ic_icrc1::{Account, Block, LedgerBalances, Transaction};
+use ic_icrc1::{Account, Block, Operation, Transaction};
 use ic_ledger_canister_core::{
+    approvals::AllowanceTable,
     archive::{ArchiveCanisterWasm, ArchiveOptions},
     blockchain::Blockchain,
     ledger::{apply_transaction, block_locations, LedgerData, TransactionInfo},
     range_utils,
+    stable_memory::{MigrateToStable, StableAllowancesData, StableBalances, StableBlockData},
 };
 use ic_ledger_core::{
     balances::Balances,
-    block::{BlockIndex, BlockType, HashOf},
+    block::{BlockIndex, BlockType, FeeCollector, HashOf},
     timestamp::TimeStamp,
     tokens::Tokens,
 };
+use num_traits::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::{BTreeMap, VecDeque};
 use std::time::Duration;
 
 const TRANSACTION_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
@@ -34,6 +40,9 @@ const MAX_ACCOUNTS: usize = 28_000_000;
 const MAX_TRANSACTIONS_PER_REQUEST: usize = 2_000;
 const ACCOUNTS_OVERFLOW_TRIM_QUANTITY: usize = 100_000;
 const MAX_TRANSACTIONS_IN_WINDOW: usize = 3_000_000;
+/// The maximum number of entries of each kind moved to stable memory in a
+/// single migration step.
+const MIGRATION_BATCH_SIZE: usize = 10_000;
 const MAX_TRANSACTIONS_TO_PURGE: usize = 100_000;
 
 #[derive(Debug, Clone)]
@@ -93,6 +102,7 @@ impl From<Value> for StoredValue {
 #[derive(Deserialize, CandidType, Clone, Debug, PartialEq)]
 pub struct InitArgs {
     pub minting_account: Account,
+    pub fee_collector_account: Option<Account>,
     pub initial_balances: Vec<(Account, u64)>,
     pub transfer_fee: u64,
     pub token_name: String,
@@ -101,12 +111,35 @@ pub struct InitArgs {
     pub archive_options: ArchiveOptions,
 }
 
+#[derive(Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub enum ChangeFeeCollector {
+    Unset,
+    SetTo(Account),
+}
+
+/// The parameters the ledger accepts on upgrade. Fields that are not set
+/// leave the corresponding ledger configuration unchanged.
+#[derive(Deserialize, CandidType, Clone, Debug, Default, PartialEq)]
+pub struct UpgradeArgs {
+    /// Metadata entries to set. Each entry replaces the existing entry with the
+    /// same key, if any; other existing entries are kept.
+    pub metadata: Option<Vec<(String, Value)>>,
+    pub token_name: Option<String>,
+    pub token_symbol: Option<String>,
+    pub transfer_fee: Option<u64>,
+    pub change_fee_collector: Option<ChangeFeeCollector>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Ledger {
-    balances: LedgerBalances,
-    blockchain: Blockchain<CdkRuntime, Icrc1ArchiveWasm>,
+    balances: Balances<Account, StableBalances<Account>>,
+    #[serde(default)]
+    approvals: AllowanceTable<StableAllowancesData<Account>>,
+    blockchain: Blockchain<CdkRuntime, Icrc1ArchiveWasm, StableBlockData>,
 
     minting_account: Account,
+    #[serde(default)]
+    fee_collector: Option<FeeCollector<Account>>,
 
     transactions_by_hash: BTreeMap<HashOf<Transaction>, BlockIndex>,
     transactions_by_height: VecDeque<TransactionInfo<Transaction>>,
@@ -121,6 +154,7 @@ impl Ledger {
     pub fn from_init_args(
         InitArgs {
             minting_account,
+            fee_collector_account,
             initial_balances,
             transfer_fee,
             token_name,
@@ -130,12 +164,18 @@ impl Ledger {
         }: InitArgs,
         now: TimeStamp,
     ) -> Self {
+        if fee_collector_account.as_ref() == Some(&minting_account) {
+            panic!("the fee collector account cannot be the same as the minting account");
+        }
+
         let mut ledger = Self {
-            balances: LedgerBalances::default(),
+            balances: Balances::default(),
+            approvals: AllowanceTable::default(),
             blockchain: Blockchain::new_with_archive(archive_options),
             transactions_by_hash: BTreeMap::new(),
             transactions_by_height: VecDeque::new(),
             minting_account,
+            fee_collector: fee_collector_account.map(FeeCollector::from),
             transfer_fee: Tokens::from_e8s(transfer_fee),
             token_symbol,
             token_name,
@@ -158,6 +198,133 @@ impl Ledger {
 
         ledger
     }
+
+    /// Moves a batch of balances, allowances and blocks loaded from a legacy
+    /// ledger state to stable memory. Returns true if the migration is
+    /// complete.
+    pub fn migrate_to_stable(&mut self) -> bool {
+        self.balances.store.migrate_to_stable(MIGRATION_BATCH_SIZE);
+        self.approvals
+            .allowances_data_mut()
+            .migrate_to_stable(MIGRATION_BATCH_SIZE);
+        self.blockchain
+            .blocks
+            .migrate_to_stable(MIGRATION_BATCH_SIZE);
+        self.is_migrated()
+    }
+
+    /// Returns true if all the balances, allowances and blocks are stored in
+    /// stable memory.
+    pub fn is_migrated(&self) -> bool {
+        self.balances.store.is_migrated()
+            && self.approvals.allowances_data().is_migrated()
+            && self.blockchain.blocks.is_migrated()
+    }
+
+    pub fn upgrade(&mut self, args: UpgradeArgs) {
+        if let Some(metadata) = args.metadata {
+            for (key, value) in metadata {
+                let value = StoredValue::from(value);
+                match self.metadata.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, stored_value)) => *stored_value = value,
+                    None => self.metadata.push((key, value)),
+                }
+            }
+        }
+        if let Some(token_name) = args.token_name {
+            self.token_name = token_name;
+        }
+        if let Some(token_symbol) = args.token_symbol {
+            self.token_symbol = token_symbol;
+        }
+        if let Some(transfer_fee) = args.transfer_fee {
+            self.transfer_fee = Tokens::from_e8s(transfer_fee);
+        }
+        if let Some(change_fee_collector) = args.change_fee_collector {
+            self.fee_collector = match change_fee_collector {
+                ChangeFeeCollector::Unset => None,
+                ChangeFeeCollector::SetTo(fee_collector) => {
+                    if fee_collector == self.minting_account {
+                        panic!(
+                            "the fee collector account cannot be the same as the minting account"
+                        );
+                    }
+                    Some(FeeCollector::from(fee_collector))
+                }
+            };
+        }
+    }
+}
+
+/// Validates the transfer arguments and constructs the corresponding transaction.
+pub fn make_transfer(
+    ledger: &Ledger,
+    from_account: Account,
+    arg: TransferArg,
+) -> Result<Transaction, TransferError> {
+    let created_at_time = arg
+        .created_at_time
+        .map(TimeStamp::from_nanos_since_unix_epoch);
+
+    let amount = match arg.amount.0.to_u64() {
+        Some(n) => Tokens::from_e8s(n),
+        None => {
+            // No one can have so many tokens
+            let balance = Nat::from(ledger.balances().account_balance(&from_account).get_e8s());
+            assert!(balance < arg.amount);
+            return Err(TransferError::InsufficientFunds { balance });
+        }
+    };
+
+    let tx = if &arg.to == ledger.minting_account() {
+        let expected_fee = Nat::from(0u64);
+        if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
+            return Err(TransferError::BadFee { expected_fee });
+        }
+
+        let balance = ledger.balances().account_balance(&from_account);
+        let min_burn_amount = ledger.transfer_fee().min(balance);
+        if amount < min_burn_amount {
+            return Err(TransferError::BadBurn {
+                min_burn_amount: Nat::from(min_burn_amount.get_e8s()),
+            });
+        }
+        if amount == Tokens::ZERO {
+            return Err(TransferError::BadBurn {
+                min_burn_amount: Nat::from(ledger.transfer_fee().get_e8s()),
+            });
+        }
+
+        Transaction {
+            operation: Operation::Burn {
+                from: from_account,
+                amount: amount.get_e8s(),
+            },
+            created_at_time: created_at_time.map(|t| t.as_nanos_since_unix_epoch()),
+            memo: arg.memo,
+        }
+    } else if &from_account == ledger.minting_account() {
+        let expected_fee = Nat::from(0u64);
+        if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
+            return Err(TransferError::BadFee { expected_fee });
+        }
+        Transaction::mint(arg.to, amount, created_at_time, arg.memo)
+    } else {
+        let expected_fee_tokens = ledger.transfer_fee();
+        let expected_fee = Nat::from(expected_fee_tokens.get_e8s());
+        if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
+            return Err(TransferError::BadFee { expected_fee });
+        }
+        Transaction::transfer(
+            from_account,
+            arg.to,
+            amount,
+            expected_fee_tokens,
+            created_at_time,
+            arg.memo,
+        )
+    };
+    Ok(tx)
 }
 
 impl LedgerData for Ledger {
@@ -166,6 +333,9 @@ impl LedgerData for Ledger {
     type ArchiveWasm = Icrc1ArchiveWasm;
     type Transaction = Transaction;
     type Block = Block;
+    type BalancesStore = StableBalances<Account>;
+    type AllowancesData = StableAllowancesData<Account>;
+    type BlockData = StableBlockData;
 
     fn transaction_window(&self) -> Duration {
         TRANSACTION_WINDOW
@@ -195,19 +365,37 @@ impl LedgerData for Ledger {
         &self.token_symbol
     }
 
-    fn balances(&self) -> &Balances<Self::AccountId, HashMap<Self::AccountId, Tokens>> {
+    fn fee_collector(&self) -> Option<&FeeCollector<Self::AccountId>> {
+        self.fee_collector.as_ref()
+    }
+
+    fn fee_collector_mut(&mut self) -> Option<&mut FeeCollector<Self::AccountId>> {
+        self.fee_collector.as_mut()
+    }
+
+    fn balances(&self) -> &Balances<Self::AccountId, Self::BalancesStore> {
         &self.balances
     }
 
-    fn balances_mut(&mut self) -> &mut Balances<Self::AccountId, HashMap<Self::AccountId, Tokens>> {
+    fn balances_mut(&mut self) -> &mut Balances<Self::AccountId, Self::BalancesStore> {
         &mut self.balances
     }
 
-    fn blockchain(&self) -> &Blockchain<Self::Runtime, Self::ArchiveWasm> {
+    fn approvals(&self) -> &AllowanceTable<Self::AllowancesData> {
+        &self.approvals
+    }
+
+    fn approvals_mut(&mut self) -> &mut AllowanceTable<Self::AllowancesData> {
+        &mut self.approvals
+    }
+
+    fn blockchain(&self) -> &Blockchain<Self::Runtime, Self::ArchiveWasm, Self::BlockData> {
         &self.blockchain
     }
 
-    fn blockchain_mut(&mut self) -> &mut Blockchain<Self::Runtime, Self::ArchiveWasm> {
+    fn blockchain_mut(
+        &mut self,
+    ) -> &mut Blockchain<Self::Runtime, Self::ArchiveWasm, Self::BlockData> {
         &mut self.blockchain
     }
 
@@ -258,15 +446,30 @@ impl Ledger {
     /// The canister code must call set_certified_data with the value this function returns after
     /// each successful modification of the ledger.
     pub fn root_hash(&self) -> [u8; 32] {
-        use ic_crypto_tree_hash::{Label, MixedHashTree as T};
-        let tree = match self.blockchain().last_hash {
-            Some(hash) => T::Labeled(
-                Label::from("tip_hash"),
-                Box::new(T::Leaf(hash.as_slice().to_vec())),
-            ),
-            None => T::Empty,
-        };
-        tree.digest().0
+        self.construct_hash_tree().digest().0
+    }
+
+    /// Constructs the hash tree of the certified ledger state.
+    pub fn construct_hash_tree(&self) -> MixedHashTree {
+        match self.blockchain().last_hash {
+            Some(hash) => {
+                let last_block_index = self.blockchain().chain_length() - 1;
+                let mut last_block_index_encoded = vec![];
+                leb128::write::unsigned(&mut last_block_index_encoded, last_block_index)
+                    .expect("bug: failed to write a block index");
+                MixedHashTree::Fork(Box::new((
+                    MixedHashTree::Labeled(
+                        Label::from("last_block_index"),
+                        Box::new(MixedHashTree::Leaf(last_block_index_encoded)),
+                    ),
+                    MixedHashTree::Labeled(
+                        Label::from("tip_hash"),
+                        Box::new(MixedHashTree::Leaf(hash.as_slice().to_vec())),
+                    ),
+                )))
+            }
+            None => MixedHashTree::Empty,
+        }
     }
 
     /// Returns transactions in the specified range.
@@ -306,4 +509,41 @@ impl Ledger {
             archived_transactions,
         }
     }
+
+    /// Returns blocks in the specified range in the generic format.
+    pub fn get_blocks(&self, start: BlockIndex, length: usize) -> GetBlocksResponse {
+        let locations = block_locations(self, start, length);
+
+        let local_blocks = range_utils::take(&locations.local_blocks, MAX_TRANSACTIONS_PER_REQUEST);
+
+        let blocks = self
+            .blockchain
+            .block_slice(local_blocks.clone())
+            .iter()
+            .map(|enc_block| {
+                encoded_block_to_generic_block(enc_block)
+                    .expect("bug: failed to convert an encoded block")
+            })
+            .collect();
+
+        let archived_blocks = locations
+            .archived_blocks
+            .into_iter()
+            .map(|(canister_id, slice)| ArchivedBlockRange {
+                start: Nat::from(slice.start),
+                length: Nat::from(range_utils::range_len(&slice)),
+                callback: QueryBlockArchiveFn {
+                    canister_id,
+                    method: "get_blocks".to_string(),
+                },
+            })
+            .collect();
+
+        GetBlocksResponse {
+            first_index: Nat::from(local_blocks.start),
+            chain_length: self.blockchain.chain_length(),
+            blocks,
+            archived_blocks,
+        }
+    }
 }
@@ -1,22 +1,29 @@
 use candid::candid_method;
 use candid::types::number::Nat;
-use ic_base_types::PrincipalId;
-use ic_cdk::api::stable::{StableReader, StableWriter};
+use ic_base_types::{CanisterId, PrincipalId};
+use ic_cdk::api::stable::StableReader;
 use ic_cdk_macros::{init, post_upgrade, pre_upgrade, query, update};
 use ic_icrc1::{
     endpoints::{
-        ArchiveInfo, GetTransactionsRequest, GetTransactionsResponse, StandardRecord, TransferArg,
-        TransferError, Value,
+        Allowance, AllowanceArgs, ApproveArgs, ApproveError, ArchiveInfo, BatchTransferArg,
+        GetBlocksRequest, GetBlocksResponse, GetTransactionsRequest, GetTransactionsResponse,
+        StandardRecord, TipCertificate, TransferArg, TransferError, TransferFromArgs,
+        TransferFromError, Value,
     },
     Account, Operation, Transaction,
 };
-use ic_icrc1_ledger::{InitArgs, Ledger};
+use ic_icrc1_ledger::{make_transfer, InitArgs, Ledger, UpgradeArgs};
+use ic_ledger_canister_core::archive::Archive;
 use ic_ledger_canister_core::ledger::{
-    apply_transaction, archive_blocks, LedgerAccess, LedgerData,
+    apply_transaction, archive_blocks, can_admit_without_throttling, check_deduplication,
+    upgrade_archives, LedgerAccess, LedgerData,
 };
-use ic_ledger_core::{timestamp::TimeStamp, tokens::Tokens};
+use ic_ledger_canister_core::stable_memory::{is_legacy_layout, UpgradesReader, UpgradesWriter};
+use ic_ledger_core::{balances::InspectableBalancesStore, timestamp::TimeStamp};
 use num_traits::ToPrimitive;
+use serde_bytes::ByteBuf;
 use std::cell::RefCell;
+use std::collections::HashMap;
 
 const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
 
@@ -55,18 +62,53 @@ fn init(args: InitArgs) {
 
 #[pre_upgrade]
 fn pre_upgrade() {
-    Access::with_ledger(|ledger| ciborium::ser::into_writer(ledger, StableWriter::default()))
+    Access::with_ledger(|ledger| ciborium::ser::into_writer(ledger, UpgradesWriter::default()))
         .expect("failed to encode ledger state");
 }
 
 #[post_upgrade]
 fn post_upgrade() {
-    LEDGER.with(|cell| {
-        *cell.borrow_mut() = Some(
-            ciborium::de::from_reader(StableReader::default())
-                .expect("failed to decode ledger state"),
-        );
-    })
+    // Ledgers installed before the balances and blocks moved to stable memory
+    // serialized their whole state to the raw stable memory.
+    let ledger: Ledger = if is_legacy_layout() {
+        ciborium::de::from_reader(StableReader::default())
+    } else {
+        ciborium::de::from_reader(UpgradesReader::default())
+    }
+    .expect("failed to decode ledger state");
+    LEDGER.with(|cell| *cell.borrow_mut() = Some(ledger));
+    // Canisters cannot make calls in post_upgrade, so the archive nodes are
+    // upgraded from the timer.
+    schedule_global_timer();
+
+    // Upgrades without arguments keep the ledger configuration unchanged.
+    let arg_bytes = ic_cdk::api::call::arg_data_raw();
+    if !arg_bytes.is_empty() {
+        let (args,): (Option<UpgradeArgs>,) =
+            candid::decode_args(&arg_bytes).expect("failed to decode upgrade arguments");
+        if let Some(args) = args {
+            Access::with_ledger_mut(|ledger| ledger.upgrade(args));
+        }
+    }
+}
+
+fn schedule_global_timer() {
+    ic_cdk::api::set_global_timer(ic_cdk::api::time());
+}
+
+/// Upgrades the archive nodes after a ledger upgrade and moves the next batch
+/// of the legacy ledger state to stable memory.
+#[export_name = "canister_global_timer"]
+fn global_timer() {
+    ic_cdk::setup();
+    ic_cdk::spawn(async {
+        if !upgrade_archives::<Access>().await {
+            schedule_global_timer();
+        }
+    });
+    if !Access::with_ledger_mut(|ledger| ledger.migrate_to_stable()) {
+        schedule_global_timer();
+    }
 }
 
 fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
@@ -93,7 +135,7 @@ fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::i
         )?;
         w.encode_gauge(
             "ledger_transactions",
-            ledger.blockchain().blocks.len() as f64,
+            ledger.blockchain().num_unarchived_blocks() as f64,
             "Total number of transactions stored in the main memory.",
         )?;
         w.encode_gauge(
@@ -111,6 +153,11 @@ fn encode_metrics(w: &mut ic_metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::i
             ledger.balances().store.len() as f64,
             "Total number of accounts in the balance store.",
         )?;
+        w.encode_gauge(
+            "ledger_approval_entries",
+            ledger.approvals().len() as f64,
+            "Total number of approvals in the allowance table.",
+        )?;
         w.encode_gauge(
             "ledger_most_recent_block_time_seconds",
             (ledger
@@ -178,77 +225,332 @@ fn icrc1_total_supply() -> Nat {
     Access::with_ledger(|ledger| Nat::from(ledger.balances().total_supply().get_e8s()))
 }
 
+/// The maximum number of transfers in a single [batch_transfer] call.
+const MAX_TRANSFERS_PER_BATCH: usize = 1_000;
+
 #[update]
 #[candid_method(update)]
 async fn icrc1_transfer(arg: TransferArg) -> Result<Nat, TransferError> {
     let block_idx = Access::with_ledger_mut(|ledger| {
         let now = TimeStamp::from_nanos_since_unix_epoch(ic_cdk::api::time());
-        let created_at_time = arg
-            .created_at_time
-            .map(TimeStamp::from_nanos_since_unix_epoch);
+        let from_account = Account {
+            owner: PrincipalId::from(ic_cdk::api::caller()),
+            subaccount: arg.from_subaccount,
+        };
+        let tx = make_transfer(ledger, from_account, arg)?;
+        let (block_idx, _) = apply_transaction(ledger, tx, now)?;
+        Ok(block_idx)
+    })?;
+
+    // NB. we need to set the certified data before the first async call to make sure that the
+    // blockchain state agrees with the certificate while archiving is in progress.
+    ic_cdk::api::set_certified_data(&Access::with_ledger(Ledger::root_hash));
+
+    archive_blocks::<Access>(MAX_MESSAGE_SIZE).await;
+    Ok(Nat::from(block_idx))
+}
+
+/// Applies a batch of transfers from the caller's accounts and returns one
+/// result per transfer. Each transfer produces its own block and deduplicates
+/// on its own `created_at_time` and memo.
+#[update]
+#[candid_method(update)]
+async fn batch_transfer(arg: BatchTransferArg) -> Vec<Result<Nat, TransferError>> {
+    if arg.transfers.len() > MAX_TRANSFERS_PER_BATCH {
+        ic_cdk::api::trap(&format!(
+            "the batch contains {} transfers, the maximum is {}",
+            arg.transfers.len(),
+            MAX_TRANSFERS_PER_BATCH
+        ));
+    }
+    let caller = PrincipalId::from(ic_cdk::api::caller());
+    let atomic = arg.atomic.unwrap_or(false);
+
+    let results: Vec<Result<Nat, TransferError>> = Access::with_ledger_mut(|ledger| {
+        let now = TimeStamp::from_nanos_since_unix_epoch(ic_cdk::api::time());
+        let from_account = |arg: &TransferArg| Account {
+            owner: caller,
+            subaccount: arg.from_subaccount,
+        };
+
+        if !atomic {
+            return arg
+                .transfers
+                .into_iter()
+                .map(|arg| -> Result<Nat, TransferError> {
+                    let tx = make_transfer(ledger, from_account(&arg), arg)?;
+                    let (block_idx, _) = apply_transaction(ledger, tx, now)?;
+                    Ok(Nat::from(block_idx))
+                })
+                .collect();
+        }
+
+        let num_transfers = arg.transfers.len();
+        let txs = arg
+            .transfers
+            .into_iter()
+            .enumerate()
+            .map(|(i, arg)| make_transfer(ledger, from_account(&arg), arg).map_err(|err| (i, err)))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|txs| check_batch(ledger, &txs, now).map(|()| txs));
+        match txs {
+            Ok(txs) => txs
+                .into_iter()
+                .map(|tx| {
+                    // NB. trapping rolls back the transfers we already applied.
+                    let (block_idx, _) = apply_transaction(ledger, tx, now).unwrap_or_else(|err| {
+                        ic_cdk::api::trap(&format!(
+                            "failed to apply a validated transfer: {:?}",
+                            err
+                        ))
+                    });
+                    Ok(Nat::from(block_idx))
+                })
+                .collect(),
+            Err((failed, err)) => (0..num_transfers)
+                .map(|i| {
+                    if i == failed {
+                        Err(err.clone())
+                    } else {
+                        Err(TransferError::GenericError {
+                            error_code: Nat::from(0u64),
+                            message: format!(
+                                "the batch was rejected because transfer {} failed",
+                                failed
+                            ),
+                        })
+                    }
+                })
+                .collect(),
+        }
+    });
+
+    // NB. we need to set the certified data before the first async call to make sure that the
+    // blockchain state agrees with the certificate while archiving is in progress.
+    ic_cdk::api::set_certified_data(&Access::with_ledger(Ledger::root_hash));
+
+    archive_blocks::<Access>(MAX_MESSAGE_SIZE).await;
+    results
+}
+
+/// Checks that the ledger can apply all the transactions of an atomic batch in
+/// order. On failure, returns the position of the first transaction that would
+/// fail and the error.
+fn check_batch(
+    ledger: &Ledger,
+    txs: &[Transaction],
+    now: TimeStamp,
+) -> Result<(), (usize, TransferError)> {
+    let num_deduplicated = txs.iter().filter(|tx| tx.created_at_time.is_some()).count();
+    if !can_admit_without_throttling(ledger, num_deduplicated) {
+        return Err((0, TransferError::TemporarilyUnavailable));
+    }
+
+    let fee_collector = ledger.fee_collector().map(|fc| fc.fee_collector.clone());
+    let mut balances: HashMap<Account, u64> = HashMap::new();
+    let mut batch_hashes = HashMap::new();
+
+    for (i, tx) in txs.iter().enumerate() {
+        if let Some((_, tx_hash)) =
+            check_deduplication(ledger, tx, now).map_err(|err| (i, TransferError::from(err)))?
+        {
+            if let Some(j) = batch_hashes.insert(tx_hash, i) {
+                return Err((
+                    i,
+                    TransferError::GenericError {
+                        error_code: Nat::from(0u64),
+                        message: format!("duplicate of transfer {} in the same batch", j),
+                    },
+                ));
+            }
+        }
+
+        let (debit, credits) = match &tx.operation {
+            Operation::Mint { to, amount } => (None, vec![(to, *amount)]),
+            Operation::Burn { from, amount } => (Some((from, *amount)), vec![]),
+            Operation::Transfer {
+                from,
+                to,
+                amount,
+                fee,
+                ..
+            } => {
+                let mut credits = vec![(to, *amount)];
+                if let Some(fee_collector) = fee_collector.as_ref() {
+                    credits.push((fee_collector, *fee));
+                }
+                (Some((from, amount.saturating_add(*fee))), credits)
+            }
+            Operation::Approve { .. } => unreachable!("batches contain only transfers"),
+        };
+        if let Some((from, debit)) = debit {
+            let balance = simulated_balance(&mut balances, ledger, from);
+            if *balance < debit {
+                return Err((
+                    i,
+                    TransferError::InsufficientFunds {
+                        balance: Nat::from(*balance),
+                    },
+                ));
+            }
+            *balance -= debit;
+        }
+        for (to, credit) in credits {
+            let balance = simulated_balance(&mut balances, ledger, to);
+            *balance = balance.saturating_add(credit);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the balance of the account after the already checked transactions of a batch.
+fn simulated_balance<'a>(
+    balances: &'a mut HashMap<Account, u64>,
+    ledger: &Ledger,
+    account: &Account,
+) -> &'a mut u64 {
+    balances
+        .entry(account.clone())
+        .or_insert_with(|| ledger.balances().account_balance(account).get_e8s())
+}
+
+#[update]
+#[candid_method(update)]
+async fn icrc2_approve(arg: ApproveArgs) -> Result<Nat, ApproveError> {
+    let block_idx = Access::with_ledger_mut(|ledger| {
+        let now = TimeStamp::from_nanos_since_unix_epoch(ic_cdk::api::time());
 
         let from_account = Account {
             owner: PrincipalId::from(ic_cdk::api::caller()),
             subaccount: arg.from_subaccount,
         };
+        if from_account == arg.spender {
+            return Err(ApproveError::GenericError {
+                error_code: Nat::from(0u64),
+                message: "the spender account must differ from the approver account".to_string(),
+            });
+        }
+        if &from_account == ledger.minting_account() {
+            return Err(ApproveError::GenericError {
+                error_code: Nat::from(0u64),
+                message: "the minting account cannot approve spenders".to_string(),
+            });
+        }
+
+        // No account can hold more than u64::MAX tokens, so larger allowances
+        // are equivalent to u64::MAX.
+        let amount = arg.amount.0.to_u64().unwrap_or(u64::MAX);
+        let expected_allowance = match arg.expected_allowance {
+            Some(n) => match n.0.to_u64() {
+                Some(n) => Some(n),
+                None => {
+                    let current_allowance = ledger
+                        .approvals()
+                        .allowance(&from_account, &arg.spender, now)
+                        .amount;
+                    return Err(ApproveError::AllowanceChanged {
+                        current_allowance: Nat::from(current_allowance.get_e8s()),
+                    });
+                }
+            },
+            None => None,
+        };
+
+        let expected_fee_tokens = ledger.transfer_fee();
+        let expected_fee = Nat::from(expected_fee_tokens.get_e8s());
+        if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
+            return Err(ApproveError::BadFee { expected_fee });
+        }
+
+        let tx = Transaction {
+            operation: Operation::Approve {
+                from: from_account,
+                spender: arg.spender,
+                amount,
+                expected_allowance,
+                expires_at: arg.expires_at,
+                fee: expected_fee_tokens.get_e8s(),
+            },
+            created_at_time: arg.created_at_time,
+            memo: arg.memo,
+        };
+
+        let (block_idx, _) = apply_transaction(ledger, tx, now)?;
+        Ok(block_idx)
+    })?;
+
+    // NB. we need to set the certified data before the first async call to make sure that the
+    // blockchain state agrees with the certificate while archiving is in progress.
+    ic_cdk::api::set_certified_data(&Access::with_ledger(Ledger::root_hash));
+
+    archive_blocks::<Access>(MAX_MESSAGE_SIZE).await;
+    Ok(Nat::from(block_idx))
+}
+
+#[query]
+#[candid_method(query)]
+fn icrc2_allowance(arg: AllowanceArgs) -> Allowance {
+    Access::with_ledger(|ledger| {
+        let now = TimeStamp::from_nanos_since_unix_epoch(ic_cdk::api::time());
+        let allowance = ledger
+            .approvals()
+            .allowance(&arg.account, &arg.spender, now);
+        Allowance {
+            allowance: Nat::from(allowance.amount.get_e8s()),
+            expires_at: allowance.expires_at.map(|t| t.as_nanos_since_unix_epoch()),
+        }
+    })
+}
+
+#[update]
+#[candid_method(update)]
+async fn icrc2_transfer_from(arg: TransferFromArgs) -> Result<Nat, TransferFromError> {
+    let block_idx = Access::with_ledger_mut(|ledger| {
+        let now = TimeStamp::from_nanos_since_unix_epoch(ic_cdk::api::time());
+
+        let spender = Account {
+            owner: PrincipalId::from(ic_cdk::api::caller()),
+            subaccount: arg.spender_subaccount,
+        };
+        if &arg.from == ledger.minting_account() || &arg.to == ledger.minting_account() {
+            return Err(TransferFromError::GenericError {
+                error_code: Nat::from(0u64),
+                message: "minting and burning tokens with transfer_from is not supported"
+                    .to_string(),
+            });
+        }
 
         let amount = match arg.amount.0.to_u64() {
-            Some(n) => Tokens::from_e8s(n),
+            Some(n) => n,
             None => {
                 // No one can have so many tokens
-                let balance = Nat::from(ledger.balances().account_balance(&from_account).get_e8s());
+                let balance = Nat::from(ledger.balances().account_balance(&arg.from).get_e8s());
                 assert!(balance < arg.amount);
-                return Err(TransferError::InsufficientFunds { balance });
+                return Err(TransferFromError::InsufficientFunds { balance });
             }
         };
 
-        let tx = if &arg.to == ledger.minting_account() {
-            let expected_fee = Nat::from(0u64);
-            if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
-                return Err(TransferError::BadFee { expected_fee });
-            }
-
-            let balance = ledger.balances().account_balance(&from_account);
-            let min_burn_amount = ledger.transfer_fee().min(balance);
-            if amount < min_burn_amount {
-                return Err(TransferError::BadBurn {
-                    min_burn_amount: Nat::from(min_burn_amount.get_e8s()),
-                });
-            }
-            if amount == Tokens::ZERO {
-                return Err(TransferError::BadBurn {
-                    min_burn_amount: Nat::from(ledger.transfer_fee().get_e8s()),
-                });
-            }
-
-            Transaction {
-                operation: Operation::Burn {
-                    from: from_account,
-                    amount: amount.get_e8s(),
+        let expected_fee_tokens = ledger.transfer_fee();
+        let expected_fee = Nat::from(expected_fee_tokens.get_e8s());
+        if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
+            return Err(TransferFromError::BadFee { expected_fee });
+        }
+
+        let tx = Transaction {
+            operation: Operation::Transfer {
+                // The owner of the account does not need an allowance.
+                spender: if spender == arg.from {
+                    None
+                } else {
+                    Some(spender)
                 },
-                created_at_time: created_at_time.map(|t| t.as_nanos_since_unix_epoch()),
-                memo: arg.memo,
-            }
-        } else if &from_account == ledger.minting_account() {
-            let expected_fee = Nat::from(0u64);
-            if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
-                return Err(TransferError::BadFee { expected_fee });
-            }
-            Transaction::mint(arg.to, amount, created_at_time, arg.memo)
-        } else {
-            let expected_fee_tokens = ledger.transfer_fee();
-            let expected_fee = Nat::from(expected_fee_tokens.get_e8s());
-            if arg.fee.is_some() && arg.fee.as_ref() != Some(&expected_fee) {
-                return Err(TransferError::BadFee { expected_fee });
-            }
-            Transaction::transfer(
-                from_account,
-                arg.to,
+                from: arg.from,
+                to: arg.to,
                 amount,
-                expected_fee_tokens,
-                created_at_time,
-                arg.memo,
-            )
+                fee: expected_fee_tokens.get_e8s(),
+            },
+            created_at_time: arg.created_at_time,
+            memo: arg.memo,
         };
 
         let (block_idx, _) = apply_transaction(ledger, tx, now)?;
@@ -265,6 +567,21 @@ async fn icrc1_transfer(arg: TransferArg) -> Result<Nat, TransferError> {
 
 #[query]
 fn archives() -> Vec<ArchiveInfo> {
+    archive_infos(|archive| archive.index())
+}
+
+/// Returns the archives that can contain blocks touching the account.
+/// Archives that are left out definitely do not contain such blocks.
+#[query]
+fn archives_for_account(account: Account) -> Vec<ArchiveInfo> {
+    archive_infos(|archive| archive.index_for_account(&account))
+}
+
+fn archive_infos(
+    index: impl Fn(
+        &Archive<<Ledger as LedgerData>::Runtime, <Ledger as LedgerData>::ArchiveWasm>,
+    ) -> Vec<((u64, u64), CanisterId)>,
+) -> Vec<ArchiveInfo> {
     Access::with_ledger(|ledger| {
         ledger
             .blockchain()
@@ -274,8 +591,7 @@ fn archives() -> Vec<ArchiveInfo> {
             .as_ref()
             .iter()
             .flat_map(|archive| {
-                archive
-                    .index()
+                index(archive)
                     .into_iter()
                     .map(|((start, end), canister_id)| ArchiveInfo {
                         canister_id,
@@ -290,10 +606,16 @@ fn archives() -> Vec<ArchiveInfo> {
 #[query(name = "icrc1_supported_standards")]
 #[candid_method(query, rename = "icrc1_supported_standards")]
 fn supported_standards() -> Vec<StandardRecord> {
-    vec![StandardRecord {
-        name: "ICRC-1".to_string(),
-        url: "https://github.com/dfinity/ICRC-1".to_string(),
-    }]
+    vec![
+        StandardRecord {
+            name: "ICRC-1".to_string(),
+            url: "https://github.com/dfinity/ICRC-1".to_string(),
+        },
+        StandardRecord {
+            name: "ICRC-2".to_string(),
+            url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-2".to_string(),
+        },
+    ]
 }
 
 #[query]
@@ -305,6 +627,28 @@ fn get_transactions(req: GetTransactionsRequest) -> GetTransactionsResponse {
     Access::with_ledger(|ledger| ledger.get_transactions(start, length))
 }
 
+#[query]
+#[candid_method(query)]
+fn get_blocks(req: GetBlocksRequest) -> GetBlocksResponse {
+    let (start, length) = req
+        .as_start_and_length()
+        .unwrap_or_else(|msg| ic_cdk::api::trap(&msg));
+    Access::with_ledger(|ledger| ledger.get_blocks(start, length))
+}
+
+#[query]
+#[candid_method(query)]
+fn get_tip_certificate() -> Option<TipCertificate> {
+    let certificate = ByteBuf::from(ic_cdk::api::data_certificate()?);
+    let hash_tree = Access::with_ledger(|ledger| ledger.construct_hash_tree());
+    let mut tree_buf = vec![];
+    ciborium::ser::into_writer(&hash_tree, &mut tree_buf).expect("failed to encode the hash tree");
+    Some(TipCertificate {
+        certificate,
+        hash_tree: ByteBuf::from(tree_buf),
+    })
+}
+
 fn main() {}
 
 #[test]
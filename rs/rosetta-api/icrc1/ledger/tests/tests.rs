@@ -3,18 +3,22 @@ use candid::{CandidType, Decode, Encode};
 use ic_base_types::PrincipalId;
 use ic_icrc1::{
     endpoints::{
-        ArchiveInfo, GetTransactionsRequest, GetTransactionsResponse, StandardRecord,
-        Transaction as Tx, TransactionRange, Transfer, TransferArg, TransferError, Value,
+        Allowance, AllowanceArgs, ApproveArgs, ApproveError, ArchiveInfo, BatchTransferArg,
+        BlockRange, GenericBlock, GenericValue, GetBlocksRequest, GetBlocksResponse,
+        GetTransactionsRequest, GetTransactionsResponse, StandardRecord, TipCertificate,
+        Transaction as Tx, TransactionRange, Transfer, TransferArg, TransferError,
+        TransferFromArgs, TransferFromError, Value,
     },
     Account, Block, Memo, Operation, Transaction,
 };
-use ic_icrc1_ledger::InitArgs;
+use ic_icrc1_ledger::{ChangeFeeCollector, InitArgs, UpgradeArgs};
 use ic_ledger_canister_core::archive::ArchiveOptions;
 use ic_ledger_core::block::{BlockIndex, BlockType, HashOf};
 use ic_state_machine_tests::{CanisterId, ErrorCode, StateMachine};
 use num_traits::ToPrimitive;
 use proptest::prelude::*;
 use proptest::test_runner::{Config as TestRunnerConfig, TestCaseResult, TestRunner};
+use serde_bytes::ByteBuf;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
 use std::path::PathBuf;
@@ -61,9 +65,10 @@ fn archive_wasm() -> Vec<u8> {
     )
 }
 
-fn install_ledger(env: &StateMachine, initial_balances: Vec<(Account, u64)>) -> CanisterId {
-    let args = InitArgs {
+fn init_args(initial_balances: Vec<(Account, u64)>) -> InitArgs {
+    InitArgs {
         minting_account: MINTER.clone(),
+        fee_collector_account: None,
         initial_balances,
         transfer_fee: FEE,
         token_name: TOKEN_NAME.to_string(),
@@ -83,9 +88,16 @@ fn install_ledger(env: &StateMachine, initial_balances: Vec<(Account, u64)>) ->
             cycles_for_archive_creation: None,
             max_transactions_per_response: None,
         },
-    };
-    env.install_canister(ledger_wasm(), Encode!(&args).unwrap(), None)
-        .unwrap()
+    }
+}
+
+fn install_ledger(env: &StateMachine, initial_balances: Vec<(Account, u64)>) -> CanisterId {
+    env.install_canister(
+        ledger_wasm(),
+        Encode!(&init_args(initial_balances)).unwrap(),
+        None,
+    )
+    .unwrap()
 }
 
 fn balance_of(env: &StateMachine, ledger: CanisterId, acc: impl Into<Account>) -> u64 {
@@ -181,6 +193,104 @@ fn transfer(
     )
 }
 
+fn send_batch_transfer(
+    env: &StateMachine,
+    ledger: CanisterId,
+    from: PrincipalId,
+    arg: &BatchTransferArg,
+) -> Vec<Result<BlockIndex, TransferError>> {
+    Decode!(
+        &env.execute_ingress_as(from, ledger, "batch_transfer", Encode!(arg).unwrap())
+            .expect("failed to send a batch of transfers")
+            .bytes(),
+        Vec<Result<Nat, TransferError>>
+    )
+    .expect("failed to decode batch_transfer response")
+    .into_iter()
+    .map(|r| r.map(|n| n.0.to_u64().unwrap()))
+    .collect()
+}
+
+fn send_approval(
+    env: &StateMachine,
+    ledger: CanisterId,
+    from: PrincipalId,
+    arg: &ApproveArgs,
+) -> Result<BlockIndex, ApproveError> {
+    Decode!(
+        &env.execute_ingress_as(from, ledger, "icrc2_approve", Encode!(arg).unwrap())
+            .expect("failed to approve")
+            .bytes(),
+        Result<Nat, ApproveError>
+    )
+    .expect("failed to decode approve response")
+    .map(|n| n.0.to_u64().unwrap())
+}
+
+fn default_approve_args(spender: impl Into<Account>, amount: u64) -> ApproveArgs {
+    ApproveArgs {
+        from_subaccount: None,
+        spender: spender.into(),
+        amount: Nat::from(amount),
+        expected_allowance: None,
+        expires_at: None,
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    }
+}
+
+fn get_allowance(
+    env: &StateMachine,
+    ledger: CanisterId,
+    account: impl Into<Account>,
+    spender: impl Into<Account>,
+) -> Allowance {
+    let arg = AllowanceArgs {
+        account: account.into(),
+        spender: spender.into(),
+    };
+    Decode!(
+        &env.query(ledger, "icrc2_allowance", Encode!(&arg).unwrap())
+            .expect("failed to query the allowance")
+            .bytes(),
+        Allowance
+    )
+    .expect("failed to decode allowance response")
+}
+
+fn send_transfer_from(
+    env: &StateMachine,
+    ledger: CanisterId,
+    spender: PrincipalId,
+    arg: &TransferFromArgs,
+) -> Result<BlockIndex, TransferFromError> {
+    Decode!(
+        &env.execute_ingress_as(spender, ledger, "icrc2_transfer_from", Encode!(arg).unwrap())
+            .expect("failed to transfer funds")
+            .bytes(),
+        Result<Nat, TransferFromError>
+    )
+    .expect("failed to decode transfer_from response")
+    .map(|n| n.0.to_u64().unwrap())
+}
+
+fn default_transfer_from_args(
+    from: impl Into<Account>,
+    to: impl Into<Account>,
+    amount: u64,
+) -> TransferFromArgs {
+    TransferFromArgs {
+        spender_subaccount: None,
+        from: from.into(),
+        to: to.into(),
+        amount: Nat::from(amount),
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    }
+}
+
 fn list_archives(env: &StateMachine, ledger: CanisterId) -> Vec<ArchiveInfo> {
     Decode!(
         &env.query(ledger, "archives", Encode!().unwrap())
@@ -191,6 +301,27 @@ fn list_archives(env: &StateMachine, ledger: CanisterId) -> Vec<ArchiveInfo> {
     .expect("failed to decode archives response")
 }
 
+fn list_archives_for_account(
+    env: &StateMachine,
+    ledger: CanisterId,
+    account: impl Into<Account>,
+) -> Vec<CanisterId> {
+    Decode!(
+        &env.query(
+            ledger,
+            "archives_for_account",
+            Encode!(&account.into()).unwrap()
+        )
+        .expect("failed to query archives for account")
+        .bytes(),
+        Vec<ArchiveInfo>
+    )
+    .expect("failed to decode archives_for_account response")
+    .into_iter()
+    .map(|archive| archive.canister_id)
+    .collect()
+}
+
 fn get_archive_transaction(
     env: &StateMachine,
     archive: CanisterId,
@@ -251,6 +382,62 @@ fn get_archive_transactions(
     .expect("failed to decode get_transactions archive response")
 }
 
+fn get_blocks(
+    env: &StateMachine,
+    ledger: CanisterId,
+    start: u64,
+    length: usize,
+) -> GetBlocksResponse {
+    Decode!(
+        &env.query(
+            ledger,
+            "get_blocks",
+            Encode!(&GetBlocksRequest {
+                start: Nat::from(start),
+                length: Nat::from(length)
+            })
+            .unwrap()
+        )
+        .expect("failed to query ledger blocks")
+        .bytes(),
+        GetBlocksResponse
+    )
+    .expect("failed to decode get_blocks response")
+}
+
+fn get_archive_blocks(
+    env: &StateMachine,
+    archive: CanisterId,
+    start: u64,
+    length: usize,
+) -> BlockRange {
+    Decode!(
+        &env.query(
+            archive,
+            "get_blocks",
+            Encode!(&GetBlocksRequest {
+                start: Nat::from(start),
+                length: Nat::from(length)
+            })
+            .unwrap()
+        )
+        .expect("failed to query archive blocks")
+        .bytes(),
+        BlockRange
+    )
+    .expect("failed to decode get_blocks archive response")
+}
+
+fn get_tip_certificate(env: &StateMachine, ledger: CanisterId) -> Option<TipCertificate> {
+    Decode!(
+        &env.query(ledger, "get_tip_certificate", Encode!().unwrap())
+            .expect("failed to query the tip certificate")
+            .bytes(),
+        Option<TipCertificate>
+    )
+    .expect("failed to decode get_tip_certificate response")
+}
+
 fn system_time_to_nanos(t: SystemTime) -> u64 {
     t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_nanos() as u64
 }
@@ -326,10 +513,16 @@ fn test_metadata() {
     let standards = supported_standards(&env, canister_id);
     assert_eq!(
         standards,
-        vec![StandardRecord {
-            name: "ICRC-1".to_string(),
-            url: "https://github.com/dfinity/ICRC-1".to_string(),
-        }]
+        vec![
+            StandardRecord {
+                name: "ICRC-1".to_string(),
+                url: "https://github.com/dfinity/ICRC-1".to_string(),
+            },
+            StandardRecord {
+                name: "ICRC-2".to_string(),
+                url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-2".to_string(),
+            },
+        ]
     );
 }
 
@@ -526,6 +719,401 @@ fn test_single_transfer() {
     assert_eq!(6_000_000u64, balance_of(&env, canister_id, p2));
 }
 
+#[test]
+fn test_batch_transfer() {
+    let env = StateMachine::new();
+    let p1 = PrincipalId::new_user_test_id(1);
+    let p2 = PrincipalId::new_user_test_id(2);
+    let p3 = PrincipalId::new_user_test_id(3);
+    let canister_id = install_ledger(&env, vec![(Account::from(p1), 1_000_000)]);
+
+    let now = system_time_to_nanos(env.time());
+    let transfer_arg = |to: PrincipalId, amount: u64, created_at_time: Option<u64>| TransferArg {
+        from_subaccount: None,
+        to: to.into(),
+        fee: None,
+        created_at_time,
+        amount: Nat::from(amount),
+        memo: None,
+    };
+
+    // Per-item mode applies the transfers that succeed.
+    let results = send_batch_transfer(
+        &env,
+        canister_id,
+        p1,
+        &BatchTransferArg {
+            transfers: vec![
+                transfer_arg(p2, 100_000, Some(now)),
+                transfer_arg(p3, 10_000_000, None),
+                transfer_arg(p3, 200_000, None),
+            ],
+            atomic: None,
+        },
+    );
+    assert_eq!(results.len(), 3);
+    let first_block = results[0].clone().expect("first transfer failed");
+    assert!(matches!(
+        results[1],
+        Err(TransferError::InsufficientFunds { .. })
+    ));
+    assert_eq!(results[2], Ok(first_block + 1));
+    assert_eq!(100_000, balance_of(&env, canister_id, p2));
+    assert_eq!(200_000, balance_of(&env, canister_id, p3));
+    assert_eq!(
+        1_000_000 - 300_000 - 2 * FEE,
+        balance_of(&env, canister_id, p1)
+    );
+
+    // Deduplication still applies to each item.
+    let results = send_batch_transfer(
+        &env,
+        canister_id,
+        p1,
+        &BatchTransferArg {
+            transfers: vec![transfer_arg(p2, 100_000, Some(now))],
+            atomic: None,
+        },
+    );
+    assert_eq!(
+        results,
+        vec![Err(TransferError::Duplicate {
+            duplicate_of: Nat::from(first_block)
+        })]
+    );
+
+    // Atomic mode rejects the whole batch if a single transfer fails.
+    let results = send_batch_transfer(
+        &env,
+        canister_id,
+        p1,
+        &BatchTransferArg {
+            transfers: vec![
+                transfer_arg(p2, 100_000, None),
+                transfer_arg(p3, 1_000_000, None),
+            ],
+            atomic: Some(true),
+        },
+    );
+    assert!(matches!(
+        results[0],
+        Err(TransferError::GenericError { .. })
+    ));
+    assert!(matches!(
+        results[1],
+        Err(TransferError::InsufficientFunds { .. })
+    ));
+    assert_eq!(100_000, balance_of(&env, canister_id, p2));
+
+    let results = send_batch_transfer(
+        &env,
+        canister_id,
+        p1,
+        &BatchTransferArg {
+            transfers: vec![
+                transfer_arg(p2, 100_000, None),
+                transfer_arg(p3, 100_000, None),
+            ],
+            atomic: Some(true),
+        },
+    );
+    assert!(results.iter().all(|r| r.is_ok()));
+    assert_eq!(200_000, balance_of(&env, canister_id, p2));
+    assert_eq!(300_000, balance_of(&env, canister_id, p3));
+}
+
+#[test]
+fn test_approve_and_transfer_from() {
+    let env = StateMachine::new();
+    let from = PrincipalId::new_user_test_id(1);
+    let spender = PrincipalId::new_user_test_id(2);
+    let to = PrincipalId::new_user_test_id(3);
+    let canister_id = install_ledger(&env, vec![(Account::from(from), 1_000_000)]);
+
+    send_approval(
+        &env,
+        canister_id,
+        from,
+        &default_approve_args(spender, 500_000),
+    )
+    .expect("approval failed");
+    assert_eq!(1_000_000 - FEE, balance_of(&env, canister_id, from));
+    assert_eq!(
+        Nat::from(500_000u64),
+        get_allowance(&env, canister_id, from, spender).allowance
+    );
+
+    send_transfer_from(
+        &env,
+        canister_id,
+        spender,
+        &default_transfer_from_args(from, to, 100_000),
+    )
+    .expect("transfer_from failed");
+    assert_eq!(
+        1_000_000 - 2 * FEE - 100_000,
+        balance_of(&env, canister_id, from)
+    );
+    assert_eq!(100_000, balance_of(&env, canister_id, to));
+    assert_eq!(0, balance_of(&env, canister_id, spender));
+    assert_eq!(
+        Nat::from(500_000u64 - 100_000 - FEE),
+        get_allowance(&env, canister_id, from, spender).allowance
+    );
+
+    assert_eq!(
+        Err(TransferFromError::InsufficientAllowance {
+            allowance: Nat::from(500_000u64 - 100_000 - FEE)
+        }),
+        send_transfer_from(
+            &env,
+            canister_id,
+            spender,
+            &default_transfer_from_args(from, to, 400_000),
+        )
+    );
+
+    assert_eq!(
+        Err(ApproveError::AllowanceChanged {
+            current_allowance: Nat::from(500_000u64 - 100_000 - FEE)
+        }),
+        send_approval(
+            &env,
+            canister_id,
+            from,
+            &ApproveArgs {
+                expected_allowance: Some(Nat::from(500_000u64)),
+                ..default_approve_args(spender, 0)
+            },
+        )
+    );
+
+    env.upgrade_canister(canister_id, ledger_wasm(), Encode!().unwrap())
+        .expect("failed to upgrade the ledger");
+    assert_eq!(
+        Nat::from(500_000u64 - 100_000 - FEE),
+        get_allowance(&env, canister_id, from, spender).allowance
+    );
+
+    send_approval(&env, canister_id, from, &default_approve_args(spender, 0))
+        .expect("failed to revoke the approval");
+    assert_eq!(
+        Nat::from(0u64),
+        get_allowance(&env, canister_id, from, spender).allowance
+    );
+}
+
+#[test]
+fn test_approval_expiration() {
+    let env = StateMachine::new();
+    let from = PrincipalId::new_user_test_id(1);
+    let spender = PrincipalId::new_user_test_id(2);
+    let canister_id = install_ledger(&env, vec![(Account::from(from), 1_000_000)]);
+
+    let now = system_time_to_nanos(env.time());
+    assert_eq!(
+        Err(ApproveError::Expired { ledger_time: now }),
+        send_approval(
+            &env,
+            canister_id,
+            from,
+            &ApproveArgs {
+                expires_at: Some(now),
+                ..default_approve_args(spender, 100_000)
+            },
+        )
+    );
+
+    let expires_at = now + Duration::from_secs(3600).as_nanos() as u64;
+    send_approval(
+        &env,
+        canister_id,
+        from,
+        &ApproveArgs {
+            expires_at: Some(expires_at),
+            ..default_approve_args(spender, 100_000)
+        },
+    )
+    .expect("approval failed");
+    assert_eq!(
+        Allowance {
+            allowance: Nat::from(100_000u64),
+            expires_at: Some(expires_at),
+        },
+        get_allowance(&env, canister_id, from, spender)
+    );
+
+    env.advance_time(Duration::from_secs(3600));
+    assert_eq!(
+        Allowance {
+            allowance: Nat::from(0u64),
+            expires_at: None,
+        },
+        get_allowance(&env, canister_id, from, spender)
+    );
+}
+
+#[test]
+fn test_fee_collector() {
+    let env = StateMachine::new();
+    let p1 = PrincipalId::new_user_test_id(1);
+    let p2 = PrincipalId::new_user_test_id(2);
+    let fee_collector = Account::from(PrincipalId::new_user_test_id(3));
+    let canister_id = env
+        .install_canister(
+            ledger_wasm(),
+            Encode!(&InitArgs {
+                fee_collector_account: Some(fee_collector.clone()),
+                ..init_args(vec![(Account::from(p1), 10_000_000)])
+            })
+            .unwrap(),
+            None,
+        )
+        .unwrap();
+
+    transfer(&env, canister_id, p1, p2, 1_000_000).expect("transfer failed");
+    transfer(&env, canister_id, p1, p2, 1_000_000).expect("transfer failed");
+
+    assert_eq!(10_000_000, total_supply(&env, canister_id));
+    assert_eq!(
+        2 * FEE,
+        balance_of(&env, canister_id, fee_collector.clone())
+    );
+
+    let upgrade_args = UpgradeArgs {
+        change_fee_collector: Some(ChangeFeeCollector::Unset),
+        ..UpgradeArgs::default()
+    };
+    env.upgrade_canister(
+        canister_id,
+        ledger_wasm(),
+        Encode!(&Some(upgrade_args)).unwrap(),
+    )
+    .expect("failed to upgrade the ledger");
+
+    transfer(&env, canister_id, p1, p2, 1_000_000).expect("transfer failed");
+
+    assert_eq!(10_000_000 - FEE, total_supply(&env, canister_id));
+    assert_eq!(
+        2 * FEE,
+        balance_of(&env, canister_id, fee_collector.clone())
+    );
+
+    let upgrade_args = UpgradeArgs {
+        change_fee_collector: Some(ChangeFeeCollector::SetTo(p2.into())),
+        ..UpgradeArgs::default()
+    };
+    env.upgrade_canister(
+        canister_id,
+        ledger_wasm(),
+        Encode!(&Some(upgrade_args)).unwrap(),
+    )
+    .expect("failed to upgrade the ledger");
+
+    transfer(&env, canister_id, p1, p1, 1_000_000).expect("transfer failed");
+
+    assert_eq!(10_000_000 - FEE, total_supply(&env, canister_id));
+    assert_eq!(3_000_000 + FEE, balance_of(&env, canister_id, p2));
+    assert_eq!(
+        10_000_000 - 3_000_000 - 4 * FEE,
+        balance_of(&env, canister_id, p1)
+    );
+}
+
+#[test]
+fn test_upgrade_ledger_parameters() {
+    let env = StateMachine::new();
+    let p1 = PrincipalId::new_user_test_id(1);
+    let p2 = PrincipalId::new_user_test_id(2);
+    let canister_id = install_ledger(&env, vec![(Account::from(p1), 10_000_000)]);
+
+    const NEW_FEE: u64 = 2 * FEE;
+    const LOGO_KEY: &str = "icrc1:logo";
+    const LOGO_VALUE: &str = "data:image/png;base64,aGVsbG8=";
+    let upgrade_args = UpgradeArgs {
+        metadata: Some(vec![
+            Value::entry(TEXT_META_KEY, "happy_cat.png"),
+            Value::entry(LOGO_KEY, LOGO_VALUE),
+        ]),
+        token_name: Some("New Test Token".to_string()),
+        token_symbol: Some("NTST".to_string()),
+        transfer_fee: Some(NEW_FEE),
+        ..UpgradeArgs::default()
+    };
+    env.upgrade_canister(
+        canister_id,
+        ledger_wasm(),
+        Encode!(&Some(upgrade_args)).unwrap(),
+    )
+    .expect("failed to upgrade the ledger");
+
+    let metadata = metadata(&env, canister_id);
+    assert_eq!(metadata["icrc1:name"], Value::from("New Test Token"));
+    assert_eq!(metadata["icrc1:symbol"], Value::from("NTST"));
+    assert_eq!(metadata["icrc1:fee"], Value::from(NEW_FEE));
+    assert_eq!(metadata[TEXT_META_KEY], Value::from("happy_cat.png"));
+    assert_eq!(metadata[LOGO_KEY], Value::from(LOGO_VALUE));
+    // Entries that are not part of the upgrade arguments are kept.
+    assert_eq!(metadata[BLOB_META_KEY], Value::from(BLOB_META_VALUE));
+
+    transfer(&env, canister_id, p1, p2, 1_000_000).expect("transfer failed");
+    assert_eq!(
+        10_000_000 - 1_000_000 - NEW_FEE,
+        balance_of(&env, canister_id, p1)
+    );
+}
+
+#[test]
+fn test_fee_collector_blocks() {
+    use ic_icrc1_ledger::Ledger;
+    use ic_ledger_canister_core::ledger::{apply_transaction, LedgerData};
+    use ic_ledger_core::{timestamp::TimeStamp, tokens::Tokens};
+
+    let p1 = Account::from(PrincipalId::new_user_test_id(1));
+    let p2 = Account::from(PrincipalId::new_user_test_id(2));
+    let fee_collector = Account::from(PrincipalId::new_user_test_id(3));
+    let now = TimeStamp::from_nanos_since_unix_epoch(1_000_000_000);
+
+    let mut ledger = Ledger::from_init_args(
+        InitArgs {
+            fee_collector_account: Some(fee_collector.clone()),
+            ..init_args(vec![(p1.clone(), 10_000_000)])
+        },
+        now,
+    );
+    for _ in 0..2 {
+        let tx = Transaction::transfer(
+            p1.clone(),
+            p2.clone(),
+            Tokens::from_e8s(1_000_000),
+            Tokens::from_e8s(FEE),
+            None,
+            None,
+        );
+        apply_transaction(&mut ledger, tx, now).expect("failed to apply a transfer");
+    }
+
+    let blocks: Vec<Block> = ledger
+        .blockchain()
+        .block_slice(0..3)
+        .iter()
+        .map(|b| Block::decode(b.clone()).unwrap())
+        .collect();
+
+    // The first block records the fee collector account, the following blocks
+    // refer to the first block.
+    assert_eq!(blocks[0].fee_collector, Some(fee_collector.clone()));
+    assert_eq!(blocks[0].fee_collector_block_index, None);
+    for block in &blocks[1..] {
+        assert_eq!(block.fee_collector, None);
+        assert_eq!(block.fee_collector_block_index, Some(0));
+    }
+    assert_eq!(
+        Tokens::from_e8s(2 * FEE),
+        ledger.balances().account_balance(&fee_collector)
+    );
+}
+
 #[test]
 fn test_account_canonicalization() {
     let env = StateMachine::new();
@@ -759,6 +1347,7 @@ fn test_archiving() {
         let expected_tx = Transfer {
             from: p1.into(),
             to: p2.into(),
+            spender: None,
             amount: Nat::from(10_000 + i - 1),
             fee: Some(Nat::from(FEE)),
             memo: None,
@@ -795,6 +1384,7 @@ fn test_archiving() {
             Some(Transfer {
                 from: p1.into(),
                 to: p2.into(),
+                spender: None,
                 amount: Nat::from(10_000 + i - 1),
                 fee: Some(Nat::from(FEE)),
                 memo: None,
@@ -831,6 +1421,120 @@ fn test_archiving() {
     }
 }
 
+#[test]
+fn test_archives_for_account() {
+    let env = StateMachine::new();
+    let p1 = PrincipalId::new_user_test_id(1);
+    let p2 = PrincipalId::new_user_test_id(2);
+    let p3 = PrincipalId::new_user_test_id(3);
+
+    let canister_id = install_ledger(&env, vec![(Account::from(p1), 10_000_000)]);
+
+    for i in 0..ARCHIVE_TRIGGER_THRESHOLD {
+        transfer(&env, canister_id, p1, p2, 10_000 + i).expect("transfer failed");
+    }
+
+    env.run_until_completion(/*max_ticks=*/ 10);
+
+    let archive_info = list_archives(&env, canister_id);
+    assert_eq!(archive_info.len(), 1);
+    let archive_canister_id = archive_info[0].canister_id;
+
+    let check_archives = || {
+        assert_eq!(
+            list_archives_for_account(&env, canister_id, p1),
+            vec![archive_canister_id]
+        );
+        assert_eq!(
+            list_archives_for_account(&env, canister_id, p2),
+            vec![archive_canister_id]
+        );
+        assert_eq!(
+            list_archives_for_account(&env, canister_id, p3),
+            Vec::<CanisterId>::new()
+        );
+    };
+    check_archives();
+
+    // The filters survive ledger upgrades.
+    env.upgrade_canister(canister_id, ledger_wasm(), Encode!().unwrap())
+        .expect("failed to upgrade the ledger");
+    check_archives();
+}
+
+#[test]
+fn test_get_blocks() {
+    use ic_crypto_tree_hash::{Label, MixedHashTree};
+
+    let env = StateMachine::new();
+    let p1 = PrincipalId::new_user_test_id(1);
+    let p2 = PrincipalId::new_user_test_id(2);
+
+    let canister_id = install_ledger(&env, vec![(Account::from(p1), 10_000_000)]);
+
+    for i in 0..ARCHIVE_TRIGGER_THRESHOLD {
+        transfer(&env, canister_id, p1, p2, 10_000 + i).expect("transfer failed");
+    }
+
+    env.run_until_completion(/*max_ticks=*/ 10);
+
+    let resp = get_blocks(&env, canister_id, 0, 1_000_000);
+    assert_eq!(resp.chain_length, ARCHIVE_TRIGGER_THRESHOLD + 1);
+    assert_eq!(resp.first_index, Nat::from(NUM_BLOCKS_TO_ARCHIVE));
+    assert_eq!(resp.archived_blocks.len(), 1);
+
+    let archived = &resp.archived_blocks[0];
+    assert_eq!(archived.start, Nat::from(0));
+    assert_eq!(archived.length, Nat::from(NUM_BLOCKS_TO_ARCHIVE));
+    assert_eq!(archived.callback.method, "get_blocks");
+
+    let mut blocks: Vec<GenericBlock> = get_archive_blocks(
+        &env,
+        archived.callback.canister_id,
+        0,
+        NUM_BLOCKS_TO_ARCHIVE as usize,
+    )
+    .blocks;
+    blocks.extend(resp.blocks);
+    assert_eq!(blocks.len() as u64, resp.chain_length);
+
+    // Check that the generic blocks form a valid hash chain.
+    for i in 1..blocks.len() {
+        let parent_hash = match &blocks[i] {
+            GenericValue::Map(fields) => fields.get("phash").cloned(),
+            block => panic!("expected a map, got {:?}", block),
+        };
+        assert_eq!(
+            parent_hash,
+            Some(GenericValue::Blob(ByteBuf::from(
+                blocks[i - 1].hash().to_vec()
+            ))),
+            "block {} does not refer to the previous block",
+            i
+        );
+    }
+
+    let certificate =
+        get_tip_certificate(&env, canister_id).expect("the query must return a certificate");
+    let hash_tree: MixedHashTree = ciborium::de::from_reader(certificate.hash_tree.as_slice())
+        .expect("failed to decode the hash tree");
+    let mut last_block_index = vec![];
+    leb128::write::unsigned(&mut last_block_index, resp.chain_length - 1).unwrap();
+    assert_eq!(
+        hash_tree,
+        MixedHashTree::Fork(Box::new((
+            MixedHashTree::Labeled(
+                Label::from("last_block_index"),
+                Box::new(MixedHashTree::Leaf(last_block_index)),
+            ),
+            MixedHashTree::Labeled(
+                Label::from("tip_hash"),
+                Box::new(MixedHashTree::Leaf(blocks.last().unwrap().hash().to_vec())),
+            ),
+        )))
+    );
+}
+
 fn arb_amount() -> impl Strategy<Value = u64> {
     any::<u64>()
 }
@@ -850,14 +1554,41 @@ fn arb_account() -> impl Strategy<Value = Account> {
 }
 
 fn arb_transfer() -> impl Strategy<Value = Operation> {
-    (arb_account(), arb_account(), arb_amount(), arb_amount()).prop_map(
-        |(from, to, amount, fee)| Operation::Transfer {
+    (
+        arb_account(),
+        arb_account(),
+        proptest::option::of(arb_account()),
+        arb_amount(),
+        arb_amount(),
+    )
+        .prop_map(|(from, to, spender, amount, fee)| Operation::Transfer {
             from,
             to,
+            spender,
             amount,
             fee,
-        },
+        })
+}
+
+fn arb_approve() -> impl Strategy<Value = Operation> {
+    (
+        arb_account(),
+        arb_account(),
+        arb_amount(),
+        any::<Option<u64>>(),
+        any::<Option<u64>>(),
+        arb_amount(),
     )
+        .prop_map(
+            |(from, spender, amount, expected_allowance, expires_at, fee)| Operation::Approve {
+                from,
+                spender,
+                amount,
+                expected_allowance,
+                expires_at,
+                fee,
+            },
+        )
 }
 
 fn arb_mint() -> impl Strategy<Value = Operation> {
@@ -869,7 +1600,7 @@ fn arb_burn() -> impl Strategy<Value = Operation> {
 }
 
 fn arb_operation() -> impl Strategy<Value = Operation> {
-    prop_oneof![arb_transfer(), arb_mint(), arb_burn()]
+    prop_oneof![arb_transfer(), arb_mint(), arb_burn(), arb_approve()]
 }
 
 fn arb_transaction() -> impl Strategy<Value = Transaction> {
@@ -886,13 +1617,22 @@ fn arb_transaction() -> impl Strategy<Value = Transaction> {
 }
 
 fn arb_block() -> impl Strategy<Value = Block> {
-    (any::<Option<[u8; 32]>>(), arb_transaction(), any::<u64>()).prop_map(
-        |(parent_hash, transaction, ts)| Block {
-            parent_hash: parent_hash.map(HashOf::new),
-            transaction,
-            timestamp: ts,
-        },
+    (
+        any::<Option<[u8; 32]>>(),
+        arb_transaction(),
+        any::<u64>(),
+        proptest::option::of(arb_account()),
+        any::<Option<u64>>(),
     )
+        .prop_map(
+            |(parent_hash, transaction, ts, fee_collector, fee_collector_block_index)| Block {
+                parent_hash: parent_hash.map(HashOf::new),
+                transaction,
+                timestamp: ts,
+                fee_collector,
+                fee_collector_block_index,
+            },
+        )
 }
 
 // Generate random blocks and check that their CBOR encoding complies with the CDDL spec.
@@ -921,6 +1661,65 @@ fn block_encoding_agrees_with_the_schema() {
         .unwrap();
 }
 
+// Check that the hash of a generic block is equal to the hash of the encoded block.
+#[test]
+fn generic_block_hashes_agree_with_block_hashes() {
+    use ic_icrc1::blocks::encoded_block_to_generic_block;
+
+    let mut runner = TestRunner::default();
+    runner
+        .run(&arb_block(), |block| {
+            let encoded_block = block.encode();
+            let generic_block = encoded_block_to_generic_block(&encoded_block)
+                .expect("failed to convert an encoded block");
+            prop_assert_eq!(
+                Block::block_hash(&encoded_block).into_bytes(),
+                generic_block.hash()
+            );
+            Ok(())
+        })
+        .unwrap();
+}
+
+// Check that converting a generic block back into an encoded block preserves
+// the block and its hash.
+#[test]
+fn generic_block_to_encoded_block_round_trip() {
+    use ic_icrc1::blocks::{encoded_block_to_generic_block, generic_block_to_encoded_block};
+
+    let mut runner = TestRunner::default();
+    runner
+        .run(&arb_block(), |block| {
+            let encoded_block = block.clone().encode();
+            let generic_block = encoded_block_to_generic_block(&encoded_block)
+                .expect("failed to convert an encoded block");
+            let decoded_block = generic_block_to_encoded_block(generic_block)
+                .expect("failed to convert a generic block");
+            prop_assert_eq!(
+                Block::block_hash(&encoded_block),
+                Block::block_hash(&decoded_block)
+            );
+            prop_assert_eq!(block, Block::decode(decoded_block).unwrap());
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[test]
+fn account_stable_encoding_round_trip() {
+    use ic_ledger_canister_core::stable_memory::{StableAccountId, MAX_ACCOUNT_ID_SIZE};
+
+    let mut runner = TestRunner::default();
+    runner
+        .run(&arb_account(), |account| {
+            let bytes = account.to_stable_bytes();
+            prop_assert!(bytes.len() <= MAX_ACCOUNT_ID_SIZE as usize);
+            prop_assert_eq!(account, Account::from_stable_bytes(&bytes));
+            Ok(())
+        })
+        .unwrap();
+}
+
 // Check that different blocks produce different hashes.
 #[test]
 fn transaction_hashes_are_unique() {
@@ -0,0 +1,102 @@
+use crate::endpoints::{GenericBlock, GenericValue};
+use candid::types::number::{Int, Nat};
+use ciborium::value::{Integer, Value};
+use ic_ledger_core::block::EncodedBlock;
+use num_traits::ToPrimitive;
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+
+/// The self-describing CBOR tag that prefixes encoded blocks.
+const SELF_DESCRIBED_CBOR_TAG: u64 = 55799;
+
+/// Converts an encoded block into its generic representation.
+///
+/// The conversion preserves the structure of the block, so the hash of the
+/// generic block is equal to the hash of the encoded block.
+pub fn encoded_block_to_generic_block(block: &EncodedBlock) -> Result<GenericBlock, String> {
+    let value: Value = ciborium::de::from_reader(block.as_slice())
+        .map_err(|e| format!("failed to decode a block: {}", e))?;
+    cbor_to_generic_value(value)
+}
+
+/// Converts a generic block back into an encoded block.
+///
+/// The bytes of the resulting block might differ from the bytes the ledger
+/// produced, but the block decodes to the same value and has the same hash.
+pub fn generic_block_to_encoded_block(block: GenericBlock) -> Result<EncodedBlock, String> {
+    let value = Value::Tag(
+        SELF_DESCRIBED_CBOR_TAG,
+        Box::new(generic_value_to_cbor(block)?),
+    );
+    let mut bytes = vec![];
+    ciborium::ser::into_writer(&value, &mut bytes)
+        .map_err(|e| format!("failed to encode a block: {}", e))?;
+    Ok(EncodedBlock::from_vec(bytes))
+}
+
+fn generic_value_to_cbor(value: GenericValue) -> Result<Value, String> {
+    match value {
+        GenericValue::Nat(n) => {
+            let n: u128 =
+                n.0.to_u128()
+                    .ok_or_else(|| format!("nat {} does not fit into 128 bits", n))?;
+            Integer::try_from(n)
+                .map(Value::Integer)
+                .map_err(|e| format!("nat {} is not a valid CBOR integer: {}", n, e))
+        }
+        GenericValue::Int(i) => {
+            let i: i128 =
+                i.0.to_i128()
+                    .ok_or_else(|| format!("int {} does not fit into 128 bits", i))?;
+            Integer::try_from(i)
+                .map(Value::Integer)
+                .map_err(|e| format!("int {} is not a valid CBOR integer: {}", i, e))
+        }
+        GenericValue::Text(text) => Ok(Value::Text(text)),
+        GenericValue::Blob(bytes) => Ok(Value::Bytes(bytes.into_vec())),
+        GenericValue::Array(values) => values
+            .into_iter()
+            .map(generic_value_to_cbor)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        GenericValue::Map(entries) => entries
+            .into_iter()
+            .map(|(key, value)| Ok((Value::Text(key), generic_value_to_cbor(value)?)))
+            .collect::<Result<Vec<_>, String>>()
+            .map(Value::Map),
+    }
+}
+
+fn cbor_to_generic_value(value: Value) -> Result<GenericValue, String> {
+    match value {
+        Value::Integer(int) => {
+            let v: i128 = int.into();
+            if v < 0 {
+                Ok(GenericValue::Int(Int::from(v)))
+            } else {
+                Ok(GenericValue::Nat(Nat::from(v as u128)))
+            }
+        }
+        Value::Bytes(bytes) => Ok(GenericValue::Blob(ByteBuf::from(bytes))),
+        Value::Text(text) => Ok(GenericValue::Text(text)),
+        Value::Tag(_tag, value) => cbor_to_generic_value(*value),
+        Value::Array(values) => values
+            .into_iter()
+            .map(cbor_to_generic_value)
+            .collect::<Result<Vec<_>, _>>()
+            .map(GenericValue::Array),
+        Value::Map(entries) => {
+            let mut map = BTreeMap::new();
+            for (key, value) in entries {
+                match key {
+                    Value::Text(key) => {
+                        map.insert(key, cbor_to_generic_value(value)?);
+                    }
+                    key => return Err(format!("unsupported map key: {:?}", key)),
+                }
+            }
+            Ok(GenericValue::Map(map))
+        }
+        value => Err(format!("unsupported value type: {:?}", value)),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::hash::Hash;
 use crate::{Account, Block, Memo, Subaccount};
 use candid::types::number::{Int, Nat};
 use candid::CandidType;
@@ -5,6 +6,7 @@ use ic_base_types::CanisterId;
 use ic_ledger_canister_core::ledger::TransferError as CoreTransferError;
 use serde::Deserialize;
 use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 
 pub type NumTokens = Nat;
@@ -42,6 +44,11 @@ impl From<CoreTransferError> for TransferError {
             LTE::TxDuplicate { duplicate_of } => TE::Duplicate {
                 duplicate_of: Nat::from(duplicate_of),
             },
+            LTE::InsufficientAllowance { .. }
+            | LTE::ExpiredApproval { .. }
+            | LTE::AllowanceChanged { .. } => {
+                unreachable!("transfers do not use approvals")
+            }
         }
     }
 }
@@ -60,6 +67,149 @@ pub struct TransferArg {
     pub amount: NumTokens,
 }
 
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct BatchTransferArg {
+    pub transfers: Vec<TransferArg>,
+    /// If true, the ledger either applies all the transfers or none of them.
+    /// Otherwise, the ledger applies each transfer independently.
+    #[serde(default)]
+    pub atomic: Option<bool>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct ApproveArgs {
+    #[serde(default)]
+    pub from_subaccount: Option<Subaccount>,
+    pub spender: Account,
+    pub amount: NumTokens,
+    #[serde(default)]
+    pub expected_allowance: Option<NumTokens>,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub fee: Option<NumTokens>,
+    #[serde(default)]
+    pub memo: Option<Memo>,
+    #[serde(default)]
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum ApproveError {
+    BadFee { expected_fee: NumTokens },
+    InsufficientFunds { balance: NumTokens },
+    AllowanceChanged { current_allowance: NumTokens },
+    Expired { ledger_time: u64 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: BlockIndex },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+impl From<CoreTransferError> for ApproveError {
+    fn from(err: CoreTransferError) -> Self {
+        use ic_ledger_canister_core::ledger::TransferError as LTE;
+        use ApproveError as AE;
+
+        match err {
+            LTE::BadFee { expected_fee } => AE::BadFee {
+                expected_fee: Nat::from(expected_fee.get_e8s()),
+            },
+            LTE::InsufficientFunds { balance } => AE::InsufficientFunds {
+                balance: Nat::from(balance.get_e8s()),
+            },
+            LTE::TxTooOld { .. } => AE::TooOld,
+            LTE::TxCreatedInFuture { ledger_time } => AE::CreatedInFuture {
+                ledger_time: ledger_time.as_nanos_since_unix_epoch(),
+            },
+            LTE::TxThrottled => AE::TemporarilyUnavailable,
+            LTE::TxDuplicate { duplicate_of } => AE::Duplicate {
+                duplicate_of: Nat::from(duplicate_of),
+            },
+            LTE::ExpiredApproval { now } => AE::Expired {
+                ledger_time: now.as_nanos_since_unix_epoch(),
+            },
+            LTE::AllowanceChanged { current_allowance } => AE::AllowanceChanged {
+                current_allowance: Nat::from(current_allowance.get_e8s()),
+            },
+            LTE::InsufficientAllowance { .. } => {
+                unreachable!("approvals do not spend allowances")
+            }
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct AllowanceArgs {
+    pub account: Account,
+    pub spender: Account,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Allowance {
+    pub allowance: NumTokens,
+    pub expires_at: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct TransferFromArgs {
+    #[serde(default)]
+    pub spender_subaccount: Option<Subaccount>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: NumTokens,
+    #[serde(default)]
+    pub fee: Option<NumTokens>,
+    #[serde(default)]
+    pub memo: Option<Memo>,
+    #[serde(default)]
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum TransferFromError {
+    BadFee { expected_fee: NumTokens },
+    BadBurn { min_burn_amount: NumTokens },
+    InsufficientFunds { balance: NumTokens },
+    InsufficientAllowance { allowance: NumTokens },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: BlockIndex },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+impl From<CoreTransferError> for TransferFromError {
+    fn from(err: CoreTransferError) -> Self {
+        use ic_ledger_canister_core::ledger::TransferError as LTE;
+        use TransferFromError as TFE;
+
+        match err {
+            LTE::BadFee { expected_fee } => TFE::BadFee {
+                expected_fee: Nat::from(expected_fee.get_e8s()),
+            },
+            LTE::InsufficientFunds { balance } => TFE::InsufficientFunds {
+                balance: Nat::from(balance.get_e8s()),
+            },
+            LTE::TxTooOld { .. } => TFE::TooOld,
+            LTE::TxCreatedInFuture { ledger_time } => TFE::CreatedInFuture {
+                ledger_time: ledger_time.as_nanos_since_unix_epoch(),
+            },
+            LTE::TxThrottled => TFE::TemporarilyUnavailable,
+            LTE::TxDuplicate { duplicate_of } => TFE::Duplicate {
+                duplicate_of: Nat::from(duplicate_of),
+            },
+            LTE::InsufficientAllowance { allowance } => TFE::InsufficientAllowance {
+                allowance: Nat::from(allowance.get_e8s()),
+            },
+            LTE::ExpiredApproval { .. } | LTE::AllowanceChanged { .. } => {
+                unreachable!("transfers do not change approvals")
+            }
+        }
+    }
+}
+
 /// Variant type for the `metadata` endpoint values.
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
 pub enum Value {
@@ -187,6 +337,19 @@ pub struct Transfer {
     pub amount: Nat,
     pub from: Account,
     pub to: Account,
+    pub spender: Option<Account>,
+    pub memo: Option<Memo>,
+    pub fee: Option<Nat>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Approve {
+    pub from: Account,
+    pub spender: Account,
+    pub amount: Nat,
+    pub expected_allowance: Option<Nat>,
+    pub expires_at: Option<u64>,
     pub memo: Option<Memo>,
     pub fee: Option<Nat>,
     pub created_at_time: Option<u64>,
@@ -198,6 +361,7 @@ pub struct Transaction {
     pub mint: Option<Mint>,
     pub burn: Option<Burn>,
     pub transfer: Option<Transfer>,
+    pub approve: Option<Approve>,
     pub timestamp: u64,
 }
 
@@ -267,6 +431,110 @@ impl CandidType for QueryArchiveFn {
     }
 }
 
+/// A self-describing value that the generic block log uses to represent
+/// blocks.
+///
+/// The hash of a value does not depend on its encoding, see [GenericValue::hash].
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum GenericValue {
+    Nat(Nat),
+    Int(Int),
+    Text(String),
+    Blob(ByteBuf),
+    Array(Vec<GenericValue>),
+    Map(BTreeMap<String, GenericValue>),
+}
+
+pub type GenericBlock = GenericValue;
+
+impl GenericValue {
+    /// Computes the representation-independent hash of this value.
+    /// See https://internetcomputer.org/docs/current/references/ic-interface-spec/#hash-of-map
+    pub fn hash(&self) -> Hash {
+        crate::hash::hash_generic_value(self)
+    }
+}
+
+pub type GetBlocksRequest = GetTransactionsRequest;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockRange {
+    pub blocks: Vec<GenericBlock>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArchivedBlockRange {
+    pub start: Nat,
+    pub length: Nat,
+    pub callback: QueryBlockArchiveFn,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct GetBlocksResponse {
+    pub first_index: BlockIndex,
+    pub chain_length: u64,
+    pub blocks: Vec<GenericBlock>,
+    pub archived_blocks: Vec<ArchivedBlockRange>,
+}
+
+/// The certificate of the ledger tip.
+///
+/// The hash tree contains the hash of the last block under the `tip_hash`
+/// label and the LEB128-encoded index of the last block under the
+/// `last_block_index` label. The root hash of the tree is the certified data
+/// of the ledger canister.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct TipCertificate {
+    pub certificate: ByteBuf,
+    pub hash_tree: ByteBuf,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(try_from = "candid::types::reference::Func")]
+pub struct QueryBlockArchiveFn {
+    pub canister_id: CanisterId,
+    pub method: String,
+}
+
+impl From<QueryBlockArchiveFn> for candid::types::reference::Func {
+    fn from(archive_fn: QueryBlockArchiveFn) -> Self {
+        let p: &ic_base_types::PrincipalId = archive_fn.canister_id.as_ref();
+        Self {
+            principal: p.0,
+            method: archive_fn.method,
+        }
+    }
+}
+
+impl TryFrom<candid::types::reference::Func> for QueryBlockArchiveFn {
+    type Error = String;
+    fn try_from(func: candid::types::reference::Func) -> Result<Self, Self::Error> {
+        let canister_id = CanisterId::try_from(func.principal.as_slice())
+            .map_err(|e| format!("principal is not a canister id: {}", e))?;
+        Ok(QueryBlockArchiveFn {
+            canister_id,
+            method: func.method,
+        })
+    }
+}
+
+impl CandidType for QueryBlockArchiveFn {
+    fn _ty() -> candid::types::Type {
+        candid::types::Type::Func(candid::types::Function {
+            modes: vec![candid::parser::types::FuncMode::Query],
+            args: vec![GetBlocksRequest::_ty()],
+            rets: vec![BlockRange::_ty()],
+        })
+    }
+
+    fn idl_serialize<S>(&self, serializer: S) -> Result<(), S::Error>
+    where
+        S: candid::types::Serializer,
+    {
+        candid::types::reference::Func::from(self.clone()).idl_serialize(serializer)
+    }
+}
+
 impl From<Block> for Transaction {
     fn from(b: Block) -> Transaction {
         use crate::Operation;
@@ -276,6 +544,7 @@ impl From<Block> for Transaction {
             mint: None,
             burn: None,
             transfer: None,
+            approve: None,
             timestamp: b.timestamp,
         };
         let created_at_time = b.transaction.created_at_time;
@@ -303,6 +572,7 @@ impl From<Block> for Transaction {
             Operation::Transfer {
                 from,
                 to,
+                spender,
                 amount,
                 fee,
             } => {
@@ -310,7 +580,28 @@ impl From<Block> for Transaction {
                 tx.transfer = Some(Transfer {
                     from,
                     to,
+                    spender,
+                    amount: Nat::from(amount),
+                    fee: Some(Nat::from(fee)),
+                    created_at_time,
+                    memo,
+                });
+            }
+            Operation::Approve {
+                from,
+                spender,
+                amount,
+                expected_allowance,
+                expires_at,
+                fee,
+            } => {
+                tx.kind = "approve".to_string();
+                tx.approve = Some(Approve {
+                    from,
+                    spender,
                     amount: Nat::from(amount),
+                    expected_allowance: expected_allowance.map(Nat::from),
+                    expires_at,
                     fee: Some(Nat::from(fee)),
                     created_at_time,
                     memo,
@@ -1,3 +1,4 @@
+use crate::endpoints::GenericValue;
 use ciborium::value::Value;
 use ic_crypto_sha::Sha256;
 
@@ -72,6 +73,47 @@ fn hash_value(value: &Value) -> Result<Hash, String> {
     }
 }
 
+/// Implements representation-independent hashing for generic values.
+/// The hash agrees with [hash_cbor] on the values that both functions support.
+pub fn hash_generic_value(value: &GenericValue) -> Hash {
+    match value {
+        GenericValue::Nat(nat) => {
+            let mut buf = vec![];
+            nat.encode(&mut buf).expect("bug: failed to encode a nat");
+            Sha256::hash(&buf)
+        }
+        GenericValue::Int(int) => {
+            let mut buf = vec![];
+            int.encode(&mut buf).expect("bug: failed to encode an int");
+            Sha256::hash(&buf)
+        }
+        GenericValue::Text(text) => Sha256::hash(text.as_bytes()),
+        GenericValue::Blob(bytes) => Sha256::hash(bytes),
+        GenericValue::Array(values) => {
+            let mut hasher = Sha256::new();
+            for v in values.iter() {
+                hasher.write(&hash_generic_value(v));
+            }
+            hasher.finish()
+        }
+        GenericValue::Map(map) => {
+            let mut hpairs: Vec<_> = map
+                .iter()
+                .map(|(k, v)| (Sha256::hash(k.as_bytes()), hash_generic_value(v)))
+                .collect();
+
+            hpairs.sort_unstable();
+
+            let mut hasher = Sha256::new();
+            for (khash, vhash) in hpairs.iter() {
+                hasher.write(&khash[..]);
+                hasher.write(&vhash[..]);
+            }
+            hasher.finish()
+        }
+    }
+}
+
 #[test]
 fn check_interface_spec_example() {
     use ciborium::cbor;
@@ -104,3 +146,27 @@ fn hash_max_u64() {
         hash_value(&Value::Bytes(bytes)).expect("failed to hash leb128 bytes")
     );
 }
+
+#[test]
+fn generic_value_hash_agrees_with_cbor_hash() {
+    use ciborium::cbor;
+    use serde_bytes::ByteBuf;
+
+    let value = cbor!({
+         "request_type" => "call",
+         "canister_id" => ByteBuf::from(b"\x00\x00\x00\x00\x00\x00\x04\xD2".to_vec()),
+         "method_name" => "hello",
+         "arg" => ByteBuf::from(b"DIDL\x00\xFD*".to_vec()),
+         "nonce" => u64::MAX,
+         "paths" => [ByteBuf::from(b"time".to_vec())],
+    })
+    .unwrap();
+    let mut bytes = vec![];
+    ciborium::ser::into_writer(&value, &mut bytes).unwrap();
+
+    let generic_value = crate::blocks::encoded_block_to_generic_block(
+        &ic_ledger_core::block::EncodedBlock::from_vec(bytes),
+    )
+    .unwrap();
+    assert_eq!(hash_value(&value).unwrap(), generic_value.hash());
+}
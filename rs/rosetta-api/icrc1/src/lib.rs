@@ -1,13 +1,15 @@
+pub mod blocks;
 pub mod endpoints;
 pub mod hash;
 
 use candid::CandidType;
 use ciborium::tag::Required;
 use ic_base_types::PrincipalId;
-use ic_ledger_canister_core::ledger::LedgerTransaction;
+use ic_ledger_canister_core::ledger::{LedgerData, LedgerTransaction, TxApplyError};
+use ic_ledger_canister_core::stable_memory::StableAccountId;
 use ic_ledger_core::{
-    balances::{BalanceError, Balances, BalancesStore},
-    block::{BlockType, EncodedBlock, HashOf},
+    balances::Balances,
+    block::{BlockIndex, BlockType, EncodedBlock, FeeCollector, HashOf},
     timestamp::TimeStamp,
     tokens::Tokens,
 };
@@ -65,6 +67,37 @@ impl std::hash::Hash for Account {
     }
 }
 
+/// The stable encoding of an account is the length of the owner, the owner
+/// and the effective subaccount, so that accounts with the default
+/// subaccount have a single encoding.
+impl StableAccountId for Account {
+    fn to_stable_bytes(&self) -> Vec<u8> {
+        let owner = self.owner.as_slice();
+        let mut bytes = Vec::with_capacity(1 + owner.len() + 32);
+        bytes.push(owner.len() as u8);
+        bytes.extend_from_slice(owner);
+        bytes.extend_from_slice(self.effective_subaccount());
+        bytes
+    }
+
+    fn from_stable_bytes(bytes: &[u8]) -> Self {
+        let owner_len = bytes[0] as usize;
+        let owner = PrincipalId::try_from(&bytes[1..1 + owner_len])
+            .expect("BUG: invalid stable encoding of an account owner");
+        let subaccount: Subaccount = bytes[1 + owner_len..1 + owner_len + 32]
+            .try_into()
+            .expect("BUG: invalid stable encoding of a subaccount");
+        Self {
+            owner,
+            subaccount: if &subaccount == DEFAULT_SUBACCOUNT {
+                None
+            } else {
+                Some(subaccount)
+            },
+        }
+    }
+}
+
 impl fmt::Display for Account {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.subaccount {
@@ -99,6 +132,24 @@ where
     Account::try_from(compact_account).map_err(D::Error::custom)
 }
 
+fn ser_opt_compact_account<S>(acc: &Option<Account>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::ser::Serializer,
+{
+    acc.clone().map(CompactAccount::from).serialize(s)
+}
+
+fn de_opt_compact_account<'de, D>(d: D) -> Result<Option<Account>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    use serde::de::Error;
+    Option::<CompactAccount>::deserialize(d)?
+        .map(Account::try_from)
+        .transpose()
+        .map_err(D::Error::custom)
+}
+
 /// A compact representation of an Account.
 ///
 /// Instead of encoding accounts as structs with named fields,
@@ -174,6 +225,13 @@ pub enum Operation {
         #[serde(serialize_with = "ser_compact_account")]
         #[serde(deserialize_with = "de_compact_account")]
         to: Account,
+        /// The account that spent the allowance `from` granted to it, if the
+        /// transfer was made with `icrc2_transfer_from`.
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(serialize_with = "ser_opt_compact_account")]
+        #[serde(deserialize_with = "de_opt_compact_account")]
+        spender: Option<Account>,
         #[serde(rename = "amt")]
         amount: u64,
         fee: u64,
@@ -186,6 +244,24 @@ pub enum Operation {
         #[serde(rename = "amt")]
         amount: u64,
     },
+    #[serde(rename = "approve")]
+    Approve {
+        #[serde(serialize_with = "ser_compact_account")]
+        #[serde(deserialize_with = "de_compact_account")]
+        from: Account,
+        #[serde(serialize_with = "ser_compact_account")]
+        #[serde(deserialize_with = "de_compact_account")]
+        spender: Account,
+        #[serde(rename = "amt")]
+        amount: u64,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expected_allowance: Option<u64>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_at: Option<u64>,
+        fee: u64,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -308,20 +384,91 @@ impl LedgerTransaction for Transaction {
             })
     }
 
-    fn apply<S>(&self, balances: &mut Balances<Self::AccountId, S>) -> Result<(), BalanceError>
+    fn apply<L>(&self, ledger: &mut L, now: TimeStamp) -> Result<(), TxApplyError>
     where
-        S: Default + BalancesStore<Self::AccountId>,
+        L: LedgerData<AccountId = Self::AccountId>,
     {
         match &self.operation {
             Operation::Transfer {
                 from,
                 to,
+                spender,
+                amount,
+                fee,
+            } => {
+                let amount = Tokens::from_e8s(*amount);
+                let fee = Tokens::from_e8s(*fee);
+                match spender {
+                    None => {
+                        ledger.balances_mut().transfer(from, to, amount, fee)?;
+                        collect_fee(ledger, fee);
+                    }
+                    Some(spender) => {
+                        // The spender pays both the amount and the fee from the allowance.
+                        let allowance = ledger.approvals().allowance(from, spender, now).amount;
+                        let debit_amount = (amount + fee)
+                            .map_err(|_| TxApplyError::InsufficientAllowance { allowance })?;
+                        if allowance < debit_amount {
+                            return Err(TxApplyError::InsufficientAllowance { allowance });
+                        }
+                        ledger.balances_mut().transfer(from, to, amount, fee)?;
+                        collect_fee(ledger, fee);
+                        ledger
+                            .approvals_mut()
+                            .use_allowance(from, spender, debit_amount, now)
+                            .expect("bug: the allowance must cover the transfer amount and fee");
+                    }
+                }
+            }
+            Operation::Burn { from, amount } => ledger
+                .balances_mut()
+                .burn(from, Tokens::from_e8s(*amount))?,
+            Operation::Mint { to, amount } => {
+                ledger.balances_mut().mint(to, Tokens::from_e8s(*amount))?
+            }
+            Operation::Approve {
+                from,
+                spender,
                 amount,
+                expected_allowance,
+                expires_at,
                 fee,
-            } => balances.transfer(from, to, Tokens::from_e8s(*amount), Tokens::from_e8s(*fee)),
-            Operation::Burn { from, amount } => balances.burn(from, Tokens::from_e8s(*amount)),
-            Operation::Mint { to, amount } => balances.mint(to, Tokens::from_e8s(*amount)),
+            } => {
+                let fee = Tokens::from_e8s(*fee);
+                let balance = ledger.balances().account_balance(from);
+                if balance < fee {
+                    return Err(TxApplyError::InsufficientFunds { balance });
+                }
+                ledger.approvals_mut().approve(
+                    from,
+                    spender,
+                    Tokens::from_e8s(*amount),
+                    expires_at.map(TimeStamp::from_nanos_since_unix_epoch),
+                    now,
+                    expected_allowance.map(Tokens::from_e8s),
+                )?;
+                ledger
+                    .balances_mut()
+                    .burn(from, fee)
+                    .expect("bug: the account must have enough funds to pay the approval fee");
+                collect_fee(ledger, fee);
+            }
         }
+        Ok(())
+    }
+}
+
+/// Credits the burned transaction `fee` to the fee collector of the ledger,
+/// if there is one.
+fn collect_fee<L>(ledger: &mut L, fee: Tokens)
+where
+    L: LedgerData<AccountId = Account>,
+{
+    if let Some(fee_collector) = ledger.fee_collector().map(|fc| fc.fee_collector.clone()) {
+        ledger
+            .balances_mut()
+            .mint(&fee_collector, fee)
+            .expect("bug: failed to credit the fee collector");
     }
 }
 
@@ -354,6 +501,7 @@ impl Transaction {
             operation: Operation::Transfer {
                 from,
                 to,
+                spender: None,
                 amount: amount.get_e8s(),
                 fee: fee.get_e8s(),
             },
@@ -372,12 +520,26 @@ pub struct Block {
     pub transaction: Transaction,
     #[serde(rename = "ts")]
     pub timestamp: u64,
+    /// The account that received the fee of this block's transaction.
+    /// Only set in the first block after the fee collector changed.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "fee_col")]
+    #[serde(serialize_with = "ser_opt_compact_account")]
+    #[serde(deserialize_with = "de_opt_compact_account")]
+    pub fee_collector: Option<Account>,
+    /// The index of the block that recorded the current fee collector.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "fee_col_block")]
+    pub fee_collector_block_index: Option<BlockIndex>,
 }
 
 type TaggedBlock = Required<Block, 55799>;
 
 impl BlockType for Block {
     type Transaction = Transaction;
+    type AccountId = Account;
 
     fn encode(self) -> EncodedBlock {
         let mut bytes = vec![];
@@ -413,15 +575,46 @@ impl BlockType for Block {
         TimeStamp::from_nanos_since_unix_epoch(self.timestamp)
     }
 
+    fn accounts(&self) -> Vec<Self::AccountId> {
+        let mut accounts = match &self.transaction.operation {
+            Operation::Mint { to, .. } => vec![to.clone()],
+            Operation::Burn { from, .. } => vec![from.clone()],
+            Operation::Transfer {
+                from, to, spender, ..
+            } => {
+                let mut accounts = vec![from.clone(), to.clone()];
+                accounts.extend(spender.clone());
+                accounts
+            }
+            Operation::Approve { from, spender, .. } => vec![from.clone(), spender.clone()],
+        };
+        accounts.extend(self.fee_collector.clone());
+        accounts
+    }
+
     fn from_transaction(
         parent_hash: Option<HashOf<EncodedBlock>>,
         transaction: Self::Transaction,
         timestamp: TimeStamp,
+        fee_collector: Option<&FeeCollector<Self::AccountId>>,
     ) -> Self {
+        let (fee_collector, fee_collector_block_index) = match fee_collector {
+            Some(FeeCollector {
+                block_index: Some(block_index),
+                ..
+            }) => (None, Some(*block_index)),
+            Some(FeeCollector {
+                fee_collector,
+                block_index: None,
+            }) => (Some(fee_collector.clone()), None),
+            None => (None, None),
+        };
         Self {
             parent_hash,
             transaction,
             timestamp: timestamp.as_nanos_since_unix_epoch(),
+            fee_collector,
+            fee_collector_block_index,
         }
     }
 }
@@ -82,6 +82,11 @@ impl BalanceHistory {
         Ok(*a)
     }
 
+    /// All the entries of the history, including the pruned entry.
+    pub fn entries(&self) -> &[(BlockIndex, Tokens)] {
+        &self.inner
+    }
+
     pub fn get_last(&self) -> Tokens {
         self.inner
             .last()
@@ -180,8 +185,11 @@ impl ClientBalancesStore {
 }
 
 impl BalancesStore<AccountIdentifier> for ClientBalancesStore {
-    fn get_balance(&self, k: &AccountIdentifier) -> Option<&Tokens> {
-        self.acc_to_hist.get(k).and_then(|hist| hist.get_last_ref())
+    fn get_balance(&self, k: &AccountIdentifier) -> Option<Tokens> {
+        self.acc_to_hist
+            .get(k)
+            .and_then(|hist| hist.get_last_ref())
+            .cloned()
     }
 
     // In here, ledger removes zero amount accounts from it's map,
@@ -1,36 +1,46 @@
 use crate::balance_book::BalanceBook;
 use crate::errors::Error;
+use crate::ledger_type::{FeeCollector, LedgerType};
 use crate::store::{BlockStoreError, HashedBlock, SQLiteStore};
-use ic_ledger_core::block::{BlockIndex, BlockType, EncodedBlock, HashOf};
-use icp_ledger::{apply_operation, AccountIdentifier, Block, Tokens};
+use ic_ledger_core::block::{BlockIndex, EncodedBlock, HashOf};
+use icp_ledger::{apply_operation, AccountIdentifier, Operation, Tokens};
 use log::{error, info};
 
 pub struct Blocks {
     pub balance_book: BalanceBook,
     pub block_store: SQLiteStore,
+    /// The last block that set the fee collector and the fee collector it
+    /// set, used to resolve the fee collector of the following blocks.
+    fee_collector: Option<(BlockIndex, AccountIdentifier)>,
 }
 
 impl Blocks {
     const LOAD_FROM_STORE_BLOCK_BATCH_LEN: u64 = 10000;
 
-    pub fn new_persistent(store_location: &std::path::Path) -> Self {
-        let block_store = SQLiteStore::new_on_disk(store_location)
+    pub fn new_persistent(store_location: &std::path::Path, ledger_type: LedgerType) -> Self {
+        let block_store = SQLiteStore::new_on_disk(store_location, ledger_type)
             .expect("Failed to initialize sql store for ledger");
         Self {
             balance_book: BalanceBook::default(),
             block_store,
+            fee_collector: None,
         }
     }
 
-    pub fn new_in_memory() -> Self {
-        let block_store =
-            SQLiteStore::new_in_memory().expect("Failed to initialize sql store for ledger");
+    pub fn new_in_memory(ledger_type: LedgerType) -> Self {
+        let block_store = SQLiteStore::new_in_memory(ledger_type)
+            .expect("Failed to initialize sql store for ledger");
         Self {
             balance_book: BalanceBook::default(),
             block_store,
+            fee_collector: None,
         }
     }
 
+    pub fn ledger_type(&self) -> LedgerType {
+        self.block_store.ledger_type()
+    }
+
     pub fn load_from_store(&mut self) -> Result<u64, Error> {
         assert!(
             self.balance_book.store.acc_to_hist.is_empty(),
@@ -78,9 +88,34 @@ impl Blocks {
             };
         }
 
+        self.store_missing_account_balances()?;
+
         Ok(n)
     }
 
+    /// Stores the balance history of the balance book that is not in the
+    /// store yet, e.g. for stores created before balances were persisted.
+    fn store_missing_account_balances(&self) -> Result<(), BlockStoreError> {
+        let latest = self.block_store.get_latest_account_balance_idx()?;
+        let missing: Vec<_> = self
+            .balance_book
+            .store
+            .acc_to_hist
+            .iter()
+            .flat_map(|(acc, hist)| {
+                hist.entries()
+                    .iter()
+                    .filter(|(h, _)| latest.map_or(true, |latest| *h > latest))
+                    .map(move |(h, amount)| (*h, *acc, *amount))
+            })
+            .collect();
+        if !missing.is_empty() {
+            info!("Storing {} missing account balances", missing.len());
+            self.block_store.push_account_balances(&missing)?;
+        }
+        Ok(())
+    }
+
     pub fn is_verified_by_hash(
         &self,
         hash: &HashOf<EncodedBlock>,
@@ -96,19 +131,42 @@ impl Blocks {
     /// match the end of the chain
     pub fn push(&mut self, hb: HashedBlock) -> Result<(), BlockStoreError> {
         self.block_store.push(&hb)?;
-        self.process_block(hb)?;
-        Ok(())
+        let index = hb.index;
+        let accounts = self.apply_block(hb)?;
+        let balances = self.account_balances(index, accounts);
+        self.block_store.push_account_balances(&balances)
     }
 
     pub fn push_batch(&mut self, batch: Vec<HashedBlock>) -> Result<(), BlockStoreError> {
         self.block_store.push_batch(batch.clone())?;
+        let mut balances = vec![];
         for hb in batch {
-            self.process_block(hb)?;
+            let index = hb.index;
+            let accounts = self.apply_block(hb)?;
+            balances.extend(self.account_balances(index, accounts));
         }
-        Ok(())
+        self.block_store.push_account_balances(&balances)
+    }
+
+    /// The current balances of `accounts`, recorded at block `index`.
+    fn account_balances(
+        &self,
+        index: BlockIndex,
+        accounts: Vec<AccountIdentifier>,
+    ) -> Vec<(BlockIndex, AccountIdentifier, Tokens)> {
+        accounts
+            .into_iter()
+            .map(|acc| (index, acc, self.balance_book.account_balance(&acc)))
+            .collect()
     }
 
     pub fn process_block(&mut self, hb: HashedBlock) -> Result<(), BlockStoreError> {
+        self.apply_block(hb).map(|_| ())
+    }
+
+    /// Applies the block to the balance book and returns the accounts whose
+    /// balance it changed.
+    fn apply_block(&mut self, hb: HashedBlock) -> Result<Vec<AccountIdentifier>, BlockStoreError> {
         let HashedBlock {
             block,
             hash: _,
@@ -116,12 +174,64 @@ impl Blocks {
             index,
         } = hb;
 
-        let block = Block::decode(block).unwrap();
+        let decoded = self.ledger_type().decode_block(block).unwrap();
+        let fee_collector = match (decoded.fee, decoded.fee_collector) {
+            (Some(fee), Some(fee_collector)) => {
+                Some((self.resolve_fee_collector(index, fee_collector)?, fee))
+            }
+            _ => None,
+        };
+        let mut accounts = match decoded.block.transaction.operation {
+            Operation::Burn { from, .. } => vec![from],
+            Operation::Mint { to, .. } => vec![to],
+            Operation::Transfer { from, to, .. } => vec![from, to],
+        };
         let mut bb = &mut self.balance_book;
         bb.store.transaction_context = Some(index);
-        apply_operation(bb, &block.transaction.operation).unwrap();
+        apply_operation(bb, &decoded.block.transaction.operation).unwrap();
+        if let Some((fee_collector, fee)) = fee_collector {
+            bb.mint(&fee_collector, fee).unwrap();
+            accounts.push(fee_collector);
+        }
         bb.store.transaction_context = None;
-        Ok(())
+        accounts.sort();
+        accounts.dedup();
+        Ok(accounts)
+    }
+
+    /// Returns the account that collects the fee of the block at `index`.
+    fn resolve_fee_collector(
+        &mut self,
+        index: BlockIndex,
+        fee_collector: FeeCollector,
+    ) -> Result<AccountIdentifier, BlockStoreError> {
+        match fee_collector {
+            FeeCollector::Account(account) => {
+                self.fee_collector = Some((index, account));
+                Ok(account)
+            }
+            FeeCollector::BlockIndex(block_index) => match self.fee_collector {
+                Some((set_at, account)) if set_at == block_index => Ok(account),
+                _ => {
+                    let hb = self.block_store.get_hashed_block(&block_index)?;
+                    let decoded = self
+                        .ledger_type()
+                        .decode_block(hb.block)
+                        .map_err(BlockStoreError::Other)?;
+                    match decoded.fee_collector {
+                        Some(FeeCollector::Account(account)) => {
+                            self.fee_collector = Some((block_index, account));
+                            Ok(account)
+                        }
+                        _ => Err(BlockStoreError::Other(format!(
+                            "Block {} refers to block {} for the fee collector, \
+                            but that block does not set one",
+                            index, block_index
+                        ))),
+                    }
+                }
+            },
+        }
     }
 
     pub(crate) fn get_first_hashed_block(&self) -> Result<HashedBlock, BlockStoreError> {
@@ -160,7 +270,7 @@ impl Blocks {
                 h
             )))
         } else {
-            self.balance_book.store.get_at(*acc, *h)
+            Ok(self.block_store.get_account_balance(acc, h)?)
         }
     }
 
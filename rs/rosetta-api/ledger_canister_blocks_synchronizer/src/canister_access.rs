@@ -1,20 +1,30 @@
+use candid::{CandidType, Nat};
 use dfn_protobuf::{ProtoBuf, ToProto};
 use ic_canister_client::{Agent, HttpClient, Sender};
+use ic_icrc1::blocks::generic_block_to_encoded_block;
+use ic_icrc1::endpoints::{
+    BlockRange, GenericBlock, GetBlocksRequest, GetBlocksResponse, TipCertificate,
+};
 use ic_ledger_core::block::EncodedBlock;
 use ic_types::CanisterId;
 use icp_ledger::protobuf::{ArchiveIndexEntry, ArchiveIndexResponse, TipOfChainRequest};
 use icp_ledger::{BlockArg, BlockIndex, BlockRes, GetBlocksArgs, GetBlocksRes, TipOfChainRes};
 use log::{debug, trace, warn};
 use on_wire::{FromWire, IntoWire};
+use serde::de::DeserializeOwned;
 use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::sync::Arc;
 use tokio::task::{spawn, JoinHandle};
 use url::Url;
 
+use crate::certification::icrc1_certified_tip_index;
+use crate::ledger_type::LedgerType;
+
 pub struct CanisterAccess {
     pub agent: Agent,
     pub canister_id: CanisterId,
+    pub ledger_type: LedgerType,
     archive_list: Arc<tokio::sync::Mutex<Option<ArchiveIndexResponse>>>,
     #[allow(clippy::type_complexity)]
     ongoing_block_queries: tokio::sync::Mutex<
@@ -30,11 +40,12 @@ impl CanisterAccess {
     const BLOCKS_BATCH_LEN: u64 = 2000;
     const MAX_BLOCK_QUERIES: usize = 5;
 
-    pub fn new(url: Url, canister_id: CanisterId) -> Self {
+    pub fn new(url: Url, canister_id: CanisterId, ledger_type: LedgerType) -> Self {
         let agent = Agent::new_with_client(HttpClient::new(), url, Sender::Anonymous);
         Self {
             agent,
             canister_id,
+            ledger_type,
             archive_list: Arc::new(tokio::sync::Mutex::new(None)),
             ongoing_block_queries: Default::default(),
         }
@@ -69,16 +80,85 @@ impl CanisterAccess {
         ProtoBuf::from_bytes(bytes).map(|c| c.0)
     }
 
+    pub async fn query_candid<Payload: CandidType, Res: CandidType + DeserializeOwned>(
+        &self,
+        canister_id: CanisterId,
+        method: &str,
+        payload: Payload,
+    ) -> Result<Res, String> {
+        let arg = candid::encode_one(payload).map_err(|e| e.to_string())?;
+        let bytes = self
+            .agent
+            .execute_query(&canister_id, method, arg)
+            .await?
+            .ok_or_else(|| "Reply payload was empty".to_string())?;
+        candid::decode_one(&bytes).map_err(|e| e.to_string())
+    }
+
     pub async fn query_tip(&self) -> Result<TipOfChainRes, String> {
-        self.query("tip_of_chain_pb", TipOfChainRequest {})
-            .await
-            .map_err(|e| format!("In tip: {}", e))
+        match self.ledger_type {
+            LedgerType::Icp => self
+                .query("tip_of_chain_pb", TipOfChainRequest {})
+                .await
+                .map_err(|e| format!("In tip: {}", e)),
+            LedgerType::Icrc1 => self
+                .query_icrc1_tip()
+                .await
+                .map_err(|e| format!("In tip: {}", e)),
+        }
+    }
+
+    /// Returns the tip of an ICRC-1 ledger.
+    ///
+    /// The certification is the candid encoded [TipCertificate] and the tip
+    /// index is the one certified by it, so that the two always agree.
+    async fn query_icrc1_tip(&self) -> Result<TipOfChainRes, String> {
+        let tip_certificate: Option<TipCertificate> = self
+            .query_candid(self.canister_id, "get_tip_certificate", ())
+            .await?;
+        match tip_certificate {
+            Some(tip_certificate) => {
+                let certification =
+                    candid::encode_one(tip_certificate).map_err(|e| e.to_string())?;
+                Ok(TipOfChainRes {
+                    tip_index: icrc1_certified_tip_index(&certification)?,
+                    certification: Some(certification),
+                })
+            }
+            None => {
+                let blocks: GetBlocksResponse = self
+                    .query_candid(
+                        self.canister_id,
+                        "get_blocks",
+                        GetBlocksRequest {
+                            start: Nat::from(0u64),
+                            length: Nat::from(0u64),
+                        },
+                    )
+                    .await?;
+                let tip_index = blocks
+                    .chain_length
+                    .checked_sub(1)
+                    .ok_or_else(|| "The ledger has no blocks".to_string())?;
+                Ok(TipOfChainRes {
+                    tip_index,
+                    certification: None,
+                })
+            }
+        }
     }
 
     pub async fn query_raw_block(
         &self,
         height: BlockIndex,
     ) -> Result<Option<EncodedBlock>, String> {
+        if self.ledger_type == LedgerType::Icrc1 {
+            let blocks = self
+                .query_icrc1_blocks(height, height + 1)
+                .await
+                .map_err(|e| format!("In block: {}", e))?;
+            return Ok(blocks.into_iter().next());
+        }
         let BlockRes(b) = self
             .query("block_pb", BlockArg(height))
             .await
@@ -121,6 +201,61 @@ impl CanisterAccess {
         blocks.0.map_err(|e| format!("In blocks response: {}", e))
     }
 
+    /// Fetches the blocks in the range `[start, end)` from an ICRC-1 ledger,
+    /// following the archive callback if the first block was archived.
+    ///
+    /// Might return fewer blocks than requested.
+    async fn query_icrc1_blocks(
+        &self,
+        start: BlockIndex,
+        end: BlockIndex,
+    ) -> Result<Vec<EncodedBlock>, String> {
+        let response: GetBlocksResponse = self
+            .query_candid(
+                self.canister_id,
+                "get_blocks",
+                GetBlocksRequest {
+                    start: Nat::from(start),
+                    length: Nat::from(end - start),
+                },
+            )
+            .await
+            .map_err(|e| format!("In blocks: {}", e))?;
+
+        let blocks: Vec<GenericBlock> = match response
+            .archived_blocks
+            .into_iter()
+            .find(|range| range.start == Nat::from(start))
+        {
+            Some(archived) => {
+                let range: BlockRange = self
+                    .query_candid(
+                        archived.callback.canister_id,
+                        &archived.callback.method,
+                        GetBlocksRequest {
+                            start: archived.start,
+                            length: archived.length,
+                        },
+                    )
+                    .await
+                    .map_err(|e| format!("In archived blocks: {}", e))?;
+                range.blocks
+            }
+            None if response.first_index == Nat::from(start) => response.blocks,
+            None => {
+                return Err(format!(
+                    "In blocks response: block {} is neither in the ledger (first index: {}) nor archived",
+                    start, response.first_index
+                ))
+            }
+        };
+
+        blocks
+            .into_iter()
+            .map(generic_block_to_encoded_block)
+            .collect()
+    }
+
     pub async fn clear_outstanding_queries(&self) {
         let mut handles: VecDeque<_> = self.ongoing_block_queries.lock().await.drain(..).collect();
 
@@ -181,6 +316,10 @@ impl CanisterAccess {
         start: BlockIndex,
         end: BlockIndex,
     ) -> Result<Vec<EncodedBlock>, String> {
+        if self.ledger_type == LedgerType::Icrc1 {
+            return self.query_icrc1_blocks(start, end).await;
+        }
+
         // asking for a low number of blocks means we are close to the tip
         // so we can try fetching from ledger first
         if end - start < Self::BLOCKS_BATCH_LEN {
@@ -1,24 +1,81 @@
 use ic_certification::verify_certificate;
-use ic_ledger_core::block::{EncodedBlock, HashOf};
+use ic_crypto_tree_hash::{LookupStatus, MixedHashTree};
+use ic_icrc1::endpoints::TipCertificate;
+use ic_ledger_core::block::{BlockIndex, EncodedBlock, HashOf};
 use ic_types::{crypto::threshold_sig::ThresholdSigPublicKey, CanisterId};
 
+use crate::ledger_type::LedgerType;
+
 pub struct VerificationInfo {
     pub root_key: ThresholdSigPublicKey,
     pub canister_id: CanisterId,
 }
 
+/// Verifies that `hash` is the certified hash of the ledger tip.
+///
+/// The ICP ledger certifies the tip hash directly. For ICRC-1 ledgers `cert`
+/// is the candid encoding of the [TipCertificate], whose hash tree must
+/// contain `hash` under the `tip_hash` label.
 pub(crate) fn verify_block_hash(
+    ledger_type: LedgerType,
     cert: &icp_ledger::Certification,
     hash: HashOf<EncodedBlock>,
     info: &VerificationInfo,
 ) -> Result<(), String> {
-    verify_certificate(
-        cert.as_ref()
-            .ok_or("verify tip failed: no data certificate present")?,
-        &info.canister_id,
-        &info.root_key,
-        &hash.into_bytes(),
-    )
-    .map(|_| ()) // we don't need the result so we discard it
-    .map_err(|e| format!("Certification error: {:?}", e))
+    let cert = cert
+        .as_ref()
+        .ok_or("verify tip failed: no data certificate present")?;
+    match ledger_type {
+        LedgerType::Icp => verify_certified_data(cert, &hash.into_bytes(), info),
+        LedgerType::Icrc1 => {
+            let (tip_certificate, hash_tree) = decode_icrc1_tip_certificate(cert)?;
+            match lookup_leaf(&hash_tree, "tip_hash") {
+                Some(tip_hash) if tip_hash == hash.as_slice() => (),
+                Some(tip_hash) => {
+                    return Err(format!(
+                        "verify tip failed: certified tip hash {} does not match block hash {}",
+                        hex::encode(tip_hash),
+                        hash
+                    ))
+                }
+                None => return Err("verify tip failed: no tip hash in the hash tree".to_string()),
+            }
+            verify_certified_data(&tip_certificate.certificate, &hash_tree.digest().0, info)
+        }
+    }
+}
+
+/// Returns the index of the last block certified by the candid encoded
+/// [TipCertificate] of an ICRC-1 ledger.
+pub(crate) fn icrc1_certified_tip_index(cert: &[u8]) -> Result<BlockIndex, String> {
+    let (_, hash_tree) = decode_icrc1_tip_certificate(cert)?;
+    let mut encoded_index = lookup_leaf(&hash_tree, "last_block_index")
+        .ok_or("no last block index in the hash tree")?;
+    leb128::read::unsigned(&mut encoded_index)
+        .map_err(|e| format!("failed to decode the last block index: {}", e))
+}
+
+fn verify_certified_data(
+    cert: &[u8],
+    certified_data: &[u8],
+    info: &VerificationInfo,
+) -> Result<(), String> {
+    verify_certificate(cert, &info.canister_id, &info.root_key, certified_data)
+        .map(|_| ()) // we don't need the result so we discard it
+        .map_err(|e| format!("Certification error: {:?}", e))
+}
+
+fn decode_icrc1_tip_certificate(cert: &[u8]) -> Result<(TipCertificate, MixedHashTree), String> {
+    let tip_certificate: TipCertificate = candid::decode_one(cert)
+        .map_err(|e| format!("failed to decode the tip certificate: {}", e))?;
+    let hash_tree: MixedHashTree = ciborium::de::from_reader(tip_certificate.hash_tree.as_slice())
+        .map_err(|e| format!("failed to decode the tip hash tree: {}", e))?;
+    Ok((tip_certificate, hash_tree))
+}
+
+fn lookup_leaf<'a>(tree: &'a MixedHashTree, label: &str) -> Option<&'a [u8]> {
+    match tree.lookup(&[label]) {
+        LookupStatus::Found(MixedHashTree::Leaf(bytes)) => Some(bytes.as_slice()),
+        _ => None,
+    }
 }
@@ -4,8 +4,8 @@ use std::sync::Arc;
 
 use core::ops::Deref;
 
-use ic_ledger_core::block::{BlockIndex, BlockType, EncodedBlock, HashOf};
-use icp_ledger::{Block, TipOfChainRes};
+use ic_ledger_core::block::{BlockIndex, EncodedBlock, HashOf};
+use icp_ledger::TipOfChainRes;
 use log::{debug, error, info, trace, warn};
 use tokio::sync::RwLock;
 
@@ -13,7 +13,8 @@ use crate::blocks::Blocks;
 use crate::blocks_access::BlocksAccess;
 use crate::certification::{verify_block_hash, VerificationInfo};
 use crate::errors::Error;
-use crate::store::{BlockStoreError, HashedBlock};
+use crate::ledger_type::LedgerType;
+use crate::store::BlockStoreError;
 
 // If pruning is enabled, instead of pruning after each new block
 // we'll wait for PRUNE_DELAY blocks to accumulate and prune them in one go
@@ -48,6 +49,7 @@ where
 {
     pub blockchain: RwLock<Blocks>,
     blocks_access: Option<Arc<B>>,
+    ledger_type: LedgerType,
     // TODO: move store_max_blocks in sync or move up_to_block here
     store_max_blocks: Option<u64>,
     verification_info: Option<VerificationInfo>,
@@ -57,14 +59,15 @@ where
 impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
     pub async fn new(
         blocks_access: Option<Arc<B>>,
+        ledger_type: LedgerType,
         store_location: Option<&std::path::Path>,
         store_max_blocks: Option<u64>,
         verification_info: Option<VerificationInfo>,
         metrics: Box<dyn LedgerBlocksSynchronizerMetrics + Send + Sync>,
     ) -> Result<LedgerBlocksSynchronizer<B>, Error> {
         let mut blocks = match store_location {
-            Some(loc) => Blocks::new_persistent(loc),
-            None => Blocks::new_in_memory(),
+            Some(loc) => Blocks::new_persistent(loc, ledger_type),
+            None => Blocks::new_in_memory(ledger_type),
         };
 
         if let Some(blocks_access) = &blocks_access {
@@ -72,7 +75,7 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
             if let Some(verification_info) = &verification_info {
                 // verify if we have the right certificate/we are connecting to the right
                 // canister
-                Self::verify_tip_of_chain(blocks_access, verification_info).await?;
+                Self::verify_tip_of_chain(blocks_access, ledger_type, verification_info).await?;
             }
         }
 
@@ -103,6 +106,7 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
         Ok(Self {
             blockchain: RwLock::new(blocks),
             blocks_access,
+            ledger_type,
             store_max_blocks,
             verification_info,
             metrics,
@@ -111,6 +115,7 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
 
     async fn verify_store(blocks: &Blocks, canister_access: &B) -> Result<(), Error> {
         debug!("Verifying store...");
+        let ledger_type = blocks.ledger_type();
         let first_block = blocks.get_first_hashed_block().ok();
         match blocks.block_store.get_hashed_block(&0) {
             Ok(store_genesis) => {
@@ -120,12 +125,12 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
                     .map_err(Error::InternalError)?
                     .expect("Blockchain in the ledger canister is empty");
 
-                if store_genesis.hash != Block::block_hash(&genesis) {
+                if store_genesis.hash != ledger_type.block_hash(&genesis) {
                     let msg = format!(
                         "Genesis block from the store is different than \
                         in the ledger canister. Store hash: {}, canister hash: {}",
                         store_genesis.hash,
-                        Block::block_hash(&genesis)
+                        ledger_type.block_hash(&genesis)
                     );
                     error!("{}", msg);
                     return Err(Error::InternalError(msg));
@@ -161,13 +166,13 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
                 return Err(Error::InternalError(msg));
             }
             let queried_block = queried_block.unwrap();
-            if first_block.hash != Block::block_hash(&queried_block) {
+            if first_block.hash != ledger_type.block_hash(&queried_block) {
                 let msg = format!(
                     "Oldest block snapshot does not match the block on \
                     the blockchain. Index: {}, snapshot hash: {}, canister hash: {}",
                     first_block.index,
                     first_block.hash,
-                    Block::block_hash(&queried_block)
+                    ledger_type.block_hash(&queried_block)
                 );
                 error!("{}", msg);
                 return Err(Error::InternalError(msg));
@@ -180,6 +185,7 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
 
     async fn verify_tip_of_chain(
         canister_access: &B,
+        ledger_type: LedgerType,
         verification_info: &VerificationInfo,
     ) -> Result<(), Error> {
         let TipOfChainRes {
@@ -195,8 +201,9 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
             .map_err(Error::InternalError)?
             .expect("Blockchain in the ledger canister is empty");
         verify_block_hash(
+            ledger_type,
             &certification,
-            Block::block_hash(&tip_block),
+            ledger_type.block_hash(&tip_block),
             verification_info,
         )
         .map_err(Error::InternalError)?;
@@ -347,8 +354,11 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
             let mut hashed_batch = Vec::new();
             hashed_batch.reserve_exact(batch.len());
             for raw_block in batch {
-                let block = Block::decode(raw_block.clone())
-                    .map_err(|err| Error::InternalError(format!("Cannot decode block: {}", err)))?;
+                let block = self
+                    .ledger_type
+                    .decode_block(raw_block.clone())
+                    .map_err(|err| Error::InternalError(format!("Cannot decode block: {}", err)))?
+                    .block;
                 if block.parent_hash != last_block_hash {
                     let err_msg = format!(
                         "Block at {}: parent hash mismatch. Expected: {:?}, got: {:?}",
@@ -357,10 +367,15 @@ impl<B: BlocksAccess> LedgerBlocksSynchronizer<B> {
                     error!("{}", err_msg);
                     return Err(Error::InternalError(err_msg));
                 }
-                let hb = HashedBlock::hash_block(raw_block, last_block_hash, i);
+                let hb = self.ledger_type.hash_block(raw_block, last_block_hash, i);
                 if i == range.end - 1 {
                     if let Some(verification_info) = &self.verification_info {
-                        verify_block_hash(&certification, hb.hash, verification_info)
+                        verify_block_hash(
+                            self.ledger_type,
+                            &certification,
+                            hb.hash,
+                            verification_info,
+                        )
                             .map_err(Error::InternalError)?;
                     }
                 }
@@ -401,6 +416,7 @@ mod test {
 
     use crate::blocks_access::BlocksAccess;
     use crate::ledger_blocks_sync::LedgerBlocksSynchronizer;
+    use crate::ledger_type::LedgerType;
 
     use super::NopMetrics;
 
@@ -447,6 +463,7 @@ mod test {
     ) -> LedgerBlocksSynchronizer<RangeOfBlocks> {
         LedgerBlocksSynchronizer::new(
             Some(Arc::new(RangeOfBlocks::new(blocks))),
+            LedgerType::Icp,
             /* store_location = */ None,
             /* store_max_blocks = */ None,
             /* verification_info = */ None,
@@ -0,0 +1,252 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ic_ledger_canister_core::ledger::LedgerTransaction;
+use ic_ledger_core::block::{BlockIndex, BlockType, EncodedBlock, HashOf};
+use ic_ledger_core::timestamp::TimeStamp;
+use ic_ledger_core::Tokens;
+use icp_ledger::{AccountIdentifier, Block, Memo, Operation, Transaction};
+
+use crate::store::HashedBlock;
+
+/// The type of the ledger canister the blocks are synchronized from.
+///
+/// The store and the balance book work on the ICP representation of blocks,
+/// ICRC-1 blocks are converted to it when they are processed (see
+/// [LedgerType::decode_block]). The encoded blocks and their hashes are
+/// always the ones produced by the ledger.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LedgerType {
+    /// The ICP ledger, blocks are encoded with protobuf.
+    Icp,
+    /// An ICRC-1 ledger, blocks are encoded with CBOR.
+    Icrc1,
+}
+
+impl Default for LedgerType {
+    fn default() -> Self {
+        Self::Icp
+    }
+}
+
+impl fmt::Display for LedgerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Icp => write!(f, "icp"),
+            Self::Icrc1 => write!(f, "icrc1"),
+        }
+    }
+}
+
+impl FromStr for LedgerType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "icp" => Ok(Self::Icp),
+            "icrc1" => Ok(Self::Icrc1),
+            _ => Err(format!(
+                "Unknown ledger type {}, expected icp or icrc1",
+                s
+            )),
+        }
+    }
+}
+
+/// The account that receives the fees of a block.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FeeCollector {
+    /// The fee collector is set by this block.
+    Account(AccountIdentifier),
+    /// The fee collector was set by the block with the given index.
+    BlockIndex(BlockIndex),
+}
+
+/// A block converted to the ICP representation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedBlock {
+    pub block: Block,
+    /// The hash of the transaction as computed by the ledger.
+    pub transaction_hash: HashOf<Transaction>,
+    /// The fee paid by the transaction, if any.
+    pub fee: Option<Tokens>,
+    /// The account credited with the fee, if the ledger has a fee collector.
+    pub fee_collector: Option<FeeCollector>,
+}
+
+impl LedgerType {
+    pub fn block_hash(self, block: &EncodedBlock) -> HashOf<EncodedBlock> {
+        match self {
+            Self::Icp => Block::block_hash(block),
+            Self::Icrc1 => ic_icrc1::Block::block_hash(block),
+        }
+    }
+
+    pub fn hash_block(
+        self,
+        block: EncodedBlock,
+        parent_hash: Option<HashOf<EncodedBlock>>,
+        index: BlockIndex,
+    ) -> HashedBlock {
+        HashedBlock {
+            hash: self.block_hash(&block),
+            block,
+            parent_hash,
+            index,
+        }
+    }
+
+    /// Decodes a block of this ledger type into the ICP representation.
+    ///
+    /// ICRC-1 accounts are mapped to the [AccountIdentifier] of the same
+    /// principal and subaccount. Approvals are represented as burns of the
+    /// approval fee, as they don't move any other tokens.
+    pub fn decode_block(self, block: EncodedBlock) -> Result<DecodedBlock, String> {
+        match self {
+            Self::Icp => {
+                let block = Block::decode(block)?;
+                let transaction_hash = block.transaction.hash();
+                let fee = match block.transaction.operation {
+                    Operation::Transfer { fee, .. } => Some(fee),
+                    Operation::Burn { .. } | Operation::Mint { .. } => None,
+                };
+                Ok(DecodedBlock {
+                    block,
+                    transaction_hash,
+                    fee,
+                    fee_collector: None,
+                })
+            }
+            Self::Icrc1 => {
+                let block = ic_icrc1::Block::decode(block)?;
+                let transaction_hash = HashOf::new(block.transaction.hash().into_bytes());
+                let (operation, fee) = match block.transaction.operation {
+                    ic_icrc1::Operation::Mint { to, amount } => (
+                        Operation::Mint {
+                            to: to.into(),
+                            amount: Tokens::from_e8s(amount),
+                        },
+                        None,
+                    ),
+                    ic_icrc1::Operation::Burn { from, amount } => (
+                        Operation::Burn {
+                            from: from.into(),
+                            amount: Tokens::from_e8s(amount),
+                        },
+                        None,
+                    ),
+                    ic_icrc1::Operation::Transfer {
+                        from,
+                        to,
+                        amount,
+                        fee,
+                        ..
+                    } => (
+                        Operation::Transfer {
+                            from: from.into(),
+                            to: to.into(),
+                            amount: Tokens::from_e8s(amount),
+                            fee: Tokens::from_e8s(fee),
+                        },
+                        Some(Tokens::from_e8s(fee)),
+                    ),
+                    ic_icrc1::Operation::Approve { from, fee, .. } => (
+                        Operation::Burn {
+                            from: from.into(),
+                            amount: Tokens::from_e8s(fee),
+                        },
+                        Some(Tokens::from_e8s(fee)),
+                    ),
+                };
+                let fee_collector = match (block.fee_collector, block.fee_collector_block_index)
+                {
+                    (Some(fee_collector), _) => Some(FeeCollector::Account(fee_collector.into())),
+                    (None, Some(block_index)) => Some(FeeCollector::BlockIndex(block_index)),
+                    (None, None) => None,
+                };
+                Ok(DecodedBlock {
+                    block: Block {
+                        parent_hash: block.parent_hash,
+                        transaction: Transaction {
+                            operation,
+                            memo: Memo(0),
+                            created_at_time: block
+                                .transaction
+                                .created_at_time
+                                .map(TimeStamp::from_nanos_since_unix_epoch),
+                        },
+                        timestamp: TimeStamp::from_nanos_since_unix_epoch(block.timestamp),
+                    },
+                    transaction_hash,
+                    fee,
+                    fee_collector,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_icrc1::Account;
+    use ic_types::PrincipalId;
+
+    fn account(n: u64, subaccount: Option<[u8; 32]>) -> Account {
+        Account {
+            owner: PrincipalId::new_user_test_id(n),
+            subaccount,
+        }
+    }
+
+    #[test]
+    fn decode_icrc1_transfer() {
+        let from = account(1, None);
+        let to = account(2, Some([1; 32]));
+        let fee_collector = account(3, None);
+        let transaction = ic_icrc1::Transaction::transfer(
+            from.clone(),
+            to.clone(),
+            Tokens::from_e8s(1_000),
+            Tokens::from_e8s(10),
+            Some(TimeStamp::from_nanos_since_unix_epoch(5)),
+            Some(ic_icrc1::Memo::from(42)),
+        );
+        let block = ic_icrc1::Block {
+            parent_hash: Some(HashOf::new([7; 32])),
+            transaction: transaction.clone(),
+            timestamp: 6,
+            fee_collector: Some(fee_collector.clone()),
+            fee_collector_block_index: None,
+        }
+        .encode();
+
+        let hb = LedgerType::Icrc1.hash_block(block.clone(), Some(HashOf::new([7; 32])), 1);
+        assert_eq!(hb.hash, ic_icrc1::Block::block_hash(&block));
+
+        let decoded = LedgerType::Icrc1.decode_block(block).unwrap();
+        assert_eq!(
+            decoded.transaction_hash.into_bytes(),
+            transaction.hash().into_bytes()
+        );
+        assert_eq!(decoded.block.parent_hash, Some(HashOf::new([7; 32])));
+        assert_eq!(
+            decoded.block.timestamp,
+            TimeStamp::from_nanos_since_unix_epoch(6)
+        );
+        assert_eq!(
+            decoded.block.transaction.operation,
+            Operation::Transfer {
+                from: from.into(),
+                to: to.into(),
+                amount: Tokens::from_e8s(1_000),
+                fee: Tokens::from_e8s(10),
+            }
+        );
+        assert_eq!(decoded.fee, Some(Tokens::from_e8s(10)));
+        assert_eq!(
+            decoded.fee_collector,
+            Some(FeeCollector::Account(fee_collector.into()))
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::balance_book::BalanceBook;
+use crate::ledger_type::LedgerType;
 use ic_ledger_core::{
     block::{BlockIndex, BlockType, EncodedBlock, HashOf},
     Tokens,
@@ -14,10 +15,10 @@ use std::sync::Mutex;
 
 mod database_access {
     use super::vec_into_array;
+    use crate::ledger_type::LedgerType;
     use crate::store::{BlockStoreError, HashedBlock};
-    use ic_ledger_canister_core::ledger::LedgerTransaction;
-    use ic_ledger_core::block::{BlockType, EncodedBlock, HashOf};
-    use icp_ledger::{AccountIdentifier, Block, Operation};
+    use ic_ledger_core::block::{EncodedBlock, HashOf};
+    use icp_ledger::{AccountIdentifier, Operation};
     use rusqlite::{params, types::Null, Connection};
 
     pub fn push_hashed_block(
@@ -37,9 +38,10 @@ mod database_access {
     pub fn push_transaction(
         connection: &mut Connection,
         tx: &icp_ledger::Transaction,
+        tx_hash: &HashOf<icp_ledger::Transaction>,
         index: &u64,
     ) -> Result<(), BlockStoreError> {
-        let tx_hash = tx.hash().into_bytes().to_vec();
+        let tx_hash = tx_hash.into_bytes().to_vec();
         let operation_type = tx.operation.clone();
         let command = "INSERT INTO transactions (block_idx,tx_hash,operation_type,from_account,to_account,amount,fee) VALUES (?1, ?2, ?3, ?4, ?5,?6,?7)";
         match operation_type {
@@ -131,6 +133,7 @@ mod database_access {
     }
     pub fn get_transaction(
         connection: &mut Connection,
+        ledger_type: LedgerType,
         block_idx: &u64,
     ) -> Result<icp_ledger::Transaction, BlockStoreError> {
         let command = "SELECT block from blocks where idx = ?";
@@ -143,8 +146,10 @@ mod database_access {
                 Ok(row
                     .get(0)
                     .map(|b| {
-                        Block::decode(EncodedBlock::from_vec(b))
+                        ledger_type
+                            .decode_block(EncodedBlock::from_vec(b))
                             .unwrap()
+                            .block
                             .transaction
                     })
                     .unwrap())
@@ -391,6 +396,14 @@ mod database_access {
             None => Ok(false),
         }
     }
+    pub fn get_latest_account_balance_idx(
+        connection: &mut Connection,
+    ) -> Result<Option<u64>, BlockStoreError> {
+        let command = "SELECT MAX(block_idx) FROM account_balances";
+        connection
+            .query_row(command, [], |row| row.get(0))
+            .map_err(|e| BlockStoreError::Other(e.to_string()))
+    }
     pub fn get_account_balance(
         connection: &mut Connection,
         block_idx: &u64,
@@ -423,6 +436,8 @@ pub struct HashedBlock {
 }
 
 impl HashedBlock {
+    /// Hashes a block of the ICP ledger, see [LedgerType::hash_block] for
+    /// other ledger types.
     pub fn hash_block(
         block: EncodedBlock,
         parent_hash: Option<HashOf<EncodedBlock>>,
@@ -437,6 +452,23 @@ impl HashedBlock {
     }
 }
 
+/// The filters of a transaction search, see [SQLiteStore::search_transactions].
+/// A block matches if it matches all the filters that are set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransactionQuery {
+    pub transaction_hash: Option<HashOf<icp_ledger::Transaction>>,
+    /// Matches the blocks that changed the balance of the account.
+    pub account: Option<AccountIdentifier>,
+    /// Matches the blocks whose operation has the given type, e.g. `Transfer`.
+    pub operation_type: Option<String>,
+    /// Matches the blocks at or below the given index.
+    pub max_block: Option<BlockIndex>,
+    /// The number of matching blocks to skip, starting from the most recent.
+    pub offset: usize,
+    /// The maximum number of blocks to return.
+    pub limit: usize,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum BlockStoreError {
     NotFound(BlockIndex),
@@ -454,29 +486,34 @@ fn vec_into_array(v: Vec<u8>) -> [u8; 32] {
 
 pub struct SQLiteStore {
     connection: Mutex<rusqlite::Connection>,
+    ledger_type: LedgerType,
 }
 
 impl SQLiteStore {
     /// Constructs a new SQLite on-disk store.
-    pub fn new_on_disk(location: &Path) -> Result<Self, BlockStoreError> {
+    pub fn new_on_disk(location: &Path, ledger_type: LedgerType) -> Result<Self, BlockStoreError> {
         std::fs::create_dir_all(location)
             .expect("Unable to create directory for SQLite on-disk store.");
         let path = location.join("db.sqlite");
         let connection =
             rusqlite::Connection::open(&path).expect("Unable to open SQLite database connection");
-        Self::new(connection)
+        Self::new(connection, ledger_type)
     }
 
     /// Constructs a new SQLite in-memory store.
-    pub fn new_in_memory() -> Result<Self, BlockStoreError> {
+    pub fn new_in_memory(ledger_type: LedgerType) -> Result<Self, BlockStoreError> {
         let connection = rusqlite::Connection::open_in_memory()
             .expect("Unable to open SQLite in-memory database connection");
-        Self::new(connection)
+        Self::new(connection, ledger_type)
     }
 
-    fn new(connection: rusqlite::Connection) -> Result<Self, BlockStoreError> {
+    fn new(
+        connection: rusqlite::Connection,
+        ledger_type: LedgerType,
+    ) -> Result<Self, BlockStoreError> {
         let store = Self {
             connection: Mutex::new(connection),
+            ledger_type,
         };
         store
             .connection
@@ -491,6 +528,11 @@ impl SQLiteStore {
         Ok(store)
     }
 
+    /// The type of the ledger whose blocks are stored.
+    pub fn ledger_type(&self) -> LedgerType {
+        self.ledger_type
+    }
+
     fn create_tables(&self) -> Result<(), rusqlite::Error> {
         let connection = self.connection.lock().unwrap();
         connection.execute(
@@ -529,6 +571,20 @@ impl SQLiteStore {
             "#,
             [],
         )?;
+        // Table of account balances after each block that changed them. The
+        // last balance of each account before the oldest block is kept when
+        // pruning, so it has no foreign key on the blocks.
+        connection.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS account_balances (
+                block_idx INTEGER NOT NULL,
+                account VARCHAR(64) NOT NULL,
+                icpt INTEGER NOT NULL,
+                PRIMARY KEY(account,block_idx)
+            )
+            "#,
+            [],
+        )?;
         connection.execute(
             r#"
             CREATE TABLE IF NOT EXISTS transactions (
@@ -737,7 +793,7 @@ impl SQLiteStore {
         block_idx: &u64,
     ) -> Result<icp_ledger::Transaction, BlockStoreError> {
         let mut connection = self.connection.lock().unwrap();
-        database_access::get_transaction(&mut *connection, block_idx)
+        database_access::get_transaction(&mut *connection, self.ledger_type, block_idx)
     }
     fn check_table_coherence(&self) -> Result<(), BlockStoreError> {
         let mut connection = self.connection.lock().unwrap();
@@ -784,9 +840,11 @@ impl SQLiteStore {
             for missing_index in difference_transaction_indices {
                 let missing_block =
                     database_access::get_hashed_block(&mut *connection, &missing_index)?;
+                let decoded = self.ledger_type.decode_block(missing_block.block).unwrap();
                 database_access::push_transaction(
                     &mut *connection,
-                    &Block::decode(missing_block.block).unwrap().transaction,
+                    &decoded.block.transaction,
+                    &decoded.transaction_hash,
                     &missing_index,
                 )?;
             }
@@ -918,9 +976,11 @@ impl SQLiteStore {
     pub fn push(&self, hb: &HashedBlock) -> Result<(), BlockStoreError> {
         let mut con = self.connection.lock().unwrap();
         database_access::push_hashed_block(&mut *con, hb)?;
+        let decoded = self.ledger_type.decode_block(hb.block.clone()).unwrap();
         database_access::push_transaction(
             &mut *con,
-            &Block::decode(hb.block.clone()).unwrap().transaction,
+            &decoded.block.transaction,
+            &decoded.transaction_hash,
             &hb.index,
         )?;
         //TODO: UPDATE ACCOUNT BALANCES
@@ -942,9 +1002,11 @@ impl SQLiteStore {
                     return Err(e);
                 }
             };
+            let decoded = self.ledger_type.decode_block(hb.block.clone()).unwrap();
             match database_access::push_transaction(
                 &mut *connection,
-                &Block::decode(hb.block.clone()).unwrap().transaction,
+                &decoded.block.transaction,
+                &decoded.transaction_hash,
                 &hb.index,
             ) {
                 Ok(_) => (),
@@ -962,6 +1024,100 @@ impl SQLiteStore {
         Ok(())
     }
 
+    /// Stores the balances of accounts after the blocks that changed them.
+    pub fn push_account_balances(
+        &self,
+        balances: &[(BlockIndex, AccountIdentifier, Tokens)],
+    ) -> Result<(), BlockStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection
+            .transaction()
+            .map_err(|e| BlockStoreError::Other(e.to_string()))?;
+        {
+            let mut stmt = tx
+                .prepare("INSERT OR REPLACE INTO account_balances (block_idx, account, icpt) VALUES (?1, ?2, ?3)")
+                .map_err(|e| BlockStoreError::Other(e.to_string()))?;
+            for (block_idx, account, amount) in balances {
+                stmt.execute(params![block_idx, account.to_hex(), amount.get_e8s()])
+                    .map_err(|e| BlockStoreError::Other(e.to_string()))?;
+            }
+        }
+        tx.commit()
+            .map_err(|e| BlockStoreError::Other(e.to_string()))
+    }
+
+    /// The index of the most recent block with stored account balances.
+    pub fn get_latest_account_balance_idx(&self) -> Result<Option<BlockIndex>, BlockStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        database_access::get_latest_account_balance_idx(&mut *connection)
+    }
+
+    /// Returns the indices of the verified blocks matching `query`, most
+    /// recent first, and the total number of matching blocks.
+    ///
+    /// Only the blocks after the oldest block are searched once the store
+    /// has been pruned, as the balances at the oldest block are a snapshot.
+    pub fn search_transactions(
+        &self,
+        query: &TransactionQuery,
+    ) -> Result<(Vec<BlockIndex>, usize), BlockStoreError> {
+        let min_block = match self.get_first_hashed_block() {
+            Ok(hb) if hb.index > 0 => hb.index + 1,
+            _ => 0,
+        };
+        let max_block = query.max_block.unwrap_or(i64::MAX as u64);
+        let transaction_hash = query
+            .transaction_hash
+            .map(|hash| hash.into_bytes().to_vec());
+        let account = query.account.map(|account| account.to_hex());
+        let filter = r#"
+            FROM transactions t JOIN blocks b ON b.idx = t.block_idx
+            WHERE b.verified = TRUE AND t.block_idx >= ?1 AND t.block_idx <= ?2
+            AND (?3 IS NULL OR t.tx_hash = ?3)
+            AND (?4 IS NULL OR t.operation_type = ?4)
+            AND (?5 IS NULL OR t.block_idx IN
+                (SELECT block_idx FROM account_balances WHERE account = ?5))
+        "#;
+
+        let connection = self.connection.lock().unwrap();
+        let total_count: usize = connection
+            .query_row(
+                &format!("SELECT COUNT(*) {}", filter),
+                params![
+                    min_block,
+                    max_block,
+                    transaction_hash,
+                    query.operation_type,
+                    account
+                ],
+                |row| row.get(0),
+            )
+            .map_err(|e| BlockStoreError::Other(e.to_string()))?;
+        let mut stmt = connection
+            .prepare(&format!(
+                "SELECT t.block_idx {} ORDER BY t.block_idx DESC LIMIT ?6 OFFSET ?7",
+                filter
+            ))
+            .map_err(|e| BlockStoreError::Other(e.to_string()))?;
+        let indices = stmt
+            .query_map(
+                params![
+                    min_block,
+                    max_block,
+                    transaction_hash,
+                    query.operation_type,
+                    account,
+                    query.limit,
+                    query.offset
+                ],
+                |row| row.get(0),
+            )
+            .map_err(|e| BlockStoreError::Other(e.to_string()))?
+            .collect::<Result<Vec<BlockIndex>, _>>()
+            .map_err(|e| BlockStoreError::Other(e.to_string()))?;
+        Ok((indices, total_count))
+    }
+
     pub fn prune(&mut self, hb: &HashedBlock, balance_book: &BalanceBook) -> Result<(), String> {
         self.write_oldest_block_snapshot(hb, balance_book)?;
         let mut connection = self.connection.lock().unwrap();
@@ -984,6 +1140,19 @@ impl SQLiteStore {
         )
         .map_err(|e| e.to_string())?;
 
+        // Keep the last balance of each account before the new oldest block.
+        tx.execute(
+            r#"
+            DELETE FROM account_balances WHERE block_idx > 0 AND block_idx < ?1
+            AND (account, block_idx) NOT IN (
+                SELECT account, MAX(block_idx) FROM account_balances
+                WHERE block_idx < ?1 GROUP BY account
+            )
+            "#,
+            params![hb.index],
+        )
+        .map_err(|e| e.to_string())?;
+
         tx.execute(
             "DELETE FROM blocks WHERE idx > 0 AND idx < ?",
             params![hb.index],
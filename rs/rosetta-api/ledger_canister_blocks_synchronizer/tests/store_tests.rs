@@ -1,5 +1,6 @@
 use ic_ledger_canister_blocks_synchronizer::{
     balance_book::BalanceBook,
+    ledger_type::LedgerType,
     store::{BlockStoreError, SQLiteStore},
 };
 use ic_ledger_canister_blocks_synchronizer_test_utils::{
@@ -11,7 +12,7 @@ use icp_ledger::{AccountIdentifier, Block, BlockIndex};
 use rusqlite::params;
 use std::{collections::BTreeMap, path::Path};
 pub(crate) fn sqlite_on_disk_store(path: &Path) -> SQLiteStore {
-    SQLiteStore::new_on_disk(path).expect("Unable to create store")
+    SQLiteStore::new_on_disk(path, LedgerType::Icp).expect("Unable to create store")
 }
 
 #[actix_rt::test]
@@ -0,0 +1,131 @@
+use crate::stable_memory::{self, StableAccountId};
+use ic_base_types::CanisterId;
+use std::collections::BTreeMap;
+
+/// The number of bits in the filter of an archive node (256 KiB).
+const NUM_BITS: u64 = 1 << 21;
+
+/// The number of bit positions each account sets in the filter.
+const NUM_HASHES: u64 = 4;
+
+/// A Bloom filter over the accounts that appear in the blocks of an archive
+/// node.
+///
+/// The filter can report false positives but never false negatives: if
+/// [AccountFilter::may_contain] returns false, the archive node has no blocks
+/// touching the account and clients can skip it.
+///
+/// The bits of the filter live in stable memory, which only stores the
+/// 64-bit words that have some bit set.
+pub struct AccountFilter {
+    node: CanisterId,
+}
+
+impl AccountFilter {
+    /// Returns the filter of the specified archive node.
+    pub fn of_node(node: CanisterId) -> Self {
+        Self { node }
+    }
+
+    /// Records the accounts in the filter.
+    pub fn insert_all<'a, A: StableAccountId + 'a>(
+        &self,
+        accounts: impl IntoIterator<Item = &'a A>,
+    ) {
+        let mut masks = BTreeMap::<u32, u64>::new();
+        for account in accounts {
+            for bit in bit_positions(&account.to_stable_bytes()) {
+                *masks.entry((bit / 64) as u32).or_default() |= 1 << (bit % 64);
+            }
+        }
+        for (word, mask) in masks {
+            let bits = stable_memory::account_filter_word(&self.node, word);
+            if bits | mask != bits {
+                stable_memory::set_account_filter_word(&self.node, word, bits | mask);
+            }
+        }
+    }
+
+    /// Returns false if the account was definitely never inserted.
+    pub fn may_contain<A: StableAccountId>(&self, account: &A) -> bool {
+        bit_positions(&account.to_stable_bytes()).all(|bit| {
+            stable_memory::account_filter_word(&self.node, (bit / 64) as u32) & (1 << (bit % 64))
+                != 0
+        })
+    }
+
+    /// Removes all the bits of the filter from stable memory.
+    pub fn clear(&self) {
+        stable_memory::remove_account_filter(&self.node);
+    }
+}
+
+/// Derives the bit positions of an account from two independent hashes of
+/// its stable encoding (Kirsch-Mitzenmacher double hashing).
+fn bit_positions(account_bytes: &[u8]) -> impl Iterator<Item = u64> {
+    let h1 = fnv1a(FNV_OFFSET_BASIS, account_bytes);
+    let h2 = fnv1a(FNV_OFFSET_BASIS ^ ALT_SEED, account_bytes) | 1;
+    (0..NUM_HASHES).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % NUM_BITS)
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+const ALT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Hashes the bytes with the 64-bit FNV-1a function.
+///
+/// The filter is persisted across upgrades, so the bit positions must only
+/// depend on the stable encoding of the account: neither the standard library
+/// hasher nor the `Hash` implementations are guaranteed to be stable between
+/// Rust releases.
+fn fnv1a(offset_basis: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(offset_basis, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestAccount(u64);
+
+    impl StableAccountId for TestAccount {
+        fn to_stable_bytes(&self) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+
+        fn from_stable_bytes(bytes: &[u8]) -> Self {
+            Self(u64::from_be_bytes(bytes.try_into().unwrap()))
+        }
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn filter_contains_inserted_accounts() {
+        let filter = AccountFilter::of_node(CanisterId::from_u64(1));
+        let other_filter = AccountFilter::of_node(CanisterId::from_u64(2));
+        let accounts: Vec<_> = (0..1_000).map(TestAccount).collect();
+        filter.insert_all(accounts.iter());
+
+        assert!(accounts.iter().all(|account| filter.may_contain(account)));
+        // With 1000 accounts in 2^21 bits, false positives are rare.
+        let num_false_positives = (1_000..11_000)
+            .filter(|n| filter.may_contain(&TestAccount(*n)))
+            .count();
+        assert!(num_false_positives < 10, "{}", num_false_positives);
+        // The filters of different nodes are independent.
+        assert!(!accounts
+            .iter()
+            .any(|account| other_filter.may_contain(account)));
+
+        filter.clear();
+        assert!(!accounts.iter().any(|account| filter.may_contain(account)));
+    }
+}
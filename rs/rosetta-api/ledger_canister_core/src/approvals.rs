@@ -0,0 +1,282 @@
+use ic_ledger_core::timestamp::TimeStamp;
+use ic_ledger_core::tokens::Tokens;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Allowance {
+    pub amount: Tokens,
+    pub expires_at: Option<TimeStamp>,
+}
+
+impl Default for Allowance {
+    fn default() -> Self {
+        Self {
+            amount: Tokens::ZERO,
+            expires_at: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ApproveError {
+    /// The expiration time of the approval is in the past.
+    ExpiredApproval { now: TimeStamp },
+    /// The current allowance does not match the allowance the caller expected.
+    AllowanceChanged { current_allowance: Tokens },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InsufficientAllowance(pub Tokens);
+
+/// The storage of the allowance table.
+///
+/// The keys of allowances are pairs `(account, spender)`.
+pub trait AllowancesData {
+    type AccountId;
+
+    fn get_allowance(
+        &self,
+        account_spender: &(Self::AccountId, Self::AccountId),
+    ) -> Option<Allowance>;
+
+    fn set_allowance(
+        &mut self,
+        account_spender: (Self::AccountId, Self::AccountId),
+        allowance: Allowance,
+    );
+
+    fn remove_allowance(&mut self, account_spender: &(Self::AccountId, Self::AccountId));
+
+    fn insert_expiry(
+        &mut self,
+        expires_at: TimeStamp,
+        account_spender: (Self::AccountId, Self::AccountId),
+    );
+
+    fn remove_expiry(
+        &mut self,
+        expires_at: TimeStamp,
+        account_spender: &(Self::AccountId, Self::AccountId),
+    );
+
+    /// Returns the entry of the expiration queue with the earliest expiration
+    /// time.
+    fn first_expiry(&self) -> Option<(TimeStamp, (Self::AccountId, Self::AccountId))>;
+
+    /// Returns the number of allowances, including the expired allowances
+    /// that have not been pruned yet.
+    fn len_allowances(&self) -> usize;
+}
+
+/// Allowances stored in the heap.
+///
+/// Allowances with an expiration date are also indexed by the expiration
+/// time, so that the ledger can prune them once they expire.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound(
+    serialize = "AccountId: Serialize",
+    deserialize = "AccountId: Deserialize<'de> + Ord"
+))]
+pub struct HeapAllowancesData<AccountId: Ord> {
+    allowances: BTreeMap<(AccountId, AccountId), Allowance>,
+    expiration_queue: BTreeSet<(TimeStamp, (AccountId, AccountId))>,
+}
+
+impl<AccountId: Ord> Default for HeapAllowancesData<AccountId> {
+    fn default() -> Self {
+        Self {
+            allowances: BTreeMap::new(),
+            expiration_queue: BTreeSet::new(),
+        }
+    }
+}
+
+impl<AccountId: Ord + Clone> HeapAllowancesData<AccountId> {
+    /// Removes an arbitrary allowance together with its expiration entry and
+    /// returns it.
+    pub fn pop_allowance(&mut self) -> Option<((AccountId, AccountId), Allowance)> {
+        let key = self.allowances.keys().next()?.clone();
+        let allowance = self.allowances.remove(&key)?;
+        if let Some(expires_at) = allowance.expires_at {
+            self.expiration_queue.remove(&(expires_at, key.clone()));
+        }
+        Some((key, allowance))
+    }
+}
+
+impl<AccountId: Ord + Clone> AllowancesData for HeapAllowancesData<AccountId> {
+    type AccountId = AccountId;
+
+    fn get_allowance(&self, account_spender: &(AccountId, AccountId)) -> Option<Allowance> {
+        self.allowances.get(account_spender).copied()
+    }
+
+    fn set_allowance(&mut self, account_spender: (AccountId, AccountId), allowance: Allowance) {
+        self.allowances.insert(account_spender, allowance);
+    }
+
+    fn remove_allowance(&mut self, account_spender: &(AccountId, AccountId)) {
+        self.allowances.remove(account_spender);
+    }
+
+    fn insert_expiry(&mut self, expires_at: TimeStamp, account_spender: (AccountId, AccountId)) {
+        self.expiration_queue.insert((expires_at, account_spender));
+    }
+
+    fn remove_expiry(&mut self, expires_at: TimeStamp, account_spender: &(AccountId, AccountId)) {
+        self.expiration_queue
+            .remove(&(expires_at, account_spender.clone()));
+    }
+
+    fn first_expiry(&self) -> Option<(TimeStamp, (AccountId, AccountId))> {
+        self.expiration_queue.iter().next().cloned()
+    }
+
+    fn len_allowances(&self) -> usize {
+        self.allowances.len()
+    }
+}
+
+/// The allowances the account owners granted to spenders.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub struct AllowanceTable<AD> {
+    allowances_data: AD,
+}
+
+impl<AD> AllowanceTable<AD>
+where
+    AD: AllowancesData,
+    AD::AccountId: Clone,
+{
+    /// Returns the allowance that `account` granted to `spender`. Expired
+    /// allowances are reported as zero.
+    pub fn allowance(
+        &self,
+        account: &AD::AccountId,
+        spender: &AD::AccountId,
+        now: TimeStamp,
+    ) -> Allowance {
+        let key = (account.clone(), spender.clone());
+        match self.allowances_data.get_allowance(&key) {
+            Some(allowance) if allowance.expires_at.map_or(true, |t| now < t) => allowance,
+            _ => Allowance::default(),
+        }
+    }
+
+    /// Sets the allowance that `account` granted to `spender` to `amount`.
+    ///
+    /// If `expected_allowance` is set, the approval only succeeds if the
+    /// current allowance is equal to the expected one. Returns the new
+    /// allowance amount.
+    pub fn approve(
+        &mut self,
+        account: &AD::AccountId,
+        spender: &AD::AccountId,
+        amount: Tokens,
+        expires_at: Option<TimeStamp>,
+        now: TimeStamp,
+        expected_allowance: Option<Tokens>,
+    ) -> Result<Tokens, ApproveError> {
+        if let Some(expires_at) = expires_at {
+            if expires_at <= now {
+                return Err(ApproveError::ExpiredApproval { now });
+            }
+        }
+
+        let current_allowance = self.allowance(account, spender, now).amount;
+        if let Some(expected_allowance) = expected_allowance {
+            if expected_allowance != current_allowance {
+                return Err(ApproveError::AllowanceChanged { current_allowance });
+            }
+        }
+
+        let key = (account.clone(), spender.clone());
+        self.remove(&key);
+        if amount != Tokens::ZERO {
+            if let Some(expires_at) = expires_at {
+                self.allowances_data.insert_expiry(expires_at, key.clone());
+            }
+            self.allowances_data
+                .set_allowance(key, Allowance { amount, expires_at });
+        }
+        Ok(amount)
+    }
+
+    /// Deducts `amount` from the allowance that `account` granted to
+    /// `spender`. Returns the remaining allowance.
+    pub fn use_allowance(
+        &mut self,
+        account: &AD::AccountId,
+        spender: &AD::AccountId,
+        amount: Tokens,
+        now: TimeStamp,
+    ) -> Result<Tokens, InsufficientAllowance> {
+        let allowance = self.allowance(account, spender, now);
+        let remaining =
+            (allowance.amount - amount).map_err(|_| InsufficientAllowance(allowance.amount))?;
+
+        let key = (account.clone(), spender.clone());
+        if remaining == Tokens::ZERO {
+            self.remove(&key);
+        } else {
+            self.allowances_data.set_allowance(
+                key,
+                Allowance {
+                    amount: remaining,
+                    expires_at: allowance.expires_at,
+                },
+            );
+        }
+        Ok(remaining)
+    }
+
+    /// Removes at most `limit` allowances that expired before `now` and
+    /// returns the number of removed allowances.
+    pub fn prune(&mut self, now: TimeStamp, limit: usize) -> usize {
+        let mut pruned = 0;
+        while pruned < limit {
+            let (expires_at, key) = match self.allowances_data.first_expiry() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if now < expires_at {
+                break;
+            }
+            self.allowances_data.remove_expiry(expires_at, &key);
+            self.allowances_data.remove_allowance(&key);
+            pruned += 1;
+        }
+        pruned
+    }
+
+    /// Returns the number of allowances in the table, including the expired
+    /// allowances that have not been pruned yet.
+    pub fn len(&self) -> usize {
+        self.allowances_data.len_allowances()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn allowances_data(&self) -> &AD {
+        &self.allowances_data
+    }
+
+    pub fn allowances_data_mut(&mut self) -> &mut AD {
+        &mut self.allowances_data
+    }
+
+    fn remove(&mut self, key: &(AD::AccountId, AD::AccountId)) {
+        if let Some(Allowance {
+            expires_at: Some(expires_at),
+            ..
+        }) = self.allowances_data.get_allowance(key)
+        {
+            self.allowances_data.remove_expiry(expires_at, key);
+        }
+        self.allowances_data.remove_allowance(key);
+    }
+}
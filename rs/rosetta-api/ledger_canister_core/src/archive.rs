@@ -1,19 +1,26 @@
-use crate::{runtime::Runtime, spawn};
+use crate::{
+    account_filter::AccountFilter, runtime::Runtime, spawn, stable_memory::StableAccountId,
+};
 use candid::{CandidType, Encode};
 use ic_base_types::{CanisterId, PrincipalId};
+use ic_crypto_sha::Sha256;
 use ic_ic00_types::IC_00;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::marker::PhantomData;
 use std::sync::{Arc, RwLock};
 
-use ic_ledger_core::block::EncodedBlock;
+use ic_ledger_core::block::{BlockType, EncodedBlock};
 
 fn default_cycles_for_archive_creation() -> u64 {
     0
 }
 
+fn nodes_upgrade_pending_after_upgrade() -> bool {
+    true
+}
+
 #[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
 pub struct ArchiveOptions {
     /// The number of blocks which, when exceeded, will trigger an archiving
@@ -124,6 +131,23 @@ pub struct Archive<Rt: Runtime, Wasm: ArchiveCanisterWasm> {
     #[serde(default)]
     pub max_transactions_per_response: Option<usize>,
 
+    /// The nodes that have an [AccountFilter]. Nodes created before the
+    /// ledger started tracking accounts have no filter and can contain any
+    /// account.
+    #[serde(default)]
+    filtered_nodes: BTreeSet<CanisterId>,
+
+    /// The SHA-256 hash of the Wasm module that each archive node is known to
+    /// run.
+    #[serde(default)]
+    node_wasm_hashes: BTreeMap<CanisterId, [u8; 32]>,
+
+    /// Whether the ledger still has to upgrade the archive nodes to the
+    /// archive Wasm of the current ledger version. Set on every ledger
+    /// upgrade, as that is when the archive Wasm can change.
+    #[serde(skip, default = "nodes_upgrade_pending_after_upgrade")]
+    nodes_upgrade_pending: bool,
+
     /// Whether there are outstanding calls to the archive at the moment.
     // We do not need to persist this flag because we cannot have any oustanding calls
     // on upgrade.
@@ -149,6 +173,9 @@ impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Archive<Rt, Wasm> {
             num_blocks_to_archive: options.num_blocks_to_archive,
             cycles_for_archive_creation: options.cycles_for_archive_creation.unwrap_or(0),
             max_transactions_per_response: options.max_transactions_per_response,
+            filtered_nodes: BTreeSet::new(),
+            node_wasm_hashes: BTreeMap::new(),
+            nodes_upgrade_pending: false,
             archiving_in_progress: false,
             _marker: PhantomData,
         }
@@ -169,6 +196,59 @@ impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Archive<Rt, Wasm> {
     pub fn nodes(&self) -> &[CanisterId] {
         &self.nodes
     }
+
+    /// Returns the part of the [Archive::index] that can contain blocks
+    /// touching the specified account. Nodes that are left out definitely do not
+    /// contain such blocks.
+    pub fn index_for_account<AccountId: StableAccountId>(
+        &self,
+        account: &AccountId,
+    ) -> Vec<((u64, u64), CanisterId)> {
+        self.index()
+            .into_iter()
+            .filter(|(_, canister_id)| {
+                !self.filtered_nodes.contains(canister_id)
+                    || AccountFilter::of_node(*canister_id).may_contain(account)
+            })
+            .collect()
+    }
+
+    /// Returns true if the archive nodes have not been upgraded since the
+    /// last ledger upgrade, see [upgrade_archive_nodes].
+    pub fn nodes_upgrade_pending(&self) -> bool {
+        self.nodes_upgrade_pending
+    }
+
+    /// Returns the hash of the Wasm module that all archive nodes run, if all
+    /// nodes are known to run the same module.
+    pub fn archive_wasm_hash(&self) -> Option<[u8; 32]> {
+        let wasm_hash = *self.node_wasm_hashes.get(self.nodes.first()?)?;
+        self.nodes
+            .iter()
+            .all(|node| self.node_wasm_hashes.get(node) == Some(&wasm_hash))
+            .then(|| wasm_hash)
+    }
+
+    /// Adds the accounts of the blocks appended to the node to the node's
+    /// filter. If the accounts are unknown, the node can contain any account
+    /// from now on.
+    fn record_accounts<AccountId: StableAccountId>(
+        &mut self,
+        node_canister_id: CanisterId,
+        accounts: Option<Vec<AccountId>>,
+    ) {
+        if !self.filtered_nodes.contains(&node_canister_id) {
+            return;
+        }
+        let filter = AccountFilter::of_node(node_canister_id);
+        match accounts {
+            Some(accounts) => filter.insert_all(accounts.iter()),
+            None => {
+                filter.clear();
+                self.filtered_nodes.remove(&node_canister_id);
+            }
+        }
+    }
 }
 
 /// Grabs a write lock on the archive and executes a synchronous function under the lock.
@@ -192,11 +272,17 @@ fn inspect_archive<R, Rt: Runtime, Wasm: ArchiveCanisterWasm>(
 /// Sends the blocks to an archive canister (creating new archive canister if necessary).
 /// On success, returns the number of blocks archived (equal to blocks.len()).
 /// On failure, returns the number of successfully archived blocks and a description of the error.
-pub async fn send_blocks_to_archive<Rt: Runtime, Wasm: ArchiveCanisterWasm>(
+pub async fn send_blocks_to_archive<Rt, Wasm, B>(
     archive: Arc<RwLock<Option<Archive<Rt, Wasm>>>>,
     mut blocks: VecDeque<EncodedBlock>,
     max_ledger_msg_size_bytes: usize,
-) -> Result<usize, (usize, FailedToArchiveBlocks)> {
+) -> Result<usize, (usize, FailedToArchiveBlocks)>
+where
+    Rt: Runtime,
+    Wasm: ArchiveCanisterWasm,
+    B: BlockType,
+    B::AccountId: StableAccountId,
+{
     Rt::print("[archive] send_blocks_to_archive(): start");
 
     let max_chunk_size = inspect_archive(&archive, |archive| {
@@ -239,6 +325,7 @@ pub async fn send_blocks_to_archive<Rt: Runtime, Wasm: ArchiveCanisterWasm>(
             if chunk.is_empty() {
                 return Err((num_sent_blocks, FailedToArchiveBlocks("empty chunk".into())));
             }
+            let accounts = chunk_accounts::<B>(&chunk);
             Rt::print(format!(
                 "[archive] calling append_blocks() with a chunk of size {}",
                 chunk_len
@@ -273,6 +360,7 @@ pub async fn send_blocks_to_archive<Rt: Runtime, Wasm: ArchiveCanisterWasm>(
                         heights.1 += chunk_len as u64;
                     }
                 }
+                archive.record_accounts(node_canister_id, accounts);
                 archive.nodes_block_ranges.get(node_index).cloned().unwrap()
             });
 
@@ -320,9 +408,11 @@ async fn create_and_initialize_node_canister<Rt: Runtime, Wasm: ArchiveCanisterW
 
     Rt::print("[archive] calling install_code()");
 
+    let wasm = Wasm::archive_wasm().into_owned();
+    let wasm_hash = Sha256::hash(&wasm);
     let () = spawn::install_code::<Rt>(
         node_canister_id,
-        Wasm::archive_wasm().into_owned(),
+        wasm,
         Encode!(
             &Rt::id(),
             &node_block_height_offset,
@@ -342,24 +432,33 @@ async fn create_and_initialize_node_canister<Rt: Runtime, Wasm: ArchiveCanisterW
     })?;
 
     Rt::print(format!(
-        "[archive] setting controller_id for archive node: {}",
-        controller_id
+        "[archive] setting controllers for archive node: {}, {}",
+        controller_id,
+        Rt::id()
     ));
 
+    // NB. the ledger stays a controller of the node so that it can upgrade
+    // the node to a new archive Wasm.
     let res: Result<(), (i32, String)> = Rt::call(
         IC_00,
-        "set_controller",
+        "update_settings",
         0,
-        (ic_ic00_types::SetControllerArgs::new(
-            node_canister_id,
-            controller_id,
-        ),),
+        (ic_ic00_types::UpdateSettingsArgs {
+            canister_id: node_canister_id.get(),
+            settings: ic_ic00_types::CanisterSettingsArgs::new(
+                None,
+                Some(vec![controller_id, Rt::id().get()]),
+                None,
+                None,
+                None,
+            ),
+        },),
     )
     .await;
 
     res.map_err(|(code, msg)| {
         let s = format!(
-            "Setting controller of archive node failed with code {}: {}",
+            "Setting controllers of archive node failed with code {}: {}",
             code, msg
         );
         FailedToArchiveBlocks(s)
@@ -367,6 +466,8 @@ async fn create_and_initialize_node_canister<Rt: Runtime, Wasm: ArchiveCanisterW
 
     let node_index = inspect_archive(archive, |archive| {
         archive.nodes.push(node_canister_id);
+        archive.filtered_nodes.insert(node_canister_id);
+        archive.node_wasm_hashes.insert(node_canister_id, wasm_hash);
         archive.last_node_index()
     });
 
@@ -428,6 +529,81 @@ async fn node_and_capacity<Rt: Runtime, Wasm: ArchiveCanisterWasm>(
     }
 }
 
+/// Upgrades the archive nodes that might run a different Wasm module than
+/// the one that `Wasm` provides.
+///
+/// The nodes are upgraded only once after each ledger upgrade. Nodes whose
+/// upgrade fails are tried again after the next ledger upgrade.
+///
+/// NB. archive nodes created before the ledger stayed a controller of its
+/// nodes can only be upgraded after their controller adds the ledger as a
+/// controller.
+pub async fn upgrade_archive_nodes<Rt: Runtime, Wasm: ArchiveCanisterWasm>(
+    archive: &Arc<RwLock<Option<Archive<Rt, Wasm>>>>,
+) -> Result<(), Vec<FailedToUpgradeArchive>> {
+    let (nodes, node_wasm_hashes) = match inspect_archive(archive, |archive| {
+        if !archive.nodes_upgrade_pending {
+            return None;
+        }
+        archive.nodes_upgrade_pending = false;
+        Some((archive.nodes.clone(), archive.node_wasm_hashes.clone()))
+    }) {
+        Some(state) => state,
+        None => return Ok(()),
+    };
+
+    let wasm = Wasm::archive_wasm();
+    let wasm_hash = Sha256::hash(&wasm);
+    let mut failures = vec![];
+    for node_canister_id in nodes {
+        if node_wasm_hashes.get(&node_canister_id) == Some(&wasm_hash) {
+            continue;
+        }
+        Rt::print(format!(
+            "[archive] upgrading archive node {}",
+            node_canister_id
+        ));
+        match spawn::upgrade_code::<Rt>(
+            node_canister_id,
+            wasm.to_vec(),
+            Encode!().expect("failed to encode empty arguments"),
+        )
+        .await
+        {
+            Ok(()) => inspect_archive(archive, |archive| {
+                archive.node_wasm_hashes.insert(node_canister_id, wasm_hash);
+            }),
+            Err((reject_code, message)) => failures.push(FailedToUpgradeArchive {
+                canister_id: node_canister_id,
+                reason: format!(
+                    "install_code failed; reject_code={}, message={}",
+                    reject_code, message
+                ),
+            }),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+/// Decodes the chunk and returns the accounts its blocks refer to, or `None`
+/// if some block cannot be decoded.
+fn chunk_accounts<B>(chunk: &[EncodedBlock]) -> Option<Vec<B::AccountId>>
+where
+    B: BlockType,
+{
+    let mut accounts = vec![];
+    for encoded_block in chunk {
+        let block = B::decode(encoded_block.clone()).ok()?;
+        accounts.extend(block.accounts());
+    }
+    Some(accounts)
+}
+
 /// Extract longest prefix from `blocks` which fits in `max_size`
 fn take_prefix(blocks: &mut VecDeque<EncodedBlock>, mut max_size: usize) -> Vec<EncodedBlock> {
     let mut result = vec![];
@@ -444,3 +620,225 @@ fn take_prefix(blocks: &mut VecDeque<EncodedBlock>, mut max_size: usize) -> Vec<
 /// This error type should only be returned in the case where an await has been
 /// passed but we do not think that the archive canister has received the blocks
 pub struct FailedToArchiveBlocks(pub String);
+
+/// The ledger failed to upgrade an archive node.
+#[derive(Debug)]
+pub struct FailedToUpgradeArchive {
+    pub canister_id: CanisterId,
+    pub reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use candid::utils::{decode_args, encode_args, ArgumentDecoder, ArgumentEncoder};
+    use candid::Decode;
+    use ic_ic00_types::InstallCodeArgs;
+    use std::cell::RefCell;
+    use std::future::Future;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    thread_local! {
+        /// The canisters that received an `install_code` call.
+        static INSTALLED: RefCell<Vec<CanisterId>> = RefCell::new(vec![]);
+        /// The canisters on which `install_code` fails.
+        static FAILING: RefCell<BTreeSet<CanisterId>> = RefCell::new(BTreeSet::new());
+    }
+
+    struct TestRuntime;
+
+    #[async_trait]
+    impl Runtime for TestRuntime {
+        fn id() -> CanisterId {
+            CanisterId::from_u64(100)
+        }
+
+        fn print(_msg: impl AsRef<str>) {}
+
+        async fn call<In, Out>(
+            id: CanisterId,
+            method: &str,
+            _cycles: u64,
+            args: In,
+        ) -> Result<Out, (i32, String)>
+        where
+            In: ArgumentEncoder + Send,
+            Out: for<'a> ArgumentDecoder<'a>,
+        {
+            assert_eq!((id, method), (IC_00, "install_code"));
+            let args = Decode!(&encode_args(args).unwrap(), InstallCodeArgs).unwrap();
+            let canister_id = CanisterId::new(args.canister_id).unwrap();
+            INSTALLED.with(|installed| installed.borrow_mut().push(canister_id));
+            if FAILING.with(|failing| failing.borrow().contains(&canister_id)) {
+                return Err((5, "the ledger is not a controller".to_string()));
+            }
+            Ok(decode_args(&Encode!().unwrap()).unwrap())
+        }
+    }
+
+    struct TestWasm;
+
+    impl ArchiveCanisterWasm for TestWasm {
+        fn archive_wasm() -> Cow<'static, [u8]> {
+            Cow::Borrowed(b"archive wasm")
+        }
+    }
+
+    struct TestAccount(u64);
+
+    impl StableAccountId for TestAccount {
+        fn to_stable_bytes(&self) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+
+        fn from_stable_bytes(bytes: &[u8]) -> Self {
+            Self(u64::from_be_bytes(bytes.try_into().unwrap()))
+        }
+    }
+
+    type TestArchive = Arc<RwLock<Option<Archive<TestRuntime, TestWasm>>>>;
+
+    /// Returns an archive that is being archived to, with one node of 10
+    /// blocks for each specified canister ID.
+    fn archive_with_nodes(nodes: &[u64]) -> TestArchive {
+        let mut archive = Archive::new(ArchiveOptions {
+            trigger_threshold: 10,
+            num_blocks_to_archive: 10,
+            node_max_memory_size_bytes: None,
+            max_message_size_bytes: None,
+            controller_id: PrincipalId::new_user_test_id(1),
+            cycles_for_archive_creation: None,
+            max_transactions_per_response: None,
+        });
+        for (i, node) in nodes.iter().enumerate() {
+            let first_block = 10 * i as u64;
+            archive.nodes.push(CanisterId::from_u64(*node));
+            archive
+                .nodes_block_ranges
+                .push((first_block, first_block + 9));
+        }
+        archive.archiving_in_progress = true;
+        Arc::new(RwLock::new(Some(archive)))
+    }
+
+    /// Polls the future to completion. The test runtime completes all calls
+    /// synchronously, so the future never waits for a wake-up.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        fn noop_raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                noop_raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    fn take_installed() -> Vec<CanisterId> {
+        INSTALLED.with(|installed| installed.take())
+    }
+
+    /// Emulates a ledger upgrade by round-tripping the archive through its
+    /// serialized form.
+    fn upgrade_ledger(archive: &TestArchive) -> TestArchive {
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(archive.read().unwrap().as_ref().unwrap(), &mut bytes).unwrap();
+        let mut upgraded: Archive<TestRuntime, TestWasm> =
+            ciborium::de::from_reader(&bytes[..]).unwrap();
+        upgraded.archiving_in_progress = true;
+        Arc::new(RwLock::new(Some(upgraded)))
+    }
+
+    fn upgrade(archive: &TestArchive) -> Result<(), Vec<CanisterId>> {
+        block_on(upgrade_archive_nodes(archive)).map_err(|failures| {
+            failures
+                .into_iter()
+                .map(|failure| failure.canister_id)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn upgrade_archive_nodes_once_per_ledger_upgrade() {
+        let archive = archive_with_nodes(&[1, 2, 3]);
+        let node = CanisterId::from_u64;
+        let archive_wasm_hash = |archive: &TestArchive| {
+            archive
+                .read()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .archive_wasm_hash()
+        };
+
+        // Nodes of a new archive need no upgrade.
+        assert_eq!(upgrade(&archive), Ok(()));
+        assert_eq!(take_installed(), vec![]);
+
+        // A failing node does not stop the upgrade of the other nodes.
+        let archive = upgrade_ledger(&archive);
+        FAILING.with(|failing| failing.borrow_mut().insert(node(2)));
+        assert_eq!(upgrade(&archive), Err(vec![node(2)]));
+        assert_eq!(take_installed(), vec![node(1), node(2), node(3)]);
+        assert_eq!(archive_wasm_hash(&archive), None);
+
+        // The nodes are upgraded only once per ledger upgrade.
+        FAILING.with(|failing| failing.borrow_mut().clear());
+        assert_eq!(upgrade(&archive), Ok(()));
+        assert_eq!(take_installed(), vec![]);
+
+        // The next ledger upgrade retries the failed node.
+        let archive = upgrade_ledger(&archive);
+        assert_eq!(upgrade(&archive), Ok(()));
+        assert_eq!(take_installed(), vec![node(2)]);
+        assert_eq!(
+            archive_wasm_hash(&archive),
+            Some(Sha256::hash(&TestWasm::archive_wasm()))
+        );
+
+        // All nodes run the current Wasm.
+        let archive = upgrade_ledger(&archive);
+        assert_eq!(upgrade(&archive), Ok(()));
+        assert_eq!(take_installed(), vec![]);
+    }
+
+    #[test]
+    fn index_for_account_skips_nodes_without_account() {
+        let archive = archive_with_nodes(&[11, 12, 13, 14]);
+        let mut archive_guard = archive.write().unwrap();
+        let archive = archive_guard.as_mut().unwrap();
+        let node = CanisterId::from_u64;
+        // Node 11 predates the account filters.
+        for id in [12, 13, 14] {
+            archive.filtered_nodes.insert(node(id));
+        }
+        archive.record_accounts(node(12), Some(vec![TestAccount(1), TestAccount(2)]));
+        archive.record_accounts(node(13), Some(vec![TestAccount(2)]));
+        // The accounts in the blocks of node 14 are unknown.
+        archive.record_accounts(node(14), Some(vec![TestAccount(3)]));
+        archive.record_accounts::<TestAccount>(node(14), None);
+
+        let nodes_for_account = |account: u64| -> Vec<CanisterId> {
+            archive
+                .index_for_account(&TestAccount(account))
+                .into_iter()
+                .map(|(_, canister_id)| canister_id)
+                .collect()
+        };
+        assert_eq!(nodes_for_account(1), vec![node(11), node(12), node(14)]);
+        assert_eq!(
+            nodes_for_account(2),
+            vec![node(11), node(12), node(13), node(14)]
+        );
+        assert_eq!(nodes_for_account(4), vec![node(11), node(14)]);
+    }
+}
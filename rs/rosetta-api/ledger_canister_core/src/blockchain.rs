@@ -5,16 +5,92 @@ use crate::{
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::convert::TryFrom;
+use std::ops::Range;
 use std::sync::{Arc, RwLock};
 
 use ic_ledger_core::block::{BlockIndex, BlockType, EncodedBlock, HashOf};
 use ic_ledger_core::timestamp::TimeStamp;
 
+/// The storage of the blocks that have not been archived yet.
+///
+/// Blocks are addressed by their position relative to the oldest unarchived
+/// block.
+pub trait BlockData {
+    /// Appends a block to the storage.
+    fn add_block(&mut self, block: EncodedBlock);
+
+    /// Returns the block at the specified position.
+    fn get_block(&self, position: u64) -> Option<EncodedBlock>;
+
+    /// Returns the blocks at the specified positions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some positions are out of bounds.
+    fn get_blocks(&self, positions: Range<u64>) -> Vec<EncodedBlock>;
+
+    /// Removes the `num_blocks` oldest blocks from the storage.
+    fn remove_oldest_blocks(&mut self, num_blocks: u64);
+
+    /// Returns the number of blocks in the storage.
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the most recent block.
+    fn last(&self) -> Option<EncodedBlock> {
+        self.len()
+            .checked_sub(1)
+            .and_then(|position| self.get_block(position))
+    }
+}
+
+/// Blocks stored in the heap.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub struct HeapBlockData {
+    blocks: Vec<EncodedBlock>,
+}
+
+impl HeapBlockData {
+    /// Removes all the blocks from the storage and returns them, the oldest
+    /// block first.
+    pub fn take_blocks(&mut self) -> Vec<EncodedBlock> {
+        std::mem::take(&mut self.blocks)
+    }
+}
+
+impl BlockData for HeapBlockData {
+    fn add_block(&mut self, block: EncodedBlock) {
+        self.blocks.push(block);
+    }
+
+    fn get_block(&self, position: u64) -> Option<EncodedBlock> {
+        self.blocks.get(usize::try_from(position).ok()?).cloned()
+    }
+
+    fn get_blocks(&self, positions: Range<u64>) -> Vec<EncodedBlock> {
+        let start = usize::try_from(positions.start).unwrap();
+        let end = usize::try_from(positions.end).unwrap();
+        self.blocks[start..end].to_vec()
+    }
+
+    fn remove_oldest_blocks(&mut self, num_blocks: u64) {
+        self.blocks = self.blocks.split_off(usize::try_from(num_blocks).unwrap());
+    }
+
+    fn len(&self) -> u64 {
+        self.blocks.len() as u64
+    }
+}
+
 /// Stores a chain of transactions with their metadata
 #[derive(Serialize, Deserialize, Debug)]
-#[serde(bound = "")]
-pub struct Blockchain<Rt: Runtime, Wasm: ArchiveCanisterWasm> {
-    pub blocks: Vec<EncodedBlock>,
+#[serde(bound(serialize = "BD: Serialize", deserialize = "BD: Deserialize<'de>"))]
+pub struct Blockchain<Rt: Runtime, Wasm: ArchiveCanisterWasm, BD: BlockData = HeapBlockData> {
+    pub blocks: BD,
     pub last_hash: Option<HashOf<EncodedBlock>>,
 
     /// The timestamp of the most recent block. Must be monotonically
@@ -31,10 +107,15 @@ pub struct Blockchain<Rt: Runtime, Wasm: ArchiveCanisterWasm> {
     pub num_archived_blocks: u64,
 }
 
-impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Default for Blockchain<Rt, Wasm> {
+impl<Rt, Wasm, BD> Default for Blockchain<Rt, Wasm, BD>
+where
+    Rt: Runtime,
+    Wasm: ArchiveCanisterWasm,
+    BD: BlockData + Default,
+{
     fn default() -> Self {
         Self {
-            blocks: vec![],
+            blocks: BD::default(),
             last_hash: None,
             last_timestamp: TimeStamp::from_nanos_since_unix_epoch(0),
             archive: Arc::new(RwLock::new(None)),
@@ -43,7 +124,12 @@ impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Default for Blockchain<Rt, Wasm> {
     }
 }
 
-impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Blockchain<Rt, Wasm> {
+impl<Rt, Wasm, BD> Blockchain<Rt, Wasm, BD>
+where
+    Rt: Runtime,
+    Wasm: ArchiveCanisterWasm,
+    BD: BlockData + Default,
+{
     pub fn new_with_archive(archive_options: ArchiveOptions) -> Self {
         Self {
             archive: Arc::new(RwLock::new(Some(Archive::new(archive_options)))),
@@ -67,20 +153,19 @@ impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Blockchain<Rt, Wasm> {
         self.last_timestamp = block.timestamp();
         let encoded_block = block.encode();
         self.last_hash = Some(B::block_hash(&encoded_block));
-        self.blocks.push(encoded_block);
+        self.blocks.add_block(encoded_block);
         Ok(self.chain_length().checked_sub(1).unwrap())
     }
 
-    pub fn get(&self, height: BlockIndex) -> Option<&EncodedBlock> {
+    pub fn get(&self, height: BlockIndex) -> Option<EncodedBlock> {
         if height < self.num_archived_blocks() {
             None
         } else {
-            self.blocks
-                .get(usize::try_from(height - self.num_archived_blocks()).unwrap())
+            self.blocks.get_block(height - self.num_archived_blocks())
         }
     }
 
-    pub fn last(&self) -> Option<&EncodedBlock> {
+    pub fn last(&self) -> Option<EncodedBlock> {
         self.blocks.last()
     }
 
@@ -89,20 +174,20 @@ impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Blockchain<Rt, Wasm> {
     }
 
     pub fn num_unarchived_blocks(&self) -> u64 {
-        self.blocks.len() as u64
+        self.blocks.len()
     }
 
     /// The range of block indices that are not archived yet.
     pub fn local_block_range(&self) -> std::ops::Range<u64> {
-        self.num_archived_blocks..self.num_archived_blocks + self.blocks.len() as u64
+        self.num_archived_blocks..self.num_archived_blocks + self.blocks.len()
     }
 
-    /// Returns the slice of blocks stored locally.
+    /// Returns the blocks stored locally.
     ///
     /// # Panic
     ///
     /// This function panics if the specified range is not a subset of locally available blocks.
-    pub fn block_slice(&self, local_blocks: std::ops::Range<u64>) -> &[EncodedBlock] {
+    pub fn block_slice(&self, local_blocks: std::ops::Range<u64>) -> Vec<EncodedBlock> {
         use crate::range_utils::{is_subrange, offset};
 
         assert!(
@@ -112,7 +197,9 @@ impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Blockchain<Rt, Wasm> {
             self.local_block_range()
         );
 
-        &self.blocks[offset(&local_blocks, self.num_archived_blocks)]
+        let positions = offset(&local_blocks, self.num_archived_blocks);
+        self.blocks
+            .get_blocks(positions.start as u64..positions.end as u64)
     }
 
     pub fn chain_length(&self) -> BlockIndex {
@@ -122,14 +209,14 @@ impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Blockchain<Rt, Wasm> {
     pub fn remove_archived_blocks(&mut self, len: usize) {
         // redundant since split_off would panic, but here we can give a more
         // descriptive message
-        if len > self.blocks.len() {
+        if len as u64 > self.blocks.len() {
             panic!(
                 "Asked to remove more blocks than present. Present: {}, to remove: {}",
                 self.blocks.len(),
                 len
             );
         }
-        self.blocks = self.blocks.split_off(len);
+        self.blocks.remove_oldest_blocks(len as u64);
         self.num_archived_blocks += len as u64;
     }
 
@@ -149,8 +236,10 @@ impl<Rt: Runtime, Wasm: ArchiveCanisterWasm> Blockchain<Rt, Wasm> {
             return VecDeque::new();
         }
 
-        let blocks_to_archive: VecDeque<EncodedBlock> =
-            VecDeque::from(self.blocks[0..num_blocks_to_archive.min(num_blocks_before)].to_vec());
+        let blocks_to_archive: VecDeque<EncodedBlock> = VecDeque::from(
+            self.blocks
+                .get_blocks(0..num_blocks_to_archive.min(num_blocks_before) as u64),
+        );
 
         println!(
             "get_blocks_for_archiving(): trigger_threshold: {}, num_blocks: {}, blocks before archiving: {}, blocks to archive: {}",
@@ -1,12 +1,19 @@
-use crate::{archive::ArchiveCanisterWasm, blockchain::Blockchain, range_utils, runtime::Runtime};
+use crate::{
+    approvals::{AllowanceTable, AllowancesData, ApproveError, InsufficientAllowance},
+    archive::ArchiveCanisterWasm,
+    blockchain::{BlockData, Blockchain},
+    range_utils,
+    runtime::Runtime,
+    stable_memory::StableAccountId,
+};
 use ic_base_types::CanisterId;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::{BTreeMap, VecDeque};
 use std::ops::Range;
 use std::time::Duration;
 
-use ic_ledger_core::balances::{BalanceError, Balances, BalancesStore};
-use ic_ledger_core::block::{BlockIndex, BlockType, EncodedBlock, HashOf};
+use ic_ledger_core::balances::{BalanceError, Balances, InspectableBalancesStore};
+use ic_ledger_core::block::{BlockIndex, BlockType, EncodedBlock, FeeCollector, HashOf};
 use ic_ledger_core::timestamp::TimeStamp;
 use ic_ledger_core::tokens::Tokens;
 
@@ -37,10 +44,44 @@ pub trait LedgerTransaction: Sized {
     /// Returns the hash of this transaction.
     fn hash(&self) -> HashOf<Self>;
 
-    /// Applies this transaction to the balance book.
-    fn apply<S>(&self, balances: &mut Balances<Self::AccountId, S>) -> Result<(), BalanceError>
+    /// Applies this transaction to the balance book and the allowance table of the ledger.
+    fn apply<L>(&self, ledger: &mut L, now: TimeStamp) -> Result<(), TxApplyError>
     where
-        S: Default + BalancesStore<Self::AccountId>;
+        L: LedgerData<AccountId = Self::AccountId>;
+}
+
+/// An error that prevents a transaction from being applied to the ledger state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TxApplyError {
+    InsufficientFunds { balance: Tokens },
+    InsufficientAllowance { allowance: Tokens },
+    ExpiredApproval { now: TimeStamp },
+    AllowanceChanged { current_allowance: Tokens },
+}
+
+impl From<BalanceError> for TxApplyError {
+    fn from(err: BalanceError) -> Self {
+        match err {
+            BalanceError::InsufficientFunds { balance } => Self::InsufficientFunds { balance },
+        }
+    }
+}
+
+impl From<InsufficientAllowance> for TxApplyError {
+    fn from(InsufficientAllowance(allowance): InsufficientAllowance) -> Self {
+        Self::InsufficientAllowance { allowance }
+    }
+}
+
+impl From<ApproveError> for TxApplyError {
+    fn from(err: ApproveError) -> Self {
+        match err {
+            ApproveError::ExpiredApproval { now } => Self::ExpiredApproval { now },
+            ApproveError::AllowanceChanged { current_allowance } => {
+                Self::AllowanceChanged { current_allowance }
+            }
+        }
+    }
 }
 
 pub trait LedgerAccess {
@@ -62,11 +103,14 @@ pub trait LedgerAccess {
 }
 
 pub trait LedgerData {
-    type AccountId: std::hash::Hash + Ord + Eq + Clone;
+    type AccountId: StableAccountId + std::hash::Hash + Ord + Eq + Clone;
     type ArchiveWasm: ArchiveCanisterWasm;
     type Runtime: Runtime;
-    type Block: BlockType<Transaction = Self::Transaction>;
+    type Block: BlockType<Transaction = Self::Transaction, AccountId = Self::AccountId>;
     type Transaction: LedgerTransaction<AccountId = Self::AccountId> + Ord + Clone;
+    type BalancesStore: InspectableBalancesStore<Self::AccountId> + Default;
+    type AllowancesData: AllowancesData<AccountId = Self::AccountId>;
+    type BlockData: BlockData + Default;
 
     // Purge configuration
 
@@ -95,13 +139,23 @@ pub trait LedgerData {
     /// Token symbol (e.g., BTC).
     fn token_symbol(&self) -> &str;
 
+    /// The account that receives transaction fees. The ledger burns the fees
+    /// if there is no fee collector.
+    fn fee_collector(&self) -> Option<&FeeCollector<Self::AccountId>>;
+    fn fee_collector_mut(&mut self) -> Option<&mut FeeCollector<Self::AccountId>>;
+
     // Ledger data structures
 
-    fn balances(&self) -> &Balances<Self::AccountId, HashMap<Self::AccountId, Tokens>>;
-    fn balances_mut(&mut self) -> &mut Balances<Self::AccountId, HashMap<Self::AccountId, Tokens>>;
+    fn balances(&self) -> &Balances<Self::AccountId, Self::BalancesStore>;
+    fn balances_mut(&mut self) -> &mut Balances<Self::AccountId, Self::BalancesStore>;
+
+    fn approvals(&self) -> &AllowanceTable<Self::AllowancesData>;
+    fn approvals_mut(&mut self) -> &mut AllowanceTable<Self::AllowancesData>;
 
-    fn blockchain(&self) -> &Blockchain<Self::Runtime, Self::ArchiveWasm>;
-    fn blockchain_mut(&mut self) -> &mut Blockchain<Self::Runtime, Self::ArchiveWasm>;
+    fn blockchain(&self) -> &Blockchain<Self::Runtime, Self::ArchiveWasm, Self::BlockData>;
+    fn blockchain_mut(
+        &mut self,
+    ) -> &mut Blockchain<Self::Runtime, Self::ArchiveWasm, Self::BlockData>;
 
     fn transactions_by_hash(&self) -> &BTreeMap<HashOf<Self::Transaction>, BlockIndex>;
     fn transactions_by_hash_mut(&mut self) -> &mut BTreeMap<HashOf<Self::Transaction>, BlockIndex>;
@@ -121,22 +175,35 @@ pub enum TransferError {
     TxCreatedInFuture { ledger_time: TimeStamp },
     TxThrottled,
     TxDuplicate { duplicate_of: BlockIndex },
+    InsufficientAllowance { allowance: Tokens },
+    ExpiredApproval { now: TimeStamp },
+    AllowanceChanged { current_allowance: Tokens },
 }
 
-/// Adds a new block with the specified transaction to the ledger.
-pub fn apply_transaction<L: LedgerData>(
-    ledger: &mut L,
-    transaction: L::Transaction,
-    now: TimeStamp,
-) -> Result<(BlockIndex, HashOf<EncodedBlock>), TransferError> {
-    let num_pruned = purge_old_transactions(ledger, now);
-
-    // If we pruned some transactions, let this one through
-    // otherwise throttle if there are too many
-    if num_pruned == 0 && throttle(ledger, now) {
-        return Err(TransferError::TxThrottled);
+impl From<TxApplyError> for TransferError {
+    fn from(err: TxApplyError) -> Self {
+        match err {
+            TxApplyError::InsufficientFunds { balance } => Self::InsufficientFunds { balance },
+            TxApplyError::InsufficientAllowance { allowance } => {
+                Self::InsufficientAllowance { allowance }
+            }
+            TxApplyError::ExpiredApproval { now } => Self::ExpiredApproval { now },
+            TxApplyError::AllowanceChanged { current_allowance } => {
+                Self::AllowanceChanged { current_allowance }
+            }
+        }
     }
+}
 
+/// Checks that a transaction requesting deduplication falls into the
+/// transaction window and does not duplicate a recent transaction.
+/// Returns the creation time and the hash of the transaction if the caller
+/// requested deduplication.
+pub fn check_deduplication<L: LedgerData>(
+    ledger: &L,
+    transaction: &L::Transaction,
+    now: TimeStamp,
+) -> Result<Option<(TimeStamp, HashOf<L::Transaction>)>, TransferError> {
     let maybe_time_and_hash = transaction
         .created_at_time()
         .map(|created_at_time| (created_at_time, transaction.hash()));
@@ -160,15 +227,45 @@ pub fn apply_transaction<L: LedgerData>(
         }
     }
 
-    transaction
-        .apply(ledger.balances_mut())
-        .map_err(|e| match e {
-            BalanceError::InsufficientFunds { balance } => {
-                TransferError::InsufficientFunds { balance }
-            }
-        })?;
+    Ok(maybe_time_and_hash)
+}
+
+/// Returns true if the ledger can apply `num_transactions` transactions
+/// requesting deduplication without throttling any of them.
+pub fn can_admit_without_throttling<L: LedgerData>(ledger: &L, num_transactions: usize) -> bool {
+    ledger
+        .transactions_by_height()
+        .len()
+        .saturating_add(num_transactions)
+        < ledger.max_transactions_in_window() / 2
+}
 
-    let block = L::Block::from_transaction(ledger.blockchain().last_hash, transaction, now);
+/// Adds a new block with the specified transaction to the ledger.
+pub fn apply_transaction<L: LedgerData>(
+    ledger: &mut L,
+    transaction: L::Transaction,
+    now: TimeStamp,
+) -> Result<(BlockIndex, HashOf<EncodedBlock>), TransferError> {
+    let num_pruned = purge_old_transactions(ledger, now);
+    let max_approvals_to_prune = ledger.max_transactions_to_purge();
+    ledger.approvals_mut().prune(now, max_approvals_to_prune);
+
+    // If we pruned some transactions, let this one through
+    // otherwise throttle if there are too many
+    if num_pruned == 0 && throttle(ledger, now) {
+        return Err(TransferError::TxThrottled);
+    }
+
+    let maybe_time_and_hash = check_deduplication(ledger, &transaction, now)?;
+
+    transaction.apply(ledger, now)?;
+
+    let block = L::Block::from_transaction(
+        ledger.blockchain().last_hash,
+        transaction,
+        now,
+        ledger.fee_collector(),
+    );
     let block_timestamp = block.timestamp();
 
     let height = ledger
@@ -176,6 +273,14 @@ pub fn apply_transaction<L: LedgerData>(
         .add_block(block)
         .expect("failed to add block");
 
+    if let Some(fee_collector) = ledger.fee_collector_mut() {
+        // The first block after a fee collector change records the full
+        // account, all the following blocks refer to that block.
+        if fee_collector.block_index.is_none() {
+            fee_collector.block_index = Some(height);
+        }
+    }
+
     if let Some((_, tx_hash)) = maybe_time_and_hash {
         // The caller requested deduplication, so we have to remember this
         // transaction within the dedup window.
@@ -201,15 +306,17 @@ pub fn apply_transaction<L: LedgerData>(
         let burn_tx = L::Transaction::burn(account, balance, Some(now), Some(TRIMMED_MEMO));
 
         burn_tx
-            .apply(ledger.balances_mut())
+            .apply(ledger, now)
             .expect("failed to burn funds that must have existed");
 
-        let parent_hash = ledger.blockchain().last_hash;
+        let block = L::Block::from_transaction(
+            ledger.blockchain().last_hash,
+            burn_tx,
+            now,
+            ledger.fee_collector(),
+        );
 
-        ledger
-            .blockchain_mut()
-            .add_block(L::Block::from_transaction(parent_hash, burn_tx, now))
-            .unwrap();
+        ledger.blockchain_mut().add_block(block).unwrap();
     }
 
     Ok((height, ledger.blockchain().last_hash.unwrap()))
@@ -320,15 +427,15 @@ fn select_accounts_to_trim<L: LedgerData>(ledger: &L) -> Vec<(Tokens, L::Account
 
     // Accumulate up to `trim_quantity` accounts
     for (account, balance) in iter.by_ref().take(num_accounts) {
-        to_trim.push((*balance, account.clone()));
+        to_trim.push((balance, account));
     }
 
     for (account, balance) in iter {
         // If any account's balance is lower than the maximum in our set,
         // include that account, and remove the current maximum
         if let Some((greatest_balance, _)) = to_trim.peek() {
-            if balance < greatest_balance {
-                to_trim.push((*balance, account.clone()));
+            if balance < *greatest_balance {
+                to_trim.push((balance, account));
                 to_trim.pop();
             }
         }
@@ -380,7 +487,12 @@ pub async fn archive_blocks<LA: LedgerAccess>(max_message_size: usize) {
     let num_blocks = blocks_to_archive.len();
     print::<LA>(&format!("[ledger] archiving {} blocks", num_blocks));
 
-    let result = send_blocks_to_archive(archive_arc, blocks_to_archive, max_message_size).await;
+    let result = send_blocks_to_archive::<_, _, <LA::Ledger as LedgerData>::Block>(
+        archive_arc,
+        blocks_to_archive,
+        max_message_size,
+    )
+    .await;
 
     LA::with_ledger_mut(|ledger| match result {
         Ok(num_sent_blocks) => ledger
@@ -398,6 +510,54 @@ pub async fn archive_blocks<LA: LedgerAccess>(max_message_size: usize) {
     });
 }
 
+/// Upgrades the archive nodes to the archive Wasm of the current ledger
+/// version, once after each ledger upgrade.
+///
+/// Canisters cannot make calls in post_upgrade, so ledgers call this from a
+/// global timer that they set in post_upgrade.
+///
+/// Returns false if the upgrade is pending, but the ledger is archiving blocks
+/// at the moment, in which case the caller should try again later. Holding
+/// the archiving guard guarantees that blocks are never appended to a node
+/// that is being upgraded.
+pub async fn upgrade_archives<LA: LedgerAccess>() -> bool {
+    use crate::archive::{
+        upgrade_archive_nodes, ArchivingGuard, ArchivingGuardError, FailedToUpgradeArchive,
+    };
+    use std::sync::Arc;
+
+    let archive_arc = LA::with_ledger(|ledger| ledger.blockchain().archive.clone());
+    let upgrade_pending = archive_arc
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|archive| archive.nodes_upgrade_pending())
+        .unwrap_or(false);
+    if !upgrade_pending {
+        return true;
+    }
+
+    let _archiving_guard = match ArchivingGuard::new(Arc::clone(&archive_arc)) {
+        Ok(guard) => guard,
+        Err(ArchivingGuardError::NoArchive) => return true,
+        Err(ArchivingGuardError::AlreadyArchiving) => return false,
+    };
+
+    if let Err(failures) = upgrade_archive_nodes(&archive_arc).await {
+        for FailedToUpgradeArchive {
+            canister_id,
+            reason,
+        } in failures
+        {
+            <<<LA as LedgerAccess>::Ledger as LedgerData>::Runtime as Runtime>::print(format!(
+                "[ledger] failed to upgrade archive node {}: {}",
+                canister_id, reason
+            ));
+        }
+    }
+    true
+}
+
 /// The distribution of a block range across canisters.
 pub struct BlockLocations {
     /// Blocks currently owned by the main ledger canister.
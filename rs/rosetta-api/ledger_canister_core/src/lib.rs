@@ -1,6 +1,9 @@
+pub mod account_filter;
+pub mod approvals;
 pub mod archive;
 pub mod blockchain;
 pub mod ledger;
 pub mod range_utils;
 pub mod runtime;
 mod spawn;
+pub mod stable_memory;
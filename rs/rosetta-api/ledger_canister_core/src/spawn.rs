@@ -11,9 +11,32 @@ where
     Rt: Runtime,
 {
     Rt::print("[spawn] install_code()");
+    call_install_code::<Rt>(CanisterInstallMode::Install, canister_id, wasm_module, arg).await
+}
 
+pub async fn upgrade_code<Rt>(
+    canister_id: CanisterId,
+    wasm_module: Vec<u8>,
+    arg: Vec<u8>,
+) -> Result<(), (i32, String)>
+where
+    Rt: Runtime,
+{
+    Rt::print(format!("[spawn] upgrade_code({})", canister_id));
+    call_install_code::<Rt>(CanisterInstallMode::Upgrade, canister_id, wasm_module, arg).await
+}
+
+async fn call_install_code<Rt>(
+    mode: CanisterInstallMode,
+    canister_id: CanisterId,
+    wasm_module: Vec<u8>,
+    arg: Vec<u8>,
+) -> Result<(), (i32, String)>
+where
+    Rt: Runtime,
+{
     let install_code = InstallCodeArgs {
-        mode: CanisterInstallMode::Install,
+        mode,
         canister_id: canister_id.get(),
         wasm_module,
         arg,
@@ -50,3 +73,23 @@ where
     Rt::print(format!("[spawn] create_canister() = {:?}", result));
     result.map(|r| r.get_canister_id())
 }
+
+pub async fn deposit_cycles<Rt>(canister_id: CanisterId, cycles: u64) -> Result<(), (i32, String)>
+where
+    Rt: Runtime,
+{
+    Rt::print(format!(
+        "[spawn] deposit_cycles(canister_id={}, cycles={})",
+        canister_id, cycles
+    ));
+
+    let () = Rt::call(
+        IC_00,
+        "deposit_cycles",
+        cycles,
+        (CanisterIdRecord::from(canister_id),),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,693 @@
+//! Ledger state stored in stable memory.
+//!
+//! The ledger keeps balances, allowances and unarchived blocks in stable
+//! structures so that upgrades do not need to serialize them. Ledgers that
+//! were installed before the stable structures existed keep their state in
+//! the heap until it is migrated by [MigrateToStable::migrate_to_stable],
+//! which moves a bounded number of entries per call so that the migration
+//! can be spread across several messages.
+//!
+//! The rest of the ledger state is serialized to a dedicated virtual memory
+//! on upgrades, see [UpgradesWriter] and [UpgradesReader].
+//!
+//! The account filters of the archive nodes are stored in stable memory as
+//! well, see [crate::account_filter::AccountFilter].
+use crate::approvals::{Allowance, AllowancesData, HeapAllowancesData};
+use crate::blockchain::BlockData;
+use ic_base_types::CanisterId;
+use ic_ledger_core::balances::{BalancesStore, InspectableBalancesStore};
+use ic_ledger_core::block::EncodedBlock;
+use ic_ledger_core::timestamp::TimeStamp;
+use ic_ledger_core::tokens::Tokens;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, Memory, StableBTreeMap};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::ops::Range;
+
+const WASM_PAGE_SIZE: u64 = 65536;
+
+/// The magic bytes the memory manager writes at the beginning of the stable
+/// memory.
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+
+const UPGRADES_MEMORY_ID: MemoryId = MemoryId::new(0);
+const BALANCES_MEMORY_ID: MemoryId = MemoryId::new(1);
+const BLOCKS_MEMORY_ID: MemoryId = MemoryId::new(2);
+const ALLOWANCES_MEMORY_ID: MemoryId = MemoryId::new(3);
+const ALLOWANCE_EXPIRATIONS_MEMORY_ID: MemoryId = MemoryId::new(4);
+const ACCOUNT_FILTERS_MEMORY_ID: MemoryId = MemoryId::new(5);
+
+/// The maximum size of the stable encoding of an account.
+pub const MAX_ACCOUNT_ID_SIZE: u32 = 64;
+/// The maximum size of an encoded block stored in stable memory.
+const MAX_BLOCK_SIZE: u32 = 1024;
+/// The maximum size of an encoded allowance.
+const MAX_ALLOWANCE_SIZE: u32 = 512;
+/// The maximum size of an encoded `(account, spender)` pair.
+const MAX_ACCOUNT_PAIR_SIZE: u32 = 256;
+/// The maximum size of an account filter key: the length-prefixed principal
+/// of the archive node followed by the index of the filter word.
+const MAX_ACCOUNT_FILTER_KEY_SIZE: u32 = 1 + 29 + 4;
+
+type VMem = VirtualMemory<DefaultMemoryImpl>;
+type StableMap = StableBTreeMap<VMem, Vec<u8>, Vec<u8>>;
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    /// Account balances indexed by the stable encoding of the account.
+    static BALANCES: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(BALANCES_MEMORY_ID),
+        MAX_ACCOUNT_ID_SIZE,
+        8,
+    ));
+
+    /// Unarchived blocks indexed by their big-endian key.
+    static BLOCKS: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(BLOCKS_MEMORY_ID),
+        8,
+        MAX_BLOCK_SIZE,
+    ));
+
+    /// Allowances indexed by the concatenated encodings of the account and
+    /// the spender.
+    static ALLOWANCES: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(ALLOWANCES_MEMORY_ID),
+        2 * MAX_ACCOUNT_ID_SIZE,
+        MAX_ALLOWANCE_SIZE,
+    ));
+
+    /// Allowance expiration times indexed by the big-endian expiration
+    /// timestamp followed by the allowance key.
+    static ALLOWANCE_EXPIRATIONS: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(ALLOWANCE_EXPIRATIONS_MEMORY_ID),
+        8 + 2 * MAX_ACCOUNT_ID_SIZE,
+        MAX_ACCOUNT_PAIR_SIZE,
+    ));
+
+    /// The non-zero words of the account filters of the archive nodes,
+    /// indexed by the length-prefixed principal of the node followed by the
+    /// big-endian index of the word.
+    static ACCOUNT_FILTERS: RefCell<StableMap> = RefCell::new(StableMap::init(
+        memory(ACCOUNT_FILTERS_MEMORY_ID),
+        MAX_ACCOUNT_FILTER_KEY_SIZE,
+        8,
+    ));
+}
+
+fn memory(id: MemoryId) -> VMem {
+    MEMORY_MANAGER.with(|m| m.borrow().get(id))
+}
+
+/// Returns true if the stable memory contains the state written by a ledger
+/// version that serialized its whole state to the raw stable memory.
+///
+/// This function must be called before any stable structure is accessed: the
+/// memory manager claims the stable memory on initialization.
+pub fn is_legacy_layout() -> bool {
+    let memory = DefaultMemoryImpl::default();
+    if memory.size() == 0 {
+        return false;
+    }
+    let mut magic = [0u8; 3];
+    memory.read(0, &mut magic);
+    &magic != MEMORY_MANAGER_MAGIC
+}
+
+/// An account identifier that can be used as a key of a stable structure.
+pub trait StableAccountId: Sized {
+    /// Encodes the account. The encoding must be injective, self-delimiting
+    /// and at most [MAX_ACCOUNT_ID_SIZE] bytes long.
+    fn to_stable_bytes(&self) -> Vec<u8>;
+
+    /// Decodes an account encoded with [StableAccountId::to_stable_bytes].
+    fn from_stable_bytes(bytes: &[u8]) -> Self;
+}
+
+/// A part of the ledger state that can be moved from the heap to stable
+/// memory incrementally.
+pub trait MigrateToStable {
+    /// Moves at most `limit` entries from the heap to stable memory and
+    /// returns the number of entries moved.
+    fn migrate_to_stable(&mut self, limit: usize) -> usize;
+
+    /// Returns true if no entries are left in the heap.
+    fn is_migrated(&self) -> bool;
+}
+
+fn encode_tokens(tokens: Tokens) -> Vec<u8> {
+    tokens.get_e8s().to_be_bytes().to_vec()
+}
+
+fn decode_tokens(bytes: &[u8]) -> Tokens {
+    Tokens::from_e8s(u64::from_be_bytes(
+        bytes.try_into().expect("BUG: invalid balance encoding"),
+    ))
+}
+
+fn encode_cbor<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut buf = vec![];
+    ciborium::ser::into_writer(value, &mut buf).expect("failed to encode a value to CBOR");
+    buf
+}
+
+fn decode_cbor<T: DeserializeOwned>(bytes: &[u8]) -> T {
+    ciborium::de::from_reader(bytes).expect("failed to decode a value from CBOR")
+}
+
+/// Account balances stored in stable memory.
+///
+/// The balances loaded from a legacy ledger state are kept in the heap until
+/// they are migrated.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(transparent)]
+#[serde(bound(
+    serialize = "AccountId: Serialize",
+    deserialize = "AccountId: Deserialize<'de> + std::hash::Hash + Eq"
+))]
+pub struct StableBalances<AccountId> {
+    pending: HashMap<AccountId, Tokens>,
+}
+
+impl<AccountId> Default for StableBalances<AccountId> {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl<AccountId> BalancesStore<AccountId> for StableBalances<AccountId>
+where
+    AccountId: StableAccountId + std::hash::Hash + Eq,
+{
+    fn get_balance(&self, k: &AccountId) -> Option<Tokens> {
+        BALANCES
+            .with(|b| b.borrow().get(&k.to_stable_bytes()))
+            .map(|bytes| decode_tokens(&bytes))
+            .or_else(|| self.pending.get(k).copied())
+    }
+
+    fn update<F, E>(&mut self, k: AccountId, mut f: F) -> Result<Tokens, E>
+    where
+        F: FnMut(Option<&Tokens>) -> Result<Tokens, E>,
+    {
+        let old_balance = self.get_balance(&k);
+        let new_balance = f(old_balance.as_ref())?;
+        self.pending.remove(&k);
+        BALANCES.with(|b| {
+            let mut b = b.borrow_mut();
+            if new_balance != Tokens::ZERO {
+                b.insert(k.to_stable_bytes(), encode_tokens(new_balance))
+                    .expect("failed to insert a balance into stable memory");
+            } else {
+                b.remove(&k.to_stable_bytes());
+            }
+        });
+        Ok(new_balance)
+    }
+}
+
+impl<AccountId> InspectableBalancesStore<AccountId> for StableBalances<AccountId>
+where
+    AccountId: StableAccountId + std::hash::Hash + Eq + Clone,
+{
+    /// Iterates over the balances. The balances stored in stable memory are
+    /// copied to the heap first, so the cost is linear in the number of
+    /// accounts.
+    fn iter(&self) -> Box<dyn Iterator<Item = (AccountId, Tokens)> + '_> {
+        let stable: Vec<(AccountId, Tokens)> = BALANCES.with(|b| {
+            b.borrow()
+                .iter()
+                .map(|(k, v)| (AccountId::from_stable_bytes(&k), decode_tokens(&v)))
+                .collect()
+        });
+        Box::new(
+            stable.into_iter().chain(
+                self.pending
+                    .iter()
+                    .map(|(account, balance)| (account.clone(), *balance)),
+            ),
+        )
+    }
+
+    fn len(&self) -> usize {
+        BALANCES.with(|b| b.borrow().len() as usize) + self.pending.len()
+    }
+}
+
+impl<AccountId> MigrateToStable for StableBalances<AccountId>
+where
+    AccountId: StableAccountId + std::hash::Hash + Eq + Clone,
+{
+    fn migrate_to_stable(&mut self, limit: usize) -> usize {
+        let accounts: Vec<AccountId> = self.pending.keys().take(limit).cloned().collect();
+        BALANCES.with(|b| {
+            let mut b = b.borrow_mut();
+            for account in accounts.iter() {
+                let balance = self.pending.remove(account).unwrap();
+                b.insert(account.to_stable_bytes(), encode_tokens(balance))
+                    .expect("failed to insert a balance into stable memory");
+            }
+        });
+        accounts.len()
+    }
+
+    fn is_migrated(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// The serialized representation of [StableBlockData].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BlockDataRepr {
+    /// The list of blocks written by the ledger versions that kept all the
+    /// unarchived blocks in the heap.
+    Legacy(VecDeque<EncodedBlock>),
+    Current {
+        pending: VecDeque<EncodedBlock>,
+        first_key: u64,
+    },
+}
+
+/// Unarchived blocks stored in stable memory.
+///
+/// The block at position `p` is stored under the key `first_key + p`. The
+/// oldest blocks of a legacy ledger state stay in the heap until they are
+/// migrated; they always precede the blocks stored in stable memory.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(from = "BlockDataRepr")]
+pub struct StableBlockData {
+    pending: VecDeque<EncodedBlock>,
+    first_key: u64,
+}
+
+impl From<BlockDataRepr> for StableBlockData {
+    fn from(repr: BlockDataRepr) -> Self {
+        match repr {
+            BlockDataRepr::Legacy(pending) => Self {
+                pending,
+                first_key: 0,
+            },
+            BlockDataRepr::Current { pending, first_key } => Self { pending, first_key },
+        }
+    }
+}
+
+impl StableBlockData {
+    fn num_pending(&self) -> u64 {
+        self.pending.len() as u64
+    }
+
+    fn num_stable(&self) -> u64 {
+        BLOCKS.with(|b| b.borrow().len())
+    }
+
+    fn key(&self, position: u64) -> Vec<u8> {
+        (self.first_key + position).to_be_bytes().to_vec()
+    }
+}
+
+impl BlockData for StableBlockData {
+    fn add_block(&mut self, block: EncodedBlock) {
+        let key = self.key(self.len());
+        BLOCKS.with(|b| {
+            b.borrow_mut()
+                .insert(key, block.into_vec())
+                .expect("failed to insert a block into stable memory")
+        });
+    }
+
+    fn get_block(&self, position: u64) -> Option<EncodedBlock> {
+        if position < self.num_pending() {
+            return self
+                .pending
+                .get(usize::try_from(position).unwrap())
+                .cloned();
+        }
+        BLOCKS
+            .with(|b| b.borrow().get(&self.key(position)))
+            .map(EncodedBlock::from_vec)
+    }
+
+    fn get_blocks(&self, positions: Range<u64>) -> Vec<EncodedBlock> {
+        assert!(
+            positions.end <= self.len(),
+            "requested blocks {:?} out of bounds (number of blocks: {})",
+            positions,
+            self.len()
+        );
+        positions
+            .map(|position| self.get_block(position).unwrap())
+            .collect()
+    }
+
+    fn remove_oldest_blocks(&mut self, num_blocks: u64) {
+        // The pending blocks occupy the first positions, so the removed
+        // blocks that are in stable memory are at the positions following
+        // them.
+        let from_pending = num_blocks.min(self.num_pending());
+        BLOCKS.with(|b| {
+            let mut b = b.borrow_mut();
+            for position in from_pending..num_blocks {
+                b.remove(&self.key(position));
+            }
+        });
+        self.pending.drain(..usize::try_from(from_pending).unwrap());
+        self.first_key += num_blocks;
+    }
+
+    fn len(&self) -> u64 {
+        self.num_pending() + self.num_stable()
+    }
+}
+
+impl MigrateToStable for StableBlockData {
+    fn migrate_to_stable(&mut self, limit: usize) -> usize {
+        let mut migrated = 0;
+        BLOCKS.with(|b| {
+            let mut b = b.borrow_mut();
+            while migrated < limit {
+                let block = match self.pending.pop_back() {
+                    Some(block) => block,
+                    None => break,
+                };
+                b.insert(self.key(self.pending.len() as u64), block.into_vec())
+                    .expect("failed to insert a block into stable memory");
+                migrated += 1;
+            }
+        });
+        migrated
+    }
+
+    fn is_migrated(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Allowances stored in stable memory.
+///
+/// The allowances loaded from a legacy ledger state are kept in the heap
+/// until they are migrated.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(transparent)]
+#[serde(bound(
+    serialize = "AccountId: Serialize",
+    deserialize = "AccountId: Deserialize<'de> + Ord"
+))]
+pub struct StableAllowancesData<AccountId: Ord> {
+    pending: HeapAllowancesData<AccountId>,
+}
+
+impl<AccountId: Ord> Default for StableAllowancesData<AccountId> {
+    fn default() -> Self {
+        Self {
+            pending: HeapAllowancesData::default(),
+        }
+    }
+}
+
+fn allowance_key<AccountId: StableAccountId>(account_spender: &(AccountId, AccountId)) -> Vec<u8> {
+    let mut key = account_spender.0.to_stable_bytes();
+    key.extend(account_spender.1.to_stable_bytes());
+    key
+}
+
+fn expiration_key<AccountId: StableAccountId>(
+    expires_at: TimeStamp,
+    account_spender: &(AccountId, AccountId),
+) -> Vec<u8> {
+    let mut key = expires_at
+        .as_nanos_since_unix_epoch()
+        .to_be_bytes()
+        .to_vec();
+    key.extend(allowance_key(account_spender));
+    key
+}
+
+impl<AccountId> AllowancesData for StableAllowancesData<AccountId>
+where
+    AccountId: StableAccountId + Serialize + DeserializeOwned + Ord + Clone,
+{
+    type AccountId = AccountId;
+
+    fn get_allowance(&self, account_spender: &(AccountId, AccountId)) -> Option<Allowance> {
+        ALLOWANCES
+            .with(|a| a.borrow().get(&allowance_key(account_spender)))
+            .map(|bytes| decode_cbor(&bytes))
+            .or_else(|| self.pending.get_allowance(account_spender))
+    }
+
+    fn set_allowance(&mut self, account_spender: (AccountId, AccountId), allowance: Allowance) {
+        self.pending.remove_allowance(&account_spender);
+        ALLOWANCES.with(|a| {
+            a.borrow_mut()
+                .insert(allowance_key(&account_spender), encode_cbor(&allowance))
+                .expect("failed to insert an allowance into stable memory")
+        });
+    }
+
+    fn remove_allowance(&mut self, account_spender: &(AccountId, AccountId)) {
+        self.pending.remove_allowance(account_spender);
+        ALLOWANCES.with(|a| a.borrow_mut().remove(&allowance_key(account_spender)));
+    }
+
+    fn insert_expiry(&mut self, expires_at: TimeStamp, account_spender: (AccountId, AccountId)) {
+        ALLOWANCE_EXPIRATIONS.with(|e| {
+            e.borrow_mut()
+                .insert(
+                    expiration_key(expires_at, &account_spender),
+                    encode_cbor(&account_spender),
+                )
+                .expect("failed to insert an allowance expiration into stable memory")
+        });
+    }
+
+    fn remove_expiry(&mut self, expires_at: TimeStamp, account_spender: &(AccountId, AccountId)) {
+        self.pending.remove_expiry(expires_at, account_spender);
+        ALLOWANCE_EXPIRATIONS.with(|e| {
+            e.borrow_mut()
+                .remove(&expiration_key(expires_at, account_spender))
+        });
+    }
+
+    fn first_expiry(&self) -> Option<(TimeStamp, (AccountId, AccountId))> {
+        let stable = ALLOWANCE_EXPIRATIONS.with(|e| {
+            e.borrow().iter().next().map(|(k, v)| {
+                let nanos = u64::from_be_bytes(k[..8].try_into().unwrap());
+                (
+                    TimeStamp::from_nanos_since_unix_epoch(nanos),
+                    decode_cbor(&v),
+                )
+            })
+        });
+        match (stable, self.pending.first_expiry()) {
+            (Some(stable), Some(pending)) => Some(std::cmp::min(stable, pending)),
+            (stable, pending) => stable.or(pending),
+        }
+    }
+
+    fn len_allowances(&self) -> usize {
+        ALLOWANCES.with(|a| a.borrow().len() as usize) + self.pending.len_allowances()
+    }
+}
+
+impl<AccountId> MigrateToStable for StableAllowancesData<AccountId>
+where
+    AccountId: StableAccountId + Serialize + DeserializeOwned + Ord + Clone,
+{
+    fn migrate_to_stable(&mut self, limit: usize) -> usize {
+        let mut migrated = 0;
+        while migrated < limit {
+            let (key, allowance) = match self.pending.pop_allowance() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if let Some(expires_at) = allowance.expires_at {
+                self.insert_expiry(expires_at, key.clone());
+            }
+            self.set_allowance(key, allowance);
+            migrated += 1;
+        }
+        migrated
+    }
+
+    fn is_migrated(&self) -> bool {
+        self.pending.len_allowances() == 0
+    }
+}
+
+fn account_filter_prefix(node: &CanisterId) -> Vec<u8> {
+    let principal = node.get().into_vec();
+    let mut prefix = Vec::with_capacity(1 + principal.len());
+    prefix.push(principal.len() as u8);
+    prefix.extend_from_slice(&principal);
+    prefix
+}
+
+fn account_filter_key(node: &CanisterId, word: u32) -> Vec<u8> {
+    let mut key = account_filter_prefix(node);
+    key.extend_from_slice(&word.to_be_bytes());
+    key
+}
+
+/// Returns the specified word of the account filter of an archive node.
+pub(crate) fn account_filter_word(node: &CanisterId, word: u32) -> u64 {
+    ACCOUNT_FILTERS
+        .with(|f| f.borrow().get(&account_filter_key(node, word)))
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .unwrap_or(0)
+}
+
+/// Sets the specified word of the account filter of an archive node.
+pub(crate) fn set_account_filter_word(node: &CanisterId, word: u32, bits: u64) {
+    ACCOUNT_FILTERS.with(|f| {
+        f.borrow_mut()
+            .insert(account_filter_key(node, word), bits.to_le_bytes().to_vec())
+            .expect("failed to insert an account filter word into stable memory")
+    });
+}
+
+/// Removes the account filter of an archive node.
+pub(crate) fn remove_account_filter(node: &CanisterId) {
+    ACCOUNT_FILTERS.with(|f| {
+        let mut f = f.borrow_mut();
+        let keys: Vec<_> = f
+            .range(account_filter_prefix(node), None)
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            f.remove(&key);
+        }
+    });
+}
+
+/// Writes the serialized ledger state to the virtual memory reserved for
+/// upgrades.
+pub struct UpgradesWriter {
+    memory: VMem,
+    offset: u64,
+}
+
+impl Default for UpgradesWriter {
+    fn default() -> Self {
+        Self {
+            memory: memory(UPGRADES_MEMORY_ID),
+            offset: 0,
+        }
+    }
+}
+
+impl io::Write for UpgradesWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let end = self.offset + buf.len() as u64;
+        let capacity = self.memory.size() * WASM_PAGE_SIZE;
+        if end > capacity {
+            let additional_pages = (end - capacity + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+            if self.memory.grow(additional_pages) == -1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "failed to grow the upgrades memory",
+                ));
+            }
+        }
+        self.memory.write(self.offset, buf);
+        self.offset = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads the ledger state written by [UpgradesWriter].
+pub struct UpgradesReader {
+    memory: VMem,
+    offset: u64,
+}
+
+impl Default for UpgradesReader {
+    fn default() -> Self {
+        Self {
+            memory: memory(UPGRADES_MEMORY_ID),
+            offset: 0,
+        }
+    }
+}
+
+impl io::Read for UpgradesReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let capacity = self.memory.size() * WASM_PAGE_SIZE;
+        let n = (buf.len() as u64).min(capacity.saturating_sub(self.offset));
+        self.memory
+            .read(self.offset, &mut buf[..usize::try_from(n).unwrap()]);
+        self.offset += n;
+        Ok(usize::try_from(n).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(n: u64) -> EncodedBlock {
+        EncodedBlock::from_vec(n.to_be_bytes().to_vec())
+    }
+
+    fn blocks(positions: Range<u64>) -> Vec<EncodedBlock> {
+        positions.map(block).collect()
+    }
+
+    /// Returns block data for a legacy ledger with blocks `0..10` in the heap
+    /// of which the newest `num_migrated` are already in stable memory.
+    fn partially_migrated_block_data(num_migrated: usize) -> StableBlockData {
+        let mut data = StableBlockData::from(BlockDataRepr::Legacy(blocks(0..10).into()));
+        assert_eq!(data.migrate_to_stable(num_migrated), num_migrated);
+        data
+    }
+
+    #[test]
+    fn archive_pending_and_stable_blocks_during_migration() {
+        let mut data = partially_migrated_block_data(4);
+        data.add_block(block(10));
+        data.add_block(block(11));
+
+        // Blocks 0..6 are pending, so this removes blocks 6 and 7 from
+        // stable memory.
+        data.remove_oldest_blocks(8);
+        assert!(data.is_migrated());
+        assert_eq!(data.len(), 4);
+        assert_eq!(data.get_blocks(0..4), blocks(8..12));
+        assert_eq!(BLOCKS.with(|b| b.borrow().len()), 4);
+
+        data.add_block(block(12));
+        assert_eq!(data.get_blocks(0..5), blocks(8..13));
+    }
+
+    #[test]
+    fn archive_pending_blocks_during_migration() {
+        let mut data = partially_migrated_block_data(4);
+
+        data.remove_oldest_blocks(3);
+        assert!(!data.is_migrated());
+        assert_eq!(data.len(), 7);
+        assert_eq!(data.get_blocks(0..7), blocks(3..10));
+
+        // Finish the migration and check that the blocks keep their
+        // positions.
+        assert_eq!(data.migrate_to_stable(usize::MAX), 3);
+        assert!(data.is_migrated());
+        data.add_block(block(10));
+        assert_eq!(data.get_blocks(0..8), blocks(3..11));
+
+        data.remove_oldest_blocks(5);
+        assert_eq!(data.get_blocks(0..3), blocks(8..11));
+        assert_eq!(BLOCKS.with(|b| b.borrow().len()), 3);
+    }
+}
@@ -8,7 +8,7 @@ use std::marker::PhantomData;
 
 pub trait BalancesStore<AccountId> {
     /// Returns the balance on the specified account.
-    fn get_balance(&self, k: &AccountId) -> Option<&Tokens>;
+    fn get_balance(&self, k: &AccountId) -> Option<Tokens>;
 
     /// Update balance for an account using function f.
     /// Its arg is previous balance or None if not found and
@@ -18,9 +18,22 @@ pub trait BalancesStore<AccountId> {
         F: FnMut(Option<&Tokens>) -> Result<Tokens, E>;
 }
 
+/// A balances store that can enumerate the accounts it holds.
+pub trait InspectableBalancesStore<AccountId>: BalancesStore<AccountId> {
+    /// Returns an iterator over the accounts with a non-zero balance.
+    fn iter(&self) -> Box<dyn Iterator<Item = (AccountId, Tokens)> + '_>;
+
+    /// Returns the number of accounts with a non-zero balance.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 impl<AccountId: std::hash::Hash + Eq> BalancesStore<AccountId> for HashMap<AccountId, Tokens> {
-    fn get_balance(&self, k: &AccountId) -> Option<&Tokens> {
-        self.get(k)
+    fn get_balance(&self, k: &AccountId) -> Option<Tokens> {
+        self.get(k).cloned()
     }
 
     fn update<F, E>(&mut self, k: AccountId, mut f: F) -> Result<Tokens, E>
@@ -48,6 +61,19 @@ impl<AccountId: std::hash::Hash + Eq> BalancesStore<AccountId> for HashMap<Accou
     }
 }
 
+impl<AccountId> InspectableBalancesStore<AccountId> for HashMap<AccountId, Tokens>
+where
+    AccountId: std::hash::Hash + Eq + Clone,
+{
+    fn iter(&self) -> Box<dyn Iterator<Item = (AccountId, Tokens)> + '_> {
+        Box::new(HashMap::iter(self).map(|(account, balance)| (account.clone(), *balance)))
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
 /// An error returned by `Balances` if the debit operation fails.
 #[derive(Debug)]
 pub enum BalanceError {
@@ -163,10 +189,7 @@ where
     }
 
     pub fn account_balance(&self, account: &AccountId) -> Tokens {
-        self.store
-            .get_balance(account)
-            .cloned()
-            .unwrap_or(Tokens::ZERO)
+        self.store.get_balance(account).unwrap_or(Tokens::ZERO)
     }
 
     /// Returns the total quantity of Tokens that are "in existence" -- that
@@ -169,22 +169,47 @@ impl EncodedBlock {
     }
 }
 
+/// The account that receives the transaction fees.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FeeCollector<AccountId> {
+    pub fee_collector: AccountId,
+    /// The index of the first block that recorded this fee collector.
+    ///
+    /// Blocks following that block refer to the fee collector by this index
+    /// instead of repeating the full account.
+    pub block_index: Option<BlockIndex>,
+}
+
+impl<AccountId> From<AccountId> for FeeCollector<AccountId> {
+    fn from(fee_collector: AccountId) -> Self {
+        Self {
+            fee_collector,
+            block_index: None,
+        }
+    }
+}
+
 pub trait BlockType: Sized {
     type Transaction;
+    type AccountId;
 
     /// Constructs a new block containing the given transaction.
     ///
+    /// If the ledger has a `fee_collector`, the block records either the
+    /// collector account or a reference to the block that recorded it first.
+    ///
     /// Law:
     ///
     /// ```text
-    /// forall PH, TX, TS:
-    ///     from_transaction(PH, TX, TS).parent_hash() = PH
-    ///   ∧ from_transaction(PH, TX, TS).timestamp() = TS
+    /// forall PH, TX, TS, FC:
+    ///     from_transaction(PH, TX, TS, FC).parent_hash() = PH
+    ///   ∧ from_transaction(PH, TX, TS, FC).timestamp() = TS
     /// ```
     fn from_transaction(
         parent_hash: Option<HashOf<EncodedBlock>>,
         tx: Self::Transaction,
         block_timestamp: TimeStamp,
+        fee_collector: Option<&FeeCollector<Self::AccountId>>,
     ) -> Self;
 
     /// Encodes this block into a binary representation.
@@ -225,4 +250,8 @@ pub trait BlockType: Sized {
 
     /// Returns the time at which the ledger constructed this block.
     fn timestamp(&self) -> TimeStamp;
+
+    /// Returns the accounts that this block refers to, including the fee
+    /// collector if the block records it.
+    fn accounts(&self) -> Vec<Self::AccountId>;
 }
@@ -18,41 +18,64 @@ use crate::transaction_id::TransactionIdentifier;
 use crate::{convert, errors};
 use dfn_protobuf::ProtoBuf;
 use ic_crypto_tree_hash::Path;
+use ic_ledger_canister_blocks_synchronizer::ledger_type::LedgerType;
 use ic_ledger_canister_blocks_synchronizer::store::HashedBlock;
 use ic_ledger_core::block::{BlockType, HashOf};
 use ic_types::messages::{HttpCanisterUpdate, HttpReadState};
 use ic_types::{CanisterId, PrincipalId};
-use icp_ledger::{Block, BlockIndex, Operation as LedgerOperation, SendArgs, Subaccount, Tokens};
+use icp_ledger::{BlockIndex, Operation as LedgerOperation, SendArgs, Subaccount, Tokens};
 use on_wire::{FromWire, IntoWire};
 use serde_json::map::Map;
 use serde_json::{from_value, Number, Value};
+use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
 
 /// This module converts from ledger_canister data structures to Rosetta data
 /// structures
 
 pub fn block_to_transaction(
+    ledger_type: LedgerType,
     hb: &HashedBlock,
     token_name: &str,
 ) -> Result<models::Transaction, ApiError> {
-    let block = Block::decode(hb.block.clone())
+    let decoded = ledger_type
+        .decode_block(hb.block.clone())
         .map_err(|err| ApiError::internal_error(format!("Cannot decode block: {}", err)))?;
+    let block = decoded.block;
     let transaction = block.transaction;
-    let transaction_identifier = TransactionIdentifier::from(&transaction);
+    let transaction_identifier = TransactionIdentifier::from(&decoded.transaction_hash);
     let operation = transaction.operation;
-    let operations = {
+    let mut operations = {
         let mut ops = Request::requests_to_operations(&[Request::Transfer(operation)], token_name)?;
         for op in ops.iter_mut() {
             op.status = Some(STATUS_COMPLETED.to_string());
         }
         ops
     };
-    let mut t = models::Transaction::new(transaction_identifier, operations);
     let mut metadata = Map::new();
-    metadata.insert(
-        "memo".to_string(),
-        Value::Number(Number::from(transaction.memo.0)),
-    );
+    match ledger_type {
+        LedgerType::Icp => {
+            metadata.insert(
+                "memo".to_string(),
+                Value::Number(Number::from(transaction.memo.0)),
+            );
+        }
+        LedgerType::Icrc1 => {
+            let icrc1_block = ic_icrc1::Block::decode(hb.block.clone())
+                .map_err(|err| ApiError::internal_error(format!("Cannot decode block: {}", err)))?;
+            let accounts = icrc1_block_accounts(&icrc1_block.transaction.operation);
+            set_icrc1_operation_accounts(&mut operations, &accounts);
+            if let Some(memo) = icrc1_block.transaction.memo {
+                metadata.insert(
+                    "icrc1_memo".to_string(),
+                    serde_json::to_value(&memo)
+                        .map_err(|e| ApiError::internal_error(e.to_string()))?,
+                );
+            }
+        }
+    }
+    let mut t = models::Transaction::new(transaction_identifier, operations);
     metadata.insert(
         "block_height".to_string(),
         Value::Number(Number::from(hb.index)),
@@ -65,6 +88,53 @@ pub fn block_to_transaction(
     Ok(t)
 }
 
+/// Returns the ICRC-1 accounts involved in an operation, indexed by the
+/// [icp_ledger::AccountIdentifier] they are stored under.
+fn icrc1_block_accounts(
+    operation: &ic_icrc1::Operation,
+) -> HashMap<icp_ledger::AccountIdentifier, ic_icrc1::Account> {
+    let accounts = match operation {
+        ic_icrc1::Operation::Mint { to, .. } => vec![to],
+        ic_icrc1::Operation::Burn { from, .. } => vec![from],
+        ic_icrc1::Operation::Transfer { from, to, .. } => vec![from, to],
+        ic_icrc1::Operation::Approve { from, .. } => vec![from],
+    };
+    accounts
+        .into_iter()
+        .map(|account| (account.clone().into(), account.clone()))
+        .collect()
+}
+
+/// Returns the ICRC-1 accounts referenced by `ops`, indexed by the
+/// [icp_ledger::AccountIdentifier] the requests refer to them with.
+pub fn icrc1_accounts_from_operations(
+    ops: &[Operation],
+) -> HashMap<icp_ledger::AccountIdentifier, ic_icrc1::Account> {
+    ops.iter()
+        .filter_map(|op| op.account.as_ref())
+        .filter_map(|aid| icrc1_account_from_model_account_identifier(aid).ok())
+        .map(|account| (account.clone().into(), account))
+        .collect()
+}
+
+/// Replaces the account identifiers of `ops` with the ICRC-1 representation
+/// of the accounts they refer to.
+pub fn set_icrc1_operation_accounts(
+    ops: &mut [Operation],
+    accounts: &HashMap<icp_ledger::AccountIdentifier, ic_icrc1::Account>,
+) {
+    for op in ops.iter_mut() {
+        if let Some(account) = op
+            .account
+            .as_ref()
+            .and_then(|aid| from_model_account_identifier(aid).ok())
+            .and_then(|aid| accounts.get(&aid))
+        {
+            op.account = Some(icrc1_account_to_model_account_identifier(account));
+        }
+    }
+}
+
 /// Convert from operations to requests.
 pub fn operations_to_requests(
     ops: &[Operation],
@@ -260,10 +330,55 @@ pub fn to_model_account_identifier(aid: &icp_ledger::AccountIdentifier) -> Accou
     AccountIdentifier::new(aid.to_hex())
 }
 
+/// Parses an account identifier in either of the supported formats: the hex
+/// encoded ICP account identifier, or the ICRC-1 principal and optional hex
+/// encoded subaccount (see [icrc1_account_from_model_account_identifier]).
 pub fn from_model_account_identifier(
     aid: &AccountIdentifier,
 ) -> Result<icp_ledger::AccountIdentifier, String> {
-    icp_ledger::AccountIdentifier::from_hex(&aid.address).map_err(|e| e)
+    if aid.sub_account.is_none() {
+        if let Ok(account) = icp_ledger::AccountIdentifier::from_hex(&aid.address) {
+            return Ok(account);
+        }
+    }
+    icrc1_account_from_model_account_identifier(aid)
+        .map(icp_ledger::AccountIdentifier::from)
+        .map_err(|e| format!("Invalid account identifier {}: {}", aid.address, e))
+}
+
+/// ICRC-1 accounts are represented by the textual principal as the address and
+/// the hex encoded subaccount, which is omitted for the default subaccount.
+pub fn icrc1_account_to_model_account_identifier(account: &ic_icrc1::Account) -> AccountIdentifier {
+    AccountIdentifier {
+        address: account.owner.to_string(),
+        sub_account: account
+            .subaccount
+            .filter(|subaccount| subaccount != &[0; 32])
+            .map(|subaccount| models::SubAccountIdentifier {
+                address: hex::encode(subaccount),
+                metadata: None,
+            }),
+        metadata: None,
+    }
+}
+
+pub fn icrc1_account_from_model_account_identifier(
+    aid: &AccountIdentifier,
+) -> Result<ic_icrc1::Account, String> {
+    let owner = PrincipalId::from_str(&aid.address).map_err(|e| e.to_string())?;
+    let subaccount = match &aid.sub_account {
+        None => None,
+        Some(sub_account) => {
+            let bytes = hex::decode(&sub_account.address).map_err(|e| e.to_string())?;
+            Some(<[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| {
+                format!(
+                    "Subaccount must be 32 bytes long, got {} bytes",
+                    bytes.len()
+                )
+            })?)
+        }
+    };
+    Ok(ic_icrc1::Account { owner, subaccount })
 }
 
 const LAST_HEIGHT: &str = "last_height";
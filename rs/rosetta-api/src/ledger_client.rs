@@ -1,6 +1,7 @@
 mod handle_add_hotkey;
 mod handle_disburse;
 mod handle_follow;
+mod handle_icrc1_transfer;
 mod handle_merge_maturity;
 mod handle_neuron_info;
 mod handle_remove_hotkey;
@@ -30,11 +31,13 @@ use ic_ledger_canister_blocks_synchronizer::certification::VerificationInfo;
 use ic_ledger_canister_blocks_synchronizer::ledger_blocks_sync::{
     LedgerBlocksSynchronizer, LedgerBlocksSynchronizerMetrics,
 };
+use ic_ledger_canister_blocks_synchronizer::ledger_type::LedgerType;
 use ic_nns_governance::pb::v1::{manage_neuron::NeuronIdOrSubaccount, GovernanceError, NeuronInfo};
 use ic_types::messages::{HttpCallContent, MessageId};
 use ic_types::CanisterId;
 use ic_types::{crypto::threshold_sig::ThresholdSigPublicKey, messages::SignedRequestBytes};
-use icp_ledger::{BlockIndex, Symbol, TransferFee, TransferFeeArgs, DEFAULT_TRANSFER_FEE};
+use icp_ledger::{BlockIndex, Symbol, Tokens, TransferFee, TransferFeeArgs, DEFAULT_TRANSFER_FEE};
+use num_traits::ToPrimitive;
 use on_wire::{FromWire, IntoWire};
 
 use crate::convert;
@@ -42,11 +45,12 @@ use crate::errors::{ApiError, Details, ICError};
 use crate::ledger_client::neuron_response::NeuronResponse;
 use crate::ledger_client::{
     handle_add_hotkey::handle_add_hotkey, handle_disburse::handle_disburse,
-    handle_follow::handle_follow, handle_merge_maturity::handle_merge_maturity,
-    handle_neuron_info::handle_neuron_info, handle_remove_hotkey::handle_remove_hotkey,
-    handle_send::handle_send, handle_set_dissolve_timestamp::handle_set_dissolve_timestamp,
-    handle_spawn::handle_spawn, handle_stake::handle_stake,
-    handle_start_dissolve::handle_start_dissolve, handle_stop_dissolve::handle_stop_dissolve,
+    handle_follow::handle_follow, handle_icrc1_transfer::handle_icrc1_transfer,
+    handle_merge_maturity::handle_merge_maturity, handle_neuron_info::handle_neuron_info,
+    handle_remove_hotkey::handle_remove_hotkey, handle_send::handle_send,
+    handle_set_dissolve_timestamp::handle_set_dissolve_timestamp, handle_spawn::handle_spawn,
+    handle_stake::handle_stake, handle_start_dissolve::handle_start_dissolve,
+    handle_stop_dissolve::handle_stop_dissolve,
 };
 use crate::models::{EnvelopePair, Object, SignedTransaction};
 use crate::request::request_result::RequestResult;
@@ -79,6 +83,7 @@ pub trait LedgerAccess {
     fn ledger_canister_id(&self) -> &CanisterId;
     fn governance_canister_id(&self) -> &CanisterId;
     fn token_symbol(&self) -> &str;
+    fn ledger_type(&self) -> LedgerType;
     async fn submit(&self, _envelopes: SignedTransaction) -> Result<TransactionResults, ApiError>;
     async fn cleanup(&self);
     async fn neuron_info(
@@ -96,6 +101,7 @@ pub struct LedgerClient {
     canister_access: Option<Arc<CanisterAccess>>,
     ic_url: Url,
     token_symbol: String,
+    ledger_type: LedgerType,
     offline: bool,
 }
 
@@ -111,6 +117,7 @@ impl LedgerClient {
         ic_url: Url,
         canister_id: CanisterId,
         token_symbol: String,
+        ledger_type: LedgerType,
         governance_canister_id: CanisterId,
         store_location: Option<&std::path::Path>,
         store_max_blocks: Option<u64>,
@@ -120,7 +127,7 @@ impl LedgerClient {
         let canister_access = if offline {
             None
         } else {
-            let canister_access = CanisterAccess::new(ic_url.clone(), canister_id);
+            let canister_access = CanisterAccess::new(ic_url.clone(), canister_id, ledger_type);
             LedgerClient::check_ledger_symbol(&token_symbol, &canister_access).await?;
             Some(Arc::new(canister_access))
         };
@@ -130,6 +137,7 @@ impl LedgerClient {
         });
         let ledger_blocks_synchronizer = LedgerBlocksSynchronizer::new(
             canister_access.clone(),
+            ledger_type,
             store_location,
             store_max_blocks,
             verification_info,
@@ -141,6 +149,7 @@ impl LedgerClient {
             ledger_blocks_synchronizer,
             canister_id,
             token_symbol,
+            ledger_type,
             governance_canister_id,
             canister_access,
             ic_url,
@@ -152,23 +161,31 @@ impl LedgerClient {
         token_symbol: &str,
         canister_access: &CanisterAccess,
     ) -> Result<(), ApiError> {
-        let arg = CandidOne(())
-            .into_bytes()
-            .map_err(|e| ApiError::internal_error(format!("Serialization failed: {:?}", e)))?;
-
-        let symbol_res: Result<Symbol, String> = canister_access
-            .agent
-            .execute_query(&canister_access.canister_id, "symbol", arg)
-            .await
-            .and_then(|bytes| {
-                CandidOne::from_bytes(
-                    bytes.ok_or_else(|| "symbol reply payload was empty".to_string())?,
-                )
-                .map(|c| c.0)
-            });
+        let symbol_res: Result<String, String> = match canister_access.ledger_type {
+            LedgerType::Icp => {
+                let arg = CandidOne(()).into_bytes().map_err(|e| {
+                    ApiError::internal_error(format!("Serialization failed: {:?}", e))
+                })?;
+                canister_access
+                    .agent
+                    .execute_query(&canister_access.canister_id, "symbol", arg)
+                    .await
+                    .and_then(|bytes| {
+                        CandidOne::from_bytes(
+                            bytes.ok_or_else(|| "symbol reply payload was empty".to_string())?,
+                        )
+                        .map(|c: CandidOne<Symbol>| c.0.symbol)
+                    })
+            }
+            LedgerType::Icrc1 => {
+                canister_access
+                    .query_candid(canister_access.canister_id, "icrc1_symbol", ())
+                    .await
+            }
+        };
 
         match symbol_res {
-            Ok(Symbol { symbol }) => {
+            Ok(symbol) => {
                 if symbol != token_symbol {
                     return Err(ApiError::internal_error(format!(
                         "The ledger serves a different token ({}) than specified ({})",
@@ -219,6 +236,10 @@ impl LedgerAccess for LedgerClient {
         &self.token_symbol
     }
 
+    fn ledger_type(&self) -> LedgerType {
+        self.ledger_type
+    }
+
     async fn submit(&self, envelopes: SignedTransaction) -> Result<TransactionResults, ApiError> {
         if self.offline {
             return Err(ApiError::NotAvailableOffline(false, Details::default()));
@@ -333,6 +354,21 @@ impl LedgerAccess for LedgerClient {
     }
 
     async fn transfer_fee(&self) -> Result<TransferFee, ApiError> {
+        if self.ledger_type == LedgerType::Icrc1 {
+            let canister_access = self.canister_access.as_ref().unwrap();
+            let fee: candid::Nat = canister_access
+                .query_candid(self.canister_id, "icrc1_fee", ())
+                .await
+                .map_err(|e| {
+                    ApiError::internal_error(format!("Error querying icrc1_fee: {}", e))
+                })?;
+            let fee = fee.0.to_u64().ok_or_else(|| {
+                ApiError::internal_error(format!("The ledger fee {} does not fit into u64", fee))
+            })?;
+            return Ok(TransferFee {
+                transfer_fee: Tokens::from_e8s(fee),
+            });
+        }
         let agent = &self.canister_access.as_ref().unwrap().agent;
         let arg = CandidOne(TransferFeeArgs {})
             .into_bytes()
@@ -659,7 +695,10 @@ impl LedgerClient {
             RequestType::MergeMaturity { .. } => handle_merge_maturity(bytes),
             RequestType::NeuronInfo { .. } => handle_neuron_info(bytes),
             RequestType::RemoveHotKey { .. } => handle_remove_hotkey(bytes),
-            RequestType::Send => handle_send(bytes),
+            RequestType::Send => match self.ledger_type {
+                LedgerType::Icp => handle_send(bytes),
+                LedgerType::Icrc1 => handle_icrc1_transfer(bytes),
+            },
             RequestType::SetDissolveTimestamp { .. } => handle_set_dissolve_timestamp(bytes),
             RequestType::Spawn { .. } => handle_spawn(bytes),
             RequestType::Stake { .. } => handle_stake(bytes),
@@ -0,0 +1,29 @@
+use crate::errors::ApiError;
+use crate::ledger_client::OperationOutput;
+use candid::Nat;
+use ic_icrc1::endpoints::TransferError;
+use num_traits::ToPrimitive;
+
+pub fn handle_icrc1_transfer(
+    bytes: Vec<u8>,
+) -> Result<Result<Option<OperationOutput>, ApiError>, String> {
+    let res: Result<Nat, TransferError> = candid::decode_one(&bytes).map_err(|err| {
+        format!(
+            "While parsing the reply of the icrc1_transfer call: {}",
+            err
+        )
+    })?;
+    match res {
+        Ok(block_index) => {
+            let block_index = block_index
+                .0
+                .to_u64()
+                .ok_or_else(|| format!("Block index {} does not fit into u64", block_index))?;
+            Ok(Ok(Some(OperationOutput::BlockIndex(block_index))))
+        }
+        Err(err) => Ok(Err(ApiError::TransactionRejected(
+            false,
+            format!("Could not perform the transfer: {:?}", err).into(),
+        ))),
+    }
+}
@@ -1,6 +1,7 @@
 use clap::Parser;
 use ic_crypto_internal_threshold_sig_bls12381 as bls12_381;
 use ic_crypto_utils_threshold_sig_der::parse_threshold_sig_key;
+use ic_ledger_canister_blocks_synchronizer::ledger_type::LedgerType;
 use ic_rosetta_api::request_handler::RosettaRequestHandler;
 use ic_rosetta_api::rosetta_server::{RosettaApiServer, RosettaApiServerOpt};
 use ic_rosetta_api::{ledger_client, DEFAULT_BLOCKCHAIN, DEFAULT_TOKEN_SYMBOL};
@@ -20,6 +21,9 @@ struct Opt {
     ic_canister_id: Option<String>,
     #[clap(short = 't', long = "token-sybol")]
     token_symbol: Option<String>,
+    /// Supported options: icp, icrc1. ICRC-1 ledgers require --canister-id.
+    #[clap(long = "ledger-type", default_value = "icp")]
+    ledger_type: LedgerType,
     /// Id of the governance canister to use for neuron management.
     #[clap(short = 'g', long = "governance-canister-id")]
     governance_canister_id: Option<String>,
@@ -74,6 +78,12 @@ async fn main() -> std::io::Result<()> {
     log::info!("Listening on {}:{}", opt.listen_address, opt.listen_port);
     let addr = format!("{}:{}", opt.listen_address, opt.listen_port);
 
+    if opt.ledger_type == LedgerType::Icrc1 && opt.ic_canister_id.is_none() {
+        log::error!("The canister id of the ICRC-1 ledger must be set with --canister-id.");
+        panic!("Missing ICRC-1 ledger canister id");
+    }
+    log::info!("Ledger type set to {}", opt.ledger_type);
+
     let (root_key, canister_id, governance_canister_id, url) = if opt.mainnet {
         let root_key = match opt.root_key {
             Some(root_key_path) => parse_threshold_sig_key(root_key_path.as_path())?,
@@ -154,6 +164,7 @@ async fn main() -> std::io::Result<()> {
     };
 
     let Opt {
+        ledger_type,
         store_max_blocks,
         offline,
         exit_on_sync,
@@ -167,6 +178,7 @@ async fn main() -> std::io::Result<()> {
         url,
         canister_id,
         token_symbol,
+        ledger_type,
         governance_canister_id,
         store_location,
         store_max_blocks,
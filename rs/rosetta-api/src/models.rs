@@ -811,6 +811,11 @@ pub struct ConstructionPayloadsRequestMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memo: Option<u64>,
 
+    /// The memo to use for an ICRC-1 ledger transfer, up to 32 bytes.
+    /// Takes precedence over `memo` on ICRC-1 ledgers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icrc1_memo: Option<Vec<u8>>,
+
     /// The earliest acceptable expiry date for a ledger transfer.
     /// Must be withing 24 hours from created_at_time.
     /// Represents number of nanoseconds since UNIX epoch.
@@ -9,14 +9,13 @@ mod construction_submit;
 
 use crate::{convert, models, API_VERSION, NODE_VERSION};
 use ic_ledger_canister_blocks_synchronizer::blocks::Blocks;
-use ic_ledger_canister_blocks_synchronizer::store::HashedBlock;
-use ic_ledger_core::block::BlockType;
+use ic_ledger_canister_blocks_synchronizer::store::{HashedBlock, TransactionQuery};
 use ic_nns_common::pb::v1::NeuronId;
 use ic_nns_governance::pb::v1::manage_neuron::NeuronIdOrSubaccount;
 use ic_types::crypto::DOMAIN_IC_REQUEST;
 use ic_types::messages::MessageId;
 use ic_types::CanisterId;
-use icp_ledger::{Block, BlockIndex};
+use icp_ledger::BlockIndex;
 use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
 use strum::IntoEnumIterator;
@@ -25,6 +24,7 @@ use crate::convert::{from_model_account_identifier, neuron_account_from_public_k
 use crate::errors::ApiError;
 use crate::ledger_client::LedgerAccess;
 use crate::models::amount::tokens_to_amount;
+use crate::models::operation::OperationType;
 use crate::models::{
     AccountBalanceRequest, AccountBalanceResponse, Allow, BalanceAccountType, BlockIdentifier,
     BlockResponse, BlockTransaction, BlockTransactionResponse, Error, MempoolResponse,
@@ -33,6 +33,7 @@ use crate::models::{
     OperationStatus, Operator, PartialBlockIdentifier, SearchTransactionsResponse, SyncStatus,
     Version,
 };
+use crate::request_types::STATUS_COMPLETED;
 
 /// The maximum amount of blocks to retrieve in a single search.
 const MAX_SEARCH_LIMIT: usize = 10_000;
@@ -135,8 +136,7 @@ impl RosettaRequestHandler {
             None
         };
 
-        let account_id = icp_ledger::AccountIdentifier::from_hex(&msg.account_identifier.address)
-            .map_err(|e| {
+        let account_id = from_model_account_identifier(&msg.account_identifier).map_err(|e| {
             ApiError::invalid_account_id(format!(
                 "Account {} is not valid address, {}",
                 &msg.account_identifier.address, e,
@@ -161,12 +161,16 @@ impl RosettaRequestHandler {
 
         let blocks = self.ledger.read_blocks().await;
         let hb = get_block(&blocks, Some(msg.block_identifier))?;
-        let block = Block::decode(hb.block.clone())
-            .map_err(|err| ApiError::internal_error(format!("Cannot decode block: {}", err)))?;
+        let block = blocks
+            .ledger_type()
+            .decode_block(hb.block.clone())
+            .map_err(|err| ApiError::internal_error(format!("Cannot decode block: {}", err)))?
+            .block;
         let b_id = convert::block_id(&hb)?;
         let parent_id = create_parent_block_id(&blocks, &hb)?;
 
         let transactions = vec![convert::block_to_transaction(
+            blocks.ledger_type(),
             &hb,
             self.ledger.token_symbol(),
         )?];
@@ -195,7 +199,8 @@ impl RosettaRequestHandler {
             hash: Some(msg.block_identifier.hash),
         });
         let hb = get_block(&blocks, b_id)?;
-        let transaction = convert::block_to_transaction(&hb, self.ledger.token_symbol())?;
+        let transaction =
+            convert::block_to_transaction(blocks.ledger_type(), &hb, self.ledger.token_symbol())?;
         Ok(BlockTransactionResponse::new(transaction))
     }
 
@@ -293,7 +298,13 @@ impl RosettaRequestHandler {
         let tip = blocks.get_latest_verified_hashed_block()?;
         let tip_id = convert::block_id(&tip)?;
         let tip_timestamp = models::timestamp::from_system_time(
-            Block::decode(tip.block).unwrap().timestamp.into(),
+            blocks
+                .ledger_type()
+                .decode_block(tip.block)
+                .map_err(|err| ApiError::internal_error(format!("Cannot decode block: {}", err)))?
+                .block
+                .timestamp
+                .into(),
         )?;
 
         let genesis_block = blocks.block_store.get_hashed_block(&0)?;
@@ -379,7 +390,11 @@ impl RosettaRequestHandler {
         for hb in block_range.into_iter().rev() {
             txs.push(BlockTransaction::new(
                 convert::block_id(&hb)?,
-                convert::block_to_transaction(&hb, self.ledger.token_symbol())?,
+                convert::block_to_transaction(
+                    blocks.ledger_type(),
+                    &hb,
+                    self.ledger.token_symbol(),
+                )?,
             ));
         }
 
@@ -415,22 +430,6 @@ impl RosettaRequestHandler {
             return Err(ApiError::invalid_request("currency not supported"));
         }
 
-        if msg.status.is_some() {
-            return Err(ApiError::invalid_request("status not supported"));
-        }
-
-        if msg._type.is_some() {
-            return Err(ApiError::invalid_request("type not supported"));
-        }
-
-        if msg.address.is_some() {
-            return Err(ApiError::invalid_request("address not supported"));
-        }
-
-        if msg.success.is_some() {
-            return Err(ApiError::invalid_request("success not supported"));
-        }
-
         let max_block = match msg.max_block {
             Some(x) => Some(
                 u64::try_from(x)
@@ -452,78 +451,104 @@ impl RosettaRequestHandler {
         };
         let limit = std::cmp::min(limit, MAX_SEARCH_LIMIT);
 
-        if msg.transaction_identifier.is_none() && msg.account_identifier.is_none() {
-            return self.get_blocks_range(max_block, offset, limit).await;
-        }
-
-        let blocks = self.ledger.read_blocks().await;
-
-        let last_idx = blocks.get_latest_verified_hashed_block()?.index;
+        let transaction_hash = match &msg.transaction_identifier {
+            Some(tid) => Some(
+                ic_ledger_core::block::HashOf::try_from(tid)
+                    .map_err(|e| ApiError::InvalidTransactionId(false, e.into()))?,
+            ),
+            None => None,
+        };
 
-        let mut heights = Vec::new();
-        let mut total_count = 0;
+        let account = match &msg.account_identifier {
+            Some(aid) => Some(
+                from_model_account_identifier(aid)
+                    .map_err(|e| ApiError::InvalidAccountId(false, e.into()))?,
+            ),
+            None => None,
+        };
+        let address = match &msg.address {
+            Some(address) => Some(
+                from_model_account_identifier(&models::AccountIdentifier::new(address.clone()))
+                    .map_err(|e| ApiError::InvalidAccountId(false, e.into()))?,
+            ),
+            None => None,
+        };
 
-        if let Some(tid) = &msg.transaction_identifier {
-            if msg.account_identifier.is_some() {
-                return Err(ApiError::invalid_request(
-                    "Only one of transaction_identitier and account_identifier should be populated",
-                ));
+        // The blocks only contain ledger operations, which all completed
+        // successfully. Neuron management operations are never in blocks.
+        let mut matches_nothing =
+            msg.status.iter().any(|s| s != STATUS_COMPLETED) || msg.success == Some(false);
+        let operation_type = match &msg._type {
+            Some(t) => match OperationType::iter().find(|o| o.to_string() == *t) {
+                Some(OperationType::Transaction) | Some(OperationType::Fee) => Some("Transfer"),
+                Some(OperationType::Mint) => Some("Mint"),
+                Some(OperationType::Burn) => Some("Burn"),
+                Some(_) => {
+                    matches_nothing = true;
+                    None
+                }
+                None => {
+                    return Err(ApiError::invalid_request(format!(
+                        "Unknown operation type: {}",
+                        t
+                    )))
+                }
+            },
+            None => None,
+        };
+        let account = match (account, address) {
+            (Some(account), Some(address)) if account != address => {
+                matches_nothing = true;
+                None
             }
+            (account, address) => account.or(address),
+        };
 
-            let tid = ic_ledger_core::block::HashOf::try_from(tid)
-                .map_err(|e| ApiError::InvalidTransactionId(false, e.into()))?;
-
-            if let Ok(i) = blocks.block_store.get_block_idx_by_transaction_hash(&tid) {
-                heights.push(i);
-                total_count += 1;
-            }
+        if matches_nothing {
+            return Ok(SearchTransactionsResponse::new(vec![], 0, None));
         }
 
-        let mut next_offset = None;
-
-        if let Some(aid) = &msg.account_identifier {
-            let acc = from_model_account_identifier(aid)
-                .map_err(|e| ApiError::InvalidAccountId(false, e.into()))?;
-
-            let hist = blocks.balance_book.store.get_history(&acc, max_block);
-            heights = hist
-                .iter()
-                .rev()
-                .map(|(h, _)| *h)
-                .filter(|h| *h <= last_idx)
-                .skip(offset)
-                .collect();
-
-            let cnt = offset
-                .checked_add(heights.len())
-                .ok_or_else(|| ApiError::internal_error("total count overflow"))?;
-            total_count = i64::try_from(cnt).map_err(|e| {
-                ApiError::internal_error(format!("Total count does not fit in i64: {}", e))
-            })?;
-
-            if heights.len() > limit {
-                let next = offset
-                    .checked_add(limit)
-                    .ok_or_else(|| ApiError::internal_error("offset + limit overflow"))?;
-                next_offset = Some(i64::try_from(next).map_err(|e| {
-                    ApiError::internal_error(format!("Next offset cannot fit in i64: {}", e))
-                })?);
-            }
-            heights.truncate(limit);
+        if transaction_hash.is_none() && account.is_none() && operation_type.is_none() {
+            return self.get_blocks_range(max_block, offset, limit).await;
         }
 
+        let blocks = self.ledger.read_blocks().await;
+
+        let (heights, total_count) = blocks.block_store.search_transactions(&TransactionQuery {
+            transaction_hash,
+            account,
+            operation_type: operation_type.map(String::from),
+            max_block,
+            offset,
+            limit,
+        })?;
+
+        let next = offset
+            .checked_add(limit)
+            .ok_or_else(|| ApiError::internal_error("offset + limit overflow"))?;
+        let next_offset = if total_count > next {
+            Some(i64::try_from(next).map_err(|e| {
+                ApiError::internal_error(format!("Next offset cannot fit in i64: {}", e))
+            })?)
+        } else {
+            None
+        };
+        let total_count = i64::try_from(total_count).map_err(|e| {
+            ApiError::internal_error(format!("Total count does not fit in i64: {}", e))
+        })?;
+
         let mut txs: Vec<BlockTransaction> = Vec::new();
 
         for i in heights {
-            if blocks.is_verified_by_idx(&i)? {
-                let hb = blocks.block_store.get_hashed_block(&i)?;
-                txs.push(BlockTransaction::new(
-                    convert::block_id(&hb)?,
-                    convert::block_to_transaction(&hb, self.ledger.token_symbol())?,
-                ));
-            } else {
-                return Err(ApiError::InvalidBlockId(true, Default::default()));
-            }
+            let hb = blocks.block_store.get_hashed_block(&i)?;
+            txs.push(BlockTransaction::new(
+                convert::block_id(&hb)?,
+                convert::block_to_transaction(
+                    blocks.ledger_type(),
+                    &hb,
+                    self.ledger.token_symbol(),
+                )?,
+            ));
         }
 
         Ok(SearchTransactionsResponse::new(
@@ -1,9 +1,13 @@
-use crate::convert::{account_from_public_key, neuron_account_from_public_key};
+use crate::convert::{
+    account_from_public_key, icrc1_account_to_model_account_identifier,
+    neuron_account_from_public_key, principal_id_from_public_key,
+};
 use crate::errors::ApiError;
 use crate::models::{
     self, AccountType, ConstructionDeriveRequestMetadata, ConstructionDeriveResponse,
 };
 use crate::request_handler::{verify_network_id, RosettaRequestHandler};
+use ic_ledger_canister_blocks_synchronizer::ledger_type::LedgerType;
 
 impl RosettaRequestHandler {
     /// Derive an AccountIdentifier from a PublicKey.
@@ -23,6 +27,11 @@ impl RosettaRequestHandler {
                 &msg.public_key,
                 neuron_index,
             )?,
+            _ if self.ledger.ledger_type() == LedgerType::Icrc1 => {
+                icrc1_account_to_model_account_identifier(&ic_icrc1::Account::from(
+                    principal_id_from_public_key(&msg.public_key)?,
+                ))
+            }
             _ => account_from_public_key(&msg.public_key)?,
         });
 
@@ -1,4 +1,6 @@
-use crate::convert::{self, from_arg, to_model_account_identifier};
+use crate::convert::{
+    self, from_arg, icrc1_account_to_model_account_identifier, to_model_account_identifier,
+};
 use crate::errors::ApiError;
 use crate::models::{ConstructionParseRequest, ConstructionParseResponse, ParsedTransaction};
 use crate::request_handler::{verify_network_id, RosettaRequestHandler};
@@ -14,9 +16,13 @@ use ic_nns_governance::pb::v1::{
 
 use crate::models::seconds::Seconds;
 use crate::request::Request;
+use candid::Nat;
+use ic_icrc1::endpoints::TransferArg;
 use ic_types::messages::{Blob, HttpCallContent, HttpCanisterUpdate};
 use ic_types::PrincipalId;
-use icp_ledger::{AccountIdentifier, Operation, SendArgs};
+use icp_ledger::{AccountIdentifier, Operation, SendArgs, Tokens};
+use num_traits::ToPrimitive;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 
 impl RosettaRequestHandler {
@@ -42,16 +48,29 @@ impl RosettaRequestHandler {
 
         let mut requests = vec![];
         let mut from_ai = vec![];
+        let mut icrc1_accounts = HashMap::new();
 
-        for (request_type, HttpCanisterUpdate { arg, sender, .. }) in updates {
-            let from = PrincipalId::try_from(sender.0)
-                .map_err(|e| ApiError::internal_error(e.to_string()))?
-                .into();
+        for (
+            request_type,
+            HttpCanisterUpdate {
+                arg,
+                sender,
+                method_name,
+                ..
+            },
+        ) in updates
+        {
+            let sender = PrincipalId::try_from(sender.0)
+                .map_err(|e| ApiError::internal_error(e.to_string()))?;
+            let from = sender.into();
             if msg.signed {
                 from_ai.push(from);
             }
 
             match request_type {
+                RequestType::Send if method_name == "icrc1_transfer" => {
+                    icrc1_send(&mut requests, &mut icrc1_accounts, arg, sender)?
+                }
                 RequestType::Send => send(&mut requests, arg, from)?,
                 RequestType::Stake { neuron_index } => {
                     stake(&mut requests, arg, from, neuron_index)?
@@ -93,10 +112,20 @@ impl RosettaRequestHandler {
 
         from_ai.sort();
         from_ai.dedup();
-        let from_ai = from_ai.iter().map(to_model_account_identifier).collect();
+        let from_ai = from_ai
+            .iter()
+            .map(|aid| match icrc1_accounts.get(aid) {
+                Some(account) => icrc1_account_to_model_account_identifier(account),
+                None => to_model_account_identifier(aid),
+            })
+            .collect();
+
+        let mut operations =
+            Request::requests_to_operations(&requests, self.ledger.token_symbol())?;
+        convert::set_icrc1_operation_accounts(&mut operations, &icrc1_accounts);
 
         Ok(ConstructionParseResponse {
-            operations: Request::requests_to_operations(&requests, self.ledger.token_symbol())?,
+            operations,
             signers: None,
             account_identifier_signers: Some(from_ai),
             metadata: None,
@@ -118,6 +147,50 @@ fn send(requests: &mut Vec<Request>, arg: Blob, from: AccountIdentifier) -> Resu
     Ok(())
 }
 
+/// Handle an ICRC-1 SEND.
+fn icrc1_send(
+    requests: &mut Vec<Request>,
+    icrc1_accounts: &mut HashMap<AccountIdentifier, ic_icrc1::Account>,
+    arg: Blob,
+    sender: PrincipalId,
+) -> Result<(), ApiError> {
+    let TransferArg {
+        from_subaccount,
+        to,
+        fee,
+        amount,
+        ..
+    } = candid::decode_one(arg.0.as_ref()).map_err(|e| {
+        ApiError::internal_error(format!(
+            "Could not decode ICRC-1 transfer argument: {:?}",
+            e
+        ))
+    })?;
+    let tokens = |n: Nat| {
+        n.0.to_u64()
+            .map(Tokens::from_e8s)
+            .ok_or_else(|| ApiError::internal_error(format!("Amount {} does not fit into u64", n)))
+    };
+    let fee = fee.ok_or_else(|| {
+        ApiError::internal_error("ICRC-1 transfers constructed by Rosetta must set a fee")
+    })?;
+    let from = ic_icrc1::Account {
+        owner: sender,
+        subaccount: from_subaccount,
+    };
+    // The signer is the owner of the source account.
+    icrc1_accounts.insert(sender.into(), ic_icrc1::Account::from(sender));
+    icrc1_accounts.insert(from.clone().into(), from.clone());
+    icrc1_accounts.insert(to.clone().into(), to.clone());
+    requests.push(Request::Transfer(Operation::Transfer {
+        from: from.into(),
+        to: to.into(),
+        amount: tokens(amount)?,
+        fee: tokens(fee)?,
+    }));
+    Ok(())
+}
+
 /// Handle STAKE.
 fn stake(
     requests: &mut Vec<Request>,
@@ -1,4 +1,7 @@
+use candid::Nat;
 use dfn_candid::CandidOne;
+use ic_icrc1::endpoints::TransferArg;
+use ic_ledger_canister_blocks_synchronizer::ledger_type::LedgerType;
 use ic_nns_common::pb::v1::NeuronId;
 use ic_types::messages::{Blob, HttpCanisterUpdate, MessageId};
 use ic_types::PrincipalId;
@@ -6,6 +9,7 @@ use icp_ledger::{Memo, Operation, SendArgs, Tokens};
 use on_wire::IntoWire;
 use rand::Rng;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -14,7 +18,10 @@ use ic_nns_governance::pb::v1::{
     ClaimOrRefreshNeuronFromAccount, ManageNeuron,
 };
 
-use crate::convert::{make_read_state_from_update, to_arg, to_model_account_identifier};
+use crate::convert::{
+    icrc1_account_to_model_account_identifier, make_read_state_from_update, to_arg,
+    to_model_account_identifier,
+};
 use crate::errors::ApiError;
 use crate::ledger_client::LedgerAccess;
 use crate::models::{
@@ -76,6 +83,13 @@ impl RosettaRequestHandler {
             .map(Memo)
             .unwrap_or_else(|| Memo(rand::thread_rng().gen()));
 
+        let icrc1_memo = match meta.and_then(|meta| meta.icrc1_memo.clone()) {
+            Some(bytes) => ic_icrc1::Memo::try_from(bytes)
+                .map_err(|e| ApiError::invalid_request(format!("Invalid ICRC-1 memo: {}", e)))?,
+            None => ic_icrc1::Memo::from(memo.0),
+        };
+        let icrc1_accounts = convert::icrc1_accounts_from_operations(&ops);
+
         let mut ingress_expiries = vec![];
         let mut now = ingress_start;
         while now < ingress_end {
@@ -100,6 +114,19 @@ impl RosettaRequestHandler {
 
         for t in transactions {
             match t {
+                Request::Transfer(req) if self.ledger.ledger_type() == LedgerType::Icrc1 => {
+                    handle_icrc1_transfer(
+                        req,
+                        icrc1_memo.clone(),
+                        created_at_time,
+                        &icrc1_accounts,
+                        &self.ledger,
+                        &mut payloads,
+                        &mut updates,
+                        &pks_map,
+                        &ingress_expiries,
+                    )?
+                }
                 Request::Transfer(req) => handle_transfer(
                     req,
                     memo,
@@ -291,6 +318,87 @@ fn handle_transfer_operation(
     Ok(())
 }
 
+/// Handle TRANSFER on an ICRC-1 ledger.
+#[allow(clippy::too_many_arguments)]
+fn handle_icrc1_transfer(
+    req: Operation,
+    memo: ic_icrc1::Memo,
+    created_at_time: ic_ledger_core::timestamp::TimeStamp,
+    icrc1_accounts: &HashMap<icp_ledger::AccountIdentifier, ic_icrc1::Account>,
+    ledger: &Arc<dyn LedgerAccess + Send + Sync>,
+    payloads: &mut Vec<SigningPayload>,
+    updates: &mut Vec<(RequestType, HttpCanisterUpdate)>,
+    pks_map: &HashMap<icp_ledger::AccountIdentifier, &PublicKey>,
+    ingress_expiries: &[u64],
+) -> Result<(), ApiError> {
+    let (from, to, amount, fee) = match req {
+        Operation::Burn { .. } => {
+            return Err(ApiError::invalid_request(
+                "Burn operations are not supported through Rosetta.",
+            ))
+        }
+        Operation::Mint { .. } => {
+            return Err(ApiError::invalid_request(
+                "Mint operations are not supported through Rosetta.",
+            ))
+        }
+        Operation::Transfer {
+            from,
+            to,
+            amount,
+            fee,
+        } => (from, to, amount, fee),
+    };
+    let icrc1_account = |account: icp_ledger::AccountIdentifier| {
+        icrc1_accounts.get(&account).cloned().ok_or_else(|| {
+            ApiError::invalid_request(format!(
+                "Account {} must be specified with a principal and subaccount",
+                account
+            ))
+        })
+    };
+    let from = icrc1_account(from)?;
+    let to = icrc1_account(to)?;
+
+    let pk = pks_map
+        .get(&icp_ledger::AccountIdentifier::from(from.owner))
+        .ok_or_else(|| {
+            ApiError::internal_error(format!(
+                "Cannot find public key for principal {}",
+                from.owner
+            ))
+        })?;
+
+    let transfer_arg = TransferArg {
+        from_subaccount: from.subaccount,
+        to,
+        fee: Some(Nat::from(fee.get_e8s())),
+        created_at_time: Some(created_at_time.as_nanos_since_unix_epoch()),
+        memo: Some(memo),
+        amount: Nat::from(amount.get_e8s()),
+    };
+
+    let update = HttpCanisterUpdate {
+        canister_id: Blob(ledger.ledger_canister_id().get().to_vec()),
+        method_name: "icrc1_transfer".to_string(),
+        arg: Blob(candid::encode_one(transfer_arg).expect("Serialization failed")),
+        // As for ICP transfers, the created_at_time deduplicates identical
+        // transactions so no nonce is needed.
+        nonce: None,
+        sender: Blob(convert::principal_id_from_public_key(pk)?.into_vec()),
+        ingress_expiry: 0,
+    };
+
+    add_payloads(
+        payloads,
+        ingress_expiries,
+        &icrc1_account_to_model_account_identifier(&ic_icrc1::Account::from(from.owner)),
+        &update,
+    );
+    updates.push((RequestType::Send, update));
+    Ok(())
+}
+
 /// Handle NEURON_INFO.
 fn handle_neuron_info(
     req: NeuronInfo,
@@ -1,4 +1,6 @@
-use crate::convert::{self, to_model_account_identifier};
+use crate::convert::{
+    self, icrc1_account_to_model_account_identifier, to_model_account_identifier,
+};
 use crate::errors::ApiError;
 use crate::models::{
     ConstructionMetadataRequestOptions, ConstructionPreprocessRequest,
@@ -10,6 +12,7 @@ use crate::request_types::{
     AddHotKey, Disburse, Follow, MergeMaturity, NeuronInfo, RemoveHotKey, SetDissolveTimestamp,
     Spawn, Stake, StartDissolve, StopDissolve,
 };
+use ic_ledger_canister_blocks_synchronizer::ledger_type::LedgerType;
 use icp_ledger::Operation;
 use std::collections::HashSet;
 
@@ -33,10 +36,36 @@ impl RosettaRequestHandler {
         let required_public_keys: Result<HashSet<icp_ledger::AccountIdentifier>, ApiError> =
             transfers.into_iter().map(required_public_key).collect();
 
-        let required_public_keys: Vec<_> = required_public_keys?
-            .into_iter()
-            .map(|x| to_model_account_identifier(&x))
-            .collect();
+        let required_public_keys: Vec<_> = match self.ledger.ledger_type() {
+            LedgerType::Icp => required_public_keys?
+                .into_iter()
+                .map(|x| to_model_account_identifier(&x))
+                .collect(),
+            LedgerType::Icrc1 => {
+                let accounts = convert::icrc1_accounts_from_operations(&msg.operations);
+                // The signer of an ICRC-1 transfer is the owner of the
+                // source account, whatever its subaccount.
+                required_public_keys?
+                    .into_iter()
+                    .map(|x| {
+                        accounts
+                            .get(&x)
+                            .map(|account| account.owner)
+                            .ok_or_else(|| {
+                                ApiError::invalid_request(format!(
+                                    "Account {} must be specified with a principal and subaccount",
+                                    x
+                                ))
+                            })
+                    })
+                    .collect::<Result<HashSet<_>, ApiError>>()?
+                    .into_iter()
+                    .map(|owner| {
+                        icrc1_account_to_model_account_identifier(&ic_icrc1::Account::from(owner))
+                    })
+                    .collect()
+            }
+        };
 
         Ok(ConstructionPreprocessResponse {
             required_public_keys: Some(required_public_keys),
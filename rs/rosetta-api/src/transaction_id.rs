@@ -1,12 +1,17 @@
 use std::{convert::TryFrom, str::FromStr};
 
+use candid::Nat;
+use ic_icrc1::endpoints::TransferArg;
 use ic_ledger_canister_core::ledger::LedgerTransaction;
 use ic_ledger_core::block::HashOf;
+use ic_ledger_core::timestamp::TimeStamp;
+use ic_ledger_core::Tokens;
 use ic_types::{
     messages::{HttpCallContent, HttpRequestEnvelope},
     PrincipalId,
 };
 use icp_ledger::{SendArgs, Transaction};
+use num_traits::ToPrimitive;
 use serde::{Deserialize, Serialize};
 
 use crate::{convert, errors::ApiError, request_types::RequestType};
@@ -44,6 +49,12 @@ impl TransactionIdentifier {
         match request_type {
             RequestType::Send => {
                 let HttpCallContent::Call { update } = &signed_transaction.content;
+                if update.method_name == "icrc1_transfer" {
+                    return Self::try_from_icrc1_transfer(
+                        update.sender.clone().0,
+                        update.arg.clone().0,
+                    );
+                }
                 let from = PrincipalId::try_from(update.sender.clone().0)
                     .map_err(|e| ApiError::internal_error(e.to_string()))?;
                 let SendArgs {
@@ -84,6 +95,56 @@ impl TransactionIdentifier {
     }
 }
 
+impl TransactionIdentifier {
+    /// Computes the hash of the transaction an ICRC-1 ledger records for an
+    /// `icrc1_transfer` call with the given sender and argument.
+    fn try_from_icrc1_transfer(
+        sender: Vec<u8>,
+        arg: Vec<u8>,
+    ) -> Result<TransactionIdentifier, ApiError> {
+        let owner =
+            PrincipalId::try_from(sender).map_err(|e| ApiError::internal_error(e.to_string()))?;
+        let TransferArg {
+            from_subaccount,
+            to,
+            fee,
+            created_at_time,
+            memo,
+            amount,
+        } = candid::decode_one(&arg).map_err(|e| {
+            ApiError::internal_error(format!("Could not decode ICRC-1 transfer argument: {}", e))
+        })?;
+        let tokens = |n: Nat| {
+            n.0.to_u64().map(Tokens::from_e8s).ok_or_else(|| {
+                ApiError::internal_error(format!("Amount {} does not fit into u64", n))
+            })
+        };
+        let fee = fee.ok_or_else(|| ApiError::internal_error(
+            "A transaction ID cannot be generated from a constructed ICRC-1 transfer without an explicit 'fee'"
+        ))?;
+        let created_at_time = created_at_time.ok_or_else(|| ApiError::internal_error(
+            "A transaction ID cannot be generated from a constructed transaction without an explicit 'created_at_time'"
+        ))?;
+        let from = ic_icrc1::Account {
+            owner,
+            subaccount: from_subaccount,
+        };
+        let hash = ic_icrc1::Transaction::transfer(
+            from,
+            to,
+            tokens(amount)?,
+            tokens(fee)?,
+            Some(TimeStamp::from_nanos_since_unix_epoch(created_at_time)),
+            memo,
+        )
+        .hash();
+
+        Ok(TransactionIdentifier::from(&HashOf::<Transaction>::new(
+            hash.into_bytes(),
+        )))
+    }
+}
+
 impl From<&Transaction> for TransactionIdentifier {
     fn from(tx: &Transaction) -> Self {
         TransactionIdentifier::from(&tx.hash())
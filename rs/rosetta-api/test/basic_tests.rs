@@ -353,6 +353,188 @@ async fn blocks_test() {
     assert_eq!(resp.next_offset, Some(50));
 }
 
+#[actix_rt::test]
+async fn search_transactions_filters_test() {
+    init_test_logger();
+
+    let ledger = Arc::new(TestLedger::new());
+    let req_handler = RosettaRequestHandler::new_with_default_blockchain(ledger.clone());
+    let mut scribe = Scribe::new();
+    let num_accounts = 10;
+
+    scribe.gen_accounts(num_accounts, 1_000_000);
+    for _i in 0..50 {
+        scribe.gen_transaction();
+    }
+
+    for b in &scribe.blockchain {
+        ledger.add_block(b.clone()).await.ok();
+    }
+
+    let mut mints = vec![];
+    let mut transfers = vec![];
+    for hb in scribe.blockchain.iter().rev() {
+        match Block::decode(hb.block.clone())
+            .unwrap()
+            .transaction
+            .operation
+        {
+            icp_ledger::Operation::Mint { .. } => mints.push(hb.index),
+            icp_ledger::Operation::Transfer { .. } => transfers.push(hb.index),
+            icp_ledger::Operation::Burn { .. } => (),
+        }
+    }
+    let indices = |resp: &SearchTransactionsResponse| -> Vec<BlockIndex> {
+        resp.transactions
+            .iter()
+            .map(|t| t.block_identifier.index as BlockIndex)
+            .collect()
+    };
+
+    let mut req = SearchTransactionsRequest::new(req_handler.network_id(), None, None);
+    req._type = Some("MINT".to_string());
+    let resp = req_handler.search_transactions(req.clone()).await.unwrap();
+    assert_eq!(indices(&resp), mints);
+    assert_eq!(resp.total_count, mints.len() as i64);
+
+    req._type = Some("FEE".to_string());
+    req.limit = Some(5);
+    req.offset = Some(2);
+    let resp = req_handler.search_transactions(req.clone()).await.unwrap();
+    assert_eq!(indices(&resp), transfers[2..7].to_vec());
+    assert_eq!(resp.total_count, transfers.len() as i64);
+    assert_eq!(resp.next_offset, Some(7));
+
+    req._type = Some("STAKE".to_string());
+    let resp = req_handler.search_transactions(req.clone()).await.unwrap();
+    assert!(resp.transactions.is_empty());
+    assert_eq!(resp.total_count, 0);
+
+    req._type = Some("NOT_AN_OPERATION".to_string());
+    assert!(req_handler.search_transactions(req.clone()).await.is_err());
+
+    // The address filter is equivalent to the account identifier filter.
+    let acc = acc_id(0);
+    let mut req = SearchTransactionsRequest::new(
+        req_handler.network_id(),
+        None,
+        Some(to_model_account_identifier(&acc)),
+    );
+    let by_account = req_handler.search_transactions(req.clone()).await.unwrap();
+    req.account_identifier = None;
+    req.address = Some(acc.to_hex());
+    let by_address = req_handler.search_transactions(req.clone()).await.unwrap();
+    assert_eq!(by_account, by_address);
+    assert!(!by_address.transactions.is_empty());
+
+    // The first blocks mint the initial balance of each account, in order.
+    req.max_block = Some(num_accounts as i64 - 1);
+    req.status = Some("COMPLETED".to_string());
+    req.success = Some(true);
+    let resp = req_handler.search_transactions(req.clone()).await.unwrap();
+    assert_eq!(indices(&resp), vec![0]);
+
+    req.success = Some(false);
+    let resp = req_handler.search_transactions(req.clone()).await.unwrap();
+    assert!(resp.transactions.is_empty());
+}
+
+#[actix_rt::test]
+async fn account_balance_at_block_test() {
+    init_test_logger();
+
+    let ledger = Arc::new(TestLedger::new());
+    let req_handler = RosettaRequestHandler::new_with_default_blockchain(ledger.clone());
+    let mut scribe = Scribe::new();
+    let num_transactions: usize = 100;
+    let num_accounts = 10;
+
+    scribe.gen_accounts(num_accounts, 1_000_000);
+    for _i in 0..num_transactions {
+        scribe.gen_transaction();
+    }
+
+    for b in &scribe.blockchain {
+        ledger.add_block(b.clone()).await.ok();
+    }
+
+    for h in [num_accounts as usize - 1, num_accounts as usize + 17, 80] {
+        let hb = &scribe.blockchain[h];
+        for i in 0..num_accounts {
+            let acc = acc_id(i);
+            let expected = Ok(AccountBalanceResponse::new(
+                block_id(hb).unwrap(),
+                vec![tokens_to_amount(
+                    *scribe.balance_history[h].get(&acc).unwrap(),
+                    DEFAULT_TOKEN_SYMBOL,
+                )
+                .unwrap()],
+            ));
+
+            // fetch by index
+            let by_index = PartialBlockIdentifier {
+                index: Some(h as i64),
+                hash: None,
+            };
+            assert_eq!(balance_at(&req_handler, acc, by_index).await, expected);
+
+            // fetch by hash
+            let by_hash = PartialBlockIdentifier {
+                index: None,
+                hash: Some(from_hash(&hb.hash)),
+            };
+            assert_eq!(balance_at(&req_handler, acc, by_hash).await, expected);
+
+            // fetch by both
+            let by_both = PartialBlockIdentifier {
+                index: Some(h as i64),
+                hash: Some(from_hash(&hb.hash)),
+            };
+            assert_eq!(balance_at(&req_handler, acc, by_both).await, expected);
+        }
+    }
+
+    let acc = acc_id(0);
+    let unknown_index = PartialBlockIdentifier {
+        index: Some(scribe.blockchain.len() as i64),
+        hash: None,
+    };
+    assert_eq!(
+        balance_at(&req_handler, acc, unknown_index).await,
+        Err(ApiError::InvalidBlockId(true, Default::default()))
+    );
+
+    // The hash of a block that is not in the chain.
+    let unknown_hash = PartialBlockIdentifier {
+        index: None,
+        hash: Some("00".repeat(32)),
+    };
+    assert_eq!(
+        balance_at(&req_handler, acc, unknown_hash).await,
+        Err(ApiError::InvalidBlockId(true, Default::default()))
+    );
+
+    let mismatched = PartialBlockIdentifier {
+        index: Some(1),
+        hash: Some(from_hash(&scribe.blockchain[2].hash)),
+    };
+    assert_eq!(
+        balance_at(&req_handler, acc, mismatched).await,
+        Err(ApiError::InvalidBlockId(false, Default::default()))
+    );
+}
+
+async fn balance_at(
+    req_handler: &RosettaRequestHandler,
+    acc: AccountIdentifier,
+    block_id: PartialBlockIdentifier,
+) -> Result<AccountBalanceResponse, ApiError> {
+    let mut msg =
+        AccountBalanceRequest::new(req_handler.network_id(), to_model_account_identifier(&acc));
+    msg.block_identifier = Some(block_id);
+    req_handler.account_balance(msg).await
+}
+
 #[actix_rt::test]
 async fn balances_test() {
     init_test_logger();
@@ -601,7 +783,7 @@ async fn load_from_store_test() {
     let location = tmpdir.path();
     let scribe = Scribe::new_with_sample_data(10, 150);
 
-    let mut blocks = Blocks::new_persistent(location);
+    let mut blocks = Blocks::new_persistent(location, LedgerType::Icp);
     let mut last_verified = 0;
     for hb in &scribe.blockchain {
         blocks.push(hb.clone()).unwrap();
@@ -627,7 +809,7 @@ async fn load_from_store_test() {
 
     drop(req_handler);
 
-    let mut blocks = Blocks::new_persistent(location);
+    let mut blocks = Blocks::new_persistent(location, LedgerType::Icp);
     blocks.load_from_store().unwrap();
 
     assert!(blocks.is_verified_by_idx(&10).unwrap());
@@ -644,7 +826,7 @@ async fn load_from_store_test() {
 
     drop(blocks);
 
-    let mut blocks = Blocks::new_persistent(location);
+    let mut blocks = Blocks::new_persistent(location, LedgerType::Icp);
     blocks.load_from_store().unwrap();
 
     verify_balances(&scribe, &blocks, 0);
@@ -665,7 +847,7 @@ async fn load_from_store_test() {
 
     drop(req_handler);
 
-    let mut blocks = Blocks::new_persistent(location);
+    let mut blocks = Blocks::new_persistent(location, LedgerType::Icp);
     blocks.load_from_store().unwrap();
 
     verify_balances(&scribe, &blocks, 10);
@@ -701,7 +883,7 @@ async fn load_unverified_test() {
     let location = tmpdir.path();
     let scribe = Scribe::new_with_sample_data(10, 150);
 
-    let mut blocks = Blocks::new_persistent(location);
+    let mut blocks = Blocks::new_persistent(location, LedgerType::Icp);
     for hb in &scribe.blockchain {
         blocks.push(hb.clone()).unwrap();
         if hb.index < 20 {
@@ -721,7 +903,7 @@ async fn load_unverified_test() {
 
     drop(blocks);
 
-    let mut blocks = Blocks::new_persistent(location);
+    let mut blocks = Blocks::new_persistent(location, LedgerType::Icp);
     blocks.load_from_store().unwrap();
     let last_verified = (scribe.blockchain.len() - 1) as u64;
     blocks
@@ -746,7 +928,7 @@ async fn store_batch_test() {
     let location = tmpdir.path();
     let scribe = Scribe::new_with_sample_data(10, 150);
 
-    let mut blocks = Blocks::new_persistent(location);
+    let mut blocks = Blocks::new_persistent(location, LedgerType::Icp);
     for hb in &scribe.blockchain {
         if hb.index < 21 {
             blocks.push(hb.clone()).unwrap();
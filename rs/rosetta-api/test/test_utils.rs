@@ -18,6 +18,7 @@ use tokio::sync::RwLock;
 
 use async_trait::async_trait;
 use ic_ledger_canister_blocks_synchronizer::blocks::Blocks;
+use ic_ledger_canister_blocks_synchronizer::ledger_type::LedgerType;
 use ic_ledger_canister_blocks_synchronizer::store::HashedBlock;
 
 use ic_rosetta_api::convert::{from_arg, to_model_account_identifier};
@@ -56,7 +57,7 @@ pub struct TestLedger {
 impl TestLedger {
     pub fn new() -> Self {
         Self {
-            blockchain: RwLock::new(Blocks::new_in_memory()),
+            blockchain: RwLock::new(Blocks::new_in_memory(LedgerType::Icp)),
             canister_id: CanisterId::new(
                 PrincipalId::from_str("5v3p4-iyaaa-aaaaa-qaaaa-cai").unwrap(),
             )
@@ -128,6 +129,10 @@ impl LedgerAccess for TestLedger {
         DEFAULT_TOKEN_SYMBOL
     }
 
+    fn ledger_type(&self) -> LedgerType {
+        LedgerType::Icp
+    }
+
     async fn sync_blocks(&self, _stopped: Arc<AtomicBool>) -> Result<(), ApiError> {
         let mut queue = self.submit_queue.write().await;
 
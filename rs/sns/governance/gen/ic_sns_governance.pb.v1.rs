@@ -347,6 +347,131 @@ pub struct ManageSnsMetadata {
     ::prost::Message,
 )]
 pub struct UpgradeSnsToNextVersion {}
+/// A proposal function to transfer funds from one of the SNS treasuries, which
+/// are accounts owned by the SNS governance canister, to a target account.
+///
+/// The amount of a single transfer, and the total amount transferred from a
+/// treasury over any 7 days, are limited relative to the treasury's balance.
+/// See `TRANSFER_SNS_TREASURY_FUNDS_*` in src/governance.rs.
+#[derive(
+    candid::CandidType,
+    candid::Deserialize,
+    comparable::Comparable,
+    Clone,
+    PartialEq,
+    ::prost::Message,
+)]
+pub struct TransferSnsTreasuryFunds {
+    #[prost(enumeration = "transfer_sns_treasury_funds::TransferFrom", tag = "1")]
+    pub from_treasury: i32,
+    /// The amount to transfer, in e8s. The transaction fee is paid by the
+    /// treasury, on top of this amount.
+    #[prost(uint64, tag = "2")]
+    pub amount_e8s: u64,
+    /// An optional memo to use for the transfer.
+    #[prost(uint64, optional, tag = "3")]
+    pub memo: ::core::option::Option<u64>,
+    /// The principal to transfer the funds to.
+    #[prost(message, optional, tag = "4")]
+    pub to_principal: ::core::option::Option<::ic_base_types::PrincipalId>,
+    /// An (optional) subaccount of the principal to transfer the funds to. If
+    /// not set, the principal's default subaccount is used.
+    #[prost(message, optional, tag = "5")]
+    pub to_subaccount: ::core::option::Option<Subaccount>,
+}
+/// Nested message and enum types in `TransferSnsTreasuryFunds`.
+pub mod transfer_sns_treasury_funds {
+    /// The treasury from which the funds are transferred.
+    #[derive(
+        candid::CandidType,
+        candid::Deserialize,
+        comparable::Comparable,
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration,
+    )]
+    #[repr(i32)]
+    pub enum TransferFrom {
+        Unspecified = 0,
+        /// The ICP treasury, i.e. the main account of the governance canister on
+        /// the NNS (ICP) ledger, where the ICP raised by the swap is sent.
+        IcpTreasury = 1,
+        /// The SNS token treasury, i.e. the treasury subaccount of the governance
+        /// canister on the SNS ledger.
+        SnsTokenTreasury = 2,
+    }
+    impl TransferFrom {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                TransferFrom::Unspecified => "TRANSFER_FROM_UNSPECIFIED",
+                TransferFrom::IcpTreasury => "TRANSFER_FROM_ICP_TREASURY",
+                TransferFrom::SnsTokenTreasury => "TRANSFER_FROM_SNS_TOKEN_TREASURY",
+            }
+        }
+    }
+}
+/// A proposal function to mint new SNS tokens to a target account. The tokens
+/// are minted from the minting account of the SNS ledger, which is the main
+/// account of the SNS governance canister.
+#[derive(
+    candid::CandidType,
+    candid::Deserialize,
+    comparable::Comparable,
+    Clone,
+    PartialEq,
+    ::prost::Message,
+)]
+pub struct MintSnsTokens {
+    /// The amount to mint, in e8s.
+    #[prost(uint64, tag = "1")]
+    pub amount_e8s: u64,
+    /// An optional memo to use for the minting transaction.
+    #[prost(uint64, optional, tag = "2")]
+    pub memo: ::core::option::Option<u64>,
+    /// The principal to mint the tokens to.
+    #[prost(message, optional, tag = "3")]
+    pub to_principal: ::core::option::Option<::ic_base_types::PrincipalId>,
+    /// An (optional) subaccount of the principal to mint the tokens to. If not
+    /// set, the principal's default subaccount is used.
+    #[prost(message, optional, tag = "4")]
+    pub to_subaccount: ::core::option::Option<Subaccount>,
+}
+/// A proposal function to change the parameters of the SNS ledger. The ledger
+/// is upgraded, to its currently deployed version, with the new parameters.
+/// Fields with None values will remain unchanged.
+#[derive(
+    candid::CandidType,
+    candid::Deserialize,
+    comparable::Comparable,
+    Clone,
+    PartialEq,
+    ::prost::Message,
+)]
+pub struct ManageLedgerParameters {
+    /// The new transfer fee of the ledger, in e8s.
+    #[prost(uint64, optional, tag = "1")]
+    pub transfer_fee: ::core::option::Option<u64>,
+    /// The new name of the token, must be between 4 and 255 characters.
+    #[prost(string, optional, tag = "2")]
+    pub token_name: ::core::option::Option<::prost::alloc::string::String>,
+    /// The new symbol of the token, must be between 3 and 10 characters.
+    #[prost(string, optional, tag = "3")]
+    pub token_symbol: ::core::option::Option<::prost::alloc::string::String>,
+    /// The new logo of the token, in the same format as SnsMetadata.logo. It is
+    /// stored in the ledger's metadata under the `icrc1:logo` key.
+    #[prost(string, optional, tag = "4")]
+    pub token_logo: ::core::option::Option<::prost::alloc::string::String>,
+}
 /// A proposal is the immutable input of a proposal submission.
 #[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]
 #[compare_default]
@@ -373,7 +498,7 @@ pub struct Proposal {
     ///
     /// See `impl From<&Action> for u64` in src/types.rs for the implementation
     /// of this mapping.
-    #[prost(oneof = "proposal::Action", tags = "4, 5, 6, 7, 8, 9, 10, 11, 12")]
+    #[prost(oneof = "proposal::Action", tags = "4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15")]
     pub action: ::core::option::Option<proposal::Action>,
 }
 /// Nested message and enum types in `Proposal`.
@@ -446,6 +571,21 @@ pub mod proposal {
         /// Id = 8
         #[prost(message, tag = "12")]
         ManageSnsMetadata(super::ManageSnsMetadata),
+        /// Transfer funds from one of the SNS treasuries to a target account.
+        ///
+        /// Id = 9.
+        #[prost(message, tag = "13")]
+        TransferSnsTreasuryFunds(super::TransferSnsTreasuryFunds),
+        /// Mint new SNS tokens to a target account.
+        ///
+        /// Id = 10.
+        #[prost(message, tag = "14")]
+        MintSnsTokens(super::MintSnsTokens),
+        /// Change the parameters of the SNS ledger.
+        ///
+        /// Id = 11.
+        #[prost(message, tag = "15")]
+        ManageLedgerParameters(super::ManageLedgerParameters),
     }
 }
 #[derive(candid::CandidType, candid::Deserialize, comparable::Comparable)]
@@ -706,6 +846,17 @@ pub struct ProposalData {
     /// parameters can be changed without affecting existing proposals.
     #[prost(uint64, tag = "18")]
     pub wait_for_quiet_deadline_increase_seconds: u64,
+    /// The minimum proportion of the total voting power, in basis points, that
+    /// must vote yes for the proposal to be adopted. If not set, the proposal
+    /// only needs MIN_NUMBER_VOTES_FOR_PROPOSAL_RATIO of the total voting power.
+    #[prost(uint64, optional, tag = "19")]
+    pub minimum_yes_proportion_of_total_basis_points: ::core::option::Option<u64>,
+    /// The minimum proportion of the exercised voting power (i.e. the voting
+    /// power of the yes and no votes), in basis points, that must vote yes for
+    /// the proposal to be adopted. The proposal needs strictly more yes votes
+    /// than this proportion. If not set, a simple majority (50%) is used.
+    #[prost(uint64, optional, tag = "20")]
+    pub minimum_yes_proportion_of_exercised_basis_points: ::core::option::Option<u64>,
 }
 /// The nervous system's parameters, which are parameters that can be changed, via proposals,
 /// by each nervous system community.
@@ -30,14 +30,15 @@ use crate::pb::v1::{
     GetProposalResponse, GetSnsInitializationParametersRequest,
     GetSnsInitializationParametersResponse, Governance as GovernanceProto, GovernanceError,
     ListNervousSystemFunctionsResponse, ListNeurons, ListNeuronsResponse, ListProposals,
-    ListProposalsResponse, ManageNeuron, ManageNeuronResponse, ManageSnsMetadata,
-    NervousSystemParameters, Neuron, NeuronId, NeuronPermission, NeuronPermissionList,
-    NeuronPermissionType, Proposal, ProposalData, ProposalDecisionStatus, ProposalId,
-    ProposalRewardStatus, RewardEvent, Tally, UpgradeSnsControlledCanister,
-    UpgradeSnsToNextVersion, Vote,
+    ListProposalsResponse, ManageLedgerParameters, ManageNeuron, ManageNeuronResponse,
+    ManageSnsMetadata, MintSnsTokens, NervousSystemParameters, Neuron, NeuronId, NeuronPermission,
+    NeuronPermissionList, NeuronPermissionType, Proposal, ProposalData, ProposalDecisionStatus,
+    ProposalId, ProposalRewardStatus, RewardEvent, Tally, TransferSnsTreasuryFunds,
+    UpgradeSnsControlledCanister, UpgradeSnsToNextVersion, Vote,
 };
 use ic_base_types::PrincipalId;
-use ic_icrc1::{Account, Subaccount};
+use ic_icrc1::{endpoints::Value, Account, Subaccount};
+use ic_icrc1_ledger::UpgradeArgs as LedgerUpgradeArgs;
 use ic_ledger_core::Tokens;
 use ic_nervous_system_common::i2d;
 use lazy_static::lazy_static;
@@ -58,6 +59,7 @@ use crate::pb::v1::{
     manage_neuron::{AddNeuronPermissions, RemoveNeuronPermissions},
     manage_neuron_response::{DisburseMaturityResponse, MergeMaturityResponse},
     proposal::Action,
+    transfer_sns_treasury_funds::TransferFrom,
     ExecuteGenericNervousSystemFunction, NervousSystemFunction, WaitForQuietState,
 };
 use crate::proposal::{
@@ -67,14 +69,18 @@ use crate::proposal::{
 
 use crate::pb::v1::governance::{SnsMetadata, UpgradeInProgress, Version};
 use crate::sns_upgrade::{
-    get_all_sns_canisters, get_running_version, get_upgrade_params, get_wasm, UpgradeSnsParams,
+    get_all_sns_canisters, get_running_version, get_upgrade_params, get_wasm, SnsCanisterType,
+    UpgradeSnsParams,
 };
-use crate::types::{is_registered_function_id, Environment, HeapGrowthPotential, LedgerUpdateLock};
-use candid::Encode;
+use crate::types::{
+    is_registered_function_id, Environment, HeapGrowthPotential, LedgerUpdateLock, ONE_DAY_SECONDS,
+};
+use candid::{Decode, Encode};
 use dfn_core::api::{id, spawn, CanisterId};
 use ic_nervous_system_common::{ledger, NervousSystemError};
 use ic_nervous_system_root::ChangeCanisterProposal;
 use ic_nns_constants::LEDGER_CANISTER_ID as NNS_LEDGER_CANISTER_ID;
+use icp_ledger::DEFAULT_TRANSFER_FEE as NNS_DEFAULT_TRANSFER_FEE;
 
 lazy_static! {
     pub static ref NERVOUS_SYSTEM_FUNCTION_DELETION_MARKER: NervousSystemFunction =
@@ -90,6 +96,30 @@ lazy_static! {
 /// this limit, the payload will not be returned in the reply.
 pub const EXECUTE_NERVOUS_SYSTEM_FUNCTION_PAYLOAD_LISTING_BYTES_MAX: usize = 1000; // 1 KB
 
+/// The nonce used to compute the subaccount of the SNS token treasury, i.e. the
+/// subaccount of the governance canister on the SNS ledger that holds the
+/// treasury's SNS tokens.
+pub const TREASURY_SUBACCOUNT_NONCE: u64 = 0;
+
+/// The maximum amount that a single TransferSnsTreasuryFunds proposal can transfer,
+/// in basis points of the treasury's balance.
+pub const TRANSFER_SNS_TREASURY_FUNDS_MAX_BASIS_POINTS_PER_PROPOSAL: u64 = 2_500;
+
+/// The maximum total amount that TransferSnsTreasuryFunds proposals can transfer
+/// from a treasury within TRANSFER_SNS_TREASURY_FUNDS_WINDOW_SECONDS, in basis
+/// points of the treasury's balance at the beginning of that window, i.e. of its
+/// current balance plus the amount transferred from it within the window.
+pub const TRANSFER_SNS_TREASURY_FUNDS_MAX_BASIS_POINTS_PER_WINDOW: u64 = 5_000;
+
+/// The duration of the rolling window over which the total amount transferred
+/// from a treasury by TransferSnsTreasuryFunds proposals is limited.
+pub const TRANSFER_SNS_TREASURY_FUNDS_WINDOW_SECONDS: u64 = 7 * ONE_DAY_SECONDS;
+
+/// For this long after a ManageLedgerParameters proposal that changes the
+/// transfer fee is executed, governance checks whether the ledger has been
+/// upgraded to the new fee, and takes over the ledger's fee once it has.
+pub const SYNC_TRANSACTION_FEE_WITH_LEDGER_WINDOW_SECONDS: u64 = ONE_DAY_SECONDS;
+
 const MAX_HEAP_SIZE_IN_KIB: usize = 4 * 1024 * 1024;
 const WASM32_PAGE_SIZE_IN_KIB: usize = 64;
 
@@ -534,6 +564,10 @@ pub struct Governance {
     /// Implementation of the interface with the SNS ledger canister.
     ledger: Box<dyn Ledger>,
 
+    /// Implementation of the interface with the NNS (ICP) ledger canister, which
+    /// holds the ICP treasury.
+    nns_ledger: Box<dyn Ledger>,
+
     /// Cached data structure that (for each proposal function_id) maps a followee to
     /// the set of its followers. It is the inverse of the mapping from follower
     /// to followees that is stored in each (follower) neuron.
@@ -584,11 +618,17 @@ pub fn neuron_account_id(subaccount: Subaccount) -> Account {
     }
 }
 
+/// Returns the given number of basis points of `amount_e8s`, rounded down.
+fn basis_points_of(amount_e8s: u64, basis_points: u64) -> u64 {
+    (amount_e8s as u128 * basis_points as u128 / 10_000) as u64
+}
+
 impl Governance {
     pub fn new(
         proto: ValidGovernanceProto,
         env: Box<dyn Environment>,
         ledger: Box<dyn Ledger>,
+        nns_ledger: Box<dyn Ledger>,
     ) -> Self {
         let mut proto = proto.into_inner();
 
@@ -620,6 +660,7 @@ impl Governance {
             proto,
             env,
             ledger,
+            nns_ledger,
             function_followee_index: BTreeMap::new(),
             principal_to_neuron_ids_index: BTreeMap::new(),
             closest_proposal_deadline_timestamp_seconds: 0,
@@ -1852,6 +1893,17 @@ impl Governance {
             proposal::Action::ManageSnsMetadata(manage_sns_metadata) => {
                 self.perform_manage_sns_metadata(manage_sns_metadata)
             }
+            proposal::Action::TransferSnsTreasuryFunds(transfer) => {
+                self.perform_transfer_sns_treasury_funds(proposal_id, &transfer)
+                    .await
+            }
+            proposal::Action::MintSnsTokens(mint) => {
+                self.perform_mint_sns_tokens(proposal_id, &mint).await
+            }
+            proposal::Action::ManageLedgerParameters(manage_ledger_parameters) => {
+                self.perform_manage_ledger_parameters(proposal_id, manage_ledger_parameters)
+                    .await
+            }
             // This should not be possible, because Proposal validation is performed when
             // a proposal is first made.
             proposal::Action::Unspecified(_) => Err(GovernanceError::new_with_message(
@@ -1990,6 +2042,270 @@ impl Governance {
         Ok(())
     }
 
+    /// Transfers funds from an SNS treasury as a result of an adopted
+    /// TransferSnsTreasuryFunds proposal.
+    ///
+    /// The limits on the amount are checked again, as the treasury's balance
+    /// and the transfers made from it may have changed since the proposal was made.
+    async fn perform_transfer_sns_treasury_funds(
+        &self,
+        proposal_id: u64,
+        transfer: &TransferSnsTreasuryFunds,
+    ) -> Result<(), GovernanceError> {
+        self.check_transfer_sns_treasury_funds_limits(transfer, Some(proposal_id))
+            .await?;
+
+        let to_account = transfer
+            .to_account()
+            .map_err(|e| GovernanceError::new_with_message(ErrorType::InvalidProposal, e))?;
+        let (ledger, treasury_account, fee_e8s) =
+            self.treasury_ledger_account_and_fee(transfer.from_treasury)?;
+        let block_height = ledger
+            .transfer_funds(
+                transfer.amount_e8s,
+                fee_e8s,
+                treasury_account.subaccount,
+                to_account.clone(),
+                transfer.memo.unwrap_or(0),
+            )
+            .await?;
+
+        println!(
+            "{}Transferred {} e8s from the {:?} treasury to {:?} at block height {}.",
+            log_prefix(),
+            transfer.amount_e8s,
+            transfer.from_treasury(),
+            to_account,
+            block_height
+        );
+        Ok(())
+    }
+
+    /// Returns the ledger that holds the given treasury, the treasury's account on
+    /// that ledger, and the ledger's transaction fee.
+    fn treasury_ledger_account_and_fee(
+        &self,
+        from_treasury: i32,
+    ) -> Result<(&dyn Ledger, Account, u64), GovernanceError> {
+        let governance_id = self.env.canister_id().get();
+        match TransferFrom::from_i32(from_treasury) {
+            Some(TransferFrom::IcpTreasury) => Ok((
+                &*self.nns_ledger,
+                Account {
+                    owner: governance_id,
+                    subaccount: None,
+                },
+                NNS_DEFAULT_TRANSFER_FEE.get_e8s(),
+            )),
+            Some(TransferFrom::SnsTokenTreasury) => Ok((
+                &*self.ledger,
+                Account {
+                    owner: governance_id,
+                    subaccount: Some(ledger::compute_distribution_subaccount_bytes(
+                        governance_id,
+                        TREASURY_SUBACCOUNT_NONCE,
+                    )),
+                },
+                self.transaction_fee_e8s(),
+            )),
+            _ => Err(GovernanceError::new_with_message(
+                ErrorType::InvalidProposal,
+                format!("Unknown treasury to transfer funds from: {}", from_treasury),
+            )),
+        }
+    }
+
+    /// Returns the total amount, in e8s, transferred from the given treasury by
+    /// TransferSnsTreasuryFunds proposals that were executed within the last
+    /// TRANSFER_SNS_TREASURY_FUNDS_WINDOW_SECONDS, or that were adopted and whose
+    /// execution is still in progress. The proposal `excluded_proposal_id`, if
+    /// any, is not taken into account.
+    fn treasury_amount_transferred_in_window_e8s(
+        &self,
+        from_treasury: i32,
+        excluded_proposal_id: Option<u64>,
+    ) -> u64 {
+        let window_start_seconds = self
+            .env
+            .now()
+            .saturating_sub(TRANSFER_SNS_TREASURY_FUNDS_WINDOW_SECONDS);
+        self.proto
+            .proposals
+            .iter()
+            .filter(|(id, _)| Some(**id) != excluded_proposal_id)
+            .filter_map(|(_, data)| {
+                let amount_e8s = match data.proposal.as_ref()?.action.as_ref()? {
+                    Action::TransferSnsTreasuryFunds(transfer)
+                        if transfer.from_treasury == from_treasury =>
+                    {
+                        transfer.amount_e8s
+                    }
+                    _ => return None,
+                };
+                match data.status() {
+                    ProposalDecisionStatus::Adopted => Some(amount_e8s),
+                    ProposalDecisionStatus::Executed
+                        if data.executed_timestamp_seconds >= window_start_seconds =>
+                    {
+                        Some(amount_e8s)
+                    }
+                    _ => None,
+                }
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Checks that the amount of the given transfer is within the limits relative
+    /// to the balance of the treasury it is made from, i.e. that
+    /// - it is at most TRANSFER_SNS_TREASURY_FUNDS_MAX_BASIS_POINTS_PER_PROPOSAL
+    ///   of the treasury's balance, and
+    /// - together with the amount transferred from the treasury within the last
+    ///   TRANSFER_SNS_TREASURY_FUNDS_WINDOW_SECONDS (see
+    ///   `treasury_amount_transferred_in_window_e8s`), it is at most
+    ///   TRANSFER_SNS_TREASURY_FUNDS_MAX_BASIS_POINTS_PER_WINDOW of the treasury's
+    ///   balance at the beginning of that window.
+    async fn check_transfer_sns_treasury_funds_limits(
+        &self,
+        transfer: &TransferSnsTreasuryFunds,
+        proposal_id: Option<u64>,
+    ) -> Result<(), GovernanceError> {
+        let (ledger, treasury_account, _) =
+            self.treasury_ledger_account_and_fee(transfer.from_treasury)?;
+        let balance_e8s = ledger.account_balance(treasury_account).await?.get_e8s();
+
+        let max_per_proposal_e8s = basis_points_of(
+            balance_e8s,
+            TRANSFER_SNS_TREASURY_FUNDS_MAX_BASIS_POINTS_PER_PROPOSAL,
+        );
+        if transfer.amount_e8s > max_per_proposal_e8s {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                format!(
+                    "The amount to transfer, {} e8s, exceeds the maximum amount of a \
+                     single transfer from the {:?} treasury, {} e8s ({} basis points \
+                     of its balance of {} e8s).",
+                    transfer.amount_e8s,
+                    transfer.from_treasury(),
+                    max_per_proposal_e8s,
+                    TRANSFER_SNS_TREASURY_FUNDS_MAX_BASIS_POINTS_PER_PROPOSAL,
+                    balance_e8s,
+                ),
+            ));
+        }
+
+        let transferred_e8s =
+            self.treasury_amount_transferred_in_window_e8s(transfer.from_treasury, proposal_id);
+        let max_per_window_e8s = basis_points_of(
+            balance_e8s.saturating_add(transferred_e8s),
+            TRANSFER_SNS_TREASURY_FUNDS_MAX_BASIS_POINTS_PER_WINDOW,
+        );
+        if transferred_e8s.saturating_add(transfer.amount_e8s) > max_per_window_e8s {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                format!(
+                    "The amount to transfer, {} e8s, added to the {} e8s already transferred \
+                     from the {:?} treasury in the last {} seconds, exceeds the maximum of \
+                     {} e8s that can be transferred in that period.",
+                    transfer.amount_e8s,
+                    transferred_e8s,
+                    transfer.from_treasury(),
+                    TRANSFER_SNS_TREASURY_FUNDS_WINDOW_SECONDS,
+                    max_per_window_e8s,
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Mints SNS tokens as a result of an adopted MintSnsTokens proposal. The
+    /// governance canister's main account is the minting account of the SNS ledger.
+    async fn perform_mint_sns_tokens(
+        &self,
+        proposal_id: u64,
+        mint: &MintSnsTokens,
+    ) -> Result<(), GovernanceError> {
+        let to_account = mint
+            .to_account()
+            .map_err(|e| GovernanceError::new_with_message(ErrorType::InvalidProposal, e))?;
+        let block_height = self
+            .ledger
+            .transfer_funds(
+                mint.amount_e8s,
+                0,    // Minting transfers don't pay a fee.
+                None, // This is a minting transfer, no 'from' account is needed
+                to_account.clone(),
+                mint.memo.unwrap_or(0),
+            )
+            .await?;
+
+        println!(
+            "{}Minted {} e8s to {:?} at block height {} (proposal {}).",
+            log_prefix(),
+            mint.amount_e8s,
+            to_account,
+            block_height,
+            proposal_id
+        );
+        Ok(())
+    }
+
+    /// Changes the parameters of the SNS ledger as a result of an adopted
+    /// ManageLedgerParameters proposal, by upgrading the ledger, via root, to its
+    /// currently deployed version with the new parameters as upgrade argument.
+    async fn perform_manage_ledger_parameters(
+        &mut self,
+        proposal_id: u64,
+        manage_ledger_parameters: ManageLedgerParameters,
+    ) -> Result<(), GovernanceError> {
+        err_if_another_upgrade_is_in_progress(&self.proto.proposals, proposal_id)?;
+
+        let ledger_canister_id = self.proto.ledger_canister_id_or_panic();
+        let ledger_wasm_hash = self.proto.deployed_version_or_panic().ledger_wasm_hash;
+        let ledger_wasm = get_wasm(&*self.env, ledger_wasm_hash, SnsCanisterType::Ledger)
+            .await
+            .map_err(|e| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!("Could not execute proposal: {}", e),
+                )
+            })?
+            .wasm;
+
+        let ManageLedgerParameters {
+            transfer_fee,
+            token_name,
+            token_symbol,
+            token_logo,
+        } = manage_ledger_parameters;
+        let upgrade_args = LedgerUpgradeArgs {
+            metadata: token_logo.map(|token_logo| {
+                vec![Value::entry(
+                    ManageLedgerParameters::TOKEN_LOGO_METADATA_KEY,
+                    token_logo,
+                )]
+            }),
+            token_name,
+            token_symbol,
+            transfer_fee,
+            change_fee_collector: None,
+        };
+        let arg = Encode!(&Some(upgrade_args)).expect("Could not encode ledger upgrade args");
+
+        // Root only acknowledges the upgrade here and performs it afterwards.
+        // Therefore, transaction_fee_e8s is only updated once the ledger
+        // reports the new fee (see sync_transaction_fee_with_ledger).
+        self.upgrade_non_root_canister(ledger_canister_id, ledger_wasm, arg)
+            .await?;
+
+        println!(
+            "{}Upgraded the SNS ledger with new parameters (proposal {}).",
+            log_prefix(),
+            proposal_id
+        );
+        Ok(())
+    }
+
     /// Executes a (non-native) nervous system function as a result of an adopted proposal.
     async fn perform_execute_generic_nervous_system_function(
         &self,
@@ -2102,14 +2418,17 @@ impl Governance {
             ));
         }
 
-        self.upgrade_non_root_canister(target_canister_id, upgrade.new_canister_wasm)
+        self.upgrade_non_root_canister(target_canister_id, upgrade.new_canister_wasm, vec![])
             .await
     }
 
+    /// Upgrades the given (non-root) SNS canister, via root, to the given wasm,
+    /// passing it the given (candid encoded) upgrade argument.
     async fn upgrade_non_root_canister(
         &mut self,
         target_canister_id: CanisterId,
         wasm: Vec<u8>,
+        arg: Vec<u8>,
     ) -> Result<(), GovernanceError> {
         // Serialize upgrade.
         let payload = {
@@ -2127,7 +2446,8 @@ impl Governance {
 
             let change_canister_arg =
                 ChangeCanisterProposal::new(stop_before_installing, mode, target_canister_id)
-                    .with_wasm(wasm);
+                    .with_wasm(wasm)
+                    .with_arg(arg);
 
             candid::Encode!(&change_canister_arg).unwrap()
         };
@@ -2188,7 +2508,7 @@ impl Governance {
             upgrade_canister_directly(&*self.env, root_canister_id, target_wasm).await?;
         } else {
             for target_canister_id in canister_ids_to_upgrade {
-                self.upgrade_non_root_canister(target_canister_id, target_wasm.clone())
+                self.upgrade_non_root_canister(target_canister_id, target_wasm.clone(), vec![])
                     .await?;
             }
         }
@@ -2277,9 +2597,17 @@ impl Governance {
         }
 
         let reserved_canisters = self.reserved_canister_targets();
-        validate_and_render_proposal(proposal, &*self.env, &self.proto, reserved_canisters)
-            .await
-            .map_err(|e| GovernanceError::new_with_message(ErrorType::InvalidProposal, e))
+        let rendering =
+            validate_and_render_proposal(proposal, &*self.env, &self.proto, reserved_canisters)
+                .await
+                .map_err(|e| GovernanceError::new_with_message(ErrorType::InvalidProposal, e))?;
+
+        if let Some(Action::TransferSnsTreasuryFunds(transfer)) = &proposal.action {
+            self.check_transfer_sns_treasury_funds_limits(transfer, None)
+                .await?;
+        }
+
+        Ok(rendering)
     }
 
     /// Makes a new proposal with the given proposer neuron ID and proposal.
@@ -2468,6 +2796,14 @@ impl Governance {
             .nervous_system_parameters()
             .voting_rewards_parameters
             .is_some();
+        // Critical proposals need a higher proportion of yes votes to be adopted.
+        let (
+            minimum_yes_proportion_of_total_basis_points,
+            minimum_yes_proportion_of_exercised_basis_points,
+        ) = match action.minimum_yes_proportions_basis_points() {
+            Some((of_total, of_exercised)) => (Some(of_total), Some(of_exercised)),
+            None => (None, None),
+        };
         // Create the proposal.
         let mut proposal_data = ProposalData {
             action: u64::from(action),
@@ -2481,6 +2817,8 @@ impl Governance {
             is_eligible_for_rewards,
             initial_voting_period_seconds,
             wait_for_quiet_deadline_increase_seconds,
+            minimum_yes_proportion_of_total_basis_points,
+            minimum_yes_proportion_of_exercised_basis_points,
             // Writing these explicitly so that we have to make a consious decision
             // about what to do when adding a new field to `ProposalData`.
             latest_tally: ProposalData::default().latest_tally,
@@ -3579,6 +3917,10 @@ impl Governance {
             self.check_upgrade_status().await;
         }
 
+        if self.transfer_fee_to_sync().is_some() {
+            self.sync_transaction_fee_with_ledger().await;
+        }
+
         self.maybe_gc();
     }
 
@@ -3865,6 +4207,75 @@ impl Governance {
         })
     }
 
+    /// Returns the transfer fee set by the most recently executed
+    /// ManageLedgerParameters proposal that changes the fee, if that proposal
+    /// was executed within the last SYNC_TRANSACTION_FEE_WITH_LEDGER_WINDOW_SECONDS
+    /// and governance does not charge this fee yet.
+    fn transfer_fee_to_sync(&self) -> Option<u64> {
+        let now = self.env.now();
+        let transfer_fee = self
+            .proto
+            .proposals
+            .values()
+            .rev()
+            .filter(|proposal_data| {
+                proposal_data.executed_timestamp_seconds > 0
+                    && now
+                        < proposal_data
+                            .executed_timestamp_seconds
+                            .saturating_add(SYNC_TRANSACTION_FEE_WITH_LEDGER_WINDOW_SECONDS)
+            })
+            .find_map(|proposal_data| {
+                match proposal_data
+                    .proposal
+                    .as_ref()
+                    .and_then(|proposal| proposal.action.as_ref())
+                {
+                    Some(Action::ManageLedgerParameters(ManageLedgerParameters {
+                        transfer_fee: Some(transfer_fee),
+                        ..
+                    })) => Some(*transfer_fee),
+                    _ => None,
+                }
+            })?;
+
+        if transfer_fee == self.transaction_fee_e8s() {
+            return None;
+        }
+        Some(transfer_fee)
+    }
+
+    /// Sets transaction_fee_e8s to the transfer fee that the ledger currently
+    /// charges, as governance pays (and charges) the ledger's transfer fee.
+    async fn sync_transaction_fee_with_ledger(&mut self) {
+        let ledger_canister_id = self.proto.ledger_canister_id_or_panic();
+        let result = self
+            .env
+            .call_canister(ledger_canister_id, "icrc1_fee", Encode!().unwrap())
+            .await
+            .map_err(|err| format!("Canister method call failed: {:?}", err))
+            .and_then(|reply| {
+                Decode!(&reply, candid::Nat)
+                    .map_err(|err| format!("Could not decode the fee: {}", err))
+            })
+            .and_then(|fee| u64::try_from(fee.0).map_err(|err| format!("Invalid fee: {}", err)));
+
+        match result {
+            Ok(fee) => {
+                self.proto
+                    .parameters
+                    .as_mut()
+                    .expect("NervousSystemParameters not present")
+                    .transaction_fee_e8s = Some(fee);
+            }
+            Err(err) => println!(
+                "{}Could not get the transfer fee from the ledger: {}",
+                log_prefix(),
+                err
+            ),
+        }
+    }
+
     /// Checks if there is a pending upgrade.
     fn should_check_upgrade_status(&self) -> bool {
         self.proto.pending_version.is_some()
@@ -4075,9 +4486,10 @@ fn err_if_another_upgrade_is_in_progress(
     id_to_proposal_data: &BTreeMap</* proposal ID */ u64, ProposalData>,
     executing_proposal_id: u64,
 ) -> Result<(), GovernanceError> {
-    let upgrade_action_ids: [u64; 2] = [
+    let upgrade_action_ids: [u64; 3] = [
         (&Action::UpgradeSnsControlledCanister(UpgradeSnsControlledCanister::default())).into(),
         (&Action::UpgradeSnsToNextVersion(UpgradeSnsToNextVersion::default())).into(),
+        (&Action::ManageLedgerParameters(ManageLedgerParameters::default())).into(),
     ];
 
     for (other_proposal_id, proposal_data) in id_to_proposal_data {
@@ -4358,6 +4770,7 @@ mod tests {
                         transfer_funds_arrived: transfer_funds_arrived.clone(),
                         transfer_funds_continue: transfer_funds_continue.clone(),
                     }),
+                    Box::new(DoNothingLedger {}),
                 );
 
                 // Step 2: Execute code under test.
@@ -4814,6 +5227,7 @@ mod tests {
             .unwrap(),
             Box::new(NativeEnvironment::default()),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
         let swap_canister_id = governance.proto.swap_canister_id_or_panic();
 
@@ -4858,6 +5272,7 @@ mod tests {
             .unwrap(),
             Box::new(NativeEnvironment::new(Some(CanisterId::from(1000)))),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         // Step 2: Run code under test.
@@ -4974,6 +5389,7 @@ mod tests {
             .unwrap(),
             Box::new(NativeEnvironment::default()),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         // Step 2: Execute code under test.
@@ -5314,6 +5730,7 @@ mod tests {
             .unwrap(),
             Box::new(env),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         // When we execute the proposal
@@ -5456,6 +5873,7 @@ mod tests {
             .unwrap(),
             Box::new(env),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         assert_eq!(
@@ -5532,6 +5950,7 @@ mod tests {
             .unwrap(),
             Box::new(env),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         assert_eq!(
@@ -5608,6 +6027,7 @@ mod tests {
             .unwrap(),
             Box::new(env),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         assert_eq!(
@@ -5716,6 +6136,7 @@ mod tests {
             .unwrap(),
             Box::new(env),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         // Helper function to assert failures.
@@ -5924,6 +6345,7 @@ mod tests {
             .unwrap(),
             Box::new(env),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         let valid = NervousSystemFunction {
@@ -5962,6 +6384,7 @@ mod tests {
             .unwrap(),
             Box::new(env),
             Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
         );
 
         let list_that_should_fail = vec![
@@ -6029,4 +6452,265 @@ mod tests {
             result
         );
     }
+
+    /// A ledger in which every account has the same, fixed balance.
+    struct FixedBalanceLedger {
+        balance_e8s: u64,
+    }
+
+    #[async_trait]
+    impl Ledger for FixedBalanceLedger {
+        async fn transfer_funds(
+            &self,
+            _amount_e8s: u64,
+            _fee_e8s: u64,
+            _from_subaccount: Option<Subaccount>,
+            _to: Account,
+            _memo: u64,
+        ) -> Result<u64, NervousSystemError> {
+            unimplemented!();
+        }
+
+        async fn total_supply(&self) -> Result<Tokens, NervousSystemError> {
+            unimplemented!()
+        }
+
+        async fn account_balance(&self, _account: Account) -> Result<Tokens, NervousSystemError> {
+            Ok(Tokens::from_e8s(self.balance_e8s))
+        }
+
+        fn canister_id(&self) -> CanisterId {
+            unimplemented!()
+        }
+    }
+
+    fn icp_treasury_transfer(amount_e8s: u64) -> TransferSnsTreasuryFunds {
+        TransferSnsTreasuryFunds {
+            from_treasury: TransferFrom::IcpTreasury as i32,
+            amount_e8s,
+            memo: None,
+            to_principal: Some(PrincipalId::new_user_test_id(1)),
+            to_subaccount: None,
+        }
+    }
+
+    fn executed_icp_treasury_transfer_proposal(
+        id: u64,
+        amount_e8s: u64,
+        executed_timestamp_seconds: u64,
+    ) -> ProposalData {
+        ProposalData {
+            id: Some(id.into()),
+            proposal: Some(Proposal {
+                action: Some(Action::TransferSnsTreasuryFunds(icp_treasury_transfer(
+                    amount_e8s,
+                ))),
+                ..Default::default()
+            }),
+            decided_timestamp_seconds: executed_timestamp_seconds,
+            executed_timestamp_seconds,
+            latest_tally: Some(Tally {
+                yes: 1,
+                no: 0,
+                total: 1,
+                timestamp_seconds: 1,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transfer_sns_treasury_funds_limits() {
+        // Step 1: Prepare the world. The ICP treasury holds 100 ICP. Within the
+        // last week, 60 ICP have been transferred out of it by two proposals.
+        // Another transfer happened before that and is not taken into account.
+        let env = NativeEnvironment::new(Some(CanisterId::from(1000)));
+        let now = env.now;
+        let governance = Governance::new(
+            GovernanceProto {
+                proposals: btreemap! {
+                    1 => executed_icp_treasury_transfer_proposal(
+                        1, 30 * E8, now - 8 * ONE_DAY_SECONDS
+                    ),
+                    2 => executed_icp_treasury_transfer_proposal(
+                        2, 30 * E8, now - 2 * ONE_DAY_SECONDS
+                    ),
+                    3 => executed_icp_treasury_transfer_proposal(
+                        3, 30 * E8, now - ONE_DAY_SECONDS
+                    ),
+                },
+                ..basic_governance_proto()
+            }
+            .try_into()
+            .unwrap(),
+            Box::new(env),
+            Box::new(DoNothingLedger {}),
+            Box::new(FixedBalanceLedger {
+                balance_e8s: 100 * E8,
+            }),
+        );
+
+        // Step 2 & 3: Run code under test and inspect results.
+        let check = |amount_e8s: u64, proposal_id: Option<u64>| {
+            governance.check_transfer_sns_treasury_funds_limits(
+                &icp_treasury_transfer(amount_e8s),
+                proposal_id,
+            )
+        };
+
+        // Together with the 60 ICP transferred within the window, at most 50% of
+        // the 160 ICP held at the beginning of the window can be transferred.
+        let result = check(21 * E8, None).await;
+        assert!(result.is_err(), "{:?}", result);
+        let result = check(20 * E8, None).await;
+        assert!(result.is_ok(), "{:?}", result);
+
+        // A proposal is not counted against itself. Then, the limit of a single
+        // transfer, 25% of the balance, applies.
+        let result = check(25 * E8, Some(3)).await;
+        assert!(result.is_ok(), "{:?}", result);
+        let result = check(26 * E8, Some(3)).await;
+        assert!(result.is_err(), "{:?}", result);
+    }
+
+    #[test]
+    fn test_manage_ledger_parameters_upgrades_ledger_and_syncs_transaction_fee() {
+        // Step 1: Prepare the world.
+        use ProposalDecisionStatus as Status;
+        const NEW_TRANSFER_FEE_E8S: u64 = 20_000;
+
+        let manage_ledger_parameters = ManageLedgerParameters {
+            transfer_fee: Some(NEW_TRANSFER_FEE_E8S),
+            token_name: Some("New Token Name".to_string()),
+            token_symbol: None,
+            token_logo: None,
+        };
+        let action = Action::ManageLedgerParameters(manage_ledger_parameters);
+
+        let proposal_id = 1;
+        let proposal = ProposalData {
+            action: (&action).into(),
+            id: Some(proposal_id.into()),
+            ballots: btreemap! {
+                "neuron 1".to_string() => Ballot {
+                    vote: Vote::Yes as i32,
+                    voting_power: 9001,
+                    cast_timestamp_seconds: 1,
+                },
+            },
+            wait_for_quiet_state: Some(WaitForQuietState::default()),
+            proposal: Some(Proposal {
+                title: "Manage Ledger Parameters Proposal".to_string(),
+                action: Some(action),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(proposal.status(), Status::Open);
+
+        let root_canister_id = canister_test_id(500);
+        let governance_canister_id = canister_test_id(501);
+        let ledger_canister_id = canister_test_id(502);
+        let current_version = SnsVersion {
+            root_wasm_hash: vec![1, 2, 3],
+            governance_wasm_hash: vec![2, 3, 4],
+            ledger_wasm_hash: vec![3, 4, 5],
+            swap_wasm_hash: vec![4, 5, 6],
+            archive_wasm_hash: vec![5, 6, 7],
+            index_wasm_hash: vec![6, 7, 8],
+        };
+        let ledger_wasm = vec![9, 8, 7, 6, 5, 4, 3, 2];
+
+        let mut env = NativeEnvironment::new(Some(governance_canister_id));
+        env.default_canister_call_response =
+            Err((Some(1), "Oh no something was not covered!".to_string()));
+        env.set_call_canister_response(
+            SNS_WASM_CANISTER_ID,
+            "get_wasm",
+            Encode!(&GetWasmRequest {
+                hash: current_version.ledger_wasm_hash.clone()
+            })
+            .unwrap(),
+            Ok(Encode!(&GetWasmResponse {
+                wasm: Some(SnsWasm {
+                    wasm: ledger_wasm.clone(),
+                    canister_type: SnsCanisterType::Ledger.into()
+                })
+            })
+            .unwrap()),
+        );
+        // The ledger must be upgraded, to its current version, with the new parameters.
+        env.require_call_canister_invocation(
+            root_canister_id,
+            "change_canister",
+            Encode!(&ChangeCanisterProposal::new(
+                true,
+                CanisterInstallMode::Upgrade,
+                ledger_canister_id
+            )
+            .with_wasm(ledger_wasm)
+            .with_arg(
+                Encode!(&Some(LedgerUpgradeArgs {
+                    transfer_fee: Some(NEW_TRANSFER_FEE_E8S),
+                    token_name: Some("New Token Name".to_string()),
+                    ..Default::default()
+                }))
+                .unwrap()
+            ))
+            .unwrap(),
+            Some(Ok(Encode!().unwrap())),
+        );
+        let assert_required_calls = env.get_assert_required_calls_fn();
+        // Once the upgrade has taken effect, the ledger charges the new fee.
+        env.set_call_canister_response(
+            ledger_canister_id,
+            "icrc1_fee",
+            Encode!().unwrap(),
+            Ok(Encode!(&candid::Nat::from(NEW_TRANSFER_FEE_E8S)).unwrap()),
+        );
+
+        let mut governance = Governance::new(
+            GovernanceProto {
+                proposals: btreemap! {
+                    proposal_id => proposal
+                },
+                root_canister_id: Some(root_canister_id.get()),
+                ledger_canister_id: Some(ledger_canister_id.get()),
+                deployed_version: Some(current_version.into()),
+                ..basic_governance_proto()
+            }
+            .try_into()
+            .unwrap(),
+            Box::new(env),
+            Box::new(DoNothingLedger {}),
+            Box::new(DoNothingLedger {}),
+        );
+
+        // Step 2: Run code under test.
+        let proposal_data = execute_proposal(&mut governance, proposal_id);
+
+        // Step 3: Inspect results.
+        assert_required_calls();
+        assert_eq!(proposal_data.status(), Status::Executed);
+        // The fee is not changed until the ledger reports it.
+        let original_transaction_fee_e8s = basic_governance_proto()
+            .parameters
+            .unwrap()
+            .transaction_fee_e8s
+            .unwrap();
+        assert_ne!(original_transaction_fee_e8s, NEW_TRANSFER_FEE_E8S);
+        assert_eq!(
+            governance.transaction_fee_e8s(),
+            original_transaction_fee_e8s
+        );
+        assert_eq!(
+            governance.transfer_fee_to_sync(),
+            Some(NEW_TRANSFER_FEE_E8S)
+        );
+
+        governance.run_periodic_tasks().now_or_never();
+
+        assert_eq!(governance.transaction_fee_e8s(), NEW_TRANSFER_FEE_E8S);
+        assert_eq!(governance.transfer_fee_to_sync(), None);
+    }
 }
@@ -3,10 +3,12 @@ use crate::governance::{log_prefix, NERVOUS_SYSTEM_FUNCTION_DELETION_MARKER};
 use crate::pb::v1::governance::{SnsMetadata, Version};
 use crate::pb::v1::nervous_system_function::{FunctionType, GenericNervousSystemFunction};
 use crate::pb::v1::proposal::Action;
+use crate::pb::v1::transfer_sns_treasury_funds::TransferFrom;
 use crate::pb::v1::{
-    proposal, ExecuteGenericNervousSystemFunction, Governance, ManageSnsMetadata, Motion,
-    NervousSystemFunction, NervousSystemParameters, Proposal, ProposalData, ProposalDecisionStatus,
-    ProposalRewardStatus, Tally, UpgradeSnsControlledCanister, UpgradeSnsToNextVersion, Vote,
+    proposal, ExecuteGenericNervousSystemFunction, Governance, ManageLedgerParameters,
+    ManageSnsMetadata, MintSnsTokens, Motion, NervousSystemFunction, NervousSystemParameters,
+    Proposal, ProposalData, ProposalDecisionStatus, ProposalRewardStatus, Tally,
+    TransferSnsTreasuryFunds, UpgradeSnsControlledCanister, UpgradeSnsToNextVersion, Vote,
 };
 use crate::sns_upgrade::{get_upgrade_params, UpgradeSnsParams};
 use crate::types::Environment;
@@ -14,6 +16,7 @@ use crate::{validate_chars_count, validate_len, validate_required_field};
 use dfn_core::api::CanisterId;
 use ic_base_types::PrincipalId;
 use ic_crypto_sha::Sha256;
+use ic_ledger_core::Tokens;
 use std::collections::{BTreeMap, HashSet};
 use std::convert::TryFrom;
 
@@ -37,6 +40,29 @@ pub const PROPOSAL_MOTION_TEXT_BYTES_MAX: usize = 10000;
 /// voting power in favor of the proposal divided by the total available voting power.
 pub const MIN_NUMBER_VOTES_FOR_PROPOSAL_RATIO: f64 = 0.03;
 
+/// The minimum proportion of the total voting power, in basis points, that must vote
+/// yes for a critical proposal (see `Action::minimum_yes_proportions_basis_points`)
+/// to be adopted.
+pub const CRITICAL_PROPOSAL_MINIMUM_YES_PROPORTION_OF_TOTAL_BASIS_POINTS: u64 = 2_000;
+
+/// The proportion of the exercised voting power, in basis points, that yes votes
+/// must exceed for a critical proposal (see `Action::minimum_yes_proportions_basis_points`)
+/// to be adopted.
+pub const CRITICAL_PROPOSAL_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS: u64 = 6_700;
+
+/// The minimum proportion of the total voting power, in basis points, that must vote
+/// yes for a TransferSnsTreasuryFunds proposal to be adopted. This is higher than for
+/// other critical proposals, as the transferred funds leave the SNS for good.
+pub const TRANSFER_SNS_TREASURY_FUNDS_MINIMUM_YES_PROPORTION_OF_TOTAL_BASIS_POINTS: u64 = 3_000;
+
+/// The proportion of the exercised voting power, in basis points, that yes votes
+/// must exceed for a TransferSnsTreasuryFunds proposal to be adopted.
+pub const TRANSFER_SNS_TREASURY_FUNDS_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS: u64 = 6_700;
+
+/// The proportion of the exercised voting power, in basis points, that yes votes
+/// must exceed for a proposal that is not critical to be adopted, i.e. a majority.
+const DEFAULT_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS: u64 = 5_000;
+
 /// The maximum number of proposals returned by one call to the method `list_proposals`,
 /// which can be used to list all proposals in a paginated fashion.
 pub const MAX_LIST_PROPOSAL_RESULTS: u32 = 100;
@@ -194,6 +220,16 @@ pub async fn validate_and_render_action(
         proposal::Action::ManageSnsMetadata(manage_sns_metadata) => {
             validate_and_render_manage_sns_metadata(manage_sns_metadata)
         }
+        proposal::Action::TransferSnsTreasuryFunds(transfer) => {
+            validate_and_render_transfer_sns_treasury_funds(transfer)
+        }
+        proposal::Action::MintSnsTokens(mint) => validate_and_render_mint_sns_tokens(mint),
+        proposal::Action::ManageLedgerParameters(manage_ledger_parameters) => {
+            validate_and_render_manage_ledger_parameters(
+                manage_ledger_parameters,
+                current_parameters,
+            )
+        }
     }
 }
 
@@ -611,6 +647,160 @@ pub fn validate_and_render_manage_sns_metadata(
     }
 }
 
+/// Validates and renders a proposal with action TransferSnsTreasuryFunds.
+///
+/// This only validates the proposal's fields. Whether the amount is within the limits
+/// relative to the treasury's balance is checked by the governance canister, which
+/// can query the ledgers, when the proposal is made and again when it is executed.
+pub fn validate_and_render_transfer_sns_treasury_funds(
+    transfer: &TransferSnsTreasuryFunds,
+) -> Result<String, String> {
+    let mut defects = vec![];
+
+    let (treasury, token) = match TransferFrom::from_i32(transfer.from_treasury) {
+        Some(TransferFrom::IcpTreasury) => ("ICP treasury", "ICP"),
+        Some(TransferFrom::SnsTokenTreasury) => ("SNS token treasury", "SNS tokens"),
+        _ => {
+            defects.push(format!(
+                "from_treasury must be either TRANSFER_FROM_ICP_TREASURY or \
+                 TRANSFER_FROM_SNS_TOKEN_TREASURY, but it is {}.",
+                transfer.from_treasury
+            ));
+            ("", "")
+        }
+    };
+
+    if transfer.amount_e8s == 0 {
+        defects.push("amount_e8s must be greater than 0.".to_string());
+    }
+
+    let to_account = match transfer.to_account() {
+        Ok(to_account) => Some(to_account),
+        Err(err) => {
+            defects.push(format!("Invalid target account: {}", err));
+            None
+        }
+    };
+
+    if !defects.is_empty() {
+        return Err(format!(
+            "TransferSnsTreasuryFunds proposal was invalid for the following reason(s):\n{}",
+            defects.join("\n"),
+        ));
+    }
+
+    let to_account = to_account.expect("The target account must have been validated.");
+    Ok(format!(
+        r"# Proposal to transfer SNS treasury funds:
+## Source treasury: {}
+## Amount (e8s): {} ({} {})
+## Target principal: {}
+## Target subaccount: {}
+## Memo: {}",
+        treasury,
+        transfer.amount_e8s,
+        Tokens::from_e8s(transfer.amount_e8s),
+        token,
+        to_account.owner,
+        to_account
+            .subaccount
+            .map_or_else(|| "None".to_string(), hex::encode),
+        transfer.memo.unwrap_or(0),
+    ))
+}
+
+/// Validates and renders a proposal with action MintSnsTokens.
+pub fn validate_and_render_mint_sns_tokens(mint: &MintSnsTokens) -> Result<String, String> {
+    let mut defects = vec![];
+
+    if mint.amount_e8s == 0 {
+        defects.push("amount_e8s must be greater than 0.".to_string());
+    }
+
+    let to_account = match mint.to_account() {
+        Ok(to_account) => Some(to_account),
+        Err(err) => {
+            defects.push(format!("Invalid target account: {}", err));
+            None
+        }
+    };
+
+    if !defects.is_empty() {
+        return Err(format!(
+            "MintSnsTokens proposal was invalid for the following reason(s):\n{}",
+            defects.join("\n"),
+        ));
+    }
+
+    let to_account = to_account.expect("The target account must have been validated.");
+    Ok(format!(
+        r"# Proposal to mint SNS tokens:
+## Amount (e8s): {} ({} SNS tokens)
+## Target principal: {}
+## Target subaccount: {}
+## Memo: {}",
+        mint.amount_e8s,
+        Tokens::from_e8s(mint.amount_e8s),
+        to_account.owner,
+        to_account
+            .subaccount
+            .map_or_else(|| "None".to_string(), hex::encode),
+        mint.memo.unwrap_or(0),
+    ))
+}
+
+/// Validates and renders a proposal with action ManageLedgerParameters.
+pub fn validate_and_render_manage_ledger_parameters(
+    manage_ledger_parameters: &ManageLedgerParameters,
+    current_parameters: &NervousSystemParameters,
+) -> Result<String, String> {
+    let mut no_change = true;
+    let mut render = "# Proposal to change the ledger parameters:\n".to_string();
+    if let Some(new_transfer_fee) = manage_ledger_parameters.transfer_fee {
+        // Governance charges the ledger's transfer fee, which must stay below
+        // the minimum stake of a neuron (see NervousSystemParameters::validate).
+        let neuron_minimum_stake_e8s = current_parameters
+            .neuron_minimum_stake_e8s
+            .expect("NervousSystemParameters must have neuron_minimum_stake_e8s");
+        if new_transfer_fee >= neuron_minimum_stake_e8s {
+            return Err(format!(
+                "ManageLedgerParameters.transfer_fee ({}) must be less than \
+                 NervousSystemParameters.neuron_minimum_stake_e8s ({})",
+                new_transfer_fee, neuron_minimum_stake_e8s
+            ));
+        }
+        render += &format!(
+            "# New transfer fee (e8s): {} ({} SNS tokens) \n",
+            new_transfer_fee,
+            Tokens::from_e8s(new_transfer_fee)
+        );
+        no_change = false;
+    }
+    if let Some(new_token_name) = &manage_ledger_parameters.token_name {
+        ManageLedgerParameters::validate_token_name(new_token_name)?;
+        render += &format!("# New token name: {} \n", new_token_name);
+        no_change = false;
+    }
+    if let Some(new_token_symbol) = &manage_ledger_parameters.token_symbol {
+        ManageLedgerParameters::validate_token_symbol(new_token_symbol)?;
+        render += &format!("# New token symbol: {} \n", new_token_symbol);
+        no_change = false;
+    }
+    if let Some(new_token_logo) = &manage_ledger_parameters.token_logo {
+        SnsMetadata::validate_logo(new_token_logo)?;
+        render += &format!("# New token logo (base64 encoding): \n {}", new_token_logo);
+        no_change = false;
+    }
+    if no_change {
+        Err(
+            "Error: ManageLedgerParameters must change at least one value, all values are None"
+                .to_string(),
+        )
+    } else {
+        Ok(render)
+    }
+}
+
 impl ProposalData {
     /// Returns the proposal's decision status. See [ProposalDecisionStatus] in the SNS's
     /// proto for more information.
@@ -830,24 +1020,47 @@ impl ProposalData {
     /// either there is a majority of yes-votes or the proposal's deadline has passed.
     pub fn is_accepted(&self) -> bool {
         if let Some(tally) = self.latest_tally.as_ref() {
-            (tally.yes as f64 >= tally.total as f64 * MIN_NUMBER_VOTES_FOR_PROPOSAL_RATIO)
-                && tally.yes > tally.no
+            self.has_enough_yes_votes_of_total(tally.yes, tally.total)
+                && self.has_enough_yes_votes_of_exercised(tally.yes, tally.yes + tally.no)
         } else {
             false
         }
     }
 
+    /// Returns true if `yes` is at least the minimum proportion of the total
+    /// voting power `total` that must vote yes for this proposal to be adopted.
+    fn has_enough_yes_votes_of_total(&self, yes: u64, total: u64) -> bool {
+        match self.minimum_yes_proportion_of_total_basis_points {
+            Some(basis_points) => yes as u128 * 10_000 >= total as u128 * basis_points as u128,
+            None => yes as f64 >= total as f64 * MIN_NUMBER_VOTES_FOR_PROPOSAL_RATIO,
+        }
+    }
+
+    /// Returns true if `yes` exceeds the minimum proportion of the exercised
+    /// voting power `exercised` that must vote yes for this proposal to be adopted.
+    fn has_enough_yes_votes_of_exercised(&self, yes: u64, exercised: u64) -> bool {
+        let basis_points = self
+            .minimum_yes_proportion_of_exercised_basis_points
+            .unwrap_or(DEFAULT_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS);
+        yes as u128 * 10_000 > exercised as u128 * basis_points as u128
+    }
+
     /// Returns true if a decision can be made right now to adopt or reject the proposal.
     /// The proposal must be tallied prior to calling this method.
     pub(crate) fn can_make_decision(&self, now_seconds: u64) -> bool {
         if let Some(tally) = &self.latest_tally {
             // Even when a proposal's deadline has not passed, a proposal is
-            // adopted if strictly more than half of the votes are 'yes' and
-            // rejected if at least half of the votes are 'no'. The conditions
-            // are described as below to avoid overflow. In the absence of overflow,
-            // the below is equivalent to (2 * yes > total) || (2 * no >= total).
-            let majority =
-                (tally.yes > tally.total - tally.yes) || (tally.no >= tally.total - tally.no);
+            // adopted if it has enough 'yes' votes even if all remaining votes
+            // are 'no', and rejected if it does not have enough 'yes' votes even
+            // if all remaining votes are 'yes'. For proposals that need a simple
+            // majority, this means that the proposal is adopted if strictly more
+            // than half of the votes are 'yes' and rejected if at least half of
+            // the votes are 'no'.
+            let max_yes = tally.total - tally.no;
+            let majority = (self.has_enough_yes_votes_of_total(tally.yes, tally.total)
+                && self.has_enough_yes_votes_of_exercised(tally.yes, tally.total))
+                || !(self.has_enough_yes_votes_of_total(max_yes, tally.total)
+                    && self.has_enough_yes_votes_of_exercised(max_yes, tally.total));
             let expired = !self.accepts_vote(now_seconds);
             let decision_reason = match (majority, expired) {
                 (true, true) => Some("majority and expiration"),
@@ -1889,4 +2102,205 @@ Version {
             &functions_map,
         ));
     }
+
+    fn basic_transfer_sns_treasury_funds() -> TransferSnsTreasuryFunds {
+        TransferSnsTreasuryFunds {
+            from_treasury: TransferFrom::IcpTreasury as i32,
+            amount_e8s: 1_000_000,
+            memo: Some(42),
+            to_principal: Some(basic_principal_id()),
+            to_subaccount: None,
+        }
+    }
+
+    #[test]
+    fn validate_and_render_transfer_sns_treasury_funds_succeeds_for_valid_transfer() {
+        let rendering =
+            validate_and_render_transfer_sns_treasury_funds(&basic_transfer_sns_treasury_funds())
+                .unwrap();
+        assert!(rendering.contains("ICP treasury"), "{}", rendering);
+        assert!(rendering.contains("1000000"), "{}", rendering);
+        assert!(rendering.contains("## Memo: 42"), "{}", rendering);
+    }
+
+    #[test]
+    fn validate_and_render_transfer_sns_treasury_funds_rejects_invalid_fields() {
+        let mut transfer = basic_transfer_sns_treasury_funds();
+        transfer.from_treasury = TransferFrom::Unspecified as i32;
+        assert_is_err(validate_and_render_transfer_sns_treasury_funds(&transfer));
+
+        let mut transfer = basic_transfer_sns_treasury_funds();
+        transfer.amount_e8s = 0;
+        assert_is_err(validate_and_render_transfer_sns_treasury_funds(&transfer));
+
+        let mut transfer = basic_transfer_sns_treasury_funds();
+        transfer.to_principal = None;
+        assert_is_err(validate_and_render_transfer_sns_treasury_funds(&transfer));
+    }
+
+    #[test]
+    fn validate_and_render_mint_sns_tokens_rejects_invalid_fields() {
+        let mint = MintSnsTokens {
+            amount_e8s: 1_000_000,
+            memo: None,
+            to_principal: Some(basic_principal_id()),
+            to_subaccount: None,
+        };
+        assert_is_ok(validate_and_render_mint_sns_tokens(&mint));
+
+        assert_is_err(validate_and_render_mint_sns_tokens(&MintSnsTokens {
+            amount_e8s: 0,
+            ..mint.clone()
+        }));
+        assert_is_err(validate_and_render_mint_sns_tokens(&MintSnsTokens {
+            to_principal: None,
+            ..mint
+        }));
+    }
+
+    #[test]
+    fn validate_and_render_manage_ledger_parameters_validates_new_values() {
+        let current_parameters = NervousSystemParameters::with_default_values();
+        let validate = |manage_ledger_parameters: ManageLedgerParameters| {
+            validate_and_render_manage_ledger_parameters(
+                &manage_ledger_parameters,
+                &current_parameters,
+            )
+        };
+
+        let render = validate(ManageLedgerParameters {
+            transfer_fee: Some(20_000),
+            token_name: Some("New Token".to_string()),
+            token_symbol: Some("NTK".to_string()),
+            token_logo: Some("data:image/png;base64,aGVsbG8=".to_string()),
+        })
+        .unwrap();
+        assert!(render.contains("20000"), "{}", render);
+        assert!(render.contains("New Token"), "{}", render);
+
+        // At least one value must be changed.
+        assert_is_err(validate(ManageLedgerParameters::default()));
+        // The transfer fee must be less than the minimum stake of a neuron.
+        assert_is_err(validate(ManageLedgerParameters {
+            transfer_fee: current_parameters.neuron_minimum_stake_e8s,
+            ..Default::default()
+        }));
+        assert_is_err(validate(ManageLedgerParameters {
+            token_symbol: Some("S".repeat(ManageLedgerParameters::MAX_TOKEN_SYMBOL_LENGTH + 1)),
+            ..Default::default()
+        }));
+        // Lengths are counted in characters, not bytes.
+        assert!(validate(ManageLedgerParameters {
+            token_symbol: Some("€".repeat(ManageLedgerParameters::MAX_TOKEN_SYMBOL_LENGTH)),
+            ..Default::default()
+        })
+        .is_ok());
+        assert_is_err(validate(ManageLedgerParameters {
+            token_symbol: Some("€".repeat(ManageLedgerParameters::MAX_TOKEN_SYMBOL_LENGTH + 1)),
+            ..Default::default()
+        }));
+        assert_is_err(validate(ManageLedgerParameters {
+            token_name: Some(" Token".to_string()),
+            ..Default::default()
+        }));
+        // The token may not be renamed to the ICP.
+        assert_is_err(validate(ManageLedgerParameters {
+            token_symbol: Some("icp".to_string()),
+            ..Default::default()
+        }));
+        assert_is_err(validate(ManageLedgerParameters {
+            token_name: Some("Internet Computer".to_string()),
+            ..Default::default()
+        }));
+        assert_is_err(validate(ManageLedgerParameters {
+            token_logo: Some("not a logo".to_string()),
+            ..Default::default()
+        }));
+    }
+
+    fn proposal_with_tally(action: &Action, yes: u64, no: u64, total: u64) -> ProposalData {
+        let minimum_yes_proportions = action.minimum_yes_proportions_basis_points();
+        ProposalData {
+            latest_tally: Some(Tally {
+                timestamp_seconds: 1,
+                yes,
+                no,
+                total,
+            }),
+            minimum_yes_proportion_of_total_basis_points: minimum_yes_proportions
+                .map(|(of_total, _)| of_total),
+            minimum_yes_proportion_of_exercised_basis_points: minimum_yes_proportions
+                .map(|(_, of_exercised)| of_exercised),
+            ..Default::default()
+        }
+    }
+
+    fn critical_proposal_with_tally(yes: u64, no: u64, total: u64) -> ProposalData {
+        proposal_with_tally(&Action::MintSnsTokens(Default::default()), yes, no, total)
+    }
+
+    fn transfer_sns_treasury_funds_proposal_with_tally(
+        yes: u64,
+        no: u64,
+        total: u64,
+    ) -> ProposalData {
+        proposal_with_tally(
+            &Action::TransferSnsTreasuryFunds(Default::default()),
+            yes,
+            no,
+            total,
+        )
+    }
+
+    #[test]
+    fn critical_proposal_requires_higher_thresholds_to_be_accepted() {
+        // 60% of the exercised voting power is not enough for a critical proposal.
+        assert!(!critical_proposal_with_tally(60, 40, 100).is_accepted());
+        // 5% of the total voting power is not enough for a critical proposal.
+        assert!(!critical_proposal_with_tally(5, 0, 100).is_accepted());
+        // 20% of the total and more than 67% of the exercised voting power is.
+        assert!(critical_proposal_with_tally(20, 9, 100).is_accepted());
+
+        // The same tallies are accepted for proposals without the critical thresholds.
+        let mut proposal = critical_proposal_with_tally(60, 40, 100);
+        proposal.minimum_yes_proportion_of_total_basis_points = None;
+        proposal.minimum_yes_proportion_of_exercised_basis_points = None;
+        assert!(proposal.is_accepted());
+    }
+
+    #[test]
+    fn critical_proposal_can_make_decision_before_deadline() {
+        let now_seconds = 1;
+        // A majority of yes votes does not decide a critical proposal early, as the
+        // remaining voting power could still push it below 67% of the exercised votes.
+        let proposal = critical_proposal_with_tally(60, 0, 100);
+        assert!(!proposal.can_make_decision(now_seconds));
+        // 67% yes of the total decides the proposal.
+        let proposal = critical_proposal_with_tally(68, 0, 100);
+        assert!(proposal.can_make_decision(now_seconds));
+        // More than 33% no votes makes it impossible to adopt the proposal.
+        let proposal = critical_proposal_with_tally(0, 34, 100);
+        assert!(proposal.can_make_decision(now_seconds));
+    }
+
+    #[test]
+    fn transfer_sns_treasury_funds_proposal_requires_treasury_thresholds_to_be_accepted() {
+        let proposal = transfer_sns_treasury_funds_proposal_with_tally(25, 10, 100);
+        assert_eq!(
+            proposal.minimum_yes_proportion_of_total_basis_points,
+            Some(TRANSFER_SNS_TREASURY_FUNDS_MINIMUM_YES_PROPORTION_OF_TOTAL_BASIS_POINTS)
+        );
+        assert_eq!(
+            proposal.minimum_yes_proportion_of_exercised_basis_points,
+            Some(TRANSFER_SNS_TREASURY_FUNDS_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS)
+        );
+        // 25% of the total voting power is enough for other critical proposals, but
+        // not for a treasury transfer.
+        assert!(critical_proposal_with_tally(25, 10, 100).is_accepted());
+        assert!(!proposal.is_accepted());
+        // 60% of the exercised voting power is not enough either.
+        assert!(!transfer_sns_treasury_funds_proposal_with_tally(60, 40, 100).is_accepted());
+        // 30% of the total and more than 67% of the exercised voting power is.
+        assert!(transfer_sns_treasury_funds_proposal_with_tally(30, 14, 100).is_accepted());
+    }
 }
@@ -1,4 +1,5 @@
 use crate::{
+    account_from_proto,
     governance::{log_prefix, Governance, TimeWarp, NERVOUS_SYSTEM_FUNCTION_DELETION_MARKER},
     pb::v1::{
         claim_swap_neurons_request::NeuronParameters,
@@ -9,14 +10,22 @@ use crate::{
         nervous_system_function::FunctionType,
         proposal::Action,
         DefaultFollowees, Empty, ExecuteGenericNervousSystemFunction, GovernanceError,
-        ManageNeuronResponse, NervousSystemFunction, NervousSystemParameters, NeuronId,
+        ManageLedgerParameters, ManageNeuronResponse, MintSnsTokens, NervousSystemFunction,
+        NervousSystemParameters, NeuronId, TransferSnsTreasuryFunds,
+    },
+    proposal::{
+        ValidGenericNervousSystemFunction,
+        CRITICAL_PROPOSAL_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS,
+        CRITICAL_PROPOSAL_MINIMUM_YES_PROPORTION_OF_TOTAL_BASIS_POINTS,
+        TRANSFER_SNS_TREASURY_FUNDS_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS,
+        TRANSFER_SNS_TREASURY_FUNDS_MINIMUM_YES_PROPORTION_OF_TOTAL_BASIS_POINTS,
     },
-    proposal::ValidGenericNervousSystemFunction,
 };
 
 use async_trait::async_trait;
 
 use ic_base_types::{CanisterId, PrincipalId};
+use ic_icrc1::Account;
 use ic_ledger_core::{tokens::Tokens, tokens::TOKEN_SUBDIVIDABLE_BY};
 use ic_nervous_system_common::NervousSystemError;
 
@@ -73,6 +82,15 @@ pub mod native_action_ids {
 
     /// ManageSnsMetadata Action.
     pub const MANAGE_SNS_METADATA: u64 = 8;
+
+    /// TransferSnsTreasuryFunds Action.
+    pub const TRANSFER_SNS_TREASURY_FUNDS: u64 = 9;
+
+    /// MintSnsTokens Action.
+    pub const MINT_SNS_TOKENS: u64 = 10;
+
+    /// ManageLedgerParameters Action.
+    pub const MANAGE_LEDGER_PARAMETERS: u64 = 11;
 }
 
 impl governance::Mode {
@@ -189,6 +207,33 @@ impl governance::Mode {
                 ),
             )),
 
+            Action::TransferSnsTreasuryFunds(_) => Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                format!(
+                    "TransferSnsTreasuryFunds proposals are not allowed while \
+                     governance is in PreInitializationSwap mode: {:#?}",
+                    action,
+                ),
+            )),
+
+            Action::MintSnsTokens(_) => Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                format!(
+                    "MintSnsTokens proposals are not allowed while \
+                     governance is in PreInitializationSwap mode: {:#?}",
+                    action,
+                ),
+            )),
+
+            Action::ManageLedgerParameters(_) => Err(GovernanceError::new_with_message(
+                ErrorType::PreconditionFailed,
+                format!(
+                    "ManageLedgerParameters proposals are not allowed while \
+                     governance is in PreInitializationSwap mode: {:#?}",
+                    action,
+                ),
+            )),
+
             _ => Ok(()),
         }
     }
@@ -235,6 +280,101 @@ impl governance::Mode {
     }
 }
 
+impl TransferSnsTreasuryFunds {
+    /// Returns the ledger account that the funds are transferred to, or an
+    /// error if `to_principal` is not set or `to_subaccount` is invalid.
+    pub fn to_account(&self) -> Result<Account, String> {
+        account_from_proto(crate::pb::v1::Account {
+            owner: self.to_principal,
+            subaccount: self.to_subaccount.clone(),
+        })
+    }
+}
+
+impl MintSnsTokens {
+    /// Returns the ledger account that the tokens are minted to, or an
+    /// error if `to_principal` is not set or `to_subaccount` is invalid.
+    pub fn to_account(&self) -> Result<Account, String> {
+        account_from_proto(crate::pb::v1::Account {
+            owner: self.to_principal,
+            subaccount: self.to_subaccount.clone(),
+        })
+    }
+}
+
+impl ManageLedgerParameters {
+    /// The maximum number of characters allowed for a token symbol.
+    pub const MAX_TOKEN_SYMBOL_LENGTH: usize = 10;
+
+    /// The minimum number of characters allowed for a token symbol.
+    pub const MIN_TOKEN_SYMBOL_LENGTH: usize = 3;
+
+    /// The maximum number of characters allowed for a token name.
+    pub const MAX_TOKEN_NAME_LENGTH: usize = 255;
+
+    /// The minimum number of characters allowed for a token name.
+    pub const MIN_TOKEN_NAME_LENGTH: usize = 4;
+
+    /// The key under which the ledger stores the token logo in its metadata.
+    pub const TOKEN_LOGO_METADATA_KEY: &'static str = "icrc1:logo";
+
+    /// Token symbols that cannot be used (compared case-insensitively).
+    pub const BANNED_TOKEN_SYMBOLS: &'static [&'static str] = &["ICP", "DFINITY"];
+
+    /// Token names that cannot be used (compared case-insensitively and
+    /// ignoring whitespace).
+    pub const BANNED_TOKEN_NAMES: &'static [&'static str] =
+        &["internetcomputer", "internetcomputerprotocol"];
+
+    /// Validates a token symbol, both of a new SNS (see `SnsInitPayload`) and
+    /// of a ManageLedgerParameters proposal. Its length is counted in
+    /// characters.
+    pub fn validate_token_symbol(token_symbol: &str) -> Result<(), String> {
+        let length = token_symbol.chars().count();
+        if length > Self::MAX_TOKEN_SYMBOL_LENGTH || length < Self::MIN_TOKEN_SYMBOL_LENGTH {
+            return Err(format!(
+                "Token symbol must be between {} and {} characters, given character count: {}",
+                Self::MIN_TOKEN_SYMBOL_LENGTH,
+                Self::MAX_TOKEN_SYMBOL_LENGTH,
+                length
+            ));
+        }
+        if token_symbol != token_symbol.trim() {
+            return Err("Token symbol must not have leading or trailing whitespaces".to_string());
+        }
+        if Self::BANNED_TOKEN_SYMBOLS.contains(&token_symbol.to_uppercase().as_str()) {
+            return Err("Banned token symbol, please chose another one.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Validates a token name, both of a new SNS (see `SnsInitPayload`) and of
+    /// a ManageLedgerParameters proposal. Its length is counted in characters.
+    pub fn validate_token_name(token_name: &str) -> Result<(), String> {
+        let length = token_name.chars().count();
+        if length > Self::MAX_TOKEN_NAME_LENGTH || length < Self::MIN_TOKEN_NAME_LENGTH {
+            return Err(format!(
+                "Token name must be between {} and {} characters, given character count: {}",
+                Self::MIN_TOKEN_NAME_LENGTH,
+                Self::MAX_TOKEN_NAME_LENGTH,
+                length
+            ));
+        }
+        if token_name != token_name.trim() {
+            return Err("Token name must not have leading or trailing whitespaces".to_string());
+        }
+        let normalized_token_name = token_name
+            .to_lowercase()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>();
+        if Self::BANNED_TOKEN_NAMES.contains(&normalized_token_name.as_str()) {
+            return Err("Banned token name, please chose another one.".to_string());
+        }
+        Ok(())
+    }
+}
+
 impl From<&manage_neuron::Command> for neuron_in_flight_command::Command {
     #[rustfmt::skip]
     fn from(src: &manage_neuron::Command) -> neuron_in_flight_command::Command {
@@ -1124,6 +1264,25 @@ impl Action {
         }
     }
 
+    /// Returns the minimum proportions of the total and of the exercised voting
+    /// power, in basis points, that must vote yes for proposals with such an
+    /// action to be adopted if they are critical, i.e. need a higher proportion
+    /// of yes votes to be adopted than other proposals, and None otherwise.
+    /// See `*_MINIMUM_YES_PROPORTION_OF_*` in src/proposal.rs.
+    pub(crate) fn minimum_yes_proportions_basis_points(&self) -> Option<(u64, u64)> {
+        match self {
+            Action::TransferSnsTreasuryFunds(_) => Some((
+                TRANSFER_SNS_TREASURY_FUNDS_MINIMUM_YES_PROPORTION_OF_TOTAL_BASIS_POINTS,
+                TRANSFER_SNS_TREASURY_FUNDS_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS,
+            )),
+            Action::MintSnsTokens(_) => Some((
+                CRITICAL_PROPOSAL_MINIMUM_YES_PROPORTION_OF_TOTAL_BASIS_POINTS,
+                CRITICAL_PROPOSAL_MINIMUM_YES_PROPORTION_OF_EXERCISED_BASIS_POINTS,
+            )),
+            _ => None,
+        }
+    }
+
     // Returns the native functions, i.e. the ones that are supported directly by the governance canister.
     pub fn native_functions() -> Vec<NervousSystemFunction> {
         vec![
@@ -1179,6 +1338,34 @@ impl Action {
                 ),
                 function_type: Some(FunctionType::NativeNervousSystemFunction(Empty {})),
             },
+            NervousSystemFunction {
+                id: native_action_ids::TRANSFER_SNS_TREASURY_FUNDS,
+                name: "Transfer SNS treasury funds".to_string(),
+                description: Some(
+                    "Proposal to transfer funds from an SNS Governance controlled treasury \
+                     account to a target account."
+                        .to_string(),
+                ),
+                function_type: Some(FunctionType::NativeNervousSystemFunction(Empty {})),
+            },
+            NervousSystemFunction {
+                id: native_action_ids::MINT_SNS_TOKENS,
+                name: "Mint SNS tokens".to_string(),
+                description: Some(
+                    "Proposal to mint new SNS tokens to a target account.".to_string(),
+                ),
+                function_type: Some(FunctionType::NativeNervousSystemFunction(Empty {})),
+            },
+            NervousSystemFunction {
+                id: native_action_ids::MANAGE_LEDGER_PARAMETERS,
+                name: "Manage ledger parameters".to_string(),
+                description: Some(
+                    "Proposal to change the parameters of the SNS ledger, such as its \
+                     transfer fee or the name, symbol and logo of the token."
+                        .to_string(),
+                ),
+                function_type: Some(FunctionType::NativeNervousSystemFunction(Empty {})),
+            },
         ]
     }
 
@@ -1217,6 +1404,9 @@ impl From<&Action> for u64 {
             }
             Action::ExecuteGenericNervousSystemFunction(proposal) => proposal.function_id,
             Action::ManageSnsMetadata(_) => native_action_ids::MANAGE_SNS_METADATA,
+            Action::TransferSnsTreasuryFunds(_) => native_action_ids::TRANSFER_SNS_TREASURY_FUNDS,
+            Action::MintSnsTokens(_) => native_action_ids::MINT_SNS_TOKENS,
+            Action::ManageLedgerParameters(_) => native_action_ids::MANAGE_LEDGER_PARAMETERS,
         }
     }
 }
@@ -1999,6 +2189,9 @@ pub(crate) mod tests {
 
             let disallowed_in_pre_initialization_swap = vec! [
                 Action::ManageNervousSystemParameters(Default::default()),
+                Action::TransferSnsTreasuryFunds     (Default::default()),
+                Action::MintSnsTokens                (Default::default()),
+                Action::ManageLedgerParameters       (Default::default()),
             ];
 
             // Conditionally allow: No targetting SNS canisters.
@@ -739,7 +739,12 @@ impl SNSBuilder {
         let valid_governance = ValidGovernanceProto::try_from(self.governance).unwrap();
         let mut sns = SNS {
             fixture: fixture.clone(),
-            governance: Governance::new(valid_governance, Box::new(fixture), ledger),
+            governance: Governance::new(
+                valid_governance,
+                Box::new(fixture.clone()),
+                ledger,
+                Box::new(fixture),
+            ),
             initial_state: None,
         };
         sns.capture_state();
@@ -6,6 +6,7 @@
     candid::Deserialize,
     serde::Serialize,
     Eq,
+    comparable::Comparable,
     Clone,
     PartialEq,
     ::prost::Message,
@@ -134,6 +135,7 @@ pub mod sns_init_payload {
         candid::Deserialize,
         serde::Serialize,
         Eq,
+        comparable::Comparable,
         Clone,
         PartialEq,
         ::prost::Oneof,
@@ -164,6 +166,7 @@ pub mod sns_init_payload {
     candid::Deserialize,
     serde::Serialize,
     Eq,
+    comparable::Comparable,
     Clone,
     PartialEq,
     ::prost::Message,
@@ -188,6 +191,7 @@ pub struct FractionalDeveloperVotingPower {
     candid::Deserialize,
     serde::Serialize,
     Eq,
+    comparable::Comparable,
     Clone,
     PartialEq,
     ::prost::Message,
@@ -207,6 +211,7 @@ pub struct DeveloperDistribution {
     candid::Deserialize,
     serde::Serialize,
     Eq,
+    comparable::Comparable,
     Clone,
     PartialEq,
     ::prost::Message,
@@ -224,6 +229,7 @@ pub struct TreasuryDistribution {
     candid::Deserialize,
     serde::Serialize,
     Eq,
+    comparable::Comparable,
     Clone,
     PartialEq,
     ::prost::Message,
@@ -246,6 +252,7 @@ pub struct SwapDistribution {
     candid::Deserialize,
     serde::Serialize,
     Eq,
+    comparable::Comparable,
     Clone,
     PartialEq,
     ::prost::Message,
@@ -263,6 +270,7 @@ pub struct AirdropDistribution {
     candid::Deserialize,
     serde::Serialize,
     Eq,
+    comparable::Comparable,
     Clone,
     PartialEq,
     ::prost::Message,
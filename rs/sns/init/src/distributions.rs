@@ -23,7 +23,7 @@ use std::collections::BTreeMap;
 pub const DEFAULT_NEURON_STAKING_NONCE: u64 = 0;
 
 /// The static MEMO used when calculating the SNS Treasury subaccount.
-pub const TREASURY_SUBACCOUNT_NONCE: u64 = 0;
+pub use ic_sns_governance::governance::TREASURY_SUBACCOUNT_NONCE;
 
 /// The static MEMO used when calculating the subaccount of future token swaps.
 pub const SWAP_SUBACCOUNT_NONCE: u64 = 1;
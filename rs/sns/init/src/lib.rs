@@ -19,44 +19,29 @@ use ic_nns_constants::{
 use ic_sns_governance::init::GovernanceCanisterInitPayloadBuilder;
 use ic_sns_governance::pb::v1::governance::{SnsMetadata, Version};
 use ic_sns_governance::pb::v1::{
-    Governance, NervousSystemParameters, Neuron, NeuronPermissionList, NeuronPermissionType,
-    VotingRewardsParameters,
+    Governance, ManageLedgerParameters, NervousSystemParameters, Neuron, NeuronPermissionList,
+    NeuronPermissionType, VotingRewardsParameters,
 };
 use ic_sns_governance::types::DEFAULT_TRANSFER_FEE;
 use ic_sns_root::pb::v1::SnsRootCanister;
 use ic_sns_swap::pb::v1::Init as SwapInit;
-use lazy_static::lazy_static;
-use maplit::{btreemap, hashset};
-use std::collections::{BTreeMap, HashSet};
+use maplit::btreemap;
+use std::collections::BTreeMap;
 
 #[cfg(feature = "test")]
 use std::str::FromStr;
 
 /// The maximum number of characters allowed for token symbol.
-pub const MAX_TOKEN_SYMBOL_LENGTH: usize = 10;
+pub const MAX_TOKEN_SYMBOL_LENGTH: usize = ManageLedgerParameters::MAX_TOKEN_SYMBOL_LENGTH;
 
 /// The minimum number of characters allowed for token symbol.
-pub const MIN_TOKEN_SYMBOL_LENGTH: usize = 3;
+pub const MIN_TOKEN_SYMBOL_LENGTH: usize = ManageLedgerParameters::MIN_TOKEN_SYMBOL_LENGTH;
 
 /// The maximum number of characters allowed for token name.
-pub const MAX_TOKEN_NAME_LENGTH: usize = 255;
+pub const MAX_TOKEN_NAME_LENGTH: usize = ManageLedgerParameters::MAX_TOKEN_NAME_LENGTH;
 
 /// The minimum number of characters allowed for token name.
-pub const MIN_TOKEN_NAME_LENGTH: usize = 4;
-
-// Token Symbols that can not be used.
-lazy_static! {
-    static ref BANNED_TOKEN_SYMBOLS: HashSet<&'static str> = hashset! {
-        "ICP", "DFINITY"
-    };
-}
-
-// Token Names that can not be used.
-lazy_static! {
-    static ref BANNED_TOKEN_NAMES: HashSet<&'static str> = hashset! {
-        "internetcomputer", "internetcomputerprotocol"
-    };
-}
+pub const MIN_TOKEN_NAME_LENGTH: usize = ManageLedgerParameters::MIN_TOKEN_NAME_LENGTH;
 
 /// The canister IDs of all SNS canisters
 #[derive(Debug, Clone)]
@@ -240,6 +225,7 @@ impl SnsInitPayload {
             minting_account,
             initial_balances,
             transfer_fee,
+            fee_collector_account: None,
             token_name,
             token_symbol,
             metadata: vec![],
@@ -265,6 +251,7 @@ impl SnsInitPayload {
     fn index_init_args(&self, sns_canister_ids: &SnsCanisterIds) -> IndexInitArgs {
         IndexInitArgs {
             ledger_id: CanisterId::new(sns_canister_ids.ledger).unwrap(),
+            retrieve_blocks_from_ledger_interval_seconds: None,
         }
     }
 
@@ -444,31 +431,7 @@ impl SnsInitPayload {
             .as_ref()
             .ok_or_else(|| "Error: token-symbol must be specified".to_string())?;
 
-        if token_symbol.len() > MAX_TOKEN_SYMBOL_LENGTH {
-            return Err(format!(
-                "Error: token-symbol must be fewer than {} characters, given character count: {}",
-                MAX_TOKEN_SYMBOL_LENGTH,
-                token_symbol.len()
-            ));
-        }
-
-        if token_symbol.len() < MIN_TOKEN_SYMBOL_LENGTH {
-            return Err(format!(
-                "Error: token-symbol must be greater than {} characters, given character count: {}",
-                MIN_TOKEN_SYMBOL_LENGTH,
-                token_symbol.len()
-            ));
-        }
-
-        if token_symbol != token_symbol.trim() {
-            return Err("Token symbol must not have leading or trailing whitespaces".to_string());
-        }
-
-        if BANNED_TOKEN_SYMBOLS.contains::<str>(&token_symbol.clone().to_uppercase()) {
-            return Err("Banned token symbol, please chose another one.".to_string());
-        }
-
-        Ok(())
+        ManageLedgerParameters::validate_token_symbol(token_symbol)
     }
 
     fn validate_token_name(&self) -> Result<(), String> {
@@ -477,37 +440,7 @@ impl SnsInitPayload {
             .as_ref()
             .ok_or_else(|| "Error: token-name must be specified".to_string())?;
 
-        if token_name.len() > MAX_TOKEN_NAME_LENGTH {
-            return Err(format!(
-                "Error: token-name must be fewer than {} characters, given character count: {}",
-                MAX_TOKEN_NAME_LENGTH,
-                token_name.len()
-            ));
-        }
-
-        if token_name.len() < MIN_TOKEN_NAME_LENGTH {
-            return Err(format!(
-                "Error: token-name must be greater than {} characters, given character count: {}",
-                MIN_TOKEN_NAME_LENGTH,
-                token_name.len()
-            ));
-        }
-
-        if token_name != token_name.trim() {
-            return Err("Token name must not have leading or trailing whitespaces".to_string());
-        }
-
-        if BANNED_TOKEN_NAMES.contains::<str>(
-            &token_name
-                .to_lowercase()
-                .chars()
-                .filter(|c| !c.is_whitespace())
-                .collect::<String>(),
-        ) {
-            return Err("Banned token name, please chose another one.".to_string());
-        }
-
-        Ok(())
+        ManageLedgerParameters::validate_token_name(token_name)
     }
 
     fn validate_token_distribution(&self) -> Result<(), String> {
@@ -883,6 +816,20 @@ mod test {
         assert!(sns_init_payload.validate().is_err());
         sns_init_payload = get_sns_init_payload();
 
+        sns_init_payload.token_symbol = Some("icp".to_string());
+        assert!(sns_init_payload.validate().is_err());
+        sns_init_payload = get_sns_init_payload();
+
+        // Lengths are counted in characters, not bytes, as in
+        // ManageLedgerParameters proposals.
+        sns_init_payload.token_symbol = Some("€".repeat(MAX_TOKEN_SYMBOL_LENGTH));
+        assert!(sns_init_payload.validate().is_ok());
+        sns_init_payload = get_sns_init_payload();
+
+        sns_init_payload.token_symbol = Some("€".repeat(MAX_TOKEN_SYMBOL_LENGTH + 1));
+        assert!(sns_init_payload.validate().is_err());
+        sns_init_payload = get_sns_init_payload();
+
         sns_init_payload.transaction_fee_e8s = None;
         assert!(sns_init_payload.validate().is_err());
         sns_init_payload = get_sns_init_payload();
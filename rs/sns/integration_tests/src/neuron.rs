@@ -967,6 +967,7 @@ async fn zero_total_reward_shares() {
         proto.try_into().unwrap(),
         Box::new(environment),
         Box::new(EmptyLedger {}),
+        Box::new(EmptyLedger {}),
     );
     // Prevent gc.
     governance.latest_gc_timestamp_seconds = now;
@@ -1176,6 +1177,7 @@ async fn couple_of_neurons_who_voted_get_rewards() {
         proto.try_into().unwrap(),
         Box::new(environment),
         Box::new(StubLedger {}),
+        Box::new(StubLedger {}),
     );
     // Prevent gc.
     governance.latest_gc_timestamp_seconds = now;
@@ -137,6 +137,7 @@ impl SnsTestsInitPayloadBuilder {
                 max_transactions_per_response: None,
             },
             transfer_fee: DEFAULT_TRANSFER_FEE.get_e8s(),
+            fee_collector_account: None,
             token_symbol: "TKX".to_string(),
             token_name: "Token Example".to_string(),
             metadata: vec![],
@@ -149,6 +150,7 @@ impl SnsTestsInitPayloadBuilder {
 
         let index = IndexInitArgs {
             ledger_id: CanisterId::from_u64(0),
+            retrieve_blocks_from_ledger_interval_seconds: None,
         };
 
         SnsTestsInitPayloadBuilder {
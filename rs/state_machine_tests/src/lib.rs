@@ -11,7 +11,15 @@ use ic_crypto_internal_threshold_sig_bls12381::types::SecretKeyBytes;
 use ic_crypto_internal_types::sign::threshold_sig::public_key::CspThresholdSigPublicKey;
 use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, LabeledTree::SubTree};
 use ic_cycles_account_manager::CyclesAccountManager;
-pub use ic_error_types::{ErrorCode, UserError};
+use ic_embedders::{
+    wasm_utils::{
+        coverage::{self, CoverageMap},
+        decoding::decode_wasm,
+        validate_and_instrument_for_testing,
+    },
+    WasmtimeEmbedder,
+};
+pub use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_execution_environment::ExecutionServices;
 use ic_ic00_types::{self as ic00, CanisterIdRecord, InstallCodeArgs, Method, Payload};
 pub use ic_ic00_types::{CanisterInstallMode, CanisterSettingsArgs, UpdateSettingsArgs};
@@ -23,7 +31,7 @@ use ic_interfaces::{
 };
 use ic_interfaces_registry::RegistryClient;
 use ic_interfaces_state_manager::{CertificationScope, StateHashError, StateManager, StateReader};
-use ic_logger::ReplicaLogger;
+use ic_logger::{replica_logger::no_op_logger, ReplicaLogger};
 use ic_messaging::MessageRoutingImpl;
 use ic_metrics::MetricsRegistry;
 use ic_protobuf::registry::{
@@ -47,9 +55,10 @@ use ic_registry_routing_table::{
 };
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::page_map::Buffer;
+use ic_replicated_state::replicated_state::ReplicatedStateMessageRouting;
 use ic_replicated_state::{
     canister_state::{NumWasmPages, WASM_PAGE_SIZE_IN_BYTES},
-    Memory, PageMap, ReplicatedState,
+    Global, Memory, PageMap, ReplicatedState,
 };
 use ic_state_manager::StateManagerImpl;
 use ic_test_utilities_metrics::fetch_histogram_stats;
@@ -66,7 +75,8 @@ use ic_types::{
     batch::{Batch, BatchPayload, IngressPayload},
     consensus::certification::Certification,
     messages::{
-        Blob, HttpCallContent, HttpCanisterUpdate, HttpRequestEnvelope, SignedIngress, UserQuery,
+        Blob, CallbackId, HttpCallContent, HttpCanisterUpdate, HttpRequestEnvelope, Payload,
+        RejectContext, Request, RequestOrResponse, Response, SignedIngress, UserQuery,
     },
     time::current_time_and_expiry_time,
     CryptoHashOfPartialState, Height, NodeId, NumberOfNodes, Randomness, RegistryVersion,
@@ -77,7 +87,7 @@ pub use ic_types::{
     time::Time,
     CanisterId, CryptoHashOfState, Cycles, PrincipalId, SubnetId, UserId,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 pub use slog::Level;
 use std::fmt;
 use std::path::Path;
@@ -85,7 +95,11 @@ use std::str::FromStr;
 use std::string::ToString;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
-use std::{collections::BTreeMap, convert::TryFrom};
+use std::{
+    cell::RefCell,
+    collections::{btree_map::Entry, BTreeMap, VecDeque},
+    convert::TryFrom,
+};
 use tempfile::TempDir;
 use tokio::runtime::Runtime;
 
@@ -103,6 +117,24 @@ impl Verifier for FakeVerifier {
 
 const GENESIS: Time = Time::from_nanos_since_unix_epoch(1_620_328_630_000_000_000);
 
+/// Name of the file holding the registry in an exported state directory.
+const EXPORTED_REGISTRY_FILE: &str = "registry.pb";
+/// Name of the file holding the [ExportedMetadata] in an exported state
+/// directory.
+const EXPORTED_METADATA_FILE: &str = "state_machine.cbor";
+/// Name of the directory holding the checkpoint in an exported state
+/// directory. It matches the name the state layout uses for checkpoints, so
+/// that the state manager can load it directly.
+const EXPORTED_CHECKPOINTS_DIR: &str = "checkpoints";
+
+/// Parts of the state machine that are not captured by the checkpoint or the
+/// registry, but must be restored to continue from an exported state.
+#[derive(Serialize, Deserialize)]
+struct ExportedMetadata {
+    nonce: u64,
+    time_nanos: u64,
+}
+
 /// Constructs the initial version of the registry containing a subnet with the
 /// specified SUBNET_ID, with the node with the specified NODE_ID assigned to
 /// it.
@@ -189,10 +221,67 @@ fn into_cbor<R: Serialize>(r: &R) -> Vec<u8> {
     ser.into_inner()
 }
 
+/// The basic blocks of a canister module executed so far.
+struct ModuleCoverage {
+    map: CoverageMap,
+    bitmap: Vec<u64>,
+    /// The positions of the bitmap globals among the exported globals of the
+    /// instrumented module, by bitmap word.
+    global_positions: Vec<Option<usize>>,
+}
+
+impl ModuleCoverage {
+    /// Returns `None` if the module cannot be decoded or instrumented.
+    fn new(binary: Arc<Vec<u8>>) -> Option<Self> {
+        let wasm = decode_wasm(binary).ok()?;
+        let map = CoverageMap::new(&wasm).ok()?;
+        let mut config = ic_config::embedders::Config::default();
+        config.feature_flags.coverage_instrumentation = FlagStatus::Enabled;
+        let embedder = WasmtimeEmbedder::new(config, no_op_logger());
+        let (_, instrumentation_output) =
+            validate_and_instrument_for_testing(&embedder, &wasm).ok()?;
+        let global_positions =
+            coverage::bitmap_global_positions(&instrumentation_output.binary, map.num_globals())
+                .ok()?;
+        Some(Self {
+            bitmap: vec![0; map.num_globals()],
+            map,
+            global_positions,
+        })
+    }
+}
+
+/// Recursively copies the directory `src` to `dst`, preserving file
+/// permissions.
+fn copy_dir_recursively(src: &Path, dst: &Path) {
+    std::fs::create_dir_all(dst)
+        .unwrap_or_else(|e| panic!("failed to create directory {}: {}", dst.display(), e));
+    for entry in std::fs::read_dir(src)
+        .unwrap_or_else(|e| panic!("failed to read directory {}: {}", src.display(), e))
+    {
+        let entry = entry.expect("failed to get directory entry");
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        if src_path.is_dir() {
+            copy_dir_recursively(&src_path, &dst_path);
+        } else {
+            std::fs::copy(&src_path, &dst_path).unwrap_or_else(|e| {
+                panic!(
+                    "failed to copy {} to {}: {}",
+                    src_path.display(),
+                    dst_path.display(),
+                    e
+                )
+            });
+        }
+    }
+}
+
 /// Bundles the configuration of a `StateMachine`.
 pub struct StateMachineConfig {
     subnet_config: SubnetConfig,
     hypervisor_config: HypervisorConfig,
+    fault_injection: bool,
 }
 
 impl StateMachineConfig {
@@ -200,8 +289,54 @@ impl StateMachineConfig {
         Self {
             subnet_config,
             hypervisor_config,
+            fault_injection: false,
         }
     }
+
+    /// Enables fault injection into inter-canister calls, see
+    /// [StateMachine::inject_fault].
+    ///
+    /// Messages between canisters are then only delivered in between rounds,
+    /// so tests might need more ticks to reach the same state.
+    pub fn with_fault_injection(mut self) -> Self {
+        self.fault_injection = true;
+        self
+    }
+}
+
+/// Selects the inter-canister calls that a [Fault] is injected into. Fields
+/// that are `None` match any call.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallMatcher {
+    pub caller: Option<CanisterId>,
+    pub callee: Option<CanisterId>,
+    pub method_name: Option<String>,
+}
+
+impl CallMatcher {
+    fn matches(&self, request: &Request) -> bool {
+        self.caller.map_or(true, |caller| caller == request.sender)
+            && self
+                .callee
+                .map_or(true, |callee| callee == request.receiver)
+            && self
+                .method_name
+                .as_ref()
+                .map_or(true, |method_name| *method_name == request.method_name)
+    }
+}
+
+/// A fault to inject into the inter-canister calls selected by a
+/// [CallMatcher].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The call is not delivered to the callee. Instead, the caller gets a
+    /// reject response with the specified code and message, and the attached
+    /// cycles are refunded.
+    Reject { code: RejectCode, message: String },
+    /// The response to the call is delivered to the caller the specified
+    /// number of rounds later than it would be otherwise.
+    DelayResponse { rounds: u64 },
 }
 
 /// Represents a replicated state machine detached from the network layer that
@@ -222,6 +357,17 @@ pub struct StateMachine {
     checkpoints_enabled: std::cell::Cell<bool>,
     nonce: std::cell::Cell<u64>,
     time: std::cell::Cell<Time>,
+    coverage_enabled: bool,
+    /// Coverage of all canister modules executed so far, by module hash.
+    coverage: RefCell<BTreeMap<[u8; 32], ModuleCoverage>>,
+    fault_injection_enabled: bool,
+    faults: RefCell<Vec<(CallMatcher, Fault)>>,
+    /// Calls whose response must be delayed, by caller and callback, along
+    /// with the number of rounds to delay the response for.
+    delayed_calls: RefCell<BTreeMap<(CanisterId, CallbackId), u64>>,
+    /// Delayed responses along with the number of rounds left until they are
+    /// delivered.
+    delayed_responses: RefCell<Vec<(u64, RequestOrResponse)>>,
 }
 
 impl Default for StateMachine {
@@ -249,6 +395,7 @@ impl StateMachine {
             GENESIS,
             None,
             false,
+            None,
         )
     }
 
@@ -260,17 +407,71 @@ impl StateMachine {
             GENESIS,
             Some(config),
             false,
+            None,
+        )
+    }
+
+    /// Constructs a new environment from a state previously exported with
+    /// [checkpoint_and_export_state_to_dir].
+    ///
+    /// The exported directory is not modified, so the same golden state can
+    /// be used as a starting point by many tests.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `dir` does not contain a valid exported state.
+    pub fn new_from_exported_state<P: AsRef<Path>>(
+        dir: P,
+        config: Option<StateMachineConfig>,
+    ) -> Self {
+        let dir = dir.as_ref();
+        assert!(
+            dir.is_dir(),
+            "exported state at {} must be a directory",
+            dir.display()
+        );
+
+        let metadata_bytes = std::fs::read(dir.join(EXPORTED_METADATA_FILE)).unwrap_or_else(|e| {
+            panic!(
+                "failed to read state machine metadata from {}: {}",
+                dir.display(),
+                e
+            )
+        });
+        let metadata: ExportedMetadata = serde_cbor::from_slice(&metadata_bytes)
+            .expect("failed to decode state machine metadata");
+        let registry_data_provider = Arc::new(ProtoRegistryDataProvider::load_from_file(
+            dir.join(EXPORTED_REGISTRY_FILE),
+        ));
+
+        let state_dir = TempDir::new().expect("failed to create a temporary directory");
+        copy_dir_recursively(
+            &dir.join(EXPORTED_CHECKPOINTS_DIR),
+            &state_dir.path().join(EXPORTED_CHECKPOINTS_DIR),
+        );
+
+        Self::setup_from_dir(
+            state_dir,
+            metadata.nonce,
+            Time::from_nanos_since_unix_epoch(metadata.time_nanos),
+            config,
+            true,
+            Some(registry_data_provider),
         )
     }
 
     /// Constructs and initializes a new state machine that uses the specified
     /// directory for storing states.
+    ///
+    /// If `registry_data_provider` is `None`, a fresh single-node registry is
+    /// created.
     fn setup_from_dir(
         state_dir: TempDir,
         nonce: u64,
         time: Time,
         config: Option<StateMachineConfig>,
         checkpoints_enabled: bool,
+        registry_data_provider: Option<Arc<ProtoRegistryDataProvider>>,
     ) -> Self {
         use slog::Drain;
 
@@ -290,18 +491,31 @@ impl StateMachine {
         let node_id = NodeId::from(PrincipalId::new_node_test_id(1));
         let metrics_registry = MetricsRegistry::new();
         let subnet_type = SubnetType::System;
-        let (subnet_config, mut hypervisor_config) = match config {
-            Some(config) => (config.subnet_config, config.hypervisor_config),
+        let (subnet_config, mut hypervisor_config, fault_injection_enabled) = match config {
+            Some(config) => (
+                config.subnet_config,
+                config.hypervisor_config,
+                config.fault_injection,
+            ),
             None => (
                 SubnetConfigs::default().own_subnet_config(subnet_type),
                 HypervisorConfig::default(),
+                false,
             ),
         };
 
-        let (registry_data_provider, registry_client) =
-            make_single_node_registry(subnet_id, subnet_type, node_id);
+        let (registry_data_provider, registry_client) = match registry_data_provider {
+            Some(data_provider) => {
+                let registry_client =
+                    Arc::new(FakeRegistryClient::new(Arc::clone(&data_provider) as _));
+                registry_client.update_to_latest_version();
+                (data_provider, registry_client)
+            }
+            None => make_single_node_registry(subnet_id, subnet_type, node_id),
+        };
 
         let sm_config = ic_config::state_manager::Config::new(state_dir.path().to_path_buf());
+        let coverage_enabled = hypervisor_config.coverage_instrumentation == FlagStatus::Enabled;
 
         if !(std::env::var("SANDBOX_BINARY").is_ok() && std::env::var("LAUNCHER_BINARY").is_ok()) {
             hypervisor_config.canister_sandboxing_flag = FlagStatus::Disabled;
@@ -337,7 +551,12 @@ impl StateMachine {
         //
         // The API state machine provides is blocking anyway.
         let execution_services = runtime.block_on(async {
-            ExecutionServices::setup_execution(
+            let setup_execution = if fault_injection_enabled {
+                ExecutionServices::setup_execution_without_same_subnet_induction
+            } else {
+                ExecutionServices::setup_execution
+            };
+            setup_execution(
                 replica_logger.clone(),
                 &metrics_registry,
                 subnet_id,
@@ -392,6 +611,12 @@ impl StateMachine {
             checkpoints_enabled: std::cell::Cell::new(checkpoints_enabled),
             nonce: std::cell::Cell::new(nonce),
             time: std::cell::Cell::new(time),
+            coverage_enabled,
+            coverage: RefCell::new(BTreeMap::new()),
+            fault_injection_enabled,
+            faults: RefCell::new(vec![]),
+            delayed_calls: RefCell::new(BTreeMap::new()),
+            delayed_responses: RefCell::new(vec![]),
         }
     }
 
@@ -410,7 +635,7 @@ impl StateMachine {
         // to the same root.
         let (state_dir, nonce, time, checkpoints_enabled) = self.into_components();
 
-        Self::setup_from_dir(state_dir, nonce, time, None, checkpoints_enabled)
+        Self::setup_from_dir(state_dir, nonce, time, None, checkpoints_enabled, None)
     }
 
     /// Same as [restart_node], but the subnet will have the specified `config`
@@ -420,7 +645,14 @@ impl StateMachine {
         // to the same root.
         let (state_dir, nonce, time, checkpoints_enabled) = self.into_components();
 
-        Self::setup_from_dir(state_dir, nonce, time, Some(config), checkpoints_enabled)
+        Self::setup_from_dir(
+            state_dir,
+            nonce,
+            time,
+            Some(config),
+            checkpoints_enabled,
+            None,
+        )
     }
 
     /// If the argument is true, the state machine will create an on-disk
@@ -499,6 +731,206 @@ impl StateMachine {
             .deliver_batch(batch)
             .expect("MR queue overflow");
         self.await_height(batch_number);
+        self.collect_coverage();
+        self.apply_faults();
+    }
+
+    /// Injects the specified fault into all inter-canister calls on this
+    /// subnet that match `matcher` and are made from now on. If several
+    /// injected faults match a call, the one injected first applies.
+    ///
+    /// This works without modifying canister code, so it can be used to test
+    /// how canisters handle rejects that rarely happen in practice (e.g., a
+    /// full callee queue or an out-of-cycles callee). Calls to the management
+    /// canister are not affected.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the state machine was not created with
+    /// [StateMachineConfig::with_fault_injection].
+    pub fn inject_fault(&self, matcher: CallMatcher, fault: Fault) {
+        assert!(
+            self.fault_injection_enabled,
+            "fault injection is not enabled for this state machine"
+        );
+        self.faults.borrow_mut().push((matcher, fault));
+    }
+
+    /// Removes all faults injected with [inject_fault]. Responses that are
+    /// already being delayed are still delivered with the delay.
+    pub fn clear_faults(&self) {
+        self.faults.borrow_mut().clear();
+    }
+
+    /// Applies the injected faults to the messages in the loopback stream,
+    /// which holds all messages between canisters of this subnet in between
+    /// rounds if fault injection is enabled.
+    fn apply_faults(&self) {
+        if !self.fault_injection_enabled {
+            return;
+        }
+        let faults = self.faults.borrow();
+        let mut delayed_calls = self.delayed_calls.borrow_mut();
+        let mut delayed_responses = self.delayed_responses.borrow_mut();
+        for (rounds, _) in delayed_responses.iter_mut() {
+            *rounds = rounds.saturating_sub(1);
+        }
+
+        let has_due_responses = delayed_responses.iter().any(|(rounds, _)| *rounds == 0);
+        let has_loopback_messages = self
+            .state_manager
+            .get_latest_state()
+            .take()
+            .get_stream(&self.subnet_id)
+            .map_or(false, |stream| !stream.messages().is_empty());
+        let may_affect_messages =
+            has_loopback_messages && (!faults.is_empty() || !delayed_calls.is_empty());
+        if !has_due_responses && !may_affect_messages {
+            return;
+        }
+
+        let (height, mut state) = self.state_manager.take_tip();
+        let mut streams = state.take_streams();
+
+        // Remove all messages from the loopback stream. They have not been
+        // inducted yet, so we can safely re-append them below.
+        let mut messages = vec![];
+        if let Some(mut loopback_stream) = streams.get_mut(&self.subnet_id) {
+            messages = loopback_stream
+                .messages()
+                .iter()
+                .map(|(_, msg)| msg.clone())
+                .collect();
+            let end = loopback_stream.messages_end();
+            loopback_stream.discard_messages_before(end, &VecDeque::new());
+            while loopback_stream.signals_end() < end {
+                loopback_stream.increment_signals_end();
+            }
+        }
+
+        let mut forwarded = vec![];
+        for msg in messages {
+            match msg {
+                RequestOrResponse::Request(request) => {
+                    match faults.iter().find(|(matcher, _)| matcher.matches(&request)) {
+                        Some((_, Fault::Reject { code, message })) => {
+                            forwarded.push(RequestOrResponse::Response(Arc::new(Response {
+                                originator: request.sender,
+                                respondent: request.receiver,
+                                originator_reply_callback: request.sender_reply_callback,
+                                refund: request.payment,
+                                response_payload: Payload::Reject(RejectContext::new(
+                                    *code,
+                                    message.clone(),
+                                )),
+                            })));
+                        }
+                        Some((_, Fault::DelayResponse { rounds })) => {
+                            delayed_calls
+                                .insert((request.sender, request.sender_reply_callback), *rounds);
+                            forwarded.push(RequestOrResponse::Request(request));
+                        }
+                        None => forwarded.push(RequestOrResponse::Request(request)),
+                    }
+                }
+                RequestOrResponse::Response(response) => {
+                    match delayed_calls
+                        .remove(&(response.originator, response.originator_reply_callback))
+                    {
+                        Some(rounds) => {
+                            delayed_responses.push((rounds, RequestOrResponse::Response(response)))
+                        }
+                        None => forwarded.push(RequestOrResponse::Response(response)),
+                    }
+                }
+            }
+        }
+
+        let (due, pending): (Vec<_>, Vec<_>) = delayed_responses
+            .drain(..)
+            .partition(|(rounds, _)| *rounds == 0);
+        *delayed_responses = pending;
+        forwarded.extend(due.into_iter().map(|(_, msg)| msg));
+
+        let mut loopback_stream = streams.get_mut_or_insert(self.subnet_id);
+        for msg in forwarded {
+            loopback_stream.push(msg);
+        }
+        state.put_streams(streams);
+        self.state_manager.commit_and_certify(
+            state,
+            height.increment(),
+            CertificationScope::Metadata,
+        );
+    }
+
+    /// Merges the coverage bitmaps of all canisters in the latest state into
+    /// the coverage accumulated so far.
+    fn collect_coverage(&self) {
+        if !self.coverage_enabled {
+            return;
+        }
+        let state = self.state_manager.get_latest_state().take();
+        let mut coverage = self.coverage.borrow_mut();
+        for canister in state.canisters_iter() {
+            let execution_state = match canister.execution_state.as_ref() {
+                Some(execution_state) => execution_state,
+                None => continue,
+            };
+            let binary = &execution_state.wasm_binary.binary;
+            let module_coverage = match coverage.entry(binary.module_hash()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => match ModuleCoverage::new(binary.to_shared_vec()) {
+                    Some(module_coverage) => entry.insert(module_coverage),
+                    None => continue,
+                },
+            };
+            let ModuleCoverage {
+                bitmap,
+                global_positions,
+                ..
+            } = module_coverage;
+            for (word, position) in bitmap.iter_mut().zip(global_positions.iter()) {
+                let global =
+                    position.and_then(|position| execution_state.exported_globals.get(position));
+                if let Some(Global::I64(value)) = global {
+                    *word |= *value as u64;
+                }
+            }
+        }
+    }
+
+    /// Returns an LCOV report of the basic blocks of all canister modules
+    /// executed by this state machine so far.
+    ///
+    /// Coverage is only collected if the state machine was created with the
+    /// `coverage_instrumentation` flag of the hypervisor config enabled. Only
+    /// replicated executions (updates, heartbeats, callbacks, etc.) are
+    /// recorded, queries are not. If a module contains DWARF debug
+    /// information, the report refers to its source files, otherwise to
+    /// `<module hash>.wasm` with one line per Wasm function index.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if coverage instrumentation is disabled.
+    pub fn coverage_report(&self) -> String {
+        assert!(
+            self.coverage_enabled,
+            "coverage instrumentation is not enabled for this state machine"
+        );
+        self.coverage
+            .borrow()
+            .iter()
+            .map(|(module_hash, module_coverage)| {
+                let module_name: String = module_hash
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                module_coverage
+                    .map
+                    .lcov_report(&format!("{}.wasm", module_name), &module_coverage.bitmap)
+            })
+            .collect()
     }
 
     fn await_height(&self, h: Height) {
@@ -712,6 +1144,67 @@ impl StateMachine {
             .commit_and_certify(state, h.increment(), CertificationScope::Full);
     }
 
+    /// Checkpoints the latest replicated state and exports the full state of
+    /// this state machine into the specified directory, which must not exist
+    /// yet.
+    ///
+    /// Creating the checkpoint commits the latest state at a new height, so
+    /// the state machine advances by one height, like after a round without
+    /// messages. Returns the height of the exported checkpoint.
+    ///
+    /// The exported state consists of that checkpoint, the registry and the
+    /// state machine time. Use [new_from_exported_state] to construct a state
+    /// machine from it. This is useful for sharing expensive fixtures (e.g., a
+    /// fully set up NNS) between tests and test runs.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `dir` already exists or the export fails.
+    pub fn checkpoint_and_export_state_to_dir<P: AsRef<Path>>(&self, dir: P) -> Height {
+        let dir = dir.as_ref();
+        assert!(
+            !dir.exists(),
+            "export directory {} already exists",
+            dir.display()
+        );
+
+        // Committing the tip with the full scope forces a checkpoint of the
+        // latest state.
+        let (h, state) = self.state_manager.take_tip();
+        let height = h.increment();
+        self.state_manager
+            .commit_and_certify(state, height, CertificationScope::Full);
+
+        let checkpoint = self
+            .state_manager
+            .state_layout()
+            .checkpoint(height)
+            .expect("failed to obtain the exported checkpoint");
+        let checkpoint_path = checkpoint.raw_path();
+        let checkpoint_name = checkpoint_path
+            .file_name()
+            .expect("checkpoint path must have a file name");
+
+        std::fs::create_dir_all(dir.join(EXPORTED_CHECKPOINTS_DIR))
+            .expect("failed to create export directory");
+        copy_dir_recursively(
+            checkpoint_path,
+            &dir.join(EXPORTED_CHECKPOINTS_DIR).join(checkpoint_name),
+        );
+
+        self.registry_data_provider
+            .write_to_file(dir.join(EXPORTED_REGISTRY_FILE));
+
+        let metadata = ExportedMetadata {
+            nonce: self.nonce.get(),
+            time_nanos: self.time.get().as_nanos_since_unix_epoch(),
+        };
+        std::fs::write(dir.join(EXPORTED_METADATA_FILE), into_cbor(&metadata))
+            .expect("failed to write state machine metadata");
+
+        height
+    }
+
     pub fn install_wasm_in_mode(
         &self,
         canister_id: CanisterId,
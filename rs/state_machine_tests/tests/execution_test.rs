@@ -1,14 +1,15 @@
 use ic_config::{
     execution_environment::Config as HypervisorConfig,
+    flag_status::FlagStatus,
     subnet_config::{CyclesAccountManagerConfig, SubnetConfigs},
 };
 use ic_registry_subnet_type::SubnetType;
 use ic_state_machine_tests::{
-    CanisterSettingsArgs, ErrorCode, PrincipalId, StateMachine, StateMachineConfig, SubnetId,
-    UserError,
+    CallMatcher, CanisterSettingsArgs, ErrorCode, Fault, IngressState, IngressStatus, PrincipalId,
+    RejectCode, StateMachine, StateMachineConfig, SubnetId, UserError,
 };
-use ic_types::{ingress::WasmResult, Cycles, NumBytes};
-use ic_universal_canister::{wasm, UNIVERSAL_CANISTER_WASM};
+use ic_types::{ingress::WasmResult, CanisterId, Cycles, NumBytes};
+use ic_universal_canister::{call_args, wasm, UNIVERSAL_CANISTER_WASM};
 use std::convert::TryInto;
 
 const INITIAL_CYCLES_BALANCE: Cycles = Cycles::new(100_000_000_000_000);
@@ -223,6 +224,67 @@ fn test_canister_delete_restart() {
     );
 }
 
+/// Tests that a state exported to a directory can be used to boot several
+/// independent state machines with the same canister state and time.
+#[test]
+fn test_export_and_import_state() {
+    let env = StateMachine::new();
+
+    let canister_id = env.install_canister_wat(TEST_CANISTER, vec![], None);
+    env.execute_ingress(canister_id, "inc", vec![]).unwrap();
+    env.advance_time(std::time::Duration::from_secs(60));
+
+    let export_dir = tempfile::TempDir::new().unwrap();
+    let state_dir = export_dir.path().join("state");
+    let height = env.checkpoint_and_export_state_to_dir(&state_dir);
+    // Every export checkpoints the state at a new height.
+    assert_eq!(
+        env.checkpoint_and_export_state_to_dir(export_dir.path().join("state_2")),
+        height.increment()
+    );
+
+    for _ in 0..2 {
+        let imported = StateMachine::new_from_exported_state(&state_dir, None);
+        assert_eq!(imported.time(), env.time());
+
+        let val = imported.query(canister_id, "read", vec![]).unwrap().bytes();
+        assert_eq!(to_int(val), 1);
+
+        imported
+            .execute_ingress(canister_id, "inc", vec![])
+            .unwrap();
+        let val = imported.query(canister_id, "read", vec![]).unwrap().bytes();
+        assert_eq!(to_int(val), 2);
+    }
+}
+
+/// Tests that the state machine collects the coverage of executed canister
+/// code if the coverage instrumentation is enabled.
+#[test]
+fn test_coverage_report() {
+    let env = StateMachine::new_with_config(StateMachineConfig::new(
+        SubnetConfigs::default().own_subnet_config(SubnetType::System),
+        HypervisorConfig {
+            coverage_instrumentation: FlagStatus::Enabled,
+            ..HypervisorConfig::default()
+        },
+    ));
+
+    let canister_id = env.install_canister_wat(TEST_CANISTER, vec![], None);
+    env.execute_ingress(canister_id, "inc", vec![]).unwrap();
+    env.execute_ingress(canister_id, "inc", vec![]).unwrap();
+    let val = env.query(canister_id, "read", vec![]).unwrap().bytes();
+    assert_eq!(to_int(val), 2);
+
+    let report = env.coverage_report();
+    assert!(report.starts_with("TN:\nSF:"), "{}", report);
+    assert!(report.ends_with("end_of_record\n"), "{}", report);
+    // "inc" was executed, the other methods were not.
+    assert!(report.contains("FNDA:1,"), "{}", report);
+    assert!(report.contains("FNDA:0,"), "{}", report);
+    assert!(report.contains("FNH:1\n"), "{}", report);
+}
+
 /// The test checks that the canister stable memory is discarded on code
 /// re-install, and that the stable memory stays discarded after a checkpoint
 /// recovery. It's a common bug in execution to reset a page map in memory, but
@@ -785,3 +847,160 @@ fn max_canister_memory_respected_even_when_no_memory_allocation_is_set() {
     );
     assert_replied(res, 0);
 }
+
+fn fault_injection_env() -> StateMachine {
+    StateMachine::new_with_config(
+        StateMachineConfig::new(
+            SubnetConfigs::default().own_subnet_config(SubnetType::System),
+            HypervisorConfig::default(),
+        )
+        .with_fault_injection(),
+    )
+}
+
+/// Returns a universal canister payload that calls `callee`, which replies
+/// with "pong". The caller replies with the reject code if the call fails.
+fn ping_payload(callee: &CanisterId) -> Vec<u8> {
+    wasm()
+        .inter_update(
+            callee.get(),
+            call_args()
+                .other_side(wasm().reply_data(b"pong").build())
+                .on_reject(wasm().reject_code().int_to_blob().append_and_reply()),
+        )
+        .build()
+}
+
+/// Verifies that an injected reject fault is delivered to the caller instead
+/// of the callee's reply and that clearing faults restores normal delivery.
+#[test]
+fn test_fault_injection_rejects_calls() {
+    let env = fault_injection_env();
+    let caller = env
+        .install_canister(UNIVERSAL_CANISTER_WASM.into(), vec![], None)
+        .unwrap();
+    let callee = env
+        .install_canister(UNIVERSAL_CANISTER_WASM.into(), vec![], None)
+        .unwrap();
+
+    assert_eq!(
+        env.execute_ingress(caller, "update", ping_payload(&callee)),
+        Ok(WasmResult::Reply(b"pong".to_vec()))
+    );
+
+    env.inject_fault(
+        CallMatcher {
+            callee: Some(callee),
+            ..Default::default()
+        },
+        Fault::Reject {
+            code: RejectCode::SysTransient,
+            message: "injected fault".to_string(),
+        },
+    );
+    assert_eq!(
+        env.execute_ingress(caller, "update", ping_payload(&callee)),
+        Ok(WasmResult::Reply(
+            (RejectCode::SysTransient as u32).to_le_bytes().to_vec()
+        ))
+    );
+
+    env.clear_faults();
+    assert_eq!(
+        env.execute_ingress(caller, "update", ping_payload(&callee)),
+        Ok(WasmResult::Reply(b"pong".to_vec()))
+    );
+}
+
+/// Verifies that a delayed response is held back for the requested number of
+/// rounds and delivered afterwards.
+#[test]
+fn test_fault_injection_delays_responses() {
+    const DELAY_ROUNDS: u64 = 10;
+
+    let env = fault_injection_env();
+    let caller = env
+        .install_canister(UNIVERSAL_CANISTER_WASM.into(), vec![], None)
+        .unwrap();
+    let callee = env
+        .install_canister(UNIVERSAL_CANISTER_WASM.into(), vec![], None)
+        .unwrap();
+
+    env.inject_fault(
+        CallMatcher {
+            caller: Some(caller),
+            callee: Some(callee),
+            ..Default::default()
+        },
+        Fault::DelayResponse {
+            rounds: DELAY_ROUNDS,
+        },
+    );
+    let msg_id = env.send_ingress(
+        PrincipalId::new_anonymous(),
+        caller,
+        "update",
+        ping_payload(&callee),
+    );
+    for _ in 0..DELAY_ROUNDS {
+        env.tick();
+    }
+    assert!(!matches!(
+        env.ingress_status(&msg_id),
+        IngressStatus::Known {
+            state: IngressState::Completed(_),
+            ..
+        }
+    ));
+
+    assert_eq!(
+        env.await_ingress(msg_id, 10),
+        Ok(WasmResult::Reply(b"pong".to_vec()))
+    );
+}
+
+/// Verifies that a caller whose reject callback traps on an injected
+/// `CanisterError` reject, as it would if the callee trapped, fails the
+/// original message and still runs its cleanup callback.
+#[test]
+fn test_fault_injection_trap_in_reject_callback() {
+    let env = fault_injection_env();
+    let caller = env
+        .install_canister(UNIVERSAL_CANISTER_WASM.into(), vec![], None)
+        .unwrap();
+    let callee = env
+        .install_canister(UNIVERSAL_CANISTER_WASM.into(), vec![], None)
+        .unwrap();
+
+    env.inject_fault(
+        CallMatcher {
+            callee: Some(callee),
+            method_name: Some("update".to_string()),
+            ..Default::default()
+        },
+        Fault::Reject {
+            code: RejectCode::CanisterError,
+            message: "callee trapped".to_string(),
+        },
+    );
+    let payload = wasm()
+        .inter_update(
+            callee.get(),
+            call_args()
+                .other_side(wasm().reply_data(b"pong").build())
+                .on_reject(wasm().trap_with_blob(b"reject callback trapped").build())
+                .on_cleanup(wasm().set_global_data(b"cleaned up").build()),
+        )
+        .build();
+    let err = env.execute_ingress(caller, "update", payload).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterCalledTrap);
+
+    assert_eq!(
+        env.query(
+            caller,
+            "query",
+            wasm().get_global_data().append_and_reply().build()
+        ),
+        Ok(WasmResult::Reply(b"cleaned up".to_vec()))
+    );
+}
@@ -227,6 +227,7 @@ pub(crate) async fn install_ledger(node: &IcNodeSnapshot, logger: &Logger) -> Ca
         minting_account,
         initial_balances: vec![(account1.clone(), 5_000_000_000_000_u64)],
         transfer_fee: 1_000,
+        fee_collector_account: None,
         token_name: "Wrapped Bitcoin".to_string(),
         token_symbol: "ckBTC".to_string(),
         metadata: vec![],
@@ -70,6 +70,7 @@ fn test(handle: IcHandle, ctx: &ic_fondue::pot::Context) {
             minting_account,
             initial_balances: vec![(account1.clone(), 1_000_000_000)],
             transfer_fee: 1_000,
+            fee_collector_account: None,
             token_name: "Example Token".to_string(),
             token_symbol: "XTK".to_string(),
             metadata: vec![],
@@ -1212,6 +1212,8 @@ impl UserHandle {
             block_index: block,
             controller: *controller_id,
             subnet_type,
+            subnet_selection: None,
+            settings: None,
         };
 
         let result: Result<CanisterId, NotifyError> = self
@@ -39,6 +39,7 @@ use ic_nns_governance::pb::v1::{
 };
 
 use crate::nns::NnsExt;
+use candid::Encode;
 use canister_test::Canister;
 use dfn_candid::{candid, candid_one};
 use ic_nns_test_utils::ids::{TEST_NEURON_1_ID, TEST_NEURON_2_ID, TEST_NEURON_3_ID};
@@ -50,6 +51,7 @@ use ic_nervous_system_common_test_keys::{
 };
 use ic_nns_common::types::{NeuronId, ProposalId};
 use ic_nns_constants::GOVERNANCE_CANISTER_ID;
+use ic_nns_governance::nns_function_payload::UpgradeRootProposalPayload;
 use ic_registry_subnet_type::SubnetType;
 use rand::Rng;
 use slog::info;
@@ -572,13 +574,22 @@ async fn submit_proposal(
     neuron: (NeuronId, &Ed25519KeyPair),
     update_type: NnsFunction,
 ) -> ProposalId {
+    // Governance rejects proposals whose payload does not decode, so this has
+    // to be well-formed, even though the module itself is not a valid wasm.
+    assert_eq!(update_type, NnsFunction::NnsRootUpgrade);
+    let payload = Encode!(&UpgradeRootProposalPayload {
+        wasm_module: Vec::new(),
+        module_arg: Vec::new(),
+        stop_upgrade_start: false,
+    })
+    .unwrap();
     let proposal = Proposal {
         title: Some("<proposal created from initialization>".to_string()),
         summary: "".to_string(),
         url: "".to_string(),
         action: Some(proposal::Action::ExecuteNnsFunction(ExecuteNnsFunction {
             nns_function: update_type as i32,
-            payload: Vec::new(),
+            payload,
         })),
     };
 